@@ -0,0 +1,51 @@
+//! Compares the throughput of the memory backends dumping a large buffer of the current process.
+//!
+//! Run with `cargo bench --features io_uring` to include the io_uring backend.
+use std::{hint::black_box, io, time::Instant};
+
+use libinspector::introspection::{access::MemoryReader, mem::ProcMem, vm::ProcessVm};
+
+/// Size of the buffer dumped by each backend.
+const SIZE: usize = 512 << 20;
+
+/// Number of dumps averaged for each backend.
+const ROUNDS: u32 = 5;
+
+fn bench(name: &str, reader: &dyn MemoryReader, address: u64) {
+    // Warms up the page tables and caches
+    reader
+        .copy_to(address, SIZE as u64, &mut io::sink())
+        .unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        reader
+            .copy_to(black_box(address), SIZE as u64, &mut io::sink())
+            .unwrap();
+    }
+    let seconds = start.elapsed().as_secs_f64() / ROUNDS as f64;
+
+    println!(
+        "{name:<16} {:>8.2} ms {:>8.2} GiB/s",
+        seconds * 1000.0,
+        SIZE as f64 / seconds / (1u64 << 30) as f64
+    );
+}
+
+fn main() {
+    let pid = std::process::id();
+    // Faults every page in, so all backends read resident memory
+    let buffer: Vec<u8> = (0..SIZE).map(|i| i as u8).collect();
+    let address = buffer.as_ptr() as u64;
+
+    bench("process_vm_readv", &ProcessVm::new(pid), address);
+    bench("/proc/pid/mem", &ProcMem::open(pid).unwrap(), address);
+    #[cfg(feature = "io_uring")]
+    bench(
+        "io_uring",
+        &libinspector::introspection::uring::UringMem::open(pid).unwrap(),
+        address,
+    );
+
+    black_box(buffer);
+}
@@ -0,0 +1,80 @@
+//! Measures the throughput of the scanner searching a large buffer of the current process.
+use std::{hint::black_box, time::Instant};
+
+use libinspector::introspection::{
+    pattern::Pattern,
+    process::Process,
+    scan::{ScanResults, ScanValue, Scanner},
+    vm::ProcessVm,
+};
+
+/// Size of the buffer scanned.
+const SIZE: usize = 256 << 20;
+
+/// Number of scans averaged for each search.
+const ROUNDS: u32 = 5;
+
+fn bench(name: &str, scan: impl Fn() -> ScanResults) {
+    // Warms up the page tables and caches
+    black_box(scan());
+
+    let start = Instant::now();
+    let mut found = 0;
+    for _ in 0..ROUNDS {
+        found = black_box(scan()).len();
+    }
+    let seconds = start.elapsed().as_secs_f64() / ROUNDS as f64;
+
+    println!(
+        "{name:<24} {:>8.2} ms {:>8.2} GiB/s {found:>8} found",
+        seconds * 1000.0,
+        SIZE as f64 / seconds / (1u64 << 30) as f64
+    );
+}
+
+fn main() {
+    // Pseudo-random bytes, so the searched values are rare as in real memory
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let buffer: Vec<u8> = (0..SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let address = buffer.as_ptr() as u64;
+
+    let process = Process::from_pid(std::process::id()).unwrap();
+    let scanner: Scanner<ProcessVm> = process
+        .scanner()
+        .filter_segments(|segment| segment.contains(address));
+    let unaligned = process
+        .scanner()
+        .filter_segments(|segment| segment.contains(address))
+        .with_alignment(1);
+
+    bench("u8", || scanner.scan(&ScanValue::U8(0x42)).unwrap());
+    bench("u32", || {
+        scanner.scan(&ScanValue::U32(0xdead_beef)).unwrap()
+    });
+    bench("u64", || {
+        scanner
+            .scan(&ScanValue::U64(0x0123_4567_89ab_cdef))
+            .unwrap()
+    });
+    bench("u32 unaligned", || {
+        unaligned.scan(&ScanValue::U32(0xdead_beef)).unwrap()
+    });
+    bench("f64", || scanner.scan(&ScanValue::F64(1.5)).unwrap());
+    bench("bytes", || {
+        scanner
+            .scan(&ScanValue::from(&b"libinspector"[..]))
+            .unwrap()
+    });
+
+    let pattern: Pattern = "48 8B ?? ?? 89 05 ?? ?? ?? ?? C3".parse().unwrap();
+    bench("pattern", || scanner.scan_pattern(&pattern).unwrap());
+
+    black_box(buffer);
+}
@@ -1,2 +1,58 @@
+pub mod access;
+pub mod arch;
+pub mod audit;
+pub mod auxv;
+pub mod batch;
+pub mod breakpoint;
+pub mod cache;
+pub mod capabilities;
+pub mod detour;
+pub mod elf;
+pub mod fallback;
+pub mod fd;
+pub mod fpu;
+pub mod freeze;
+pub mod handle;
+pub mod idle;
+pub mod idmap;
+pub mod imports;
+pub mod kallsyms;
+pub mod layout;
+pub mod limits;
+pub mod linkmap;
+pub mod location;
+pub mod map_files;
+pub mod mem;
+pub mod memory;
+pub mod mountinfo;
+pub mod net;
+pub mod ns;
+pub mod numa;
+pub mod pagemap;
+pub mod patch;
+pub mod pattern;
+pub mod pod;
+pub mod pointer;
 pub mod process;
+pub mod ptrace;
+pub mod remote;
+pub mod resolve;
+pub mod scan;
+pub mod sched;
+pub mod seccomp;
 pub mod segment;
+pub mod session;
+pub mod spill;
+pub mod stack;
+pub mod strace;
+pub mod stream;
+pub mod strings;
+pub mod symbol;
+pub mod syscall;
+pub mod thread;
+#[cfg(feature = "io_uring")]
+pub mod uring;
+pub mod verify;
+pub mod vm;
+pub mod watch;
+pub mod watchpoint;
@@ -0,0 +1,458 @@
+//! This module contains the traits abstracting the access to the memory of a process, implemented
+//! by the different backends.
+use std::{
+    fmt, io,
+    mem::{align_of, MaybeUninit},
+    ops::Range,
+};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    arch::Bitness,
+    pagemap::page_size,
+    pod::{bytes_of, bytes_of_slice, from_bytes_with, uninit_bytes_of_mut, Pod},
+    stream::{HolePolicy, MemoryStream},
+};
+
+/// Size of the chunks read when copying memory to a writer.
+const COPY_CHUNK_SIZE: usize = 1 << 20;
+
+/// Reads the address space of a process.
+pub trait MemoryReader {
+    /// Reads `buffer.len()` bytes at `address`, failing if any of them is unreadable.
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()>;
+
+    /// Reads `buffer.len()` bytes at `address` into a caller-provided buffer that does not need
+    /// to be initialized, returning it initialized.
+    ///
+    /// The default implementation zeroes the buffer before reading into it. Backends that only
+    /// ever write to the buffer override it to skip that pass. The returned slice must be the
+    /// whole `buffer`: callers relying on it being initialized fail otherwise.
+    fn read_uninit<'b>(
+        &self,
+        address: u64,
+        buffer: &'b mut [MaybeUninit<u8>],
+    ) -> anyhow::Result<&'b mut [u8]> {
+        buffer.fill(MaybeUninit::new(0));
+        // SAFETY: every byte was just initialized
+        let buffer = unsafe { assume_init_mut(buffer) };
+        self.read(address, buffer)?;
+        Ok(buffer)
+    }
+
+    /// Reads `len` bytes at `address` into a new buffer.
+    fn read_bytes(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(len);
+        read_initialized(self, address, &mut buffer.spare_capacity_mut()[..len])?;
+        // SAFETY: the first `len` bytes were initialized by the read
+        unsafe { buffer.set_len(len) };
+        Ok(buffer)
+    }
+
+    /// Copies `len` bytes at `address` to `writer` in chunks, without holding them all in memory.
+    fn copy_to(&self, address: u64, len: u64, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        let mut buffer = vec![0; COPY_CHUNK_SIZE.min(len as usize)];
+        let end = address + len;
+        let mut address = address;
+
+        while address < end {
+            let chunk = buffer.len().min((end - address) as usize);
+            self.read(address, &mut buffer[..chunk])?;
+            writer
+                .write_all(&buffer[..chunk])
+                .context("Failed to write memory copy")?;
+            address += chunk as u64;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads into `buffer` with [`MemoryReader::read_uninit`], failing unless the whole buffer was
+/// returned initialized, as an implementation may return a shorter or another slice.
+fn read_initialized<R: MemoryReader + ?Sized>(
+    reader: &R,
+    address: u64,
+    buffer: &mut [MaybeUninit<u8>],
+) -> anyhow::Result<()> {
+    let (start, len) = (buffer.as_ptr() as *const u8, buffer.len());
+    let read = reader.read_uninit(address, buffer)?;
+    if read.as_ptr() != start || read.len() != len {
+        bail!("Read of {len} bytes at {address:#x} did not initialize the buffer");
+    }
+
+    Ok(())
+}
+
+/// Writes the address space of a process.
+pub trait MemoryWriter {
+    /// Writes `data` at `address`, failing if any byte could not be written.
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()>;
+}
+
+/// Writes of naturally aligned words performed as a single store, so a target reading the word
+/// concurrently sees either the old or the new value, never a mix of both. Backends fail on sizes
+/// they cannot store at once rather than emulating them.
+pub trait AtomicWriter {
+    /// Stores `value` at `address`, which must be 4-byte aligned.
+    fn write_atomic_u32(&self, address: u64, value: u32) -> anyhow::Result<()>;
+
+    /// Stores `value` at `address`, which must be 8-byte aligned.
+    fn write_atomic_u64(&self, address: u64, value: u64) -> anyhow::Result<()>;
+}
+
+impl<T: MemoryReader + ?Sized> MemoryReader for &T {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        (**self).read(address, buffer)
+    }
+
+    fn read_uninit<'b>(
+        &self,
+        address: u64,
+        buffer: &'b mut [MaybeUninit<u8>],
+    ) -> anyhow::Result<&'b mut [u8]> {
+        (**self).read_uninit(address, buffer)
+    }
+
+    fn copy_to(&self, address: u64, len: u64, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        (**self).copy_to(address, len, writer)
+    }
+}
+
+impl<T: MemoryWriter + ?Sized> MemoryWriter for &T {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        (**self).write(address, data)
+    }
+}
+
+impl<T: MemoryReader + ?Sized> MemoryReader for Box<T> {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        (**self).read(address, buffer)
+    }
+
+    fn read_uninit<'b>(
+        &self,
+        address: u64,
+        buffer: &'b mut [MaybeUninit<u8>],
+    ) -> anyhow::Result<&'b mut [u8]> {
+        (**self).read_uninit(address, buffer)
+    }
+
+    fn copy_to(&self, address: u64, len: u64, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        (**self).copy_to(address, len, writer)
+    }
+}
+
+impl<T: MemoryWriter + ?Sized> MemoryWriter for Box<T> {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        (**self).write(address, data)
+    }
+}
+
+/// Views an initialized buffer of `MaybeUninit` as bytes.
+///
+/// # Safety
+///
+/// Every byte of `buffer` must be initialized.
+pub(crate) unsafe fn assume_init_mut(buffer: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    // SAFETY: MaybeUninit<u8> has the layout of u8, and the caller guarantees initialization
+    unsafe { &mut *(buffer as *mut [MaybeUninit<u8>] as *mut [u8]) }
+}
+
+/// Fails if `address` is not suitably aligned for a `T`.
+pub(crate) fn check_alignment<T>(address: u64) -> anyhow::Result<()> {
+    if !address.is_multiple_of(align_of::<T>() as u64) {
+        bail!(
+            "Address {address:#x} is not aligned for {} (alignment {})",
+            std::any::type_name::<T>(),
+            align_of::<T>()
+        );
+    }
+
+    Ok(())
+}
+
+/// Bytes obtained by [`MemoryReaderExt::read_partial`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartialRead {
+    /// Number of bytes actually read
+    pub read: usize,
+    /// Unreadable ranges, in address order and merged when adjacent. Their bytes are zeroed in
+    /// the buffer.
+    pub gaps: Vec<Range<u64>>,
+}
+
+impl PartialRead {
+    /// Whether every byte was read.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+
+/// Error of [`MemoryReaderExt::read_exact`], listing the ranges that could not be read. It can
+/// be recovered from the returned error with [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreadableMemory {
+    pub gaps: Vec<Range<u64>>,
+}
+
+impl fmt::Display for UnreadableMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unreadable memory at")?;
+        for gap in &self.gaps {
+            write!(f, " {:#x}-{:#x}", gap.start, gap.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnreadableMemory {}
+
+/// A string read from the memory of a process, as code units of type `C`, without terminator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteString<C> {
+    units: Vec<C>,
+    terminated: bool,
+}
+
+impl<C> RemoteString<C> {
+    pub fn units(&self) -> &[C] {
+        &self.units
+    }
+
+    pub fn into_units(self) -> Vec<C> {
+        self.units
+    }
+
+    /// Whether the terminator was found, rather than reaching the maximum length or an
+    /// unreadable page.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+}
+
+impl RemoteString<u8> {
+    /// Decodes the string as UTF-8, failing on invalid sequences.
+    pub fn decode(&self) -> anyhow::Result<String> {
+        String::from_utf8(self.units.clone()).context("Remote string is not valid UTF-8")
+    }
+
+    /// Decodes the string as UTF-8, replacing invalid sequences.
+    pub fn decode_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.units).into_owned()
+    }
+}
+
+impl RemoteString<u16> {
+    /// Decodes the string as UTF-16, failing on unpaired surrogates.
+    pub fn decode(&self) -> anyhow::Result<String> {
+        String::from_utf16(&self.units).context("Remote string is not valid UTF-16")
+    }
+
+    /// Decodes the string as UTF-16, replacing unpaired surrogates.
+    pub fn decode_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.units)
+    }
+}
+
+/// Typed reads on top of any [`MemoryReader`].
+pub trait MemoryReaderExt: MemoryReader {
+    /// Reads a `T` at `address`, which must be aligned for `T` as it would be in the target.
+    fn read_value<T: Pod>(&self, address: u64) -> anyhow::Result<T> {
+        check_alignment::<T>(address)?;
+        self.read_value_unaligned(address)
+    }
+
+    /// Reads a `T` at `address` without alignment requirement, e.g. in a packed structure.
+    fn read_value_unaligned<T: Pod>(&self, address: u64) -> anyhow::Result<T> {
+        from_bytes_with(|bytes| self.read(address, bytes))
+    }
+
+    /// Reads a pointer of a process of the given bitness at `address`, which must be aligned to
+    /// the pointer size.
+    fn read_pointer(&self, address: u64, bitness: Bitness) -> anyhow::Result<u64> {
+        match bitness {
+            Bitness::Bits32 => self.read_value::<u32>(address).map(u64::from),
+            Bitness::Bits64 => self.read_value::<u64>(address),
+        }
+    }
+
+    /// Reads an array of `count` pointers of a process of the given bitness at `address`.
+    fn read_pointers(
+        &self,
+        address: u64,
+        count: usize,
+        bitness: Bitness,
+    ) -> anyhow::Result<Vec<u64>> {
+        match bitness {
+            Bitness::Bits32 => Ok(self
+                .read_vec::<u32>(address, count)?
+                .into_iter()
+                .map(u64::from)
+                .collect()),
+            Bitness::Bits64 => self.read_vec::<u64>(address, count),
+        }
+    }
+
+    /// Reads an array of `count` values at `address`, which must be aligned for `T`, in a
+    /// single read.
+    fn read_vec<T: Pod>(&self, address: u64, count: usize) -> anyhow::Result<Vec<T>> {
+        check_alignment::<T>(address)?;
+        let mut values = Vec::with_capacity(count);
+        read_initialized(
+            self,
+            address,
+            uninit_bytes_of_mut(&mut values.spare_capacity_mut()[..count]),
+        )?;
+        // SAFETY: the bytes of the first `count` values were initialized, and make valid Pods
+        unsafe { values.set_len(count) };
+        Ok(values)
+    }
+
+    /// Reads `buffer.len()` bytes at `address`, failing with [`UnreadableMemory`] if any of them
+    /// is unreadable.
+    fn read_exact(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        if self.read(address, buffer).is_ok() {
+            return Ok(());
+        }
+
+        let partial = self.read_partial(address, buffer);
+        if partial.is_complete() {
+            // The memory became readable in the meantime
+            return Ok(());
+        }
+        Err(UnreadableMemory { gaps: partial.gaps }.into())
+    }
+
+    /// Reads what can be read of `buffer.len()` bytes at `address`, page by page around the
+    /// unreadable ones, e.g. guard pages or mappings removed concurrently.
+    fn read_partial(&self, address: u64, buffer: &mut [u8]) -> PartialRead {
+        if self.read(address, buffer).is_ok() {
+            return PartialRead {
+                read: buffer.len(),
+                gaps: Vec::new(),
+            };
+        }
+
+        let page_size = page_size();
+        let end = address + buffer.len() as u64;
+        let mut partial = PartialRead::default();
+        let mut current = address;
+
+        while current < end {
+            let next = (current - current % page_size + page_size).min(end);
+            let chunk = &mut buffer[(current - address) as usize..(next - address) as usize];
+
+            if self.read(current, chunk).is_ok() {
+                partial.read += chunk.len();
+            } else {
+                chunk.fill(0);
+                match partial.gaps.last_mut() {
+                    Some(gap) if gap.end == current => gap.end = next,
+                    _ => partial.gaps.push(current..next),
+                }
+            }
+            current = next;
+        }
+
+        partial
+    }
+
+    /// Streams the memory of `range` in chunks, handling unreadable pages according to `policy`.
+    fn read_stream(&self, range: Range<u64>, policy: HolePolicy) -> MemoryStream<'_, Self> {
+        MemoryStream::new(self, range, policy)
+    }
+
+    /// Reads a NUL-terminated string of at most `max_len` bytes at `address`.
+    ///
+    /// The string is read page by page, so one running into an unmapped page is returned
+    /// unterminated rather than failing. Only an unreadable first page is an error.
+    fn read_cstring(&self, address: u64, max_len: usize) -> anyhow::Result<RemoteString<u8>> {
+        let (units, terminated) = read_terminated(self, address, max_len, 1)?;
+        Ok(RemoteString { units, terminated })
+    }
+
+    /// Reads a UTF-16 string of at most `max_len` code units at `address`, terminated by a null
+    /// code unit, in the native byte order. See [`Self::read_cstring`].
+    fn read_utf16_string(&self, address: u64, max_len: usize) -> anyhow::Result<RemoteString<u16>> {
+        let (bytes, terminated) = read_terminated(self, address, max_len, 2)?;
+        let units = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_ne_bytes([unit[0], unit[1]]))
+            .collect();
+        Ok(RemoteString { units, terminated })
+    }
+}
+
+/// Reads units of `unit` bytes at `address` until a null unit, at most `max_len` units or an
+/// unreadable page. Returns the bytes before the terminator and whether it was found.
+fn read_terminated<R: MemoryReader + ?Sized>(
+    reader: &R,
+    address: u64,
+    max_len: usize,
+    unit: usize,
+) -> anyhow::Result<(Vec<u8>, bool)> {
+    let page_size = page_size();
+    let max_bytes = max_len.saturating_mul(unit);
+    let mut bytes = Vec::new();
+    let mut scanned = 0;
+
+    while bytes.len() < max_bytes {
+        let current = address + bytes.len() as u64;
+        let chunk = ((page_size - current % page_size) as usize).min(max_bytes - bytes.len());
+        let start = bytes.len();
+        bytes.resize(start + chunk, 0);
+
+        if let Err(error) = reader.read(current, &mut bytes[start..]) {
+            if start == 0 {
+                return Err(error);
+            }
+            break;
+        }
+
+        while scanned + unit <= bytes.len() {
+            if bytes[scanned..scanned + unit].iter().all(|&byte| byte == 0) {
+                bytes.truncate(scanned);
+                return Ok((bytes, true));
+            }
+            scanned += unit;
+        }
+    }
+
+    // Drops the bytes of an unreadable chunk and of a unit cut in half
+    bytes.truncate(scanned);
+    Ok((bytes, false))
+}
+
+impl<R: MemoryReader + ?Sized> MemoryReaderExt for R {}
+
+/// Typed writes on top of any [`MemoryWriter`].
+pub trait MemoryWriterExt: MemoryWriter {
+    /// Writes `value` at `address`, which must be aligned for `T` as it would be in the target.
+    fn write_value<T: Pod>(&self, address: u64, value: &T) -> anyhow::Result<()> {
+        check_alignment::<T>(address)?;
+        self.write_value_unaligned(address, value)
+    }
+
+    /// Writes `value` at `address` without alignment requirement.
+    fn write_value_unaligned<T: Pod>(&self, address: u64, value: &T) -> anyhow::Result<()> {
+        self.write(address, bytes_of(value))
+    }
+
+    /// Writes the array `values` at `address`, which must be aligned for `T`, in a single write.
+    fn write_slice<T: Pod>(&self, address: u64, values: &[T]) -> anyhow::Result<()> {
+        check_alignment::<T>(address)?;
+        self.write(address, bytes_of_slice(values))
+    }
+}
+
+impl<W: MemoryWriter + ?Sized> MemoryWriterExt for W {}
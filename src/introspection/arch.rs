@@ -0,0 +1,484 @@
+//! This module contains the structs and functions to handle the architecture of a process, which
+//! may differ from the one of the inspector.
+//! Based on https://www.man7.org/linux/man-pages/man5/elf.5.html
+use std::{fs, io, io::Read};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    pod::{bytes_of_slice_mut, Pod},
+    process::Pid,
+};
+
+/// Index of the class byte in `e_ident`.
+const EI_CLASS: usize = 4;
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+
+/// Offset of `e_machine` in the ELF header, the same for both classes.
+const E_MACHINE: usize = 18;
+const EM_386: u16 = 3;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/// Reads the start of the ELF header of the executable of `pid`, up to `e_machine`.
+fn read_elf_header(pid: Pid) -> anyhow::Result<[u8; E_MACHINE + 2]> {
+    let path = format!("/proc/{pid}/exe");
+    let mut header = [0; E_MACHINE + 2];
+    fs::File::open(&path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .with_context(|| format!("Failed to read the ELF header of {path}"))?;
+
+    Ok(header)
+}
+
+/// Word size of a process: a 64-bit kernel also runs 32-bit (compat) processes, whose pointers,
+/// auxiliary vector and registers are 32-bit wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bitness {
+    Bits32,
+    Bits64,
+}
+
+impl Bitness {
+    /// Bitness of the inspector itself.
+    pub const NATIVE: Bitness = if cfg!(target_pointer_width = "64") {
+        Bitness::Bits64
+    } else {
+        Bitness::Bits32
+    };
+
+    /// Reads the bitness of the process `pid` from the ELF class of `/proc/<pid>/exe`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Self::from_elf_ident(&read_elf_header(pid)?)
+    }
+
+    /// Parses the class of an ELF identification, the first bytes of an ELF file.
+    pub fn from_elf_ident(ident: &[u8]) -> anyhow::Result<Self> {
+        if !ident.starts_with(b"\x7fELF") {
+            bail!("Not an ELF file");
+        }
+
+        match ident.get(EI_CLASS) {
+            Some(&ELFCLASS32) => Ok(Bitness::Bits32),
+            Some(&ELFCLASS64) => Ok(Bitness::Bits64),
+            Some(class) => bail!("Invalid ELF class {class}"),
+            None => bail!("Truncated ELF identification"),
+        }
+    }
+
+    /// Size of a pointer, in bytes.
+    pub fn pointer_size(self) -> usize {
+        match self {
+            Bitness::Bits32 => 4,
+            Bitness::Bits64 => 8,
+        }
+    }
+
+    /// Highest address representable in the address space.
+    pub fn max_address(self) -> u64 {
+        match self {
+            Bitness::Bits32 => u32::MAX as u64,
+            Bitness::Bits64 => u64::MAX,
+        }
+    }
+
+    /// Decodes a native-endian pointer of [`Self::pointer_size`] bytes.
+    pub fn pointer_from_bytes(self, bytes: &[u8]) -> anyhow::Result<u64> {
+        match (self, bytes.len()) {
+            (Bitness::Bits32, 4) => Ok(u32::from_ne_bytes(bytes.try_into().unwrap()) as u64),
+            (Bitness::Bits64, 8) => Ok(u64::from_ne_bytes(bytes.try_into().unwrap())),
+            (_, len) => bail!("Invalid pointer size {len} for {self:?}"),
+        }
+    }
+
+    /// Formats `address` zero-padded to the width of a pointer, e.g. `0x0804a000` or
+    /// `0x00007f3a1c000000`.
+    pub fn format_address(self, address: u64) -> String {
+        format!("{address:#0width$x}", width = 2 + 2 * self.pointer_size())
+    }
+}
+
+/// General purpose registers of a 32-bit x86 process, as returned by `PTRACE_GETREGSET` with
+/// `NT_PRSTATUS` for a compat process (the i386 `struct user_regs_struct`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Regs32 {
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub ebp: u32,
+    pub eax: u32,
+    pub xds: u32,
+    pub xes: u32,
+    pub xfs: u32,
+    pub xgs: u32,
+    pub orig_eax: u32,
+    pub eip: u32,
+    pub xcs: u32,
+    pub eflags: u32,
+    pub esp: u32,
+    pub xss: u32,
+}
+
+// SAFETY: repr(C) with only u32 fields, so no padding
+unsafe impl Pod for Regs32 {}
+
+/// `PTRACE_GETREGS` from a 64-bit tracer returns the registers of a compat process in the 64-bit
+/// layout, with the 32-bit registers zero-extended.
+#[cfg(target_arch = "x86_64")]
+impl From<&libc::user_regs_struct> for Regs32 {
+    fn from(regs: &libc::user_regs_struct) -> Self {
+        Regs32 {
+            ebx: regs.rbx as u32,
+            ecx: regs.rcx as u32,
+            edx: regs.rdx as u32,
+            esi: regs.rsi as u32,
+            edi: regs.rdi as u32,
+            ebp: regs.rbp as u32,
+            eax: regs.rax as u32,
+            xds: regs.ds as u32,
+            xes: regs.es as u32,
+            xfs: regs.fs as u32,
+            xgs: regs.gs as u32,
+            orig_eax: regs.orig_rax as u32,
+            eip: regs.rip as u32,
+            xcs: regs.cs as u32,
+            eflags: regs.eflags as u32,
+            esp: regs.rsp as u32,
+            xss: regs.ss as u32,
+        }
+    }
+}
+
+/// Instruction set of a process, selecting its registers, breakpoint instruction and system call
+/// convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arch {
+    /// 32-bit x86, natively or as a compat process on x86_64
+    X86,
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    /// Architecture of the inspector itself.
+    #[cfg(target_arch = "x86_64")]
+    pub const NATIVE: Arch = Arch::X86_64;
+    #[cfg(target_arch = "x86")]
+    pub const NATIVE: Arch = Arch::X86;
+    #[cfg(target_arch = "aarch64")]
+    pub const NATIVE: Arch = Arch::Aarch64;
+
+    /// Reads the architecture of the process `pid` from the ELF header of `/proc/<pid>/exe`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Self::from_elf_header(&read_elf_header(pid)?)
+    }
+
+    /// Parses the machine of an ELF header, of which at least the first 20 bytes are needed.
+    pub fn from_elf_header(header: &[u8]) -> anyhow::Result<Self> {
+        Bitness::from_elf_ident(header)?;
+        let Some(machine) = header.get(E_MACHINE..E_MACHINE + 2) else {
+            bail!("Truncated ELF header");
+        };
+
+        // All the supported architectures are little-endian
+        Self::from_elf_machine(u16::from_le_bytes([machine[0], machine[1]]))
+    }
+
+    /// Converts the `e_machine` field of an ELF header.
+    pub fn from_elf_machine(machine: u16) -> anyhow::Result<Self> {
+        match machine {
+            EM_386 => Ok(Arch::X86),
+            EM_X86_64 => Ok(Arch::X86_64),
+            EM_AARCH64 => Ok(Arch::Aarch64),
+            machine => bail!("Unsupported ELF machine {machine}"),
+        }
+    }
+
+    pub fn bitness(self) -> Bitness {
+        match self {
+            Arch::X86 => Bitness::Bits32,
+            Arch::X86_64 | Arch::Aarch64 => Bitness::Bits64,
+        }
+    }
+
+    /// Instruction trapping into the tracer: `int3` on x86, `brk #0` on aarch64.
+    pub fn breakpoint(self) -> &'static [u8] {
+        match self {
+            Arch::X86 | Arch::X86_64 => &[0xcc],
+            Arch::Aarch64 => &[0x00, 0x00, 0x20, 0xd4],
+        }
+    }
+
+    /// Number of bytes the program counter has moved past a breakpoint when it is reported, to
+    /// rewind before resuming: `int3` traps after executing, `brk` before.
+    pub fn breakpoint_pc_offset(self) -> u64 {
+        match self {
+            Arch::X86 | Arch::X86_64 => 1,
+            Arch::Aarch64 => 0,
+        }
+    }
+
+    /// Instruction entering a system call: `int 0x80`, `syscall` or `svc #0`.
+    pub fn syscall_instruction(self) -> &'static [u8] {
+        match self {
+            Arch::X86 => &[0xcd, 0x80],
+            Arch::X86_64 => &[0x0f, 0x05],
+            Arch::Aarch64 => &[0x01, 0x00, 0x00, 0xd4],
+        }
+    }
+
+    /// Alignment required for instruction addresses.
+    pub fn instruction_alignment(self) -> u64 {
+        match self {
+            Arch::X86 | Arch::X86_64 => 1,
+            Arch::Aarch64 => 4,
+        }
+    }
+
+    /// Size of the general purpose registers set, as exchanged with `PTRACE_GETREGSET`.
+    fn registers_size(self) -> usize {
+        match self {
+            Arch::X86 => size_of::<Regs32>(),
+            Arch::X86_64 => size_of::<X86_64Regs>(),
+            Arch::Aarch64 => size_of::<Aarch64Regs>(),
+        }
+    }
+}
+
+/// General purpose registers of an x86_64 process (`struct user_regs_struct`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct X86_64Regs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+// SAFETY: repr(C) with only u64 fields, so no padding
+unsafe impl Pod for X86_64Regs {}
+
+/// General purpose registers of an aarch64 process (`struct user_pt_regs`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Aarch64Regs {
+    /// x0 to x30, x30 being the link register
+    pub regs: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64,
+}
+
+// SAFETY: repr(C) with only u64 fields, so no padding
+unsafe impl Pod for Aarch64Regs {}
+
+/// General purpose registers of a stopped thread, in the layout of its architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registers {
+    X86(Regs32),
+    X86_64(X86_64Regs),
+    Aarch64(Aarch64Regs),
+}
+
+impl Registers {
+    pub fn arch(&self) -> Arch {
+        match self {
+            Registers::X86(_) => Arch::X86,
+            Registers::X86_64(_) => Arch::X86_64,
+            Registers::Aarch64(_) => Arch::Aarch64,
+        }
+    }
+
+    /// Program counter.
+    pub fn pc(&self) -> u64 {
+        match self {
+            Registers::X86(regs) => regs.eip as u64,
+            Registers::X86_64(regs) => regs.rip,
+            Registers::Aarch64(regs) => regs.pc,
+        }
+    }
+
+    pub fn set_pc(&mut self, pc: u64) {
+        match self {
+            Registers::X86(regs) => regs.eip = pc as u32,
+            Registers::X86_64(regs) => regs.rip = pc,
+            Registers::Aarch64(regs) => regs.pc = pc,
+        }
+    }
+
+    /// Stack pointer.
+    pub fn sp(&self) -> u64 {
+        match self {
+            Registers::X86(regs) => regs.esp as u64,
+            Registers::X86_64(regs) => regs.rsp,
+            Registers::Aarch64(regs) => regs.sp,
+        }
+    }
+
+    pub fn set_sp(&mut self, sp: u64) {
+        match self {
+            Registers::X86(regs) => regs.esp = sp as u32,
+            Registers::X86_64(regs) => regs.rsp = sp,
+            Registers::Aarch64(regs) => regs.sp = sp,
+        }
+    }
+
+    /// Number of the system call the thread is stopped in.
+    pub fn syscall_number(&self) -> u64 {
+        match self {
+            Registers::X86(regs) => regs.orig_eax as u64,
+            Registers::X86_64(regs) => regs.orig_rax,
+            Registers::Aarch64(regs) => regs.regs[8],
+        }
+    }
+
+    /// Arguments of the system call the thread is stopped in, in the convention of its
+    /// architecture.
+    pub fn syscall_args(&self) -> [u64; 6] {
+        match self {
+            Registers::X86(regs) => {
+                [regs.ebx, regs.ecx, regs.edx, regs.esi, regs.edi, regs.ebp].map(u64::from)
+            }
+            Registers::X86_64(regs) => [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+            Registers::Aarch64(regs) => regs.regs[..6].try_into().unwrap(),
+        }
+    }
+
+    /// Sets up the registers to execute system call `number` with `args` at the next system
+    /// call instruction.
+    pub fn set_syscall(&mut self, number: u64, args: [u64; 6]) {
+        match self {
+            Registers::X86(regs) => {
+                let args = args.map(|arg| arg as u32);
+                regs.eax = number as u32;
+                [regs.ebx, regs.ecx, regs.edx, regs.esi, regs.edi, regs.ebp] = args;
+            }
+            Registers::X86_64(regs) => {
+                regs.rax = number;
+                [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9] = args;
+            }
+            Registers::Aarch64(regs) => {
+                regs.regs[8] = number;
+                regs.regs[..6].copy_from_slice(&args);
+            }
+        }
+    }
+
+    /// Return value of a function or system call, sign-extended for 32-bit processes so errors
+    /// read as negative.
+    pub fn return_value(&self) -> u64 {
+        match self {
+            Registers::X86(regs) => regs.eax as i32 as i64 as u64,
+            Registers::X86_64(regs) => regs.rax,
+            Registers::Aarch64(regs) => regs.regs[0],
+        }
+    }
+
+    pub fn set_return_value(&mut self, value: u64) {
+        match self {
+            Registers::X86(regs) => regs.eax = value as u32,
+            Registers::X86_64(regs) => regs.rax = value,
+            Registers::Aarch64(regs) => regs.regs[0] = value,
+        }
+    }
+
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        match self {
+            Registers::X86(regs) => bytes_of_slice_mut(std::slice::from_mut(regs)),
+            Registers::X86_64(regs) => bytes_of_slice_mut(std::slice::from_mut(regs)),
+            Registers::Aarch64(regs) => bytes_of_slice_mut(std::slice::from_mut(regs)),
+        }
+    }
+
+    /// Reads the general purpose registers of `tid`, a thread traced and stopped by the calling
+    /// thread, with `PTRACE_GETREGSET`.
+    pub fn get(tid: Pid, arch: Arch) -> anyhow::Result<Self> {
+        let mut registers = match arch {
+            Arch::X86 => Registers::X86(Regs32::default()),
+            Arch::X86_64 => Registers::X86_64(X86_64Regs::default()),
+            Arch::Aarch64 => Registers::Aarch64(Aarch64Regs::default()),
+        };
+        let bytes = registers.as_bytes_mut();
+        let mut iovec = libc::iovec {
+            iov_base: bytes.as_mut_ptr().cast(),
+            iov_len: bytes.len(),
+        };
+
+        // SAFETY: the iovec points to a buffer of the size of the registers
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GETREGSET,
+                tid as libc::pid_t,
+                libc::NT_PRSTATUS,
+                &mut iovec as *mut libc::iovec,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to read the registers of {tid}"));
+        }
+        // The kernel returns the registers in the layout of the tracee
+        if iovec.iov_len != arch.registers_size() {
+            bail!(
+                "Registers of {tid} are {} bytes, not those of {arch:?}",
+                iovec.iov_len
+            );
+        }
+
+        Ok(registers)
+    }
+
+    /// Writes the general purpose registers of `tid`, a thread traced and stopped by the calling
+    /// thread, with `PTRACE_SETREGSET`.
+    pub fn set(&self, tid: Pid) -> anyhow::Result<()> {
+        let mut registers = *self;
+        let bytes = registers.as_bytes_mut();
+        let mut iovec = libc::iovec {
+            iov_base: bytes.as_mut_ptr().cast(),
+            iov_len: bytes.len(),
+        };
+
+        // SAFETY: the iovec points to a buffer of the size of the registers
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_SETREGSET,
+                tid as libc::pid_t,
+                libc::NT_PRSTATUS,
+                &mut iovec as *mut libc::iovec,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to write the registers of {tid}"));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! This module contains the crate-wide policy applied to writes to the memory of processes, to
+//! review them or rehearse a tool without modifying its targets.
+use std::{
+    backtrace::Backtrace,
+    cell::Cell,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use crate::introspection::{access::MemoryReader, process::Pid};
+
+/// Policy in effect, `None` when writes are applied without being logged.
+static WRITE_POLICY: RwLock<Option<Arc<WritePolicy>>> = RwLock::new(None);
+
+thread_local! {
+    /// Whether the writes of the backends are audited by the writer calling them instead, see
+    /// [`DeferredAudit`].
+    static DEFERRED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A write to the memory of a process, as seen by the [`WritePolicy`] before it is performed.
+#[derive(Debug)]
+pub struct WriteRecord {
+    pub pid: Pid,
+    pub address: u64,
+    /// Bytes before the write, if they could be read
+    pub old: Option<Vec<u8>>,
+    pub new: Vec<u8>,
+    /// Name of the backend performing the write
+    pub backend: &'static str,
+    /// Whether the write was suppressed by a dry run
+    pub suppressed: bool,
+    /// Call stack of the write, if enabled with [`WritePolicy::with_backtraces`]
+    pub backtrace: Option<Backtrace>,
+}
+
+type Logger = Arc<dyn Fn(&WriteRecord) + Send + Sync>;
+
+/// What happens to every write of every backend of the crate, once installed.
+#[derive(Clone, Default)]
+pub struct WritePolicy {
+    dry_run: bool,
+    backtraces: bool,
+    pids: Option<Vec<Pid>>,
+    logger: Option<Logger>,
+}
+
+impl fmt::Debug for WritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WritePolicy")
+            .field("dry_run", &self.dry_run)
+            .field("backtraces", &self.backtraces)
+            .field("pids", &self.pids)
+            .field("logger", &self.logger.is_some())
+            .finish()
+    }
+}
+
+impl WritePolicy {
+    /// A policy applying writes without logging them, as when none is installed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Suppresses the writes: they are logged and reported as successful, but not performed.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Captures the call stack of each write in its record, which is slow.
+    pub fn with_backtraces(mut self, backtraces: bool) -> Self {
+        self.backtraces = backtraces;
+        self
+    }
+
+    /// Restricts the policy to the writes to `pid`, others are applied without being logged.
+    /// Can be called several times to add processes.
+    pub fn for_pid(mut self, pid: Pid) -> Self {
+        self.pids.get_or_insert_with(Vec::new).push(pid);
+        self
+    }
+
+    /// Calls `logger` with every write, before it is performed or suppressed. The writes of a
+    /// [`crate::introspection::fallback::FallbackWriter`] are logged once, after they are
+    /// performed, with the backend that succeeded.
+    pub fn with_logger(mut self, logger: impl Fn(&WriteRecord) + Send + Sync + 'static) -> Self {
+        self.logger = Some(Arc::new(logger));
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Makes this policy the one of all the writes of the crate, replacing the previous one.
+    pub fn install(self) {
+        *WRITE_POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(self));
+    }
+
+    /// Removes the installed policy: writes are applied without being logged.
+    pub fn uninstall() {
+        *WRITE_POLICY.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Returns the installed policy, if any, as it is when called.
+    pub fn installed() -> Option<Arc<WritePolicy>> {
+        WRITE_POLICY
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Returns the installed policy if it applies to the writes to `pid`.
+    fn applying_to(pid: Pid) -> Option<Arc<WritePolicy>> {
+        Self::installed()
+            .filter(|policy| policy.pids.as_ref().is_none_or(|pids| pids.contains(&pid)))
+    }
+}
+
+/// Submits a write of `data` at `address` in `pid` to the installed policy, reading the old
+/// bytes with `reader`. Returns whether the backend should perform it.
+pub(crate) fn audit_write(
+    pid: Pid,
+    address: u64,
+    data: &[u8],
+    backend: &'static str,
+    reader: &dyn MemoryReader,
+) -> bool {
+    if DEFERRED.get() {
+        return true;
+    }
+    match DeferredAudit::begin(pid, address, data, reader) {
+        Some(audit) => {
+            let suppressed = audit.is_suppressed();
+            audit.finish(backend);
+            !suppressed
+        }
+        None => true,
+    }
+}
+
+/// A write submitted to the installed policy by a writer trying several backends, logged once
+/// with the backend that performed it.
+pub(crate) struct DeferredAudit {
+    policy: Arc<WritePolicy>,
+    record: WriteRecord,
+}
+
+impl DeferredAudit {
+    /// Submits a write of `data` at `address` in `pid`, reading the old bytes with `reader`.
+    /// `None` if no policy applies to it.
+    pub(crate) fn begin(
+        pid: Pid,
+        address: u64,
+        data: &[u8],
+        reader: &dyn MemoryReader,
+    ) -> Option<Self> {
+        let policy = WritePolicy::applying_to(pid)?;
+        let record = WriteRecord {
+            pid,
+            address,
+            old: policy
+                .logger
+                .as_ref()
+                .and_then(|_| reader.read_bytes(address, data.len()).ok()),
+            new: data.to_vec(),
+            backend: "",
+            suppressed: policy.dry_run,
+            backtrace: (policy.logger.is_some() && policy.backtraces)
+                .then(Backtrace::force_capture),
+        };
+
+        Some(DeferredAudit { policy, record })
+    }
+
+    /// Whether the write must not be performed.
+    pub(crate) fn is_suppressed(&self) -> bool {
+        self.record.suppressed
+    }
+
+    /// Runs `write`, the backends it calls not auditing their writes.
+    pub(crate) fn run<T>(&self, write: impl FnOnce() -> T) -> T {
+        /// Restores the previous state, even if `write` panics.
+        struct Restore(bool);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                DEFERRED.set(self.0);
+            }
+        }
+
+        let _restore = Restore(DEFERRED.replace(true));
+        write()
+    }
+
+    /// Logs the write, performed or attempted by `backend`.
+    pub(crate) fn finish(mut self, backend: &'static str) {
+        if let Some(logger) = &self.policy.logger {
+            self.record.backend = backend;
+            logger(&self.record);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! This module contains the structs and functions to introspect the ELF auxiliary vector of a process.
+//! Based on https://www.man7.org/linux/man-pages/man3/getauxval.3.html
+use anyhow::bail;
+
+use crate::introspection::arch::Bitness;
+
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_BASE: u64 = 7;
+pub const AT_FLAGS: u64 = 8;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_UID: u64 = 11;
+pub const AT_EUID: u64 = 12;
+pub const AT_GID: u64 = 13;
+pub const AT_EGID: u64 = 14;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_HWCAP: u64 = 16;
+pub const AT_CLKTCK: u64 = 17;
+pub const AT_SECURE: u64 = 23;
+pub const AT_BASE_PLATFORM: u64 = 24;
+pub const AT_RANDOM: u64 = 25;
+pub const AT_HWCAP2: u64 = 26;
+pub const AT_EXECFN: u64 = 31;
+pub const AT_SYSINFO_EHDR: u64 = 33;
+pub const AT_MINSIGSTKSZ: u64 = 51;
+
+/// The auxiliary vector passed by the kernel to the process at `execve`, as found in
+/// `/proc/<pid>/auxv`.
+///
+/// Values that are addresses (e.g. `AT_RANDOM`, `AT_EXECFN`) point into the target's memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuxVec {
+    entries: Vec<(u64, u64)>,
+}
+
+impl AuxVec {
+    /// Parses the raw content of `/proc/<pid>/auxv` of a process of the same bitness as the
+    /// inspector, made of native-endian `(type, value)` words.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::parse_with(bytes, Bitness::NATIVE)
+    }
+
+    /// Parses the raw content of `/proc/<pid>/auxv` of a process of the given bitness: the words
+    /// of a compat process are 32-bit wide.
+    pub fn parse_with(bytes: &[u8], bitness: Bitness) -> anyhow::Result<Self> {
+        let word = bitness.pointer_size();
+
+        if !bytes.len().is_multiple_of(2 * word) {
+            bail!("Truncated auxiliary vector ({} bytes)", bytes.len());
+        }
+
+        let entries = bytes
+            .chunks_exact(2 * word)
+            .map(|entry| {
+                let key = bitness.pointer_from_bytes(&entry[..word]).unwrap();
+                let value = bitness.pointer_from_bytes(&entry[word..]).unwrap();
+                (key, value)
+            })
+            .take_while(|&(key, _)| key != AT_NULL)
+            .collect();
+
+        Ok(AuxVec { entries })
+    }
+
+    /// Returns the value of the entry of type `key`, if present.
+    pub fn get(&self, key: u64) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, value)| value)
+    }
+
+    /// All the `(type, value)` entries, in order.
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    /// Entry point of the executable.
+    pub fn entry(&self) -> Option<u64> {
+        self.get(AT_ENTRY)
+    }
+
+    /// Address of the program headers of the executable.
+    pub fn phdr(&self) -> Option<u64> {
+        self.get(AT_PHDR)
+    }
+
+    /// Size of one program header entry.
+    pub fn phent(&self) -> Option<u64> {
+        self.get(AT_PHENT)
+    }
+
+    /// Number of program headers.
+    pub fn phnum(&self) -> Option<u64> {
+        self.get(AT_PHNUM)
+    }
+
+    /// Base address of the program interpreter (usually the dynamic linker).
+    /// `None` or `Some(0)` for statically linked executables.
+    pub fn base(&self) -> Option<u64> {
+        self.get(AT_BASE)
+    }
+
+    /// Address of sixteen random bytes, used e.g. as the stack canary seed.
+    pub fn random(&self) -> Option<u64> {
+        self.get(AT_RANDOM)
+    }
+
+    /// Address of the NUL-terminated pathname used to execute the program.
+    pub fn execfn(&self) -> Option<u64> {
+        self.get(AT_EXECFN)
+    }
+
+    /// System page size.
+    pub fn page_size(&self) -> Option<u64> {
+        self.get(AT_PAGESZ)
+    }
+
+    /// Address of the vDSO ELF header.
+    pub fn sysinfo_ehdr(&self) -> Option<u64> {
+        self.get(AT_SYSINFO_EHDR)
+    }
+
+    /// Hardware capabilities bitmask.
+    pub fn hwcap(&self) -> Option<u64> {
+        self.get(AT_HWCAP)
+    }
+
+    /// `true` if the program runs in secure mode (e.g. setuid).
+    pub fn secure(&self) -> Option<bool> {
+        self.get(AT_SECURE).map(|secure| secure != 0)
+    }
+}
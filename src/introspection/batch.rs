@@ -0,0 +1,221 @@
+//! This module contains the scans of several processes at once, e.g. every worker of a server.
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::Context;
+
+use crate::introspection::{
+    pattern::Pattern,
+    process::{Pid, Process},
+    scan::{CancellationToken, ScanResults, ScanValue, Scanner},
+    segment::{Segment, Segments},
+    vm::ProcessVm,
+};
+
+/// Returns the IDs of the processes currently running, in increasing order.
+pub fn pids() -> anyhow::Result<Vec<Pid>> {
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc").context("Failed to read /proc")? {
+        if let Some(pid) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            pids.push(pid);
+        }
+    }
+    pids.sort_unstable();
+
+    Ok(pids)
+}
+
+/// What a [`BatchScan`] searches in each process.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanTarget {
+    Value(ScanValue),
+    Pattern(Pattern),
+}
+
+impl From<ScanValue> for ScanTarget {
+    fn from(value: ScanValue) -> Self {
+        ScanTarget::Value(value)
+    }
+}
+
+impl From<Pattern> for ScanTarget {
+    fn from(pattern: Pattern) -> Self {
+        ScanTarget::Pattern(pattern)
+    }
+}
+
+/// Results of a [`BatchScan`] in one process.
+#[derive(Debug)]
+pub struct ProcessResults {
+    pub pid: Pid,
+    /// Name of the process, see [`Process::name`]
+    pub name: String,
+    /// Results of the scan, or why the process could not be scanned, e.g. when not allowed to
+    /// read its memory
+    pub results: anyhow::Result<ScanResults>,
+}
+
+type ProcessFilter = Arc<dyn Fn(&Process) -> bool + Send + Sync>;
+
+type SegmentFilter = Arc<dyn Fn(&Segment) -> bool + Send + Sync>;
+
+/// The same scan run in every process matching a filter, from several threads.
+///
+/// Processes exiting before they are scanned are left out, the other failures are reported in
+/// the results of each process.
+#[derive(Clone)]
+pub struct BatchScan {
+    target: ScanTarget,
+    filter: Option<ProcessFilter>,
+    segment_filter: Option<SegmentFilter>,
+    alignment: Option<usize>,
+    threads: usize,
+    cancellation: Option<CancellationToken>,
+}
+
+impl std::fmt::Debug for BatchScan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchScan")
+            .field("target", &self.target)
+            .field("filter", &self.filter.is_some())
+            .field("segment_filter", &self.segment_filter.is_some())
+            .field("alignment", &self.alignment)
+            .field("threads", &self.threads)
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl BatchScan {
+    /// Searches `target` in all the processes, with a thread per CPU.
+    pub fn new(target: impl Into<ScanTarget>) -> Self {
+        BatchScan {
+            target: target.into(),
+            filter: None,
+            segment_filter: None,
+            alignment: None,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            cancellation: None,
+        }
+    }
+
+    /// Only scans the processes for which `filter` returns `true`.
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(&Process) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Only scans the processes named `name`, e.g. `nginx`.
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.with_filter(move |process| process.name() == name)
+    }
+
+    /// Only scans the segments for which `filter` returns `true`, see
+    /// [`Scanner::filter_segments`].
+    pub fn with_segment_filter(
+        mut self,
+        filter: impl Fn(&Segment) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.segment_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// See [`Scanner::with_alignment`].
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// Sets the number of processes scanned at once, at least one.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Stops the scans once `cancellation` is cancelled, see [`Scanner::with_cancellation`].
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Scans the processes currently running, see [`pids`].
+    pub fn run(&self) -> anyhow::Result<Vec<ProcessResults>> {
+        Ok(self.run_pids(&pids()?))
+    }
+
+    /// Scans the processes `pids` matching the filter. Results are in the order of `pids`.
+    pub fn run_pids(&self, pids: &[Pid]) -> Vec<ProcessResults> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(pids.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(pid) = pids.get(index) else {
+                        break;
+                    };
+                    if let Some(scanned) = self.scan_pid(*pid) {
+                        results
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .push((index, scanned));
+                    }
+                });
+            }
+        });
+
+        let mut results = results
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        results.sort_unstable_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, scanned)| scanned).collect()
+    }
+
+    /// Scans the process `pid`, if it still runs and matches the filter.
+    fn scan_pid(&self, pid: Pid) -> Option<ProcessResults> {
+        let process = Process::from_pid(pid).ok()?;
+        if !self.filter.as_ref().is_none_or(|filter| filter(&process)) {
+            return None;
+        }
+
+        Some(ProcessResults {
+            pid,
+            name: process.name().to_string(),
+            results: self.scan(pid),
+        })
+    }
+
+    fn scan(&self, pid: Pid) -> anyhow::Result<ScanResults> {
+        // Unlike `Process::from_pid`, fails when not allowed to read the mappings
+        let segments = Segments::from_pid(pid)?;
+        let mut scanner = Scanner::new(ProcessVm::new(pid), segments.as_slice());
+        if let Some(filter) = &self.segment_filter {
+            scanner = scanner.filter_segments(|segment| filter(segment));
+        }
+        if let Some(alignment) = self.alignment {
+            scanner = scanner.with_alignment(alignment);
+        }
+        if let Some(cancellation) = &self.cancellation {
+            scanner = scanner.with_cancellation(cancellation.clone());
+        }
+
+        match &self.target {
+            ScanTarget::Value(value) => scanner.scan(value),
+            ScanTarget::Pattern(pattern) => scanner.scan_pattern(pattern),
+        }
+    }
+}
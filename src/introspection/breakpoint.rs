@@ -0,0 +1,334 @@
+//! This module contains the software breakpoints, trapping a traced thread when it executes an
+//! instruction.
+use std::{collections::BTreeMap, mem::ManuallyDrop, ptr};
+
+use anyhow::{anyhow, bail};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryWriter},
+    arch::{Arch, Registers},
+    process::Pid,
+    ptrace::{PtraceSession, Stop},
+};
+
+/// Identifier of a breakpoint of a [`BreakpointManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BreakpointId(u64);
+
+/// A breakpoint installed by a [`BreakpointManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: BreakpointId,
+    pub address: u64,
+    /// Number of times the thread stopped at the breakpoint
+    pub hits: u64,
+    /// Whether the breakpoint instruction is currently written
+    pub enabled: bool,
+}
+
+/// A thread stopped at a breakpoint, its program counter moved back to the breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub id: BreakpointId,
+    pub address: u64,
+    pub tid: Pid,
+    /// Number of hits of the breakpoint, this one included
+    pub hits: u64,
+    pub registers: Registers,
+}
+
+/// What stopped the thread of a [`BreakpointManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointEvent {
+    Hit(Box<BreakpointHit>),
+    /// Any other stop, or the end of the thread
+    Stop(Stop),
+}
+
+type HitCallback = Box<dyn FnMut(&BreakpointHit)>;
+
+struct Entry {
+    breakpoint: Breakpoint,
+    /// Bytes replaced by the breakpoint instruction
+    original: Vec<u8>,
+    callback: Option<HitCallback>,
+}
+
+impl std::fmt::Debug for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Entry")
+            .field("breakpoint", &self.breakpoint)
+            .field("original", &self.original)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+/// Software breakpoints of a traced thread: the instructions at their addresses are replaced by
+/// the breakpoint instruction of the architecture, e.g. `int3`, and restored once removed.
+///
+/// Hits are handled transparently: the program counter is moved back to the breakpoint, and
+/// the original instruction is executed with a single step when resuming before writing the
+/// breakpoint again. Every breakpoint is removed when the manager is dropped, before its session
+/// detaches.
+///
+/// Breakpoints are written in code shared by the threads of the process: a thread which is not
+/// traced and executes one is killed by the `SIGTRAP`, so all the threads running the code
+/// should be traced, or the others stopped.
+#[derive(Debug)]
+pub struct BreakpointManager {
+    session: PtraceSession,
+    arch: Arch,
+    entries: BTreeMap<BreakpointId, Entry>,
+    next_id: u64,
+    /// Signal received while stepping over a breakpoint, delivered when resuming
+    pending_signal: libc::c_int,
+}
+
+impl BreakpointManager {
+    /// Manages the breakpoints of the thread traced by `session`.
+    pub fn new(session: PtraceSession) -> anyhow::Result<Self> {
+        let arch = session.arch()?;
+        Ok(BreakpointManager {
+            session,
+            arch,
+            entries: BTreeMap::new(),
+            next_id: 0,
+            pending_signal: 0,
+        })
+    }
+
+    pub fn session(&self) -> &PtraceSession {
+        &self.session
+    }
+
+    /// The session of the thread, e.g. to read its memory while it is stopped.
+    pub fn session_mut(&mut self) -> &mut PtraceSession {
+        &mut self.session
+    }
+
+    /// Removes every breakpoint and returns the session.
+    pub fn into_session(mut self) -> anyhow::Result<PtraceSession> {
+        self.remove_all()?;
+        let mut manager = ManuallyDrop::new(self);
+        // SAFETY: the fields are read or dropped once, and the manager is not dropped
+        unsafe {
+            ptr::drop_in_place(&mut manager.entries);
+            Ok(ptr::read(&manager.session))
+        }
+    }
+
+    /// Installs a breakpoint at `address`. The thread must be stopped.
+    pub fn insert(&mut self, address: u64) -> anyhow::Result<BreakpointId> {
+        if !address.is_multiple_of(self.arch.instruction_alignment()) {
+            bail!("Misaligned instruction address {address:#x}");
+        }
+        if self.find(address).is_some() {
+            bail!("A breakpoint is already set at {address:#x}");
+        }
+        let instruction = self.arch.breakpoint();
+        let memory = self.session.memory();
+        let original = memory.read_bytes(address, instruction.len())?;
+        memory.write(address, instruction)?;
+
+        let id = BreakpointId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(
+            id,
+            Entry {
+                breakpoint: Breakpoint {
+                    id,
+                    address,
+                    hits: 0,
+                    enabled: true,
+                },
+                original,
+                callback: None,
+            },
+        );
+
+        Ok(id)
+    }
+
+    /// Installs a breakpoint at `address`, calling `callback` on each hit.
+    pub fn insert_with_callback(
+        &mut self,
+        address: u64,
+        callback: impl FnMut(&BreakpointHit) + 'static,
+    ) -> anyhow::Result<BreakpointId> {
+        let id = self.insert(address)?;
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.callback = Some(Box::new(callback));
+        }
+
+        Ok(id)
+    }
+
+    fn entry_mut(&mut self, id: BreakpointId) -> anyhow::Result<&mut Entry> {
+        self.entries
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("No breakpoint {id:?}"))
+    }
+
+    /// Writes or restores the instruction of the breakpoint `id`, keeping it.
+    pub fn set_enabled(&mut self, id: BreakpointId, enabled: bool) -> anyhow::Result<()> {
+        let memory = self.session.memory();
+        let instruction = self.arch.breakpoint();
+        let entry = self.entry_mut(id)?;
+        if entry.breakpoint.enabled != enabled {
+            let bytes = match enabled {
+                true => instruction,
+                false => &entry.original,
+            };
+            memory.write(entry.breakpoint.address, bytes)?;
+            entry.breakpoint.enabled = enabled;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the breakpoint `id`, restoring the original instruction.
+    pub fn remove(&mut self, id: BreakpointId) -> anyhow::Result<Breakpoint> {
+        self.set_enabled(id, false)?;
+        let entry = self
+            .entries
+            .remove(&id)
+            .ok_or_else(|| anyhow!("No breakpoint {id:?}"))?;
+        Ok(entry.breakpoint)
+    }
+
+    /// Removes every breakpoint, restoring the original instructions.
+    pub fn remove_all(&mut self) -> anyhow::Result<()> {
+        let ids: Vec<BreakpointId> = self.entries.keys().copied().collect();
+        let mut result = Ok(());
+        for id in ids {
+            if let Err(error) = self.remove(id) {
+                result = result.and(Err(error));
+                self.entries.remove(&id);
+            }
+        }
+
+        result
+    }
+
+    pub fn get(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.entries.get(&id).map(|entry| &entry.breakpoint)
+    }
+
+    /// Breakpoints in the order they were installed.
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.entries.values().map(|entry| &entry.breakpoint)
+    }
+
+    /// Breakpoint at `address`, if any.
+    pub fn find(&self, address: u64) -> Option<&Breakpoint> {
+        self.breakpoints()
+            .find(|breakpoint| breakpoint.address == address)
+    }
+
+    /// Resumes the thread, delivering its pending signal, first stepping over the breakpoint it
+    /// is stopped at, if any.
+    pub fn cont(&mut self) -> anyhow::Result<()> {
+        // The signal of the current stop is delivered once the step is done
+        if self.session.pending_signal() != 0 {
+            self.pending_signal = self.session.pending_signal();
+        }
+        if let Some(stop) = self.step_over()? {
+            bail!(
+                "Thread {} stopped stepping over a breakpoint: {stop:?}",
+                self.session.tid()
+            );
+        }
+        let signal = std::mem::take(&mut self.pending_signal);
+        self.session.cont(signal)
+    }
+
+    /// Executes the original instruction at the enabled breakpoint the thread is stopped at,
+    /// then writes the breakpoint again. Returns how the thread ended if it did.
+    fn step_over(&mut self) -> anyhow::Result<Option<Stop>> {
+        let pc = self.session.registers()?.pc();
+        let Some(id) = self
+            .breakpoints()
+            .find(|breakpoint| breakpoint.address == pc && breakpoint.enabled)
+            .map(|breakpoint| breakpoint.id)
+        else {
+            return Ok(None);
+        };
+
+        self.set_enabled(id, false)?;
+        loop {
+            self.session.step(0)?;
+            match self.session.wait()? {
+                Stop::Signal(libc::SIGTRAP) => {
+                    self.session.suppress_signal();
+                    break;
+                }
+                stop if stop.is_exit() => return Ok(Some(stop)),
+                // Delivered once the step is done
+                Stop::Signal(signal) => {
+                    self.pending_signal = signal;
+                    self.session.suppress_signal();
+                }
+                _ => {}
+            }
+        }
+        self.set_enabled(id, true)?;
+
+        Ok(None)
+    }
+
+    /// Waits for the thread to stop, handling the hits of the breakpoints.
+    pub fn wait(&mut self) -> anyhow::Result<BreakpointEvent> {
+        let stop = self.session.wait()?;
+        if stop != Stop::Signal(libc::SIGTRAP) {
+            return Ok(BreakpointEvent::Stop(stop));
+        }
+
+        let mut registers = self.session.registers()?;
+        let address = registers
+            .pc()
+            .wrapping_sub(self.arch.breakpoint_pc_offset());
+        let Some(id) = self
+            .breakpoints()
+            .find(|breakpoint| breakpoint.address == address && breakpoint.enabled)
+            .map(|breakpoint| breakpoint.id)
+        else {
+            return Ok(BreakpointEvent::Stop(stop));
+        };
+
+        // The trap comes from the breakpoint, not from a signal to deliver
+        self.session.suppress_signal();
+        registers.set_pc(address);
+        self.session.set_registers(&registers)?;
+        let tid = self.session.tid();
+        let entry = self.entry_mut(id)?;
+        entry.breakpoint.hits += 1;
+        let hit = BreakpointHit {
+            id,
+            address,
+            tid,
+            hits: entry.breakpoint.hits,
+            registers,
+        };
+        if let Some(callback) = &mut entry.callback {
+            callback(&hit);
+        }
+
+        Ok(BreakpointEvent::Hit(Box::new(hit)))
+    }
+
+    /// Resumes the thread and waits for it to stop, see [`Self::cont`] and [`Self::wait`].
+    pub fn run(&mut self) -> anyhow::Result<BreakpointEvent> {
+        self.cont()?;
+        self.wait()
+    }
+}
+
+impl Drop for BreakpointManager {
+    fn drop(&mut self) {
+        // The instructions can only be restored while the thread is stopped
+        if self.session.stop().is_ok() {
+            let _ = self.remove_all();
+        }
+    }
+}
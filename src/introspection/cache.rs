@@ -0,0 +1,231 @@
+//! This module contains a caching layer over the memory backends.
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryWriter},
+    pagemap::page_size,
+};
+
+/// Default number of pages kept by a [`CachedReader`].
+const DEFAULT_CAPACITY: usize = 1024;
+
+#[derive(Debug)]
+struct CachedPage {
+    data: Box<[u8]>,
+    fetched: Instant,
+    /// Key of the page in `Cache::recency`
+    used: u64,
+}
+
+/// Pages by address, evicted least recently used first.
+#[derive(Debug, Default)]
+struct Cache {
+    pages: HashMap<u64, CachedPage>,
+    /// Page addresses by last use
+    recency: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl Cache {
+    fn remove(&mut self, page: u64) {
+        if let Some(cached) = self.pages.remove(&page) {
+            self.recency.remove(&cached.used);
+        }
+    }
+
+    fn touch(&mut self, page: u64) {
+        if let Some(cached) = self.pages.get_mut(&page) {
+            self.recency.remove(&cached.used);
+            self.clock += 1;
+            cached.used = self.clock;
+            self.recency.insert(self.clock, page);
+        }
+    }
+
+    fn insert(&mut self, page: u64, data: Box<[u8]>, capacity: usize) {
+        self.remove(page);
+        while self.pages.len() >= capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&oldest);
+        }
+
+        self.clock += 1;
+        self.pages.insert(
+            page,
+            CachedPage {
+                data,
+                fetched: Instant::now(),
+                used: self.clock,
+            },
+        );
+        self.recency.insert(self.clock, page);
+    }
+}
+
+/// Keeps the most recently read pages of a backend in memory, so repeated reads of the same
+/// structures (e.g. resolving pointer chains) do not reach the process again.
+///
+/// The cache does not see the target changing its memory: stale pages are served until they
+/// expire (see [`Self::with_ttl`]), are evicted or are invalidated. Writes through the cache
+/// invalidate the pages they touch.
+#[derive(Debug)]
+pub struct CachedReader<R> {
+    inner: R,
+    page_size: u64,
+    capacity: usize,
+    ttl: Option<Duration>,
+    cache: Mutex<Cache>,
+}
+
+impl<R> CachedReader<R> {
+    pub fn new(inner: R) -> Self {
+        CachedReader {
+            inner,
+            page_size: page_size(),
+            capacity: DEFAULT_CAPACITY,
+            ttl: None,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// Sets the maximum number of pages kept, at least 1.
+    pub fn with_capacity(mut self, pages: usize) -> Self {
+        self.capacity = pages.max(1);
+        self
+    }
+
+    /// Sets how long a page is served from the cache after being read. Pages never expire by
+    /// default.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Number of pages currently cached, including expired ones not evicted yet.
+    pub fn len(&self) -> usize {
+        self.lock().map_or(0, |cache| cache.pages.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the cached pages overlapping `range`.
+    pub fn invalidate(&self, range: Range<u64>) {
+        let Ok(mut cache) = self.lock() else {
+            return;
+        };
+        for page in self.pages_of(range) {
+            cache.remove(page);
+        }
+    }
+
+    /// Drops all the cached pages.
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.lock() {
+            *cache = Cache::default();
+        }
+    }
+
+    fn lock(&self) -> anyhow::Result<std::sync::MutexGuard<'_, Cache>> {
+        self.cache
+            .lock()
+            .map_err(|_| anyhow!("Memory cache is poisoned"))
+    }
+
+    /// Addresses of the pages overlapping `range`.
+    fn pages_of(&self, range: Range<u64>) -> impl Iterator<Item = u64> {
+        let first = range.start - range.start % self.page_size;
+        let page_size = self.page_size;
+        (first..range.end).step_by(page_size as usize)
+    }
+
+    fn is_fresh(&self, page: &CachedPage) -> bool {
+        self.ttl.is_none_or(|ttl| page.fetched.elapsed() < ttl)
+    }
+}
+
+impl<R: MemoryReader> MemoryReader for CachedReader<R> {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let end = address + buffer.len() as u64;
+        let mut cache = self.lock()?;
+        let pages: Vec<u64> = self.pages_of(address..end).collect();
+
+        // Fetches each run of consecutive missing pages with a single read
+        let mut index = 0;
+        while index < pages.len() {
+            let missing = |page: &u64| {
+                cache
+                    .pages
+                    .get(page)
+                    .is_none_or(|cached| !self.is_fresh(cached))
+            };
+            if !missing(&pages[index]) {
+                index += 1;
+                continue;
+            }
+
+            let run = pages[index..]
+                .iter()
+                .take_while(|page| missing(page))
+                .count();
+            let mut data = vec![0; run * self.page_size as usize];
+            self.inner.read(pages[index], &mut data)?;
+            for (page, chunk) in pages[index..index + run]
+                .iter()
+                .zip(data.chunks_exact(self.page_size as usize))
+            {
+                cache.insert(*page, chunk.into(), self.capacity);
+            }
+            index += run;
+        }
+
+        for page in pages {
+            cache.touch(page);
+            // Pages of this read may have been evicted by a capacity smaller than the read
+            let Some(cached) = cache.pages.get(&page) else {
+                drop(cache);
+                return self.inner.read(address, buffer);
+            };
+
+            let start = address.max(page);
+            let stop = end.min(page + self.page_size);
+            buffer[(start - address) as usize..(stop - address) as usize]
+                .copy_from_slice(&cached.data[(start - page) as usize..(stop - page) as usize]);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: MemoryWriter> MemoryWriter for CachedReader<R> {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.invalidate(address..address + data.len() as u64);
+        self.inner.write(address, data)
+    }
+}
@@ -0,0 +1,236 @@
+//! This module contains the structs and functions to introspect the capability sets of a process.
+//! Based on https://www.man7.org/linux/man-pages/man7/capabilities.7.html
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Context};
+
+/// A Linux capability, numbered as in `include/uapi/linux/capability.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Capability {
+    Chown = 0,
+    DacOverride = 1,
+    DacReadSearch = 2,
+    Fowner = 3,
+    Fsetid = 4,
+    Kill = 5,
+    Setgid = 6,
+    Setuid = 7,
+    Setpcap = 8,
+    LinuxImmutable = 9,
+    NetBindService = 10,
+    NetBroadcast = 11,
+    NetAdmin = 12,
+    NetRaw = 13,
+    IpcLock = 14,
+    IpcOwner = 15,
+    SysModule = 16,
+    SysRawio = 17,
+    SysChroot = 18,
+    SysPtrace = 19,
+    SysPacct = 20,
+    SysAdmin = 21,
+    SysBoot = 22,
+    SysNice = 23,
+    SysResource = 24,
+    SysTime = 25,
+    SysTtyConfig = 26,
+    Mknod = 27,
+    Lease = 28,
+    AuditWrite = 29,
+    AuditControl = 30,
+    Setfcap = 31,
+    MacOverride = 32,
+    MacAdmin = 33,
+    Syslog = 34,
+    WakeAlarm = 35,
+    BlockSuspend = 36,
+    AuditRead = 37,
+    Perfmon = 38,
+    Bpf = 39,
+    CheckpointRestore = 40,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 41] = [
+        Capability::Chown,
+        Capability::DacOverride,
+        Capability::DacReadSearch,
+        Capability::Fowner,
+        Capability::Fsetid,
+        Capability::Kill,
+        Capability::Setgid,
+        Capability::Setuid,
+        Capability::Setpcap,
+        Capability::LinuxImmutable,
+        Capability::NetBindService,
+        Capability::NetBroadcast,
+        Capability::NetAdmin,
+        Capability::NetRaw,
+        Capability::IpcLock,
+        Capability::IpcOwner,
+        Capability::SysModule,
+        Capability::SysRawio,
+        Capability::SysChroot,
+        Capability::SysPtrace,
+        Capability::SysPacct,
+        Capability::SysAdmin,
+        Capability::SysBoot,
+        Capability::SysNice,
+        Capability::SysResource,
+        Capability::SysTime,
+        Capability::SysTtyConfig,
+        Capability::Mknod,
+        Capability::Lease,
+        Capability::AuditWrite,
+        Capability::AuditControl,
+        Capability::Setfcap,
+        Capability::MacOverride,
+        Capability::MacAdmin,
+        Capability::Syslog,
+        Capability::WakeAlarm,
+        Capability::BlockSuspend,
+        Capability::AuditRead,
+        Capability::Perfmon,
+        Capability::Bpf,
+        Capability::CheckpointRestore,
+    ];
+
+    /// Name of the capability as used by `capsh`, e.g. `cap_sys_ptrace`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Chown => "cap_chown",
+            Capability::DacOverride => "cap_dac_override",
+            Capability::DacReadSearch => "cap_dac_read_search",
+            Capability::Fowner => "cap_fowner",
+            Capability::Fsetid => "cap_fsetid",
+            Capability::Kill => "cap_kill",
+            Capability::Setgid => "cap_setgid",
+            Capability::Setuid => "cap_setuid",
+            Capability::Setpcap => "cap_setpcap",
+            Capability::LinuxImmutable => "cap_linux_immutable",
+            Capability::NetBindService => "cap_net_bind_service",
+            Capability::NetBroadcast => "cap_net_broadcast",
+            Capability::NetAdmin => "cap_net_admin",
+            Capability::NetRaw => "cap_net_raw",
+            Capability::IpcLock => "cap_ipc_lock",
+            Capability::IpcOwner => "cap_ipc_owner",
+            Capability::SysModule => "cap_sys_module",
+            Capability::SysRawio => "cap_sys_rawio",
+            Capability::SysChroot => "cap_sys_chroot",
+            Capability::SysPtrace => "cap_sys_ptrace",
+            Capability::SysPacct => "cap_sys_pacct",
+            Capability::SysAdmin => "cap_sys_admin",
+            Capability::SysBoot => "cap_sys_boot",
+            Capability::SysNice => "cap_sys_nice",
+            Capability::SysResource => "cap_sys_resource",
+            Capability::SysTime => "cap_sys_time",
+            Capability::SysTtyConfig => "cap_sys_tty_config",
+            Capability::Mknod => "cap_mknod",
+            Capability::Lease => "cap_lease",
+            Capability::AuditWrite => "cap_audit_write",
+            Capability::AuditControl => "cap_audit_control",
+            Capability::Setfcap => "cap_setfcap",
+            Capability::MacOverride => "cap_mac_override",
+            Capability::MacAdmin => "cap_mac_admin",
+            Capability::Syslog => "cap_syslog",
+            Capability::WakeAlarm => "cap_wake_alarm",
+            Capability::BlockSuspend => "cap_block_suspend",
+            Capability::AuditRead => "cap_audit_read",
+            Capability::Perfmon => "cap_perfmon",
+            Capability::Bpf => "cap_bpf",
+            Capability::CheckpointRestore => "cap_checkpoint_restore",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A set of capabilities, backed by the kernel bitmask.
+///
+/// Bits unknown to this crate (capabilities added by newer kernels) are preserved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    pub fn from_bits(bits: u64) -> Self {
+        Capabilities(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        self.0 & (1 << capability as u8) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the known capabilities of the set.
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        Capability::ALL.into_iter().filter(|cap| self.has(*cap))
+    }
+}
+
+impl FromStr for Capabilities {
+    type Err = anyhow::Error;
+
+    /// Parses a hexadecimal bitmask, e.g. `000001ffffffffff`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Capabilities(
+            u64::from_str_radix(s.trim(), 16)
+                .with_context(|| format!("Invalid capability set: {s}"))?,
+        ))
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.iter().map(|cap| cap.name()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Capability sets of a process, as found in `/proc/<pid>/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessCapabilities {
+    /// CapInh: preserved across an `execve`
+    pub inheritable: Capabilities,
+    /// CapPrm: limiting superset of the effective capabilities
+    pub permitted: Capabilities,
+    /// CapEff: used by the kernel for permission checks
+    pub effective: Capabilities,
+    /// CapBnd: limits the capabilities gained during `execve`
+    pub bounding: Capabilities,
+    /// CapAmb: preserved across an `execve` of an unprivileged program, Linux >= 4.3
+    pub ambient: Capabilities,
+}
+
+impl FromStr for ProcessCapabilities {
+    type Err = anyhow::Error;
+
+    /// Parses the `Cap*` lines of `/proc/<pid>/status`, other lines are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let get = |key: &str| -> anyhow::Result<Capabilities> {
+            s.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+                .ok_or_else(|| anyhow!("Missing {key} in status"))?
+                .parse()
+        };
+
+        Ok(ProcessCapabilities {
+            inheritable: get("CapInh")?,
+            permitted: get("CapPrm")?,
+            effective: get("CapEff")?,
+            bounding: get("CapBnd")?,
+            ambient: get("CapAmb")?,
+        })
+    }
+}
@@ -0,0 +1,559 @@
+//! This module contains the x86_64 detours, hooking a function of a process by replacing its
+//! first instructions with a jump to a handler, and the length disassembler relocating these
+//! instructions to a trampoline calling the original function.
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReaderExt, MemoryWriter},
+    arch::Arch,
+    pagemap::page_size,
+    patch::{code_memory, Patch, PatchSet},
+    ptrace::{FrozenProcess, PtraceSession},
+    remote::{map_anonymous, remote_munmap},
+    segment::Segment,
+};
+
+/// Longest x86 instruction.
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// Size of a `jmp rel32`.
+const JUMP_LEN: usize = 5;
+
+/// Size of a `jmp [rip]` followed by its 64-bit target.
+const ABSOLUTE_JUMP_LEN: usize = 14;
+
+/// Distance from the hook within which the trampolines are mapped, short of the 2 GiB reach of
+/// 32-bit offsets.
+const NEAR_RANGE: u64 = 0x7fff_0000;
+
+/// Lowest address mapped by default, see `vm.mmap_min_addr`.
+const MIN_ADDRESS: u64 = 0x10000;
+
+/// Free ranges tried when mapping the trampolines near the hook.
+const NEAR_ATTEMPTS: usize = 16;
+
+/// Kind of a relative branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchKind {
+    Jump,
+    Call,
+    /// `jcc`, with its condition code
+    Conditional(u8),
+    /// `loop`, `loope`, `loopne` or `jrcxz`, which only take 8-bit offsets
+    Loop,
+}
+
+/// An x86_64 instruction decoded by [`decode`], with what relocating it requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u64,
+    pub len: usize,
+    /// Offset of the 32-bit displacement of its RIP-relative operand
+    pub rip_displacement: Option<usize>,
+    /// Kind and target of a relative branch
+    pub branch: Option<(BranchKind, u64)>,
+    /// Whether the next instruction may run after it, false for `ret`, `jmp`, `ud2`, etc.
+    pub falls_through: bool,
+}
+
+impl Instruction {
+    /// Address of the next instruction.
+    pub fn end(&self) -> u64 {
+        self.address + self.len as u64
+    }
+}
+
+/// Decodes the length and the relative operands of the x86_64 instruction at the start of
+/// `code`, located at `address`.
+pub fn decode(code: &[u8], address: u64) -> anyhow::Result<Instruction> {
+    let byte = |i: usize| {
+        code.get(i)
+            .copied()
+            .ok_or_else(|| anyhow!("Truncated instruction at {address:#x}"))
+    };
+    let invalid = || anyhow!("Invalid instruction at {address:#x}");
+
+    let mut i = 0;
+    let mut operand_16 = false;
+    let mut address_32 = false;
+    loop {
+        match byte(i)? {
+            0x66 => operand_16 = true,
+            0x67 => address_32 = true,
+            0xf0 | 0xf2 | 0xf3 | 0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 => {}
+            _ => break,
+        }
+        i += 1;
+    }
+    let mut rex_w = false;
+    if let rex @ 0x40..=0x4f = byte(i)? {
+        rex_w = rex & 0x08 != 0;
+        i += 1;
+    }
+
+    let opcode = byte(i)?;
+    i += 1;
+    // Size of the immediates of the operand size, 16 or 32 bits
+    let imm_z = if operand_16 { 2 } else { 4 };
+    let mut modrm = false;
+    let mut imm = 0;
+    // Kind and size of the offset of a relative branch
+    let mut branch = None;
+    let mut falls_through = true;
+    match opcode {
+        0x0f => {
+            let opcode = byte(i)?;
+            i += 1;
+            match opcode {
+                0x38 => {
+                    i += 1;
+                    modrm = true;
+                }
+                0x3a => {
+                    i += 1;
+                    modrm = true;
+                    imm = 1;
+                }
+                0x80..=0x8f => branch = Some((BranchKind::Conditional(opcode & 0x0f), 4)),
+                0x0b => falls_through = false,
+                0x05..=0x09 | 0x0e | 0x30..=0x37 | 0x77 | 0xa0..=0xa2 | 0xa8..=0xaa => {}
+                0xc8..=0xcf => {}
+                0x0f | 0x70..=0x73 | 0xa4 | 0xac | 0xba | 0xc2 | 0xc4..=0xc6 => {
+                    modrm = true;
+                    imm = 1;
+                }
+                _ => modrm = true,
+            }
+        }
+        // VEX and EVEX prefixes, followed by the opcode and a ModRM byte
+        0xc4 | 0xc5 | 0x62 => {
+            let map = match opcode {
+                0xc5 => 1,
+                0xc4 => byte(i)? & 0x1f,
+                _ => byte(i)? & 0x07,
+            };
+            i += match opcode {
+                0xc5 => 1,
+                0xc4 => 2,
+                _ => 3,
+            };
+            let opcode = byte(i)?;
+            i += 1;
+            // vzeroupper and vzeroall
+            modrm = !(map == 1 && opcode == 0x77);
+            if map == 3 || (map == 1 && matches!(opcode, 0x70..=0x73 | 0xc2 | 0xc4..=0xc6)) {
+                imm = 1;
+            }
+        }
+        0x00..=0x3f if opcode & 0x07 < 4 => modrm = true,
+        0x00..=0x3f if opcode & 0x07 == 4 => imm = 1,
+        0x00..=0x3f if opcode & 0x07 == 5 => imm = imm_z,
+        0x50..=0x5f | 0x6c..=0x6f | 0x90..=0x99 | 0x9b..=0x9f | 0xa4..=0xa7 | 0xaa..=0xaf => {}
+        0xc9 | 0xd7 | 0xec..=0xef | 0xf1 | 0xf4 | 0xf5 | 0xf8..=0xfd => {}
+        0x63 | 0x84..=0x8f | 0xd0..=0xd3 | 0xd8..=0xdf | 0xf6 | 0xf7 | 0xfe | 0xff => modrm = true,
+        0x69 | 0x81 | 0xc7 => {
+            modrm = true;
+            imm = imm_z;
+        }
+        0x6b | 0x80 | 0x83 | 0xc0 | 0xc1 | 0xc6 => {
+            modrm = true;
+            imm = 1;
+        }
+        0x6a | 0xa8 | 0xb0..=0xb7 | 0xcd | 0xe4..=0xe7 => imm = 1,
+        0x68 | 0xa9 => imm = imm_z,
+        0xa0..=0xa3 => imm = if address_32 { 4 } else { 8 },
+        0xb8..=0xbf => imm = if rex_w { 8 } else { imm_z },
+        0xc8 => imm = 3,
+        0xc2 | 0xca => {
+            imm = 2;
+            falls_through = false;
+        }
+        0xc3 | 0xcb | 0xcc | 0xcf => falls_through = false,
+        0x70..=0x7f => branch = Some((BranchKind::Conditional(opcode & 0x0f), 1)),
+        0xe0..=0xe3 => branch = Some((BranchKind::Loop, 1)),
+        0xe8 => branch = Some((BranchKind::Call, 4)),
+        0xe9 => branch = Some((BranchKind::Jump, 4)),
+        0xeb => branch = Some((BranchKind::Jump, 1)),
+        _ => return Err(invalid()),
+    }
+
+    let mut rip_displacement = None;
+    if modrm {
+        let modrm = byte(i)?;
+        i += 1;
+        let (mode, reg, rm) = (modrm >> 6, (modrm >> 3) & 0x07, modrm & 0x07);
+        if mode != 3 {
+            let mut displacement = match mode {
+                1 => 1,
+                2 => 4,
+                _ => 0,
+            };
+            if rm == 4 {
+                let sib = byte(i)?;
+                i += 1;
+                if mode == 0 && sib & 0x07 == 5 {
+                    displacement = 4;
+                }
+            } else if mode == 0 && rm == 5 {
+                rip_displacement = Some(i);
+                displacement = 4;
+            }
+            i += displacement;
+        }
+        match (opcode, reg) {
+            // test has an immediate, unlike the rest of its group
+            (0xf6, 0 | 1) => imm = 1,
+            (0xf7, 0 | 1) => imm = imm_z,
+            // Indirect jumps
+            (0xff, 4 | 5) => falls_through = false,
+            _ => {}
+        }
+    }
+
+    let branch = match branch {
+        Some(_) if operand_16 => bail!("16-bit relative branch at {address:#x}"),
+        Some((kind, size)) => {
+            let bytes = code
+                .get(i..i + size)
+                .ok_or_else(|| anyhow!("Truncated instruction at {address:#x}"))?;
+            let offset = match size {
+                1 => bytes[0] as i8 as i64,
+                _ => i32::from_le_bytes(bytes.try_into()?) as i64,
+            };
+            i += size;
+            if kind == BranchKind::Jump {
+                falls_through = false;
+            }
+            Some((kind, (address + i as u64).wrapping_add(offset as u64)))
+        }
+        None => None,
+    };
+
+    let len = i + imm;
+    if len > MAX_INSTRUCTION_LEN {
+        return Err(invalid());
+    }
+    if len > code.len() {
+        bail!("Truncated instruction at {address:#x}");
+    }
+
+    Ok(Instruction {
+        address,
+        len,
+        rip_displacement,
+        branch,
+        falls_through,
+    })
+}
+
+/// Offset of a 32-bit relative operand from `from`, the end of its instruction, to `target`.
+fn rel32(from: u64, target: u64) -> Option<i32> {
+    i32::try_from(target.wrapping_sub(from) as i64).ok()
+}
+
+/// Appends a jump from `from` to `target`: a `jmp rel32` if it is in range, a `jmp [rip]`
+/// followed by the target otherwise.
+fn push_jump(code: &mut Vec<u8>, from: u64, target: u64) {
+    match rel32(from + JUMP_LEN as u64, target) {
+        Some(offset) => {
+            code.push(0xe9);
+            code.extend_from_slice(&offset.to_le_bytes());
+        }
+        None => {
+            code.extend_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+            code.extend_from_slice(&target.to_le_bytes());
+        }
+    }
+}
+
+/// Appends the instruction `instruction`, of bytes `bytes`, moved to `to`.
+fn relocate_instruction(
+    code: &mut Vec<u8>,
+    instruction: &Instruction,
+    bytes: &[u8],
+    to: u64,
+) -> anyhow::Result<()> {
+    let address = instruction.address;
+    match instruction.branch {
+        None => {
+            let start = code.len();
+            code.extend_from_slice(bytes);
+            if let Some(offset) = instruction.rip_displacement {
+                let displacement = i32::from_le_bytes(bytes[offset..offset + 4].try_into()?);
+                let target = instruction.end().wrapping_add(displacement as i64 as u64);
+                let displacement =
+                    rel32(to + instruction.len as u64, target).with_context(|| {
+                        format!("RIP-relative operand at {address:#x} out of range")
+                    })?;
+                code[start + offset..start + offset + 4]
+                    .copy_from_slice(&displacement.to_le_bytes());
+            }
+        }
+        Some((BranchKind::Jump, target)) => push_jump(code, to, target),
+        Some((BranchKind::Call, target)) => match rel32(to + 5, target) {
+            Some(offset) => {
+                code.push(0xe8);
+                code.extend_from_slice(&offset.to_le_bytes());
+            }
+            None => {
+                // call [rip + 2]; jmp +8; target
+                code.extend_from_slice(&[0xff, 0x15, 0x02, 0, 0, 0, 0xeb, 0x08]);
+                code.extend_from_slice(&target.to_le_bytes());
+            }
+        },
+        Some((BranchKind::Conditional(condition), target)) => match rel32(to + 6, target) {
+            Some(offset) => {
+                code.extend_from_slice(&[0x0f, 0x80 | condition]);
+                code.extend_from_slice(&offset.to_le_bytes());
+            }
+            None => {
+                // The opposite condition jumps over an absolute jump
+                code.extend_from_slice(&[0x70 | (condition ^ 1), ABSOLUTE_JUMP_LEN as u8]);
+                code.extend_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+                code.extend_from_slice(&target.to_le_bytes());
+            }
+        },
+        Some((BranchKind::Loop, _)) => bail!("Cannot relocate the loop at {address:#x}"),
+    }
+
+    Ok(())
+}
+
+/// Relocates the whole instructions of `code` from `from` to `to`, adjusting their relative
+/// operands, and widening their relative branches, possibly to absolute ones.
+pub fn relocate(code: &[u8], from: u64, to: u64) -> anyhow::Result<Vec<u8>> {
+    let end = from + code.len() as u64;
+    let mut relocated = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction = decode(&code[offset..], from + offset as u64)?;
+        if let Some((_, target)) = instruction.branch {
+            if target > from && target < end {
+                bail!(
+                    "Branch at {:#x} into the relocated instructions",
+                    instruction.address
+                );
+            }
+        }
+        let bytes = &code[offset..offset + instruction.len];
+        let address = to + relocated.len() as u64;
+        relocate_instruction(&mut relocated, &instruction, bytes, address)?;
+        offset += instruction.len;
+    }
+
+    Ok(relocated)
+}
+
+/// Maps `len` bytes within [`NEAR_RANGE`] of `address` in the process of `session`, trying the
+/// free ranges nearest to it first.
+fn map_near(session: &mut PtraceSession, address: u64, len: u64) -> anyhow::Result<Option<u64>> {
+    let segments = Segment::get_from_pid(session.tid())?;
+    let low = address.saturating_sub(NEAR_RANGE).max(MIN_ADDRESS);
+    let high = address.saturating_add(NEAR_RANGE);
+    let mut candidates: Vec<u64> = segments
+        .windows(2)
+        .filter(|pair| pair[1].start() - pair[0].end() >= len)
+        .map(|pair| match pair[0].end() < address {
+            true => pair[1].start() - len,
+            false => pair[0].end(),
+        })
+        .filter(|candidate| (low..=high - len).contains(candidate))
+        .collect();
+    candidates.sort_by_key(|candidate| candidate.abs_diff(address));
+
+    for candidate in candidates.into_iter().take(NEAR_ATTEMPTS) {
+        let flags = libc::MAP_PRIVATE | libc::MAP_FIXED_NOREPLACE;
+        let prot = libc::PROT_READ | libc::PROT_EXEC;
+        match map_anonymous(session, candidate, len, prot, flags) {
+            Ok(mapped) if mapped == candidate => return Ok(Some(mapped)),
+            // Kernels before 4.17 take the address as a hint
+            Ok(mapped) => remote_munmap(session, mapped, len)?,
+            Err(_) => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// A function of a process hooked by a [`DetourBuilder`].
+#[derive(Debug)]
+pub struct Detour {
+    address: u64,
+    handler: u64,
+    trampoline: u64,
+    /// Mapping holding the trampoline
+    region: u64,
+    region_len: u64,
+    patches: PatchSet,
+}
+
+impl Detour {
+    /// Address of the hooked function.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn handler(&self) -> u64 {
+        self.handler
+    }
+
+    /// Address of the trampoline, running the relocated first instructions of the function then
+    /// jumping to the rest of it: the handler calls it to call the original function.
+    pub fn trampoline(&self) -> u64 {
+        self.trampoline
+    }
+
+    /// Number of bytes of the function replaced by the jump to the handler.
+    pub fn overwritten(&self) -> usize {
+        self.patches.patches()[0].len()
+    }
+
+    /// Restores the first instructions of the function and unmaps the trampoline, while the
+    /// threads of `frozen` are stopped.
+    ///
+    /// No thread may be running the trampoline, or return to it, e.g. from a call relocated in it.
+    pub fn remove(mut self, frozen: &mut FrozenProcess) -> anyhow::Result<()> {
+        let region = self.region..self.region + self.region_len;
+        for session in frozen.sessions() {
+            if region.contains(&session.registers()?.pc()) {
+                bail!("Thread {} is running the trampoline", session.tid());
+            }
+        }
+        self.patches.revert(frozen)?;
+
+        let tid = frozen.tids()[0];
+        let session = frozen
+            .session_mut(tid)
+            .ok_or_else(|| anyhow!("Thread {tid} not frozen"))?;
+        remote_munmap(session, self.region, self.region_len)
+    }
+}
+
+/// Builds an x86_64 [`Detour`], replacing the first instructions of the function at an address
+/// with a jump to a handler.
+///
+/// The replaced instructions are relocated to a trampoline mapped within reach of 32-bit
+/// offsets of the function, so that it is hooked with a 5-byte `jmp`, relayed by an absolute
+/// jump in the trampoline mapping if the handler is out of reach. A 14-byte absolute jump is
+/// used when no memory can be mapped near the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetourBuilder {
+    address: u64,
+    handler: u64,
+    absolute_jump: bool,
+}
+
+impl DetourBuilder {
+    /// Hooks the function at `address` with the function at `handler`.
+    pub fn new(address: u64, handler: u64) -> Self {
+        DetourBuilder {
+            address,
+            handler,
+            absolute_jump: false,
+        }
+    }
+
+    /// Hooks the function with a 14-byte absolute jump, mapping the trampoline anywhere.
+    pub fn with_absolute_jump(mut self) -> Self {
+        self.absolute_jump = true;
+        self
+    }
+
+    /// Installs the detour while the threads of `frozen` are stopped, running `mmap` in the
+    /// first one to map the trampoline.
+    pub fn install(&self, frozen: &mut FrozenProcess) -> anyhow::Result<Detour> {
+        let tid = frozen.tids()[0];
+        let session = frozen
+            .session_mut(tid)
+            .ok_or_else(|| anyhow!("Thread {tid} not frozen"))?;
+        if session.arch()? != Arch::X86_64 {
+            bail!("Detours are only supported on x86_64");
+        }
+
+        let region_len = page_size();
+        let near = match self.absolute_jump {
+            true => None,
+            false => map_near(session, self.address, region_len)?,
+        };
+        let (region, jump_len) = match near {
+            Some(region) => (region, JUMP_LEN),
+            None => {
+                let prot = libc::PROT_READ | libc::PROT_EXEC;
+                let region = map_anonymous(session, 0, region_len, prot, libc::MAP_PRIVATE)?;
+                (region, ABSOLUTE_JUMP_LEN)
+            }
+        };
+
+        let result = self.build(frozen, region, jump_len);
+        if result.is_err() {
+            if let Some(session) = frozen.session_mut(tid) {
+                let _ = remote_munmap(session, region, region_len);
+            }
+        }
+        let (trampoline, patches) = result?;
+
+        Ok(Detour {
+            address: self.address,
+            handler: self.handler,
+            trampoline,
+            region,
+            region_len,
+            patches,
+        })
+    }
+
+    /// Writes the trampoline in the mapping at `region` and hooks the function with a jump of
+    /// `jump_len` bytes, returning the address of the trampoline and the applied patch.
+    fn build(
+        &self,
+        frozen: &FrozenProcess,
+        region: u64,
+        jump_len: usize,
+    ) -> anyhow::Result<(u64, PatchSet)> {
+        let memory = code_memory(frozen);
+        let mut code = [0; 2 * MAX_INSTRUCTION_LEN + ABSOLUTE_JUMP_LEN];
+        memory.read_partial(self.address, &mut code);
+
+        let mut overwritten = 0;
+        while overwritten < jump_len {
+            let instruction = decode(&code[overwritten..], self.address + overwritten as u64)?;
+            overwritten += instruction.len;
+            if !instruction.falls_through && overwritten < jump_len {
+                bail!("Function at {:#x} too short to hook", self.address);
+            }
+        }
+
+        // The handler is reached through an absolute jump if out of reach of the hook
+        let mut relay = Vec::new();
+        let mut entry = self.handler;
+        if jump_len == JUMP_LEN && rel32(self.address + JUMP_LEN as u64, self.handler).is_none() {
+            push_jump(&mut relay, region, self.handler);
+            entry = region;
+        }
+        let trampoline = region + relay.len() as u64;
+        let mut trampoline_code = relocate(&code[..overwritten], self.address, trampoline)?;
+        let back = trampoline + trampoline_code.len() as u64;
+        push_jump(
+            &mut trampoline_code,
+            back,
+            self.address + overwritten as u64,
+        );
+        relay.extend_from_slice(&trampoline_code);
+        memory.write(region, &relay)?;
+
+        let mut hook = Vec::with_capacity(overwritten);
+        match jump_len {
+            JUMP_LEN => push_jump(&mut hook, self.address, entry),
+            _ => {
+                hook.extend_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+                hook.extend_from_slice(&entry.to_le_bytes());
+            }
+        }
+        // Traps if anything jumps into the rest of the replaced instructions
+        hook.resize(overwritten, 0xcc);
+        let mut patches = PatchSet::new().with(Patch::new(self.address, hook));
+        patches.apply(frozen)?;
+
+        Ok((trampoline, patches))
+    }
+}
@@ -0,0 +1,905 @@
+//! This module contains the structs and functions to parse the ELF headers of the files mapped
+//! by a process, from disk or from its memory, and to group the segments of each file in a
+//! module.
+//! Based on https://www.man7.org/linux/man-pages/man5/elf.5.html
+use std::{
+    fs,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    access::MemoryReader,
+    arch::{Arch, Bitness},
+    imports::{self, Import},
+    process::Pid,
+    segment::{Device, InodeId, Segment},
+    symbol::Symbol,
+};
+
+/// Types of ELF files, `e_type`.
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+pub const ET_CORE: u16 = 4;
+
+/// Types of program headers, `p_type`.
+pub const PT_NULL: u32 = 0;
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+pub const PT_INTERP: u32 = 3;
+pub const PT_NOTE: u32 = 4;
+pub const PT_PHDR: u32 = 6;
+pub const PT_TLS: u32 = 7;
+pub const PT_GNU_EH_FRAME: u32 = 0x6474_e550;
+pub const PT_GNU_STACK: u32 = 0x6474_e551;
+pub const PT_GNU_RELRO: u32 = 0x6474_e552;
+
+/// Permissions of program headers, `p_flags`.
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+/// Types of sections, `sh_type`.
+pub const SHT_NULL: u32 = 0;
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_HASH: u32 = 5;
+pub const SHT_DYNAMIC: u32 = 6;
+pub const SHT_NOTE: u32 = 7;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_REL: u32 = 9;
+pub const SHT_DYNSYM: u32 = 11;
+pub const SHT_GNU_HASH: u32 = 0x6fff_fff6;
+
+/// Flags of sections, `sh_flags`.
+pub const SHF_WRITE: u64 = 1;
+pub const SHF_ALLOC: u64 = 2;
+pub const SHF_EXECINSTR: u64 = 4;
+
+/// Tags of dynamic entries, `d_tag`.
+pub const DT_NULL: u64 = 0;
+pub const DT_NEEDED: u64 = 1;
+pub const DT_PLTRELSZ: u64 = 2;
+pub const DT_PLTGOT: u64 = 3;
+pub const DT_HASH: u64 = 4;
+pub const DT_STRTAB: u64 = 5;
+pub const DT_SYMTAB: u64 = 6;
+pub const DT_RELA: u64 = 7;
+pub const DT_RELASZ: u64 = 8;
+pub const DT_RELAENT: u64 = 9;
+pub const DT_STRSZ: u64 = 10;
+pub const DT_SYMENT: u64 = 11;
+pub const DT_SONAME: u64 = 14;
+pub const DT_REL: u64 = 17;
+pub const DT_RELSZ: u64 = 18;
+pub const DT_RELENT: u64 = 19;
+pub const DT_PLTREL: u64 = 20;
+pub const DT_DEBUG: u64 = 21;
+pub const DT_JMPREL: u64 = 23;
+pub const DT_GNU_HASH: u64 = 0x6fff_fef5;
+
+/// Index of the data encoding byte in `e_ident`.
+const EI_DATA: usize = 5;
+const ELFDATA2LSB: u8 = 1;
+/// `e_shstrndx` value telling the index is in the `sh_link` of section 0.
+const SHN_XINDEX: u16 = 0xffff;
+
+/// Largest program headers read from memory, where the size of the file is unknown.
+const MAX_MAPPED_HEADERS: u64 = 1 << 20;
+
+/// Largest dynamic section read from memory, a few hundred bytes in practice.
+const MAX_DYNAMIC_SIZE: u64 = 1 << 20;
+
+/// Size of the ELF header of a class.
+fn header_size(bitness: Bitness) -> usize {
+    match bitness {
+        Bitness::Bits32 => 52,
+        Bitness::Bits64 => 64,
+    }
+}
+
+/// Size of a program header of a class.
+fn program_header_size(bitness: Bitness) -> usize {
+    match bitness {
+        Bitness::Bits32 => 32,
+        Bitness::Bits64 => 56,
+    }
+}
+
+/// Size of a section header of a class.
+fn section_header_size(bitness: Bitness) -> usize {
+    match bitness {
+        Bitness::Bits32 => 40,
+        Bitness::Bits64 => 64,
+    }
+}
+
+/// Cursor over the little-endian fields of an ELF structure, whose size was checked.
+pub(crate) struct Fields<'a> {
+    bytes: &'a [u8],
+    bitness: Bitness,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(bytes: &'a [u8], bitness: Bitness) -> Self {
+        Fields { bytes, bitness }
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        field.try_into().unwrap()
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+
+    pub(crate) fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    pub(crate) fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    /// A field as wide as an address of the class, e.g. `Elf64_Addr` or `Elf32_Off`.
+    pub(crate) fn word(&mut self) -> u64 {
+        match self.bitness {
+            Bitness::Bits32 => self.u32() as u64,
+            Bitness::Bits64 => u64::from_le_bytes(self.take()),
+        }
+    }
+}
+
+/// The ELF header, at the start of an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfHeader {
+    /// Class of the file, from `e_ident`
+    pub bitness: Bitness,
+    /// Type of the file, e.g. [`ET_DYN`] for shared libraries and position independent
+    /// executables
+    pub kind: u16,
+    pub machine: u16,
+    /// Address of the entry point, not relocated
+    pub entry: u64,
+    /// File offset of the program headers
+    pub phoff: u64,
+    /// File offset of the section headers
+    pub shoff: u64,
+    pub flags: u32,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    /// Number of section headers, `e_shnum`: 0 when it does not fit, the number being then in
+    /// the `sh_size` of section 0, which [`Elf`] reads
+    pub shnum: u64,
+    /// Index of the section holding the names of the sections, `e_shstrndx`: `0xffff` when it
+    /// does not fit, the index being then in the `sh_link` of section 0, which [`Elf`] reads
+    pub shstrndx: u32,
+}
+
+impl ElfHeader {
+    /// Parses the ELF header at the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        let bitness = Bitness::from_elf_ident(bytes)?;
+        if bytes.get(EI_DATA) != Some(&ELFDATA2LSB) {
+            bail!("Unsupported big-endian ELF file");
+        }
+        if bytes.len() < header_size(bitness) {
+            bail!("Truncated ELF header");
+        }
+
+        let mut fields = Fields::new(&bytes[16..], bitness);
+        let kind = fields.u16();
+        let machine = fields.u16();
+        let _version = fields.u32();
+        let entry = fields.word();
+        let phoff = fields.word();
+        let shoff = fields.word();
+        let flags = fields.u32();
+        let _ehsize = fields.u16();
+        let phentsize = fields.u16();
+        let phnum = fields.u16();
+        let shentsize = fields.u16();
+        let shnum = fields.u16();
+        let shstrndx = fields.u16();
+
+        Ok(ElfHeader {
+            bitness,
+            kind,
+            machine,
+            entry,
+            phoff,
+            shoff,
+            flags,
+            phentsize,
+            phnum,
+            shentsize,
+            shnum: shnum as u64,
+            shstrndx: shstrndx as u32,
+        })
+    }
+
+    /// Architecture of the machine the file is built for.
+    pub fn arch(&self) -> anyhow::Result<Arch> {
+        Arch::from_elf_machine(self.machine)
+    }
+}
+
+/// A program header, describing a segment of an ELF file, e.g. one mapped by the loader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    /// Type of the segment, e.g. [`PT_LOAD`]
+    pub kind: u32,
+    /// Permissions of the segment, e.g. [`PF_X`]
+    pub flags: u32,
+    pub offset: u64,
+    /// Address of the segment, not relocated
+    pub vaddr: u64,
+    pub paddr: u64,
+    /// Size in the file
+    pub filesz: u64,
+    /// Size in memory, larger than in the file for zero-initialized data
+    pub memsz: u64,
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    /// Parses a program header of a file of the given class.
+    fn parse(bytes: &[u8], bitness: Bitness) -> Self {
+        let mut fields = Fields::new(bytes, bitness);
+        match bitness {
+            Bitness::Bits32 => ProgramHeader {
+                kind: fields.u32(),
+                offset: fields.word(),
+                vaddr: fields.word(),
+                paddr: fields.word(),
+                filesz: fields.word(),
+                memsz: fields.word(),
+                flags: fields.u32(),
+                align: fields.word(),
+            },
+            Bitness::Bits64 => ProgramHeader {
+                kind: fields.u32(),
+                flags: fields.u32(),
+                offset: fields.word(),
+                vaddr: fields.word(),
+                paddr: fields.word(),
+                filesz: fields.word(),
+                memsz: fields.word(),
+                align: fields.word(),
+            },
+        }
+    }
+
+    pub fn is_load(&self) -> bool {
+        self.kind == PT_LOAD
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.flags & PF_X != 0
+    }
+}
+
+/// A section header, describing a section of an ELF file, e.g. `.text` or `.symtab`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionHeader {
+    /// Name read from the section name table, empty if there is none
+    pub name: String,
+    /// Type of the section, e.g. [`SHT_SYMTAB`]
+    pub kind: u32,
+    /// Flags of the section, e.g. [`SHF_ALLOC`]
+    pub flags: u64,
+    /// Address of the section in memory if it is mapped, not relocated
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: u64,
+    /// Size of the entries of the section, if it is a table
+    pub entsize: u64,
+}
+
+impl SectionHeader {
+    /// Parses a section header of a file of the given class, whose name is not read yet.
+    fn parse(bytes: &[u8], bitness: Bitness) -> (u32, Self) {
+        let mut fields = Fields::new(bytes, bitness);
+        let name = fields.u32();
+        let header = SectionHeader {
+            name: String::new(),
+            kind: fields.u32(),
+            flags: fields.word(),
+            addr: fields.word(),
+            offset: fields.word(),
+            size: fields.word(),
+            link: fields.u32(),
+            info: fields.u32(),
+            addralign: fields.word(),
+            entsize: fields.word(),
+        };
+
+        (name, header)
+    }
+
+    /// Whether the section is mapped in memory when the file is loaded.
+    pub fn is_alloc(&self) -> bool {
+        self.flags & SHF_ALLOC != 0
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.flags & SHF_EXECINSTR != 0
+    }
+
+    /// The section takes no space in the file, e.g. `.bss`.
+    pub fn is_nobits(&self) -> bool {
+        self.kind == SHT_NOBITS
+    }
+}
+
+/// The headers of an ELF file: its ELF header, program headers and section headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elf {
+    header: ElfHeader,
+    program_headers: Vec<ProgramHeader>,
+    section_headers: Vec<SectionHeader>,
+}
+
+impl Elf {
+    /// Parses the headers of the ELF file `bytes`.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::read_with(bytes.len() as u64, |offset, buffer| {
+            let Some(bytes) = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| bytes.get(offset..offset.checked_add(buffer.len())?))
+            else {
+                bail!("Truncated ELF file");
+            };
+            buffer.copy_from_slice(bytes);
+            Ok(())
+        })
+    }
+
+    /// Reads the headers of the ELF file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Self::from_file(&file).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Reads the headers of the ELF file `file`, without reading the rest.
+    pub fn from_file(file: &fs::File) -> anyhow::Result<Self> {
+        let size = file
+            .metadata()
+            .context("Failed to get the size of the file")?
+            .len();
+        Self::read_with(size, |offset, buffer| {
+            file.read_exact_at(buffer, offset)
+                .with_context(|| format!("Failed to read {:#x} bytes at {offset:#x}", buffer.len()))
+        })
+    }
+
+    /// Reads the headers of the ELF file mapped at `base` in `memory`, the start of the mapping
+    /// of its start, e.g. [`Module::base`].
+    ///
+    /// The section headers are usually not mapped, in which case they are left empty.
+    pub fn from_memory<M>(memory: &M, base: u64) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        // The ELF and program headers are mapped along with the start of the file
+        let read_start = |offset: u64, buffer: &mut [u8]| memory.read(base + offset, buffer);
+        let header = Self::read_header(read_start)?;
+        let program_headers = Self::read_program_headers(&header, read_start, MAX_MAPPED_HEADERS)?;
+        let mut elf = Elf {
+            header,
+            program_headers,
+            section_headers: Vec::new(),
+        };
+
+        let bias = elf.load_bias(base);
+        let read_mapped = |offset: u64, buffer: &mut [u8]| {
+            let end = offset + buffer.len() as u64;
+            match elf.offset_to_vaddr(offset) {
+                Some(vaddr) if elf.offset_to_vaddr(end - 1) == Some(vaddr + end - 1 - offset) => {
+                    memory.read(vaddr.wrapping_add(bias), buffer)
+                }
+                _ => bail!("File range {offset:#x}-{end:#x} is not mapped"),
+            }
+        };
+        // Nothing past the end of the last loaded segment is mapped
+        let size = elf
+            .loads()
+            .map(|load| load.offset.saturating_add(load.filesz))
+            .max()
+            .unwrap_or(0);
+        if let Ok(section_headers) = Self::read_section_headers(&elf.header, read_mapped, size) {
+            elf.section_headers = section_headers;
+        }
+
+        Ok(elf)
+    }
+
+    /// Reads the headers of a file of `size` bytes with `read`, which fills a buffer with the
+    /// bytes at a file offset.
+    fn read_with(
+        size: u64,
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()> + Copy,
+    ) -> anyhow::Result<Self> {
+        let header = Self::read_header(read)?;
+        let program_headers = Self::read_program_headers(&header, read, size)?;
+        let section_headers = Self::read_section_headers(&header, read, size)?;
+
+        Ok(Elf {
+            header,
+            program_headers,
+            section_headers,
+        })
+    }
+
+    fn read_header(
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<ElfHeader> {
+        // Read the identification first, not to read past the end of a short 32-bit file
+        let mut bytes = [0; 64];
+        read(0, &mut bytes[..16])?;
+        let bitness = Bitness::from_elf_ident(&bytes)?;
+        read(16, &mut bytes[16..header_size(bitness)])?;
+        ElfHeader::parse(&bytes[..header_size(bitness)])
+    }
+
+    /// Reads `len` bytes at `offset` of a file of `size` bytes, checked against its size before
+    /// allocating them.
+    fn read_bytes(
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()>,
+        offset: u64,
+        len: u64,
+        size: u64,
+    ) -> anyhow::Result<Vec<u8>> {
+        if offset.checked_add(len).is_none_or(|end| end > size) {
+            bail!("{len:#x} bytes at {offset:#x} are past the end of the file of {size:#x} bytes");
+        }
+        let mut bytes = vec![0; len as usize];
+        read(offset, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    /// Reads `count` entries of `entry_size` bytes, at least `min_size`, at `offset` of a file
+    /// of `size` bytes.
+    fn read_table(
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()>,
+        size: u64,
+        offset: u64,
+        count: u64,
+        entry_size: u16,
+        min_size: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        if (entry_size as usize) < min_size {
+            bail!("Invalid ELF table entry size {entry_size}");
+        }
+        let Some(len) = count.checked_mul(entry_size as u64) else {
+            bail!("Invalid ELF table of {count} entries");
+        };
+
+        Self::read_bytes(read, offset, len, size)
+    }
+
+    fn read_program_headers(
+        header: &ElfHeader,
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()>,
+        size: u64,
+    ) -> anyhow::Result<Vec<ProgramHeader>> {
+        let bitness = header.bitness;
+        let bytes = Self::read_table(
+            read,
+            size,
+            header.phoff,
+            header.phnum as u64,
+            header.phentsize,
+            program_header_size(bitness),
+        )
+        .context("Failed to read the program headers")?;
+
+        Ok(bytes
+            .chunks_exact(header.phentsize.max(1) as usize)
+            .map(|entry| ProgramHeader::parse(entry, bitness))
+            .collect())
+    }
+
+    fn read_section_headers(
+        header: &ElfHeader,
+        read: impl Fn(u64, &mut [u8]) -> anyhow::Result<()>,
+        size: u64,
+    ) -> anyhow::Result<Vec<SectionHeader>> {
+        if header.shoff == 0 {
+            return Ok(Vec::new());
+        }
+        let bitness = header.bitness;
+        let entry_size = section_header_size(bitness);
+        let first = Self::read_bytes(&read, header.shoff, entry_size as u64, size)
+            .context("Failed to read the section headers")?;
+        let (_, first) = SectionHeader::parse(&first, bitness);
+
+        // Counts too large for the ELF header are in the first section header
+        let count = match header.shnum {
+            0 => first.size,
+            count => count,
+        };
+        let names_index = match header.shstrndx as u16 {
+            SHN_XINDEX => first.link,
+            index => index as u32,
+        };
+        let bytes = Self::read_table(
+            &read,
+            size,
+            header.shoff,
+            count,
+            header.shentsize,
+            entry_size,
+        )
+        .context("Failed to read the section headers")?;
+        let mut sections: Vec<_> = bytes
+            .chunks_exact(header.shentsize.max(1) as usize)
+            .map(|entry| SectionHeader::parse(entry, bitness))
+            .collect();
+
+        let names = match sections.get(names_index as usize) {
+            Some((_, names)) if names_index != 0 && !names.is_nobits() => {
+                Self::read_bytes(&read, names.offset, names.size, size)
+                    .context("Failed to read the section names")?
+            }
+            _ => Vec::new(),
+        };
+        for (name, section) in &mut sections {
+            if let Some(bytes) = names.get(*name as usize..) {
+                let len = bytes
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(bytes.len());
+                section.name = String::from_utf8_lossy(&bytes[..len]).into_owned();
+            }
+        }
+
+        Ok(sections.into_iter().map(|(_, section)| section).collect())
+    }
+
+    pub fn header(&self) -> &ElfHeader {
+        &self.header
+    }
+
+    pub fn program_headers(&self) -> &[ProgramHeader] {
+        &self.program_headers
+    }
+
+    pub fn section_headers(&self) -> &[SectionHeader] {
+        &self.section_headers
+    }
+
+    /// The first section named `name`, e.g. `.text`.
+    pub fn section(&self, name: &str) -> Option<&SectionHeader> {
+        self.section_headers
+            .iter()
+            .find(|section| section.name == name)
+    }
+
+    /// The program headers of the segments mapped by the loader.
+    pub fn loads(&self) -> impl Iterator<Item = &ProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(|program_header| program_header.is_load())
+    }
+
+    /// Address, not relocated, at which the byte at `offset` in the file is mapped, if any.
+    pub fn offset_to_vaddr(&self, offset: u64) -> Option<u64> {
+        self.loads()
+            .find(|load| offset >= load.offset && offset - load.offset < load.filesz)
+            .map(|load| load.vaddr + (offset - load.offset))
+    }
+
+    /// Reads the dynamic section of the file mapped with the load bias `bias` from `memory`, if
+    /// it has one. It must be within a loaded segment and at most 1 MiB.
+    pub fn read_dynamic<M>(&self, memory: &M, bias: u64) -> anyhow::Result<Option<Dynamic>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let Some(dynamic) = self
+            .program_headers
+            .iter()
+            .find(|program_header| program_header.kind == PT_DYNAMIC)
+        else {
+            return Ok(None);
+        };
+
+        let end = dynamic.vaddr.checked_add(dynamic.memsz);
+        let loaded = end.is_some_and(|end| {
+            self.loads().any(|load| {
+                dynamic.vaddr >= load.vaddr
+                    && load
+                        .vaddr
+                        .checked_add(load.memsz)
+                        .is_some_and(|load_end| end <= load_end)
+            })
+        });
+        if !loaded || dynamic.memsz > MAX_DYNAMIC_SIZE {
+            bail!(
+                "Invalid dynamic section of {} bytes at {:#x}",
+                dynamic.memsz,
+                dynamic.vaddr
+            );
+        }
+        // The bias is negative when the file is mapped below its preferred base
+        let address = dynamic
+            .vaddr
+            .checked_add_signed(bias as i64)
+            .ok_or_else(|| anyhow!("Dynamic section at {:#x} out of memory", dynamic.vaddr))?;
+
+        let bitness = self.header.bitness;
+        let entry_size = 2 * bitness.pointer_size();
+        let bytes = memory
+            .read_bytes(address, dynamic.memsz as usize)
+            .context("Failed to read the dynamic section")?;
+        let mut entries = Vec::new();
+        for entry in bytes.chunks_exact(entry_size) {
+            let mut fields = Fields::new(entry, bitness);
+            let (tag, value) = (fields.word(), fields.word());
+            if tag == DT_NULL {
+                break;
+            }
+            entries.push((tag, value));
+        }
+
+        Ok(Some(Dynamic { entries, bias }))
+    }
+
+    /// Address the start of the file is mapped at when loaded where its headers tell: 0 for
+    /// shared libraries and position independent executables.
+    pub fn preferred_base(&self) -> u64 {
+        // The start of the file is mapped at the start of the page of the first segment
+        self.loads()
+            .next()
+            .map_or(0, |load| load.vaddr.wrapping_sub(load.offset))
+    }
+
+    /// Difference between the addresses of the file mapped at `base`, the start of the mapping of
+    /// its start, and the addresses of its headers: 0 for an executable that is not position
+    /// independent.
+    pub fn load_bias(&self, base: u64) -> u64 {
+        base.wrapping_sub(self.preferred_base())
+    }
+}
+
+/// The dynamic section of an ELF file mapped in memory, listing what the loader needs, e.g. the
+/// dynamic symbol table and the libraries to load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dynamic {
+    /// `(d_tag, d_val)` pairs, up to `DT_NULL`
+    entries: Vec<(u64, u64)>,
+    bias: u64,
+}
+
+impl Dynamic {
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    /// Value of the first entry tagged `tag`, e.g. [`DT_STRSZ`].
+    pub fn get(&self, tag: u64) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(entry_tag, _)| *entry_tag == tag)
+            .map(|(_, value)| *value)
+    }
+
+    /// Values of the entries tagged `tag`, e.g. the names of the [`DT_NEEDED`] libraries.
+    pub fn get_all(&self, tag: u64) -> impl Iterator<Item = u64> + '_ {
+        self.entries
+            .iter()
+            .filter(move |(entry_tag, _)| *entry_tag == tag)
+            .map(|(_, value)| *value)
+    }
+
+    /// Address of the first entry tagged `tag`, e.g. [`DT_SYMTAB`], in memory.
+    ///
+    /// The loader relocates the addresses of the dynamic section in place, except where it is
+    /// read-only, e.g. in the vDSO, so the ones below the load bias are relocated here.
+    pub fn address(&self, tag: u64) -> Option<u64> {
+        self.get(tag).map(|value| {
+            if value < self.bias {
+                value.wrapping_add(self.bias)
+            } else {
+                value
+            }
+        })
+    }
+}
+
+/// A file mapped by a process with at least one executable mapping, e.g. its executable or a
+/// shared library, along with the segments mapped from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    path: PathBuf,
+    device: Device,
+    inode: InodeId,
+    deleted: bool,
+    /// Segments mapped from the file, sorted by address
+    segments: Vec<Segment>,
+    /// ELF headers, once read
+    headers: Option<Elf>,
+}
+
+impl Module {
+    /// Groups the file-backed segments of `segments`, sorted by address, by file.
+    ///
+    /// A module starts at each mapping of the start of a file and takes the following mappings
+    /// of the same file, so that a file loaded twice makes two modules. Files without an
+    /// executable mapping, e.g. data files, are left out.
+    pub fn group(segments: &[Segment]) -> Vec<Module> {
+        let mut modules: Vec<Module> = Vec::new();
+        for segment in segments {
+            let (Some(path), Some(device), Some(inode)) =
+                (segment.path(), segment.device(), segment.inode())
+            else {
+                continue;
+            };
+            if inode == 0 {
+                continue;
+            }
+
+            if segment.offset() == 0 {
+                modules.push(Module {
+                    path: path.to_path_buf(),
+                    device,
+                    inode,
+                    deleted: segment.deleted(),
+                    segments: vec![segment.clone()],
+                    headers: None,
+                });
+            } else if let Some(module) = modules
+                .iter_mut()
+                .rev()
+                .find(|module| module.device == device && module.inode == inode)
+            {
+                module.segments.push(segment.clone());
+            }
+        }
+
+        modules.retain(|module| {
+            module
+                .segments
+                .iter()
+                .any(|segment| segment.permissions().is_executable())
+        });
+        modules
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// File name of the module, e.g. `libc.so.6`.
+    pub fn name(&self) -> &str {
+        self.path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    pub fn inode(&self) -> InodeId {
+        self.inode
+    }
+
+    /// The file was unlinked since it was mapped.
+    pub fn deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Start of the mapping of the start of the file, where its ELF header is.
+    pub fn base(&self) -> u64 {
+        self.segments[0].start()
+    }
+
+    /// End of the last segment mapped from the file.
+    pub fn end(&self) -> u64 {
+        self.segments[self.segments.len() - 1].end()
+    }
+
+    /// ELF headers of the module, if read with [`Self::read_headers`].
+    pub fn headers(&self) -> Option<&Elf> {
+        self.headers.as_ref()
+    }
+
+    /// Address the module is mapped at when not moved by ASLR, known once its headers are read.
+    pub fn preferred_base(&self) -> Option<u64> {
+        self.headers.as_ref().map(Elf::preferred_base)
+    }
+
+    /// Difference between the base of the module and its preferred base, the ASLR slide, known
+    /// once its headers are read. It is also the load bias to add to the addresses of its
+    /// headers and symbols.
+    pub fn slide(&self) -> Option<u64> {
+        self.preferred_base()
+            .map(|preferred| self.base().wrapping_sub(preferred))
+    }
+
+    /// Reads the ELF headers of the module from `memory`, the memory of the process mapping it,
+    /// and keeps them, e.g. to compute its slide.
+    pub fn read_headers<M>(&mut self, memory: &M) -> anyhow::Result<&Elf>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let headers = self.read_elf_from_memory(memory)?;
+        Ok(self.headers.insert(headers))
+    }
+
+    /// Reads the symbols the module exports from `memory`, the memory of the process mapping it.
+    /// See [`imports::read_exports`].
+    pub fn exports<M>(&self, memory: &M) -> anyhow::Result<Vec<Symbol>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        imports::read_exports(self, memory)
+    }
+
+    /// Reads the symbols the module imports from `memory`, the memory of the process mapping it,
+    /// with the addresses they currently resolve to. See [`imports::read_imports`].
+    pub fn imports<M>(&self, memory: &M) -> anyhow::Result<Vec<Import>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        imports::read_imports(self, memory)
+    }
+
+    /// Returns true if `address` is inside a segment of the module.
+    pub fn contains(&self, address: u64) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| segment.contains(address))
+    }
+
+    /// Opens the file of the module mapped by the process `pid`, through its root directory so
+    /// that it is found from another mount namespace, or through `/proc/<pid>/map_files` if it
+    /// was deleted.
+    pub fn open(&self, pid: Pid) -> anyhow::Result<fs::File> {
+        if self.deleted {
+            return self.segments[0].open_map_file(pid);
+        }
+        let path = Path::new(&format!("/proc/{pid}/root"))
+            .join(self.path.strip_prefix("/").unwrap_or(&self.path));
+        fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))
+    }
+
+    /// Reads the ELF headers of the file of the module mapped by the process `pid`.
+    pub fn read_elf(&self, pid: Pid) -> anyhow::Result<Elf> {
+        Elf::from_file(&self.open(pid)?)
+            .with_context(|| format!("Failed to parse {}", self.path.display()))
+    }
+
+    /// Reads the ELF headers of the module from `memory`, the memory of the process mapping it.
+    /// See [`Elf::from_memory`].
+    pub fn read_elf_from_memory<M>(&self, memory: &M) -> anyhow::Result<Elf>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        Elf::from_memory(memory, self.base())
+            .with_context(|| format!("Failed to parse {} in memory", self.path.display()))
+    }
+}
@@ -0,0 +1,200 @@
+//! This module contains a writer trying the memory backends from the least to the most intrusive.
+use std::{fs, io, sync::OnceLock};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryWriter},
+    audit::DeferredAudit,
+    mem::ProcMem,
+    process::Pid,
+    ptrace::PtraceMem,
+    vm::ProcessVm,
+};
+
+/// Backends a [`FallbackWriter`] may fall back to when `process_vm_writev` is refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FallbackPolicy {
+    /// Only use `process_vm_writev`.
+    Never,
+    /// Then `/proc/<pid>/mem`, which can write read-only mappings.
+    ProcMem,
+    /// Then `/proc/<pid>/mem`, then `PTRACE_POKEDATA`, which stops the target for each write.
+    #[default]
+    ProcMemThenPtrace,
+}
+
+/// Backend that performed a write of a [`FallbackWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteBackend {
+    ProcessVm,
+    ProcMem,
+    Ptrace,
+}
+
+impl WriteBackend {
+    /// Name of the backend in the records of the [`crate::introspection::audit::WritePolicy`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            WriteBackend::ProcessVm => "process_vm_writev",
+            WriteBackend::ProcMem => "/proc/pid/mem",
+            WriteBackend::Ptrace => "ptrace",
+        }
+    }
+}
+
+/// Writes with `process_vm_writev`, falling back to the backends allowed by its policy when the
+/// write is refused with `EPERM` or `EFAULT`, typically to patch code in a read-only mapping.
+///
+/// Reads use `process_vm_readv` with the same fallbacks.
+#[derive(Debug)]
+pub struct FallbackWriter {
+    vm: ProcessVm,
+    policy: FallbackPolicy,
+    /// Opened on first use, `None` if it could not be
+    mem: OnceLock<Option<ProcMem>>,
+    /// Access through a session of the caller, see [`Self::with_ptrace`]
+    ptrace: Option<PtraceMem>,
+}
+
+impl FallbackWriter {
+    pub fn new(pid: Pid, policy: FallbackPolicy) -> Self {
+        FallbackWriter {
+            vm: ProcessVm::new(pid),
+            policy,
+            mem: OnceLock::new(),
+            ptrace: None,
+        }
+    }
+
+    /// Falls back to `memory` for `PTRACE_POKEDATA`, e.g. the
+    /// [`PtraceSession::memory`](super::ptrace::PtraceSession::memory) of a session the caller
+    /// holds, instead of attaching to the target around each access.
+    ///
+    /// Without it, a target the calling thread already traces is accessed without attaching,
+    /// as attaching again would fail.
+    pub fn with_ptrace(mut self, memory: PtraceMem) -> Self {
+        self.ptrace = Some(memory);
+        self
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.vm.pid()
+    }
+
+    pub fn policy(&self) -> FallbackPolicy {
+        self.policy
+    }
+
+    /// The `PTRACE_PEEKDATA` and `PTRACE_POKEDATA` backend, only attaching to the target if it
+    /// is not traced by the calling thread already.
+    fn ptrace(&self) -> PtraceMem {
+        self.ptrace
+            .unwrap_or_else(|| match traced_by_current_thread(self.pid()) {
+                true => PtraceMem::attached(self.pid()),
+                false => PtraceMem::new(self.pid()),
+            })
+    }
+
+    fn mem(&self) -> Option<&ProcMem> {
+        self.mem
+            .get_or_init(|| ProcMem::open(self.pid()).ok())
+            .as_ref()
+    }
+
+    /// Writes `data` at `address`, returning the backend that succeeded.
+    ///
+    /// If every allowed backend fails, the error of the first one is returned. The write is
+    /// submitted once to the installed write policy, with the backend that performed it.
+    pub fn write_with(&self, address: u64, data: &[u8]) -> anyhow::Result<WriteBackend> {
+        let Some(audit) = DeferredAudit::begin(self.pid(), address, data, self) else {
+            return self.try_write(address, data);
+        };
+        if audit.is_suppressed() {
+            audit.finish(WriteBackend::ProcessVm.name());
+            return Ok(WriteBackend::ProcessVm);
+        }
+
+        let result = audit.run(|| self.try_write(address, data));
+        audit.finish(result.as_ref().unwrap_or(&WriteBackend::ProcessVm).name());
+        result
+    }
+
+    /// Writes `data` at `address` with each allowed backend until one succeeds.
+    fn try_write(&self, address: u64, data: &[u8]) -> anyhow::Result<WriteBackend> {
+        let error = match self.vm.write(address, data) {
+            Ok(()) => return Ok(WriteBackend::ProcessVm),
+            Err(error) if self.policy == FallbackPolicy::Never || !is_refused(&error) => {
+                return Err(error)
+            }
+            Err(error) => error,
+        };
+
+        if let Some(mem) = self.mem().filter(|mem| mem.writable()) {
+            if mem.write(address, data).is_ok() {
+                return Ok(WriteBackend::ProcMem);
+            }
+        }
+
+        if self.policy == FallbackPolicy::ProcMemThenPtrace
+            && self.ptrace().write(address, data).is_ok()
+        {
+            return Ok(WriteBackend::Ptrace);
+        }
+
+        Err(error)
+    }
+}
+
+/// Whether the calling thread traces `pid`, from the `TracerPid` of `/proc/<pid>/status`.
+fn traced_by_current_thread(pid: Pid) -> bool {
+    let Ok(status) = fs::read_to_string(format!("/proc/{pid}/status")) else {
+        return false;
+    };
+    // SAFETY: gettid takes no argument
+    let tid = unsafe { libc::gettid() };
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("TracerPid:"))
+        .and_then(|tracer| tracer.trim().parse::<libc::pid_t>().ok())
+        == Some(tid)
+}
+
+/// Whether `error` is a refusal of the memory access rather than e.g. a missing process.
+fn is_refused(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|cause| matches!(cause.raw_os_error(), Some(libc::EPERM | libc::EFAULT)))
+}
+
+impl MemoryReader for FallbackWriter {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let error = match self.vm.read(address, buffer) {
+            Ok(()) => return Ok(()),
+            Err(error) if self.policy == FallbackPolicy::Never || !is_refused(&error) => {
+                return Err(error)
+            }
+            Err(error) => error,
+        };
+
+        if let Some(mem) = self.mem() {
+            if mem.read(address, buffer).is_ok() {
+                return Ok(());
+            }
+        }
+
+        if self.policy == FallbackPolicy::ProcMemThenPtrace
+            && self.ptrace().read(address, buffer).is_ok()
+        {
+            return Ok(());
+        }
+
+        Err(error)
+    }
+}
+
+impl MemoryWriter for FallbackWriter {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.write_with(address, data).map(|_| ())
+    }
+}
@@ -0,0 +1,226 @@
+//! This module contains the structs and functions to introspect the open file descriptors of a process.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_fdinfo.5.html
+use std::{fs, io, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::{
+    net::{NetTables, Socket},
+    process::Pid,
+    segment::InodeId,
+};
+
+/// Type of the object a file descriptor refers to, deduced from its `/proc/<pid>/fd` link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FdKind {
+    /// A file on a filesystem, including devices and directories.
+    File,
+    /// A socket, identified by its inode in the socket filesystem.
+    Socket(InodeId),
+    /// A pipe or FIFO, identified by its inode in the pipe filesystem.
+    Pipe(InodeId),
+    /// An [eventfd(2)](https://www.man7.org/linux/man-pages/man2/eventfd.2.html).
+    EventFd,
+    /// An [epoll(7)](https://www.man7.org/linux/man-pages/man7/epoll.7.html) instance.
+    EventPoll,
+    /// A [signalfd(2)](https://www.man7.org/linux/man-pages/man2/signalfd.2.html).
+    SignalFd,
+    /// A [timerfd_create(2)](https://www.man7.org/linux/man-pages/man2/timerfd_create.2.html) timer.
+    TimerFd,
+    /// An [inotify(7)](https://www.man7.org/linux/man-pages/man7/inotify.7.html) instance.
+    Inotify,
+    /// A [fanotify(7)](https://www.man7.org/linux/man-pages/man7/fanotify.7.html) instance.
+    Fanotify,
+    /// A [pidfd_open(2)](https://www.man7.org/linux/man-pages/man2/pidfd_open.2.html) descriptor.
+    PidFd,
+    /// Any other anonymous inode, e.g. `[io_uring]` or `[perf_event]`.
+    AnonInode(String),
+}
+
+impl FromStr for FdKind {
+    type Err = anyhow::Error;
+
+    /// Parses the target of a `/proc/<pid>/fd/<fd>` link.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn inode(s: &str, prefix: &str) -> Option<anyhow::Result<InodeId>> {
+            s.strip_prefix(prefix)?.strip_suffix(']').map(|inode| {
+                inode
+                    .parse()
+                    .with_context(|| format!("Invalid inode in fd link: {s}"))
+            })
+        }
+
+        if let Some(inode) = inode(s, "socket:[") {
+            return Ok(FdKind::Socket(inode?));
+        }
+        if let Some(inode) = inode(s, "pipe:[") {
+            return Ok(FdKind::Pipe(inode?));
+        }
+
+        let Some(anon) = s.strip_prefix("anon_inode:") else {
+            return Ok(FdKind::File);
+        };
+
+        Ok(match anon {
+            "[eventfd]" => FdKind::EventFd,
+            "[eventpoll]" => FdKind::EventPoll,
+            "[signalfd]" => FdKind::SignalFd,
+            "[timerfd]" => FdKind::TimerFd,
+            "inotify" => FdKind::Inotify,
+            "[fanotify]" => FdKind::Fanotify,
+            "[pidfd]" => FdKind::PidFd,
+            _ => FdKind::AnonInode(anon.trim_matches(['[', ']']).to_string()),
+        })
+    }
+}
+
+/// Information about a file descriptor, as found in `/proc/<pid>/fdinfo/<fd>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FdInfo {
+    /// Current file offset.
+    pub pos: u64,
+    /// File access mode and status flags, see [open(2)](https://www.man7.org/linux/man-pages/man2/open.2.html).
+    pub flags: u32,
+    /// ID of the mount containing the file, Linux >= 3.15.
+    pub mnt_id: Option<u64>,
+    /// Inode number of the file, Linux >= 5.14.
+    pub ino: Option<InodeId>,
+}
+
+impl FromStr for FdInfo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pos = None;
+        let mut flags = None;
+        let mut mnt_id = None;
+        let mut ino = None;
+
+        for line in s.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key {
+                "pos" => pos = Some(value.parse().context("Invalid fdinfo pos")?),
+                "flags" => {
+                    flags = Some(u32::from_str_radix(value, 8).context("Invalid fdinfo flags")?)
+                }
+                "mnt_id" => mnt_id = Some(value.parse().context("Invalid fdinfo mnt_id")?),
+                "ino" => ino = Some(value.parse().context("Invalid fdinfo ino")?),
+                // Type-specific lines (eventfd-count, tfd, inotify, ...)
+                _ => {}
+            }
+        }
+
+        Ok(FdInfo {
+            pos: pos.ok_or_else(|| anyhow!("Missing pos in fdinfo"))?,
+            flags: flags.ok_or_else(|| anyhow!("Missing flags in fdinfo"))?,
+            mnt_id,
+            ino,
+        })
+    }
+}
+
+/// An open file descriptor of a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessFd {
+    /// The file descriptor number
+    fd: i32,
+    /// Target of the `/proc/<pid>/fd/<fd>` link
+    target: PathBuf,
+    /// Type of the object referred to
+    kind: FdKind,
+    /// Content of `/proc/<pid>/fdinfo/<fd>`
+    info: FdInfo,
+}
+
+impl ProcessFd {
+    /// Reads the file descriptor `fd` of the process `pid`.
+    pub fn from_pid(pid: Pid, fd: i32) -> anyhow::Result<Self> {
+        let link = format!("/proc/{pid}/fd/{fd}");
+        let target = fs::read_link(&link).with_context(|| format!("Failed to read {link}"))?;
+        let kind = target.to_string_lossy().parse()?;
+
+        let path = format!("/proc/{pid}/fdinfo/{fd}");
+        let info = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {path}"))?
+            .parse()?;
+
+        Ok(ProcessFd {
+            fd,
+            target,
+            kind,
+            info,
+        })
+    }
+
+    /// Lists the open file descriptors of the process `pid`, sorted by number.
+    ///
+    /// File descriptors closed while listing are skipped.
+    pub fn list(pid: Pid) -> anyhow::Result<Vec<Self>> {
+        let path = format!("/proc/{pid}/fd");
+        let mut fds = Vec::new();
+
+        for entry in fs::read_dir(&path).with_context(|| format!("Failed to read {path}"))? {
+            let entry = entry?;
+            let Some(fd) = entry.file_name().to_str().and_then(|fd| fd.parse().ok()) else {
+                continue;
+            };
+
+            match ProcessFd::from_pid(pid, fd) {
+                Ok(fd) => fds.push(fd),
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        fds.sort_by_key(|fd| fd.fd);
+        Ok(fds)
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    pub fn target(&self) -> &PathBuf {
+        &self.target
+    }
+
+    pub fn kind(&self) -> &FdKind {
+        &self.kind
+    }
+
+    pub fn info(&self) -> &FdInfo {
+        &self.info
+    }
+
+    /// Current file offset.
+    pub fn pos(&self) -> u64 {
+        self.info.pos
+    }
+
+    /// File access mode and status flags.
+    pub fn flags(&self) -> u32 {
+        self.info.flags
+    }
+
+    /// Resolves the socket referred to by this file descriptor.
+    ///
+    /// Returns `None` if it is not a socket, or if its protocol has no table in `/proc/<pid>/net`
+    /// (e.g. netlink or raw sockets).
+    pub fn socket<'a>(&self, tables: &'a NetTables) -> Option<&'a Socket> {
+        match self.kind {
+            FdKind::Socket(inode) => tables.find(inode),
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if `error` was caused by a missing file, e.g. a closed fd or an exited process.
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
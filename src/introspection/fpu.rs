@@ -0,0 +1,330 @@
+//! This module contains the floating point and vector registers of a stopped thread: the x87,
+//! SSE and AVX state on x86, the NEON and SVE registers on aarch64.
+//! Based on https://www.man7.org/linux/man-pages/man2/ptrace.2.html and the XSAVE layout of the
+//! Intel SDM, volume 1, chapter 13.
+use std::io;
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{arch::Arch, process::Pid};
+
+/// `NT_PRFPREG`, the FXSAVE area on x86_64 and the NEON registers on aarch64.
+const NT_PRFPREG: libc::c_int = 2;
+/// FXSAVE area of a 32-bit x86 process, whose `NT_PRFPREG` is the older x87-only layout.
+const NT_PRXFPREG: libc::c_int = 0x46e6_2b7f;
+/// Full XSAVE area on x86, with the AVX state.
+const NT_X86_XSTATE: libc::c_int = 0x202;
+const NT_ARM_SVE: libc::c_int = 0x405;
+
+/// Size of the legacy FXSAVE area, at the start of the XSAVE area.
+const FXSAVE_SIZE: usize = 512;
+const MXCSR_OFFSET: usize = 24;
+const ST_OFFSET: usize = 32;
+const XMM_OFFSET: usize = 160;
+/// Offset of `XSTATE_BV` in the XSAVE header, telling which components are not in their
+/// initial state.
+const XSTATE_BV_OFFSET: usize = 512;
+/// Bit of the AVX component in `XSTATE_BV`.
+const XSTATE_AVX: u64 = 1 << 2;
+/// Offset of the upper halves of the ymm registers, in the standard format used by ptrace.
+const YMM_HIGH_OFFSET: usize = 576;
+/// Largest XSAVE area read, enough for AVX-512 and AMX.
+const MAX_XSTATE_SIZE: usize = 16 * 1024;
+
+/// Size of `struct user_fpsimd_state`: 32 128-bit registers, `fpsr`, `fpcr` and padding.
+const FPSIMD_SIZE: usize = 32 * 16 + 16;
+/// Size of `struct user_sve_header`.
+const SVE_HEADER_SIZE: usize = 16;
+/// Flag of `user_sve_header` set when the registers are in the SVE layout, not the FPSIMD one.
+const SVE_PT_REGS_SVE: u16 = 1;
+/// Largest SVE register set read, for the largest vector length of 2048 bits.
+const MAX_SVE_SIZE: usize = 64 * 1024;
+
+/// Reads the register set `note` of `tid` into `buffer`, returning its size.
+pub(crate) fn get_regset(tid: Pid, note: libc::c_int, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut iovec = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: buffer.len(),
+    };
+    // SAFETY: the iovec points to `buffer`, which the kernel fills up to its length
+    let result = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETREGSET,
+            tid as libc::pid_t,
+            note,
+            &mut iovec as *mut libc::iovec,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(iovec.iov_len)
+}
+
+/// Writes the register set `note` of `tid` from `buffer`.
+pub(crate) fn set_regset(tid: Pid, note: libc::c_int, buffer: &[u8]) -> io::Result<()> {
+    let mut iovec = libc::iovec {
+        iov_base: buffer.as_ptr() as *mut libc::c_void,
+        iov_len: buffer.len(),
+    };
+    // SAFETY: the iovec points to `buffer`, which the kernel only reads
+    let result = unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETREGSET,
+            tid as libc::pid_t,
+            note,
+            &mut iovec as *mut libc::iovec,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Floating point and vector registers of a stopped thread, as exchanged with ptrace.
+///
+/// On x86 this is the XSAVE area when the kernel provides it, or else the FXSAVE area without
+/// the AVX state. On aarch64 these are the NEON registers, see [`SveRegisters`] for SVE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpRegisters {
+    arch: Arch,
+    /// Register set the bytes were read from
+    note: libc::c_int,
+    bytes: Vec<u8>,
+}
+
+impl FpRegisters {
+    /// Reads the floating point and vector registers of `tid`, a thread traced and stopped by
+    /// the calling thread.
+    pub fn get(tid: Pid, arch: Arch) -> anyhow::Result<Self> {
+        let context = || format!("Failed to read the floating point registers of {tid}");
+        let (note, mut bytes) = match arch {
+            Arch::X86 | Arch::X86_64 => {
+                let mut bytes = vec![0; MAX_XSTATE_SIZE];
+                match get_regset(tid, NT_X86_XSTATE, &mut bytes) {
+                    Ok(len) => {
+                        bytes.truncate(len);
+                        (NT_X86_XSTATE, bytes)
+                    }
+                    // Processors without XSAVE only have the FXSAVE area
+                    Err(error) if error.raw_os_error() == Some(libc::ENODEV) => {
+                        let note = match arch {
+                            Arch::X86 => NT_PRXFPREG,
+                            _ => NT_PRFPREG,
+                        };
+                        (note, vec![0; FXSAVE_SIZE])
+                    }
+                    Err(error) => return Err(error).with_context(context),
+                }
+            }
+            Arch::Aarch64 => (NT_PRFPREG, vec![0; FPSIMD_SIZE]),
+        };
+        if note != NT_X86_XSTATE {
+            let len = get_regset(tid, note, &mut bytes).with_context(context)?;
+            if len != bytes.len() {
+                bail!("Floating point registers of {tid} are {len} bytes, not those of {arch:?}");
+            }
+        }
+        if bytes.len() < FXSAVE_SIZE {
+            bail!("Truncated floating point registers of {tid}");
+        }
+
+        Ok(FpRegisters { arch, note, bytes })
+    }
+
+    /// Writes the floating point and vector registers of `tid`, a thread traced and stopped by
+    /// the calling thread.
+    pub fn set(&self, tid: Pid) -> anyhow::Result<()> {
+        set_regset(tid, self.note, &self.bytes)
+            .with_context(|| format!("Failed to write the floating point registers of {tid}"))
+    }
+
+    pub fn arch(&self) -> Arch {
+        self.arch
+    }
+
+    /// Raw register set, in the layout of the kernel.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn u128_at(&self, offset: usize) -> u128 {
+        u128::from_le_bytes(self.bytes[offset..offset + 16].try_into().unwrap())
+    }
+
+    fn set_u128_at(&mut self, offset: usize, value: u128) {
+        self.bytes[offset..offset + 16].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Number of 128-bit vector registers: xmm0-7 on x86, xmm0-15 on x86_64, v0-31 on aarch64.
+    pub fn vector_count(&self) -> usize {
+        match self.arch {
+            Arch::X86 => 8,
+            Arch::X86_64 => 16,
+            Arch::Aarch64 => 32,
+        }
+    }
+
+    /// Offset of the 128-bit vector register `index`.
+    fn vector_offset(&self, index: usize) -> Option<usize> {
+        if index >= self.vector_count() {
+            return None;
+        }
+        Some(match self.arch {
+            Arch::X86 | Arch::X86_64 => XMM_OFFSET + index * 16,
+            Arch::Aarch64 => index * 16,
+        })
+    }
+
+    /// The 128-bit vector register `index`, xmm on x86 and v (q) on aarch64, lane 0 in the low
+    /// bits.
+    pub fn vector(&self, index: usize) -> Option<u128> {
+        Some(self.u128_at(self.vector_offset(index)?))
+    }
+
+    pub fn set_vector(&mut self, index: usize, value: u128) -> anyhow::Result<()> {
+        let Some(offset) = self.vector_offset(index) else {
+            bail!("No vector register {index} on {:?}", self.arch);
+        };
+        self.set_u128_at(offset, value);
+        Ok(())
+    }
+
+    /// The SSE register xmm`index`, `None` on aarch64.
+    pub fn xmm(&self, index: usize) -> Option<u128> {
+        match self.arch {
+            Arch::X86 | Arch::X86_64 => self.vector(index),
+            Arch::Aarch64 => None,
+        }
+    }
+
+    /// Whether the AVX state was read, so the upper halves of the ymm registers are known.
+    pub fn has_avx(&self) -> bool {
+        self.note == NT_X86_XSTATE && self.bytes.len() >= YMM_HIGH_OFFSET + 16 * 16
+    }
+
+    fn xstate_bv(&self) -> u64 {
+        u64::from_le_bytes(
+            self.bytes[XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// The AVX register ymm`index` as its lower (xmm) and upper halves, `None` without AVX.
+    pub fn ymm(&self, index: usize) -> Option<[u128; 2]> {
+        let low = self.xmm(index)?;
+        if !self.has_avx() {
+            return None;
+        }
+        // The upper halves are zero while the AVX state is in its initial state
+        let high = match self.xstate_bv() & XSTATE_AVX {
+            0 => 0,
+            _ => self.u128_at(YMM_HIGH_OFFSET + index * 16),
+        };
+        Some([low, high])
+    }
+
+    pub fn set_ymm(&mut self, index: usize, value: [u128; 2]) -> anyhow::Result<()> {
+        if self.xmm(index).is_none() || !self.has_avx() {
+            bail!("No ymm register {index}");
+        }
+        if self.xstate_bv() & XSTATE_AVX == 0 {
+            // Leaving the initial state, the other upper halves are zero
+            for other in 0..self.vector_count() {
+                self.set_u128_at(YMM_HIGH_OFFSET + other * 16, 0);
+            }
+            let xstate_bv = self.xstate_bv() | XSTATE_AVX;
+            self.bytes[XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + 8]
+                .copy_from_slice(&xstate_bv.to_le_bytes());
+        }
+        self.set_vector(index, value[0])?;
+        self.set_u128_at(YMM_HIGH_OFFSET + index * 16, value[1]);
+        Ok(())
+    }
+
+    /// The x87 register st`index` as its 80-bit extended precision value, `None` on aarch64.
+    pub fn st(&self, index: usize) -> Option<[u8; 10]> {
+        if self.arch == Arch::Aarch64 || index >= 8 {
+            return None;
+        }
+        let offset = ST_OFFSET + index * 16;
+        Some(self.bytes[offset..offset + 10].try_into().unwrap())
+    }
+
+    /// SSE control and status register, `None` on aarch64.
+    pub fn mxcsr(&self) -> Option<u32> {
+        match self.arch {
+            Arch::X86 | Arch::X86_64 => Some(u32::from_le_bytes(
+                self.bytes[MXCSR_OFFSET..MXCSR_OFFSET + 4]
+                    .try_into()
+                    .unwrap(),
+            )),
+            Arch::Aarch64 => None,
+        }
+    }
+
+    /// Floating point status register, `None` on x86.
+    pub fn fpsr(&self) -> Option<u32> {
+        (self.arch == Arch::Aarch64)
+            .then(|| u32::from_le_bytes(self.bytes[512..516].try_into().unwrap()))
+    }
+
+    /// Floating point control register, `None` on x86.
+    pub fn fpcr(&self) -> Option<u32> {
+        (self.arch == Arch::Aarch64)
+            .then(|| u32::from_le_bytes(self.bytes[516..520].try_into().unwrap()))
+    }
+}
+
+/// Scalable vector registers of an aarch64 thread, as exchanged with ptrace (`NT_ARM_SVE`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SveRegisters {
+    /// `struct user_sve_header` followed by the registers
+    bytes: Vec<u8>,
+}
+
+impl SveRegisters {
+    /// Reads the SVE registers of `tid`, a thread of an aarch64 process traced and stopped by
+    /// the calling thread. Fails on processors without SVE.
+    pub fn get(tid: Pid) -> anyhow::Result<Self> {
+        let mut bytes = vec![0; MAX_SVE_SIZE];
+        let len = get_regset(tid, NT_ARM_SVE, &mut bytes)
+            .with_context(|| format!("Failed to read the SVE registers of {tid}"))?;
+        if len < SVE_HEADER_SIZE {
+            bail!("Truncated SVE registers of {tid}");
+        }
+        bytes.truncate(len);
+
+        Ok(SveRegisters { bytes })
+    }
+
+    /// Writes the SVE registers of `tid`, a thread traced and stopped by the calling thread.
+    pub fn set(&self, tid: Pid) -> anyhow::Result<()> {
+        set_regset(tid, NT_ARM_SVE, &self.bytes)
+            .with_context(|| format!("Failed to write the SVE registers of {tid}"))
+    }
+
+    /// Length of the vector registers of the thread, in bytes.
+    pub fn vector_length(&self) -> usize {
+        u16::from_le_bytes([self.bytes[8], self.bytes[9]]) as usize
+    }
+
+    /// Whether the registers are in the SVE layout. Threads which did not use SVE yet only have
+    /// their NEON registers, see [`FpRegisters`].
+    pub fn is_active(&self) -> bool {
+        u16::from_le_bytes([self.bytes[12], self.bytes[13]]) & SVE_PT_REGS_SVE != 0
+    }
+
+    /// The bytes of the scalable vector register z`index`, lane 0 first, while active.
+    pub fn z(&self, index: usize) -> Option<&[u8]> {
+        if !self.is_active() || index >= 32 {
+            return None;
+        }
+        let offset = SVE_HEADER_SIZE + index * self.vector_length();
+        self.bytes.get(offset..offset + self.vector_length())
+    }
+}
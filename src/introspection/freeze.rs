@@ -0,0 +1,228 @@
+//! This module contains the freezer, keeping values of a process constant by rewriting them.
+use std::{
+    collections::BTreeMap,
+    fs,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+
+use crate::introspection::{access::MemoryWriter, process::Pid, scan::ScanValue};
+
+/// Default interval between two rewrites of the frozen values.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Identifier of a value frozen by a [`Freezer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FreezeId(u64);
+
+/// A value kept at an address by a [`Freezer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrozenValue {
+    pub id: FreezeId,
+    pub address: u64,
+    pub value: ScanValue,
+    /// Whether the value is currently rewritten
+    pub enabled: bool,
+    /// Error of the last rewrite, if it failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct State {
+    entries: BTreeMap<FreezeId, FrozenValue>,
+    next_id: u64,
+    interval: Duration,
+    /// Set once the target exited
+    paused: bool,
+    stopped: bool,
+}
+
+struct Shared {
+    pid: Pid,
+    writer: Box<dyn MemoryWriter + Send + Sync>,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state stays consistent even if a thread panicked while holding it
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Rewrites the enabled values until stopped, pausing once the target exited.
+    fn run(&self) {
+        let mut state = self.lock();
+        while !state.stopped {
+            if !state.paused && !process_exists(self.pid) {
+                state.paused = true;
+            }
+            if !state.paused {
+                for entry in state.entries.values_mut().filter(|entry| entry.enabled) {
+                    entry.error = self
+                        .writer
+                        .write(entry.address, &entry.value.to_bytes())
+                        .err()
+                        .map(|error| format!("{error:#}"));
+                }
+            }
+
+            let interval = state.interval;
+            state = self
+                .wake
+                .wait_timeout(state, interval)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+}
+
+/// Whether the process `pid` still runs, zombies excluded.
+fn process_exists(pid: Pid) -> bool {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return false;
+    };
+    // The state follows the command name, which may contain parentheses
+    stat.rsplit_once(')')
+        .and_then(|(_, rest)| rest.trim_start().chars().next())
+        .is_some_and(|state| state != 'Z' && state != 'X')
+}
+
+/// Keeps values of a process constant by rewriting them from a background thread at a fixed
+/// interval, e.g. to pin the health of a game character.
+///
+/// The thread stops writing once the target exits, see [`Self::is_paused`], and stops when the
+/// freezer is dropped.
+pub struct Freezer {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for Freezer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Freezer")
+            .field("pid", &self.shared.pid)
+            .field("state", &*self.shared.lock())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Freezer {
+    /// Starts a freezer writing to the process `pid` through `writer`.
+    pub fn new(pid: Pid, writer: impl MemoryWriter + Send + Sync + 'static) -> Self {
+        let shared = Arc::new(Shared {
+            pid,
+            writer: Box::new(writer),
+            state: Mutex::new(State {
+                entries: BTreeMap::new(),
+                next_id: 0,
+                interval: DEFAULT_INTERVAL,
+                paused: false,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+        });
+        let thread = thread::spawn({
+            let shared = shared.clone();
+            move || shared.run()
+        });
+
+        Freezer {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.shared.pid
+    }
+
+    /// Sets the interval between two rewrites of the values, 100 ms by default.
+    pub fn set_interval(&self, interval: Duration) {
+        self.shared.lock().interval = interval;
+        self.shared.wake.notify_all();
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.shared.lock().interval
+    }
+
+    /// Keeps `value` at `address`, writing it right away.
+    pub fn freeze(&self, address: u64, value: impl Into<ScanValue>) -> FreezeId {
+        let mut state = self.shared.lock();
+        let id = FreezeId(state.next_id);
+        state.next_id += 1;
+        state.entries.insert(
+            id,
+            FrozenValue {
+                id,
+                address,
+                value: value.into(),
+                enabled: true,
+                error: None,
+            },
+        );
+        self.shared.wake.notify_all();
+        id
+    }
+
+    /// Stops rewriting the value `id`, returning it if it was frozen.
+    pub fn unfreeze(&self, id: FreezeId) -> Option<FrozenValue> {
+        self.shared.lock().entries.remove(&id)
+    }
+
+    /// Resumes or suspends the rewrites of the value `id`, without forgetting it.
+    pub fn set_enabled(&self, id: FreezeId, enabled: bool) -> anyhow::Result<()> {
+        let mut state = self.shared.lock();
+        let entry = state
+            .entries
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("No frozen value {id:?}"))?;
+        entry.enabled = enabled;
+        if enabled {
+            self.shared.wake.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Changes the value kept by `id`.
+    pub fn set_value(&self, id: FreezeId, value: impl Into<ScanValue>) -> anyhow::Result<()> {
+        let mut state = self.shared.lock();
+        let entry = state
+            .entries
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("No frozen value {id:?}"))?;
+        entry.value = value.into();
+        self.shared.wake.notify_all();
+        Ok(())
+    }
+
+    pub fn get(&self, id: FreezeId) -> Option<FrozenValue> {
+        self.shared.lock().entries.get(&id).cloned()
+    }
+
+    /// Frozen values, in the order they were added.
+    pub fn entries(&self) -> Vec<FrozenValue> {
+        self.shared.lock().entries.values().cloned().collect()
+    }
+
+    /// Whether the rewrites stopped because the target exited.
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().paused
+    }
+}
+
+impl Drop for Freezer {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! This module contains the structs and functions to access the memory of a process.
+use std::io;
+
+use anyhow::bail;
+
+use crate::introspection::{
+    access::{AtomicWriter, MemoryReader, MemoryWriter},
+    mem::ProcMem,
+    process::Pid,
+    segment::Segment,
+};
+
+/// An open handle on the address space of a process, through `/proc/<pid>/mem`.
+///
+/// See [`ProcMem`] for the permissions required.
+#[derive(Debug)]
+pub struct ProcessHandle {
+    mem: ProcMem,
+}
+
+impl ProcessHandle {
+    /// Opens the memory of the process `pid`, read-only if writing is not permitted.
+    pub fn open(pid: Pid) -> anyhow::Result<Self> {
+        Ok(ProcessHandle {
+            mem: ProcMem::open(pid)?,
+        })
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.mem.pid()
+    }
+
+    pub fn writable(&self) -> bool {
+        self.mem.writable()
+    }
+
+    /// The backend used by the handle.
+    pub fn mem(&self) -> &ProcMem {
+        &self.mem
+    }
+
+    /// Reads the segments of the process, bound to this handle.
+    pub fn segments(&self) -> anyhow::Result<Vec<SegmentHandle<'_, Self>>> {
+        Ok(Segment::get_from_pid(self.pid())?
+            .into_iter()
+            .map(|segment| SegmentHandle::new(self, segment))
+            .collect())
+    }
+}
+
+impl MemoryReader for ProcessHandle {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        self.mem.read(address, buffer)
+    }
+}
+
+impl MemoryWriter for ProcessHandle {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.mem.write(address, data)
+    }
+}
+
+impl AtomicWriter for ProcessHandle {
+    fn write_atomic_u32(&self, address: u64, value: u32) -> anyhow::Result<()> {
+        self.mem.write_atomic_u32(address, value)
+    }
+
+    fn write_atomic_u64(&self, address: u64, value: u64) -> anyhow::Result<()> {
+        self.mem.write_atomic_u64(address, value)
+    }
+}
+
+/// A segment bound to a memory backend of the process it was read from, to access its contents.
+#[derive(Debug)]
+pub struct SegmentHandle<'a, M: ?Sized> {
+    memory: &'a M,
+    segment: Segment,
+}
+
+impl<'a, M: ?Sized> SegmentHandle<'a, M> {
+    /// Binds `segment` to `memory`, which must access the same process.
+    pub fn new(memory: &'a M, segment: Segment) -> Self {
+        SegmentHandle { memory, segment }
+    }
+
+    pub fn segment(&self) -> &Segment {
+        &self.segment
+    }
+
+    pub fn into_segment(self) -> Segment {
+        self.segment
+    }
+
+    /// Fails if `offset..offset + len` is not inside the segment.
+    fn check_bounds(&self, offset: u64, len: usize) -> anyhow::Result<u64> {
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.segment.size() => Ok(self.segment.start() + offset),
+            _ => bail!(
+                "Range {offset:#x}+{len:#x} is out of segment {:#x}-{:#x}",
+                self.segment.start(),
+                self.segment.end()
+            ),
+        }
+    }
+}
+
+impl<M: MemoryReader + ?Sized> SegmentHandle<'_, M> {
+    /// Reads the whole contents of the segment.
+    pub fn read(&self) -> anyhow::Result<Vec<u8>> {
+        self.memory
+            .read_bytes(self.segment.start(), self.segment.size() as usize)
+    }
+
+    /// Reads `buffer.len()` bytes at `offset` from the start of the segment.
+    pub fn read_at(&self, offset: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let address = self.check_bounds(offset, buffer.len())?;
+        self.memory.read(address, buffer)
+    }
+
+    /// Copies the contents of the segment to `writer` in chunks, returning the number of bytes
+    /// written.
+    pub fn dump_to(&self, writer: &mut impl io::Write) -> anyhow::Result<u64> {
+        self.memory
+            .copy_to(self.segment.start(), self.segment.size(), writer)?;
+        Ok(self.segment.size())
+    }
+}
+
+impl<M: MemoryWriter + ?Sized> SegmentHandle<'_, M> {
+    /// Writes `data` at `offset` from the start of the segment.
+    pub fn write(&self, offset: u64, data: &[u8]) -> anyhow::Result<()> {
+        let address = self.check_bounds(offset, data.len())?;
+        self.memory.write(address, data)
+    }
+}
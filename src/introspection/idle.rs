@@ -0,0 +1,142 @@
+//! This module contains the structs and functions to estimate the working set of a process with
+//! idle page tracking.
+//! Based on https://www.kernel.org/doc/html/latest/admin-guide/mm/idle_page_tracking.html
+use std::{collections::BTreeMap, fs, os::unix::fs::FileExt};
+
+use anyhow::Context;
+
+use crate::introspection::{pagemap::Pagemap, process::Pid, segment::Segment};
+
+const PAGE_IDLE_BITMAP: &str = "/sys/kernel/mm/page_idle/bitmap";
+
+/// Working set of a segment, measured since [`IdlePageTracker::mark_idle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentWorkingSet {
+    pub segment: Segment,
+    /// Number of resident pages accessed since they were marked idle
+    pub accessed_pages: u64,
+    /// Number of resident pages left untouched
+    pub idle_pages: u64,
+}
+
+/// Working set of a process, measured since [`IdlePageTracker::mark_idle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkingSet {
+    pub page_size: u64,
+    pub segments: Vec<SegmentWorkingSet>,
+}
+
+impl WorkingSet {
+    /// Number of pages accessed, all segments included.
+    pub fn accessed_pages(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.accessed_pages)
+            .sum()
+    }
+
+    /// Number of pages left untouched, all segments included.
+    pub fn idle_pages(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.idle_pages).sum()
+    }
+
+    /// Estimated working set size, in bytes.
+    pub fn size(&self) -> u64 {
+        self.accessed_pages() * self.page_size
+    }
+}
+
+/// Marks the pages of a process idle and later reports which were accessed.
+///
+/// Requires `CAP_SYS_ADMIN` and a kernel built with `CONFIG_IDLE_PAGE_TRACKING`. Only pages on
+/// the LRU lists (user memory and page cache) can be tracked.
+#[derive(Debug)]
+pub struct IdlePageTracker {
+    pagemap: Pagemap,
+    bitmap: fs::File,
+}
+
+impl IdlePageTracker {
+    pub fn new(pid: Pid) -> anyhow::Result<Self> {
+        let bitmap = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(PAGE_IDLE_BITMAP)
+            .with_context(|| format!("Failed to open {PAGE_IDLE_BITMAP}"))?;
+
+        Ok(IdlePageTracker {
+            pagemap: Pagemap::open(pid)?,
+            bitmap,
+        })
+    }
+
+    /// Physical frame numbers of the resident pages of `segment`.
+    fn frames(&self, segment: &Segment) -> anyhow::Result<Vec<u64>> {
+        Ok(self
+            .pagemap
+            .read(segment.start()..segment.end())?
+            .into_iter()
+            .filter_map(|entry| entry.pfn())
+            .collect())
+    }
+
+    /// Marks every resident page of the process idle.
+    pub fn mark_idle(&self) -> anyhow::Result<()> {
+        // Bitmap words to set, the file only accepts whole 64-bit words
+        let mut words: BTreeMap<u64, u64> = BTreeMap::new();
+        for segment in Segment::get_from_pid(self.pagemap.pid())? {
+            for pfn in self.frames(&segment)? {
+                *words.entry(pfn / 64).or_default() |= 1 << (pfn % 64);
+            }
+        }
+
+        for (index, word) in words {
+            self.bitmap
+                .write_all_at(&word.to_ne_bytes(), index * 8)
+                .with_context(|| format!("Failed to write {PAGE_IDLE_BITMAP}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reports, per segment, the resident pages accessed since the last [`Self::mark_idle`].
+    /// Pages faulted in since then count as accessed.
+    pub fn working_set(&self) -> anyhow::Result<WorkingSet> {
+        let mut words: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut segments = Vec::new();
+
+        for segment in Segment::get_from_pid(self.pagemap.pid())? {
+            let (mut accessed_pages, mut idle_pages) = (0, 0);
+
+            for pfn in self.frames(&segment)? {
+                let word = match words.get(&(pfn / 64)) {
+                    Some(word) => *word,
+                    None => {
+                        let mut buffer = [0; 8];
+                        self.bitmap
+                            .read_exact_at(&mut buffer, pfn / 64 * 8)
+                            .with_context(|| format!("Failed to read {PAGE_IDLE_BITMAP}"))?;
+                        *words.entry(pfn / 64).or_insert(u64::from_ne_bytes(buffer))
+                    }
+                };
+
+                if word & (1 << (pfn % 64)) != 0 {
+                    idle_pages += 1;
+                } else {
+                    accessed_pages += 1;
+                }
+            }
+
+            segments.push(SegmentWorkingSet {
+                segment,
+                accessed_pages,
+                idle_pages,
+            });
+        }
+
+        Ok(WorkingSet {
+            page_size: self.pagemap.page_size(),
+            segments,
+        })
+    }
+}
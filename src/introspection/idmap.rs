@@ -0,0 +1,90 @@
+//! This module contains the structs and functions to introspect the user and group ID mappings of a
+//! user namespace.
+//! Based on https://www.man7.org/linux/man-pages/man7/user_namespaces.7.html
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+
+/// A range of IDs mapped between two user namespaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdMapEntry {
+    /// First ID of the range in the user namespace of the process
+    pub inside: u32,
+    /// First ID of the range in the user namespace of the reader of the map
+    pub outside: u32,
+    /// Number of IDs in the range
+    pub length: u32,
+}
+
+/// The content of `/proc/<pid>/uid_map` or `/proc/<pid>/gid_map`.
+///
+/// When read from another user namespace, outside IDs are relative to the namespace of the
+/// reader. When read from the same user namespace, they are relative to its parent namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMap {
+    entries: Vec<IdMapEntry>,
+}
+
+impl IdMap {
+    /// A map translating every ID to itself.
+    pub fn identity() -> Self {
+        IdMap {
+            entries: vec![IdMapEntry {
+                inside: 0,
+                outside: 0,
+                length: u32::MAX,
+            }],
+        }
+    }
+
+    pub fn entries(&self) -> &[IdMapEntry] {
+        &self.entries
+    }
+
+    /// Translates an ID of the process's namespace to the reader's, `None` if unmapped.
+    pub fn to_outside(&self, id: u32) -> Option<u32> {
+        self.entries.iter().find_map(|entry| {
+            let offset = id.checked_sub(entry.inside)?;
+            (offset < entry.length).then(|| entry.outside + offset)
+        })
+    }
+
+    /// Translates an ID of the reader's namespace to the process's, `None` if unmapped.
+    pub fn to_inside(&self, id: u32) -> Option<u32> {
+        self.entries.iter().find_map(|entry| {
+            let offset = id.checked_sub(entry.outside)?;
+            (offset < entry.length).then(|| entry.inside + offset)
+        })
+    }
+}
+
+impl FromStr for IdMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut fields = line.split_whitespace().map(|field| {
+                    field
+                        .parse::<u32>()
+                        .with_context(|| format!("Invalid ID map field: {field}"))
+                });
+                let mut next = || {
+                    fields
+                        .next()
+                        .ok_or_else(|| anyhow!("Malformed ID map line: {line}"))?
+                };
+
+                Ok(IdMapEntry {
+                    inside: next()?,
+                    outside: next()?,
+                    length: next()?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(IdMap { entries })
+    }
+}
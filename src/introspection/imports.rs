@@ -0,0 +1,217 @@
+//! This module contains the imports and exports of the modules of a process: the symbols they
+//! take from other modules through relocations, e.g. in their GOT, and the ones they give.
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt},
+    arch::{Arch, Bitness},
+    elf::{
+        Fields, Module, DT_JMPREL, DT_PLTREL, DT_PLTRELSZ, DT_REL, DT_RELA, DT_RELAENT, DT_RELASZ,
+        DT_RELENT, DT_RELSZ,
+    },
+    symbol::{read_dynamic_symbols, Symbol, SymbolBinding, SymbolKind, SymbolTable},
+};
+
+/// Relocation types writing the address of a symbol to a word, `R_<arch>_<type>`.
+const R_X86_64_64: u32 = 1;
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_JUMP_SLOT: u32 = 7;
+const R_386_32: u32 = 1;
+const R_386_GLOB_DAT: u32 = 6;
+const R_386_JMP_SLOT: u32 = 7;
+const R_AARCH64_ABS64: u32 = 257;
+const R_AARCH64_GLOB_DAT: u32 = 1025;
+const R_AARCH64_JUMP_SLOT: u32 = 1026;
+
+/// Whether the relocation type `kind` of `arch` writes the address of its symbol to a word, the
+/// ones through which a module imports symbols.
+fn writes_address(arch: Arch, kind: u32) -> bool {
+    match arch {
+        Arch::X86_64 => matches!(kind, R_X86_64_64 | R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT),
+        Arch::X86 => matches!(kind, R_386_32 | R_386_GLOB_DAT | R_386_JMP_SLOT),
+        Arch::Aarch64 => matches!(
+            kind,
+            R_AARCH64_ABS64 | R_AARCH64_GLOB_DAT | R_AARCH64_JUMP_SLOT
+        ),
+    }
+}
+
+/// A relocation of a dynamic symbol.
+#[derive(Debug, Clone, Copy)]
+struct Relocation {
+    /// Address written, not relocated
+    offset: u64,
+    kind: u32,
+    /// Index of the symbol in the dynamic symbol table
+    symbol: u32,
+    addend: i64,
+}
+
+/// Parses the relocations `bytes` of a file of the given class, with addends if `rela`.
+fn parse_relocations(
+    bytes: &[u8],
+    entry_size: usize,
+    bitness: Bitness,
+    rela: bool,
+) -> anyhow::Result<Vec<Relocation>> {
+    let min_size = bitness.pointer_size() * if rela { 3 } else { 2 };
+    if entry_size < min_size {
+        bail!("Invalid relocation size {entry_size}");
+    }
+
+    Ok(bytes
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            let mut fields = Fields::new(entry, bitness);
+            let (offset, info) = (fields.word(), fields.word());
+            let addend = match (rela, bitness) {
+                (false, _) => 0,
+                (true, Bitness::Bits32) => fields.word() as u32 as i32 as i64,
+                (true, Bitness::Bits64) => fields.word() as i64,
+            };
+            let (symbol, kind) = match bitness {
+                Bitness::Bits32 => ((info >> 8) as u32, (info & 0xff) as u32),
+                Bitness::Bits64 => ((info >> 32) as u32, info as u32),
+            };
+
+            Relocation {
+                offset,
+                kind,
+                symbol,
+                addend,
+            }
+        })
+        .collect())
+}
+
+/// A symbol a module takes from another, through a word the loader writes its address to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    name: String,
+    kind: SymbolKind,
+    binding: SymbolBinding,
+    /// Address of the word holding the address of the symbol, e.g. a GOT entry
+    slot: u64,
+    /// Address the import currently resolves to
+    address: u64,
+    /// Type of the relocation, `R_<arch>_<type>`
+    relocation: u32,
+}
+
+impl Import {
+    /// Name of the symbol, without its version.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+
+    /// Binding of the symbol: a [`SymbolBinding::Weak`] import may stay unresolved, at 0.
+    pub fn binding(&self) -> SymbolBinding {
+        self.binding
+    }
+
+    /// Address of the word holding the address of the symbol, e.g. a GOT entry, in the process.
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Address the import currently resolves to, read from its slot.
+    ///
+    /// A function bound lazily that was not called yet resolves to the PLT of the importing
+    /// module, see [`Self::is_bound`].
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn relocation(&self) -> u32 {
+        self.relocation
+    }
+
+    /// Whether the import resolves to another module than `module`, the one importing it.
+    pub fn is_bound(&self, module: &Module) -> bool {
+        self.address != 0 && !module.contains(self.address)
+    }
+}
+
+/// Reads the symbols exported by `module` from `memory`, the memory of the process mapping it:
+/// its global dynamic symbols, at their addresses in the process.
+pub fn read_exports<M>(module: &Module, memory: &M) -> anyhow::Result<Vec<Symbol>>
+where
+    M: MemoryReader + ?Sized,
+{
+    let table = SymbolTable::from_memory(memory, module.base())
+        .with_context(|| format!("Failed to read the exports of {}", module.path().display()))?;
+
+    Ok(table
+        .symbols()
+        .iter()
+        .filter(|symbol| symbol.binding() != SymbolBinding::Local)
+        .cloned()
+        .collect())
+}
+
+/// Reads the symbols imported by `module` from `memory`, the memory of the process mapping it,
+/// with the addresses they currently resolve to, sorted by slot.
+pub fn read_imports<M>(module: &Module, memory: &M) -> anyhow::Result<Vec<Import>>
+where
+    M: MemoryReader + ?Sized,
+{
+    let symbols = read_dynamic_symbols(memory, module.base())
+        .with_context(|| format!("Failed to read the imports of {}", module.path().display()))?;
+    let header = symbols.elf.header();
+    let (arch, bitness) = (header.arch()?, header.bitness);
+    let dynamic = &symbols.dynamic;
+
+    // The PLT relocations may be in a table of their own, or at the end of the other one
+    let mut tables = Vec::new();
+    if let (Some(address), Some(size)) = (dynamic.address(DT_RELA), dynamic.get(DT_RELASZ)) {
+        tables.push((address, size, dynamic.get(DT_RELAENT), true));
+    }
+    if let (Some(address), Some(size)) = (dynamic.address(DT_REL), dynamic.get(DT_RELSZ)) {
+        tables.push((address, size, dynamic.get(DT_RELENT), false));
+    }
+    if let (Some(address), Some(size)) = (dynamic.address(DT_JMPREL), dynamic.get(DT_PLTRELSZ)) {
+        let rela = dynamic.get(DT_PLTREL) == Some(DT_RELA);
+        let entry_size = dynamic.get(if rela { DT_RELAENT } else { DT_RELENT });
+        tables.push((address, size, entry_size, rela));
+    }
+
+    let mut imports = Vec::new();
+    for (address, size, entry_size, rela) in tables {
+        let words = if rela { 3 } else { 2 };
+        let entry_size = entry_size.map_or(words * bitness.pointer_size(), |size| size as usize);
+        let bytes = memory
+            .read_bytes(address, size as usize)
+            .context("Failed to read the relocations")?;
+
+        for relocation in parse_relocations(&bytes, entry_size, bitness, rela)? {
+            let Some(entry) = symbols.entries.get(relocation.symbol as usize) else {
+                continue;
+            };
+            if relocation.symbol == 0
+                || entry.is_defined()
+                || !writes_address(arch, relocation.kind)
+            {
+                continue;
+            }
+
+            let slot = relocation.offset.wrapping_add(symbols.bias);
+            let value = memory.read_pointer(slot, bitness)?;
+            imports.push(Import {
+                name: entry.name.clone(),
+                kind: entry.kind(),
+                binding: entry.binding(),
+                slot,
+                address: value.wrapping_sub(relocation.addend as u64) & bitness.max_address(),
+                relocation: relocation.kind,
+            });
+        }
+    }
+
+    imports.sort_by_key(|import| import.slot);
+    imports.dedup_by_key(|import| import.slot);
+    Ok(imports)
+}
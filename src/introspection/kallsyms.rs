@@ -0,0 +1,126 @@
+//! This module contains the structs and functions to resolve kernel addresses to symbols.
+//! Based on `/proc/kallsyms`, see https://www.kernel.org/doc/html/latest/admin-guide/sysctl/kernel.html#kptr-restrict
+use std::{fs, str::FromStr, sync::OnceLock};
+
+use anyhow::{anyhow, Context};
+
+/// A symbol of the running kernel or of one of its modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelSymbol {
+    /// Address of the symbol, 0 if kernel pointers are hidden
+    pub address: u64,
+    /// Symbol type as reported by `nm`, e.g. `T` for a global text symbol
+    pub kind: char,
+    /// Symbol name
+    pub name: String,
+    /// Kernel module defining the symbol, if not built-in
+    pub module: Option<String>,
+}
+
+impl FromStr for KernelSymbol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed kallsyms line: {s}"))
+        };
+
+        let address = next()?;
+        let address = u64::from_str_radix(address, 16)
+            .with_context(|| format!("Invalid kallsyms address: {address}"))?;
+        let kind = next()?
+            .chars()
+            .next()
+            .ok_or_else(|| anyhow!("Malformed kallsyms line: {s}"))?;
+        let name = next()?.to_string();
+        let module = fields
+            .next()
+            .map(|module| module.trim_matches(['[', ']']).to_string());
+
+        Ok(KernelSymbol {
+            address,
+            kind,
+            name,
+            module,
+        })
+    }
+}
+
+/// Kernel symbol table, sorted by address.
+#[derive(Debug, Clone, Default)]
+pub struct Kallsyms {
+    symbols: Vec<KernelSymbol>,
+}
+
+static KALLSYMS: OnceLock<Kallsyms> = OnceLock::new();
+
+impl Kallsyms {
+    /// Reads `/proc/kallsyms`.
+    pub fn load() -> anyhow::Result<Self> {
+        fs::read_to_string("/proc/kallsyms")
+            .context("Failed to read /proc/kallsyms")?
+            .parse()
+    }
+
+    /// Returns the symbol table, reading `/proc/kallsyms` on first use only.
+    ///
+    /// Modules loaded afterwards are not visible, use [`Kallsyms::load`] to get a fresh table.
+    pub fn cached() -> anyhow::Result<&'static Self> {
+        if let Some(kallsyms) = KALLSYMS.get() {
+            return Ok(kallsyms);
+        }
+
+        let kallsyms = Kallsyms::load()?;
+        Ok(KALLSYMS.get_or_init(|| kallsyms))
+    }
+
+    /// `false` if kernel pointers are hidden from us (see `kptr_restrict`), in which case no
+    /// address can be resolved.
+    pub fn has_addresses(&self) -> bool {
+        self.symbols.iter().any(|symbol| symbol.address != 0)
+    }
+
+    /// Finds the symbol containing `address`, along with the offset of `address` in it.
+    pub fn resolve(&self, address: u64) -> Option<(&KernelSymbol, u64)> {
+        if address == 0 {
+            return None;
+        }
+
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        let symbol = self.symbols.get(index.checked_sub(1)?)?;
+        if symbol.address == 0 {
+            return None;
+        }
+
+        Some((symbol, address - symbol.address))
+    }
+
+    /// Finds a symbol by name.
+    pub fn find(&self, name: &str) -> Option<&KernelSymbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    pub fn symbols(&self) -> &[KernelSymbol] {
+        &self.symbols
+    }
+}
+
+impl FromStr for Kallsyms {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut symbols = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<anyhow::Result<Vec<KernelSymbol>>>()?;
+        symbols.sort_by_key(|symbol| symbol.address);
+
+        Ok(Kallsyms { symbols })
+    }
+}
@@ -0,0 +1,314 @@
+//! This module contains the heuristic reconstruction of the layout of structures in the memory of
+//! a process, as a first draft when reversing them.
+use std::fmt;
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt},
+    arch::Bitness,
+    location::{Location, LocationBase, Locator},
+};
+
+/// Largest number of bytes of the strings pointed to by fields.
+const MAX_STRING_LEN: usize = 256;
+
+/// Shortest string pointed to or stored by a field.
+const MIN_STRING_LEN: usize = 4;
+
+/// Number of words at the start of a vtable searched for a pointer to code.
+const VTABLE_HEADER: usize = 4;
+
+/// Largest absolute value of the fields guessed as integers.
+const MAX_SMALL_INT: u64 = 0x10_0000;
+
+/// Likely type of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    /// Zero, which may be a null pointer, an integer or padding
+    Zero,
+    /// Pointer to a table of pointers to code in a read-only mapping of a module
+    VtablePointer(Location),
+    /// Pointer to the code of a module
+    CodePointer(Location),
+    /// Pointer to a NUL-terminated printable UTF-8 string, and the string
+    StringPointer(Location, String),
+    /// Pointer to the data of a module
+    ModulePointer(Location),
+    /// Pointer to any other mapping, e.g. `[heap]` or an anonymous one
+    HeapPointer(Location),
+    Float(f32),
+    Double(f64),
+    /// Integer of the size of the field, small enough to be a count, an ID or a flag
+    Int(i64),
+    /// Printable ASCII characters stored in the field itself, up to a NUL
+    Text(String),
+    /// Any other value, in native endianness
+    Unknown(u64),
+}
+
+/// A field of a [`Layout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub offset: usize,
+    pub size: usize,
+    pub kind: FieldKind,
+}
+
+impl Field {
+    /// C type of the field in a draft structure.
+    fn c_type(&self) -> String {
+        match (&self.kind, self.size) {
+            (FieldKind::StringPointer(..), _) => "char *".to_string(),
+            (
+                FieldKind::VtablePointer(_)
+                | FieldKind::CodePointer(_)
+                | FieldKind::ModulePointer(_)
+                | FieldKind::HeapPointer(_),
+                _,
+            ) => "void *".to_string(),
+            (FieldKind::Float(_), _) => "float ".to_string(),
+            (FieldKind::Double(_), _) => "double ".to_string(),
+            (FieldKind::Int(_), size) => format!("int{}_t ", size * 8),
+            (FieldKind::Text(_), _) => "char ".to_string(),
+            (_, size) => format!("uint{}_t ", size * 8),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    /// Formats the field as a C declaration, e.g. `float field_18; // 100`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            FieldKind::VtablePointer(_) => format!("vtable_{:x}", self.offset),
+            _ => format!("field_{:x}", self.offset),
+        };
+        let array = match self.kind {
+            FieldKind::Text(_) => format!("[{}]", self.size),
+            _ => String::new(),
+        };
+        write!(f, "{}{name}{array}; // +{:#x}", self.c_type(), self.offset)?;
+        match &self.kind {
+            FieldKind::Zero => Ok(()),
+            FieldKind::VtablePointer(location) => write!(f, " -> {location} (vtable)"),
+            FieldKind::CodePointer(location) => write!(f, " -> {location} (code)"),
+            FieldKind::StringPointer(location, text) => write!(f, " -> {location} {text:?}"),
+            FieldKind::ModulePointer(location) | FieldKind::HeapPointer(location) => {
+                write!(f, " -> {location}")
+            }
+            FieldKind::Float(value) => write!(f, " {value}"),
+            FieldKind::Double(value) => write!(f, " {value}"),
+            FieldKind::Int(value) => write!(f, " {value}"),
+            FieldKind::Text(text) => write!(f, " {text:?}"),
+            FieldKind::Unknown(value) => write!(f, " {value:#x}"),
+        }
+    }
+}
+
+/// Draft layout of the structure at an address, built by [`StructAnalyzer::analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub address: u64,
+    /// Fields in offset order, covering the window analyzed
+    pub fields: Vec<Field>,
+}
+
+impl fmt::Display for Layout {
+    /// Formats the layout as a draft C structure.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "struct struct_{:x} {{", self.address)?;
+        for field in &self.fields {
+            writeln!(f, "    {field}")?;
+        }
+        write!(f, "}};")
+    }
+}
+
+/// Guesses the types of the fields of structures from their values: pointers are told apart
+/// by the mapping they point to, and the other values by their magnitude as integers and
+/// floats. Fields are assumed aligned on their size.
+#[derive(Debug)]
+pub struct StructAnalyzer<'a, R: ?Sized> {
+    reader: &'a R,
+    locator: &'a Locator,
+    bitness: Bitness,
+}
+
+impl<'a, R: MemoryReader + ?Sized> StructAnalyzer<'a, R> {
+    /// Analyzes structures read through `reader`, with pointers of `bitness` resolved by
+    /// `locator`.
+    pub fn new(reader: &'a R, locator: &'a Locator, bitness: Bitness) -> Self {
+        StructAnalyzer {
+            reader,
+            locator,
+            bitness,
+        }
+    }
+
+    /// Reads `size` bytes at `address`, rounded up to a pointer, and guesses the type of each
+    /// field.
+    pub fn analyze(&self, address: u64, size: usize) -> anyhow::Result<Layout> {
+        let word = self.bitness.pointer_size();
+        let data = self
+            .reader
+            .read_bytes(address, size.div_ceil(word) * word)?;
+
+        let mut fields: Vec<Field> = Vec::new();
+        for (index, bytes) in data.chunks_exact(word).enumerate() {
+            for field in self.classify(bytes, index * word) {
+                // Characters arrays span several words
+                if let (Some(last), FieldKind::Text(text)) = (fields.last_mut(), &field.kind) {
+                    if let FieldKind::Text(previous) = &mut last.kind {
+                        if previous.len() == last.size {
+                            previous.push_str(text);
+                            last.size += field.size;
+                            continue;
+                        }
+                    }
+                }
+                fields.push(field);
+            }
+        }
+
+        Ok(Layout { address, fields })
+    }
+
+    /// Guesses the fields of the word `bytes` at `offset`.
+    fn classify(&self, bytes: &[u8], offset: usize) -> Vec<Field> {
+        let field = |offset, size, kind| Field { offset, size, kind };
+        let value = self.bitness.pointer_from_bytes(bytes).unwrap_or_default();
+        if value == 0 {
+            return vec![field(offset, bytes.len(), FieldKind::Zero)];
+        }
+        if let Some(kind) = self.classify_pointer(value) {
+            return vec![field(offset, bytes.len(), kind)];
+        }
+        if let Some(kind) = classify_text(bytes) {
+            return vec![field(offset, bytes.len(), kind)];
+        }
+        if let Some(kind) = classify_number(bytes) {
+            return vec![field(offset, bytes.len(), kind)];
+        }
+
+        // Two 32-bit fields, unless neither looks like one
+        if bytes.len() == 8 {
+            let halves: Vec<(u32, Option<FieldKind>)> = bytes
+                .chunks_exact(4)
+                .map(|half| {
+                    let value = u32::from_ne_bytes(half.try_into().unwrap());
+                    match value {
+                        0 => (value, Some(FieldKind::Zero)),
+                        _ => (value, classify_number(half)),
+                    }
+                })
+                .collect();
+            if halves.iter().any(|(_, kind)| kind.is_some()) {
+                return halves
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (value, kind))| {
+                        let kind = kind.unwrap_or(FieldKind::Unknown(value.into()));
+                        field(offset + index * 4, 4, kind)
+                    })
+                    .collect();
+            }
+        }
+
+        vec![field(offset, bytes.len(), FieldKind::Unknown(value))]
+    }
+
+    /// Guesses what `value` points to, if it is a pointer.
+    fn classify_pointer(&self, value: u64) -> Option<FieldKind> {
+        let segment = self.locator.segments().find(value)?;
+        let location = self.locator.locate(value)?;
+        let permissions = segment.permissions();
+        if permissions.is_executable() {
+            return Some(FieldKind::CodePointer(location));
+        }
+
+        let in_module = matches!(location.base, LocationBase::Module(_));
+        if in_module && !permissions.is_writable() && self.points_to_code(value) {
+            return Some(FieldKind::VtablePointer(location));
+        }
+        if let Some(text) = self.read_string(value) {
+            return Some(FieldKind::StringPointer(location, text));
+        }
+
+        Some(match in_module {
+            true => FieldKind::ModulePointer(location),
+            false => FieldKind::HeapPointer(location),
+        })
+    }
+
+    /// Whether one of the first words at `address` points to executable code. Rust vtables
+    /// start with the size and alignment of the type, and may not have a destructor.
+    fn points_to_code(&self, address: u64) -> bool {
+        let word = self.bitness.pointer_size();
+        let mut bytes = [0; VTABLE_HEADER * 8];
+        let bytes = &mut bytes[..VTABLE_HEADER * word];
+        self.reader.read(address, bytes).is_ok()
+            && bytes.chunks_exact(word).any(|entry| {
+                self.bitness
+                    .pointer_from_bytes(entry)
+                    .ok()
+                    .and_then(|target| self.locator.segments().find(target))
+                    .is_some_and(|segment| segment.permissions().is_executable())
+            })
+    }
+
+    /// Reads the NUL-terminated printable string at `address`, if any.
+    fn read_string(&self, address: u64) -> Option<String> {
+        let mut buffer = [0; MAX_STRING_LEN];
+        let partial = self.reader.read_partial(address, &mut buffer);
+        let readable = partial
+            .gaps
+            .first()
+            .map_or(buffer.len(), |gap| (gap.start - address) as usize);
+        let len = buffer[..readable].iter().position(|byte| *byte == 0)?;
+        let text = std::str::from_utf8(&buffer[..len]).ok()?;
+        (text.chars().count() >= MIN_STRING_LEN && text.chars().all(|c| !c.is_control()))
+            .then(|| text.to_string())
+    }
+}
+
+/// Guesses printable ASCII characters padded with NULs.
+fn classify_text(bytes: &[u8]) -> Option<FieldKind> {
+    let len = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    let printable = bytes[..len]
+        .iter()
+        .all(|byte| byte.is_ascii_graphic() || *byte == b' ');
+    let padded = bytes[len..].iter().all(|byte| *byte == 0);
+    (len >= MIN_STRING_LEN.min(bytes.len()) && printable && padded)
+        .then(|| FieldKind::Text(String::from_utf8_lossy(&bytes[..len]).into_owned()))
+}
+
+/// Guesses a small integer or a float of the size of `bytes`.
+fn classify_number(bytes: &[u8]) -> Option<FieldKind> {
+    let (int, float) = match bytes.len() {
+        4 => {
+            let bytes = bytes.try_into().ok()?;
+            let float = f32::from_ne_bytes(bytes);
+            let plausible = float.is_normal() && (1e-3..1e7).contains(&float.abs());
+            (
+                i32::from_ne_bytes(bytes) as i64,
+                plausible.then_some(FieldKind::Float(float)),
+            )
+        }
+        8 => {
+            let bytes = bytes.try_into().ok()?;
+            let float = f64::from_ne_bytes(bytes);
+            let plausible = float.is_normal() && (1e-6..1e12).contains(&float.abs());
+            (
+                i64::from_ne_bytes(bytes),
+                plausible.then_some(FieldKind::Double(float)),
+            )
+        }
+        _ => return None,
+    };
+
+    match int.unsigned_abs() < MAX_SMALL_INT {
+        true => Some(FieldKind::Int(int)),
+        false => float,
+    }
+}
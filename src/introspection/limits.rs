@@ -0,0 +1,144 @@
+//! This module contains the structs and functions to introspect the resource limits of a process.
+//! Based on https://www.man7.org/linux/man-pages/man2/getrlimit.2.html
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+/// Width of the `Limit` column in `/proc/<pid>/limits`.
+const NAME_WIDTH: usize = 26;
+
+/// Value of a soft or hard resource limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LimitValue {
+    /// A finite limit, expressed in the unit of the resource.
+    Value(u64),
+    /// No limit (`RLIM_INFINITY`).
+    Unlimited,
+}
+
+impl LimitValue {
+    /// Returns true if `amount` does not exceed this limit.
+    pub fn allows(&self, amount: u64) -> bool {
+        match self {
+            LimitValue::Value(value) => amount <= *value,
+            LimitValue::Unlimited => true,
+        }
+    }
+}
+
+impl FromStr for LimitValue {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unlimited" => Ok(LimitValue::Unlimited),
+            _ => Ok(LimitValue::Value(
+                s.parse()
+                    .with_context(|| format!("Invalid limit value: {s}"))?,
+            )),
+        }
+    }
+}
+
+/// Soft and hard values of a resource limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    /// The value the kernel enforces for the resource.
+    pub soft: LimitValue,
+    /// The ceiling for the soft limit.
+    pub hard: LimitValue,
+}
+
+/// Resource limits of a process, as found in `/proc/<pid>/limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessLimits {
+    /// RLIMIT_CPU, in seconds.
+    pub cpu_time: Limit,
+    /// RLIMIT_FSIZE, in bytes.
+    pub file_size: Limit,
+    /// RLIMIT_DATA, in bytes.
+    pub data_size: Limit,
+    /// RLIMIT_STACK, in bytes.
+    pub stack_size: Limit,
+    /// RLIMIT_CORE, in bytes.
+    pub core_file_size: Limit,
+    /// RLIMIT_RSS, in bytes.
+    pub resident_set: Limit,
+    /// RLIMIT_NPROC, in processes.
+    pub processes: Limit,
+    /// RLIMIT_NOFILE, in files.
+    pub open_files: Limit,
+    /// RLIMIT_MEMLOCK, in bytes.
+    pub locked_memory: Limit,
+    /// RLIMIT_AS, in bytes.
+    pub address_space: Limit,
+    /// RLIMIT_LOCKS, in locks.
+    pub file_locks: Limit,
+    /// RLIMIT_SIGPENDING, in signals.
+    pub pending_signals: Limit,
+    /// RLIMIT_MSGQUEUE, in bytes.
+    pub msgqueue_size: Limit,
+    /// RLIMIT_NICE, as a ceiling of `20 - nice`.
+    pub nice_priority: Limit,
+    /// RLIMIT_RTPRIO, as a ceiling of the real-time priority.
+    pub realtime_priority: Limit,
+    /// RLIMIT_RTTIME, in microseconds.
+    pub realtime_timeout: Limit,
+}
+
+impl FromStr for ProcessLimits {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rows = Vec::new();
+
+        // Skip the header line
+        for line in s.lines().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (Some(name), Some(values)) = (line.get(..NAME_WIDTH), line.get(NAME_WIDTH..))
+            else {
+                bail!("Malformed limits line: {line}");
+            };
+
+            let mut values = values.split_whitespace();
+            let soft = values
+                .next()
+                .ok_or_else(|| anyhow!("Missing soft limit: {line}"))?
+                .parse()?;
+            let hard = values
+                .next()
+                .ok_or_else(|| anyhow!("Missing hard limit: {line}"))?
+                .parse()?;
+
+            rows.push((name.trim(), Limit { soft, hard }));
+        }
+
+        let get = |name: &str| -> anyhow::Result<Limit> {
+            rows.iter()
+                .find(|(row, _)| *row == name)
+                .map(|(_, limit)| *limit)
+                .ok_or_else(|| anyhow!("Missing limit: {name}"))
+        };
+
+        Ok(ProcessLimits {
+            cpu_time: get("Max cpu time")?,
+            file_size: get("Max file size")?,
+            data_size: get("Max data size")?,
+            stack_size: get("Max stack size")?,
+            core_file_size: get("Max core file size")?,
+            resident_set: get("Max resident set")?,
+            processes: get("Max processes")?,
+            open_files: get("Max open files")?,
+            locked_memory: get("Max locked memory")?,
+            address_space: get("Max address space")?,
+            file_locks: get("Max file locks")?,
+            pending_signals: get("Max pending signals")?,
+            msgqueue_size: get("Max msgqueue size")?,
+            nice_priority: get("Max nice priority")?,
+            realtime_priority: get("Max realtime priority")?,
+            realtime_timeout: get("Max realtime timeout")?,
+        })
+    }
+}
@@ -0,0 +1,446 @@
+//! This module contains the objects loaded by the dynamic linker of a process as it sees them:
+//! the `link_map` list its `r_debug` structure points to, the one debuggers follow, and the
+//! notifications of its changes.
+//!
+//! Unlike grouping the mappings of the process by file, this names the objects as they were
+//! loaded, e.g. the path given to `dlopen` for a file deleted since, and leaves out the files
+//! mapped without the loader.
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt},
+    arch::Bitness,
+    breakpoint::{BreakpointHit, BreakpointId, BreakpointManager},
+    elf::{Elf, Fields, Module, DT_DEBUG},
+    process::Process,
+    symbol::SymbolTable,
+};
+
+/// Values of `r_debug.r_state`.
+const RT_CONSISTENT: u32 = 0;
+const RT_ADD: u32 = 1;
+const RT_DELETE: u32 = 2;
+
+/// Longest path of an object read from its `link_map`.
+const PATH_MAX: usize = 4096;
+
+/// Most objects followed in a `link_map` list, in case it is being modified.
+const MAX_OBJECTS: usize = 1 << 16;
+
+/// State of the `link_map` list, which the loader sets around its changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkMapState {
+    /// No change is in progress, `RT_CONSISTENT`
+    Consistent,
+    /// An object is being added, `RT_ADD`
+    Add,
+    /// An object is being removed, `RT_DELETE`
+    Delete,
+    Unknown(u32),
+}
+
+impl From<u32> for LinkMapState {
+    fn from(value: u32) -> Self {
+        match value {
+            RT_CONSISTENT => LinkMapState::Consistent,
+            RT_ADD => LinkMapState::Add,
+            RT_DELETE => LinkMapState::Delete,
+            value => LinkMapState::Unknown(value),
+        }
+    }
+}
+
+/// The `r_debug` structure of the dynamic linker of a process, for one link namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RDebug {
+    /// Address of the structure in the process
+    address: u64,
+    bitness: Bitness,
+    version: u32,
+    /// Address of the first `link_map`, `r_map`
+    map: u64,
+    /// Address of the function the loader calls around its changes, `r_brk`
+    brk: u64,
+    state: LinkMapState,
+    /// Base of the loader, `r_ldbase`
+    ldbase: u64,
+    /// Address of the `r_debug` of the next namespace, `r_next`, 0 before version 2
+    next: u64,
+}
+
+impl RDebug {
+    /// Reads the `r_debug` structure at `address` in `memory`, the memory of a process of the
+    /// given bitness.
+    pub fn read<M>(memory: &M, address: u64, bitness: Bitness) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        // r_version and r_state are ints padded to a word
+        let word = bitness.pointer_size();
+        let bytes = memory
+            .read_bytes(address, 5 * word)
+            .with_context(|| format!("Failed to read r_debug at {address:#x}"))?;
+        let mut fields = Fields::new(&bytes, bitness);
+        let version = fields.word() as u32;
+        let (map, brk) = (fields.word(), fields.word());
+        let state = LinkMapState::from(fields.word() as u32);
+        let ldbase = fields.word();
+
+        // r_debug_extended, from glibc 2.35
+        let next = if version >= 2 {
+            let bytes = memory
+                .read_bytes(address + 5 * word as u64, word)
+                .context("Failed to read r_debug_extended")?;
+            Fields::new(&bytes, bitness).word()
+        } else {
+            0
+        };
+
+        Ok(RDebug {
+            address,
+            bitness,
+            version,
+            map,
+            brk,
+            state,
+            ldbase,
+            next,
+        })
+    }
+
+    /// Finds and reads the `r_debug` structure of the loader of a process from `memory`, its
+    /// memory, given the base of its executable and the base of its loader, `AT_BASE`.
+    ///
+    /// The loader stores its address in the `DT_DEBUG` entry of the dynamic section of the
+    /// executable. Where it does not, e.g. when the loader was run as a program, the `_r_debug`
+    /// symbol of the loader is used instead.
+    pub fn find<M>(
+        memory: &M,
+        executable_base: u64,
+        interpreter_base: Option<u64>,
+    ) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let elf = Elf::from_memory(memory, executable_base)
+            .context("Failed to read the headers of the executable")?;
+        let bitness = elf.header().bitness;
+        let dynamic = elf.read_dynamic(memory, elf.load_bias(executable_base))?;
+        if let Some(address) = dynamic.and_then(|dynamic| dynamic.get(DT_DEBUG)) {
+            if address != 0 {
+                return Self::read(memory, address, bitness);
+            }
+        }
+
+        let Some(interpreter_base) = interpreter_base.filter(|base| *base != 0) else {
+            bail!("The executable has no dynamic linker");
+        };
+        let table = SymbolTable::from_memory(memory, interpreter_base)
+            .context("Failed to read the symbols of the dynamic linker")?;
+        match table.get("_r_debug") {
+            Some(symbol) => Self::read(memory, symbol.address(), bitness),
+            None => bail!("The dynamic linker does not export _r_debug"),
+        }
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn bitness(&self) -> Bitness {
+        self.bitness
+    }
+
+    /// Version of the protocol: 1, or 2 for `r_debug_extended` which links the namespaces.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Address of the first `link_map` of the list, the one of the executable in the base
+    /// namespace.
+    pub fn map(&self) -> u64 {
+        self.map
+    }
+
+    /// Address of the function the loader calls before and after changing the list, for
+    /// debuggers to set a breakpoint at.
+    pub fn brk(&self) -> u64 {
+        self.brk
+    }
+
+    pub fn state(&self) -> LinkMapState {
+        self.state
+    }
+
+    /// Whether no change of the list is in progress, so that it can be followed safely.
+    pub fn is_consistent(&self) -> bool {
+        self.state == LinkMapState::Consistent
+    }
+
+    /// Base of the dynamic linker.
+    pub fn ldbase(&self) -> u64 {
+        self.ldbase
+    }
+
+    /// Reads the `r_debug` of the next namespace, e.g. one made by `dlmopen`, if any.
+    pub fn next<M>(&self, memory: &M) -> anyhow::Result<Option<Self>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        match self.next {
+            0 => Ok(None),
+            next => Self::read(memory, next, self.bitness).map(Some),
+        }
+    }
+
+    /// Reads the `r_debug` of this namespace again and of the following ones from `memory`.
+    pub fn namespaces<M>(&self, memory: &M) -> anyhow::Result<Vec<Self>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let mut namespaces = vec![Self::read(memory, self.address, self.bitness)?];
+        while let Some(next) = namespaces[namespaces.len() - 1].next(memory)? {
+            if namespaces
+                .iter()
+                .any(|namespace| namespace.address == next.address)
+            {
+                bail!("The namespaces of r_debug at {:#x} loop", self.address);
+            }
+            namespaces.push(next);
+        }
+
+        Ok(namespaces)
+    }
+
+    /// Follows the `link_map` list from `memory`, in the order the objects were loaded.
+    ///
+    /// The list may change while it is read if the process runs and its state is not
+    /// [`LinkMapState::Consistent`], in which case the error is to be retried.
+    pub fn link_map<M>(&self, memory: &M) -> anyhow::Result<Vec<LinkMapEntry>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        let mut address = self.map;
+        while address != 0 {
+            if !seen.insert(address) || entries.len() == MAX_OBJECTS {
+                bail!("The link map at {:#x} loops", self.map);
+            }
+
+            let entry = LinkMapEntry::read(memory, address, self.bitness)?;
+            address = entry.next;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// An object loaded by the dynamic linker, from its `link_map` structure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkMapEntry {
+    /// Address of the structure in the process
+    address: u64,
+    /// Difference between the addresses in the file and in memory, `l_addr`
+    bias: u64,
+    /// Path the object was loaded from, `l_name`
+    path: PathBuf,
+    /// Address of the dynamic section, `l_ld`
+    dynamic: u64,
+    next: u64,
+    previous: u64,
+}
+
+impl LinkMapEntry {
+    /// Reads the `link_map` structure at `address` in `memory`, the memory of a process of the
+    /// given bitness, along with the path it points to.
+    pub fn read<M>(memory: &M, address: u64, bitness: Bitness) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let bytes = memory
+            .read_bytes(address, 5 * bitness.pointer_size())
+            .with_context(|| format!("Failed to read link_map at {address:#x}"))?;
+        let mut fields = Fields::new(&bytes, bitness);
+        let (bias, name, dynamic) = (fields.word(), fields.word(), fields.word());
+        let (next, previous) = (fields.word(), fields.word());
+
+        let path = match name {
+            0 => PathBuf::new(),
+            name => {
+                let name = memory
+                    .read_cstring(name, PATH_MAX)
+                    .with_context(|| format!("Failed to read the name of link_map {address:#x}"))?;
+                PathBuf::from(OsString::from_vec(name.into_units()))
+            }
+        };
+
+        Ok(LinkMapEntry {
+            address,
+            bias,
+            path,
+            dynamic,
+            next,
+            previous,
+        })
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Load bias of the object, `l_addr`: 0 for an executable that is not position independent.
+    pub fn bias(&self) -> u64 {
+        self.bias
+    }
+
+    /// Path the object was loaded from: empty for the executable, and the name of the vDSO for
+    /// it.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Address of the dynamic section of the object.
+    pub fn dynamic(&self) -> u64 {
+        self.dynamic
+    }
+
+    /// Address of the next `link_map`, 0 for the last one.
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    /// Address of the previous `link_map`, 0 for the first one.
+    pub fn previous(&self) -> u64 {
+        self.previous
+    }
+
+    /// The module of `modules` mapping the object, the one its dynamic section is in. `None` for
+    /// the vDSO, which is not mapped from a file.
+    pub fn module<'a>(&self, modules: &'a [Module]) -> Option<&'a Module> {
+        modules.iter().find(|module| module.contains(self.dynamic))
+    }
+}
+
+/// A change of the objects loaded by the dynamic linker, see [`LoaderWatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoaderEvent {
+    /// An object was added to the list, e.g. by `dlopen`
+    ModuleLoaded(LinkMapEntry),
+    /// An object was removed from the list, e.g. by `dlclose`, as it was last read
+    ModuleUnloaded(LinkMapEntry),
+}
+
+/// Notifications of the objects the dynamic linker of a traced process loads and unloads, e.g.
+/// plugins opened at runtime, from a breakpoint at its rendezvous function, `r_debug.r_brk`.
+///
+/// The loader calls the function before changing its list and once the list is consistent
+/// again. The hits of the breakpoint are passed to [`Self::handle`], which compares the list
+/// with the one of the last consistent hit.
+#[derive(Debug)]
+pub struct LoaderWatch {
+    r_debug: RDebug,
+    breakpoint: BreakpointId,
+    /// Objects of all the namespaces, as of the last consistent hit
+    objects: Vec<LinkMapEntry>,
+}
+
+impl LoaderWatch {
+    /// Reads the objects loaded by the process of the thread of `manager`, which must be
+    /// stopped, and installs the breakpoint at its rendezvous function.
+    pub fn new(manager: &mut BreakpointManager) -> anyhow::Result<Self> {
+        let tid = manager.session().tid();
+        let r_debug = Process::from_pid(tid)?.r_debug()?;
+        let Some(objects) = Self::read_objects(&r_debug, &manager.session().memory())? else {
+            bail!("The dynamic linker of {tid} is changing its objects");
+        };
+        let breakpoint = manager.insert(r_debug.brk())?;
+
+        Ok(LoaderWatch {
+            r_debug,
+            breakpoint,
+            objects,
+        })
+    }
+
+    /// Reads the objects of all the namespaces, or `None` if one of them is being changed.
+    fn read_objects<M>(r_debug: &RDebug, memory: &M) -> anyhow::Result<Option<Vec<LinkMapEntry>>>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let namespaces = r_debug.namespaces(memory)?;
+        if !namespaces.iter().all(RDebug::is_consistent) {
+            return Ok(None);
+        }
+
+        let mut objects = Vec::new();
+        for namespace in &namespaces {
+            objects.extend(namespace.link_map(memory)?);
+        }
+
+        Ok(Some(objects))
+    }
+
+    pub fn r_debug(&self) -> &RDebug {
+        &self.r_debug
+    }
+
+    /// The breakpoint at the rendezvous function, whose hits are to be passed to
+    /// [`Self::handle`].
+    pub fn breakpoint(&self) -> BreakpointId {
+        self.breakpoint
+    }
+
+    /// Objects loaded as of the last consistent hit.
+    pub fn objects(&self) -> &[LinkMapEntry] {
+        &self.objects
+    }
+
+    /// Handles a `hit` of a breakpoint of `manager`, returning the objects loaded and unloaded
+    /// since the last consistent hit, unloaded first.
+    ///
+    /// Nothing is returned for the hits of other breakpoints, nor for the ones before a change.
+    pub fn handle(
+        &mut self,
+        manager: &BreakpointManager,
+        hit: &BreakpointHit,
+    ) -> anyhow::Result<Vec<LoaderEvent>> {
+        if hit.id != self.breakpoint {
+            return Ok(Vec::new());
+        }
+        let Some(objects) = Self::read_objects(&self.r_debug, &manager.session().memory())? else {
+            return Ok(Vec::new());
+        };
+
+        let previous = std::mem::replace(&mut self.objects, objects);
+        let same = |a: &LinkMapEntry, b: &LinkMapEntry| {
+            (a.address, a.bias, a.dynamic, &a.path) == (b.address, b.bias, b.dynamic, &b.path)
+        };
+        let mut events: Vec<LoaderEvent> = previous
+            .iter()
+            .filter(|entry| !self.objects.iter().any(|object| same(entry, object)))
+            .map(|entry| LoaderEvent::ModuleUnloaded(entry.clone()))
+            .collect();
+        events.extend(
+            self.objects
+                .iter()
+                .filter(|object| !previous.iter().any(|entry| same(entry, object)))
+                .map(|object| LoaderEvent::ModuleLoaded(object.clone())),
+        );
+
+        Ok(events)
+    }
+
+    /// Removes the breakpoint from `manager`.
+    pub fn remove(self, manager: &mut BreakpointManager) -> anyhow::Result<()> {
+        manager.remove(self.breakpoint).map(|_| ())
+    }
+}
@@ -0,0 +1,234 @@
+//! This module contains the resolution of addresses to a module and an offset, e.g.
+//! `libfoo.so+0x1a2b3` or `[heap]+0x450`, which stay valid across runs despite ASLR.
+use std::{collections::HashMap, fmt, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::{
+    process::Pid,
+    segment::{DataSegment, Segment, SegmentPermissions, SegmentType, Segments},
+};
+
+/// What the offset of a [`Location`] is relative to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationBase {
+    /// The lowest address the file is mapped at, its BSS included.
+    Module(PathBuf),
+    /// The first special mapping of this type, e.g. `[heap]` or `[anon:name]`.
+    Special(SegmentType),
+    /// The start of an unnamed anonymous mapping, which is not stable across runs.
+    Anonymous(u64),
+}
+
+/// An address as an offset in a module or a mapping, e.g. `libfoo.so+0x1a2b3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub base: LocationBase,
+    pub offset: u64,
+}
+
+/// Pseudo-path of a special mapping, as in `/proc/<pid>/maps`.
+fn pseudo_path(segment_type: &SegmentType) -> String {
+    match segment_type {
+        SegmentType::Stack => "[stack]".to_string(),
+        SegmentType::SharedLibrary => "[vdso]".to_string(),
+        SegmentType::Data(DataSegment::Heap) => "[heap]".to_string(),
+        SegmentType::Anonymous(name) => format!("[anon:{name}]"),
+        SegmentType::SharedAnonymous(name) => format!("[anon_shmem:{name}]"),
+        SegmentType::Vvar => "[vvar]".to_string(),
+        SegmentType::VvarVclock => "[vvar_vclock]".to_string(),
+        SegmentType::Vsyscall => "[vsyscall]".to_string(),
+        SegmentType::Uprobes => "[uprobes]".to_string(),
+        SegmentType::Other(name) => format!("[{name}]"),
+        // Only file-backed mappings have these types, located as modules
+        SegmentType::Code => "[code]".to_string(),
+        SegmentType::Data(DataSegment::Initialized) => "[data]".to_string(),
+        SegmentType::Data(DataSegment::Uninitialized) => "[bss]".to_string(),
+    }
+}
+
+impl fmt::Display for Location {
+    /// Formats the location with the file name of its module, e.g. `libfoo.so+0x1a2b3`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.base {
+            LocationBase::Module(path) => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                write!(f, "{}", name.to_string_lossy())?
+            }
+            LocationBase::Special(segment_type) => write!(f, "{}", pseudo_path(segment_type))?,
+            LocationBase::Anonymous(start) => write!(f, "{start:#x}")?,
+        }
+        write!(f, "+{:#x}", self.offset)
+    }
+}
+
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    /// Parses a location as formatted, e.g. `libfoo.so+0x1a2b3`, `/usr/lib/libfoo.so+0x10` or
+    /// `[heap]+0x450`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, offset) = s
+            .rsplit_once('+')
+            .ok_or_else(|| anyhow!("Missing offset in location: {s}"))?;
+        let offset = offset.trim();
+        let offset = u64::from_str_radix(offset.trim_start_matches("0x"), 16)
+            .with_context(|| format!("Invalid offset in location: {s}"))?;
+
+        let base = base.trim();
+        let base = if base.starts_with('[') {
+            LocationBase::Special(base.parse()?)
+        } else if let Some(start) = base.strip_prefix("0x") {
+            LocationBase::Anonymous(
+                u64::from_str_radix(start, 16)
+                    .with_context(|| format!("Invalid address in location: {s}"))?,
+            )
+        } else if base.is_empty() {
+            return Err(anyhow!("Missing module in location: {s}"));
+        } else {
+            LocationBase::Module(PathBuf::from(base))
+        };
+
+        Ok(Location { base, offset })
+    }
+}
+
+/// An address along with its location and the mapping containing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub address: u64,
+    pub location: Location,
+    pub segment_type: Option<SegmentType>,
+    pub permissions: SegmentPermissions,
+}
+
+impl fmt::Display for Annotation {
+    /// Formats the annotation as e.g. `0x7f12345678 libfoo.so+0x1a2b3 (r-xp)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#x} {} ({})",
+            self.address, self.location, self.permissions
+        )
+    }
+}
+
+/// Resolves addresses to locations and back, using the segments of a process.
+#[derive(Debug, Clone, Default)]
+pub struct Locator {
+    segments: Segments,
+    /// Lowest address each file is mapped at
+    bases: HashMap<PathBuf, u64>,
+}
+
+impl Locator {
+    pub fn new(segments: impl Into<Segments>) -> Self {
+        // The segments may not come from `/proc/<pid>/maps` and miss their BSS
+        let mut segments = segments.into().into_vec();
+        Segment::infer_bss(&mut segments);
+        let segments = Segments::from(segments);
+        let mut bases = HashMap::new();
+        // Segments are sorted, so the first mapping of a file is its base
+        for segment in &segments {
+            if let Some(path) = segment.path() {
+                bases.entry(path.to_path_buf()).or_insert(segment.start());
+            }
+        }
+
+        Locator { segments, bases }
+    }
+
+    /// Reads the segments of the process `pid` from `/proc/<pid>/maps`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Ok(Self::new(Segments::from_pid(pid)?))
+    }
+
+    pub fn segments(&self) -> &Segments {
+        &self.segments
+    }
+
+    /// File mapped by `segment`, or by the mapping preceding its BSS.
+    fn module_of<'a>(&'a self, segment: &'a Segment) -> Option<&'a std::path::Path> {
+        if let Some(path) = segment.path() {
+            return Some(path);
+        }
+        if segment.segment_type() != Some(&SegmentType::Data(DataSegment::Uninitialized)) {
+            return None;
+        }
+        let index = self
+            .segments
+            .as_slice()
+            .partition_point(|other| other.start() < segment.start());
+        self.segments.as_slice()[..index]
+            .iter()
+            .rev()
+            .find_map(|other| other.path())
+    }
+
+    /// Resolves `address` to an offset in its module or mapping.
+    pub fn locate(&self, address: u64) -> Option<Location> {
+        let segment = self.segments.find(address)?;
+        let (base, start) = match self.module_of(segment) {
+            Some(path) => (
+                LocationBase::Module(path.to_path_buf()),
+                *self.bases.get(path)?,
+            ),
+            None => match segment.segment_type() {
+                Some(segment_type) => {
+                    let first = self
+                        .segments
+                        .iter()
+                        .find(|other| other.segment_type() == Some(segment_type))?;
+                    (LocationBase::Special(segment_type.clone()), first.start())
+                }
+                None => (LocationBase::Anonymous(segment.start()), segment.start()),
+            },
+        };
+
+        Some(Location {
+            base,
+            offset: address - start,
+        })
+    }
+
+    /// Resolves `address` to its location, along with the type and permissions of its mapping.
+    pub fn annotate(&self, address: u64) -> Option<Annotation> {
+        let segment = self.segments.find(address)?;
+        Some(Annotation {
+            address,
+            location: self.locate(address)?,
+            segment_type: segment.segment_type().cloned(),
+            permissions: segment.permissions(),
+        })
+    }
+
+    /// Resolves `location` back to an address. Modules are matched by their full path, or by
+    /// their file name when the location only has one.
+    pub fn resolve(&self, location: &Location) -> Option<u64> {
+        let start = match &location.base {
+            LocationBase::Module(path) => match self.bases.get(path) {
+                Some(base) => *base,
+                None if path
+                    .parent()
+                    .is_some_and(|parent| parent.as_os_str().is_empty()) =>
+                {
+                    self.segments
+                        .iter()
+                        .filter_map(|segment| segment.path())
+                        .find(|other| other.file_name() == Some(path.as_os_str()))
+                        .and_then(|other| self.bases.get(other))
+                        .copied()?
+                }
+                None => return None,
+            },
+            LocationBase::Special(segment_type) => self
+                .segments
+                .iter()
+                .find(|segment| segment.segment_type() == Some(segment_type))?
+                .start(),
+            LocationBase::Anonymous(start) => *start,
+        };
+
+        start.checked_add(location.offset)
+    }
+}
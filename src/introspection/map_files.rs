@@ -0,0 +1,102 @@
+//! This module contains the structs and functions to introspect the files backing the memory
+//! mappings of a process.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_map_files.5.html
+use std::{fs, io};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::{
+    process::{LinkTarget, Pid},
+    segment::{Segment, Segments},
+};
+
+/// A link of `/proc/<pid>/map_files`, named after the address range of a file-backed mapping.
+///
+/// Reading the links and opening them requires `CAP_SYS_ADMIN`. The opened file is the exact
+/// object that is mapped, even if it was deleted or lives in another mount namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapFile {
+    pid: Pid,
+    start: u64,
+    end: u64,
+    target: LinkTarget,
+}
+
+impl MapFile {
+    /// Path of the link of the mapping `start..end` of the process `pid`.
+    fn link(pid: Pid, start: u64, end: u64) -> String {
+        format!("/proc/{pid}/map_files/{start:x}-{end:x}")
+    }
+
+    /// Lists the file-backed mappings of the process `pid`, sorted by address.
+    pub fn list(pid: Pid) -> anyhow::Result<Vec<Self>> {
+        let path = format!("/proc/{pid}/map_files");
+        let mut map_files = Vec::new();
+
+        for entry in fs::read_dir(&path).with_context(|| format!("Failed to read {path}"))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let (start, end) = name
+                .split_once('-')
+                .and_then(|(start, end)| {
+                    Some((
+                        u64::from_str_radix(start, 16).ok()?,
+                        u64::from_str_radix(end, 16).ok()?,
+                    ))
+                })
+                .ok_or_else(|| anyhow!("Invalid map_files entry: {name}"))?;
+
+            // The mapping may disappear while listing
+            let target = match fs::read_link(entry.path()) {
+                Ok(target) => target,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {path}/{name}")),
+            };
+
+            map_files.push(MapFile {
+                pid,
+                start,
+                end,
+                target: target.into(),
+            });
+        }
+
+        map_files.sort_by_key(|map_file| map_file.start);
+        Ok(map_files)
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    pub fn target(&self) -> &LinkTarget {
+        &self.target
+    }
+
+    /// Returns the segment describing the same mapping, if any.
+    pub fn segment<'a>(&self, segments: &'a Segments) -> Option<&'a Segment> {
+        segments
+            .find(self.start)
+            .filter(|segment| segment.end() == self.end)
+    }
+
+    /// Opens the file backing the mapping, read-only.
+    pub fn open(&self) -> anyhow::Result<fs::File> {
+        open(self.pid, self.start, self.end)
+    }
+}
+
+/// Opens the file backing the mapping `start..end` of the process `pid`, read-only.
+pub(crate) fn open(pid: Pid, start: u64, end: u64) -> anyhow::Result<fs::File> {
+    let path = MapFile::link(pid, start, end);
+    fs::File::open(&path).with_context(|| format!("Failed to open {path}"))
+}
@@ -0,0 +1,123 @@
+//! This module contains the `/proc/<pid>/mem` backend to access the memory of a process.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_mem.5.html
+use std::{fs, io, os::unix::fs::FileExt};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::{check_alignment, AtomicWriter, MemoryReader, MemoryWriter},
+    audit::audit_write,
+    process::Pid,
+};
+
+/// Memory access with `pread`/`pwrite` on `/proc/<pid>/mem`, at virtual addresses.
+///
+/// Unlike [`crate::introspection::vm::ProcessVm`], writes go through the kernel as a debugger
+/// would, so pages mapped read-only in the target (e.g. code) can be patched. Requires ptrace
+/// access mode `PTRACE_MODE_ATTACH_FSCREDS`.
+#[derive(Debug)]
+pub struct ProcMem {
+    pid: Pid,
+    file: fs::File,
+    writable: bool,
+}
+
+impl ProcMem {
+    /// Opens the memory of the process `pid`, read-only if writing is not permitted.
+    pub fn open(pid: Pid) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/mem");
+
+        let (file, writable) = match fs::OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(file) => (file, true),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => (
+                fs::File::open(&path).with_context(|| format!("Failed to open {path}"))?,
+                false,
+            ),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {path}")),
+        };
+
+        Ok(ProcMem {
+            pid,
+            file,
+            writable,
+        })
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Writes `data` with a single `pwrite`, which the kernel copies as one store for an aligned
+    /// word.
+    fn write_once(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        if !self.writable {
+            bail!("Memory of {} was opened read-only", self.pid);
+        }
+        if !audit_write(self.pid, address, data, "/proc/pid/mem", self) {
+            return Ok(());
+        }
+
+        let written = self.file.write_at(data, address).with_context(|| {
+            format!(
+                "Failed to write {} bytes at {address:#x} in {}",
+                data.len(),
+                self.pid
+            )
+        })?;
+        if written != data.len() {
+            bail!(
+                "Partial write of {written} bytes at {address:#x} in {}",
+                self.pid
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryReader for ProcMem {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        self.file.read_exact_at(buffer, address).with_context(|| {
+            format!(
+                "Failed to read {} bytes at {address:#x} in {}",
+                buffer.len(),
+                self.pid
+            )
+        })
+    }
+}
+
+impl MemoryWriter for ProcMem {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        if !self.writable {
+            bail!("Memory of {} was opened read-only", self.pid);
+        }
+        if !audit_write(self.pid, address, data, "/proc/pid/mem", self) {
+            return Ok(());
+        }
+
+        self.file.write_all_at(data, address).with_context(|| {
+            format!(
+                "Failed to write {} bytes at {address:#x} in {}",
+                data.len(),
+                self.pid
+            )
+        })
+    }
+}
+
+impl AtomicWriter for ProcMem {
+    fn write_atomic_u32(&self, address: u64, value: u32) -> anyhow::Result<()> {
+        check_alignment::<u32>(address)?;
+        self.write_once(address, &value.to_ne_bytes())
+    }
+
+    fn write_atomic_u64(&self, address: u64, value: u64) -> anyhow::Result<()> {
+        check_alignment::<u64>(address)?;
+        self.write_once(address, &value.to_ne_bytes())
+    }
+}
@@ -0,0 +1,350 @@
+//! This module contains the structs and functions to introspect the memory usage of a process.
+//! Based on https://www.kernel.org/doc/html/latest/filesystems/proc.html
+use std::{collections::BTreeMap, fs, io, str::FromStr};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::{
+    process::Pid,
+    segment::{DataSegment, Segment, SegmentDetails, SegmentType},
+};
+
+/// Memory usage totals of a process, as found in `/proc/<pid>/smaps_rollup`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryRollup {
+    details: SegmentDetails,
+}
+
+impl MemoryRollup {
+    /// Reads the memory usage totals of the process `pid`.
+    ///
+    /// Uses `/proc/<pid>/smaps_rollup` (Linux >= 4.14), and falls back to summing
+    /// `/proc/<pid>/smaps` on older kernels.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/smaps_rollup");
+
+        match fs::read_to_string(&path) {
+            Ok(rollup) => rollup.parse(),
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    && fs::metadata(format!("/proc/{pid}")).is_ok() =>
+            {
+                Ok(Self::from_segments(&Segment::get_from_pid_with_details(
+                    pid,
+                )?))
+            }
+            Err(e) => Err(e).with_context(|| format!("Failed to read {path}")),
+        }
+    }
+
+    /// Sums the details of segments read from `/proc/<pid>/smaps`.
+    pub fn from_segments(segments: &[Segment]) -> Self {
+        let mut details = SegmentDetails::default();
+        for segment in segments {
+            if let Some(segment_details) = segment.details() {
+                details.accumulate(segment_details);
+            }
+        }
+
+        MemoryRollup { details }
+    }
+
+    /// All the totals, with the same keys as in smaps.
+    pub fn details(&self) -> &SegmentDetails {
+        &self.details
+    }
+
+    /// Resident set size, in bytes.
+    pub fn rss(&self) -> u64 {
+        self.details.rss()
+    }
+
+    /// Proportional set size, in bytes.
+    pub fn pss(&self) -> u64 {
+        self.details.pss()
+    }
+
+    /// Unique set size: resident memory not shared with any other process, in bytes.
+    pub fn uss(&self) -> u64 {
+        self.details.private_clean() + self.details.private_dirty()
+    }
+
+    /// Proportional set size of anonymous memory, in bytes (Linux >= 5.8).
+    pub fn pss_anon(&self) -> Option<u64> {
+        self.details.get("Pss_Anon")
+    }
+
+    /// Proportional set size of file-backed memory, in bytes (Linux >= 5.8).
+    pub fn pss_file(&self) -> Option<u64> {
+        self.details.get("Pss_File")
+    }
+
+    /// Proportional set size of shared memory, in bytes (Linux >= 5.8).
+    pub fn pss_shmem(&self) -> Option<u64> {
+        self.details.get("Pss_Shmem")
+    }
+
+    /// Memory pushed to swap, in bytes.
+    pub fn swap(&self) -> u64 {
+        self.details.swap()
+    }
+
+    /// Proportional swap share, in bytes.
+    pub fn swap_pss(&self) -> u64 {
+        self.details.swap_pss()
+    }
+
+    /// Memory locked in RAM, in bytes.
+    pub fn locked(&self) -> u64 {
+        self.details.locked()
+    }
+
+    /// Memory deduplicated by KSM, in bytes (Linux >= 6.6).
+    pub fn ksm(&self) -> Option<u64> {
+        self.details.ksm()
+    }
+}
+
+impl FromStr for MemoryRollup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segment = Segment::parse_smaps(s)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Empty smaps_rollup"))?;
+
+        Ok(MemoryRollup {
+            details: segment.details().cloned().unwrap_or_default(),
+        })
+    }
+}
+
+/// Huge page usage of a process, computed from `/proc/<pid>/smaps`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HugePageSummary {
+    /// Anonymous memory backed by transparent huge pages, in bytes
+    pub anon_huge_pages: u64,
+    /// Shared memory mapped with huge pages, in bytes
+    pub shmem_pmd_mapped: u64,
+    /// File-backed memory mapped with huge pages, in bytes
+    pub file_pmd_mapped: u64,
+    /// Memory backed by hugetlbfs, in bytes
+    pub hugetlb: u64,
+    /// Number of segments eligible for transparent huge pages
+    pub thp_eligible_segments: usize,
+    /// Number of segments actually using transparent huge pages
+    pub thp_segments: usize,
+    /// Number of segments backed by hugetlbfs
+    pub hugetlb_segments: usize,
+}
+
+impl HugePageSummary {
+    /// Reads the huge page usage of the process `pid`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Ok(Self::from_segments(&Segment::get_from_pid_with_details(
+            pid,
+        )?))
+    }
+
+    /// Sums the huge page usage of segments read from `/proc/<pid>/smaps`.
+    pub fn from_segments(segments: &[Segment]) -> Self {
+        let mut summary = HugePageSummary::default();
+
+        for details in segments.iter().filter_map(Segment::details) {
+            summary.anon_huge_pages += details.anon_huge_pages();
+            summary.shmem_pmd_mapped += details.shmem_pmd_mapped();
+            summary.file_pmd_mapped += details.file_pmd_mapped();
+            summary.hugetlb += details.hugetlb();
+            summary.thp_eligible_segments += details.thp_eligible() as usize;
+            summary.thp_segments += details.uses_thp() as usize;
+            summary.hugetlb_segments += details.is_hugetlb() as usize;
+        }
+
+        summary
+    }
+
+    /// Memory backed by transparent huge pages, in bytes.
+    pub fn thp(&self) -> u64 {
+        self.anon_huge_pages + self.shmem_pmd_mapped + self.file_pmd_mapped
+    }
+}
+
+/// Kernel samepage merging statistics of a process, as found in `/proc/<pid>/ksm_stat`.
+///
+/// Counts are in pages. Fields missing from older kernels are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KsmStat {
+    /// Pages of the process scanned by KSM, Linux >= 6.1
+    pub rmap_items: u64,
+    /// Pages merged with the shared zero page, Linux >= 6.10
+    pub zero_pages: Option<u64>,
+    /// Pages merged with other pages, Linux >= 6.6
+    pub merging_pages: Option<u64>,
+    /// Memory saved by KSM minus its metadata overhead, in bytes, Linux >= 6.6
+    pub process_profit: Option<i64>,
+    /// KSM was enabled for the whole process with `PR_SET_MEMORY_MERGE`, Linux >= 6.6
+    pub merge_any: Option<bool>,
+    /// Some mappings of the process are mergeable, Linux >= 6.6
+    pub mergeable: Option<bool>,
+}
+
+impl KsmStat {
+    /// Reads the KSM statistics of the process `pid`, `None` if the kernel does not report them.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Option<Self>> {
+        let path = format!("/proc/{pid}/ksm_stat");
+
+        match fs::read_to_string(&path) {
+            Ok(stat) => Ok(Some(stat.parse()?)),
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    && fs::metadata(format!("/proc/{pid}")).is_ok() =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e).with_context(|| format!("Failed to read {path}")),
+        }
+    }
+}
+
+impl FromStr for KsmStat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let get = |key: &str| {
+            s.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?;
+                Some(value.trim_start_matches(':').trim())
+            })
+        };
+        let number = |key: &str| -> anyhow::Result<Option<u64>> {
+            get(key)
+                .map(|value| {
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid {key} in ksm_stat: {value}"))
+                })
+                .transpose()
+        };
+        let flag = |key: &str| get(key).map(|value| value == "yes");
+
+        Ok(KsmStat {
+            rmap_items: number("ksm_rmap_items ")?
+                .ok_or_else(|| anyhow!("Missing ksm_rmap_items in ksm_stat"))?,
+            zero_pages: number("ksm_zero_pages ")?,
+            merging_pages: number("ksm_merging_pages ")?,
+            process_profit: get("ksm_process_profit ")
+                .map(|value| {
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid ksm_process_profit: {value}"))
+                })
+                .transpose()?,
+            merge_any: flag("ksm_merge_any:"),
+            mergeable: flag("ksm_mergeable:"),
+        })
+    }
+}
+
+/// Memory usage of the segments sharing a backing file or name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MappingUsage {
+    /// Path of the backing file, or pseudo-path such as `[heap]` or `[anon:name]`
+    pub name: String,
+    /// Number of segments grouped under this name
+    pub segments: usize,
+    pub rss: u64,
+    pub pss: u64,
+    pub uss: u64,
+    pub swap: u64,
+}
+
+/// Name used to group a segment in a [`MemorySummary`].
+fn mapping_name(segment: &Segment) -> String {
+    if let Some(path) = segment.path() {
+        return path.display().to_string();
+    }
+
+    match segment.segment_type() {
+        Some(SegmentType::Stack) => "[stack]".to_string(),
+        Some(SegmentType::Data(DataSegment::Heap)) => "[heap]".to_string(),
+        Some(SegmentType::SharedLibrary) => "[vdso]".to_string(),
+        Some(SegmentType::Anonymous(name)) => format!("[anon:{name}]"),
+        Some(SegmentType::SharedAnonymous(name)) => format!("[anon_shmem:{name}]"),
+        Some(SegmentType::Vvar) => "[vvar]".to_string(),
+        Some(SegmentType::VvarVclock) => "[vvar_vclock]".to_string(),
+        Some(SegmentType::Vsyscall) => "[vsyscall]".to_string(),
+        Some(SegmentType::Uprobes) => "[uprobes]".to_string(),
+        Some(SegmentType::Other(name)) => format!("[{name}]"),
+        _ => "[anon]".to_string(),
+    }
+}
+
+/// Memory usage report of a process, in the spirit of `smem`, computed from
+/// `/proc/<pid>/smaps`. All amounts are in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySummary {
+    /// Resident set size
+    pub rss: u64,
+    /// Proportional set size
+    pub pss: u64,
+    /// Unique set size, also the private resident memory
+    pub uss: u64,
+    /// Resident memory shared with other processes
+    pub shared: u64,
+    pub swap: u64,
+    /// Usage grouped by backing file or name, largest PSS first
+    pub mappings: Vec<MappingUsage>,
+}
+
+impl MemorySummary {
+    /// Computes the memory summary of the process `pid`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Ok(Self::from_segments(&Segment::get_from_pid_with_details(
+            pid,
+        )?))
+    }
+
+    /// Computes the memory summary of segments read from `/proc/<pid>/smaps`.
+    pub fn from_segments(segments: &[Segment]) -> Self {
+        let mut summary = MemorySummary::default();
+        let mut mappings: BTreeMap<String, MappingUsage> = BTreeMap::new();
+
+        for segment in segments {
+            let Some(details) = segment.details() else {
+                continue;
+            };
+            let uss = details.private_clean() + details.private_dirty();
+
+            summary.rss += details.rss();
+            summary.pss += details.pss();
+            summary.uss += uss;
+            summary.shared += details.shared_clean() + details.shared_dirty();
+            summary.swap += details.swap();
+
+            let name = mapping_name(segment);
+            let mapping = mappings
+                .entry(name.clone())
+                .or_insert_with(|| MappingUsage {
+                    name,
+                    ..Default::default()
+                });
+            mapping.segments += 1;
+            mapping.rss += details.rss();
+            mapping.pss += details.pss();
+            mapping.uss += uss;
+            mapping.swap += details.swap();
+        }
+
+        summary.mappings = mappings.into_values().collect();
+        summary
+            .mappings
+            .sort_by(|a, b| b.pss.cmp(&a.pss).then_with(|| a.name.cmp(&b.name)));
+        summary
+    }
+
+    /// Returns the usage of the mappings backed by `name`, e.g. a library path or `[heap]`.
+    pub fn mapping(&self, name: &str) -> Option<&MappingUsage> {
+        self.mappings.iter().find(|mapping| mapping.name == name)
+    }
+}
@@ -0,0 +1,169 @@
+//! This module contains the structs and functions to introspect the mount namespace of a process.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_mountinfo.5.html
+use std::{
+    ffi::OsString,
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::segment::Device;
+
+/// A mount in the mount namespace of a process, as found in `/proc/<pid>/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// Unique ID of the mount
+    pub mount_id: u32,
+    /// ID of the parent mount, or of itself for the root of the namespace
+    pub parent_id: u32,
+    /// Device of the mounted filesystem
+    pub device: Device,
+    /// Path of the directory of the filesystem forming the root of this mount
+    pub root: PathBuf,
+    /// Path of the mount point, relative to the root directory of the process
+    pub mount_point: PathBuf,
+    /// Per-mount options
+    pub mount_options: Vec<String>,
+    /// Optional fields, e.g. `shared:1` or `master:2`
+    pub optional_fields: Vec<String>,
+    /// Filesystem type, e.g. `ext4` or `fuse.sshfs`
+    pub fs_type: String,
+    /// Filesystem-specific information, e.g. the backing device
+    pub source: Option<String>,
+    /// Per-superblock options
+    pub super_options: Vec<String>,
+}
+
+impl MountInfo {
+    /// Translates a path as seen from this mount point to a path relative to the root of the
+    /// mounted filesystem, or `None` if `path` isn't under this mount point.
+    pub fn to_fs_path(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(&self.mount_point).ok()?;
+        Some(self.root.join(relative))
+    }
+}
+
+/// Decodes the octal escapes (`\040` for a space, ...) used by the kernel in mountinfo paths.
+fn unescape(s: &str) -> OsString {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|octal| u8::from_str_radix(std::str::from_utf8(octal).ok()?, 8).ok());
+
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    OsString::from_vec(decoded)
+}
+
+impl FromStr for MountInfo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed mountinfo line: {s}"))
+        };
+
+        let mount_id = next()?.parse().context("Invalid mount ID")?;
+        let parent_id = next()?.parse().context("Invalid parent mount ID")?;
+        let device = next()?;
+        let (major, minor) = device
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid mount device: {device}"))?;
+        let device = Device::new(
+            major.parse().context("Invalid device major")?,
+            minor.parse().context("Invalid device minor")?,
+        );
+        let root = PathBuf::from(unescape(next()?));
+        let mount_point = PathBuf::from(unescape(next()?));
+        let mount_options = next()?.split(',').map(String::from).collect();
+
+        let mut optional_fields = Vec::new();
+        loop {
+            match next()? {
+                "-" => break,
+                field => optional_fields.push(field.to_string()),
+            }
+        }
+
+        let fs_type = next()?.to_string();
+        let source = next()?;
+        let source = (source != "none").then(|| unescape(source).to_string_lossy().into_owned());
+        let super_options = fields
+            .next()
+            .map(|options| options.split(',').map(String::from).collect())
+            .unwrap_or_default();
+
+        Ok(MountInfo {
+            mount_id,
+            parent_id,
+            device,
+            root,
+            mount_point,
+            mount_options,
+            optional_fields,
+            fs_type,
+            source,
+            super_options,
+        })
+    }
+}
+
+/// The mounts of a mount namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountTable {
+    mounts: Vec<MountInfo>,
+}
+
+impl MountTable {
+    pub fn mounts(&self) -> &[MountInfo] {
+        &self.mounts
+    }
+
+    /// Finds a mount by its ID.
+    pub fn by_id(&self, mount_id: u32) -> Option<&MountInfo> {
+        self.mounts.iter().find(|mount| mount.mount_id == mount_id)
+    }
+
+    /// Finds the mount a path belongs to, i.e. the last mounted one with the longest matching
+    /// mount point.
+    pub fn find(&self, path: &Path) -> Option<&MountInfo> {
+        self.mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.mount_point))
+            .max_by_key(|mount| mount.mount_point.components().count())
+    }
+}
+
+impl FromStr for MountTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mounts = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(MountTable { mounts })
+    }
+}
@@ -0,0 +1,261 @@
+//! This module contains the structs and functions to introspect the sockets of a network namespace.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_net.5.html
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{process::Pid, segment::InodeId};
+
+/// Transport protocol of an internet socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InetProtocol {
+    Tcp,
+    Udp,
+}
+
+/// State of an internet socket, as defined in `include/net/tcp_states.h`.
+/// UDP sockets only use `Established` and `Close`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SocketState {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    NewSynRecv,
+    BoundInactive,
+}
+
+impl TryFrom<u8> for SocketState {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x01 => SocketState::Established,
+            0x02 => SocketState::SynSent,
+            0x03 => SocketState::SynRecv,
+            0x04 => SocketState::FinWait1,
+            0x05 => SocketState::FinWait2,
+            0x06 => SocketState::TimeWait,
+            0x07 => SocketState::Close,
+            0x08 => SocketState::CloseWait,
+            0x09 => SocketState::LastAck,
+            0x0A => SocketState::Listen,
+            0x0B => SocketState::Closing,
+            0x0C => SocketState::NewSynRecv,
+            0x0D => SocketState::BoundInactive,
+            _ => bail!("Unknown socket state: {value:#x}"),
+        })
+    }
+}
+
+/// An IPv4 or IPv6 socket, as found in `/proc/<pid>/net/{tcp,tcp6,udp,udp6}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InetSocket {
+    pub protocol: InetProtocol,
+    pub local: SocketAddr,
+    /// Unspecified address and port 0 if the socket is not connected.
+    pub remote: SocketAddr,
+    pub state: SocketState,
+    /// Effective UID of the creator of the socket.
+    pub uid: u32,
+    pub inode: InodeId,
+}
+
+impl InetSocket {
+    /// Parses a line of a `/proc/<pid>/net/{tcp,tcp6,udp,udp6}` table.
+    pub fn parse(line: &str, protocol: InetProtocol) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            bail!("Malformed socket line: {line}");
+        }
+
+        Ok(InetSocket {
+            protocol,
+            local: parse_address(fields[1])?,
+            remote: parse_address(fields[2])?,
+            state: u8::from_str_radix(fields[3], 16)
+                .context("Invalid socket state")?
+                .try_into()?,
+            uid: fields[7].parse().context("Invalid socket uid")?,
+            inode: fields[9].parse().context("Invalid socket inode")?,
+        })
+    }
+}
+
+/// Parses an `ADDRESS:PORT` pair, where the address is made of native-endian 32-bit hex words.
+fn parse_address(s: &str) -> anyhow::Result<SocketAddr> {
+    let (address, port) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid socket address: {s}"))?;
+    let port = u16::from_str_radix(port, 16).with_context(|| format!("Invalid port: {s}"))?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..address.len()).step_by(8) {
+        let word = address
+            .get(i..i + 8)
+            .and_then(|word| u32::from_str_radix(word, 16).ok())
+            .ok_or_else(|| anyhow!("Invalid socket address: {s}"))?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).unwrap())),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).unwrap())),
+        _ => bail!("Invalid socket address: {s}"),
+    };
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Type of a UNIX domain socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnixSocketType {
+    Stream,
+    Datagram,
+    SeqPacket,
+}
+
+/// State of a UNIX domain socket, as defined in `include/uapi/linux/net.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnixSocketState {
+    Unconnected,
+    Connecting,
+    Connected,
+    Disconnecting,
+}
+
+/// A UNIX domain socket, as found in `/proc/<pid>/net/unix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocket {
+    pub socket_type: UnixSocketType,
+    pub state: UnixSocketState,
+    /// `true` if the socket is listening for connections.
+    pub listening: bool,
+    pub inode: InodeId,
+    /// Bound path, starting with `@` for abstract sockets.
+    pub path: Option<String>,
+}
+
+impl FromStr for UnixSocket {
+    type Err = anyhow::Error;
+
+    /// Parses a line of `/proc/<pid>/net/unix`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The path is the remainder of the line and may contain spaces
+        let mut rest = s.trim_start();
+        let mut fields = Vec::with_capacity(7);
+        for _ in 0..7 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if fields.iter().any(|f| f.is_empty()) {
+            bail!("Malformed unix socket line: {s}");
+        }
+
+        let flags = u32::from_str_radix(fields[3], 16).context("Invalid unix socket flags")?;
+
+        Ok(UnixSocket {
+            socket_type: match fields[4] {
+                "0001" => UnixSocketType::Stream,
+                "0002" => UnixSocketType::Datagram,
+                "0005" => UnixSocketType::SeqPacket,
+                t => bail!("Unknown unix socket type: {t}"),
+            },
+            state: match fields[5] {
+                "01" => UnixSocketState::Unconnected,
+                "02" => UnixSocketState::Connecting,
+                "03" => UnixSocketState::Connected,
+                "04" => UnixSocketState::Disconnecting,
+                st => bail!("Unknown unix socket state: {st}"),
+            },
+            // __SO_ACCEPTCON
+            listening: flags & 0x10000 != 0,
+            inode: fields[6].parse().context("Invalid unix socket inode")?,
+            path: (!rest.is_empty()).then(|| rest.trim_end().to_string()),
+        })
+    }
+}
+
+/// A socket resolved from one of the `/proc/<pid>/net` tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Socket {
+    Inet(InetSocket),
+    Unix(UnixSocket),
+}
+
+/// Sockets of the network namespace of a process, indexed by inode.
+#[derive(Debug, Clone, Default)]
+pub struct NetTables {
+    sockets: HashMap<InodeId, Socket>,
+}
+
+impl NetTables {
+    /// Reads the TCP, UDP and UNIX socket tables seen by the process `pid`.
+    ///
+    /// Tables missing from the kernel (e.g. IPv6 disabled) are skipped.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        let mut sockets = HashMap::new();
+
+        for (file, protocol) in [
+            ("tcp", InetProtocol::Tcp),
+            ("tcp6", InetProtocol::Tcp),
+            ("udp", InetProtocol::Udp),
+            ("udp6", InetProtocol::Udp),
+        ] {
+            let Some(table) = read_table(pid, file)? else {
+                continue;
+            };
+
+            for line in table.lines().skip(1) {
+                let socket = InetSocket::parse(line, protocol)?;
+                // Sockets in TIME_WAIT are not owned by any fd anymore
+                if socket.inode == 0 {
+                    continue;
+                }
+                sockets.insert(socket.inode, Socket::Inet(socket));
+            }
+        }
+
+        if let Some(table) = read_table(pid, "unix")? {
+            for line in table.lines().skip(1) {
+                let socket: UnixSocket = line.parse()?;
+                sockets.insert(socket.inode, Socket::Unix(socket));
+            }
+        }
+
+        Ok(NetTables { sockets })
+    }
+
+    /// Finds the socket with the given inode.
+    pub fn find(&self, inode: InodeId) -> Option<&Socket> {
+        self.sockets.get(&inode)
+    }
+
+    pub fn sockets(&self) -> impl Iterator<Item = &Socket> {
+        self.sockets.values()
+    }
+}
+
+/// Reads `/proc/<pid>/net/<file>`, or `None` if it doesn't exist.
+fn read_table(pid: Pid, file: &str) -> anyhow::Result<Option<String>> {
+    let path = format!("/proc/{pid}/net/{file}");
+
+    match fs::read_to_string(&path) {
+        Ok(table) => Ok(Some(table)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {path}")),
+    }
+}
@@ -0,0 +1,111 @@
+//! This module contains the structs and functions to introspect the namespaces of a process.
+//! Based on https://www.man7.org/linux/man-pages/man7/namespaces.7.html
+use std::{fs, io, os::unix::fs::MetadataExt};
+
+use anyhow::Context;
+
+use crate::introspection::{process::Pid, segment::InodeId};
+
+/// Kind of a Linux namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NsKind {
+    Cgroup,
+    Ipc,
+    Mnt,
+    Net,
+    Pid,
+    Time,
+    User,
+    Uts,
+}
+
+impl NsKind {
+    pub const ALL: [NsKind; 8] = [
+        NsKind::Cgroup,
+        NsKind::Ipc,
+        NsKind::Mnt,
+        NsKind::Net,
+        NsKind::Pid,
+        NsKind::Time,
+        NsKind::User,
+        NsKind::Uts,
+    ];
+
+    /// Name of the namespace link in `/proc/<pid>/ns`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NsKind::Cgroup => "cgroup",
+            NsKind::Ipc => "ipc",
+            NsKind::Mnt => "mnt",
+            NsKind::Net => "net",
+            NsKind::Pid => "pid",
+            NsKind::Time => "time",
+            NsKind::User => "user",
+            NsKind::Uts => "uts",
+        }
+    }
+}
+
+/// A namespace a process is a member of.
+///
+/// Two processes are in the same namespace if both the device and inode numbers match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Namespace {
+    pub kind: NsKind,
+    /// Device of the nsfs filesystem
+    pub device: u64,
+    /// Inode identifying the namespace, as shown in `/proc/<pid>/ns/<kind>` links
+    pub inode: InodeId,
+}
+
+impl Namespace {
+    /// Reads the namespace of the given kind of the process `pid`.
+    ///
+    /// Returns `None` if the kernel doesn't support this kind of namespace.
+    pub fn from_pid(pid: Pid, kind: NsKind) -> anyhow::Result<Option<Self>> {
+        let path = format!("/proc/{pid}/ns/{}", kind.name());
+
+        match fs::metadata(&path) {
+            Ok(metadata) => Ok(Some(Namespace {
+                kind,
+                device: metadata.dev(),
+                inode: metadata.ino(),
+            })),
+            // The process directory exists, so only the namespace kind is missing
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound
+                    && fs::metadata(format!("/proc/{pid}/ns")).is_ok() =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e).with_context(|| format!("Failed to read {path}")),
+        }
+    }
+}
+
+/// All the namespaces a process is a member of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Namespaces {
+    namespaces: Vec<Namespace>,
+}
+
+impl Namespaces {
+    /// Reads the namespaces of the process `pid` from `/proc/<pid>/ns`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        let mut namespaces = Vec::with_capacity(NsKind::ALL.len());
+        for kind in NsKind::ALL {
+            namespaces.extend(Namespace::from_pid(pid, kind)?);
+        }
+
+        Ok(Namespaces { namespaces })
+    }
+
+    /// Returns the namespace of the given kind, `None` if unsupported by the kernel.
+    pub fn get(&self, kind: NsKind) -> Option<&Namespace> {
+        self.namespaces.iter().find(|ns| ns.kind == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Namespace> {
+        self.namespaces.iter()
+    }
+}
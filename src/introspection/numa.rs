@@ -0,0 +1,163 @@
+//! This module contains the structs and functions to introspect the NUMA placement of a process's memory.
+//! Based on https://www.man7.org/linux/man-pages/man7/numa.7.html
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+
+/// Parses a node list such as `0-3,5`.
+fn parse_nodes(s: &str) -> anyhow::Result<Vec<u32>> {
+    let mut nodes = Vec::new();
+
+    for range in s.split(',').filter(|range| !range.is_empty()) {
+        let parse = |n: &str| {
+            n.parse::<u32>()
+                .with_context(|| format!("Invalid NUMA node list: {s}"))
+        };
+        match range.split_once('-') {
+            Some((first, last)) => nodes.extend(parse(first)?..=parse(last)?),
+            None => nodes.push(parse(range)?),
+        }
+    }
+
+    Ok(nodes)
+}
+
+/// Memory policy of a mapping, see [set_mempolicy(2)](https://www.man7.org/linux/man-pages/man2/set_mempolicy.2.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// The process (or system) default policy
+    Default,
+    /// Allocate on the local node of the allocating CPU
+    Local,
+    /// Allocate on the given node first, or nodes with `prefer (many)`, falling back to others
+    Preferred(Vec<u32>),
+    /// Only allocate on the given nodes
+    Bind(Vec<u32>),
+    /// Interleave allocations across the given nodes
+    Interleave(Vec<u32>),
+    /// Weighted interleave across the given nodes, Linux >= 6.9
+    WeightedInterleave(Vec<u32>),
+}
+
+impl FromStr for NumaPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, nodes) = s.split_once(':').unwrap_or((s, ""));
+        // Mode flags, e.g. `bind=static:0-1`
+        let mode = mode.split('=').next().unwrap_or(mode);
+
+        Ok(match mode {
+            "default" => NumaPolicy::Default,
+            "local" => NumaPolicy::Local,
+            "prefer" | "prefer (many)" => NumaPolicy::Preferred(parse_nodes(nodes)?),
+            "bind" => NumaPolicy::Bind(parse_nodes(nodes)?),
+            "interleave" => NumaPolicy::Interleave(parse_nodes(nodes)?),
+            "weighted interleave" => NumaPolicy::WeightedInterleave(parse_nodes(nodes)?),
+            _ => bail!("Unknown NUMA policy: {s}"),
+        })
+    }
+}
+
+/// NUMA placement of a mapping, as found in `/proc/<pid>/numa_maps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumaInfo {
+    pub policy: NumaPolicy,
+    /// Number of pages allocated on each node
+    pub node_pages: BTreeMap<u32, u64>,
+    /// Number of anonymous pages
+    pub anon: u64,
+    /// Number of dirty pages
+    pub dirty: u64,
+    /// Number of pages mapped, if different from `anon` and `dirty`
+    pub mapped: u64,
+    /// Highest number of processes mapping a single page
+    pub mapmax: u64,
+    /// Number of pages in the swap cache
+    pub swapcache: u64,
+    /// Number of pages on the active list
+    pub active: Option<u64>,
+    /// Number of pages under writeback
+    pub writeback: u64,
+    /// Page size of the mapping, in bytes
+    pub kernel_page_size: u64,
+    /// The mapping is backed by hugetlbfs
+    pub huge: bool,
+}
+
+impl NumaInfo {
+    /// Total number of pages allocated, all nodes included.
+    pub fn total_pages(&self) -> u64 {
+        self.node_pages.values().sum()
+    }
+}
+
+/// Parses a line of `/proc/<pid>/numa_maps`, returning the start address of the mapping and its
+/// NUMA placement.
+pub fn parse_numa_line(line: &str) -> anyhow::Result<(u64, NumaInfo)> {
+    let (start, rest) = line
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("Malformed numa_maps line: {line}"))?;
+    let start = u64::from_str_radix(start, 16).context("Invalid numa_maps address")?;
+
+    let mut fields = rest.split_whitespace().peekable();
+    let mut policy = fields
+        .next()
+        .ok_or_else(|| anyhow!("Missing policy in numa_maps line: {line}"))?
+        .to_string();
+    // The policy names containing a space, `weighted interleave` and `prefer (many)`
+    let second_word = match policy.as_str() {
+        "weighted" => true,
+        "prefer" => fields.peek().is_some_and(|next| next.starts_with("(many)")),
+        _ => false,
+    };
+    if second_word {
+        policy.push(' ');
+        policy.push_str(fields.next().unwrap_or_default());
+    }
+
+    let mut info = NumaInfo {
+        policy: policy.parse()?,
+        node_pages: BTreeMap::new(),
+        anon: 0,
+        dirty: 0,
+        mapped: 0,
+        mapmax: 0,
+        swapcache: 0,
+        active: None,
+        writeback: 0,
+        kernel_page_size: 0,
+        huge: false,
+    };
+
+    for field in fields {
+        let Some((key, value)) = field.split_once('=') else {
+            info.huge |= field == "huge";
+            continue;
+        };
+        let number = || {
+            value
+                .parse::<u64>()
+                .with_context(|| format!("Invalid numa_maps field: {field}"))
+        };
+
+        match key {
+            "anon" => info.anon = number()?,
+            "dirty" => info.dirty = number()?,
+            "mapped" => info.mapped = number()?,
+            "mapmax" => info.mapmax = number()?,
+            "swapcache" => info.swapcache = number()?,
+            "active" => info.active = Some(number()?),
+            "writeback" => info.writeback = number()?,
+            "kernelpagesize_kB" => info.kernel_page_size = number()? * 1024,
+            _ => {
+                if let Some(node) = key.strip_prefix('N').and_then(|n| n.parse().ok()) {
+                    info.node_pages.insert(node, number()?);
+                }
+                // `file=<path>`, `heap`, `stack` are already known from maps
+            }
+        }
+    }
+
+    Ok((start, info))
+}
@@ -0,0 +1,383 @@
+//! This module contains the structs and functions to introspect the physical pages backing the
+//! memory of a process.
+//! Based on https://www.kernel.org/doc/html/latest/admin-guide/mm/pagemap.html
+use std::{
+    fs, io,
+    ops::Range,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::fs::FileExt,
+    },
+};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{process::Pid, segment::Segment};
+
+/// Flags of `/proc/kpageflags`, see `include/uapi/linux/kernel-page-flags.h`.
+pub const KPF_LOCKED: u32 = 0;
+pub const KPF_ERROR: u32 = 1;
+pub const KPF_REFERENCED: u32 = 2;
+pub const KPF_UPTODATE: u32 = 3;
+pub const KPF_DIRTY: u32 = 4;
+pub const KPF_LRU: u32 = 5;
+pub const KPF_ACTIVE: u32 = 6;
+pub const KPF_SLAB: u32 = 7;
+pub const KPF_WRITEBACK: u32 = 8;
+pub const KPF_RECLAIM: u32 = 9;
+pub const KPF_BUDDY: u32 = 10;
+pub const KPF_MMAP: u32 = 11;
+pub const KPF_ANON: u32 = 12;
+pub const KPF_SWAPCACHE: u32 = 13;
+pub const KPF_SWAPBACKED: u32 = 14;
+pub const KPF_COMPOUND_HEAD: u32 = 15;
+pub const KPF_COMPOUND_TAIL: u32 = 16;
+pub const KPF_HUGE: u32 = 17;
+pub const KPF_UNEVICTABLE: u32 = 18;
+pub const KPF_HWPOISON: u32 = 19;
+pub const KPF_NOPAGE: u32 = 20;
+pub const KPF_KSM: u32 = 21;
+pub const KPF_THP: u32 = 22;
+pub const KPF_OFFLINE: u32 = 23;
+pub const KPF_ZERO_PAGE: u32 = 24;
+pub const KPF_IDLE: u32 = 25;
+pub const KPF_PGTABLE: u32 = 26;
+
+/// Size of a page of the running system, in bytes.
+pub fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+/// A 64-bit entry of `/proc/<pid>/pagemap`, describing one virtual page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PagemapEntry(u64);
+
+impl PagemapEntry {
+    pub fn from_bits(bits: u64) -> Self {
+        PagemapEntry(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    fn bit(&self, bit: u32) -> bool {
+        self.0 & (1 << bit) != 0
+    }
+
+    /// The page is present in RAM.
+    pub fn present(&self) -> bool {
+        self.bit(63)
+    }
+
+    /// The page is in swap.
+    pub fn swapped(&self) -> bool {
+        self.bit(62)
+    }
+
+    /// The page is file-backed or shared anonymous.
+    pub fn file_or_shared(&self) -> bool {
+        self.bit(61)
+    }
+
+    /// The page is write-protected by userfaultfd, Linux >= 5.13.
+    pub fn uffd_wp(&self) -> bool {
+        self.bit(57)
+    }
+
+    /// The page is mapped exclusively by this process, Linux >= 4.2.
+    pub fn exclusive(&self) -> bool {
+        self.bit(56)
+    }
+
+    /// The page was written to since the last write to `/proc/<pid>/clear_refs`.
+    pub fn soft_dirty(&self) -> bool {
+        self.bit(55)
+    }
+
+    /// Page frame number of a present page.
+    ///
+    /// The kernel reports 0 to readers without `CAP_SYS_ADMIN`, which gives `None` as well.
+    pub fn pfn(&self) -> Option<u64> {
+        let pfn = self.0 & ((1 << 55) - 1);
+        (self.present() && pfn != 0).then_some(pfn)
+    }
+
+    /// Swap type of a swapped page.
+    pub fn swap_type(&self) -> Option<u64> {
+        self.swapped().then_some(self.0 & 0x1f)
+    }
+
+    /// Offset in the swap area of a swapped page.
+    pub fn swap_offset(&self) -> Option<u64> {
+        self.swapped().then_some((self.0 >> 5) & ((1 << 50) - 1))
+    }
+}
+
+/// Flags of a physical page, as found in `/proc/kpageflags`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct KPageFlags(u64);
+
+impl KPageFlags {
+    pub fn from_bits(bits: u64) -> Self {
+        KPageFlags(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if the `KPF_*` flag is set.
+    pub fn has(&self, flag: u32) -> bool {
+        self.0 & (1 << flag) != 0
+    }
+}
+
+/// What to reset when writing to `/proc/<pid>/clear_refs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearRefs {
+    /// 1 : Referenced and accessed bits of all pages
+    All,
+    /// 2 : Referenced and accessed bits of anonymous pages
+    Anonymous,
+    /// 3 : Referenced and accessed bits of file-backed pages
+    File,
+    /// 4 : Soft-dirty bits of all pages, requires `CONFIG_MEM_SOFT_DIRTY`
+    SoftDirty,
+    /// 5 : Peak resident set size (`VmHWM`)
+    PeakRss,
+}
+
+/// Resets the page tracking bits of the process `pid`, see [`ClearRefs`].
+pub fn clear_refs(pid: Pid, kind: ClearRefs) -> anyhow::Result<()> {
+    let value = match kind {
+        ClearRefs::All => "1",
+        ClearRefs::Anonymous => "2",
+        ClearRefs::File => "3",
+        ClearRefs::SoftDirty => "4",
+        ClearRefs::PeakRss => "5",
+    };
+
+    let path = format!("/proc/{pid}/clear_refs");
+    fs::write(&path, value).with_context(|| format!("Failed to write {path}"))
+}
+
+/// `MADV_COLLAPSE`, missing from `libc`.
+const MADV_COLLAPSE: libc::c_int = 25;
+
+/// Advice on the use of memory, given with `madvise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// Frees the pages, private anonymous memory reading as zeros afterwards
+    DontNeed,
+    /// Deactivates the pages, reclaimed first under memory pressure
+    Cold,
+    /// Reclaims the pages, e.g. swapping them out
+    PageOut,
+    /// Reads the pages ahead
+    WillNeed,
+    /// Collapses the pages into transparent huge pages, since Linux 6.1
+    Collapse,
+}
+
+impl Advice {
+    /// The `MADV_*` value of the advice.
+    pub fn to_raw(&self) -> libc::c_int {
+        match self {
+            Advice::DontNeed => libc::MADV_DONTNEED,
+            Advice::Cold => libc::MADV_COLD,
+            Advice::PageOut => libc::MADV_PAGEOUT,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+            Advice::Collapse => MADV_COLLAPSE,
+        }
+    }
+}
+
+/// Gives `advice` on the pages of `range` of the process `pid` with `process_madvise(2)`,
+/// without running code in it unlike [`crate::introspection::remote::remote_madvise`].
+/// Returns the number of bytes advised, which is less than the range when it is not mapped
+/// whole.
+///
+/// The range is extended to whole pages. The kernel only takes [`Advice::Cold`],
+/// [`Advice::PageOut`], [`Advice::WillNeed`] and [`Advice::Collapse`] from other processes, and
+/// requires `CAP_SYS_NICE` along with the permission to ptrace the process.
+pub fn advise(pid: Pid, range: Range<u64>, advice: Advice) -> anyhow::Result<u64> {
+    if advice == Advice::DontNeed {
+        bail!("{advice:?} can only be given by the process itself");
+    }
+    let page_size = page_size();
+    let start = range.start / page_size * page_size;
+    let end = range.end.next_multiple_of(page_size);
+
+    // SAFETY: pidfd_open takes no pointer
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if pidfd < 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("Failed to open a pidfd for {pid}"));
+    }
+    // SAFETY: the descriptor was just opened and is owned by nothing else
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd as RawFd) };
+
+    let iovec = libc::iovec {
+        iov_base: start as *mut libc::c_void,
+        iov_len: (end - start) as usize,
+    };
+    // SAFETY: the iovec is valid for the duration of the call, and only describes memory of
+    // the other process
+    let advised = unsafe {
+        libc::syscall(
+            libc::SYS_process_madvise,
+            pidfd.as_raw_fd(),
+            &iovec as *const libc::iovec,
+            1,
+            advice.to_raw(),
+            0,
+        )
+    };
+    if advised < 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("Failed to advise {start:#x}-{end:#x} in {pid}"));
+    }
+
+    Ok(advised as u64)
+}
+
+/// The pages of a segment written to since the soft-dirty bits were last cleared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirtyPages {
+    pub segment: Segment,
+    /// Addresses of the dirty pages
+    pub pages: Vec<u64>,
+}
+
+/// A virtual page of a process along with what is known about its backing physical page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    /// Virtual address of the page
+    pub address: u64,
+    pub entry: PagemapEntry,
+    /// Flags of the physical page, only available with `CAP_SYS_ADMIN`
+    pub flags: Option<KPageFlags>,
+    /// Number of times the physical page is mapped, only available with `CAP_SYS_ADMIN`
+    pub map_count: Option<u64>,
+}
+
+/// Reads a native-endian `u64` array entry at `index` of `file`.
+fn read_u64(file: &fs::File, index: u64) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    file.read_exact_at(&mut buffer, index * 8)?;
+    Ok(u64::from_ne_bytes(buffer))
+}
+
+/// Reader over `/proc/<pid>/pagemap`.
+#[derive(Debug)]
+pub struct Pagemap {
+    pid: Pid,
+    file: fs::File,
+    page_size: u64,
+}
+
+impl Pagemap {
+    pub fn open(pid: Pid) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/pagemap");
+        let file = fs::File::open(&path).with_context(|| format!("Failed to open {path}"))?;
+
+        Ok(Pagemap {
+            pid,
+            file,
+            page_size: page_size(),
+        })
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn page_size(&self) -> u64 {
+        self.page_size
+    }
+
+    /// Reads the entries of the pages overlapping `range`, one per page.
+    pub fn read(&self, range: Range<u64>) -> anyhow::Result<Vec<PagemapEntry>> {
+        if range.start > range.end {
+            bail!("Invalid range: {:#x}-{:#x}", range.start, range.end);
+        }
+        let first = range.start / self.page_size;
+        let last = range.end.div_ceil(self.page_size);
+
+        // Pages past the end of the user address space (e.g. `[vsyscall]`) have no entry, they
+        // are left zeroed
+        let mut buffer = vec![0; ((last - first) * 8) as usize];
+        let mut read = 0;
+        while read < buffer.len() {
+            match self
+                .file
+                .read_at(&mut buffer[read..], first * 8 + read as u64)
+            {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to read pagemap of {}", self.pid))
+                }
+            }
+        }
+
+        Ok(buffer
+            .chunks_exact(8)
+            .map(|chunk| PagemapEntry(u64::from_ne_bytes(chunk.try_into().unwrap())))
+            .collect())
+    }
+
+    /// Reads the pages overlapping `range`, enriched with `/proc/kpageflags` and
+    /// `/proc/kpagecount` when the physical frame numbers are readable.
+    pub fn pages(&self, range: Range<u64>) -> anyhow::Result<Vec<Page>> {
+        let first = range.start / self.page_size * self.page_size;
+        let entries = self.read(range)?;
+
+        // Both files require CAP_SYS_ADMIN, as do the PFNs they are indexed by
+        let kpageflags = fs::File::open("/proc/kpageflags").ok();
+        let kpagecount = fs::File::open("/proc/kpagecount").ok();
+
+        Ok(entries
+            .into_iter()
+            .zip((first..).step_by(self.page_size as usize))
+            .map(|(entry, address)| {
+                let lookup = |file: &Option<fs::File>| read_u64(file.as_ref()?, entry.pfn()?).ok();
+                Page {
+                    address,
+                    entry,
+                    flags: lookup(&kpageflags).map(KPageFlags),
+                    map_count: lookup(&kpagecount),
+                }
+            })
+            .collect())
+    }
+
+    /// Reports the pages of `segments` written to since the last [`ClearRefs::SoftDirty`].
+    /// Segments without any dirty page are omitted.
+    pub fn soft_dirty_pages(&self, segments: &[Segment]) -> anyhow::Result<Vec<DirtyPages>> {
+        let mut dirty = Vec::new();
+
+        for segment in segments {
+            let pages: Vec<u64> = self
+                .read(segment.start()..segment.end())?
+                .into_iter()
+                .zip((segment.start()..).step_by(self.page_size as usize))
+                .filter(|(entry, _)| entry.soft_dirty())
+                .map(|(_, address)| address)
+                .collect();
+
+            if !pages.is_empty() {
+                dirty.push(DirtyPages {
+                    segment: segment.clone(),
+                    pages,
+                });
+            }
+        }
+
+        Ok(dirty)
+    }
+}
@@ -0,0 +1,252 @@
+//! This module contains the code patches, replacing bytes of a process and restoring the
+//! original ones.
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryWriter},
+    arch::Arch,
+    pod::{bytes_of, Pod},
+    ptrace::{FrozenProcess, PtraceMem},
+};
+
+/// `nop` on aarch64.
+const AARCH64_NOP: [u8; 4] = [0x1f, 0x20, 0x03, 0xd5];
+
+/// Bytes of a process replaced by others, e.g. instructions, which [`Self::revert`] restores.
+///
+/// The original bytes are read when the patch is applied, so that patches overlapping each
+/// other are restored when reverted in the reverse order they were applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    address: u64,
+    replacement: Vec<u8>,
+    /// Bytes replaced, while the patch is applied
+    original: Option<Vec<u8>>,
+}
+
+impl Patch {
+    /// A patch writing `replacement` at `address`.
+    pub fn new(address: u64, replacement: impl Into<Vec<u8>>) -> Self {
+        Patch {
+            address,
+            replacement: replacement.into(),
+            original: None,
+        }
+    }
+
+    /// A patch writing the bytes of `value` at `address`, e.g. a constant of an instruction.
+    pub fn value<T: Pod>(address: u64, value: T) -> Self {
+        Self::new(address, bytes_of(&value))
+    }
+
+    /// A patch replacing `len` bytes at `address` with no-op instructions of `arch`.
+    pub fn nop(arch: Arch, address: u64, len: usize) -> anyhow::Result<Self> {
+        let replacement = match arch {
+            Arch::X86 | Arch::X86_64 => vec![0x90; len],
+            Arch::Aarch64 => {
+                if !address.is_multiple_of(4) || !len.is_multiple_of(4) {
+                    bail!("Misaligned instructions at {address:#x}");
+                }
+                AARCH64_NOP.repeat(len / 4)
+            }
+        };
+
+        Ok(Self::new(address, replacement))
+    }
+
+    /// A patch replacing the instruction at `address` with a jump to `target`: a 5-byte `jmp`
+    /// within 2 GiB on x86, a `b` within 128 MiB on aarch64.
+    pub fn jump(arch: Arch, address: u64, target: u64) -> anyhow::Result<Self> {
+        let replacement = match arch {
+            Arch::X86 | Arch::X86_64 => {
+                let offset = target.wrapping_sub(address.wrapping_add(5)) as i64;
+                let offset = match arch {
+                    // The offset wraps around the 32-bit address space
+                    Arch::X86 => offset as i32,
+                    _ => i32::try_from(offset).with_context(|| {
+                        format!("Jump from {address:#x} to {target:#x} out of range")
+                    })?,
+                };
+                let mut replacement = vec![0xe9];
+                replacement.extend_from_slice(&offset.to_le_bytes());
+                replacement
+            }
+            Arch::Aarch64 => {
+                let offset = target.wrapping_sub(address) as i64;
+                if !address.is_multiple_of(4) || !target.is_multiple_of(4) {
+                    bail!("Misaligned jump from {address:#x} to {target:#x}");
+                }
+                if !(-(1 << 27)..1 << 27).contains(&offset) {
+                    bail!("Jump from {address:#x} to {target:#x} out of range");
+                }
+                let instruction = 0x1400_0000 | ((offset >> 2) as u32 & 0x03ff_ffff);
+                instruction.to_le_bytes().to_vec()
+            }
+        };
+
+        Ok(Self::new(address, replacement))
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Number of bytes replaced.
+    pub fn len(&self) -> usize {
+        self.replacement.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replacement.is_empty()
+    }
+
+    pub fn replacement(&self) -> &[u8] {
+        &self.replacement
+    }
+
+    /// Bytes replaced by the patch, while it is applied.
+    pub fn original(&self) -> Option<&[u8]> {
+        self.original.as_deref()
+    }
+
+    pub fn is_applied(&self) -> bool {
+        self.original.is_some()
+    }
+
+    /// Whether `address` is inside the patch, past its first byte, where a thread resuming
+    /// would execute part of an instruction.
+    fn splits(&self, address: u64) -> bool {
+        address > self.address && address < self.address + self.len() as u64
+    }
+
+    /// Saves the original bytes and writes the replacement through `memory`, which must be able
+    /// to write code, e.g. [`crate::introspection::mem::ProcMem`].
+    pub fn apply<M>(&mut self, memory: &M) -> anyhow::Result<()>
+    where
+        M: MemoryReader + MemoryWriter + ?Sized,
+    {
+        if self.is_applied() {
+            bail!("Patch at {:#x} already applied", self.address);
+        }
+        let original = memory.read_bytes(self.address, self.len())?;
+        memory
+            .write(self.address, &self.replacement)
+            .with_context(|| format!("Failed to patch {:#x}", self.address))?;
+        self.original = Some(original);
+
+        Ok(())
+    }
+
+    /// Writes the original bytes back through `memory`.
+    pub fn revert<M>(&mut self, memory: &M) -> anyhow::Result<()>
+    where
+        M: MemoryWriter + ?Sized,
+    {
+        let Some(original) = &self.original else {
+            bail!("Patch at {:#x} not applied", self.address);
+        };
+        memory
+            .write(self.address, original)
+            .with_context(|| format!("Failed to revert the patch at {:#x}", self.address))?;
+        self.original = None;
+
+        Ok(())
+    }
+}
+
+/// Memory of a frozen process written through ptrace, which can write pages mapped read-only
+/// unlike [`FrozenProcess::memory`].
+pub(crate) fn code_memory(frozen: &FrozenProcess) -> PtraceMem {
+    // A frozen process has at least one thread
+    frozen.sessions()[0].memory()
+}
+
+/// Patches applied and reverted together in a frozen process, so that no thread runs code
+/// patched in part.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchSet {
+    patches: Vec<Patch>,
+}
+
+impl PatchSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `patch`, applied after the previous ones.
+    pub fn with(mut self, patch: Patch) -> Self {
+        self.push(patch);
+        self
+    }
+
+    pub fn push(&mut self, patch: Patch) {
+        self.patches.push(patch);
+    }
+
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    /// Whether any patch is applied.
+    pub fn is_applied(&self) -> bool {
+        self.patches.iter().any(Patch::is_applied)
+    }
+
+    /// Checks that no thread of `frozen` would resume inside a patch.
+    fn check_threads(&self, frozen: &FrozenProcess) -> anyhow::Result<()> {
+        for session in frozen.sessions() {
+            let pc = session.registers()?.pc();
+            if let Some(patch) = self.patches.iter().find(|patch| patch.splits(pc)) {
+                bail!(
+                    "Thread {} is stopped inside the patch at {:#x}",
+                    session.tid(),
+                    patch.address
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies every patch in order while the threads of `frozen` are stopped. If one fails, the
+    /// ones applied are reverted before returning the error.
+    pub fn apply(&mut self, frozen: &FrozenProcess) -> anyhow::Result<()> {
+        if self.is_applied() {
+            bail!("Patch set already applied");
+        }
+        self.check_threads(frozen)?;
+
+        let memory = code_memory(frozen);
+        for i in 0..self.patches.len() {
+            if let Err(error) = self.patches[i].apply(&memory) {
+                for patch in self.patches[..i].iter_mut().rev() {
+                    let _ = patch.revert(&memory);
+                }
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverts every applied patch in the reverse order while the threads of `frozen` are
+    /// stopped, trying all of them and returning the first failure.
+    pub fn revert(&mut self, frozen: &FrozenProcess) -> anyhow::Result<()> {
+        self.check_threads(frozen)?;
+
+        let memory = code_memory(frozen);
+        let mut result = Ok(());
+        for patch in self
+            .patches
+            .iter_mut()
+            .rev()
+            .filter(|patch| patch.is_applied())
+        {
+            if let Err(error) = patch.revert(&memory) {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+}
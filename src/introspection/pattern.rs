@@ -0,0 +1,176 @@
+//! This module contains the masked byte patterns of signature scans, e.g. `48 8B ?? ?? 89 05`.
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use memchr::memmem::Finder;
+
+/// Bytes to search in which some bits are wildcards, e.g. the operands of an instruction that
+/// change between builds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    /// Expected bytes, with their wildcard bits cleared
+    bytes: Vec<u8>,
+    /// Bits compared in each byte
+    masks: Vec<u8>,
+}
+
+impl Pattern {
+    /// Builds a pattern from the expected bytes and the mask of the bits compared in each.
+    pub fn new(bytes: &[u8], masks: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() != masks.len() {
+            bail!(
+                "Pattern has {} bytes but {} masks",
+                bytes.len(),
+                masks.len()
+            );
+        }
+        if bytes.is_empty() {
+            bail!("Pattern is empty");
+        }
+
+        Ok(Pattern {
+            bytes: bytes
+                .iter()
+                .zip(masks)
+                .map(|(byte, mask)| byte & mask)
+                .collect(),
+            masks: masks.to_vec(),
+        })
+    }
+
+    /// Builds a pattern in the code style, from the bytes and a mask string where `x` marks
+    /// a byte compared and `?` a wildcard, e.g. `b"\x48\x8B\x00\x00"` and `"xx??"`.
+    pub fn from_code(bytes: &[u8], mask: &str) -> anyhow::Result<Self> {
+        let masks = mask
+            .chars()
+            .map(|c| match c {
+                'x' | 'X' => Ok(0xff),
+                '?' => Ok(0),
+                _ => Err(anyhow!("Invalid character in pattern mask: {c}")),
+            })
+            .collect::<anyhow::Result<Vec<u8>>>()?;
+        Self::new(bytes, &masks)
+    }
+
+    /// Number of bytes matched.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Expected bytes, with their wildcard bits cleared.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Bits compared in each byte.
+    pub fn masks(&self) -> &[u8] {
+        &self.masks
+    }
+
+    /// Whether `data` starts with bytes matching the pattern.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.len()
+            && data
+                .iter()
+                .zip(self.bytes.iter().zip(&self.masks))
+                .all(|(byte, (expected, mask))| byte & mask == *expected)
+    }
+
+    /// Longest run of bytes compared whole, and its offset in the pattern.
+    fn anchor(&self) -> (usize, &[u8]) {
+        let mut best = (0, 0..0);
+        let mut start = 0;
+        for (index, mask) in self.masks.iter().enumerate() {
+            if *mask != 0xff {
+                start = index + 1;
+            } else if index + 1 - start > best.1.len() {
+                best = (start, start..index + 1);
+            }
+        }
+        (best.0, &self.bytes[best.1])
+    }
+
+    /// Offsets of the matches of the pattern in `haystack`, overlapping ones included.
+    ///
+    /// Candidates are found by searching the longest run of exact bytes of the pattern with
+    /// `memchr`, so patterns with a few exact bytes in a row are found much faster.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let (anchor_offset, anchor) = self.anchor();
+        let finder = Finder::new(anchor);
+        let count = (haystack.len() + 1).saturating_sub(self.len());
+        let mut start = 0;
+
+        std::iter::from_fn(move || {
+            while start < count {
+                let offset = if anchor.is_empty() {
+                    start
+                } else {
+                    let end = (count + anchor_offset + anchor.len() - 1).min(haystack.len());
+                    let found = finder.find(&haystack[start + anchor_offset..end])?;
+                    start + found
+                };
+                start = offset + 1;
+                if self.matches(&haystack[offset..]) {
+                    return Some(offset);
+                }
+            }
+            None
+        })
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    /// Parses a pattern in the IDA or Cheat Engine syntax: bytes in hexadecimal separated by
+    /// spaces, with `?`, `??` or `**` for a wildcard byte and `?` for a wildcard nibble, e.g.
+    /// `48 8B ?? ?? 89 05` or `E8 ? ? ? ? 4?`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = Vec::new();
+        let mut masks = Vec::new();
+        for token in s.split_whitespace() {
+            let (byte, mask) = match token {
+                "?" | "??" | "*" | "**" => (0, 0),
+                _ if token.len() == 2 => {
+                    let nibble = |c: char| match c {
+                        '?' | '*' => Some((0, 0)),
+                        _ => Some((c.to_digit(16)? as u8, 0xf)),
+                    };
+                    let mut chars = token.chars();
+                    let (Some((high, high_mask)), Some((low, low_mask))) =
+                        (chars.next().and_then(nibble), chars.next().and_then(nibble))
+                    else {
+                        bail!("Invalid byte in pattern: {token}");
+                    };
+                    (high << 4 | low, high_mask << 4 | low_mask)
+                }
+                _ => bail!("Invalid byte in pattern: {token}"),
+            };
+            bytes.push(byte);
+            masks.push(mask);
+        }
+
+        Self::new(&bytes, &masks)
+    }
+}
+
+impl fmt::Display for Pattern {
+    /// Formats the pattern in the IDA syntax, e.g. `48 8B ?? ?? 89 05`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (byte, mask)) in self.bytes.iter().zip(&self.masks).enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            let nibble = |shift: u8| match (mask >> shift) & 0xf {
+                0xf => format!("{:X}", (byte >> shift) & 0xf),
+                _ => "?".to_string(),
+            };
+            write!(f, "{}{}", nibble(4), nibble(0))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+//! This module contains the marker trait for the types that can be copied to and from the memory
+//! of another process as raw bytes.
+use std::mem::{size_of, MaybeUninit};
+
+/// Plain old data: a type for which any sequence of `size_of::<T>()` bytes is a valid value.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` or `#[repr(transparent)]` (or primitives), contain no
+/// padding bytes, and have no invalid bit patterns (no `bool`, `char`, enums, references or
+/// non-nullable pointers).
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Views a value as its raw bytes.
+pub fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    // SAFETY: a Pod has no padding, all its bytes are initialized
+    unsafe { std::slice::from_raw_parts((value as *const T).cast(), size_of::<T>()) }
+}
+
+/// Views a slice of values as its raw bytes.
+pub fn bytes_of_slice<T: Pod>(values: &[T]) -> &[u8] {
+    // SAFETY: a Pod has no padding, all its bytes are initialized
+    unsafe { std::slice::from_raw_parts(values.as_ptr().cast(), std::mem::size_of_val(values)) }
+}
+
+/// Views a slice of values as its raw bytes, to overwrite them.
+pub fn bytes_of_slice_mut<T: Pod>(values: &mut [T]) -> &mut [u8] {
+    // SAFETY: any bytes written are a valid Pod
+    unsafe {
+        std::slice::from_raw_parts_mut(values.as_mut_ptr().cast(), std::mem::size_of_val(values))
+    }
+}
+
+/// Views possibly uninitialized values as their possibly uninitialized bytes, to read into them.
+pub fn uninit_bytes_of_mut<T: Pod>(values: &mut [MaybeUninit<T>]) -> &mut [MaybeUninit<u8>] {
+    // SAFETY: any bytes are valid MaybeUninit<u8>
+    unsafe {
+        std::slice::from_raw_parts_mut(values.as_mut_ptr().cast(), std::mem::size_of_val(values))
+    }
+}
+
+/// Builds a value from `size_of::<T>()` raw bytes filled by `fill`.
+pub(crate) fn from_bytes_with<T: Pod>(
+    fill: impl FnOnce(&mut [u8]) -> anyhow::Result<()>,
+) -> anyhow::Result<T> {
+    let mut value = MaybeUninit::<T>::zeroed();
+    // SAFETY: the zeroed storage is initialized and any bytes make a valid Pod
+    let bytes =
+        unsafe { std::slice::from_raw_parts_mut(value.as_mut_ptr().cast(), size_of::<T>()) };
+    fill(bytes)?;
+    // SAFETY: see above
+    Ok(unsafe { value.assume_init() })
+}
@@ -0,0 +1,394 @@
+//! This module contains the pointer maps of a process, indexing the pointers found in its memory
+//! by the address they point to.
+use std::{collections::HashSet, fmt, ops::Range, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+use crate::introspection::{
+    access::MemoryReader,
+    arch::Bitness,
+    location::{Location, LocationBase, Locator},
+};
+
+/// Default number of pointers followed by the paths of a [`PointerSearch`].
+const DEFAULT_MAX_DEPTH: usize = 4;
+
+/// Default largest offset added to a pointer by the paths of a [`PointerSearch`].
+const DEFAULT_MAX_OFFSET: u64 = 0x800;
+
+/// Default number of paths returned by a [`PointerSearch`].
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+/// A pointer stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pointer {
+    /// Address of the pointer
+    pub location: u64,
+    /// Address pointed to
+    pub target: u64,
+}
+
+/// Reverse index of the pointers of a process, from the address they point to to their
+/// location, built by [`crate::introspection::scan::Scanner::pointer_map`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerMap {
+    bitness: Bitness,
+    /// Pointers sorted by target, then by location
+    pointers: Vec<Pointer>,
+}
+
+impl PointerMap {
+    pub(crate) fn new(bitness: Bitness, mut pointers: Vec<Pointer>) -> Self {
+        pointers.sort_unstable_by_key(|pointer| (pointer.target, pointer.location));
+        PointerMap { bitness, pointers }
+    }
+
+    /// Bitness of the process, giving the size of its pointers.
+    pub fn bitness(&self) -> Bitness {
+        self.bitness
+    }
+
+    pub fn len(&self) -> usize {
+        self.pointers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pointers.is_empty()
+    }
+
+    /// Pointers in increasing target order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Pointer> {
+        self.pointers.iter()
+    }
+
+    /// Pointers to an address in `targets`, in increasing target order, in O(log n).
+    pub fn pointers_into(&self, targets: Range<u64>) -> &[Pointer] {
+        let first = self
+            .pointers
+            .partition_point(|pointer| pointer.target < targets.start);
+        let last = self
+            .pointers
+            .partition_point(|pointer| pointer.target < targets.end);
+        &self.pointers[first..last.max(first)]
+    }
+
+    /// Locations of the pointers to `target`, in increasing order.
+    pub fn pointers_to(&self, target: u64) -> impl Iterator<Item = u64> + '_ {
+        self.pointers_into(target..target.saturating_add(1))
+            .iter()
+            .map(|pointer| pointer.location)
+    }
+}
+
+/// A chain of pointers from a static address to a dynamic one: the pointer at `base` is read and
+/// the first offset added to it, then the pointer at this address is read, and so on, e.g.
+/// `libgame.so+0x12a0 -> 0x18 -> 0x40` for `*(*(libgame.so+0x12a0) + 0x18) + 0x40`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerPath {
+    pub base: Location,
+    pub offsets: Vec<i64>,
+}
+
+/// Why a [`PointerPath`] could not be followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// The pointer read is null
+    Null,
+    /// The address is not in a segment of the process
+    Unmapped,
+    /// The pointer at the address could not be read
+    Unreadable,
+}
+
+/// Error of [`PointerPath::resolve`], telling where the path broke. It can be recovered from the
+/// returned error with [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrokenPointerPath {
+    /// Number of pointers followed before the path broke
+    pub hop: usize,
+    /// Address of the pointer that could not be followed, or the address reached when unmapped
+    pub address: u64,
+    pub reason: BreakReason,
+}
+
+impl fmt::Display for BrokenPointerPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            BreakReason::Null => "null pointer",
+            BreakReason::Unmapped => "unmapped address",
+            BreakReason::Unreadable => "unreadable pointer",
+        };
+        write!(
+            f,
+            "Pointer path broken at hop {}: {reason} at {:#x}",
+            self.hop, self.address
+        )
+    }
+}
+
+impl std::error::Error for BrokenPointerPath {}
+
+impl PointerPath {
+    /// Follows the path in the memory read by `reader`, the base being resolved by `locator`.
+    ///
+    /// Each address reached, the last one included, must be in a segment of `locator`, and no
+    /// pointer read may be null, otherwise a [`BrokenPointerPath`] is returned.
+    pub fn resolve<R: MemoryReader + ?Sized>(
+        &self,
+        reader: &R,
+        locator: &Locator,
+        bitness: Bitness,
+    ) -> anyhow::Result<u64> {
+        let mut address = locator
+            .resolve(&self.base)
+            .ok_or_else(|| anyhow!("Base {} of the pointer path is not mapped", self.base))?;
+        let broken = |hop, address, reason| BrokenPointerPath {
+            hop,
+            address,
+            reason,
+        };
+
+        let mut buffer = [0; 8];
+        let buffer = &mut buffer[..bitness.pointer_size()];
+        for (hop, offset) in self.offsets.iter().enumerate() {
+            if locator.segments().find(address).is_none() {
+                return Err(broken(hop, address, BreakReason::Unmapped).into());
+            }
+            if reader.read(address, buffer).is_err() {
+                return Err(broken(hop, address, BreakReason::Unreadable).into());
+            }
+            let pointer = bitness.pointer_from_bytes(buffer)?;
+            if pointer == 0 {
+                return Err(broken(hop, address, BreakReason::Null).into());
+            }
+            address = pointer.wrapping_add_signed(*offset);
+        }
+        if locator.segments().find(address).is_none() {
+            return Err(broken(self.offsets.len(), address, BreakReason::Unmapped).into());
+        }
+
+        Ok(address)
+    }
+}
+
+impl fmt::Display for PointerPath {
+    /// Formats the path as e.g. `libgame.so+0x12a0 -> 0x18 -> -0x8`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.base)?;
+        for offset in &self.offsets {
+            match offset {
+                0.. => write!(f, " -> {offset:#x}")?,
+                _ => write!(f, " -> -{:#x}", offset.unsigned_abs())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PointerPath {
+    type Err = anyhow::Error;
+
+    /// Parses a path as formatted, e.g. `libgame.so+0x12A0 -> 0x18 -> 0x40 -> 0x8`. Offsets are
+    /// in hexadecimal, with or without `0x`, and may be negative.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split("->");
+        let base = parts.next().unwrap_or_default().trim();
+        let base = match base.contains('+') {
+            true => base.parse()?,
+            false if !base.is_empty() => format!("{base}+0").parse()?,
+            false => bail!("Missing base in pointer path: {s}"),
+        };
+        let offsets = parts
+            .map(|offset| {
+                let offset = offset.trim();
+                let (negative, digits) = match offset.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, offset),
+                };
+                let digits = digits.trim_start_matches("0x").trim_start_matches("0X");
+                let value = u64::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(|value| i64::try_from(value).ok())
+                    .ok_or_else(|| anyhow!("Invalid offset in pointer path: {offset}"))?;
+                Ok(if negative { -value } else { value })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(PointerPath { base, offsets })
+    }
+}
+
+/// Expected result of the pointer paths in a state of the process, e.g. after a restart, to
+/// rank them with [`rank_paths`].
+pub struct PathCheck<'a> {
+    pub reader: &'a dyn MemoryReader,
+    pub locator: &'a Locator,
+    pub bitness: Bitness,
+    /// Address the paths should lead to
+    pub target: u64,
+}
+
+impl std::fmt::Debug for PathCheck<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathCheck")
+            .field("bitness", &self.bitness)
+            .field("target", &self.target)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A pointer path and the number of checks it passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankedPath {
+    pub path: PointerPath,
+    /// Number of checks in which the path led to the target
+    pub matches: usize,
+}
+
+/// Ranks `paths` by the number of `checks` they pass, then by length and total offset, so the
+/// paths most likely to survive a restart come first.
+pub fn rank_paths(paths: Vec<PointerPath>, checks: &[PathCheck]) -> Vec<RankedPath> {
+    let mut ranked: Vec<RankedPath> = paths
+        .into_iter()
+        .map(|path| {
+            let matches = checks
+                .iter()
+                .filter(|check| {
+                    path.resolve(check.reader, check.locator, check.bitness)
+                        .is_ok_and(|address| address == check.target)
+                })
+                .count();
+            RankedPath { path, matches }
+        })
+        .collect();
+    ranked.sort_by_key(|ranked| {
+        let offsets: u64 = ranked
+            .path
+            .offsets
+            .iter()
+            .map(|offset| offset.unsigned_abs())
+            .sum();
+        (
+            std::cmp::Reverse(ranked.matches),
+            ranked.path.offsets.len(),
+            offsets,
+        )
+    });
+
+    ranked
+}
+
+/// Address reached while walking the pointer map back from the target.
+#[derive(Debug)]
+struct Node {
+    address: u64,
+    /// Offset added to the pointer stored at the address, and the node it leads to
+    next: Option<(i64, usize)>,
+}
+
+/// Search of the pointer paths from the modules of a process to an address, walking a
+/// [`PointerMap`] back from the address.
+///
+/// Paths start in the mappings of files, their BSS included, whose address is stable across
+/// runs once relative to the module. Each intermediate address is only reached through its
+/// shortest path to the target.
+#[derive(Debug, Clone)]
+pub struct PointerSearch<'a> {
+    map: &'a PointerMap,
+    locator: &'a Locator,
+    max_depth: usize,
+    max_offset: u64,
+    max_results: usize,
+}
+
+impl<'a> PointerSearch<'a> {
+    /// Searches paths of up to 4 pointers with offsets up to `0x800` in `map`, the modules
+    /// being found by `locator`.
+    pub fn new(map: &'a PointerMap, locator: &'a Locator) -> Self {
+        PointerSearch {
+            map,
+            locator,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_offset: DEFAULT_MAX_OFFSET,
+            max_results: DEFAULT_MAX_RESULTS,
+        }
+    }
+
+    /// Sets the largest number of pointers followed by a path.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the largest offset added to a pointer, e.g. the size of the structures pointed to.
+    pub fn with_max_offset(mut self, max_offset: u64) -> Self {
+        self.max_offset = max_offset;
+        self
+    }
+
+    /// Stops the search once `max_results` paths are found.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /// Finds the paths to `target`, shortest first.
+    pub fn find(&self, target: u64) -> Vec<PointerPath> {
+        let mut nodes = vec![Node {
+            address: target,
+            next: None,
+        }];
+        let mut visited = HashSet::from([target]);
+        let mut frontier = vec![0];
+        let mut paths = Vec::new();
+
+        for _ in 0..self.max_depth {
+            let mut next_frontier = Vec::new();
+            for index in frontier {
+                let address = nodes[index].address;
+                let targets = address.saturating_sub(self.max_offset)..address.saturating_add(1);
+                for pointer in self.map.pointers_into(targets) {
+                    if !visited.insert(pointer.location) {
+                        continue;
+                    }
+                    let offset = (address - pointer.target) as i64;
+                    nodes.push(Node {
+                        address: pointer.location,
+                        next: Some((offset, index)),
+                    });
+
+                    match self.locator.locate(pointer.location) {
+                        Some(
+                            base @ Location {
+                                base: LocationBase::Module(_),
+                                ..
+                            },
+                        ) => {
+                            paths.push(PointerPath {
+                                base,
+                                offsets: Self::offsets(&nodes, nodes.len() - 1),
+                            });
+                            if paths.len() >= self.max_results {
+                                return paths;
+                            }
+                        }
+                        _ => next_frontier.push(nodes.len() - 1),
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+
+        paths
+    }
+
+    /// Offsets of the path from the node `index` to the target.
+    fn offsets(nodes: &[Node], mut index: usize) -> Vec<i64> {
+        let mut offsets = Vec::new();
+        while let Some((offset, next)) = nodes[index].next {
+            offsets.push(offset);
+            index = next;
+        }
+        offsets
+    }
+}
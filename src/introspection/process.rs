@@ -1,10 +1,56 @@
 /// This module contains the structs and functions to introspect a process.
 /// Based on https://www.man7.org/linux/man-pages/man5/proc.5.html
-use crate::introspection::segment::Segment;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs, io,
+    ops::Range,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    arch::{Arch, Bitness},
+    auxv::AuxVec,
+    capabilities::ProcessCapabilities,
+    elf::Module,
+    fallback::{FallbackPolicy, FallbackWriter},
+    fd::ProcessFd,
+    freeze::Freezer,
+    handle::ProcessHandle,
+    idle::IdlePageTracker,
+    idmap::IdMap,
+    kallsyms::Kallsyms,
+    layout::{Layout, StructAnalyzer},
+    limits::ProcessLimits,
+    linkmap::{LinkMapEntry, RDebug},
+    location::Locator,
+    map_files::MapFile,
+    memory::{HugePageSummary, KsmStat, MemoryRollup, MemorySummary},
+    mountinfo::MountTable,
+    net::{NetTables, Socket},
+    ns::{Namespace, Namespaces, NsKind},
+    pagemap::{self, Advice, ClearRefs, DirtyPages, Pagemap},
+    pointer::PointerMap,
+    ptrace::{FrozenProcess, PtraceSession},
+    resolve::SymbolResolver,
+    scan::Scanner,
+    sched::{SchedInfo, SchedStat},
+    seccomp::SeccompMode,
+    segment::{Segment, Segments},
+    stack::{parse_kernel_stack, KernelStackFrame},
+    syscall::SyscallState,
+    thread::Thread,
+    vm::ProcessVm,
+};
 
 pub type Pid = u32; // maximum value: 2^22
 
 /// Represents a process state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProcessState {
     /// R : Running
     Running,
@@ -22,9 +68,64 @@ pub enum ProcessState {
     Dead,
     /// I : Idle
     Idle,
+    /// K : Wakekill (Linux 2.6.33 to 3.13 only)
+    Wakekill,
+    /// W : Waking (Linux 2.6.33 to 3.13 only)
+    Waking,
+    /// P : Parked (Linux 3.9 to 3.13 only)
+    Parked,
+}
+
+impl TryFrom<char> for ProcessState {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'R' => Ok(ProcessState::Running),
+            'D' => Ok(ProcessState::UninterruptibleSleep),
+            'S' => Ok(ProcessState::InterruptibleSleep),
+            'T' => Ok(ProcessState::Stopped),
+            'Z' => Ok(ProcessState::Zombie),
+            't' => Ok(ProcessState::Tracing),
+            'X' | 'x' => Ok(ProcessState::Dead),
+            'I' => Ok(ProcessState::Idle),
+            'K' => Ok(ProcessState::Wakekill),
+            'W' => Ok(ProcessState::Waking),
+            'P' => Ok(ProcessState::Parked),
+            _ => Err(anyhow!("Unknown process state: {c}")),
+        }
+    }
+}
+
+/// Target of one of the `exe`, `cwd` or `root` links of a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkTarget {
+    /// Path of the target, without the ` (deleted)` suffix
+    pub path: PathBuf,
+    /// The target has been unlinked or replaced on disk since it was opened.
+    pub deleted: bool,
+}
+
+impl From<PathBuf> for LinkTarget {
+    fn from(path: PathBuf) -> Self {
+        const DELETED: &[u8] = b" (deleted)";
+
+        let bytes = path.as_os_str().as_bytes();
+        match bytes.strip_suffix(DELETED) {
+            Some(stripped) => LinkTarget {
+                path: PathBuf::from(OsString::from_vec(stripped.to_vec())),
+                deleted: true,
+            },
+            None => LinkTarget {
+                path,
+                deleted: false,
+            },
+        }
+    }
 }
 
 /// Full status information about the process.
+#[derive(Debug)]
 pub struct Process {
     /// The process ID
     process_id: Pid,
@@ -41,7 +142,8 @@ pub struct Process {
     /// The controlling terminal of the process.
     tty_nr: u32,
     /// The ID of the foreground process group of the controlling terminal of the process.
-    tpgid: u32,
+    /// -1 if the process has no controlling terminal.
+    tpgid: i32,
     /// The kernel flags word of the process.
     flags: u32,
     /// The number of minor faults the process has made which have not required loading a memory page from disk.
@@ -66,7 +168,7 @@ pub struct Process {
     nice: i8,
     /// Number of threads in this process.
     /// Option because Process can be a thread.
-    num_threads: Option<i8>,
+    num_threads: Option<i64>,
     /// Obsolete
     itrealvalue: u64,
     /// The time the process started after system boot, measured in clock ticks.
@@ -136,8 +238,860 @@ pub struct Process {
     exit_code: u32,
 
     // additional custom fields
-    /// Threads in this process, threads in Linux are very similar to Processes so we use the same struct.
-    threads: Option<Vec<Process>>,
+    /// Threads in this process, `None` when parsed from a `stat` line alone.
+    threads: Option<Vec<Thread>>,
     /// Segments in the process's virtual address space.
-    segments: Vec<Box<Segment>>,
+    segments: Segments,
+}
+
+impl Process {
+    /// Reads `/proc/<pid>/stat`, `/proc/<pid>/task` and `/proc/<pid>/maps` and builds the
+    /// corresponding process.
+    ///
+    /// If we are not allowed to read the memory mappings of the process, its segments are left
+    /// empty.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/stat");
+        let stat = fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+
+        let mut process: Process = stat.parse()?;
+        process.refresh_threads()?;
+        match process.refresh_segments() {
+            Ok(()) => {}
+            Err(e)
+                if e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(process)
+    }
+
+    /// Reads the threads of the process again from `/proc/<pid>/task`.
+    pub fn refresh_threads(&mut self) -> anyhow::Result<()> {
+        self.threads = Some(Thread::list(self.process_id)?);
+
+        Ok(())
+    }
+
+    /// Reads the segments of the process again from `/proc/<pid>/maps`.
+    pub fn refresh_segments(&mut self) -> anyhow::Result<()> {
+        self.segments = Segments::from_pid(self.process_id)?;
+
+        Ok(())
+    }
+
+    /// Reads the segments of the process along with their memory usage details from
+    /// `/proc/<pid>/smaps`.
+    pub fn segments_with_details(&self) -> anyhow::Result<Vec<Segment>> {
+        Segment::get_from_pid_with_details(self.process_id)
+    }
+
+    /// Returns the segments of the process having memory in swap, most swapped first.
+    pub fn swapped_segments(&self) -> anyhow::Result<Vec<Segment>> {
+        let mut segments: Vec<Segment> = self
+            .segments_with_details()?
+            .into_iter()
+            .filter(|segment| segment.swap_bytes().unwrap_or(0) > 0)
+            .collect();
+
+        segments.sort_by_key(|segment| std::cmp::Reverse(segment.swap_bytes()));
+        Ok(segments)
+    }
+
+    /// Returns the segments of the process locked in RAM, either whole with mlock(2) or partly
+    /// (e.g. pages pinned by the kernel).
+    pub fn locked_segments(&self) -> anyhow::Result<Vec<Segment>> {
+        Ok(self
+            .segments_with_details()?
+            .into_iter()
+            .filter(|segment| {
+                segment
+                    .details()
+                    .is_some_and(|details| details.is_locked() || details.locked() > 0)
+            })
+            .collect())
+    }
+
+    /// Total memory of the process locked in RAM, in bytes.
+    pub fn locked_bytes(&self) -> anyhow::Result<u64> {
+        Ok(self.memory_rollup()?.locked())
+    }
+
+    /// Reads the segments of the process along with their NUMA placement from
+    /// `/proc/<pid>/numa_maps`.
+    pub fn segments_with_numa(&self) -> anyhow::Result<Vec<Segment>> {
+        Segment::get_from_pid_with_numa(self.process_id)
+    }
+
+    /// Returns the default backend to access the memory of the process, see [`ProcessVm`].
+    pub fn memory(&self) -> ProcessVm {
+        ProcessVm::new(self.process_id)
+    }
+
+    /// Returns a backend falling back to the more intrusive ones when writes are refused, see
+    /// [`FallbackWriter`].
+    pub fn fallback_writer(&self, policy: FallbackPolicy) -> FallbackWriter {
+        FallbackWriter::new(self.process_id, policy)
+    }
+
+    /// Returns a scanner over the readable segments of the process, as of the last refresh of
+    /// the segments.
+    pub fn scanner(&self) -> Scanner<ProcessVm> {
+        Scanner::new(self.memory(), self.segments())
+    }
+
+    /// Returns a locator resolving addresses to their module and offset, as of the last refresh
+    /// of the segments.
+    pub fn locator(&self) -> Locator {
+        Locator::new(self.segments().to_vec())
+    }
+
+    /// Groups the file-backed segments of the process by module, as of the last refresh of the
+    /// segments, with the ELF headers of each read from memory to compute its slide. Modules
+    /// whose headers are not readable are kept without them. See [`Module::group`].
+    pub fn modules(&self) -> Vec<Module> {
+        let memory = self.memory();
+        let mut modules = Module::group(self.segments());
+        for module in &mut modules {
+            let _ = module.read_headers(&memory);
+        }
+
+        modules
+    }
+
+    /// Address of the entry point of the executable of the process, relocated, from its
+    /// auxiliary vector.
+    pub fn entry_point(&self) -> anyhow::Result<u64> {
+        self.auxv()?.entry().ok_or_else(|| {
+            anyhow!(
+                "No entry point in the auxiliary vector of {}",
+                self.process_id
+            )
+        })
+    }
+
+    /// The module of the executable of the process, the one containing its entry point.
+    pub fn main_module(&self) -> anyhow::Result<Module> {
+        let entry = self.entry_point()?;
+        self.modules()
+            .into_iter()
+            .find(|module| module.contains(entry))
+            .ok_or_else(|| anyhow!("No module contains the entry point {entry:#x}"))
+    }
+
+    /// Reads the `r_debug` structure of the dynamic linker of the process, see [`RDebug::find`].
+    pub fn r_debug(&self) -> anyhow::Result<RDebug> {
+        let executable = self.main_module()?;
+        RDebug::find(&self.memory(), executable.base(), self.auxv()?.base())
+    }
+
+    /// Lists the objects loaded by the dynamic linker of the process, in all its namespaces,
+    /// from its `link_map` lists.
+    pub fn link_map(&self) -> anyhow::Result<Vec<LinkMapEntry>> {
+        let memory = self.memory();
+        let mut entries = Vec::new();
+        for namespace in self.r_debug()?.namespaces(&memory)? {
+            entries.extend(namespace.link_map(&memory)?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns a resolver of addresses to symbols and symbols to addresses in the modules of the
+    /// process, as of the last refresh of the segments.
+    pub fn symbol_resolver(&self) -> SymbolResolver {
+        SymbolResolver::new(self.process_id, self.segments())
+    }
+
+    /// Indexes the pointers of the writable segments of the process by the address they point
+    /// to, see [`Scanner::pointer_map`].
+    pub fn pointer_map(&self) -> anyhow::Result<PointerMap> {
+        self.scanner().pointer_map(self.bitness()?)
+    }
+
+    /// Guesses the types of the fields of the `size` bytes at `address`, see [`StructAnalyzer`].
+    pub fn analyze_struct(&self, address: u64, size: usize) -> anyhow::Result<Layout> {
+        let memory = self.memory();
+        let locator = Locator::from_pid(self.process_id)?;
+        StructAnalyzer::new(&memory, &locator, self.bitness()?).analyze(address, size)
+    }
+
+    /// Starts a freezer rewriting values in the memory of the process, see [`Freezer`].
+    pub fn freezer(&self) -> Freezer {
+        Freezer::new(self.process_id, self.memory())
+    }
+
+    /// Attaches to the main thread of the process, stopping it until the session is dropped,
+    /// see [`PtraceSession`].
+    pub fn attach(&self) -> anyhow::Result<PtraceSession> {
+        PtraceSession::attach(self.process_id)
+    }
+
+    /// Attaches to the main thread of the process without stopping it, see
+    /// [`PtraceSession::seize`].
+    pub fn seize(&self, options: libc::c_int) -> anyhow::Result<PtraceSession> {
+        PtraceSession::seize(self.process_id, options)
+    }
+
+    /// Stops every thread of the process until the guard is dropped, for reads and dumps
+    /// consistent across threads, see [`FrozenProcess`].
+    pub fn freeze(&self) -> anyhow::Result<FrozenProcess> {
+        FrozenProcess::freeze(self.process_id)
+    }
+
+    /// Opens the memory of the process through `/proc/<pid>/mem`.
+    pub fn handle(&self) -> anyhow::Result<ProcessHandle> {
+        ProcessHandle::open(self.process_id)
+    }
+
+    /// Lists the files backing the memory mappings of the process from `/proc/<pid>/map_files`.
+    pub fn map_files(&self) -> anyhow::Result<Vec<MapFile>> {
+        MapFile::list(self.process_id)
+    }
+
+    /// Opens `/proc/<pid>/pagemap` to inspect the physical pages backing the process's memory.
+    pub fn pagemap(&self) -> anyhow::Result<Pagemap> {
+        Pagemap::open(self.process_id)
+    }
+
+    /// Starts tracking the idle pages of the process to estimate its working set.
+    pub fn idle_page_tracker(&self) -> anyhow::Result<IdlePageTracker> {
+        IdlePageTracker::new(self.process_id)
+    }
+
+    /// Resets the page tracking bits of the process through `/proc/<pid>/clear_refs`.
+    pub fn clear_refs(&self, kind: ClearRefs) -> anyhow::Result<()> {
+        pagemap::clear_refs(self.process_id, kind)
+    }
+
+    /// Gives `advice` on the pages of `range` with `process_madvise(2)`, e.g. to reclaim them,
+    /// returning the number of bytes advised, see [`pagemap::advise`].
+    pub fn advise(&self, range: Range<u64>, advice: Advice) -> anyhow::Result<u64> {
+        pagemap::advise(self.process_id, range, advice)
+    }
+
+    /// Reports the pages written to since the last `clear_refs(ClearRefs::SoftDirty)`, by segment.
+    ///
+    /// Always empty on kernels built without `CONFIG_MEM_SOFT_DIRTY`.
+    pub fn soft_dirty_pages(&self) -> anyhow::Result<Vec<DirtyPages>> {
+        let segments = Segment::get_from_pid(self.process_id)?;
+        self.pagemap()?.soft_dirty_pages(&segments)
+    }
+
+    /// Reads the resource limits of the process from `/proc/<pid>/limits`.
+    pub fn limits(&self) -> anyhow::Result<ProcessLimits> {
+        self.read_to_string("limits")?.parse()
+    }
+
+    /// Reads the initial environment of the process from `/proc/<pid>/environ`.
+    ///
+    /// Variables set by the process after `execve` are not reflected.
+    pub fn environ(&self) -> anyhow::Result<HashMap<OsString, OsString>> {
+        Ok(self
+            .environ_raw()?
+            .into_iter()
+            .map(|(key, value)| (OsString::from_vec(key), OsString::from_vec(value)))
+            .collect())
+    }
+
+    /// Same as [`Process::environ`], but keeps the variables as raw bytes.
+    pub fn environ_raw(&self) -> anyhow::Result<HashMap<Vec<u8>, Vec<u8>>> {
+        let environ = self.read("environ")?;
+
+        Ok(environ
+            .split(|&b| b == 0)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.iter().position(|&b| b == b'=') {
+                Some(i) => (entry[..i].to_vec(), entry[i + 1..].to_vec()),
+                None => (entry.to_vec(), Vec::new()),
+            })
+            .collect())
+    }
+
+    /// Reads the command line arguments of the process from `/proc/<pid>/cmdline`.
+    ///
+    /// Kernel threads and zombies have no command line, in which case the vector is empty.
+    pub fn cmdline(&self) -> anyhow::Result<Vec<OsString>> {
+        let mut cmdline = self.read("cmdline")?;
+
+        // The last argument is terminated by a NUL byte, unless the process overwrote its argv
+        if cmdline.last() == Some(&0) {
+            cmdline.pop();
+        }
+        if cmdline.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(cmdline
+            .split(|&b| b == 0)
+            .map(|arg| OsString::from_vec(arg.to_vec()))
+            .collect())
+    }
+
+    /// Command line arguments of the process joined by spaces, as displayed by `ps`.
+    ///
+    /// Kernel threads are displayed as their name between brackets, e.g. `[kthreadd]`.
+    pub fn cmdline_display(&self) -> anyhow::Result<String> {
+        let cmdline = self.cmdline()?;
+        if cmdline.is_empty() {
+            return Ok(format!("[{}]", self.name));
+        }
+
+        Ok(cmdline
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    /// Lists the open file descriptors of the process from `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`.
+    pub fn fds(&self) -> anyhow::Result<Vec<ProcessFd>> {
+        ProcessFd::list(self.process_id)
+    }
+
+    /// Lists the sockets opened by the process, resolved against the TCP, UDP and UNIX tables
+    /// of its network namespace.
+    pub fn sockets(&self) -> anyhow::Result<Vec<(ProcessFd, Socket)>> {
+        let tables = NetTables::from_pid(self.process_id)?;
+
+        Ok(self
+            .fds()?
+            .into_iter()
+            .filter_map(|fd| {
+                let socket = fd.socket(&tables)?.clone();
+                Some((fd, socket))
+            })
+            .collect())
+    }
+
+    /// Resolves the executable of the process from `/proc/<pid>/exe`.
+    pub fn exe_path(&self) -> anyhow::Result<LinkTarget> {
+        self.read_link("exe")
+    }
+
+    /// Resolves the current working directory of the process from `/proc/<pid>/cwd`.
+    pub fn cwd(&self) -> anyhow::Result<LinkTarget> {
+        self.read_link("cwd")
+    }
+
+    /// Resolves the root directory of the process from `/proc/<pid>/root`, as set by
+    /// [chroot(2)](https://www.man7.org/linux/man-pages/man2/chroot.2.html).
+    pub fn root(&self) -> anyhow::Result<LinkTarget> {
+        self.read_link("root")
+    }
+
+    /// Reads the `/proc/<pid>/<file>` symbolic link.
+    fn read_link(&self, file: &str) -> anyhow::Result<LinkTarget> {
+        let path = format!("/proc/{}/{file}", self.process_id);
+        let target = fs::read_link(&path).with_context(|| format!("Failed to read {path}"))?;
+
+        Ok(target.into())
+    }
+
+    /// Reads the ELF auxiliary vector of the process from `/proc/<pid>/auxv`.
+    ///
+    /// The vector of a 32-bit process is decoded as such, falling back to the bitness of the
+    /// inspector when the executable cannot be read.
+    pub fn auxv(&self) -> anyhow::Result<AuxVec> {
+        let bitness = self.bitness().unwrap_or(Bitness::NATIVE);
+        AuxVec::parse_with(&self.read("auxv")?, bitness)
+    }
+
+    /// Detects whether the process is 32-bit or 64-bit, from the ELF class of its executable.
+    pub fn bitness(&self) -> anyhow::Result<Bitness> {
+        Bitness::from_pid(self.process_id)
+    }
+
+    /// Detects the instruction set of the process, from the ELF header of its executable.
+    pub fn arch(&self) -> anyhow::Result<Arch> {
+        Arch::from_pid(self.process_id)
+    }
+
+    /// Reads the kernel stack of the main thread of the process from `/proc/<pid>/stack`.
+    ///
+    /// This requires `CAP_SYS_ADMIN`, the underlying [`std::io::Error`] is kept so callers can
+    /// check for [`std::io::ErrorKind::PermissionDenied`].
+    pub fn kernel_stack(&self) -> anyhow::Result<Vec<KernelStackFrame>> {
+        let stack = self.read_to_string("stack").map_err(|e| {
+            match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                Some(std::io::ErrorKind::PermissionDenied) => {
+                    e.context("Reading the kernel stack requires CAP_SYS_ADMIN")
+                }
+                _ => e,
+            }
+        })?;
+
+        parse_kernel_stack(&stack)
+    }
+
+    /// Reads the system call the main thread of the process is blocked in from
+    /// `/proc/<pid>/syscall`.
+    pub fn syscall(&self) -> anyhow::Result<SyscallState> {
+        self.read_to_string("syscall")?.parse()
+    }
+
+    /// Name of the kernel function the process is sleeping in, or `None` if it is running.
+    ///
+    /// Uses `/proc/<pid>/wchan` when the kernel exposes it, and falls back to resolving the
+    /// `wchan` address against `/proc/kallsyms`.
+    pub fn wait_channel_name(&self) -> anyhow::Result<Option<String>> {
+        let wchan = self.read_to_string("wchan")?;
+        let wchan = wchan.trim();
+        if !wchan.is_empty() && wchan != "0" {
+            return Ok(Some(wchan.to_string()));
+        }
+
+        // Linux >= 5.16 only reports 0 or 1 in stat
+        if self.wchan <= 1 {
+            return Ok(None);
+        }
+
+        Ok(Kallsyms::cached()?
+            .resolve(self.wchan)
+            .map(|(symbol, _)| symbol.name.clone()))
+    }
+
+    /// Reads the scheduler data of the process from `/proc/<pid>/sched`.
+    pub fn sched(&self) -> anyhow::Result<SchedInfo> {
+        self.read_to_string("sched")?.parse()
+    }
+
+    /// Reads the scheduler statistics of the process from `/proc/<pid>/schedstat`.
+    pub fn schedstat(&self) -> anyhow::Result<SchedStat> {
+        self.read_to_string("schedstat")?.parse()
+    }
+
+    /// Reads the mounts of the mount namespace of the process from `/proc/<pid>/mountinfo`.
+    pub fn mountinfo(&self) -> anyhow::Result<MountTable> {
+        self.read_to_string("mountinfo")?.parse()
+    }
+
+    /// Reads the namespaces of the process from `/proc/<pid>/ns`.
+    pub fn namespaces(&self) -> anyhow::Result<Namespaces> {
+        Namespaces::from_pid(self.process_id)
+    }
+
+    /// Reads the namespace of the given kind of the process, `None` if unsupported by the kernel.
+    pub fn namespace(&self, kind: NsKind) -> anyhow::Result<Option<Namespace>> {
+        Namespace::from_pid(self.process_id, kind)
+    }
+
+    /// Returns true if both processes are members of the same namespace of the given kind.
+    pub fn same_namespace(&self, other: &Process, kind: NsKind) -> anyhow::Result<bool> {
+        Ok(self.namespace(kind)? == other.namespace(kind)?)
+    }
+
+    /// Reads the user ID mapping of the user namespace of the process from `/proc/<pid>/uid_map`.
+    pub fn uid_map(&self) -> anyhow::Result<IdMap> {
+        self.read_to_string("uid_map")?.parse()
+    }
+
+    /// Reads the group ID mapping of the user namespace of the process from `/proc/<pid>/gid_map`.
+    pub fn gid_map(&self) -> anyhow::Result<IdMap> {
+        self.read_to_string("gid_map")?.parse()
+    }
+
+    /// Mapping between the user namespace of the process and the inspector's, for `uid_map` or
+    /// `gid_map`.
+    fn inspector_id_map(&self, file: &str) -> anyhow::Result<IdMap> {
+        let inspector = Namespace::from_pid(std::process::id(), NsKind::User)?;
+        if self.namespace(NsKind::User)? == inspector {
+            return Ok(IdMap::identity());
+        }
+
+        self.read_to_string(file)?.parse()
+    }
+
+    /// Translates a user ID of the process's user namespace to the inspector's, `None` if the
+    /// ID has no mapping (it then appears as the overflow UID, usually 65534).
+    pub fn uid_to_inspector(&self, uid: u32) -> anyhow::Result<Option<u32>> {
+        Ok(self.inspector_id_map("uid_map")?.to_outside(uid))
+    }
+
+    /// Translates a user ID of the inspector's user namespace to the process's.
+    pub fn uid_from_inspector(&self, uid: u32) -> anyhow::Result<Option<u32>> {
+        Ok(self.inspector_id_map("uid_map")?.to_inside(uid))
+    }
+
+    /// Translates a group ID of the process's user namespace to the inspector's.
+    pub fn gid_to_inspector(&self, gid: u32) -> anyhow::Result<Option<u32>> {
+        Ok(self.inspector_id_map("gid_map")?.to_outside(gid))
+    }
+
+    /// Translates a group ID of the inspector's user namespace to the process's.
+    pub fn gid_from_inspector(&self, gid: u32) -> anyhow::Result<Option<u32>> {
+        Ok(self.inspector_id_map("gid_map")?.to_inside(gid))
+    }
+
+    /// Reads the capability sets of the process from `/proc/<pid>/status`.
+    pub fn capabilities(&self) -> anyhow::Result<ProcessCapabilities> {
+        self.read_to_string("status")?.parse()
+    }
+
+    /// Reads the seccomp mode of the process from `/proc/<pid>/status`.
+    ///
+    /// See [`crate::introspection::seccomp::seccomp_filters`] to dump the filters themselves.
+    pub fn seccomp_mode(&self) -> anyhow::Result<SeccompMode> {
+        self.read_to_string("status")?.parse()
+    }
+
+    /// Reads the memory usage totals of the process, see [`MemoryRollup::from_pid`].
+    pub fn memory_rollup(&self) -> anyhow::Result<MemoryRollup> {
+        MemoryRollup::from_pid(self.process_id)
+    }
+
+    /// Computes the RSS, PSS and USS of the process, with a breakdown by backing file.
+    pub fn memory_summary(&self) -> anyhow::Result<MemorySummary> {
+        MemorySummary::from_pid(self.process_id)
+    }
+
+    /// Reads the KSM statistics of the process from `/proc/<pid>/ksm_stat` (Linux >= 6.1), `None`
+    /// on older kernels.
+    pub fn ksm_stat(&self) -> anyhow::Result<Option<KsmStat>> {
+        KsmStat::from_pid(self.process_id)
+    }
+
+    /// Computes the transparent huge page and hugetlbfs usage of the process.
+    pub fn huge_pages(&self) -> anyhow::Result<HugePageSummary> {
+        HugePageSummary::from_pid(self.process_id)
+    }
+
+    /// Reads `/proc/<pid>/<file>`.
+    fn read(&self, file: &str) -> anyhow::Result<Vec<u8>> {
+        let path = format!("/proc/{}/{file}", self.process_id);
+        fs::read(&path).with_context(|| format!("Failed to read {path}"))
+    }
+
+    /// Reads `/proc/<pid>/<file>` as a string.
+    fn read_to_string(&self, file: &str) -> anyhow::Result<String> {
+        let path = format!("/proc/{}/{file}", self.process_id);
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.process_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> ProcessState {
+        self.state
+    }
+
+    pub fn parent_id(&self) -> Pid {
+        self.parent_id
+    }
+
+    pub fn parent_group_id(&self) -> Pid {
+        self.parent_group_id
+    }
+
+    pub fn session_id(&self) -> Pid {
+        self.session_id
+    }
+
+    pub fn tty_nr(&self) -> u32 {
+        self.tty_nr
+    }
+
+    pub fn tpgid(&self) -> i32 {
+        self.tpgid
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn minflt(&self) -> u64 {
+        self.minflt
+    }
+
+    pub fn cminflt(&self) -> u64 {
+        self.cminflt
+    }
+
+    pub fn majflt(&self) -> u64 {
+        self.majflt
+    }
+
+    pub fn cmajflt(&self) -> u64 {
+        self.cmajflt
+    }
+
+    pub fn utime(&self) -> u64 {
+        self.utime
+    }
+
+    pub fn stime(&self) -> u64 {
+        self.stime
+    }
+
+    pub fn cutime(&self) -> u64 {
+        self.cutime
+    }
+
+    pub fn cstime(&self) -> u64 {
+        self.cstime
+    }
+
+    pub fn priority(&self) -> i8 {
+        self.priority
+    }
+
+    pub fn nice(&self) -> i8 {
+        self.nice
+    }
+
+    pub fn num_threads(&self) -> Option<i64> {
+        self.num_threads
+    }
+
+    pub fn itrealvalue(&self) -> u64 {
+        self.itrealvalue
+    }
+
+    pub fn starttime(&self) -> u64 {
+        self.starttime
+    }
+
+    pub fn vsize(&self) -> u64 {
+        self.vsize
+    }
+
+    pub fn rss(&self) -> u64 {
+        self.rss
+    }
+
+    pub fn rsslim(&self) -> u64 {
+        self.rsslim
+    }
+
+    pub fn startcode(&self) -> u64 {
+        self.startcode
+    }
+
+    pub fn endcode(&self) -> u64 {
+        self.endcode
+    }
+
+    pub fn startstack(&self) -> u64 {
+        self.startstack
+    }
+
+    pub fn kstkesp(&self) -> u64 {
+        self.kstkesp
+    }
+
+    pub fn kstkeip(&self) -> u64 {
+        self.kstkeip
+    }
+
+    pub fn signal(&self) -> u64 {
+        self.signal
+    }
+
+    pub fn blocked(&self) -> u64 {
+        self.blocked
+    }
+
+    pub fn sigignore(&self) -> u64 {
+        self.sigignore
+    }
+
+    pub fn sigcatch(&self) -> u64 {
+        self.sigcatch
+    }
+
+    pub fn wchan(&self) -> u64 {
+        self.wchan
+    }
+
+    pub fn nswap(&self) -> u64 {
+        self.nswap
+    }
+
+    pub fn cnswap(&self) -> u64 {
+        self.cnswap
+    }
+
+    pub fn exit_signal(&self) -> i16 {
+        self.exit_signal
+    }
+
+    pub fn processor(&self) -> i16 {
+        self.processor
+    }
+
+    pub fn rt_priority(&self) -> u32 {
+        self.rt_priority
+    }
+
+    pub fn policy(&self) -> u32 {
+        self.policy
+    }
+
+    pub fn delayacct_blkio_ticks(&self) -> u64 {
+        self.delayacct_blkio_ticks
+    }
+
+    pub fn guest_time(&self) -> u64 {
+        self.guest_time
+    }
+
+    pub fn cguest_time(&self) -> u64 {
+        self.cguest_time
+    }
+
+    pub fn start_data(&self) -> u64 {
+        self.start_data
+    }
+
+    pub fn end_data(&self) -> u64 {
+        self.end_data
+    }
+
+    pub fn start_brk(&self) -> u64 {
+        self.start_brk
+    }
+
+    pub fn arg_start(&self) -> u64 {
+        self.arg_start
+    }
+
+    pub fn arg_end(&self) -> u64 {
+        self.arg_end
+    }
+
+    pub fn env_start(&self) -> u64 {
+        self.env_start
+    }
+
+    pub fn env_end(&self) -> u64 {
+        self.env_end
+    }
+
+    pub fn exit_code(&self) -> u32 {
+        self.exit_code
+    }
+
+    pub fn threads(&self) -> Option<&[Thread]> {
+        self.threads.as_deref()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        self.segments.as_slice()
+    }
+
+    /// Returns the segment containing `address`, as of the last refresh of the segments.
+    pub fn find_segment(&self, address: u64) -> Option<&Segment> {
+        self.segments.find(address)
+    }
+}
+
+impl FromStr for Process {
+    type Err = anyhow::Error;
+
+    /// Parses a line of `/proc/<pid>/stat`.
+    ///
+    /// The executable name is enclosed in parentheses and may itself contain spaces or
+    /// parentheses, so it spans from the first `(` to the last `)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let open = s
+            .find('(')
+            .ok_or_else(|| anyhow!("Missing '(' in stat line"))?;
+        let close = s
+            .rfind(')')
+            .ok_or_else(|| anyhow!("Missing ')' in stat line"))?;
+        if close < open {
+            bail!("Malformed stat line: {s}");
+        }
+
+        let process_id = s[..open].trim().parse().context("Invalid pid")?;
+        let name = s[open + 1..close].to_string();
+
+        let fields: Vec<&str> = s[close + 1..].split_whitespace().collect();
+        // Fields 3 to 52, Linux >= 3.5
+        if fields.len() < 50 {
+            bail!(
+                "Expected at least 52 fields in stat line, got {}",
+                fields.len() + 2
+            );
+        }
+
+        // `field(n)` returns the n-th field as numbered in proc(5)
+        fn field<T: FromStr>(fields: &[&str], n: usize) -> anyhow::Result<T>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            fields[n - 3]
+                .parse()
+                .with_context(|| format!("Invalid stat field {n}: {}", fields[n - 3]))
+        }
+
+        let state = fields[0]
+            .chars()
+            .next()
+            .ok_or_else(|| anyhow!("Empty process state"))?
+            .try_into()?;
+
+        Ok(Process {
+            process_id,
+            name,
+            state,
+            parent_id: field(&fields, 4)?,
+            parent_group_id: field(&fields, 5)?,
+            session_id: field(&fields, 6)?,
+            tty_nr: field(&fields, 7)?,
+            tpgid: field(&fields, 8)?,
+            flags: field(&fields, 9)?,
+            minflt: field(&fields, 10)?,
+            cminflt: field(&fields, 11)?,
+            majflt: field(&fields, 12)?,
+            cmajflt: field(&fields, 13)?,
+            utime: field(&fields, 14)?,
+            stime: field(&fields, 15)?,
+            cutime: field(&fields, 16)?,
+            cstime: field(&fields, 17)?,
+            priority: field(&fields, 18)?,
+            nice: field(&fields, 19)?,
+            num_threads: Some(field(&fields, 20)?),
+            itrealvalue: field(&fields, 21)?,
+            starttime: field(&fields, 22)?,
+            vsize: field(&fields, 23)?,
+            rss: field(&fields, 24)?,
+            rsslim: field(&fields, 25)?,
+            startcode: field(&fields, 26)?,
+            endcode: field(&fields, 27)?,
+            startstack: field(&fields, 28)?,
+            kstkesp: field(&fields, 29)?,
+            kstkeip: field(&fields, 30)?,
+            signal: field(&fields, 31)?,
+            blocked: field(&fields, 32)?,
+            sigignore: field(&fields, 33)?,
+            sigcatch: field(&fields, 34)?,
+            wchan: field(&fields, 35)?,
+            nswap: field(&fields, 36)?,
+            cnswap: field(&fields, 37)?,
+            exit_signal: field(&fields, 38)?,
+            processor: field(&fields, 39)?,
+            rt_priority: field(&fields, 40)?,
+            policy: field(&fields, 41)?,
+            delayacct_blkio_ticks: field(&fields, 42)?,
+            guest_time: field(&fields, 43)?,
+            cguest_time: field(&fields, 44)?,
+            start_data: field(&fields, 45)?,
+            end_data: field(&fields, 46)?,
+            start_brk: field(&fields, 47)?,
+            arg_start: field(&fields, 48)?,
+            arg_end: field(&fields, 49)?,
+            env_start: field(&fields, 50)?,
+            env_end: field(&fields, 51)?,
+            exit_code: field(&fields, 52)?,
+            threads: None,
+            segments: Segments::default(),
+        })
+    }
 }
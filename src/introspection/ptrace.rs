@@ -0,0 +1,995 @@
+//! This module contains the ptrace sessions tracing the threads of a process, and the ptrace
+//! backend to access its memory.
+//! Based on https://www.man7.org/linux/man-pages/man2/ptrace.2.html
+use std::{cell::OnceCell, fmt, fs, io, marker::PhantomData, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    access::{check_alignment, AtomicWriter, MemoryReader, MemoryWriter},
+    arch::{Arch, Registers},
+    audit::audit_write,
+    fpu::FpRegisters,
+    process::Pid,
+    remote,
+    thread::tids,
+    vm::ProcessVm,
+};
+
+/// Size of the words transferred by `PTRACE_PEEKDATA` and `PTRACE_POKEDATA`.
+const WORD_SIZE: usize = std::mem::size_of::<libc::c_long>();
+
+/// Event of the stops of seized threads, missing from the libc crate for glibc.
+const PTRACE_EVENT_STOP: libc::c_int = 128;
+
+/// How a traced thread stopped, or ended, as reported by `waitpid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// Signal-delivery-stop, the signal being delivered only if passed back when resuming
+    Signal(libc::c_int),
+    /// Entry or exit of a system call, with `PTRACE_O_TRACESYSGOOD`
+    Syscall,
+    /// `PTRACE_EVENT_*` stop enabled by an option, e.g. `PTRACE_EVENT_CLONE`
+    Event(libc::c_int),
+    /// Stop of a seized thread requested by [`PtraceSession::interrupt`]
+    Interrupted,
+    /// Group-stop of a seized thread by this signal, e.g. `SIGSTOP`, see
+    /// [`PtraceSession::listen`]
+    GroupStop(libc::c_int),
+    /// The thread exited with this status
+    Exited(libc::c_int),
+    /// The thread was killed by this signal
+    Killed(libc::c_int),
+}
+
+impl Stop {
+    /// Decodes a status returned by `waitpid`.
+    fn from_status(status: libc::c_int) -> Self {
+        if libc::WIFEXITED(status) {
+            return Stop::Exited(libc::WEXITSTATUS(status));
+        }
+        if libc::WIFSIGNALED(status) {
+            return Stop::Killed(libc::WTERMSIG(status));
+        }
+        match (libc::WSTOPSIG(status), status >> 16) {
+            (signal, 0) if signal == libc::SIGTRAP | 0x80 => Stop::Syscall,
+            (signal, 0) => Stop::Signal(signal),
+            (libc::SIGTRAP, PTRACE_EVENT_STOP) => Stop::Interrupted,
+            (signal, PTRACE_EVENT_STOP) => Stop::GroupStop(signal),
+            (_, event) => Stop::Event(event),
+        }
+    }
+
+    /// Whether the thread is gone and no longer traced.
+    pub fn is_exit(&self) -> bool {
+        matches!(self, Stop::Exited(_) | Stop::Killed(_))
+    }
+}
+
+/// Size of `siginfo_t`.
+const SIGINFO_SIZE: usize = std::mem::size_of::<libc::siginfo_t>();
+
+/// Offset of the union following `si_signo`, `si_errno` and `si_code` in `siginfo_t`.
+const SIGINFO_FIELDS_OFFSET: usize =
+    (3 * std::mem::size_of::<libc::c_int>()).next_multiple_of(std::mem::align_of::<libc::c_long>());
+
+/// `si_code` of the signals sent with `sigqueue`.
+const SI_QUEUE: libc::c_int = -1;
+
+/// A signal with its `siginfo_t`, as seen by the tracer in a signal-delivery-stop.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignalInfo {
+    bytes: [u8; SIGINFO_SIZE],
+}
+
+impl SignalInfo {
+    pub fn from_raw(raw: &libc::siginfo_t) -> Self {
+        let mut bytes = [0; SIGINFO_SIZE];
+        // SAFETY: siginfo_t is plain data of SIGINFO_SIZE bytes
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                (raw as *const libc::siginfo_t).cast::<u8>(),
+                bytes.as_mut_ptr(),
+                SIGINFO_SIZE,
+            )
+        };
+        SignalInfo { bytes }
+    }
+
+    pub fn to_raw(&self) -> libc::siginfo_t {
+        // SAFETY: the bytes hold a siginfo_t, which is plain data
+        unsafe { std::ptr::read_unaligned(self.bytes.as_ptr().cast()) }
+    }
+
+    /// Information of `signal` sent by this process with `value`, like `sigqueue` does.
+    fn queued(signal: libc::c_int, value: u64) -> Self {
+        // SAFETY: siginfo_t is plain data, valid when zeroed
+        let mut raw: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        raw.si_signo = signal;
+        raw.si_code = SI_QUEUE;
+        let mut info = Self::from_raw(&raw);
+        let fields = &mut info.bytes[SIGINFO_FIELDS_OFFSET..];
+        // `si_pid`, `si_uid` and `si_value`
+        fields[..4].copy_from_slice(&std::process::id().to_ne_bytes());
+        // SAFETY: getuid has no preconditions
+        fields[4..8].copy_from_slice(&unsafe { libc::getuid() }.to_ne_bytes());
+        let value = (value as usize).to_ne_bytes();
+        let offset = 8usize.next_multiple_of(value.len());
+        fields[offset..offset + value.len()].copy_from_slice(&value);
+
+        info
+    }
+
+    pub fn signal(&self) -> libc::c_int {
+        self.to_raw().si_signo
+    }
+
+    /// Changes the signal, keeping the other fields.
+    pub fn set_signal(&mut self, signal: libc::c_int) {
+        let mut raw = self.to_raw();
+        raw.si_signo = signal;
+        *self = Self::from_raw(&raw);
+    }
+
+    /// Origin of the signal, e.g. `SI_USER` when sent by `kill` or `SEGV_MAPERR` for a fault.
+    pub fn code(&self) -> libc::c_int {
+        self.to_raw().si_code
+    }
+
+    pub fn errno(&self) -> libc::c_int {
+        self.to_raw().si_errno
+    }
+
+    /// Whether a process sent the signal, e.g. with `kill`, rather than the kernel.
+    pub fn is_user(&self) -> bool {
+        self.code() <= 0
+    }
+
+    /// ID of the process which sent the signal, and its user ID.
+    pub fn sender(&self) -> Option<(Pid, u32)> {
+        let raw = self.to_raw();
+        // SAFETY: signals sent by processes hold the sender
+        self.is_user()
+            .then(|| unsafe { (raw.si_pid() as Pid, raw.si_uid()) })
+    }
+
+    /// Faulting address of a signal raised by the processor, e.g. the invalid access of a
+    /// `SIGSEGV`.
+    pub fn fault_address(&self) -> Option<u64> {
+        let is_fault = matches!(
+            self.signal(),
+            libc::SIGSEGV | libc::SIGBUS | libc::SIGILL | libc::SIGFPE | libc::SIGTRAP
+        );
+        let raw = self.to_raw();
+        // SAFETY: signals raised by the processor hold the address
+        (is_fault && !self.is_user()).then(|| unsafe { raw.si_addr() } as u64)
+    }
+
+    /// Value sent with the signal by `sigqueue`.
+    pub fn value(&self) -> Option<u64> {
+        let raw = self.to_raw();
+        // SAFETY: queued signals hold the value
+        (self.code() == SI_QUEUE).then(|| unsafe { raw.si_value() }.sival_ptr as u64)
+    }
+}
+
+impl fmt::Debug for SignalInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalInfo")
+            .field("signal", &self.signal())
+            .field("code", &self.code())
+            .field("errno", &self.errno())
+            .finish()
+    }
+}
+
+/// State of the thread of a [`PtraceSession`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TraceeState {
+    Stopped,
+    Running,
+    Exited,
+}
+
+/// How a [`PtraceSession`] attached to its thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachMode {
+    /// `PTRACE_ATTACH`, stopping the thread with a `SIGSTOP`
+    Attach,
+    /// `PTRACE_SEIZE`, leaving the thread running until interrupted
+    Seize,
+}
+
+/// A thread traced by the calling thread, detached and resumed when the session is dropped,
+/// including while unwinding from a panic.
+///
+/// The kernel only accepts ptrace requests from the thread which attached, so the session
+/// cannot be sent to another thread.
+#[derive(Debug)]
+pub struct PtraceSession {
+    tid: Pid,
+    mode: AttachMode,
+    state: TraceeState,
+    /// Signal of the current signal-delivery-stop, or received while stopping the thread,
+    /// delivered on detach so the target does not lose it
+    pending_signal: libc::c_int,
+    /// Whether the thread is in a signal-delivery-stop, whose signal can be changed
+    signal_stop: bool,
+    /// Architecture of the process, read on first use
+    arch: OnceCell<Arch>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl PtraceSession {
+    /// Attaches to the thread `tid` with `PTRACE_ATTACH` and waits for it to stop. Only this
+    /// thread is stopped, not the others of its process.
+    pub fn attach(tid: Pid) -> anyhow::Result<Self> {
+        // SAFETY: PTRACE_ATTACH takes no pointer
+        if unsafe { libc::ptrace(libc::PTRACE_ATTACH, tid as libc::pid_t, 0, 0) } < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to attach to {tid}"));
+        }
+
+        let mut session = PtraceSession {
+            tid,
+            mode: AttachMode::Attach,
+            state: TraceeState::Running,
+            pending_signal: 0,
+            signal_stop: false,
+            arch: OnceCell::new(),
+            _not_send: PhantomData,
+        };
+        session
+            .wait_for_sigstop()
+            .with_context(|| format!("Failed to wait for {tid} to stop"))?;
+
+        Ok(session)
+    }
+
+    /// Attaches to the thread `tid` with `PTRACE_SEIZE` and the `PTRACE_O_*` `options`, without
+    /// stopping it: monitoring tools interrupt the thread only when they need its state, see
+    /// [`Self::interrupt`].
+    pub fn seize(tid: Pid, options: libc::c_int) -> anyhow::Result<Self> {
+        // SAFETY: PTRACE_SEIZE takes the options as data
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_SEIZE,
+                tid as libc::pid_t,
+                0,
+                options as libc::c_long,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to seize {tid}"));
+        }
+
+        Ok(PtraceSession {
+            tid,
+            mode: AttachMode::Seize,
+            state: TraceeState::Running,
+            pending_signal: 0,
+            signal_stop: false,
+            arch: OnceCell::new(),
+            _not_send: PhantomData,
+        })
+    }
+
+    pub fn tid(&self) -> Pid {
+        self.tid
+    }
+
+    pub fn mode(&self) -> AttachMode {
+        self.mode
+    }
+
+    /// Whether the thread is stopped, so its memory and registers can be accessed.
+    pub fn is_stopped(&self) -> bool {
+        self.state == TraceeState::Stopped
+    }
+
+    /// Fails unless the thread is stopped.
+    fn check_stopped(&self) -> anyhow::Result<()> {
+        match self.state {
+            TraceeState::Stopped => Ok(()),
+            TraceeState::Running => bail!("Thread {} is running", self.tid),
+            TraceeState::Exited => bail!("Thread {} exited", self.tid),
+        }
+    }
+
+    /// Architecture of the process of the thread, selecting the layout of its registers.
+    pub fn arch(&self) -> anyhow::Result<Arch> {
+        if let Some(arch) = self.arch.get() {
+            return Ok(*arch);
+        }
+        let arch = Arch::from_pid(self.tid)?;
+        Ok(*self.arch.get_or_init(|| arch))
+    }
+
+    /// Reads the general purpose registers of the stopped thread.
+    pub fn registers(&self) -> anyhow::Result<Registers> {
+        self.check_stopped()?;
+        Registers::get(self.tid, self.arch()?)
+    }
+
+    /// Writes the general purpose registers of the stopped thread, which resumes with them.
+    pub fn set_registers(&self, registers: &Registers) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        if registers.arch() != self.arch()? {
+            bail!(
+                "Cannot set {:?} registers to a {:?} thread",
+                registers.arch(),
+                self.arch()?
+            );
+        }
+        registers.set(self.tid)
+    }
+
+    /// Reads the floating point and vector registers of the stopped thread.
+    pub fn fp_registers(&self) -> anyhow::Result<FpRegisters> {
+        self.check_stopped()?;
+        FpRegisters::get(self.tid, self.arch()?)
+    }
+
+    /// Writes the floating point and vector registers of the stopped thread.
+    pub fn set_fp_registers(&self, registers: &FpRegisters) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        if registers.arch() != self.arch()? {
+            bail!(
+                "Cannot set {:?} registers to a {:?} thread",
+                registers.arch(),
+                self.arch()?
+            );
+        }
+        registers.set(self.tid)
+    }
+
+    /// Reads the word at `offset` in the `struct user` of the stopped thread with
+    /// `PTRACE_PEEKUSER`, e.g. a debug register on x86.
+    pub fn peek_user(&self, offset: u64) -> anyhow::Result<u64> {
+        self.check_stopped()?;
+        // SAFETY: errno is thread-local, PEEKUSER returns the word so errno tells errors apart
+        let word = unsafe {
+            *libc::__errno_location() = 0;
+            libc::ptrace(
+                libc::PTRACE_PEEKUSER,
+                self.tid as libc::pid_t,
+                offset as *mut libc::c_void,
+                0,
+            )
+        };
+        let error = io::Error::last_os_error();
+        if word == -1 && error.raw_os_error() != Some(0) {
+            return Err(error)
+                .with_context(|| format!("Failed to read user offset {offset} of {}", self.tid));
+        }
+
+        Ok(word as u64)
+    }
+
+    /// Writes the word at `offset` in the `struct user` of the stopped thread with
+    /// `PTRACE_POKEUSER`.
+    pub fn poke_user(&self, offset: u64, value: u64) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        self.request(libc::PTRACE_POKEUSER, offset, value as libc::c_long)
+            .with_context(|| format!("Failed to write user offset {offset} of {}", self.tid))?;
+
+        Ok(())
+    }
+
+    /// Reads the signal information of the current signal-delivery-stop, e.g. the faulting
+    /// address of a `SIGSEGV`.
+    pub fn siginfo(&self) -> anyhow::Result<libc::siginfo_t> {
+        self.check_stopped()?;
+        // SAFETY: siginfo_t is plain data, valid when zeroed
+        let mut siginfo: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        // SAFETY: the kernel writes a siginfo_t to the pointer
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GETSIGINFO,
+                self.tid as libc::pid_t,
+                0,
+                &mut siginfo as *mut libc::siginfo_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to read the signal information of {}", self.tid));
+        }
+
+        Ok(siginfo)
+    }
+
+    /// Reads the system call the thread is stopped at the entry or exit of, with
+    /// `PTRACE_GET_SYSCALL_INFO`.
+    pub fn syscall_info(&self) -> anyhow::Result<libc::ptrace_syscall_info> {
+        self.check_stopped()?;
+        // SAFETY: ptrace_syscall_info is plain data, valid when zeroed
+        let mut info: libc::ptrace_syscall_info = unsafe { std::mem::zeroed() };
+        // SAFETY: the kernel writes at most the given size to the pointer
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GET_SYSCALL_INFO,
+                self.tid as libc::pid_t,
+                std::mem::size_of::<libc::ptrace_syscall_info>(),
+                &mut info as *mut libc::ptrace_syscall_info,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to read the system call of {}", self.tid));
+        }
+
+        Ok(info)
+    }
+
+    /// Calls the function at `function` in the stopped thread with `args`, see
+    /// [`remote::call_remote`].
+    pub fn call_remote(&mut self, function: u64, args: &[u64]) -> anyhow::Result<u64> {
+        remote::call_remote(self, function, args)
+    }
+
+    /// Loads the shared library at `path` in the process of the stopped thread, see
+    /// [`remote::inject_library`].
+    pub fn inject_library(&mut self, path: impl AsRef<Path>) -> anyhow::Result<u64> {
+        remote::inject_library(self, path)
+    }
+
+    /// Accesses the memory of the process of the thread while it is stopped.
+    pub fn memory(&self) -> PtraceMem {
+        PtraceMem::attached(self.tid)
+    }
+
+    /// Sets the `PTRACE_O_*` options of the session, e.g. `PTRACE_O_TRACESYSGOOD`.
+    pub fn set_options(&self, options: libc::c_int) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        self.request(libc::PTRACE_SETOPTIONS, 0, options as libc::c_long)
+            .context("Failed to set the ptrace options")?;
+
+        Ok(())
+    }
+
+    /// Resumes the thread with `PTRACE_CONT`, delivering `signal` unless 0.
+    pub fn cont(&mut self, signal: libc::c_int) -> anyhow::Result<()> {
+        self.resume(libc::PTRACE_CONT, signal)
+    }
+
+    /// Signal of the current signal-delivery-stop, delivered when detaching unless resumed or
+    /// suppressed, 0 if none.
+    pub fn pending_signal(&self) -> libc::c_int {
+        self.pending_signal
+    }
+
+    /// Keeps the signal of the current signal-delivery-stop from being delivered when detaching,
+    /// e.g. the `SIGTRAP` of a breakpoint of the tracer.
+    pub fn suppress_signal(&mut self) {
+        self.pending_signal = 0;
+    }
+
+    /// Fails unless the thread is in a signal-delivery-stop.
+    fn check_signal_stop(&self) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        if !self.signal_stop {
+            bail!("Thread {} is not stopped by a signal", self.tid);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the signal of the current signal-delivery-stop by `signal`, or suppresses it if
+    /// 0. The signal is delivered when the thread is resumed with [`Self::pending_signal`], or
+    /// detached.
+    pub fn set_pending_signal(&mut self, signal: libc::c_int) -> anyhow::Result<()> {
+        self.check_signal_stop()?;
+        self.pending_signal = signal;
+
+        Ok(())
+    }
+
+    /// Reads the signal of the current signal-delivery-stop with its information, e.g. which
+    /// process sent it.
+    pub fn signal_info(&self) -> anyhow::Result<SignalInfo> {
+        self.check_signal_stop()?;
+        Ok(SignalInfo::from_raw(&self.siginfo()?))
+    }
+
+    /// Replaces the signal of the current signal-delivery-stop by `info` with
+    /// `PTRACE_SETSIGINFO`, delivered like [`Self::set_pending_signal`].
+    pub fn set_signal_info(&mut self, info: &SignalInfo) -> anyhow::Result<()> {
+        self.check_signal_stop()?;
+        let raw = info.to_raw();
+        // SAFETY: the kernel reads a siginfo_t from the pointer
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_SETSIGINFO,
+                self.tid as libc::pid_t,
+                0,
+                &raw as *const libc::siginfo_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to set the signal information of {}", self.tid));
+        }
+        self.pending_signal = info.signal();
+
+        Ok(())
+    }
+
+    /// Sends `signal` to the thread with `tkill`. The tracer sees it in a signal-delivery-stop
+    /// once the thread is resumed, where it can still be changed.
+    pub fn inject_signal(&self, signal: libc::c_int) -> anyhow::Result<()> {
+        // SAFETY: tkill takes no pointer
+        if unsafe { libc::syscall(libc::SYS_tkill, self.tid as libc::pid_t, signal) } < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to send signal {signal} to {}", self.tid));
+        }
+
+        Ok(())
+    }
+
+    /// Sends `signal` to the thread with `value`, like `sigqueue`, see [`SignalInfo::value`].
+    pub fn queue_signal(&self, signal: libc::c_int, value: u64) -> anyhow::Result<()> {
+        let status = fs::read_to_string(format!("/proc/{}/status", self.tid))?;
+        let tgid: libc::pid_t = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Tgid:"))
+            .ok_or_else(|| anyhow!("Missing Tgid in the status of {}", self.tid))?
+            .trim()
+            .parse()?;
+        let raw = SignalInfo::queued(signal, value).to_raw();
+        // SAFETY: the kernel reads a siginfo_t from the pointer
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_rt_tgsigqueueinfo,
+                tgid,
+                self.tid as libc::pid_t,
+                signal,
+                &raw as *const libc::siginfo_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to queue signal {signal} to {}", self.tid));
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single instruction of the thread with `PTRACE_SINGLESTEP`, delivering `signal`
+    /// unless 0. The thread then stops with a `SIGTRAP`, see [`Self::wait`].
+    pub fn step(&mut self, signal: libc::c_int) -> anyhow::Result<()> {
+        self.resume(libc::PTRACE_SINGLESTEP, signal)
+    }
+
+    /// Resumes the thread with `PTRACE_SYSCALL`, delivering `signal` unless 0. The thread then
+    /// stops at the next entry or exit of a system call, see [`Self::syscall_info`].
+    pub fn syscall(&mut self, signal: libc::c_int) -> anyhow::Result<()> {
+        self.resume(libc::PTRACE_SYSCALL, signal)
+    }
+
+    /// Resumes the thread with `request`, e.g. `PTRACE_SYSCALL`, delivering `signal` unless 0.
+    fn resume(&mut self, request: libc::c_uint, signal: libc::c_int) -> anyhow::Result<()> {
+        self.check_stopped()?;
+        self.request(request, 0, signal as libc::c_long)
+            .with_context(|| format!("Failed to resume {}", self.tid))?;
+        self.state = TraceeState::Running;
+        self.pending_signal = 0;
+        self.signal_stop = false;
+
+        Ok(())
+    }
+
+    /// Stops a seized thread with `PTRACE_INTERRUPT` and waits for it to stop. A signal or an
+    /// event may be reported before the [`Stop::Interrupted`] stop, in which case the thread is
+    /// stopped as well.
+    pub fn interrupt(&mut self) -> anyhow::Result<Stop> {
+        if self.mode != AttachMode::Seize {
+            bail!("Only seized threads can be interrupted");
+        }
+        if self.state != TraceeState::Running {
+            self.check_stopped()?;
+            bail!("Thread {} is already stopped", self.tid);
+        }
+        self.request(libc::PTRACE_INTERRUPT, 0, 0)
+            .with_context(|| format!("Failed to interrupt {}", self.tid))?;
+
+        self.wait()
+    }
+
+    /// Stops the thread if it is running, with `PTRACE_INTERRUPT` if it was seized or a
+    /// `SIGSTOP` otherwise, so that its registers and memory can be accessed.
+    pub fn stop(&mut self) -> anyhow::Result<()> {
+        if self.state != TraceeState::Running {
+            return Ok(());
+        }
+        if self.mode == AttachMode::Seize {
+            self.interrupt()?;
+            return Ok(());
+        }
+        // SAFETY: tkill takes no pointer
+        if unsafe { libc::syscall(libc::SYS_tkill, self.tid as libc::pid_t, libc::SIGSTOP) } < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to stop {}", self.tid));
+        }
+
+        self.wait_for_sigstop()
+    }
+
+    /// Lets a seized thread in a [`Stop::GroupStop`] stay stopped like an untraced one, while
+    /// still reporting the signals it receives, e.g. `SIGCONT`, with `PTRACE_LISTEN`.
+    pub fn listen(&mut self) -> anyhow::Result<()> {
+        if self.mode != AttachMode::Seize {
+            bail!("Only seized threads can listen");
+        }
+        self.resume(libc::PTRACE_LISTEN, 0)
+    }
+
+    /// Waits for the thread to stop or exit.
+    pub fn wait(&mut self) -> anyhow::Result<Stop> {
+        match self.wait_with(0)? {
+            Some(stop) => Ok(stop),
+            None => bail!("Thread {} did not stop", self.tid),
+        }
+    }
+
+    /// Returns how the thread stopped if it did, without blocking.
+    pub fn try_wait(&mut self) -> anyhow::Result<Option<Stop>> {
+        self.wait_with(libc::WNOHANG)
+    }
+
+    fn wait_with(&mut self, flags: libc::c_int) -> anyhow::Result<Option<Stop>> {
+        if self.state == TraceeState::Exited {
+            bail!("Thread {} exited", self.tid);
+        }
+        let mut status = 0;
+        let result = loop {
+            // SAFETY: `status` is a valid pointer
+            let result = unsafe {
+                libc::waitpid(self.tid as libc::pid_t, &mut status, libc::__WALL | flags)
+            };
+            let error = io::Error::last_os_error();
+            if result >= 0 || error.kind() != io::ErrorKind::Interrupted {
+                break result;
+            }
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to wait for {}", self.tid));
+        }
+        if result == 0 {
+            return Ok(None);
+        }
+
+        let stop = Stop::from_status(status);
+        self.state = match stop.is_exit() {
+            true => TraceeState::Exited,
+            false => TraceeState::Stopped,
+        };
+        self.signal_stop = matches!(stop, Stop::Signal(_));
+        if let Stop::Signal(signal) = stop {
+            self.pending_signal = signal;
+        }
+
+        Ok(Some(stop))
+    }
+
+    /// Waits for the `SIGSTOP` sent to the thread, letting the signals arriving first through
+    /// once detached.
+    fn wait_for_sigstop(&mut self) -> anyhow::Result<()> {
+        let mut pending = self.pending_signal;
+        loop {
+            match self.wait()? {
+                Stop::Signal(libc::SIGSTOP) => break,
+                Stop::Exited(_) | Stop::Killed(_) => bail!("Thread {} exited", self.tid),
+                Stop::Signal(signal) => {
+                    pending = signal;
+                    self.cont(0)?;
+                }
+                Stop::Syscall | Stop::Event(_) | Stop::Interrupted | Stop::GroupStop(_) => {
+                    self.cont(0)?
+                }
+            }
+        }
+        self.pending_signal = pending;
+
+        Ok(())
+    }
+
+    /// Sends a ptrace request without pointer to the thread.
+    fn request(
+        &self,
+        request: libc::c_uint,
+        address: u64,
+        data: libc::c_long,
+    ) -> io::Result<libc::c_long> {
+        // SAFETY: the callers only send requests taking integers, not pointers
+        let result = unsafe {
+            libc::ptrace(
+                request,
+                self.tid as libc::pid_t,
+                address as *mut libc::c_void,
+                data,
+            )
+        };
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            result => Ok(result),
+        }
+    }
+
+    /// Detaches from the thread, resuming it, and reports whether it succeeded unlike dropping
+    /// the session.
+    pub fn detach(mut self) -> anyhow::Result<()> {
+        self.detach_inner()
+    }
+
+    fn detach_inner(&mut self) -> anyhow::Result<()> {
+        match self.stop() {
+            Err(_) if self.state == TraceeState::Exited => {}
+            result => result?,
+        }
+        if self.state == TraceeState::Exited {
+            return Ok(());
+        }
+
+        let result = self.request(libc::PTRACE_DETACH, 0, self.pending_signal as libc::c_long);
+        // The thread is either detached or gone
+        self.state = TraceeState::Exited;
+        result
+            .map(|_| ())
+            .with_context(|| format!("Failed to detach from {}", self.tid))
+    }
+}
+
+impl Drop for PtraceSession {
+    fn drop(&mut self) {
+        let _ = self.detach_inner();
+    }
+}
+
+/// Every thread of a process stopped by
+/// [`crate::introspection::process::Process::freeze`], so that its memory does not
+/// change while it is read, resumed when dropped.
+#[derive(Debug)]
+pub struct FrozenProcess {
+    pid: Pid,
+    /// Sessions of the threads, in increasing thread ID order
+    sessions: Vec<PtraceSession>,
+}
+
+impl FrozenProcess {
+    /// Seizes and interrupts every thread of the process `pid`, including the ones started
+    /// meanwhile, and waits for all of them to stop.
+    pub fn freeze(pid: Pid) -> anyhow::Result<Self> {
+        let mut frozen = FrozenProcess {
+            pid,
+            sessions: Vec::new(),
+        };
+        // Threads may start new ones until they are all stopped
+        loop {
+            let new: Vec<Pid> = tids(pid)?
+                .into_iter()
+                .filter(|tid| frozen.session(*tid).is_none())
+                .collect();
+            if new.is_empty() {
+                break;
+            }
+            for tid in new {
+                let mut session = match PtraceSession::seize(tid, 0) {
+                    Ok(session) => session,
+                    // The thread exited since it was listed
+                    Err(_) if !Path::new(&format!("/proc/{pid}/task/{tid}")).exists() => {
+                        continue;
+                    }
+                    Err(error) => return Err(error),
+                };
+                match session.interrupt() {
+                    Ok(stop) if stop.is_exit() => continue,
+                    Ok(_) => {}
+                    Err(_) if session.state == TraceeState::Exited => continue,
+                    Err(error) => return Err(error),
+                }
+                frozen.sessions.push(session);
+            }
+            frozen.sessions.sort_unstable_by_key(|session| session.tid);
+        }
+        if frozen.sessions.is_empty() {
+            bail!("Process {pid} has no thread left");
+        }
+
+        Ok(frozen)
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// IDs of the stopped threads, in increasing order.
+    pub fn tids(&self) -> Vec<Pid> {
+        self.sessions.iter().map(|session| session.tid).collect()
+    }
+
+    /// Sessions of the stopped threads, in increasing thread ID order.
+    pub fn sessions(&self) -> &[PtraceSession] {
+        &self.sessions
+    }
+
+    pub fn session(&self, tid: Pid) -> Option<&PtraceSession> {
+        self.sessions.iter().find(|session| session.tid == tid)
+    }
+
+    pub fn session_mut(&mut self, tid: Pid) -> Option<&mut PtraceSession> {
+        self.sessions.iter_mut().find(|session| session.tid == tid)
+    }
+
+    /// Accesses the memory of the process, which no thread changes while it is frozen.
+    pub fn memory(&self) -> ProcessVm {
+        ProcessVm::new(self.pid)
+    }
+
+    /// Resumes every thread, reporting the first failure unlike dropping the guard.
+    pub fn thaw(mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for session in self.sessions.drain(..) {
+            if let Err(error) = session.detach() {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+}
+
+/// Memory access one word at a time with `PTRACE_PEEKDATA` and `PTRACE_POKEDATA`.
+///
+/// This is the slowest backend, for environments where `process_vm_readv` and `/proc/<pid>/mem`
+/// are blocked but `PTRACE_ATTACH` is allowed. Like [`crate::introspection::mem::ProcMem`], it can
+/// write pages mapped read-only in the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtraceMem {
+    pid: Pid,
+    /// Attach and stop the target around each access, or rely on the caller being attached
+    attach: bool,
+}
+
+impl PtraceMem {
+    /// Accesses the memory of `pid`, attaching to it around each read or write.
+    pub fn new(pid: Pid) -> Self {
+        PtraceMem { pid, attach: true }
+    }
+
+    /// Accesses the memory of `pid`, which the calling thread already traces and has stopped.
+    pub fn attached(pid: Pid) -> Self {
+        PtraceMem { pid, attach: false }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn guard(&self) -> anyhow::Result<Option<PtraceSession>> {
+        self.attach
+            .then(|| PtraceSession::attach(self.pid))
+            .transpose()
+    }
+
+    fn peek(&self, address: u64) -> anyhow::Result<[u8; WORD_SIZE]> {
+        // SAFETY: errno is thread-local, PEEKDATA returns the word so errno tells errors apart
+        let word = unsafe {
+            *libc::__errno_location() = 0;
+            libc::ptrace(
+                libc::PTRACE_PEEKDATA,
+                self.pid as libc::pid_t,
+                address as *mut libc::c_void,
+                0,
+            )
+        };
+        let error = io::Error::last_os_error();
+        if word == -1 && error.raw_os_error() != Some(0) {
+            return Err(error)
+                .with_context(|| format!("Failed to read {address:#x} in {}", self.pid));
+        }
+
+        Ok(word.to_ne_bytes())
+    }
+
+    fn poke(&self, address: u64, word: [u8; WORD_SIZE]) -> anyhow::Result<()> {
+        // SAFETY: POKEDATA takes the word by value
+        let result = unsafe {
+            libc::ptrace(
+                libc::PTRACE_POKEDATA,
+                self.pid as libc::pid_t,
+                address as *mut libc::c_void,
+                libc::c_long::from_ne_bytes(word),
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Failed to write {address:#x} in {}", self.pid));
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryReader for PtraceMem {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let _guard = self.guard()?;
+
+        let mut done = 0;
+        while done < buffer.len() {
+            let current = address + done as u64;
+            let skip = (current % WORD_SIZE as u64) as usize;
+            let word = self.peek(current - skip as u64)?;
+
+            let len = (WORD_SIZE - skip).min(buffer.len() - done);
+            buffer[done..done + len].copy_from_slice(&word[skip..skip + len]);
+            done += len;
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryWriter for PtraceMem {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        if !audit_write(self.pid, address, data, "ptrace", self) {
+            return Ok(());
+        }
+        let _guard = self.guard()?;
+
+        let mut done = 0;
+        while done < data.len() {
+            let current = address + done as u64;
+            let skip = (current % WORD_SIZE as u64) as usize;
+            let len = (WORD_SIZE - skip).min(data.len() - done);
+
+            // Partial words keep the bytes around the written ones
+            let mut word = if len < WORD_SIZE {
+                self.peek(current - skip as u64)?
+            } else {
+                [0; WORD_SIZE]
+            };
+            word[skip..skip + len].copy_from_slice(&data[done..done + len]);
+            self.poke(current - skip as u64, word)?;
+            done += len;
+        }
+
+        Ok(())
+    }
+}
+
+/// `PTRACE_POKEDATA` stores a whole word at once: a `u32` is only written atomically where words
+/// are 32 bits, as storing it in a 64-bit word would revert concurrent stores to the other half.
+impl AtomicWriter for PtraceMem {
+    fn write_atomic_u32(&self, address: u64, value: u32) -> anyhow::Result<()> {
+        if WORD_SIZE != 4 {
+            bail!("PTRACE_POKEDATA cannot store 32 bits alone on this architecture");
+        }
+        check_alignment::<u32>(address)?;
+        if !audit_write(self.pid, address, &value.to_ne_bytes(), "ptrace", self) {
+            return Ok(());
+        }
+        let _guard = self.guard()?;
+
+        let mut word = [0; WORD_SIZE];
+        word[..4].copy_from_slice(&value.to_ne_bytes());
+        self.poke(address, word)
+    }
+
+    fn write_atomic_u64(&self, address: u64, value: u64) -> anyhow::Result<()> {
+        if WORD_SIZE != 8 {
+            bail!("PTRACE_POKEDATA cannot store 64 bits at once on this architecture");
+        }
+        check_alignment::<u64>(address)?;
+        if !audit_write(self.pid, address, &value.to_ne_bytes(), "ptrace", self) {
+            return Ok(());
+        }
+        let _guard = self.guard()?;
+
+        let mut word = [0; WORD_SIZE];
+        word.copy_from_slice(&value.to_ne_bytes()[..WORD_SIZE]);
+        self.poke(address, word)
+    }
+}
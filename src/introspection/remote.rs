@@ -0,0 +1,856 @@
+//! This module contains the remote calls, running functions or shellcode in a process through
+//! one of its threads traced by the inspector, e.g. to load a library.
+use std::{
+    ffi::CString,
+    io,
+    mem::ManuallyDrop,
+    ops::Range,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt, MemoryWriter},
+    arch::{Arch, Registers},
+    fpu::{get_regset, set_regset, FpRegisters},
+    pagemap::{page_size, Advice},
+    process::Pid,
+    ptrace::{PtraceSession, Stop},
+    segment::{Device, InodeId, Segment, SegmentType},
+    strace::syscall_number,
+    vm::ProcessVm,
+};
+
+/// Return address of the remote calls: returning to it faults, stopping the thread with a
+/// `SIGSEGV` at this address.
+const RETURN_ADDRESS: u64 = 0;
+
+/// Bytes below the stack pointer left untouched, as leaf functions may use them on x86_64.
+const RED_ZONE: u64 = 128;
+
+/// Register set of the system call number on aarch64, -1 outside of system calls.
+const NT_ARM_SYSTEM_CALL: libc::c_int = 0x404;
+
+/// Time given to shellcode by default, see [`Shellcode::with_timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between the checks of a thread running with a timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Bytes of code searched at once for a system call instruction.
+const SEARCH_CHUNK: u64 = 0x10000;
+
+/// `__RTLD_DLOPEN` of glibc, set in the mode of `__libc_dlopen_mode` by its internal callers
+/// along with the flags of `dlopen`.
+const RTLD_DLOPEN: u64 = 0x8000_0000;
+
+/// Signals raised by the processor, ending a remote call which faulted.
+const FAULTS: [libc::c_int; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGTRAP,
+];
+
+/// State of a stopped thread, restored once the code run in it returns.
+#[derive(Debug)]
+pub(crate) struct SavedState {
+    registers: Registers,
+    fp_registers: FpRegisters,
+    /// System call number on aarch64, kept out of the general purpose registers
+    syscall: Option<[u8; 4]>,
+    /// Signal of the stop the thread was in, sent again once restored
+    pending_signal: libc::c_int,
+}
+
+impl SavedState {
+    pub(crate) fn save(session: &PtraceSession) -> anyhow::Result<Self> {
+        let registers = session.registers()?;
+        let syscall = match registers {
+            Registers::Aarch64(_) => {
+                let mut syscall = [0; 4];
+                get_regset(session.tid(), NT_ARM_SYSTEM_CALL, &mut syscall)
+                    .context("Failed to read the system call number")?;
+                Some(syscall)
+            }
+            Registers::X86(_) | Registers::X86_64(_) => None,
+        };
+
+        Ok(SavedState {
+            registers,
+            fp_registers: session.fp_registers()?,
+            syscall,
+            pending_signal: session.pending_signal(),
+        })
+    }
+
+    pub(crate) fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Sets the registers of the thread to run other code from, keeping the kernel from
+    /// restarting the system call it was interrupted in.
+    pub(crate) fn prepare(
+        &self,
+        session: &PtraceSession,
+        registers: &mut Registers,
+    ) -> anyhow::Result<()> {
+        match registers {
+            Registers::X86(regs) => regs.orig_eax = u32::MAX,
+            Registers::X86_64(regs) => regs.orig_rax = u64::MAX,
+            Registers::Aarch64(_) => {
+                set_regset(session.tid(), NT_ARM_SYSTEM_CALL, &(-1i32).to_ne_bytes())
+                    .context("Failed to write the system call number")?;
+            }
+        }
+
+        session.set_registers(registers)
+    }
+
+    /// Restores the thread as it was when saved.
+    pub(crate) fn restore(&self, session: &PtraceSession) -> anyhow::Result<()> {
+        session.set_registers(&self.registers)?;
+        session.set_fp_registers(&self.fp_registers)?;
+        if let Some(syscall) = &self.syscall {
+            set_regset(session.tid(), NT_ARM_SYSTEM_CALL, syscall)
+                .context("Failed to write the system call number")?;
+        }
+        if self.pending_signal != 0 {
+            session.inject_signal(self.pending_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls the function at `function` of the process of the stopped thread of `session` with
+/// `args`, in the C calling convention of its architecture, and returns its return value.
+///
+/// The registers of the thread are saved and restored after the call, which returns to an
+/// invalid address to stop the thread. Signals received meanwhile are delivered, and a fault in
+/// the function is returned as an error. The thread must not be stopped inside a function
+/// holding a lock the called function takes, e.g. `malloc`'s, or the call deadlocks.
+pub fn call_remote(
+    session: &mut PtraceSession,
+    function: u64,
+    args: &[u64],
+) -> anyhow::Result<u64> {
+    call_with_data(session, function, &[], |_| args.to_vec(), None)
+}
+
+/// Calls the function at `function` like [`call_remote`], with `data` copied on the stack of the
+/// thread for the call, and the arguments built from its address. The thread is stopped if the
+/// function does not return within `timeout`.
+fn call_with_data(
+    session: &mut PtraceSession,
+    function: u64,
+    data: &[u8],
+    args: impl FnOnce(u64) -> Vec<u64>,
+    timeout: Option<Duration>,
+) -> anyhow::Result<u64> {
+    let saved = SavedState::save(session)?;
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let result = run_call(session, &saved, function, data, args, deadline);
+    let restored = saved.restore(session);
+    let value = result.with_context(|| format!("Remote call of {function:#x} failed"))?;
+    restored?;
+
+    Ok(value)
+}
+
+/// Start of the library mapped from the file `(device, inode)` among `segments`.
+fn library_base(segments: &[Segment], library: (Device, InodeId)) -> Option<u64> {
+    segments
+        .iter()
+        .find(|segment| {
+            segment.offset() == 0
+                && segment.device() == Some(library.0)
+                && segment.inode() == Some(library.1)
+        })
+        .map(Segment::start)
+}
+
+/// Address of the symbol `name` in the process `pid`, e.g. of a function of its C library.
+///
+/// The symbol is looked up in the inspector, and found at the same offset from the start of its
+/// library in the process, which must map the same file.
+pub fn remote_symbol(pid: Pid, name: &str) -> anyhow::Result<u64> {
+    let c_name = CString::new(name)?;
+    // SAFETY: the name is NUL-terminated
+    let local = unsafe { libc::dlsym(libc::RTLD_DEFAULT, c_name.as_ptr()) } as u64;
+    if local == 0 {
+        bail!("Symbol {name} not found in the inspector");
+    }
+
+    let segments = Segment::get_from_pid(std::process::id())?;
+    let library = segments
+        .iter()
+        .find(|segment| segment.contains(local))
+        .and_then(|segment| Some((segment.device()?, segment.inode()?)))
+        .ok_or_else(|| anyhow!("Symbol {name} is not in a mapped file"))?;
+    let local_base = library_base(&segments, library)
+        .ok_or_else(|| anyhow!("Library of {name} not found in the inspector"))?;
+    let remote_base = library_base(&Segment::get_from_pid(pid)?, library)
+        .ok_or_else(|| anyhow!("Library of {name} not mapped by process {pid}"))?;
+
+    Ok(remote_base + (local - local_base))
+}
+
+/// Loads the shared library at `path` in the process of the stopped thread of `session` with
+/// `dlopen`, and returns its handle, or the `dlerror` message as an error.
+///
+/// Paths with a directory are made absolute, others are searched like `dlopen` does. Before
+/// glibc 2.34, `dlopen` is in `libdl`: `__libc_dlopen_mode` is called instead when the process
+/// does not load it, with `__RTLD_DLOPEN` like glibc does, which leaves no error message. The
+/// same restrictions as for [`call_remote`] apply, e.g. the loader and `malloc` locks must not
+/// be held by the thread.
+pub fn inject_library(session: &mut PtraceSession, path: impl AsRef<Path>) -> anyhow::Result<u64> {
+    let path = path.as_ref();
+    // Opened from the working directory of the process otherwise
+    let path = match path.components().count() {
+        1 => path.to_path_buf(),
+        _ => std::path::absolute(path)?,
+    };
+    let path_bytes = CString::new(path.as_os_str().as_bytes())?;
+
+    let pid = session.tid();
+    let (dlopen, mode, dlerror) = match remote_symbol(pid, "dlopen") {
+        Ok(dlopen) => (
+            dlopen,
+            libc::RTLD_NOW as u64,
+            remote_symbol(pid, "dlerror").ok(),
+        ),
+        Err(error) => (
+            remote_symbol(pid, "__libc_dlopen_mode").map_err(|_| error)?,
+            libc::RTLD_NOW as u64 | RTLD_DLOPEN,
+            None,
+        ),
+    };
+    let handle = call_with_data(
+        session,
+        dlopen,
+        path_bytes.as_bytes_with_nul(),
+        |path| vec![path, mode],
+        None,
+    )?;
+    if handle != 0 {
+        return Ok(handle);
+    }
+
+    let message = match dlerror {
+        Some(dlerror) => match call_remote(session, dlerror, &[])? {
+            0 => None,
+            message => Some(session.memory().read_cstring(message, 4096)?.decode_lossy()),
+        },
+        None => None,
+    };
+    match message {
+        Some(message) => bail!("Failed to load {}: {message}", path.display()),
+        None => bail!("Failed to load {}", path.display()),
+    }
+}
+
+/// Machine code run in a thread of a traced process, see [`Shellcode::run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shellcode {
+    code: Vec<u8>,
+    args: Vec<u64>,
+    timeout: Duration,
+}
+
+impl Shellcode {
+    /// Shellcode running `code`, called as a function in the C calling convention of the
+    /// architecture of the thread: it takes its arguments and returns its return value the same
+    /// way, e.g. ending with `ret` on x86.
+    pub fn new(code: impl Into<Vec<u8>>) -> Self {
+        Shellcode {
+            code: code.into(),
+            args: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_args(mut self, args: &[u64]) -> Self {
+        self.args = args.to_vec();
+        self
+    }
+
+    /// Sets how long the code may run before the thread is stopped and restored, 5 seconds by
+    /// default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Runs the code in the stopped thread of `session`, e.g. one of a
+    /// [`crate::introspection::ptrace::FrozenProcess`], and returns its return value.
+    ///
+    /// The code is copied to a scratch region mapped like by [`remote_mmap`], and unmapped
+    /// afterwards. The thread is restored like by [`call_remote`] whether the code returned,
+    /// faulted or timed out.
+    pub fn run(&self, session: &mut PtraceSession) -> anyhow::Result<u64> {
+        let len = (self.code.len().max(1) as u64).next_multiple_of(page_size());
+        let prot = libc::PROT_READ | libc::PROT_EXEC;
+        let scratch = map_anonymous(session, 0, len, prot, libc::MAP_PRIVATE)?;
+
+        // Written through the read-only mapping like breakpoints
+        let result = session.memory().write(scratch, &self.code).and_then(|()| {
+            call_with_data(
+                session,
+                scratch,
+                &[],
+                |_| self.args.clone(),
+                Some(self.timeout),
+            )
+        });
+        let unmapped = remote_munmap(session, scratch, len);
+        let value = result.context("Shellcode failed")?;
+        unmapped?;
+
+        Ok(value)
+    }
+}
+
+/// Returns the address of the first occurrence of `instruction` aligned to `alignment` in
+/// `range` of `memory`, e.g. a system call instruction, reading it by chunks.
+pub fn find_instruction<M>(
+    memory: &M,
+    range: Range<u64>,
+    instruction: &[u8],
+    alignment: u64,
+) -> anyhow::Result<Option<u64>>
+where
+    M: MemoryReader + ?Sized,
+{
+    if instruction.is_empty() || alignment == 0 {
+        bail!("Invalid instruction or alignment");
+    }
+
+    let mut start = range.start.next_multiple_of(alignment);
+    while start < range.end && range.end - start >= instruction.len() as u64 {
+        // The chunks overlap so that no instruction is split between two of them
+        let len = (range.end - start).min(SEARCH_CHUNK);
+        let code = memory.read_bytes(start, len as usize)?;
+        if let Some(offset) = code
+            .windows(instruction.len())
+            .enumerate()
+            .step_by(alignment as usize)
+            .find_map(|(offset, bytes)| (bytes == instruction).then_some(offset))
+        {
+            return Ok(Some(start + offset as u64));
+        }
+        if start + len == range.end {
+            break;
+        }
+        start += len - instruction.len() as u64 + 1;
+        start = start.next_multiple_of(alignment);
+    }
+
+    Ok(None)
+}
+
+/// Returns the address of a system call instruction of the process of `session`, searched in
+/// the vDSO first, then in the executable mappings of files.
+fn find_syscall_instruction(session: &PtraceSession, arch: Arch) -> anyhow::Result<u64> {
+    let instruction = arch.syscall_instruction();
+    let alignment = arch.instruction_alignment();
+    let tid = session.tid();
+    let mut segments: Vec<Segment> = Segment::get_from_pid(tid)?
+        .into_iter()
+        .filter(|segment| {
+            segment.permissions().is_executable()
+                && (segment.path().is_some()
+                    || segment.segment_type() == Some(&SegmentType::SharedLibrary))
+        })
+        .collect();
+    segments.sort_by_key(|segment| segment.segment_type() != Some(&SegmentType::SharedLibrary));
+
+    let memory = ProcessVm::new(tid);
+    for segment in segments {
+        let range = segment.start()..segment.end();
+        let found = find_instruction(&memory, range.clone(), instruction, alignment)
+            .or_else(|_| find_instruction(&session.memory(), range, instruction, alignment));
+        if let Ok(Some(address)) = found {
+            return Ok(address);
+        }
+    }
+
+    bail!("No system call instruction found in process {tid}")
+}
+
+/// Executes the system call `number` with `args` in the stopped thread of `session`, and
+/// returns its return value, sign-extended for 32-bit processes so that errors read as
+/// `-errno`.
+///
+/// The thread executes a system call instruction found in the code of the process, which is
+/// not modified, so that other threads may keep running. Its registers are restored
+/// afterwards, and the signals it receives meanwhile are sent again to it.
+pub fn remote_syscall(
+    session: &mut PtraceSession,
+    number: u64,
+    args: [u64; 6],
+) -> anyhow::Result<u64> {
+    let arch = session.arch()?;
+    let instruction = find_syscall_instruction(session, arch)?;
+    let saved = SavedState::save(session)?;
+    let mut signals = Vec::new();
+    let result = run_syscall(session, &saved, instruction, number, args, &mut signals);
+    let restored = saved.restore(session);
+    for signal in signals {
+        session.inject_signal(signal)?;
+    }
+    let value = result.with_context(|| format!("Remote system call {number} failed"))?;
+    restored?;
+
+    Ok(value)
+}
+
+fn run_syscall(
+    session: &mut PtraceSession,
+    saved: &SavedState,
+    instruction: u64,
+    number: u64,
+    args: [u64; 6],
+    signals: &mut Vec<libc::c_int>,
+) -> anyhow::Result<u64> {
+    let mut registers = *saved.registers();
+    registers.set_syscall(number, args);
+    registers.set_pc(instruction);
+    saved.prepare(session, &mut registers)?;
+
+    loop {
+        session.step(0)?;
+        match session.wait()? {
+            Stop::Signal(libc::SIGTRAP) => {
+                session.suppress_signal();
+                break;
+            }
+            stop if stop.is_exit() => bail!("Thread {} ended: {stop:?}", session.tid()),
+            // Sent again once the thread is restored
+            Stop::Signal(signal) => {
+                signals.push(signal);
+                session.suppress_signal();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(session.registers()?.return_value())
+}
+
+/// Turns the return value of a system call into an error if it is `-errno`.
+fn syscall_result(value: u64) -> io::Result<u64> {
+    match value as i64 {
+        -4095..=-1 => Err(io::Error::from_raw_os_error(-(value as i64) as i32)),
+        _ => Ok(value),
+    }
+}
+
+/// Number of the system call `name` in the process of `session`.
+fn syscall(session: &PtraceSession, name: &str) -> anyhow::Result<u64> {
+    let arch = session.arch()?;
+    syscall_number(arch, name).ok_or_else(|| anyhow!("No system call {name} on {arch:?}"))
+}
+
+/// Maps `len` bytes of anonymous memory with the protection `prot` and the `flags` added to
+/// `MAP_ANONYMOUS` in the process of the stopped thread of `session`, and returns their
+/// address. `address` is a hint, or the address to map at with `MAP_FIXED_NOREPLACE`.
+pub(crate) fn map_anonymous(
+    session: &mut PtraceSession,
+    address: u64,
+    len: u64,
+    prot: libc::c_int,
+    flags: libc::c_int,
+) -> anyhow::Result<u64> {
+    // mmap2 on x86, which takes the offset in pages
+    let number = syscall(session, "mmap").or_else(|_| syscall(session, "mmap2"))?;
+    let args = [
+        address,
+        len,
+        prot as u64,
+        (flags | libc::MAP_ANONYMOUS) as u64,
+        // -1 as a 32-bit int
+        u32::MAX as u64,
+        0,
+    ];
+    let value = remote_syscall(session, number, args)?;
+    syscall_result(value).with_context(|| format!("Failed to map memory in {}", session.tid()))
+}
+
+/// Maps `len` bytes of private anonymous memory with the protection `prot`, e.g.
+/// `libc::PROT_READ | libc::PROT_EXEC`, in the process of the stopped thread of `session`, with
+/// an injected `mmap` system call, see [`remote_syscall`].
+///
+/// The memory can be written through [`PtraceSession::memory`] whatever its protection, and is
+/// unmapped when the returned guard is dropped.
+pub fn remote_mmap(
+    session: &mut PtraceSession,
+    len: u64,
+    prot: libc::c_int,
+) -> anyhow::Result<RemoteMapping<'_>> {
+    let address = map_anonymous(session, 0, len, prot, libc::MAP_PRIVATE)?;
+    Ok(RemoteMapping {
+        session,
+        address,
+        len,
+    })
+}
+
+/// Unmaps `len` bytes at `address` in the process of the stopped thread of `session`, with an
+/// injected `munmap` system call, see [`remote_syscall`].
+pub fn remote_munmap(session: &mut PtraceSession, address: u64, len: u64) -> anyhow::Result<()> {
+    let number = syscall(session, "munmap")?;
+    let value = remote_syscall(session, number, [address, len, 0, 0, 0, 0])?;
+    syscall_result(value)
+        .with_context(|| format!("Failed to unmap {address:#x} in {}", session.tid()))?;
+
+    Ok(())
+}
+
+/// Memory mapped in a process by [`remote_mmap`], unmapped when dropped, through the session
+/// it borrows.
+#[derive(Debug)]
+pub struct RemoteMapping<'a> {
+    session: &'a mut PtraceSession,
+    address: u64,
+    len: u64,
+}
+
+impl RemoteMapping<'_> {
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn session(&self) -> &PtraceSession {
+        self.session
+    }
+
+    /// The session of the thread, e.g. to write the memory or run code in it.
+    pub fn session_mut(&mut self) -> &mut PtraceSession {
+        self.session
+    }
+
+    /// Unmaps the memory, reporting failures unlike dropping the guard.
+    pub fn unmap(self) -> anyhow::Result<()> {
+        let mut mapping = ManuallyDrop::new(self);
+        let (address, len) = (mapping.address, mapping.len);
+        remote_munmap(mapping.session, address, len)
+    }
+
+    /// Keeps the memory mapped, returning its address.
+    pub fn leak(self) -> u64 {
+        ManuallyDrop::new(self).address
+    }
+}
+
+impl Drop for RemoteMapping<'_> {
+    fn drop(&mut self) {
+        let (address, len) = (self.address, self.len);
+        while_stopped(self.session, |session| {
+            let _ = remote_munmap(session, address, len);
+        });
+    }
+}
+
+/// Runs `f` with the thread of `session` stopped, resuming it afterwards if it was running,
+/// e.g. when a guard is dropped after the thread was resumed.
+fn while_stopped(session: &mut PtraceSession, f: impl FnOnce(&mut PtraceSession)) {
+    let running = !session.is_stopped();
+    if session.stop().is_ok() {
+        f(session);
+        if running {
+            let _ = session.cont(0);
+        }
+    }
+}
+
+/// `range` extended to whole pages.
+fn page_range(range: Range<u64>) -> Range<u64> {
+    let page_size = page_size();
+    range.start / page_size * page_size..range.end.next_multiple_of(page_size)
+}
+
+/// Changes the protection of the pages of `range` to `prot`, e.g. to make code writable, in
+/// the process of the stopped thread of `session`, with an injected `mprotect` system call.
+fn mprotect(
+    session: &mut PtraceSession,
+    range: &Range<u64>,
+    prot: libc::c_int,
+) -> anyhow::Result<()> {
+    let number = syscall(session, "mprotect")?;
+    let args = [range.start, range.end - range.start, prot as u64, 0, 0, 0];
+    let value = remote_syscall(session, number, args)?;
+    syscall_result(value).with_context(|| {
+        format!(
+            "Failed to protect {:#x}-{:#x} in {}",
+            range.start,
+            range.end,
+            session.tid()
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Changes the protection of the pages of `range` to `prot`, e.g.
+/// `libc::PROT_READ | libc::PROT_WRITE` to patch code, in the process of the stopped thread of
+/// `session`, with an injected `mprotect` system call, see [`remote_syscall`].
+///
+/// The range is extended to whole pages, which must all be mapped. The returned guard restores
+/// the protections of the mappings it covered when dropped.
+pub fn remote_mprotect(
+    session: &mut PtraceSession,
+    range: Range<u64>,
+    prot: libc::c_int,
+) -> anyhow::Result<RemoteProtection<'_>> {
+    let range = page_range(range);
+    let mut original = Vec::new();
+    let mut end = range.start;
+    for segment in Segment::get_from_pid(session.tid())? {
+        if segment.end() <= range.start || segment.start() >= range.end {
+            continue;
+        }
+        if segment.start() > end {
+            break;
+        }
+        end = segment.end().min(range.end);
+        let start = segment.start().max(range.start);
+        original.push((start..end, segment.permissions().to_prot()));
+    }
+    if end < range.end {
+        bail!("Unmapped memory at {end:#x} in {}", session.tid());
+    }
+
+    mprotect(session, &range, prot)?;
+    Ok(RemoteProtection { session, original })
+}
+
+/// Protections changed by [`remote_mprotect`], restored when dropped, through the session it
+/// borrows.
+#[derive(Debug)]
+pub struct RemoteProtection<'a> {
+    session: &'a mut PtraceSession,
+    /// Ranges of the mappings covered, with their protections
+    original: Vec<(Range<u64>, libc::c_int)>,
+}
+
+impl RemoteProtection<'_> {
+    /// Ranges whose protections are restored, with their original protections.
+    pub fn original(&self) -> &[(Range<u64>, libc::c_int)] {
+        &self.original
+    }
+
+    pub fn session(&self) -> &PtraceSession {
+        self.session
+    }
+
+    /// The session of the thread, e.g. to write the memory while its protection is changed.
+    pub fn session_mut(&mut self) -> &mut PtraceSession {
+        self.session
+    }
+
+    /// Restores the original protections, reporting failures unlike dropping the guard.
+    pub fn restore(mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for (range, prot) in std::mem::take(&mut self.original) {
+            if let Err(error) = mprotect(self.session, &range, prot) {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+
+    /// Keeps the new protections.
+    pub fn keep(mut self) {
+        self.original.clear();
+    }
+}
+
+impl Drop for RemoteProtection<'_> {
+    fn drop(&mut self) {
+        if !self.original.is_empty() {
+            let original = std::mem::take(&mut self.original);
+            while_stopped(self.session, |session| {
+                for (range, prot) in original {
+                    let _ = mprotect(session, &range, prot);
+                }
+            });
+        }
+    }
+}
+
+/// Writes the return address and arguments of a call on the stack, below `sp`, and returns the
+/// stack pointer at the entry of the function.
+fn push_frame(
+    session: &PtraceSession,
+    mut sp: u64,
+    word_size: u64,
+    return_address: Option<u64>,
+    stack_args: &[u64],
+) -> anyhow::Result<u64> {
+    // The arguments start 16-byte aligned, above the return address
+    sp = (sp - word_size * stack_args.len() as u64) & !15;
+    let memory = session.memory();
+    for (i, arg) in stack_args.iter().enumerate() {
+        let bytes = arg.to_ne_bytes();
+        memory.write(sp + i as u64 * word_size, &bytes[..word_size as usize])?;
+    }
+    if let Some(return_address) = return_address {
+        sp -= word_size;
+        memory.write(sp, &return_address.to_ne_bytes()[..word_size as usize])?;
+    }
+
+    Ok(sp)
+}
+
+fn run_call(
+    session: &mut PtraceSession,
+    saved: &SavedState,
+    function: u64,
+    data: &[u8],
+    args: impl FnOnce(u64) -> Vec<u64>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<u64> {
+    let mut registers = *saved.registers();
+    let data_address = (registers.sp() - RED_ZONE - data.len() as u64) & !15;
+    if !data.is_empty() {
+        session.memory().write(data_address, data)?;
+    }
+    let args = &args(data_address)[..];
+    let sp = data_address;
+    match &mut registers {
+        Registers::X86(regs) => {
+            if args.iter().any(|arg| *arg > u32::MAX as u64) || function > u32::MAX as u64 {
+                bail!("Arguments of 32-bit functions must fit in 32 bits");
+            }
+            regs.esp = push_frame(session, sp, 4, Some(RETURN_ADDRESS), args)? as u32;
+            regs.eip = function as u32;
+        }
+        Registers::X86_64(regs) => {
+            let (register_args, stack_args) = args.split_at(args.len().min(6));
+            let mut values = [0; 6];
+            values[..register_args.len()].copy_from_slice(register_args);
+            [regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9] = values;
+            // No vector register argument for variadic functions
+            regs.rax = 0;
+            regs.rsp = push_frame(session, sp, 8, Some(RETURN_ADDRESS), stack_args)?;
+            regs.rip = function;
+        }
+        Registers::Aarch64(regs) => {
+            let (register_args, stack_args) = args.split_at(args.len().min(8));
+            regs.regs[..register_args.len()].copy_from_slice(register_args);
+            regs.regs[30] = RETURN_ADDRESS;
+            regs.sp = push_frame(session, sp, 8, None, stack_args)?;
+            regs.pc = function;
+        }
+    }
+    saved.prepare(session, &mut registers)?;
+
+    loop {
+        session.cont(session.pending_signal())?;
+        let stop = match deadline {
+            Some(deadline) => wait_until(session, deadline)?,
+            None => session.wait()?,
+        };
+        match stop {
+            Stop::Signal(libc::SIGSEGV) if session.registers()?.pc() == RETURN_ADDRESS => {
+                session.suppress_signal();
+                return Ok(match session.registers()? {
+                    // Not sign-extended, e.g. for pointers
+                    Registers::X86(regs) => regs.eax as u64,
+                    registers => registers.return_value(),
+                });
+            }
+            Stop::Signal(signal) if FAULTS.contains(&signal) => {
+                session.suppress_signal();
+                bail!("Signal {signal} raised at {:#x}", session.registers()?.pc());
+            }
+            stop if stop.is_exit() => bail!("Thread {} ended: {stop:?}", session.tid()),
+            // Other signals are delivered when resuming
+            _ => {}
+        }
+    }
+}
+
+/// Waits for the thread of `session` to stop until `deadline`, then stops it and fails.
+fn wait_until(session: &mut PtraceSession, deadline: Instant) -> anyhow::Result<Stop> {
+    loop {
+        if let Some(stop) = session.try_wait()? {
+            return Ok(stop);
+        }
+        if Instant::now() >= deadline {
+            session.stop()?;
+            bail!("Thread {} timed out", session.tid());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Injects the system call `name` taking the start and length of the pages of `range` in the
+/// process of the stopped thread of `session`, then `args`.
+fn range_syscall(
+    session: &mut PtraceSession,
+    name: &str,
+    range: Range<u64>,
+    args: [u64; 4],
+) -> anyhow::Result<()> {
+    let range = page_range(range);
+    let number = syscall(session, name)?;
+    let [a, b, c, d] = args;
+    let value = remote_syscall(
+        session,
+        number,
+        [range.start, range.end - range.start, a, b, c, d],
+    )?;
+    syscall_result(value).with_context(|| {
+        format!(
+            "Failed to {name} {:#x}-{:#x} in {}",
+            range.start,
+            range.end,
+            session.tid()
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Gives `advice` on the pages of `range` in the process of the stopped thread of `session`,
+/// e.g. to free or reclaim them, with an injected `madvise` system call, see
+/// [`remote_syscall`].
+pub fn remote_madvise(
+    session: &mut PtraceSession,
+    range: Range<u64>,
+    advice: Advice,
+) -> anyhow::Result<()> {
+    range_syscall(session, "madvise", range, [advice.to_raw() as u64, 0, 0, 0])
+}
+
+/// Locks the pages of `range` in RAM in the process of the stopped thread of `session`, with
+/// an injected `mlock` system call, see [`remote_syscall`]. It fails past the
+/// `RLIMIT_MEMLOCK` limit of the process, unless it has `CAP_IPC_LOCK`.
+pub fn remote_mlock(session: &mut PtraceSession, range: Range<u64>) -> anyhow::Result<()> {
+    range_syscall(session, "mlock", range, [0; 4])
+}
+
+/// Unlocks the pages of `range` locked with [`remote_mlock`] in the process of the stopped
+/// thread of `session`, with an injected `munlock` system call.
+pub fn remote_munlock(session: &mut PtraceSession, range: Range<u64>) -> anyhow::Result<()> {
+    range_syscall(session, "munlock", range, [0; 4])
+}
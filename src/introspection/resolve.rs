@@ -0,0 +1,160 @@
+//! This module contains the resolution of addresses to the symbols of the modules of a process,
+//! e.g. `libc.so.6!malloc+0x10`, and of symbols to their addresses.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+
+use crate::introspection::{
+    elf::Module,
+    imports::Import,
+    process::Pid,
+    segment::{InodeId, Segment},
+    symbol::{Symbol, SymbolBinding, SymbolTable},
+    vm::ProcessVm,
+};
+
+/// Symbol tables by module, a failure to load one being kept as its message.
+type Tables = HashMap<(u64, InodeId), Result<Arc<SymbolTable>, String>>;
+
+/// Resolves addresses to symbols and symbols to addresses in the modules of a process, as of
+/// the last refresh of its segments.
+///
+/// The symbol table of a module is loaded the first time it is needed, then kept until the
+/// module is unmapped.
+#[derive(Debug)]
+pub struct SymbolResolver {
+    pid: Pid,
+    memory: ProcessVm,
+    /// Modules sorted by base
+    modules: Vec<Module>,
+    tables: Mutex<Tables>,
+}
+
+impl SymbolResolver {
+    /// A resolver of the modules of the process `pid` among its `segments`.
+    pub fn new(pid: Pid, segments: &[Segment]) -> Self {
+        SymbolResolver {
+            pid,
+            memory: ProcessVm::new(pid),
+            modules: Module::group(segments),
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// Reads the segments of the process again, e.g. after it loaded a library, keeping the
+    /// symbol tables of the modules still mapped.
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        self.modules = Module::group(&Segment::get_from_pid(self.pid)?);
+
+        let keys: Vec<_> = self.modules.iter().map(key).collect();
+        self.tables
+            .get_mut()
+            .map_err(|_| anyhow!("Symbol cache is poisoned"))?
+            .retain(|module, _| keys.contains(module));
+        Ok(())
+    }
+
+    /// The module `address` is in, if any.
+    pub fn module_at(&self, address: u64) -> Option<&Module> {
+        self.modules.iter().find(|module| module.contains(address))
+    }
+
+    /// The module named `name`, e.g. `libc.so.6`, or whose path is `name`.
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules
+            .iter()
+            .find(|module| module.name() == name || module.path().as_os_str() == name)
+    }
+
+    /// The module providing `import`, the one its address is in, e.g. to find which library a
+    /// module takes `memcpy` from. `None` for an import bound lazily not resolved yet.
+    pub fn provider(&self, import: &Import) -> Option<&Module> {
+        self.module_at(import.address())
+            .filter(|module| !module.contains(import.slot()))
+    }
+
+    /// The symbol table of `module`, loaded on first use, see [`SymbolTable::load`].
+    pub fn symbol_table(&self, module: &Module) -> anyhow::Result<Arc<SymbolTable>> {
+        let mut tables = self
+            .tables
+            .lock()
+            .map_err(|_| anyhow!("Symbol cache is poisoned"))?;
+        tables
+            .entry(key(module))
+            .or_insert_with(|| {
+                SymbolTable::load(module, self.pid, &self.memory)
+                    .map(Arc::new)
+                    .map_err(|error| format!("{error:#}"))
+            })
+            .clone()
+            .map_err(|error| anyhow!(error))
+    }
+
+    /// The module and symbol `address` is in, with the offset of the address from the symbol,
+    /// e.g. to name the function of a return address.
+    pub fn resolve_address(&self, address: u64) -> Option<(&Module, Symbol, u64)> {
+        let module = self.module_at(address)?;
+        let table = self.symbol_table(module).ok()?;
+        let (symbol, offset) = table.symbol_at(address)?;
+
+        Some((module, symbol.clone(), offset))
+    }
+
+    /// Formats `address` as `module!symbol+offset`, e.g. `libc.so.6!malloc+0x10`, or
+    /// `module+offset` from the base of its module if no symbol contains it.
+    pub fn format_address(&self, address: u64) -> Option<String> {
+        if let Some((module, symbol, offset)) = self.resolve_address(address) {
+            return Some(match offset {
+                0 => format!("{}!{}", module.name(), symbol.demangled()),
+                offset => format!("{}!{}+{offset:#x}", module.name(), symbol.demangled()),
+            });
+        }
+
+        let module = self.module_at(address)?;
+        Some(format!("{}+{:#x}", module.name(), address - module.base()))
+    }
+
+    /// The address of a symbol named `module!symbol`, e.g. `libc.so.6!malloc`, or of the first
+    /// global symbol named `symbol` among all the modules. The symbol name is matched mangled or
+    /// demangled.
+    pub fn resolve_symbol(&self, name: &str) -> Option<u64> {
+        if let Some((module, symbol)) = name.split_once('!') {
+            let table = self.symbol_table(self.module(module)?).ok()?;
+            return table.get(symbol).map(Symbol::address);
+        }
+
+        let mut found = None;
+        for module in &self.modules {
+            let Ok(table) = self.symbol_table(module) else {
+                continue;
+            };
+            match table.get(name) {
+                Some(symbol) if symbol.binding() != SymbolBinding::Local => {
+                    return Some(symbol.address())
+                }
+                Some(symbol) => {
+                    found.get_or_insert(symbol.address());
+                }
+                None => {}
+            }
+        }
+
+        found
+    }
+}
+
+/// Key of the symbol table of `module`, which stays the same until the module is unmapped.
+fn key(module: &Module) -> (u64, InodeId) {
+    (module.base(), module.inode())
+}
@@ -0,0 +1,1402 @@
+//! This module contains the value scanner, searching the memory of a process for typed values.
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    fmt,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc,
+    },
+};
+
+use anyhow::bail;
+use memchr::memmem::Finder;
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt},
+    arch::Bitness,
+    location::{Annotation, Locator},
+    pattern::Pattern,
+    pointer::{Pointer, PointerMap},
+    segment::Segment,
+    strings::{FoundString, StringExtractor, StringOptions},
+};
+
+/// Default size of the memory read at once by a [`Scanner`].
+const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+
+/// Default number of refinements of a [`ScanResults`] that can be undone.
+const DEFAULT_UNDO_DEPTH: usize = 8;
+
+/// Maximum span of the candidates read at once when refining a [`ScanResults`].
+const REFINE_WINDOW: u64 = 64 << 10;
+
+/// Type of the values searched by a [`Scanner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    /// Byte string of the given length.
+    Bytes(usize),
+}
+
+impl ValueType {
+    /// Size of the values in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            ValueType::I8 | ValueType::U8 => 1,
+            ValueType::I16 | ValueType::U16 => 2,
+            ValueType::I32 | ValueType::U32 | ValueType::F32 => 4,
+            ValueType::I64 | ValueType::U64 | ValueType::F64 => 8,
+            ValueType::Bytes(len) => *len,
+        }
+    }
+
+    /// Natural alignment of the values, 1 for byte strings.
+    pub fn alignment(&self) -> usize {
+        match self {
+            ValueType::Bytes(_) => 1,
+            _ => self.size(),
+        }
+    }
+}
+
+/// A value searched or found by a scan, stored in the target in native byte order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanValue {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Bytes(Vec<u8>),
+}
+
+macro_rules! scan_values {
+    (ints: $($int:ident($int_type:ty)),*; floats: $($float:ident($float_type:ty)),*) => {
+        impl ScanValue {
+            pub fn value_type(&self) -> ValueType {
+                match self {
+                    $(ScanValue::$int(_) => ValueType::$int,)*
+                    $(ScanValue::$float(_) => ValueType::$float,)*
+                    ScanValue::Bytes(bytes) => ValueType::Bytes(bytes.len()),
+                }
+            }
+
+            /// Bytes of the value as stored in memory.
+            pub fn to_bytes(&self) -> Vec<u8> {
+                match self {
+                    $(ScanValue::$int(value) => value.to_ne_bytes().to_vec(),)*
+                    $(ScanValue::$float(value) => value.to_ne_bytes().to_vec(),)*
+                    ScanValue::Bytes(bytes) => bytes.clone(),
+                }
+            }
+
+            /// Decodes a value of type `value_type` from its bytes in memory.
+            pub fn from_bytes(value_type: ValueType, bytes: &[u8]) -> anyhow::Result<Self> {
+                if bytes.len() != value_type.size() {
+                    bail!(
+                        "Expected {} bytes for a {value_type:?}, got {}",
+                        value_type.size(),
+                        bytes.len()
+                    );
+                }
+                Ok(match value_type {
+                    $(ValueType::$int => {
+                        ScanValue::$int(<$int_type>::from_ne_bytes(bytes.try_into()?))
+                    })*
+                    $(ValueType::$float => {
+                        ScanValue::$float(<$float_type>::from_ne_bytes(bytes.try_into()?))
+                    })*
+                    ValueType::Bytes(_) => ScanValue::Bytes(bytes.to_vec()),
+                })
+            }
+
+            /// Adds `delta` of the same type, wrapping around for integers. `None` for byte
+            /// strings or values of different types.
+            fn add(&self, delta: &ScanValue) -> Option<ScanValue> {
+                match (self, delta) {
+                    $((ScanValue::$int(a), ScanValue::$int(b)) => {
+                        Some(ScanValue::$int(a.wrapping_add(*b)))
+                    })*
+                    $((ScanValue::$float(a), ScanValue::$float(b)) => {
+                        Some(ScanValue::$float(a + b))
+                    })*
+                    _ => None,
+                }
+            }
+
+            /// The value as a float, rounded for large 64-bit integers. `None` for byte strings.
+            fn to_f64(&self) -> Option<f64> {
+                match self {
+                    $(ScanValue::$int(value) => Some(*value as f64),)*
+                    $(ScanValue::$float(value) => Some(*value as f64),)*
+                    ScanValue::Bytes(_) => None,
+                }
+            }
+
+            /// Subtracts `delta` of the same type, wrapping around for integers.
+            fn sub(&self, delta: &ScanValue) -> Option<ScanValue> {
+                match (self, delta) {
+                    $((ScanValue::$int(a), ScanValue::$int(b)) => {
+                        Some(ScanValue::$int(a.wrapping_sub(*b)))
+                    })*
+                    $((ScanValue::$float(a), ScanValue::$float(b)) => {
+                        Some(ScanValue::$float(a - b))
+                    })*
+                    _ => None,
+                }
+            }
+        }
+
+        /// Only values of the same numeric type are ordered.
+        impl PartialOrd for ScanValue {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                match (self, other) {
+                    $((ScanValue::$int(a), ScanValue::$int(b)) => a.partial_cmp(b),)*
+                    $((ScanValue::$float(a), ScanValue::$float(b)) => a.partial_cmp(b),)*
+                    (ScanValue::Bytes(a), ScanValue::Bytes(b)) if a == b => Some(Ordering::Equal),
+                    _ => None,
+                }
+            }
+        }
+
+        $(
+            impl From<$int_type> for ScanValue {
+                fn from(value: $int_type) -> Self {
+                    ScanValue::$int(value)
+                }
+            }
+        )*
+        $(
+            impl From<$float_type> for ScanValue {
+                fn from(value: $float_type) -> Self {
+                    ScanValue::$float(value)
+                }
+            }
+        )*
+    };
+}
+
+scan_values!(
+    ints: I8(i8), I16(i16), I32(i32), I64(i64), U8(u8), U16(u16), U32(u32), U64(u64);
+    floats: F32(f32), F64(f64)
+);
+
+impl From<Vec<u8>> for ScanValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        ScanValue::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for ScanValue {
+    fn from(bytes: &[u8]) -> Self {
+        ScanValue::Bytes(bytes.to_vec())
+    }
+}
+
+/// Condition on the current value of a candidate, compared to the value it had at the previous
+/// scan, to refine a [`ScanResults`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// The value is now exactly the given one, compared bytewise.
+    Equal(ScanValue),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    /// The value grew by exactly the given amount, of the same type as the scanned values.
+    IncreasedBy(ScanValue),
+    /// The value shrank by exactly the given amount, of the same type as the scanned values.
+    DecreasedBy(ScanValue),
+    /// The value is within `epsilon` of the given one, e.g. a float recomputed every frame.
+    NearlyEqual {
+        value: ScanValue,
+        epsilon: f64,
+    },
+    /// The value is between the two given ones, both included.
+    Between(ScanValue, ScanValue),
+    /// The bits of the value set in `mask` are those of `value`, e.g. a flag in a bitfield.
+    Masked {
+        value: ScanValue,
+        mask: ScanValue,
+    },
+    /// The value is an address in one of the ranges, built by [`Predicate::points_into`].
+    PointsInto(Vec<Range<u64>>),
+}
+
+impl Predicate {
+    /// Fails if the predicate cannot be evaluated on values of type `value_type`.
+    fn check(&self, value_type: ValueType) -> anyhow::Result<()> {
+        match self {
+            Predicate::Equal(value)
+            | Predicate::IncreasedBy(value)
+            | Predicate::DecreasedBy(value)
+                if value.value_type() != value_type =>
+            {
+                bail!(
+                    "Cannot compare a {:?} to {value_type:?} values",
+                    value.value_type()
+                )
+            }
+            Predicate::NearlyEqual { value, .. } | Predicate::Masked { value, .. }
+                if value.value_type() != value_type =>
+            {
+                bail!(
+                    "Cannot compare a {:?} to {value_type:?} values",
+                    value.value_type()
+                )
+            }
+            Predicate::Between(min, max)
+                if min.value_type() != value_type || max.value_type() != value_type =>
+            {
+                bail!(
+                    "Cannot compare a range of {:?} to {value_type:?} values",
+                    min.value_type()
+                )
+            }
+            Predicate::Masked { mask, .. } if mask.value_type() != value_type => {
+                bail!(
+                    "Cannot mask {value_type:?} values with a {:?}",
+                    mask.value_type()
+                )
+            }
+            Predicate::Increased
+            | Predicate::Decreased
+            | Predicate::IncreasedBy(_)
+            | Predicate::DecreasedBy(_)
+            | Predicate::NearlyEqual { .. }
+            | Predicate::Between(..)
+                if matches!(value_type, ValueType::Bytes(_)) =>
+            {
+                bail!("Byte strings can only be compared for equality")
+            }
+            Predicate::NearlyEqual { epsilon, .. } if epsilon.is_nan() || *epsilon < 0.0 => {
+                bail!("Invalid epsilon {epsilon}")
+            }
+            Predicate::Between(min, max) if min > max => {
+                bail!("Empty range from {min:?} to {max:?}")
+            }
+            Predicate::PointsInto(_)
+                if !matches!(
+                    value_type,
+                    ValueType::U32 | ValueType::U64 | ValueType::I32 | ValueType::I64
+                ) =>
+            {
+                bail!("Pointers cannot be stored in {value_type:?} values")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Matches the pointers to the segments among `segments` for which `filter` returns
+    /// `true`, e.g. to the heap. Pointers are scanned as [`ValueType::U64`] or
+    /// [`ValueType::U32`] values, depending on the bitness of the process.
+    pub fn points_into<'a>(
+        segments: impl IntoIterator<Item = &'a Segment>,
+        filter: impl Fn(&Segment) -> bool,
+    ) -> Self {
+        let mut ranges: Vec<Range<u64>> = segments
+            .into_iter()
+            .filter(|segment| filter(segment))
+            .map(|segment| segment.start()..segment.end())
+            .collect();
+        ranges.sort_unstable_by_key(|range| range.start);
+        // Adjacent segments are merged to keep the lookup short
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        Predicate::PointsInto(merged)
+    }
+
+    /// Whether a value read as `new` and previously as `old` matches the predicate.
+    fn matches(&self, value_type: ValueType, old: &[u8], new: &[u8]) -> bool {
+        let decode = |bytes| ScanValue::from_bytes(value_type, bytes).ok();
+        match self {
+            Predicate::Equal(value) => new == value.to_bytes(),
+            Predicate::Changed => old != new,
+            Predicate::Unchanged => old == new,
+            Predicate::Increased => decode(new) > decode(old),
+            Predicate::Decreased => decode(new) < decode(old),
+            Predicate::IncreasedBy(delta) => decode(old)
+                .and_then(|old| old.add(delta))
+                .is_some_and(|expected| expected.to_bytes() == new),
+            Predicate::DecreasedBy(delta) => decode(old)
+                .and_then(|old| old.sub(delta))
+                .is_some_and(|expected| expected.to_bytes() == new),
+            Predicate::NearlyEqual { value, epsilon } => decode(new)
+                .and_then(|new| Some(new.to_f64()? - value.to_f64()?))
+                .is_some_and(|difference| difference.abs() <= *epsilon),
+            Predicate::Between(min, max) => {
+                decode(new).is_some_and(|new| *min <= new && new <= *max)
+            }
+            Predicate::Masked { value, mask } => new
+                .iter()
+                .zip(value.to_bytes())
+                .zip(mask.to_bytes())
+                .all(|((new, value), mask)| new & mask == value & mask),
+            Predicate::PointsInto(ranges) => {
+                let address = match new.len() {
+                    4 => u32::from_ne_bytes(new.try_into().unwrap_or_default()).into(),
+                    _ => u64::from_ne_bytes(new.try_into().unwrap_or_default()),
+                };
+                let index = ranges.partition_point(|range| range.end <= address);
+                ranges
+                    .get(index)
+                    .is_some_and(|range| range.contains(&address))
+            }
+        }
+    }
+}
+
+/// Candidates of a scan and their values at the last scan.
+#[derive(Debug, Clone, PartialEq)]
+struct Candidates {
+    addresses: Vec<u64>,
+    /// Values of `addresses` back to back, `ValueType::size` bytes each
+    values: Vec<u8>,
+}
+
+impl Candidates {
+    /// Sorts the candidates by address, with their values of `size` bytes.
+    fn sort(&mut self, size: usize) {
+        if self.addresses.is_sorted() {
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.addresses.len()).collect();
+        order.sort_by_key(|index| self.addresses[*index]);
+        self.addresses = order.iter().map(|index| self.addresses[*index]).collect();
+        self.values = order
+            .iter()
+            .flat_map(|index| &self.values[index * size..(index + 1) * size])
+            .copied()
+            .collect();
+    }
+
+    fn relocate(&mut self, relocate: &impl Fn(u64) -> u64, size: usize) {
+        for address in &mut self.addresses {
+            *address = relocate(*address);
+        }
+        self.sort(size);
+    }
+}
+
+/// Addresses found by a scan, along with the value read at each of them.
+///
+/// The results are narrowed down by refining them with a [`Predicate`] while the target runs,
+/// and the last refinements can be undone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResults {
+    value_type: ValueType,
+    addresses: Vec<u64>,
+    /// Values of `addresses` back to back, `value_type.size()` bytes each
+    values: Vec<u8>,
+    /// Candidates before each refinement, the most recent last
+    history: VecDeque<Candidates>,
+    undo_depth: usize,
+}
+
+impl ScanResults {
+    pub fn new(value_type: ValueType) -> Self {
+        ScanResults {
+            value_type,
+            addresses: Vec::new(),
+            values: Vec::new(),
+            history: VecDeque::new(),
+            undo_depth: DEFAULT_UNDO_DEPTH,
+        }
+    }
+
+    /// Sets how many refinements can be undone, 8 by default. Older ones are forgotten.
+    pub fn with_undo_depth(mut self, depth: usize) -> Self {
+        self.undo_depth = depth;
+        let excess = self.history.len().saturating_sub(depth);
+        self.history.drain(..excess);
+        self
+    }
+
+    /// Builds results from addresses and their values back to back, in any order.
+    pub(crate) fn from_parts(
+        value_type: ValueType,
+        addresses: Vec<u64>,
+        values: Vec<u8>,
+    ) -> anyhow::Result<Self> {
+        if values.len() != addresses.len() * value_type.size() {
+            bail!(
+                "Expected {} bytes of values for {} addresses, got {}",
+                addresses.len() * value_type.size(),
+                addresses.len(),
+                values.len()
+            );
+        }
+        let mut results = ScanResults::new(value_type);
+        let mut candidates = Candidates { addresses, values };
+        candidates.sort(value_type.size());
+        results.addresses = candidates.addresses;
+        results.values = candidates.values;
+        Ok(results)
+    }
+
+    /// Values of the addresses back to back.
+    pub(crate) fn raw_values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// Moves the candidates, including the ones of the refinements that can be undone, to the
+    /// addresses returned by `relocate`.
+    pub(crate) fn relocate(&mut self, relocate: impl Fn(u64) -> u64) {
+        let size = self.value_type.size();
+        let mut candidates = Candidates {
+            addresses: std::mem::take(&mut self.addresses),
+            values: std::mem::take(&mut self.values),
+        };
+        candidates.relocate(&relocate, size);
+        self.addresses = candidates.addresses;
+        self.values = candidates.values;
+        for candidates in &mut self.history {
+            candidates.relocate(&relocate, size);
+        }
+    }
+
+    fn push(&mut self, address: u64, value: &[u8]) {
+        self.addresses.push(address);
+        self.values.extend_from_slice(value);
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    /// Addresses found, in increasing order.
+    pub fn addresses(&self) -> &[u64] {
+        &self.addresses
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.addresses.binary_search(&address).is_ok()
+    }
+
+    /// Bytes of the value found at the `index`th address.
+    pub fn value_bytes(&self, index: usize) -> Option<&[u8]> {
+        let size = self.value_type.size();
+        self.values.get(index * size..(index + 1) * size)
+    }
+
+    /// Value found at the `index`th address.
+    pub fn value(&self, index: usize) -> Option<ScanValue> {
+        let bytes = self.value_bytes(index)?;
+        ScanValue::from_bytes(self.value_type, bytes).ok()
+    }
+
+    /// Iterates over the addresses found and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, ScanValue)> + '_ {
+        (0..self.len()).filter_map(|index| Some((self.addresses[index], self.value(index)?)))
+    }
+
+    /// Resolves the addresses found to their module and offset through `locator`, in the order
+    /// of [`Self::addresses`]. Addresses no longer mapped are `None`.
+    pub fn annotate<'a>(
+        &'a self,
+        locator: &'a Locator,
+    ) -> impl Iterator<Item = Option<Annotation>> + 'a {
+        self.addresses
+            .iter()
+            .map(|address| locator.annotate(*address))
+    }
+
+    /// Reads the candidates again through `reader`, and only keeps the ones whose value matches
+    /// `predicate`. Candidates no longer readable are dropped.
+    pub fn refine<R: MemoryReader + ?Sized>(
+        &mut self,
+        reader: &R,
+        predicate: &Predicate,
+    ) -> anyhow::Result<()> {
+        predicate.check(self.value_type)?;
+
+        let size = self.value_type.size();
+        let mut refined = Candidates {
+            addresses: Vec::new(),
+            values: Vec::new(),
+        };
+        let mut buffer = Vec::new();
+        let mut index = 0;
+        while index < self.addresses.len() {
+            // Reads the nearby candidates at once
+            let start = self.addresses[index];
+            let count = self.addresses[index..]
+                .iter()
+                .take_while(|address| **address + size as u64 - start <= REFINE_WINDOW)
+                .count()
+                .max(1);
+            let end = self.addresses[index + count - 1] + size as u64;
+            buffer.resize((end - start) as usize, 0);
+            let window = reader.read(start, &mut buffer).is_ok();
+
+            for index in index..index + count {
+                let address = self.addresses[index];
+                let offset = (address - start) as usize;
+                let new = &mut buffer[offset..offset + size];
+                if !window && reader.read(address, new).is_err() {
+                    continue;
+                }
+                let old = &self.values[index * size..(index + 1) * size];
+                if predicate.matches(self.value_type, old, new) {
+                    refined.addresses.push(address);
+                    refined.values.extend_from_slice(new);
+                }
+            }
+            index += count;
+        }
+
+        let previous = Candidates {
+            addresses: std::mem::replace(&mut self.addresses, refined.addresses),
+            values: std::mem::replace(&mut self.values, refined.values),
+        };
+        if self.undo_depth > 0 {
+            if self.history.len() == self.undo_depth {
+                self.history.pop_front();
+            }
+            self.history.push_back(previous);
+        }
+        Ok(())
+    }
+
+    /// Restores the candidates as they were before the last refinement, returning false if
+    /// there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(previous) = self.history.pop_back() else {
+            return false;
+        };
+        self.addresses = previous.addresses;
+        self.values = previous.values;
+        true
+    }
+
+    /// Number of refinements that can be undone.
+    pub fn undo_len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+/// Progress of a scan, reported after each chunk of memory.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanProgress<'a> {
+    /// Number of bytes scanned so far
+    pub scanned: u64,
+    /// Number of bytes to scan in total
+    pub total: u64,
+    /// Segment being scanned, `None` when scanning a [`Snapshot`]
+    pub segment: Option<&'a Segment>,
+}
+
+impl ScanProgress<'_> {
+    /// Part of the memory scanned so far, from 0 to 1.
+    pub fn fraction(&self) -> f64 {
+        match self.total {
+            0 => 1.0,
+            total => self.scanned as f64 / total as f64,
+        }
+    }
+}
+
+type ProgressCallback = Arc<dyn Fn(&ScanProgress) + Send + Sync>;
+
+/// Shared flag to stop scans running on other threads. Clones cancel the same scans.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the scans using the token at their next chunk.
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// Error of a scan stopped by its [`CancellationToken`]. It can be recovered from the returned
+/// error with [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanCancelled {
+    /// Number of bytes scanned before the scan stopped
+    pub scanned: u64,
+}
+
+impl fmt::Display for ScanCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scan cancelled after {} bytes", self.scanned)
+    }
+}
+
+impl std::error::Error for ScanCancelled {}
+
+/// Tracks the bytes scanned, reports them and checks for cancellation.
+struct Tracker<'a> {
+    scanned: u64,
+    total: u64,
+    progress: Option<&'a ProgressCallback>,
+    cancellation: Option<&'a CancellationToken>,
+}
+
+impl Tracker<'_> {
+    fn check(&self) -> anyhow::Result<()> {
+        if self.cancellation.is_some_and(|token| token.is_cancelled()) {
+            return Err(ScanCancelled {
+                scanned: self.scanned,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, bytes: u64, segment: Option<&Segment>) {
+        self.scanned += bytes;
+        if let Some(progress) = self.progress {
+            progress(&ScanProgress {
+                scanned: self.scanned,
+                total: self.total,
+                segment,
+            });
+        }
+    }
+}
+
+/// Searches the readable segments of a process for values.
+///
+/// Memory is read in chunks of bounded size, so large mappings are never held in memory whole.
+/// Unreadable pages of the segments (e.g. guard pages) are skipped.
+pub struct Scanner<R> {
+    reader: R,
+    segments: Vec<Segment>,
+    alignment: Option<usize>,
+    chunk_size: usize,
+    progress: Option<ProgressCallback>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<R: fmt::Debug> fmt::Debug for Scanner<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scanner")
+            .field("reader", &self.reader)
+            .field("segments", &self.segments)
+            .field("alignment", &self.alignment)
+            .field("chunk_size", &self.chunk_size)
+            .field("progress", &self.progress.is_some())
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl<R: MemoryReader> Scanner<R> {
+    /// Scans the readable ones of `segments` through `reader`.
+    pub fn new(reader: R, segments: &[Segment]) -> Self {
+        Scanner {
+            reader,
+            segments: segments
+                .iter()
+                .filter(|segment| segment.permissions().is_readable())
+                .cloned()
+                .collect(),
+            alignment: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            progress: None,
+            cancellation: None,
+        }
+    }
+
+    /// Only keeps the segments matching `filter`, e.g. the writable ones.
+    pub fn filter_segments(mut self, filter: impl FnMut(&Segment) -> bool) -> Self {
+        self.segments.retain(filter);
+        self
+    }
+
+    /// Only reports the values at addresses multiple of `alignment`. Values are searched at
+    /// their natural alignment by default, see [`ValueType::alignment`].
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment.max(1));
+        self
+    }
+
+    /// Sets the size of the memory read at once, at least one byte.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Calls `progress` after each chunk of memory scanned, from the scanning thread.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&ScanProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Stops the scans when `token` is cancelled, failing them with [`ScanCancelled`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    fn tracker(&self, total: u64) -> Tracker<'_> {
+        Tracker {
+            scanned: 0,
+            total,
+            progress: self.progress.as_ref(),
+            cancellation: self.cancellation.as_ref(),
+        }
+    }
+
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    /// Segments scanned.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Alignment of the values of type `value_type` searched.
+    fn alignment_of(&self, value_type: ValueType) -> usize {
+        self.alignment.unwrap_or(value_type.alignment())
+    }
+
+    /// Finds the addresses holding exactly `value`, compared bytewise.
+    pub fn scan(&self, value: &ScanValue) -> anyhow::Result<ScanResults> {
+        let mut results = ScanResults::new(value.value_type());
+        self.scan_each(value, |address, value| {
+            results.push(address, value);
+            Ok(())
+        })?;
+
+        Ok(results)
+    }
+
+    /// Same as [`Self::scan`], calling `found` with each address found and its value in
+    /// increasing address order instead of keeping them, e.g. to send them through a bounded
+    /// channel or to spill them to a [`crate::introspection::spill::SpilledResults`]. The scan
+    /// stops at the first error returned by `found`.
+    pub fn scan_each(
+        &self,
+        value: &ScanValue,
+        mut found: impl FnMut(u64, &[u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let needle = value.to_bytes();
+        if needle.is_empty() {
+            bail!("Cannot scan for an empty value");
+        }
+
+        let alignment = self.alignment_of(value.value_type());
+        let finder = Finder::new(&needle);
+        self.for_each_chunk(needle.len(), |address, data, owned| {
+            let mut result = Ok(());
+            find_value(&finder, data, address, owned, alignment, |offset| {
+                if result.is_ok() {
+                    result = found(address + offset as u64, &needle);
+                }
+            });
+            result
+        })
+    }
+
+    /// Finds the matches of the byte signature `pattern`, at any address unless an alignment is
+    /// set. The results hold the bytes matched, wildcards included.
+    ///
+    /// Signatures of code are usually searched in the executable segments only, see
+    /// [`Self::filter_segments`].
+    pub fn scan_pattern(&self, pattern: &Pattern) -> anyhow::Result<ScanResults> {
+        let mut results = ScanResults::new(ValueType::Bytes(pattern.len()));
+        self.scan_pattern_each(pattern, |address, value| {
+            results.push(address, value);
+            Ok(())
+        })?;
+
+        Ok(results)
+    }
+
+    /// Same as [`Self::scan_pattern`], calling `found` with each match instead of keeping them,
+    /// see [`Self::scan_each`].
+    pub fn scan_pattern_each(
+        &self,
+        pattern: &Pattern,
+        mut found: impl FnMut(u64, &[u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let size = pattern.len();
+        let alignment = self.alignment.unwrap_or(1);
+        self.for_each_chunk(size, |address, data, owned| {
+            let data = &data[..owned + size - 1];
+            for offset in pattern.find_iter(data) {
+                if (address + offset as u64).is_multiple_of(alignment as u64) {
+                    found(address + offset as u64, &data[offset..offset + size])?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Extracts the printable strings of the segments, see [`StringOptions`]. Strings are cut
+    /// at unreadable pages and at the end of their segment.
+    pub fn strings(&self, options: &StringOptions) -> anyhow::Result<Vec<FoundString>> {
+        let mut extractor = StringExtractor::new(options);
+        let mut segment_end = None;
+        self.for_each_chunk(1, |address, data, _| {
+            // Adjacent segments are distinct objects
+            if let Some(segment) = self.segments.iter().find(|s| s.contains(address)) {
+                if segment_end != Some(segment.end()) {
+                    extractor.flush();
+                    segment_end = Some(segment.end());
+                }
+            }
+            extractor.feed(address, data);
+            Ok(())
+        })?;
+
+        Ok(extractor.finish())
+    }
+
+    /// Finds the aligned pointers of `bitness` stored in the writable segments that point into
+    /// any of the segments, and indexes them by the address they point to.
+    ///
+    /// Pointers are searched at their natural alignment unless an alignment is set.
+    pub fn pointer_map(&self, bitness: Bitness) -> anyhow::Result<PointerMap> {
+        let size = bitness.pointer_size();
+        let alignment = self.alignment.unwrap_or(size);
+
+        // Adjacent segments are merged, so most pointers are checked against the bounds only
+        let mut targets: Vec<Range<u64>> = Vec::new();
+        let mut segments: Vec<&Segment> = self.segments.iter().collect();
+        segments.sort_by_key(|segment| segment.start());
+        for segment in segments {
+            match targets.last_mut() {
+                Some(last) if last.end == segment.start() => last.end = segment.end(),
+                _ => targets.push(segment.start()..segment.end()),
+            }
+        }
+        let bounds = match (targets.first(), targets.last()) {
+            (Some(first), Some(last)) => first.start..last.end,
+            _ => return Ok(PointerMap::new(bitness, Vec::new())),
+        };
+
+        let mut pointers = Vec::new();
+        self.for_each_chunk(size, |address, data, owned| {
+            let writable = self
+                .segments
+                .iter()
+                .find(|segment| segment.contains(address))
+                .is_some_and(|segment| segment.permissions().is_writable());
+            if !writable {
+                return Ok(());
+            }
+            for offset in aligned_offsets(address, owned, alignment) {
+                let target = bitness.pointer_from_bytes(&data[offset..offset + size])?;
+                if !bounds.contains(&target) {
+                    continue;
+                }
+                let index = targets.partition_point(|range| range.end <= target);
+                if targets
+                    .get(index)
+                    .is_some_and(|range| range.contains(&target))
+                {
+                    pointers.push(Pointer {
+                        location: address + offset as u64,
+                        target,
+                    });
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(PointerMap::new(bitness, pointers))
+    }
+
+    /// Copies the readable memory of the segments, to later find the values of unknown initial
+    /// value matching a predicate with [`Self::scan_snapshot`].
+    pub fn snapshot(&self) -> anyhow::Result<Snapshot> {
+        let mut chunks = Vec::new();
+        self.for_each_chunk(1, |address, data, _| {
+            chunks.push(SnapshotChunk {
+                address,
+                data: data.into(),
+            });
+            Ok(())
+        })?;
+        chunks.sort_by_key(|chunk| chunk.address);
+
+        Ok(Snapshot { chunks })
+    }
+
+    /// Compares the current values of type `value_type` to the ones in `snapshot`, and returns
+    /// the addresses matching `predicate` along with their current value. Values not readable
+    /// anymore are left out.
+    pub fn scan_snapshot(
+        &self,
+        snapshot: &Snapshot,
+        value_type: ValueType,
+        predicate: &Predicate,
+    ) -> anyhow::Result<ScanResults> {
+        let mut results = ScanResults::new(value_type);
+        self.scan_snapshot_each(snapshot, value_type, predicate, |address, value| {
+            results.push(address, value);
+            Ok(())
+        })?;
+
+        Ok(results)
+    }
+
+    /// Same as [`Self::scan_snapshot`], calling `found` with each address matching and its
+    /// current value instead of keeping them, see [`Self::scan_each`].
+    pub fn scan_snapshot_each(
+        &self,
+        snapshot: &Snapshot,
+        value_type: ValueType,
+        predicate: &Predicate,
+        mut found: impl FnMut(u64, &[u8]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        predicate.check(value_type)?;
+        let size = value_type.size();
+        if size == 0 {
+            bail!("Cannot scan for empty values");
+        }
+
+        let alignment = self.alignment_of(value_type);
+        let mut old = Vec::new();
+        let mut buffer = Vec::new();
+        let mut tracker = self.tracker(snapshot.size());
+        for (index, chunk) in snapshot.chunks.iter().enumerate() {
+            tracker.check()?;
+            tracker.advance(chunk.data.len() as u64, None);
+
+            // Values starting in the chunk may end in the next one
+            old.clear();
+            old.extend_from_slice(&chunk.data);
+            if let Some(next) = snapshot.chunks.get(index + 1) {
+                if next.address == chunk.range().end {
+                    old.extend_from_slice(&next.data[..(size - 1).min(next.data.len())]);
+                }
+            }
+            if old.len() < size {
+                continue;
+            }
+
+            let owned = (old.len() - size + 1).min(chunk.data.len());
+            read_chunk(
+                &self.reader,
+                chunk.address,
+                &mut buffer,
+                old.len(),
+                owned,
+                size,
+                |address, data, owned| {
+                    let base = (address - chunk.address) as usize;
+                    for offset in aligned_offsets(address, owned, alignment) {
+                        let new = &data[offset..offset + size];
+                        let start = base + offset;
+                        if predicate.matches(value_type, &old[start..start + size], new) {
+                            found(address + offset as u64, new)?;
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Calls `visit` with the address and bytes of each chunk of the segments, and the number
+    /// of leading bytes at which the chunk owns the values of `size` bytes: chunks overlap by
+    /// `size - 1` bytes so values across their boundaries are seen once.
+    fn for_each_chunk(
+        &self,
+        size: usize,
+        visit: impl FnMut(u64, &[u8], usize) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        self.for_each_overlapping_chunk(size, size - 1, visit)
+    }
+
+    /// Same as [`Self::for_each_chunk`], with chunks overlapping by at least `overlap` bytes.
+    fn for_each_overlapping_chunk(
+        &self,
+        size: usize,
+        overlap: usize,
+        mut visit: impl FnMut(u64, &[u8], usize) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let overlap = overlap.max(size - 1);
+        let mut buffer = Vec::new();
+        let mut tracker = self.tracker(self.segments.iter().map(Segment::size).sum());
+        for segment in &self.segments {
+            let mut address = segment.start();
+            while address < segment.end() {
+                tracker.check()?;
+                let len = (self.chunk_size + overlap).min((segment.end() - address) as usize);
+                if len < size {
+                    tracker.advance(segment.end() - address, Some(segment));
+                    break;
+                }
+                let owned = (len - size + 1).min(self.chunk_size);
+                read_chunk(
+                    &self.reader,
+                    address,
+                    &mut buffer,
+                    len,
+                    owned,
+                    size,
+                    &mut visit,
+                )?;
+                address += owned as u64;
+                tracker.advance(owned as u64, Some(segment));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Offsets in the chunk at `address` of the values aligned on `alignment` it owns.
+fn aligned_offsets(address: u64, owned: usize, alignment: usize) -> impl Iterator<Item = usize> {
+    let alignment = alignment as u64;
+    let first = ((alignment - address % alignment) % alignment) as usize;
+    (first..owned).step_by(alignment as usize)
+}
+
+/// Default maximum length of the matches of [`Scanner::scan_regex`].
+#[cfg(feature = "regex")]
+const DEFAULT_MAX_MATCH_LEN: usize = 4096;
+
+/// Match of [`Scanner::scan_regex`].
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexMatch {
+    /// Address range of the whole match
+    pub range: Range<u64>,
+    /// Bytes matched
+    pub data: Vec<u8>,
+    /// Address ranges of the capture groups of the regex, `None` for the groups that did not
+    /// participate in the match. The first group is the whole match.
+    pub groups: Vec<Option<Range<u64>>>,
+}
+
+#[cfg(feature = "regex")]
+impl<R: MemoryReader> Scanner<R> {
+    /// Finds the matches of `regex` in the segments, leftmost first and not overlapping.
+    ///
+    /// Matches across the boundary of two chunks are found as long as they are at most
+    /// 4096 bytes long, see [`Self::scan_regex_with_max_len`]. Matches never span unreadable
+    /// pages.
+    pub fn scan_regex(&self, regex: &regex::bytes::Regex) -> anyhow::Result<Vec<RegexMatch>> {
+        self.scan_regex_with_max_len(regex, DEFAULT_MAX_MATCH_LEN)
+    }
+
+    /// Finds the matches of `regex`, those longer than `max_len` bytes being found only when
+    /// they do not cross the boundary of two chunks.
+    pub fn scan_regex_with_max_len(
+        &self,
+        regex: &regex::bytes::Regex,
+        max_len: usize,
+    ) -> anyhow::Result<Vec<RegexMatch>> {
+        let mut matches: Vec<RegexMatch> = Vec::new();
+        let mut locations = regex.capture_locations();
+        self.for_each_overlapping_chunk(1, max_len.max(1) - 1, |address, data, owned| {
+            // The matches of the previous chunk may end in this one
+            let mut start = matches
+                .last()
+                .map_or(0, |last| last.range.end.saturating_sub(address))
+                .min(data.len() as u64) as usize;
+
+            while start < owned {
+                let Some(found) = regex.captures_read_at(&mut locations, data, start) else {
+                    break;
+                };
+                if found.start() >= owned {
+                    break;
+                }
+                let range = |start: usize, end: usize| address + start as u64..address + end as u64;
+                matches.push(RegexMatch {
+                    range: range(found.start(), found.end()),
+                    data: found.as_bytes().to_vec(),
+                    groups: (0..locations.len())
+                        .map(|group| {
+                            let (start, end) = locations.get(group)?;
+                            Some(range(start, end))
+                        })
+                        .collect(),
+                });
+                // Empty matches move on by a byte so the search ends
+                start = found.end().max(found.start() + 1);
+            }
+            Ok(())
+        })?;
+
+        Ok(matches)
+    }
+}
+
+/// Number of words compared at once by [`find_words`], so the comparisons are vectorized.
+const WORDS_PER_BLOCK: usize = 16;
+
+/// Calls `found` with the offsets below `owned` of the occurrences of the needle of `finder` in
+/// the chunk `data` at `address`, aligned on `alignment`.
+fn find_value(
+    finder: &Finder,
+    data: &[u8],
+    address: u64,
+    owned: usize,
+    alignment: usize,
+    mut found: impl FnMut(usize),
+) {
+    let needle = finder.needle();
+    let first = ((alignment as u64 - address % alignment as u64) % alignment as u64) as usize;
+    match (needle.len(), alignment) {
+        (1, 1) => memchr::memchr_iter(needle[0], &data[..owned]).for_each(found),
+        (2, 2) => find_words::<2>(data, first, owned, needle, found),
+        (4, 4) => find_words::<4>(data, first, owned, needle, found),
+        (8, 8) => find_words::<8>(data, first, owned, needle, found),
+        (size, _) => {
+            // Skips the misaligned occurrences, overlapping ones included
+            let data = &data[..owned + size - 1];
+            let mut start = first;
+            while start < owned {
+                let Some(offset) = finder.find(&data[start..]) else {
+                    break;
+                };
+                let offset = start + offset;
+                if offset >= owned {
+                    break;
+                }
+                let misalignment = (offset - first) % alignment;
+                if misalignment == 0 {
+                    found(offset);
+                    start = offset + alignment;
+                } else {
+                    start = offset + alignment - misalignment;
+                }
+            }
+        }
+    }
+}
+
+/// Calls `found` with the offsets of the words of `N` bytes equal to `needle` from `first` to
+/// `owned`, stepping by whole words.
+fn find_words<const N: usize>(
+    data: &[u8],
+    first: usize,
+    owned: usize,
+    needle: &[u8],
+    mut found: impl FnMut(usize),
+) {
+    let Ok(needle) = <[u8; N]>::try_from(needle) else {
+        return;
+    };
+    if first >= owned {
+        return;
+    }
+    let words = (owned - first).div_ceil(N);
+    let data = &data[first..first + words * N];
+
+    // Compares blocks of words without branching, and only looks for the matches in the rare
+    // blocks having some
+    let block_size = N * WORDS_PER_BLOCK;
+    let mut blocks = data.chunks_exact(block_size);
+    for (index, block) in blocks.by_ref().enumerate() {
+        let hit = block
+            .chunks_exact(N)
+            .fold(false, |hit, word| hit | (word == needle));
+        if hit {
+            for (word, bytes) in block.chunks_exact(N).enumerate() {
+                if bytes == needle {
+                    found(first + index * block_size + word * N);
+                }
+            }
+        }
+    }
+
+    let base = first + (data.len() - blocks.remainder().len());
+    for (word, bytes) in blocks.remainder().chunks_exact(N).enumerate() {
+        if bytes == needle {
+            found(base + word * N);
+        }
+    }
+}
+
+/// Reads `len` bytes at `address` in `buffer`, and calls `visit` on each readable run of them
+/// with the number of leading bytes of the run owning values of `size` bytes.
+fn read_chunk<R: MemoryReader + ?Sized>(
+    reader: &R,
+    address: u64,
+    buffer: &mut Vec<u8>,
+    len: usize,
+    owned: usize,
+    size: usize,
+    mut visit: impl FnMut(u64, &[u8], usize) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    buffer.resize(len, 0);
+    if reader.read(address, buffer).is_ok() {
+        return visit(address, buffer, owned);
+    }
+
+    // Only visits the readable runs of the chunk
+    let partial = reader.read_partial(address, buffer);
+    let mut start = address;
+    for gap in &partial.gaps {
+        visit_run(start..gap.start, address, buffer, owned, size, &mut visit)?;
+        start = gap.end;
+    }
+    let end = address + len as u64;
+    visit_run(start..end, address, buffer, owned, size, &mut visit)
+}
+
+/// Visits the readable run `run` of the chunk at `address`.
+fn visit_run(
+    run: Range<u64>,
+    address: u64,
+    buffer: &[u8],
+    owned: usize,
+    size: usize,
+    visit: &mut impl FnMut(u64, &[u8], usize) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let start = (run.start - address) as usize;
+    let end = (run.end - address) as usize;
+    if start >= owned || end - start < size {
+        return Ok(());
+    }
+    visit(
+        run.start,
+        &buffer[start..end],
+        (end - start - size + 1).min(owned - start),
+    )
+}
+
+/// Contiguous bytes of a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SnapshotChunk {
+    address: u64,
+    data: Box<[u8]>,
+}
+
+impl SnapshotChunk {
+    fn range(&self) -> Range<u64> {
+        self.address..self.address + self.data.len() as u64
+    }
+}
+
+/// Copy of the readable memory of a process at some point, taken by [`Scanner::snapshot`].
+///
+/// The memory is kept in chunks of at most the chunk size of the scanner, and unreadable pages
+/// are left out. A snapshot is itself a [`MemoryReader`] of the memory as it was.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Chunks in address order
+    chunks: Vec<SnapshotChunk>,
+}
+
+impl Snapshot {
+    /// Builds a snapshot from contiguous bytes and their address, in any order.
+    pub(crate) fn from_chunks(chunks: impl IntoIterator<Item = (u64, Box<[u8]>)>) -> Self {
+        let mut chunks: Vec<SnapshotChunk> = chunks
+            .into_iter()
+            .map(|(address, data)| SnapshotChunk { address, data })
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.address);
+        Snapshot { chunks }
+    }
+
+    /// Contiguous bytes of the snapshot and their address, in address order.
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.chunks
+            .iter()
+            .map(|chunk| (chunk.address, &chunk.data[..]))
+    }
+
+    /// Moves each chunk to the address returned by `relocate` for its start.
+    pub(crate) fn relocate(&mut self, relocate: impl Fn(u64) -> u64) {
+        for chunk in &mut self.chunks {
+            chunk.address = relocate(chunk.address);
+        }
+        self.chunks.sort_by_key(|chunk| chunk.address);
+    }
+
+    /// Number of bytes copied.
+    pub fn size(&self) -> u64 {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.data.len() as u64)
+            .sum()
+    }
+
+    /// Address ranges copied, merged when adjacent.
+    pub fn ranges(&self) -> Vec<Range<u64>> {
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for chunk in &self.chunks {
+            match ranges.last_mut() {
+                Some(last) if last.end == chunk.address => last.end = chunk.range().end,
+                _ => ranges.push(chunk.range()),
+            }
+        }
+        ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+impl MemoryReader for Snapshot {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        let end = address + buffer.len() as u64;
+        let mut index = self
+            .chunks
+            .partition_point(|chunk| chunk.range().end <= address);
+        let mut position = address;
+
+        while position < end {
+            let Some(chunk) = self
+                .chunks
+                .get(index)
+                .filter(|chunk| chunk.range().contains(&position))
+            else {
+                bail!("Address {position:#x} is not in the snapshot");
+            };
+            let stop = end.min(chunk.range().end);
+            buffer[(position - address) as usize..(stop - address) as usize].copy_from_slice(
+                &chunk.data[(position - chunk.address) as usize..(stop - chunk.address) as usize],
+            );
+            position = stop;
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+//! This module contains the structs and functions to introspect the scheduler statistics of a process.
+//! Based on https://www.kernel.org/doc/html/latest/scheduler/sched-stats.html
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Context};
+
+/// Scheduler statistics of a task, as found in `/proc/<pid>/schedstat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedStat {
+    /// Time spent running on a CPU
+    pub run_time: Duration,
+    /// Time spent waiting on a runqueue
+    pub run_delay: Duration,
+    /// Number of timeslices run on a CPU
+    pub timeslices: u64,
+}
+
+impl FromStr for SchedStat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace().map(|field| {
+            field
+                .parse::<u64>()
+                .with_context(|| format!("Invalid schedstat field: {field}"))
+        });
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed schedstat: {s}"))?
+        };
+
+        Ok(SchedStat {
+            run_time: Duration::from_nanos(next()?),
+            run_delay: Duration::from_nanos(next()?),
+            timeslices: next()?,
+        })
+    }
+}
+
+/// Detailed scheduler data of a task, as found in `/proc/<pid>/sched`.
+///
+/// The set of fields depends on the kernel version and configuration (e.g. `CONFIG_SCHEDSTATS`),
+/// so they are kept as a map, with typed accessors for the common ones.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchedInfo {
+    fields: HashMap<String, f64>,
+}
+
+impl SchedInfo {
+    /// Returns the raw value of a field, e.g. `se.avg.util_avg`.
+    pub fn get(&self, key: &str) -> Option<f64> {
+        self.fields.get(key).copied()
+    }
+
+    /// Returns a field whose name ends with `.<key>`, as some fields moved across kernel versions
+    /// (e.g. `se.statistics.wait_sum` became `stats.wait_sum`).
+    fn get_suffix(&self, key: &str) -> Option<f64> {
+        self.get(key).or_else(|| {
+            self.fields
+                .iter()
+                .find(|(k, _)| k.strip_suffix(key).is_some_and(|k| k.ends_with('.')))
+                .map(|(_, v)| *v)
+        })
+    }
+
+    /// Returns a field expressed in milliseconds as a duration.
+    fn get_duration(&self, key: &str) -> Option<Duration> {
+        self.get_suffix(key)
+            .filter(|ms| *ms >= 0.0)
+            .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    /// Virtual runtime used by the fair scheduler to pick the next task.
+    pub fn vruntime(&self) -> Option<Duration> {
+        self.get_duration("vruntime")
+    }
+
+    /// Total time spent running on a CPU.
+    pub fn sum_exec_runtime(&self) -> Option<Duration> {
+        self.get_duration("sum_exec_runtime")
+    }
+
+    /// Total time spent waiting on a runqueue, requires `CONFIG_SCHEDSTATS`.
+    pub fn wait_sum(&self) -> Option<Duration> {
+        self.get_duration("wait_sum")
+    }
+
+    /// Longest time spent waiting on a runqueue, requires `CONFIG_SCHEDSTATS`.
+    pub fn wait_max(&self) -> Option<Duration> {
+        self.get_duration("wait_max")
+    }
+
+    /// Number of context switches.
+    pub fn nr_switches(&self) -> Option<u64> {
+        self.get("nr_switches").map(|n| n as u64)
+    }
+
+    /// Number of context switches because the task blocked.
+    pub fn nr_voluntary_switches(&self) -> Option<u64> {
+        self.get("nr_voluntary_switches").map(|n| n as u64)
+    }
+
+    /// Number of context switches because the task was preempted.
+    pub fn nr_involuntary_switches(&self) -> Option<u64> {
+        self.get("nr_involuntary_switches").map(|n| n as u64)
+    }
+
+    /// Number of migrations to another CPU.
+    pub fn nr_migrations(&self) -> Option<u64> {
+        self.get_suffix("nr_migrations").map(|n| n as u64)
+    }
+}
+
+impl FromStr for SchedInfo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter_map(|(key, value)| Some((key.trim().to_string(), value.trim().parse().ok()?)))
+            .collect();
+
+        Ok(SchedInfo { fields })
+    }
+}
@@ -0,0 +1,248 @@
+//! This module contains the structs and functions to introspect the seccomp filters of a process.
+//! Based on https://www.man7.org/linux/man-pages/man2/seccomp.2.html
+use std::{fmt, io, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::process::Pid;
+
+/// Not exported by the libc crate, see `include/uapi/linux/ptrace.h`.
+const PTRACE_SECCOMP_GET_FILTER: u32 = 0x420c;
+
+/// Seccomp mode of a process, as found in the `Seccomp` field of `/proc/<pid>/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompMode {
+    /// 0 : No restriction
+    Disabled,
+    /// 1 : Only `read`, `write`, `_exit` and `sigreturn` are allowed
+    Strict,
+    /// 2 : System calls are checked by BPF filters
+    Filter,
+}
+
+impl FromStr for SeccompMode {
+    type Err = anyhow::Error;
+
+    /// Parses the `Seccomp` line of `/proc/<pid>/status`, other lines are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mode = s
+            .lines()
+            .find_map(|line| line.strip_prefix("Seccomp:"))
+            .ok_or_else(|| anyhow!("Missing Seccomp in status"))?
+            .trim();
+
+        match mode {
+            "0" => Ok(SeccompMode::Disabled),
+            "1" => Ok(SeccompMode::Strict),
+            "2" => Ok(SeccompMode::Filter),
+            _ => bail!("Unknown seccomp mode: {mode}"),
+        }
+    }
+}
+
+/// A classic BPF instruction, see `struct sock_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpfInstruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// Names of the fields of `struct seccomp_data`, by offset.
+fn seccomp_data_field(offset: u32) -> Option<String> {
+    match offset {
+        0 => Some("nr".to_string()),
+        4 => Some("arch".to_string()),
+        8 => Some("instruction_pointer".to_string()),
+        12 => Some("instruction_pointer_hi".to_string()),
+        16..=63 => {
+            let arg = (offset - 16) / 8;
+            let hi = if (offset - 16) % 8 == 4 { "_hi" } else { "" };
+            Some(format!("args[{arg}]{hi}"))
+        }
+        _ => None,
+    }
+}
+
+/// Name of a seccomp filter return action.
+fn seccomp_action(k: u32) -> String {
+    let data = k & 0xffff;
+    match k & 0xffff_0000 {
+        0x8000_0000 => "KILL_PROCESS".to_string(),
+        0x0000_0000 => "KILL_THREAD".to_string(),
+        0x0003_0000 => format!("TRAP({data})"),
+        0x0005_0000 => format!("ERRNO({data})"),
+        0x7fc0_0000 => "USER_NOTIF".to_string(),
+        0x7ff0_0000 => format!("TRACE({data})"),
+        0x7ffc_0000 => "LOG".to_string(),
+        0x7fff_0000 => "ALLOW".to_string(),
+        _ => format!("{k:#x}"),
+    }
+}
+
+impl fmt::Display for BpfInstruction {
+    /// Formats the instruction in the syntax of `bpf_asm`, with seccomp-specific annotations.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.code;
+        let k = self.k;
+        let size = match code & 0x18 {
+            0x00 => "",
+            0x08 => "h",
+            _ => "b",
+        };
+        let src = |op: &str| match code & 0x08 {
+            0x00 => format!("{op} #{k:#x}"),
+            _ => format!("{op} x"),
+        };
+
+        match code & 0x07 {
+            // BPF_LD, BPF_LDX
+            class @ (0x00 | 0x01) => {
+                let op = if class == 0x00 { "ld" } else { "ldx" };
+                match code & 0xe0 {
+                    0x00 => write!(f, "{op}{size} #{k:#x}"),
+                    0x20 => match seccomp_data_field(k) {
+                        Some(field) => write!(f, "{op}{size} [{field}]"),
+                        None => write!(f, "{op}{size} [{k}]"),
+                    },
+                    0x40 => write!(f, "{op}{size} [x + {k}]"),
+                    0x60 => write!(f, "{op} M[{k}]"),
+                    0x80 => write!(f, "{op} len"),
+                    0xa0 => write!(f, "ldxb 4*([{k}]&0xf)"),
+                    _ => write!(f, "unknown {code:#06x}"),
+                }
+            }
+            0x02 => write!(f, "st M[{k}]"),
+            0x03 => write!(f, "stx M[{k}]"),
+            // BPF_ALU
+            0x04 => match code & 0xf0 {
+                0x00 => f.write_str(&src("add")),
+                0x10 => f.write_str(&src("sub")),
+                0x20 => f.write_str(&src("mul")),
+                0x30 => f.write_str(&src("div")),
+                0x40 => f.write_str(&src("or")),
+                0x50 => f.write_str(&src("and")),
+                0x60 => f.write_str(&src("lsh")),
+                0x70 => f.write_str(&src("rsh")),
+                0x80 => f.write_str("neg"),
+                0x90 => f.write_str(&src("mod")),
+                0xa0 => f.write_str(&src("xor")),
+                _ => write!(f, "unknown {code:#06x}"),
+            },
+            // BPF_JMP
+            0x05 => {
+                let op = match code & 0xf0 {
+                    0x00 => return write!(f, "ja +{k}"),
+                    0x10 => "jeq",
+                    0x20 => "jgt",
+                    0x30 => "jge",
+                    0x40 => "jset",
+                    _ => return write!(f, "unknown {code:#06x}"),
+                };
+                write!(f, "{}, {}, {}", src(op), self.jt, self.jf)
+            }
+            // BPF_RET
+            0x06 => match code & 0x18 {
+                0x00 => write!(f, "ret #{k:#x} ; {}", seccomp_action(k)),
+                0x08 => f.write_str("ret x"),
+                _ => f.write_str("ret a"),
+            },
+            // BPF_MISC
+            _ => match code & 0xf8 {
+                0x00 => f.write_str("tax"),
+                0x80 => f.write_str("txa"),
+                _ => write!(f, "unknown {code:#06x}"),
+            },
+        }
+    }
+}
+
+/// A classic BPF program installed as a seccomp filter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BpfProgram {
+    pub instructions: Vec<BpfInstruction>,
+}
+
+impl BpfProgram {
+    /// Disassembles the program, one numbered instruction per line.
+    pub fn disassemble(&self) -> String {
+        self.instructions
+            .iter()
+            .enumerate()
+            .map(|(i, instruction)| format!("{i:04}: {instruction}\n"))
+            .collect()
+    }
+}
+
+/// Retrieves the seccomp filter at `index` of the process `pid`, 0 being the most recently
+/// installed one. Returns `None` past the last filter.
+///
+/// The caller must be attached to the process with ptrace and the process must be stopped.
+/// This also requires `CAP_SYS_ADMIN` and a kernel built with `CONFIG_CHECKPOINT_RESTORE`.
+pub fn seccomp_filter(pid: Pid, index: usize) -> anyhow::Result<Option<BpfProgram>> {
+    // SAFETY: a NULL buffer only queries the number of instructions
+    let count = unsafe {
+        libc::ptrace(
+            PTRACE_SECCOMP_GET_FILTER as _,
+            pid as libc::pid_t,
+            index as *mut libc::c_void,
+            std::ptr::null_mut::<libc::c_void>(),
+        )
+    };
+    if count < 0 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ENOENT) => Ok(None),
+            _ => Err(error).with_context(|| format!("Failed to get seccomp filter {index}")),
+        };
+    }
+
+    let mut filter = vec![
+        libc::sock_filter {
+            code: 0,
+            jt: 0,
+            jf: 0,
+            k: 0,
+        };
+        count as usize
+    ];
+    // SAFETY: the buffer holds `count` instructions
+    let count = unsafe {
+        libc::ptrace(
+            PTRACE_SECCOMP_GET_FILTER as _,
+            pid as libc::pid_t,
+            index as *mut libc::c_void,
+            filter.as_mut_ptr(),
+        )
+    };
+    if count < 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("Failed to get seccomp filter {index}"));
+    }
+    filter.truncate(count as usize);
+
+    Ok(Some(BpfProgram {
+        instructions: filter
+            .into_iter()
+            .map(|instruction| BpfInstruction {
+                code: instruction.code,
+                jt: instruction.jt,
+                jf: instruction.jf,
+                k: instruction.k,
+            })
+            .collect(),
+    }))
+}
+
+/// Retrieves all the seccomp filters of the process `pid`, most recently installed first.
+///
+/// Same requirements as [`seccomp_filter`].
+pub fn seccomp_filters(pid: Pid) -> anyhow::Result<Vec<BpfProgram>> {
+    let mut filters = Vec::new();
+    while let Some(filter) = seccomp_filter(pid, filters.len())? {
+        filters.push(filter);
+    }
+
+    Ok(filters)
+}
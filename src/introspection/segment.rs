@@ -1,16 +1,33 @@
 /// This module contains the structs and functions to introspect a segment (memory mapping).
-use std::path::Path;
+/// Based on https://www.man7.org/linux/man-pages/man5/proc_pid_maps.5.html
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    map_files,
+    numa::{parse_numa_line, NumaInfo},
+    process::Pid,
+};
 
 pub type InodeId = u64;
 
 /// Small device abstrcation.
 /// See https://linux-kernel-labs.github.io/refs/heads/master/labs/device_model.html#classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Device {
     major: u32,
     minor: u32,
 }
 
 /// Information about a segment in the process's virtual address space.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SegmentType {
     /// The initial process's (also known as the main thread's) stack.
     Stack,
@@ -22,9 +39,52 @@ pub enum SegmentType {
     Anonymous(String),
     /// A named shared anonymous mapping.
     SharedAnonymous(String),
+    /// The kernel data shared with the vDSO, e.g. the clock parameters.
+    Vvar,
+    /// The clock pages of the vDSO, split from `[vvar]` since Linux 6.13.
+    VvarVclock,
+    /// The legacy fixed-address page emulating the vsyscall entry points.
+    Vsyscall,
+    /// The page holding the instructions of the uprobes executed out of line.
+    Uprobes,
+    /// Any other special mapping, named without the brackets.
+    Other(String),
+}
+
+impl FromStr for SegmentType {
+    type Err = anyhow::Error;
+
+    /// Parses the pseudo-path of a special mapping, e.g. `[heap]` or `[anon:name]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| anyhow!("Not a pseudo-path: {s}"))?;
+
+        if let Some(name) = name.strip_prefix("anon:") {
+            return Ok(SegmentType::Anonymous(name.to_string()));
+        }
+        if let Some(name) = name.strip_prefix("anon_shmem:") {
+            return Ok(SegmentType::SharedAnonymous(name.to_string()));
+        }
+
+        match name {
+            // Linux < 4.5 also reports thread stacks as `[stack:<tid>]`
+            "stack" => Ok(SegmentType::Stack),
+            _ if name.starts_with("stack:") => Ok(SegmentType::Stack),
+            "heap" => Ok(SegmentType::Data(DataSegment::Heap)),
+            "vdso" => Ok(SegmentType::SharedLibrary),
+            "vvar" => Ok(SegmentType::Vvar),
+            "vvar_vclock" => Ok(SegmentType::VvarVclock),
+            "vsyscall" => Ok(SegmentType::Vsyscall),
+            "uprobes" => Ok(SegmentType::Uprobes),
+            _ => Ok(SegmentType::Other(name.to_string())),
+        }
+    }
 }
 
 /// Type of data segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataSegment {
     /// The process's heap.
     Heap,
@@ -33,6 +93,7 @@ pub enum DataSegment {
 }
 
 /// Permissions for a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SegmentPermission {
     Read,
     Write,
@@ -42,14 +103,361 @@ pub enum SegmentPermission {
     Shared,
 }
 
+/// Permissions of a segment as a set of flags, e.g. `r-xp` is `READ | EXECUTE`.
+///
+/// A segment without `SHARED` is private (copy-on-write).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SegmentPermissions(u8);
+
+impl SegmentPermissions {
+    pub const READ: Self = SegmentPermissions(1 << 0);
+    pub const WRITE: Self = SegmentPermissions(1 << 1);
+    pub const EXECUTE: Self = SegmentPermissions(1 << 2);
+    pub const SHARED: Self = SegmentPermissions(1 << 3);
+
+    pub fn empty() -> Self {
+        SegmentPermissions(0)
+    }
+
+    /// Builds a set from raw bits, unknown bits are dropped.
+    pub fn from_bits_truncate(bits: u8) -> Self {
+        SegmentPermissions(bits & 0xf)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if all the flags of `other` are set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.contains(Self::READ)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.contains(Self::WRITE)
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.contains(Self::EXECUTE)
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.contains(Self::SHARED)
+    }
+
+    pub fn is_private(&self) -> bool {
+        !self.is_shared()
+    }
+
+    /// Protection flags of `mmap` and `mprotect`, e.g. `PROT_READ | PROT_EXEC`.
+    pub fn to_prot(&self) -> libc::c_int {
+        let flag = |flag: Self, prot: libc::c_int| if self.contains(flag) { prot } else { 0 };
+        flag(Self::READ, libc::PROT_READ)
+            | flag(Self::WRITE, libc::PROT_WRITE)
+            | flag(Self::EXECUTE, libc::PROT_EXEC)
+    }
+
+    /// Converts to the positional representation, e.g. `[Read, NoPermission, Execute, Private]`.
+    pub fn to_array(&self) -> [SegmentPermission; 4] {
+        let flag = |flag: Self, permission: SegmentPermission| {
+            if self.contains(flag) {
+                permission
+            } else {
+                SegmentPermission::NoPermission
+            }
+        };
+
+        [
+            flag(Self::READ, SegmentPermission::Read),
+            flag(Self::WRITE, SegmentPermission::Write),
+            flag(Self::EXECUTE, SegmentPermission::Execute),
+            if self.is_shared() {
+                SegmentPermission::Shared
+            } else {
+                SegmentPermission::Private
+            },
+        ]
+    }
+}
+
+impl std::ops::BitOr for SegmentPermissions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        SegmentPermissions(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for SegmentPermissions {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl From<[SegmentPermission; 4]> for SegmentPermissions {
+    fn from(permissions: [SegmentPermission; 4]) -> Self {
+        permissions
+            .into_iter()
+            .fold(Self::empty(), |flags, permission| match permission {
+                SegmentPermission::Read => flags | Self::READ,
+                SegmentPermission::Write => flags | Self::WRITE,
+                SegmentPermission::Execute => flags | Self::EXECUTE,
+                SegmentPermission::Shared => flags | Self::SHARED,
+                SegmentPermission::NoPermission | SegmentPermission::Private => flags,
+            })
+    }
+}
+
+impl From<SegmentPermissions> for [SegmentPermission; 4] {
+    fn from(permissions: SegmentPermissions) -> Self {
+        permissions.to_array()
+    }
+}
+
+impl fmt::Display for SegmentPermissions {
+    /// Formats the permissions as in `/proc/<pid>/maps`, e.g. `r-xp`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |flag: Self, c: char| if self.contains(flag) { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(Self::READ, 'r'),
+            flag(Self::WRITE, 'w'),
+            flag(Self::EXECUTE, 'x'),
+            if self.is_shared() { 's' } else { 'p' }
+        )
+    }
+}
+
+impl FromStr for SegmentPermissions {
+    type Err = anyhow::Error;
+
+    /// Parses the permissions column of `/proc/<pid>/maps`, e.g. `r-xp`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.chars().collect();
+        let [r, w, x, p] = chars[..] else {
+            bail!("Invalid permissions: {s}");
+        };
+
+        let flag = |c: char, expected: char, flag: Self| match c {
+            _ if c == expected => Ok(flag),
+            '-' => Ok(Self::empty()),
+            _ => Err(anyhow!("Invalid permissions: {s}")),
+        };
+        let shared = match p {
+            's' => Self::SHARED,
+            'p' => Self::empty(),
+            _ => bail!("Invalid permissions: {s}"),
+        };
+
+        Ok(flag(r, 'r', Self::READ)?
+            | flag(w, 'w', Self::WRITE)?
+            | flag(x, 'x', Self::EXECUTE)?
+            | shared)
+    }
+}
+
+/// Memory usage details of a segment, as found in `/proc/<pid>/smaps`.
+///
+/// Fields depend on the kernel version and configuration, so they are kept as a map, with typed
+/// accessors for the common ones. Values expressed in kB by the kernel are converted to bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentDetails {
+    fields: HashMap<String, u64>,
+    /// Two-letter kernel flags of the mapping, e.g. `rd`, `wr`, `lo`
+    vm_flags: Vec<String>,
+}
+
+impl SegmentDetails {
+    /// Returns the raw value of a field, e.g. `Pss_Dirty`.
+    pub fn get(&self, key: &str) -> Option<u64> {
+        self.fields.get(key).copied()
+    }
+
+    /// Size of the mapping, in bytes.
+    pub fn size(&self) -> u64 {
+        self.get("Size").unwrap_or(0)
+    }
+
+    /// Page size used by the kernel to back the mapping, in bytes.
+    pub fn kernel_page_size(&self) -> u64 {
+        self.get("KernelPageSize").unwrap_or(0)
+    }
+
+    /// Page size used by the MMU to back the mapping, in bytes.
+    pub fn mmu_page_size(&self) -> u64 {
+        self.get("MMUPageSize").unwrap_or(0)
+    }
+
+    /// Resident set size, in bytes.
+    pub fn rss(&self) -> u64 {
+        self.get("Rss").unwrap_or(0)
+    }
+
+    /// Proportional set size: resident memory divided by the number of processes sharing it,
+    /// in bytes.
+    pub fn pss(&self) -> u64 {
+        self.get("Pss").unwrap_or(0)
+    }
+
+    /// Resident memory shared with other processes and not modified, in bytes.
+    pub fn shared_clean(&self) -> u64 {
+        self.get("Shared_Clean").unwrap_or(0)
+    }
+
+    /// Resident memory shared with other processes and modified, in bytes.
+    pub fn shared_dirty(&self) -> u64 {
+        self.get("Shared_Dirty").unwrap_or(0)
+    }
+
+    /// Resident memory only used by this process and not modified, in bytes.
+    pub fn private_clean(&self) -> u64 {
+        self.get("Private_Clean").unwrap_or(0)
+    }
+
+    /// Resident memory only used by this process and modified, in bytes.
+    pub fn private_dirty(&self) -> u64 {
+        self.get("Private_Dirty").unwrap_or(0)
+    }
+
+    /// Memory marked as referenced or accessed, in bytes.
+    pub fn referenced(&self) -> u64 {
+        self.get("Referenced").unwrap_or(0)
+    }
+
+    /// Memory not backed by a file, in bytes.
+    pub fn anonymous(&self) -> u64 {
+        self.get("Anonymous").unwrap_or(0)
+    }
+
+    /// Anonymous memory pushed to swap, in bytes.
+    pub fn swap(&self) -> u64 {
+        self.get("Swap").unwrap_or(0)
+    }
+
+    /// Proportional swap share of this mapping, in bytes.
+    pub fn swap_pss(&self) -> u64 {
+        self.get("SwapPss").unwrap_or(0)
+    }
+
+    /// Memory locked in RAM, in bytes.
+    pub fn locked(&self) -> u64 {
+        self.get("Locked").unwrap_or(0)
+    }
+
+    /// `true` if the mapping was locked in RAM with mlock(2) or `MAP_LOCKED`.
+    pub fn is_locked(&self) -> bool {
+        self.has_vm_flag("lo")
+    }
+
+    /// `true` if the mapping is eligible for transparent huge pages.
+    pub fn thp_eligible(&self) -> bool {
+        self.get("THPeligible")
+            .is_some_and(|eligible| eligible != 0)
+    }
+
+    /// Memory deduplicated by KSM, in bytes (Linux >= 6.6).
+    pub fn ksm(&self) -> Option<u64> {
+        self.get("KSM")
+    }
+
+    /// Anonymous memory backed by transparent huge pages, in bytes.
+    pub fn anon_huge_pages(&self) -> u64 {
+        self.get("AnonHugePages").unwrap_or(0)
+    }
+
+    /// Shared memory (shmem, tmpfs) mapped with huge pages, in bytes.
+    pub fn shmem_pmd_mapped(&self) -> u64 {
+        self.get("ShmemPmdMapped").unwrap_or(0)
+    }
+
+    /// File-backed memory mapped with huge pages, in bytes (Linux >= 5.4).
+    pub fn file_pmd_mapped(&self) -> u64 {
+        self.get("FilePmdMapped").unwrap_or(0)
+    }
+
+    /// Memory backed by hugetlbfs pages, shared and private, in bytes.
+    pub fn hugetlb(&self) -> u64 {
+        self.get("Shared_Hugetlb").unwrap_or(0) + self.get("Private_Hugetlb").unwrap_or(0)
+    }
+
+    /// `true` if the mapping is backed by hugetlbfs.
+    pub fn is_hugetlb(&self) -> bool {
+        self.has_vm_flag("ht")
+    }
+
+    /// `true` if some of the mapping is backed by transparent huge pages.
+    pub fn uses_thp(&self) -> bool {
+        self.anon_huge_pages() + self.shmem_pmd_mapped() + self.file_pmd_mapped() > 0
+    }
+
+    pub fn vm_flags(&self) -> &[String] {
+        &self.vm_flags
+    }
+
+    /// Returns true if the mapping has the given two-letter kernel flag.
+    pub fn has_vm_flag(&self, flag: &str) -> bool {
+        self.vm_flags.iter().any(|f| f == flag)
+    }
+
+    /// Adds the amounts of `other` to these details, e.g. to compute totals over a process.
+    /// Fields that are not amounts (page sizes, flags) are left untouched.
+    pub fn accumulate(&mut self, other: &SegmentDetails) {
+        const NOT_AMOUNTS: [&str; 4] = [
+            "KernelPageSize",
+            "MMUPageSize",
+            "THPeligible",
+            "ProtectionKey",
+        ];
+
+        for (key, value) in &other.fields {
+            if NOT_AMOUNTS.contains(&key.as_str()) {
+                continue;
+            }
+            *self.fields.entry(key.clone()).or_default() += value;
+        }
+    }
+
+    /// Parses a `Key: value [kB]` line of `/proc/<pid>/smaps`.
+    fn parse_line(&mut self, line: &str) -> anyhow::Result<()> {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Malformed smaps line: {line}"))?;
+
+        if key == "VmFlags" {
+            self.vm_flags = value.split_whitespace().map(String::from).collect();
+            return Ok(());
+        }
+
+        let mut value = value.split_whitespace();
+        let amount: u64 = value
+            .next()
+            .ok_or_else(|| anyhow!("Missing value in smaps line: {line}"))?
+            .parse()
+            .with_context(|| format!("Invalid value in smaps line: {line}"))?;
+        let amount = match value.next() {
+            Some("kB") => amount * 1024,
+            _ => amount,
+        };
+
+        self.fields.insert(key.to_string(), amount);
+        Ok(())
+    }
+}
+
 /// Mapped memory region in the process's virtual address space.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Segment {
     /// Start address
     start: u64,
     /// End address
     end: u64,
     // Permissions
-    permissions: [SegmentPermission; 4],
+    permissions: SegmentPermissions,
     /// Offset into the file/whatever
     offset: u64,
     /// Device (major:minor)
@@ -57,5 +465,400 @@ pub struct Segment {
     /// Inode on that device
     inode: Option<InodeId>,
     /// Usually the file that is backing the mapping
-    path: Path,
+    path: Option<PathBuf>,
+    /// The backing file was unlinked, the ` (deleted)` suffix is stripped from `path`
+    deleted: bool,
+    /// Type of the segment, if known
+    segment_type: Option<SegmentType>,
+    /// Memory usage details, only available when read from `/proc/<pid>/smaps`
+    details: Option<SegmentDetails>,
+    /// NUMA placement, only available when read from `/proc/<pid>/numa_maps`
+    numa: Option<NumaInfo>,
+}
+
+impl Device {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Device { major, minor }
+    }
+
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+}
+
+impl Segment {
+    /// Reads the segments of the process `pid` from `/proc/<pid>/maps`.
+    ///
+    /// See [`segments`] to process them lazily instead.
+    pub fn get_from_pid(pid: Pid) -> anyhow::Result<Vec<Segment>> {
+        segments(pid)?.collect()
+    }
+
+    /// Reads the segments of the process `pid` along with their memory usage details from
+    /// `/proc/<pid>/smaps`.
+    pub fn get_from_pid_with_details(pid: Pid) -> anyhow::Result<Vec<Segment>> {
+        let path = format!("/proc/{pid}/smaps");
+        let smaps = fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+
+        let mut segments = Self::parse_smaps(&smaps)?;
+        Self::infer_bss(&mut segments);
+        Ok(segments)
+    }
+
+    /// Parses the content of `/proc/<pid>/smaps`.
+    pub fn parse_smaps(smaps: &str) -> anyhow::Result<Vec<Segment>> {
+        let mut segments: Vec<Segment> = Vec::new();
+
+        for line in smaps.lines().filter(|line| !line.trim().is_empty()) {
+            // Detail lines start with `Key:`, headers with the address range
+            let is_detail = line
+                .split_whitespace()
+                .next()
+                .is_some_and(|key| key.ends_with(':'));
+
+            if !is_detail {
+                let mut segment: Segment = line.parse()?;
+                segment.details = Some(SegmentDetails::default());
+                segments.push(segment);
+                continue;
+            }
+
+            segments
+                .last_mut()
+                .and_then(|segment| segment.details.as_mut())
+                .ok_or_else(|| anyhow!("smaps line without a segment: {line}"))?
+                .parse_line(line)?;
+        }
+
+        Ok(segments)
+    }
+
+    /// Reads the segments of the process `pid` along with their NUMA placement from
+    /// `/proc/<pid>/numa_maps`. Requires a kernel built with `CONFIG_NUMA`.
+    pub fn get_from_pid_with_numa(pid: Pid) -> anyhow::Result<Vec<Segment>> {
+        let path = format!("/proc/{pid}/numa_maps");
+        let numa_maps =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+
+        let mut segments = Self::get_from_pid(pid)?;
+        Self::attach_numa_maps(&mut segments, &numa_maps)?;
+        Ok(segments)
+    }
+
+    /// Attaches the content of `/proc/<pid>/numa_maps` to `segments`, matching lines by start
+    /// address. Segments without a matching line are left untouched.
+    pub fn attach_numa_maps(segments: &mut [Segment], numa_maps: &str) -> anyhow::Result<()> {
+        for line in numa_maps.lines().filter(|line| !line.trim().is_empty()) {
+            let (start, numa) = parse_numa_line(line)?;
+            if let Ok(i) = segments.binary_search_by_key(&start, |segment| segment.start) {
+                segments[i].numa = Some(numa);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks the anonymous mappings directly following a writable file-backed mapping as BSS.
+    pub(crate) fn infer_bss(segments: &mut [Segment]) {
+        for i in 1..segments.len() {
+            let previous = segments[i - 1].data_bounds();
+            segments[i].infer_bss_after(previous);
+        }
+    }
+
+    /// End address and permissions of an initialized data segment, which may be followed by its
+    /// BSS.
+    fn data_bounds(&self) -> Option<(u64, SegmentPermissions)> {
+        (self.segment_type == Some(SegmentType::Data(DataSegment::Initialized)))
+            .then_some((self.end, self.permissions))
+    }
+
+    /// Marks the segment as BSS if it is an anonymous mapping directly following the data
+    /// segment described by `previous`.
+    fn infer_bss_after(&mut self, previous: Option<(u64, SegmentPermissions)>) {
+        let is_bss = previous.is_some_and(|(end, permissions)| {
+            self.path.is_none()
+                && self.segment_type.is_none()
+                && self.start == end
+                && self.permissions == permissions
+        });
+
+        if is_bss {
+            self.segment_type = Some(SegmentType::Data(DataSegment::Uninitialized));
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Size of the segment in bytes.
+    pub fn size(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn permissions(&self) -> SegmentPermissions {
+        self.permissions
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn device(&self) -> Option<Device> {
+        self.device
+    }
+
+    pub fn inode(&self) -> Option<InodeId> {
+        self.inode
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns true if the file backing the segment was deleted.
+    pub fn deleted(&self) -> bool {
+        self.deleted
+    }
+
+    pub fn segment_type(&self) -> Option<&SegmentType> {
+        self.segment_type.as_ref()
+    }
+
+    pub fn details(&self) -> Option<&SegmentDetails> {
+        self.details.as_ref()
+    }
+
+    /// Bytes of the segment pushed to swap, only available when read from `/proc/<pid>/smaps`.
+    pub fn swap_bytes(&self) -> Option<u64> {
+        self.details.as_ref().map(SegmentDetails::swap)
+    }
+
+    pub fn numa(&self) -> Option<&NumaInfo> {
+        self.numa.as_ref()
+    }
+
+    /// Opens the file backing the segment through `/proc/<pid>/map_files`, which works even if
+    /// the file was deleted. See [`map_files::MapFile`].
+    pub fn open_map_file(&self, pid: Pid) -> anyhow::Result<fs::File> {
+        if self.path.is_none() {
+            bail!(
+                "Segment {:#x}-{:#x} is not file-backed",
+                self.start,
+                self.end
+            );
+        }
+        map_files::open(pid, self.start, self.end)
+    }
+
+    /// Returns true if `address` is inside the segment.
+    pub fn contains(&self, address: u64) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+
+impl FromStr for Segment {
+    type Err = anyhow::Error;
+
+    /// Parses a line of `/proc/<pid>/maps`, e.g.
+    /// `7f2c1e600000-7f2c1e628000 r--p 00000000 08:01 1234  /usr/lib/libc.so.6`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The pathname is the remainder of the line
+        let mut rest = s.trim_start();
+        let mut fields = [""; 5];
+        for field in &mut fields {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            *field = &rest[..end];
+            rest = rest[end..].trim_start();
+        }
+        let [range, permissions, offset, device, inode] = fields;
+        // Only the line terminator is stripped, trailing spaces may be part of the path
+        let pathname = rest.trim_end_matches(['\n', '\r']);
+
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Invalid address range: {s}"))?;
+        let start = u64::from_str_radix(start, 16).context("Invalid start address")?;
+        let end = u64::from_str_radix(end, 16).context("Invalid end address")?;
+
+        let permissions: SegmentPermissions = permissions.parse()?;
+
+        let offset = u64::from_str_radix(offset, 16).context("Invalid offset")?;
+
+        let (major, minor) = device
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid device: {s}"))?;
+        let device = Device::new(
+            u32::from_str_radix(major, 16).context("Invalid device major")?,
+            u32::from_str_radix(minor, 16).context("Invalid device minor")?,
+        );
+        let device = (device != Device::new(0, 0)).then_some(device);
+
+        let inode: InodeId = inode.parse().context("Invalid inode")?;
+        let inode = (inode != 0).then_some(inode);
+
+        let mut deleted = false;
+        let (path, segment_type) = if pathname.is_empty() {
+            (None, None)
+        } else if pathname.starts_with('[') {
+            (None, Some(pathname.parse()?))
+        } else {
+            let segment_type = if permissions.is_executable() {
+                Some(SegmentType::Code)
+            } else if permissions.is_writable() {
+                Some(SegmentType::Data(DataSegment::Initialized))
+            } else {
+                None
+            };
+            let pathname = match pathname.strip_suffix(" (deleted)") {
+                Some(pathname) => {
+                    deleted = true;
+                    pathname
+                }
+                None => pathname,
+            };
+            (Some(PathBuf::from(pathname)), segment_type)
+        };
+
+        Ok(Segment {
+            start,
+            end,
+            permissions,
+            offset,
+            device,
+            inode,
+            path,
+            deleted,
+            segment_type,
+            details: None,
+            numa: None,
+        })
+    }
+}
+
+/// Lazily parsed segments of a process, see [`segments`].
+#[derive(Debug)]
+pub struct SegmentsIter {
+    reader: BufReader<fs::File>,
+    path: String,
+    /// Line buffer, reused across segments
+    line: String,
+    previous: Option<(u64, SegmentPermissions)>,
+}
+
+impl Iterator for SegmentsIter {
+    type Item = anyhow::Result<Segment>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.line.clear();
+        match self.reader.read_line(&mut self.line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e).with_context(|| format!("Failed to read {}", self.path))),
+        }
+
+        let mut segment: Segment = match self.line.trim_end_matches('\n').parse() {
+            Ok(segment) => segment,
+            Err(e) => return Some(Err(e)),
+        };
+        segment.infer_bss_after(self.previous);
+        self.previous = segment.data_bounds();
+
+        Some(Ok(segment))
+    }
+}
+
+/// Iterates over the segments of the process `pid` as `/proc/<pid>/maps` is read, without
+/// collecting them.
+///
+/// Useful to stop at the first match or to go through many processes.
+pub fn segments(pid: Pid) -> anyhow::Result<SegmentsIter> {
+    let path = format!("/proc/{pid}/maps");
+    let file = fs::File::open(&path).with_context(|| format!("Failed to open {path}"))?;
+
+    Ok(SegmentsIter {
+        reader: BufReader::new(file),
+        path,
+        line: String::new(),
+        previous: None,
+    })
+}
+
+/// The segments of a process, sorted by address for fast lookups.
+///
+/// Mappings never overlap, so the sorted list doubles as an interval index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Segments {
+    segments: Vec<Segment>,
+}
+
+impl Segments {
+    /// Reads the segments of the process `pid` from `/proc/<pid>/maps`.
+    pub fn from_pid(pid: Pid) -> anyhow::Result<Self> {
+        Ok(Segment::get_from_pid(pid)?.into())
+    }
+
+    /// Returns the segment containing `address`, in O(log n).
+    pub fn find(&self, address: u64) -> Option<&Segment> {
+        let i = self
+            .segments
+            .partition_point(|segment| segment.end <= address);
+        self.segments
+            .get(i)
+            .filter(|segment| segment.contains(address))
+    }
+
+    /// Returns the segments overlapping `start..end`, in O(log n).
+    pub fn find_range(&self, start: u64, end: u64) -> &[Segment] {
+        let first = self
+            .segments
+            .partition_point(|segment| segment.end <= start);
+        let last = self.segments.partition_point(|segment| segment.start < end);
+        &self.segments[first..last.max(first)]
+    }
+
+    pub fn as_slice(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Segment> {
+        self.segments.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<Segment> {
+        self.segments
+    }
+}
+
+impl From<Vec<Segment>> for Segments {
+    fn from(mut segments: Vec<Segment>) -> Self {
+        segments.sort_by_key(|segment| segment.start);
+        Segments { segments }
+    }
+}
+
+impl<'a> IntoIterator for &'a Segments {
+    type Item = &'a Segment;
+    type IntoIter = std::slice::Iter<'a, Segment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.iter()
+    }
 }
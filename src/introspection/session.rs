@@ -0,0 +1,460 @@
+//! This module contains the scan sessions, saved to disk to resume refining scan results later.
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::MemoryReader,
+    scan::{Predicate, ScanResults, ScanValue, Snapshot, ValueType},
+    segment::Segment,
+};
+
+/// First bytes of a saved session.
+const MAGIC: &[u8; 8] = b"LISCAN\0\0";
+
+/// Version of the session format.
+const VERSION: u8 = 1;
+
+/// File-backed mapping of the target when a session was saved, to move its addresses when the
+/// target is mapped elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MappedFile {
+    start: u64,
+    end: u64,
+    offset: u64,
+    path: PathBuf,
+}
+
+/// Everything needed to resume a scan: the results and the predicates that produced them, and
+/// the snapshot of an unknown-initial-value scan.
+///
+/// Sessions are saved in a compact binary format. When the target restarted, the addresses in
+/// its file-backed mappings (e.g. the data of its modules) are moved to the new location of
+/// these mappings with [`Self::relocate`]; the other addresses are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanSession {
+    results: Option<ScanResults>,
+    snapshot: Option<Snapshot>,
+    predicates: Vec<Predicate>,
+    mappings: Vec<MappedFile>,
+}
+
+impl ScanSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_results(mut self, results: ScanResults) -> Self {
+        self.results = Some(results);
+        self
+    }
+
+    pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Records the file-backed ones of `segments`, the current mappings of the target, to move
+    /// the addresses when the session is relocated.
+    pub fn with_segments(mut self, segments: &[Segment]) -> Self {
+        self.mappings = segments
+            .iter()
+            .filter_map(|segment| {
+                Some(MappedFile {
+                    start: segment.start(),
+                    end: segment.end(),
+                    offset: segment.offset(),
+                    path: segment.path()?.to_path_buf(),
+                })
+            })
+            .collect();
+        self
+    }
+
+    pub fn results(&self) -> Option<&ScanResults> {
+        self.results.as_ref()
+    }
+
+    pub fn results_mut(&mut self) -> Option<&mut ScanResults> {
+        self.results.as_mut()
+    }
+
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Predicates applied to the results so far, oldest first.
+    pub fn predicates(&self) -> &[Predicate] {
+        &self.predicates
+    }
+
+    /// Records a predicate applied to the results outside of the session.
+    pub fn push_predicate(&mut self, predicate: Predicate) {
+        self.predicates.push(predicate);
+    }
+
+    /// Refines the results with `predicate`, see [`ScanResults::refine`], and records it.
+    pub fn refine<R: MemoryReader + ?Sized>(
+        &mut self,
+        reader: &R,
+        predicate: Predicate,
+    ) -> anyhow::Result<()> {
+        let Some(results) = &mut self.results else {
+            bail!("Scan session has no results to refine");
+        };
+        results.refine(reader, &predicate)?;
+        self.predicates.push(predicate);
+        Ok(())
+    }
+
+    /// Moves the addresses in the file-backed mappings recorded with [`Self::with_segments`] to
+    /// the same file ranges in `segments`, the mappings of the restarted target. Returns the
+    /// number of mappings found again.
+    pub fn relocate(&mut self, segments: &[Segment]) -> usize {
+        let moves: Vec<(usize, i128)> = self
+            .mappings
+            .iter()
+            .enumerate()
+            .filter_map(|(index, mapping)| {
+                let segment = segments.iter().find(|segment| {
+                    segment.path() == Some(mapping.path.as_path())
+                        && segment.offset() == mapping.offset
+                        && segment.size() == mapping.end - mapping.start
+                })?;
+                Some((index, segment.start() as i128 - mapping.start as i128))
+            })
+            .collect();
+        let mappings = &self.mappings;
+        let relocate = |address: u64| {
+            moves
+                .iter()
+                .find(|(index, _)| {
+                    (mappings[*index].start..mappings[*index].end).contains(&address)
+                })
+                .map_or(address, |(_, delta)| (address as i128 + delta) as u64)
+        };
+
+        if let Some(results) = &mut self.results {
+            results.relocate(relocate);
+        }
+        if let Some(snapshot) = &mut self.snapshot {
+            snapshot.relocate(relocate);
+        }
+
+        for (index, delta) in &moves {
+            let mapping = &mut self.mappings[*index];
+            mapping.start = (mapping.start as i128 + delta) as u64;
+            mapping.end = (mapping.end as i128 + delta) as u64;
+        }
+        moves.len()
+    }
+
+    /// Saves the session to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let file = fs::File::create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        self.write_to(&mut writer)?;
+        writer
+            .flush()
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads a session saved with [`Self::save`].
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Self::read_from(&mut BufReader::new(file))
+            .with_context(|| format!("Failed to load scan session from {}", path.display()))
+    }
+
+    /// Writes the session in its binary format.
+    pub fn write_to(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        write_varint(writer, self.mappings.len() as u64)?;
+        for mapping in &self.mappings {
+            write_varint(writer, mapping.start)?;
+            write_varint(writer, mapping.end - mapping.start)?;
+            write_varint(writer, mapping.offset)?;
+            write_bytes(writer, mapping.path.as_os_str().as_bytes())?;
+        }
+
+        write_varint(writer, self.predicates.len() as u64)?;
+        for predicate in &self.predicates {
+            write_predicate(writer, predicate)?;
+        }
+
+        match &self.results {
+            Some(results) => {
+                writer.write_all(&[1])?;
+                write_value_type(writer, results.value_type())?;
+                write_varint(writer, results.len() as u64)?;
+                // Addresses are increasing, so their differences are small
+                let mut previous = 0;
+                for address in results.addresses() {
+                    write_varint(writer, address - previous)?;
+                    previous = *address;
+                }
+                writer.write_all(results.raw_values())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+
+        match &self.snapshot {
+            Some(snapshot) => {
+                writer.write_all(&[1])?;
+                write_varint(writer, snapshot.chunks().count() as u64)?;
+                for (address, data) in snapshot.chunks() {
+                    write_varint(writer, address)?;
+                    write_bytes(writer, data)?;
+                }
+            }
+            None => writer.write_all(&[0])?,
+        }
+
+        Ok(())
+    }
+
+    /// Reads a session written with [`Self::write_to`].
+    pub fn read_from(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a scan session");
+        }
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            bail!("Unsupported scan session version {version}");
+        }
+
+        let mut session = ScanSession::new();
+        for _ in 0..read_varint(reader)? {
+            let start = read_varint(reader)?;
+            let size = read_varint(reader)?;
+            let offset = read_varint(reader)?;
+            let path = OsString::from_vec(read_bytes(reader)?);
+            session.mappings.push(MappedFile {
+                start,
+                end: start + size,
+                offset,
+                path: path.into(),
+            });
+        }
+
+        for _ in 0..read_varint(reader)? {
+            session.predicates.push(read_predicate(reader)?);
+        }
+
+        if read_u8(reader)? == 1 {
+            let value_type = read_value_type(reader)?;
+            let count = read_varint(reader)? as usize;
+            let mut addresses = Vec::with_capacity(count.min(1 << 20));
+            let mut previous = 0u64;
+            for _ in 0..count {
+                previous += read_varint(reader)?;
+                addresses.push(previous);
+            }
+            let mut values = vec![0; count * value_type.size()];
+            reader.read_exact(&mut values)?;
+            session.results = Some(ScanResults::from_parts(value_type, addresses, values)?);
+        }
+
+        if read_u8(reader)? == 1 {
+            let count = read_varint(reader)?;
+            let mut chunks = Vec::new();
+            for _ in 0..count {
+                let address = read_varint(reader)?;
+                chunks.push((address, read_bytes(reader)?.into_boxed_slice()));
+            }
+            session.snapshot = Some(Snapshot::from_chunks(chunks));
+        }
+
+        Ok(session)
+    }
+}
+
+/// Writes `value` in LEB128, 7 bits per byte.
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> anyhow::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(reader)?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!("Invalid variable-length integer")
+}
+
+fn read_u8(reader: &mut impl Read) -> anyhow::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_varint(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> anyhow::Result<Vec<u8>> {
+    let len = read_varint(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        bail!("Truncated scan session");
+    }
+    Ok(bytes)
+}
+
+/// Value types in their order in the format.
+const VALUE_TYPES: [ValueType; 10] = [
+    ValueType::I8,
+    ValueType::I16,
+    ValueType::I32,
+    ValueType::I64,
+    ValueType::U8,
+    ValueType::U16,
+    ValueType::U32,
+    ValueType::U64,
+    ValueType::F32,
+    ValueType::F64,
+];
+
+fn write_value_type(writer: &mut impl Write, value_type: ValueType) -> io::Result<()> {
+    match value_type {
+        ValueType::Bytes(len) => {
+            writer.write_all(&[VALUE_TYPES.len() as u8])?;
+            write_varint(writer, len as u64)
+        }
+        _ => {
+            let tag = VALUE_TYPES
+                .iter()
+                .position(|other| *other == value_type)
+                .unwrap_or_default();
+            writer.write_all(&[tag as u8])
+        }
+    }
+}
+
+fn read_value_type(reader: &mut impl Read) -> anyhow::Result<ValueType> {
+    let tag = read_u8(reader)? as usize;
+    match VALUE_TYPES.get(tag) {
+        Some(value_type) => Ok(*value_type),
+        None if tag == VALUE_TYPES.len() => Ok(ValueType::Bytes(read_varint(reader)? as usize)),
+        None => bail!("Invalid value type {tag}"),
+    }
+}
+
+fn write_value(writer: &mut impl Write, value: &ScanValue) -> io::Result<()> {
+    write_value_type(writer, value.value_type())?;
+    writer.write_all(&value.to_bytes())
+}
+
+fn read_value(reader: &mut impl Read) -> anyhow::Result<ScanValue> {
+    let value_type = read_value_type(reader)?;
+    let mut bytes = vec![0; value_type.size()];
+    reader.read_exact(&mut bytes)?;
+    ScanValue::from_bytes(value_type, &bytes)
+}
+
+fn write_predicate(writer: &mut impl Write, predicate: &Predicate) -> io::Result<()> {
+    match predicate {
+        Predicate::Equal(value) => {
+            writer.write_all(&[0])?;
+            write_value(writer, value)
+        }
+        Predicate::Changed => writer.write_all(&[1]),
+        Predicate::Unchanged => writer.write_all(&[2]),
+        Predicate::Increased => writer.write_all(&[3]),
+        Predicate::Decreased => writer.write_all(&[4]),
+        Predicate::IncreasedBy(value) => {
+            writer.write_all(&[5])?;
+            write_value(writer, value)
+        }
+        Predicate::DecreasedBy(value) => {
+            writer.write_all(&[6])?;
+            write_value(writer, value)
+        }
+        Predicate::NearlyEqual { value, epsilon } => {
+            writer.write_all(&[7])?;
+            write_value(writer, value)?;
+            writer.write_all(&epsilon.to_ne_bytes())
+        }
+        Predicate::Between(min, max) => {
+            writer.write_all(&[8])?;
+            write_value(writer, min)?;
+            write_value(writer, max)
+        }
+        Predicate::Masked { value, mask } => {
+            writer.write_all(&[9])?;
+            write_value(writer, value)?;
+            write_value(writer, mask)
+        }
+        Predicate::PointsInto(ranges) => {
+            writer.write_all(&[10])?;
+            write_varint(writer, ranges.len() as u64)?;
+            for range in ranges {
+                write_varint(writer, range.start)?;
+                write_varint(writer, range.end)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_predicate(reader: &mut impl Read) -> anyhow::Result<Predicate> {
+    Ok(match read_u8(reader)? {
+        0 => Predicate::Equal(read_value(reader)?),
+        1 => Predicate::Changed,
+        2 => Predicate::Unchanged,
+        3 => Predicate::Increased,
+        4 => Predicate::Decreased,
+        5 => Predicate::IncreasedBy(read_value(reader)?),
+        6 => Predicate::DecreasedBy(read_value(reader)?),
+        7 => {
+            let value = read_value(reader)?;
+            let mut epsilon = [0; 8];
+            reader.read_exact(&mut epsilon)?;
+            Predicate::NearlyEqual {
+                value,
+                epsilon: f64::from_ne_bytes(epsilon),
+            }
+        }
+        8 => Predicate::Between(read_value(reader)?, read_value(reader)?),
+        9 => Predicate::Masked {
+            value: read_value(reader)?,
+            mask: read_value(reader)?,
+        },
+        10 => {
+            let count = read_varint(reader)?;
+            let mut ranges = Vec::new();
+            for _ in 0..count {
+                ranges.push(read_varint(reader)?..read_varint(reader)?);
+            }
+            Predicate::PointsInto(ranges)
+        }
+        tag => bail!("Invalid predicate {tag}"),
+    })
+}
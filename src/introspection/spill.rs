@@ -0,0 +1,182 @@
+//! This module contains the scan results kept in a temporary file past a memory limit, for scans
+//! matching millions of addresses.
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::scan::{ScanResults, ScanValue, ValueType};
+
+/// Default number of results kept in memory by [`SpilledResults`].
+const DEFAULT_MEMORY_LIMIT: usize = 1 << 20;
+
+/// Results of a scan kept in memory up to a limit, the older ones being written to an unnamed
+/// temporary file, deleted once closed.
+///
+/// Results are usually pushed from [`crate::introspection::scan::Scanner::scan_each`], in
+/// increasing address order.
+#[derive(Debug)]
+pub struct SpilledResults {
+    value_type: ValueType,
+    memory_limit: usize,
+    directory: PathBuf,
+    /// Results not written to the file yet
+    addresses: Vec<u64>,
+    values: Vec<u8>,
+    /// Results written, each as its address and value in native endianness
+    file: Option<BufWriter<File>>,
+    spilled: usize,
+}
+
+impl SpilledResults {
+    /// Keeps up to a million results in memory, spilling the others to the temporary directory.
+    pub fn new(value_type: ValueType) -> Self {
+        SpilledResults {
+            value_type,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            directory: env::temp_dir(),
+            addresses: Vec::new(),
+            values: Vec::new(),
+            file: None,
+            spilled: 0,
+        }
+    }
+
+    /// Sets the number of results kept in memory before they are written to the file.
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> Self {
+        self.memory_limit = memory_limit.max(1);
+        self
+    }
+
+    /// Sets the directory of the temporary file, which must support `O_TMPFILE`.
+    pub fn with_directory(mut self, directory: impl AsRef<Path>) -> Self {
+        self.directory = directory.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.spilled + self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of results written to the file.
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    /// Adds the value `value` found at `address`, spilling the results in memory to the file
+    /// once they reach the limit.
+    pub fn push(&mut self, address: u64, value: &[u8]) -> anyhow::Result<()> {
+        if value.len() != self.value_type.size() {
+            bail!(
+                "Expected a value of {} bytes, got {}",
+                self.value_type.size(),
+                value.len()
+            );
+        }
+        self.addresses.push(address);
+        self.values.extend_from_slice(value);
+        if self.addresses.len() >= self.memory_limit {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the results in memory to the file.
+    fn spill(&mut self) -> anyhow::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .mode(0o600)
+                    .custom_flags(libc::O_TMPFILE)
+                    .open(&self.directory)
+                    .with_context(|| {
+                        format!("Failed to create a temporary file in {:?}", self.directory)
+                    })?;
+                self.file.insert(BufWriter::new(file))
+            }
+        };
+
+        // Reading the results may have moved the cursor
+        file.seek(SeekFrom::End(0))?;
+        let size = self.value_type.size();
+        for (index, address) in self.addresses.iter().enumerate() {
+            file.write_all(&address.to_ne_bytes())?;
+            file.write_all(&self.values[index * size..(index + 1) * size])?;
+        }
+        self.spilled += self.addresses.len();
+        self.addresses.clear();
+        self.values.clear();
+
+        Ok(())
+    }
+
+    /// Iterates over the results in the order they were pushed, reading the spilled ones back
+    /// from the file.
+    pub fn iter(
+        &mut self,
+    ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<(u64, ScanValue)>> + '_> {
+        let value_type = self.value_type;
+        let size = value_type.size();
+        let mut reader = match &mut self.file {
+            Some(file) => {
+                file.flush()?;
+                let mut file = file.get_ref();
+                file.seek(SeekFrom::Start(0))?;
+                Some(BufReader::new(file))
+            }
+            None => None,
+        };
+
+        let spilled = (0..self.spilled).map(move |_| {
+            let mut record = vec![0; 8 + size];
+            reader
+                .as_mut()
+                .context("Missing spill file")?
+                .read_exact(&mut record)
+                .context("Failed to read spilled results")?;
+            let address = u64::from_ne_bytes(record[..8].try_into()?);
+            Ok((address, ScanValue::from_bytes(value_type, &record[8..])?))
+        });
+        let values = &self.values;
+        let memory = self
+            .addresses
+            .iter()
+            .enumerate()
+            .map(move |(index, address)| {
+                let value = &values[index * size..(index + 1) * size];
+                Ok((*address, ScanValue::from_bytes(value_type, value)?))
+            });
+
+        Ok(spilled.chain(memory))
+    }
+
+    /// Loads all the results in memory, e.g. to refine them once few enough.
+    pub fn into_results(mut self) -> anyhow::Result<ScanResults> {
+        let mut addresses = Vec::with_capacity(self.len());
+        let mut values = Vec::with_capacity(self.len() * self.value_type.size());
+        for result in self.iter()? {
+            let (address, value) = result?;
+            addresses.push(address);
+            values.extend_from_slice(&value.to_bytes());
+        }
+
+        ScanResults::from_parts(self.value_type, addresses, values)
+    }
+}
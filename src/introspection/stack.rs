@@ -0,0 +1,67 @@
+//! This module contains the structs and functions to introspect the kernel stack of a process.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_stack.5.html
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+
+/// A frame of the kernel stack of a task, e.g. `[<0>] do_wait+0x1b4/0x2a0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelStackFrame {
+    /// Return address, 0 unless kernel pointers are exposed (see `kptr_restrict`)
+    pub address: u64,
+    /// Kernel function name
+    pub symbol: String,
+    /// Offset of the return address in the function
+    pub offset: Option<u64>,
+    /// Size of the function
+    pub size: Option<u64>,
+    /// Kernel module containing the function, if not built-in
+    pub module: Option<String>,
+}
+
+impl FromStr for KernelStackFrame {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (address, rest) = s
+            .strip_prefix("[<")
+            .and_then(|s| s.split_once(">]"))
+            .ok_or_else(|| anyhow!("Malformed kernel stack frame: {s}"))?;
+        let address = u64::from_str_radix(address, 16)
+            .with_context(|| format!("Invalid kernel stack address: {address}"))?;
+
+        let mut rest = rest.split_whitespace();
+        let location = rest
+            .next()
+            .ok_or_else(|| anyhow!("Missing symbol in kernel stack frame: {s}"))?;
+        let module = rest
+            .next()
+            .map(|module| module.trim_matches(['[', ']']).to_string());
+
+        let parse_hex = |n: &str| u64::from_str_radix(n.trim_start_matches("0x"), 16).ok();
+        let (symbol, offset, size) = match location.split_once('+') {
+            Some((symbol, range)) => match range.split_once('/') {
+                Some((offset, size)) => (symbol, parse_hex(offset), parse_hex(size)),
+                None => (symbol, parse_hex(range), None),
+            },
+            None => (location, None, None),
+        };
+
+        Ok(KernelStackFrame {
+            address,
+            symbol: symbol.to_string(),
+            offset,
+            size,
+            module,
+        })
+    }
+}
+
+/// Parses the content of `/proc/<pid>/stack`, innermost frame first.
+pub fn parse_kernel_stack(s: &str) -> anyhow::Result<Vec<KernelStackFrame>> {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
@@ -0,0 +1,619 @@
+//! This module contains the system call tracer, reporting the system calls of a traced thread
+//! with their decoded arguments and return values, like strace.
+//! Based on https://www.man7.org/linux/man-pages/man2/syscalls.2.html
+use std::{collections::HashSet, fmt, ops::ControlFlow, sync::mpsc};
+
+use anyhow::bail;
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryReaderExt},
+    arch::{Arch, Bitness},
+    process::Pid,
+    ptrace::{PtraceMem, PtraceSession, SignalInfo, Stop},
+};
+
+/// Default number of bytes read from string and buffer arguments.
+const DEFAULT_STRING_LIMIT: usize = 256;
+
+/// Errors are returned as values from -4095 to -1, see `IS_ERR_VALUE` in the kernel.
+const MAX_ERRNO: i64 = 4095;
+
+/// `AUDIT_ARCH_*` values reported by `PTRACE_GET_SYSCALL_INFO`.
+const AUDIT_ARCH_I386: u32 = 0x4000_0003;
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+
+/// How a system call argument is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+    /// Signed integer
+    Int,
+    /// Flags, masks, addresses and other unsigned values
+    Hex,
+    Fd,
+    /// NUL-terminated string read by the kernel, e.g. a path
+    Str,
+    /// Buffer read by the kernel, whose length is the argument at the index
+    InBuffer(usize),
+    /// Buffer written by the kernel, whose length is the return value
+    OutBuffer,
+    /// `struct timespec` read by the kernel
+    Timespec,
+}
+
+/// Name and arguments of a system call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyscallInfo {
+    pub name: &'static str,
+    pub args: &'static [ArgKind],
+}
+
+/// Number of a system call missing on an architecture.
+const NONE: u64 = u64::MAX;
+
+macro_rules! syscalls {
+    ($($name:ident $x86_64:tt $x86:tt $aarch64:tt [$($arg:expr),*];)*) => {
+        /// Numbers of the known system calls on x86_64, x86 and aarch64.
+        static SYSCALLS: &[(u64, u64, u64, SyscallInfo)] = {
+            use ArgKind::*;
+            &[$((
+                $x86_64,
+                $x86,
+                $aarch64,
+                SyscallInfo {
+                    name: stringify!($name),
+                    args: &[$($arg),*],
+                },
+            )),*]
+        };
+    };
+}
+
+syscalls! {
+    read 0 3 63 [Fd, OutBuffer, Int];
+    write 1 4 64 [Fd, InBuffer(2), Int];
+    open 2 5 NONE [Str, Hex, Hex];
+    close 3 6 57 [Fd];
+    stat 4 NONE NONE [Str, Hex];
+    fstat 5 NONE 80 [Fd, Hex];
+    lstat 6 NONE NONE [Str, Hex];
+    poll 7 168 NONE [Hex, Int, Int];
+    lseek 8 19 62 [Fd, Int, Int];
+    mmap 9 NONE 222 [Hex, Int, Hex, Hex, Fd, Hex];
+    mprotect 10 125 226 [Hex, Int, Hex];
+    munmap 11 91 215 [Hex, Int];
+    brk 12 45 214 [Hex];
+    rt_sigaction 13 174 134 [Int, Hex, Hex, Int];
+    rt_sigprocmask 14 175 135 [Int, Hex, Hex, Int];
+    rt_sigreturn 15 173 139 [];
+    ioctl 16 54 29 [Fd, Hex, Hex];
+    pread64 17 180 67 [Fd, OutBuffer, Int, Int];
+    pwrite64 18 181 68 [Fd, InBuffer(2), Int, Int];
+    readv 19 145 65 [Fd, Hex, Int];
+    writev 20 146 66 [Fd, Hex, Int];
+    access 21 33 NONE [Str, Hex];
+    pipe 22 42 NONE [Hex];
+    select 23 142 NONE [Int, Hex, Hex, Hex, Hex];
+    sched_yield 24 158 124 [];
+    mremap 25 163 216 [Hex, Int, Int, Hex, Hex];
+    msync 26 144 227 [Hex, Int, Hex];
+    mincore 27 218 232 [Hex, Int, Hex];
+    madvise 28 219 233 [Hex, Int, Int];
+    dup 32 41 23 [Fd];
+    dup2 33 63 NONE [Fd, Fd];
+    pause 34 29 NONE [];
+    nanosleep 35 162 101 [Timespec, Hex];
+    getpid 39 20 172 [];
+    sendfile 40 187 71 [Fd, Fd, Hex, Int];
+    socket 41 359 198 [Int, Int, Int];
+    connect 42 362 203 [Fd, InBuffer(2), Int];
+    accept 43 NONE 202 [Fd, Hex, Hex];
+    sendto 44 369 206 [Fd, InBuffer(2), Int, Hex, Hex, Int];
+    recvfrom 45 371 207 [Fd, OutBuffer, Int, Hex, Hex, Hex];
+    sendmsg 46 370 211 [Fd, Hex, Hex];
+    recvmsg 47 372 212 [Fd, Hex, Hex];
+    shutdown 48 373 210 [Fd, Int];
+    bind 49 361 200 [Fd, InBuffer(2), Int];
+    listen 50 363 201 [Fd, Int];
+    clone 56 120 220 [Hex, Hex, Hex, Hex, Hex];
+    fork 57 2 NONE [];
+    vfork 58 190 NONE [];
+    execve 59 11 221 [Str, Hex, Hex];
+    exit 60 1 93 [Int];
+    wait4 61 114 260 [Int, Hex, Hex, Hex];
+    kill 62 37 129 [Int, Int];
+    uname 63 122 160 [Hex];
+    fcntl 72 NONE 25 [Fd, Int, Hex];
+    flock 73 143 32 [Fd, Int];
+    fsync 74 118 82 [Fd];
+    ftruncate 77 93 46 [Fd, Int];
+    getdents 78 141 NONE [Fd, Hex, Int];
+    getcwd 79 183 17 [OutBuffer, Int];
+    chdir 80 12 49 [Str];
+    rename 82 38 NONE [Str, Str];
+    mkdir 83 39 NONE [Str, Hex];
+    rmdir 84 40 NONE [Str];
+    unlink 87 10 NONE [Str];
+    readlink 89 85 NONE [Str, OutBuffer, Int];
+    chmod 90 15 NONE [Str, Hex];
+    fchmod 91 94 52 [Fd, Hex];
+    umask 95 60 166 [Hex];
+    gettimeofday 96 78 169 [Hex, Hex];
+    getuid 102 NONE 174 [];
+    getgid 104 NONE 176 [];
+    geteuid 107 NONE 175 [];
+    getegid 108 NONE 177 [];
+    getppid 110 64 173 [];
+    mlock 149 150 228 [Hex, Int];
+    munlock 150 151 229 [Hex, Int];
+    prctl 157 172 167 [Int, Hex, Hex, Hex, Hex];
+    arch_prctl 158 384 NONE [Int, Hex];
+    gettid 186 224 178 [];
+    tkill 200 238 130 [Int, Int];
+    futex 202 240 98 [Hex, Int, Int, Hex, Hex, Int];
+    getdents64 217 220 61 [Fd, Hex, Int];
+    set_tid_address 218 258 96 [Hex];
+    clock_gettime 228 265 113 [Int, Hex];
+    clock_nanosleep 230 267 115 [Int, Hex, Timespec, Hex];
+    exit_group 231 252 94 [Int];
+    epoll_wait 232 256 NONE [Fd, Hex, Int, Int];
+    epoll_ctl 233 255 21 [Fd, Int, Fd, Hex];
+    tgkill 234 270 131 [Int, Int, Int];
+    waitid 247 284 95 [Int, Int, Hex, Hex, Hex];
+    openat 257 295 56 [Fd, Str, Hex, Hex];
+    mkdirat 258 296 34 [Fd, Str, Hex];
+    newfstatat 262 NONE 79 [Fd, Str, Hex, Hex];
+    unlinkat 263 301 35 [Fd, Str, Hex];
+    renameat 264 302 38 [Fd, Str, Fd, Str];
+    readlinkat 267 305 78 [Fd, Str, OutBuffer, Int];
+    faccessat 269 307 48 [Fd, Str, Hex];
+    pselect6 270 308 72 [Int, Hex, Hex, Hex, Hex, Hex];
+    ppoll 271 309 73 [Hex, Int, Hex, Hex, Int];
+    set_robust_list 273 311 99 [Hex, Int];
+    epoll_pwait 281 319 22 [Fd, Hex, Int, Int, Hex, Int];
+    accept4 288 364 242 [Fd, Hex, Hex, Hex];
+    eventfd2 290 328 19 [Int, Hex];
+    epoll_create1 291 329 20 [Hex];
+    dup3 292 330 24 [Fd, Fd, Hex];
+    pipe2 293 331 59 [Hex, Hex];
+    prlimit64 302 340 261 [Int, Int, Hex, Hex];
+    getrandom 318 355 278 [OutBuffer, Int, Hex];
+    memfd_create 319 356 279 [Str, Hex];
+    execveat 322 358 281 [Fd, Str, Hex, Hex, Hex];
+    statx 332 383 291 [Fd, Str, Hex, Hex, Hex];
+    rseq 334 386 293 [Hex, Int, Hex, Hex];
+    pidfd_open 434 434 434 [Int, Hex];
+    clone3 435 435 435 [Hex, Int];
+    close_range 436 436 436 [Fd, Fd, Hex];
+    openat2 437 437 437 [Fd, Str, Hex, Int];
+    faccessat2 439 439 439 [Fd, Str, Hex, Hex];
+    mmap2 NONE 192 NONE [Hex, Int, Hex, Hex, Fd, Hex];
+    stat64 NONE 195 NONE [Str, Hex];
+    lstat64 NONE 196 NONE [Str, Hex];
+    fstat64 NONE 197 NONE [Fd, Hex];
+    getuid32 NONE 199 NONE [];
+    getgid32 NONE 200 NONE [];
+    geteuid32 NONE 201 NONE [];
+    getegid32 NONE 202 NONE [];
+    fcntl64 NONE 221 NONE [Fd, Int, Hex];
+    fstatat64 NONE 300 NONE [Fd, Str, Hex, Hex];
+}
+
+/// Number of the system call `syscall` of `SYSCALLS` on `arch`.
+fn number_on(arch: Arch, syscall: &(u64, u64, u64, SyscallInfo)) -> u64 {
+    match arch {
+        Arch::X86_64 => syscall.0,
+        Arch::X86 => syscall.1,
+        Arch::Aarch64 => syscall.2,
+    }
+}
+
+/// Returns the name and arguments of the system call `number` of `arch`, if known.
+pub fn syscall_info(arch: Arch, number: u64) -> Option<&'static SyscallInfo> {
+    SYSCALLS
+        .iter()
+        .find(|syscall| number != NONE && number_on(arch, syscall) == number)
+        .map(|syscall| &syscall.3)
+}
+
+/// Returns the number of the system call `name` on `arch`, if known.
+pub fn syscall_number(arch: Arch, name: &str) -> Option<u64> {
+    SYSCALLS
+        .iter()
+        .find(|syscall| syscall.3.name == name)
+        .map(|syscall| number_on(arch, syscall))
+        .filter(|number| *number != NONE)
+}
+
+/// A decoded system call argument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SyscallArg {
+    Int(i64),
+    Hex(u64),
+    Fd(i32),
+    /// String truncated to the limit of the tracer, invalid UTF-8 replaced
+    Str(String),
+    /// Buffer truncated to the limit of the tracer
+    Bytes(Vec<u8>),
+    Timespec {
+        sec: i64,
+        nsec: i64,
+    },
+    /// Null pointer
+    Null,
+    /// Pointer to memory which could not be read
+    Unreadable(u64),
+}
+
+impl fmt::Display for SyscallArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyscallArg::Int(value) => write!(f, "{value}"),
+            SyscallArg::Hex(value) => write!(f, "{value:#x}"),
+            SyscallArg::Fd(libc::AT_FDCWD) => write!(f, "AT_FDCWD"),
+            SyscallArg::Fd(fd) => write!(f, "{fd}"),
+            SyscallArg::Str(string) => write!(f, "{string:?}"),
+            SyscallArg::Bytes(bytes) => write!(f, "\"{}\"", bytes.escape_ascii()),
+            SyscallArg::Timespec { sec, nsec } => write!(f, "{{tv_sec={sec}, tv_nsec={nsec}}}"),
+            SyscallArg::Null => write!(f, "NULL"),
+            SyscallArg::Unreadable(address) => write!(f, "{address:#x}"),
+        }
+    }
+}
+
+/// Writes `name(args)`, or `syscall_<number>(args)` for unknown system calls.
+fn write_call(
+    f: &mut fmt::Formatter<'_>,
+    name: Option<&str>,
+    number: u64,
+    args: &[SyscallArg],
+) -> fmt::Result {
+    match name {
+        Some(name) => write!(f, "{name}(")?,
+        None => write!(f, "syscall_{number}(")?,
+    }
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{arg}")?;
+    }
+    write!(f, ")")
+}
+
+/// A thread entering a system call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallEntry {
+    pub tid: Pid,
+    /// Architecture of the system call, x86 for the `int 0x80` of a x86_64 process
+    pub arch: Arch,
+    pub number: u64,
+    /// Name of the system call, if known
+    pub name: Option<&'static str>,
+    /// Raw argument registers
+    pub raw_args: [u64; 6],
+    /// Decoded arguments, all six as [`SyscallArg::Hex`] for unknown system calls
+    pub args: Vec<SyscallArg>,
+}
+
+impl fmt::Display for SyscallEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_call(f, self.name, self.number, &self.args)
+    }
+}
+
+/// A system call returning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallExit {
+    pub tid: Pid,
+    pub arch: Arch,
+    pub number: u64,
+    pub name: Option<&'static str>,
+    /// Arguments decoded at the entry, with the buffers written by the kernel filled in
+    pub args: Vec<SyscallArg>,
+    /// Value returned by the kernel, a negated error number on failure
+    pub return_value: i64,
+}
+
+impl SyscallExit {
+    /// Error number of the system call if it failed.
+    pub fn error(&self) -> Option<i32> {
+        (-MAX_ERRNO..0)
+            .contains(&self.return_value)
+            .then(|| -self.return_value as i32)
+    }
+}
+
+impl fmt::Display for SyscallExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_call(f, self.name, self.number, &self.args)?;
+        write!(f, " = {}", self.return_value)
+    }
+}
+
+/// What stopped the thread of a [`SyscallTracer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    Entry(SyscallEntry),
+    Exit(SyscallExit),
+    /// The thread received a signal, delivered when it resumes unless changed with
+    /// [`PtraceSession::set_pending_signal`] or [`PtraceSession::set_signal_info`]
+    Signal(SignalInfo),
+    /// The thread ended
+    Exited(Stop),
+}
+
+/// Traces the system calls of a thread with `PTRACE_SYSCALL`, decoding their arguments and
+/// return values.
+///
+/// The thread stops at each system call, even the ones filtered out, so traced programs run
+/// markedly slower. The string and buffer arguments are read from the memory of the thread while
+/// it is stopped.
+#[derive(Debug)]
+pub struct SyscallTracer {
+    session: PtraceSession,
+    /// Numbers of the reported system calls by architecture, all of them if `None`
+    filter: Option<HashSet<(Arch, u64)>>,
+    string_limit: usize,
+    /// System call the thread is in, between its entry and exit stops
+    current: Option<SyscallEntry>,
+}
+
+impl SyscallTracer {
+    /// Traces the system calls of the stopped thread of `session`.
+    pub fn new(session: PtraceSession) -> anyhow::Result<Self> {
+        session.set_options(libc::PTRACE_O_TRACESYSGOOD)?;
+        Ok(SyscallTracer {
+            session,
+            filter: None,
+            string_limit: DEFAULT_STRING_LIMIT,
+            current: None,
+        })
+    }
+
+    /// Reports only the system calls named `names`, e.g. `["openat", "read"]`, on any
+    /// architecture they exist on.
+    pub fn with_filter(mut self, names: &[&str]) -> anyhow::Result<Self> {
+        let mut filter = HashSet::new();
+        for name in names {
+            let numbers: Vec<(Arch, u64)> = [Arch::X86, Arch::X86_64, Arch::Aarch64]
+                .into_iter()
+                .filter_map(|arch| Some((arch, syscall_number(arch, name)?)))
+                .collect();
+            if numbers.is_empty() {
+                bail!("Unknown system call {name}");
+            }
+            filter.extend(numbers);
+        }
+        self.filter = Some(filter);
+
+        Ok(self)
+    }
+
+    /// Reads at most `limit` bytes of the string and buffer arguments.
+    pub fn with_string_limit(mut self, limit: usize) -> Self {
+        self.string_limit = limit;
+        self
+    }
+
+    pub fn session(&self) -> &PtraceSession {
+        &self.session
+    }
+
+    pub fn session_mut(&mut self) -> &mut PtraceSession {
+        &mut self.session
+    }
+
+    pub fn into_session(self) -> PtraceSession {
+        self.session
+    }
+
+    /// System call the thread is in, if stopped at its entry or exit.
+    pub fn current(&self) -> Option<&SyscallEntry> {
+        self.current.as_ref()
+    }
+
+    /// Resumes the thread, delivering its pending signal, see [`PtraceSession::pending_signal`],
+    /// until the next reported event.
+    pub fn next_event(&mut self) -> anyhow::Result<TraceEvent> {
+        loop {
+            let signal = self.session.pending_signal();
+            self.session.syscall(signal)?;
+            match self.session.wait()? {
+                Stop::Syscall => {
+                    if let Some(event) = self.syscall_stop()? {
+                        return Ok(event);
+                    }
+                }
+                stop if stop.is_exit() => return Ok(TraceEvent::Exited(stop)),
+                Stop::Signal(_) => return Ok(TraceEvent::Signal(self.session.signal_info()?)),
+                // Event and group stops are resumed
+                _ => {}
+            }
+        }
+    }
+
+    /// Calls `callback` for each event until it returns [`ControlFlow::Break`] or the thread
+    /// ends, leaving it stopped.
+    pub fn trace(
+        &mut self,
+        mut callback: impl FnMut(&TraceEvent) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            let event = self.next_event()?;
+            if callback(&event).is_break() || matches!(event, TraceEvent::Exited(_)) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sends each event through `sender` until the receiver is dropped or the thread ends.
+    ///
+    /// The ptrace session belongs to the thread which attached, so this runs on that thread,
+    /// e.g. a thread spawned to attach and trace while another one receives.
+    pub fn send_to(&mut self, sender: &mpsc::Sender<TraceEvent>) -> anyhow::Result<()> {
+        self.trace(|event| match sender.send(event.clone()) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        })
+    }
+
+    /// Decodes the entry or exit the thread is stopped at, `None` if filtered out.
+    fn syscall_stop(&mut self) -> anyhow::Result<Option<TraceEvent>> {
+        let info = self.session.syscall_info()?;
+        let arch = match info.arch {
+            AUDIT_ARCH_I386 => Arch::X86,
+            AUDIT_ARCH_X86_64 => Arch::X86_64,
+            AUDIT_ARCH_AARCH64 => Arch::Aarch64,
+            _ => self.session.arch()?,
+        };
+
+        match info.op {
+            libc::PTRACE_SYSCALL_INFO_ENTRY => {
+                // SAFETY: the kernel filled the entry member for an entry stop
+                let entry = unsafe { info.u.entry };
+                let entry = self.decode_entry(arch, entry.nr, entry.args);
+                let reported = self.is_reported(arch, entry.number);
+                self.current = Some(entry.clone());
+                Ok(reported.then_some(TraceEvent::Entry(entry)))
+            }
+            libc::PTRACE_SYSCALL_INFO_EXIT => {
+                // SAFETY: the kernel filled the exit member for an exit stop
+                let return_value = unsafe { info.u.exit.sval };
+                // The entry is missed when attaching during a system call
+                let Some(entry) = self.current.take() else {
+                    return Ok(None);
+                };
+                if !self.is_reported(entry.arch, entry.number) {
+                    return Ok(None);
+                }
+                Ok(Some(TraceEvent::Exit(
+                    self.decode_exit(entry, return_value),
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_reported(&self, arch: Arch, number: u64) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.contains(&(arch, number)))
+    }
+
+    fn memory(&self) -> PtraceMem {
+        self.session.memory()
+    }
+
+    fn decode_entry(&self, arch: Arch, number: u64, raw_args: [u64; 6]) -> SyscallEntry {
+        let bitness = arch.bitness();
+        let info = syscall_info(arch, number);
+        let args = match info {
+            Some(info) => info
+                .args
+                .iter()
+                .zip(raw_args)
+                .map(|(kind, value)| self.decode_arg(bitness, *kind, value, &raw_args))
+                .collect(),
+            None => raw_args
+                .iter()
+                .map(|value| SyscallArg::Hex(*value))
+                .collect(),
+        };
+
+        SyscallEntry {
+            tid: self.session.tid(),
+            arch,
+            number,
+            name: info.map(|info| info.name),
+            raw_args,
+            args,
+        }
+    }
+
+    fn decode_arg(
+        &self,
+        bitness: Bitness,
+        kind: ArgKind,
+        value: u64,
+        raw_args: &[u64; 6],
+    ) -> SyscallArg {
+        let value = value & bitness.max_address();
+        let signed = match bitness {
+            Bitness::Bits32 => value as u32 as i32 as i64,
+            Bitness::Bits64 => value as i64,
+        };
+        if value == 0
+            && matches!(
+                kind,
+                ArgKind::Str | ArgKind::InBuffer(_) | ArgKind::Timespec
+            )
+        {
+            return SyscallArg::Null;
+        }
+
+        let memory = self.memory();
+        let read = || -> anyhow::Result<SyscallArg> {
+            match kind {
+                ArgKind::Int => Ok(SyscallArg::Int(signed)),
+                ArgKind::Hex | ArgKind::OutBuffer => Ok(SyscallArg::Hex(value)),
+                ArgKind::Fd => Ok(SyscallArg::Fd(signed as i32)),
+                ArgKind::Str => {
+                    let string = memory.read_cstring(value, self.string_limit)?;
+                    Ok(SyscallArg::Str(string.decode_lossy()))
+                }
+                ArgKind::InBuffer(len) => {
+                    let len = raw_args[len].min(self.string_limit as u64) as usize;
+                    Ok(SyscallArg::Bytes(memory.read_bytes(value, len)?))
+                }
+                ArgKind::Timespec => {
+                    let size = bitness.pointer_size();
+                    let bytes = memory.read_bytes(value, 2 * size)?;
+                    let field = |i: usize| -> anyhow::Result<i64> {
+                        let field = bitness.pointer_from_bytes(&bytes[i * size..(i + 1) * size])?;
+                        Ok(match bitness {
+                            Bitness::Bits32 => field as u32 as i32 as i64,
+                            Bitness::Bits64 => field as i64,
+                        })
+                    };
+                    Ok(SyscallArg::Timespec {
+                        sec: field(0)?,
+                        nsec: field(1)?,
+                    })
+                }
+            }
+        };
+
+        read().unwrap_or(SyscallArg::Unreadable(value))
+    }
+
+    fn decode_exit(&self, entry: SyscallEntry, return_value: i64) -> SyscallExit {
+        let return_value = match entry.arch.bitness() {
+            Bitness::Bits32 => return_value as i32 as i64,
+            Bitness::Bits64 => return_value,
+        };
+        let mut args = entry.args;
+        if let Some(info) = syscall_info(entry.arch, entry.number) {
+            for (i, kind) in info.args.iter().enumerate() {
+                let address = entry.raw_args[i] & entry.arch.bitness().max_address();
+                if *kind != ArgKind::OutBuffer || address == 0 || return_value < 0 {
+                    continue;
+                }
+                let len = (return_value as usize).min(self.string_limit);
+                args[i] = match self.memory().read_bytes(address, len) {
+                    Ok(bytes) => SyscallArg::Bytes(bytes),
+                    Err(_) => SyscallArg::Unreadable(address),
+                };
+            }
+        }
+
+        SyscallExit {
+            tid: entry.tid,
+            arch: entry.arch,
+            number: entry.number,
+            name: entry.name,
+            args,
+            return_value,
+        }
+    }
+}
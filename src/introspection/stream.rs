@@ -0,0 +1,167 @@
+//! This module contains the structs to read large regions of memory chunk by chunk.
+use std::{io, ops::Range};
+
+use crate::introspection::{access::MemoryReader, pagemap::page_size};
+
+/// Default size of the chunks yielded by a [`MemoryStream`].
+const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+
+/// What a [`MemoryStream`] does with the unreadable pages of its range, e.g. guard pages or
+/// mappings removed while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HolePolicy {
+    /// Yields an error and ends the stream.
+    #[default]
+    Fail,
+    /// Leaves the unreadable pages out: chunks only contain readable bytes, and may be shorter.
+    Skip,
+    /// Replaces the unreadable pages with zeros, so the chunks cover the whole range.
+    ZeroFill,
+}
+
+/// Contiguous bytes read by a [`MemoryStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub address: u64,
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    /// Address range covered by the chunk.
+    pub fn range(&self) -> Range<u64> {
+        self.address..self.address + self.data.len() as u64
+    }
+}
+
+/// Reads a range of memory in chunks of bounded size, either as an iterator of [`Chunk`] or as
+/// an [`io::Read`] of the bytes in order.
+#[derive(Debug)]
+pub struct MemoryStream<'a, R: ?Sized> {
+    reader: &'a R,
+    position: u64,
+    end: u64,
+    chunk_size: usize,
+    policy: HolePolicy,
+    page_size: u64,
+    /// Chunk being consumed by `io::Read`, and the offset reached in it
+    current: Option<(Chunk, usize)>,
+}
+
+impl<'a, R: MemoryReader + ?Sized> MemoryStream<'a, R> {
+    pub fn new(reader: &'a R, range: Range<u64>, policy: HolePolicy) -> Self {
+        MemoryStream {
+            reader,
+            position: range.start,
+            end: range.end.max(range.start),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            policy,
+            page_size: page_size(),
+            current: None,
+        }
+    }
+
+    /// Sets the maximum size of the chunks, at least one byte.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn policy(&self) -> HolePolicy {
+        self.policy
+    }
+
+    /// Address of the next byte to read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reads the chunk at the current position page by page, to handle its unreadable pages.
+    fn read_holes(&mut self, size: usize) -> Option<Chunk> {
+        let end = self.position + size as u64;
+        let mut chunk = Chunk {
+            address: self.position,
+            data: Vec::with_capacity(size),
+        };
+
+        let mut address = self.position;
+        while address < end {
+            let next = (address - address % self.page_size + self.page_size).min(end);
+            let start = chunk.data.len();
+            chunk.data.resize(start + (next - address) as usize, 0);
+
+            if self.reader.read(address, &mut chunk.data[start..]).is_err() {
+                match self.policy {
+                    HolePolicy::ZeroFill => chunk.data[start..].fill(0),
+                    // Ends the chunk at the hole, the next one starts after it
+                    _ if start > 0 => {
+                        chunk.data.truncate(start);
+                        break;
+                    }
+                    _ => {
+                        chunk.data.clear();
+                        chunk.address = next;
+                    }
+                }
+            }
+            address = next;
+        }
+
+        self.position = chunk.address + chunk.data.len() as u64;
+        if chunk.data.is_empty() {
+            self.position = address;
+            return None;
+        }
+        Some(chunk)
+    }
+}
+
+impl<R: MemoryReader + ?Sized> Iterator for MemoryStream<'_, R> {
+    type Item = anyhow::Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position < self.end {
+            let size = self.chunk_size.min((self.end - self.position) as usize);
+            let mut data = vec![0; size];
+
+            match self.reader.read(self.position, &mut data) {
+                Ok(()) => {
+                    let address = self.position;
+                    self.position += size as u64;
+                    return Some(Ok(Chunk { address, data }));
+                }
+                Err(error) if self.policy == HolePolicy::Fail => {
+                    self.position = self.end;
+                    return Some(Err(error));
+                }
+                Err(_) => {
+                    if let Some(chunk) = self.read_holes(size) {
+                        return Some(Ok(chunk));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<R: MemoryReader + ?Sized> io::Read for MemoryStream<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some((chunk, offset)) = &mut self.current {
+                if *offset < chunk.data.len() {
+                    let len = buffer.len().min(chunk.data.len() - *offset);
+                    buffer[..len].copy_from_slice(&chunk.data[*offset..*offset + len]);
+                    *offset += len;
+                    return Ok(len);
+                }
+            }
+
+            match self.next() {
+                Some(Ok(chunk)) => self.current = Some((chunk, 0)),
+                Some(Err(error)) => return Err(io::Error::other(error)),
+                None => return Ok(0),
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! This module contains the extraction of the printable strings of the memory of a process, like
+//! `strings(1)` does for files.
+use std::collections::HashSet;
+
+/// Default minimum number of characters of the strings extracted.
+const DEFAULT_MIN_LEN: usize = 4;
+
+/// Encoding of a string found in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringEncoding {
+    /// Printable ASCII characters and tabs.
+    Ascii,
+    /// Printable UTF-8 text with at least one non-ASCII character. Text only made of ASCII
+    /// characters is reported as ASCII when ASCII strings are extracted too.
+    Utf8,
+    /// Printable UTF-16 little-endian text of Latin-1 characters, at even addresses. Other
+    /// characters are left out as almost any pair of ASCII bytes is a CJK character.
+    Utf16Le,
+}
+
+/// A printable string found in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundString {
+    pub address: u64,
+    pub encoding: StringEncoding,
+    pub text: String,
+}
+
+impl FoundString {
+    /// Number of bytes of the string in memory.
+    pub fn size(&self) -> usize {
+        match self.encoding {
+            StringEncoding::Utf16Le => self.text.encode_utf16().count() * 2,
+            _ => self.text.len(),
+        }
+    }
+}
+
+/// What [`crate::introspection::scan::Scanner::strings`] extracts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringOptions {
+    min_len: usize,
+    encodings: Vec<StringEncoding>,
+    dedup: bool,
+}
+
+impl Default for StringOptions {
+    fn default() -> Self {
+        StringOptions {
+            min_len: DEFAULT_MIN_LEN,
+            encodings: vec![
+                StringEncoding::Ascii,
+                StringEncoding::Utf8,
+                StringEncoding::Utf16Le,
+            ],
+            dedup: false,
+        }
+    }
+}
+
+impl StringOptions {
+    /// Extracts the strings of at least 4 characters in all the encodings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the minimum number of characters of the strings, at least 1.
+    pub fn with_min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len.max(1);
+        self
+    }
+
+    pub fn with_encodings(mut self, encodings: &[StringEncoding]) -> Self {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Only reports the first occurrence of each text in each encoding.
+    pub fn deduplicated(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub fn min_len(&self) -> usize {
+        self.min_len
+    }
+
+    pub fn encodings(&self) -> &[StringEncoding] {
+        &self.encodings
+    }
+
+    fn has(&self, encoding: StringEncoding) -> bool {
+        self.encodings.contains(&encoding)
+    }
+}
+
+fn is_printable(c: char) -> bool {
+    c == '\t' || !c.is_control()
+}
+
+/// String being read, and the address of its first byte.
+#[derive(Debug, Default)]
+struct Run {
+    start: u64,
+    text: String,
+    chars: usize,
+}
+
+impl Run {
+    fn push(&mut self, address: u64, c: char) {
+        if self.chars == 0 {
+            self.start = address;
+        }
+        self.text.push(c);
+        self.chars += 1;
+    }
+}
+
+/// Reads the strings of memory given in contiguous pieces, so strings across pieces are found
+/// whole.
+#[derive(Debug)]
+pub(crate) struct StringExtractor<'a> {
+    options: &'a StringOptions,
+    /// Address following the last byte fed, `None` before the first one
+    next: Option<u64>,
+    ascii: Run,
+    utf8: Run,
+    /// Bytes of the UTF-8 character being read, its address and the number of bytes missing
+    utf8_partial: (Vec<u8>, u64, usize),
+    utf16: Run,
+    /// Low byte of the UTF-16 code unit being read
+    utf16_low: Option<u8>,
+    seen: HashSet<(StringEncoding, String)>,
+    found: Vec<FoundString>,
+}
+
+impl<'a> StringExtractor<'a> {
+    pub(crate) fn new(options: &'a StringOptions) -> Self {
+        StringExtractor {
+            options,
+            next: None,
+            ascii: Run::default(),
+            utf8: Run::default(),
+            utf8_partial: (Vec::new(), 0, 0),
+            utf16: Run::default(),
+            utf16_low: None,
+            seen: HashSet::new(),
+            found: Vec::new(),
+        }
+    }
+
+    /// Reads the bytes `data` at `address`, following the last ones fed or not.
+    pub(crate) fn feed(&mut self, address: u64, data: &[u8]) {
+        if self.next != Some(address) {
+            self.flush();
+        }
+        for (offset, byte) in data.iter().enumerate() {
+            let address = address + offset as u64;
+            if self.options.has(StringEncoding::Ascii) {
+                self.feed_ascii(address, *byte);
+            }
+            if self.options.has(StringEncoding::Utf8) {
+                self.feed_utf8(address, *byte);
+            }
+            if self.options.has(StringEncoding::Utf16Le) {
+                self.feed_utf16(address, *byte);
+            }
+        }
+        self.next = Some(address + data.len() as u64);
+    }
+
+    /// Returns the strings found, in address order.
+    pub(crate) fn finish(mut self) -> Vec<FoundString> {
+        self.flush();
+
+        // Leaves out the ASCII parts of UTF-8 strings
+        let utf8: Vec<(u64, u64)> = self
+            .found
+            .iter()
+            .filter(|found| found.encoding == StringEncoding::Utf8)
+            .map(|found| (found.address, found.address + found.size() as u64))
+            .collect();
+        self.found.retain(|found| {
+            let index = utf8.partition_point(|(start, _)| *start <= found.address);
+            found.encoding != StringEncoding::Ascii
+                || index == 0
+                || utf8[index - 1].1 < found.address + found.size() as u64
+        });
+
+        self.found.sort_by_key(|found| found.address);
+        self.found
+    }
+
+    fn feed_ascii(&mut self, address: u64, byte: u8) {
+        if byte.is_ascii_graphic() || byte == b' ' || byte == b'\t' {
+            self.ascii.push(address, byte as char);
+        } else {
+            self.end(StringEncoding::Ascii);
+        }
+    }
+
+    fn feed_utf8(&mut self, address: u64, byte: u8) {
+        let (partial, start, missing) = &mut self.utf8_partial;
+        if *missing > 0 {
+            if byte & 0xc0 == 0x80 {
+                partial.push(byte);
+                *missing -= 1;
+                if *missing == 0 {
+                    let start = *start;
+                    match std::str::from_utf8(partial)
+                        .ok()
+                        .and_then(|s| s.chars().next())
+                    {
+                        Some(c) if is_printable(c) => self.utf8.push(start, c),
+                        _ => self.end(StringEncoding::Utf8),
+                    }
+                    self.utf8_partial.0.clear();
+                }
+                return;
+            }
+            // Truncated character, the byte may start the next one
+            partial.clear();
+            *missing = 0;
+            self.end(StringEncoding::Utf8);
+        }
+
+        let (partial, start, missing) = &mut self.utf8_partial;
+        match byte {
+            0x00..=0x7f if is_printable(byte as char) => self.utf8.push(address, byte as char),
+            0xc2..=0xf4 => {
+                partial.push(byte);
+                *start = address;
+                *missing = match byte {
+                    0xc2..=0xdf => 1,
+                    0xe0..=0xef => 2,
+                    _ => 3,
+                };
+            }
+            _ => self.end(StringEncoding::Utf8),
+        }
+    }
+
+    fn feed_utf16(&mut self, address: u64, byte: u8) {
+        if address.is_multiple_of(2) {
+            self.utf16_low = Some(byte);
+            return;
+        }
+        let Some(low) = self.utf16_low.take() else {
+            return;
+        };
+        match char::from(low) {
+            c if byte == 0 && is_printable(c) => self.utf16.push(address - 1, c),
+            _ => self.end(StringEncoding::Utf16Le),
+        }
+    }
+
+    /// Ends the strings being read, e.g. at a gap in the memory fed.
+    pub(crate) fn flush(&mut self) {
+        self.utf8_partial = (Vec::new(), 0, 0);
+        self.utf16_low = None;
+        for encoding in [
+            StringEncoding::Ascii,
+            StringEncoding::Utf8,
+            StringEncoding::Utf16Le,
+        ] {
+            self.end(encoding);
+        }
+    }
+
+    /// Ends the string being read in `encoding`, reporting it if long enough.
+    fn end(&mut self, encoding: StringEncoding) {
+        let run = std::mem::take(match encoding {
+            StringEncoding::Ascii => &mut self.ascii,
+            StringEncoding::Utf8 => &mut self.utf8,
+            StringEncoding::Utf16Le => &mut self.utf16,
+        });
+        if run.chars < self.options.min_len {
+            return;
+        }
+        // Pure ASCII text is reported once
+        if encoding == StringEncoding::Utf8
+            && self.options.has(StringEncoding::Ascii)
+            && run.text.is_ascii()
+        {
+            return;
+        }
+        if self.options.dedup && !self.seen.insert((encoding, run.text.clone())) {
+            return;
+        }
+
+        self.found.push(FoundString {
+            address: run.start,
+            encoding,
+            text: run.text,
+        });
+    }
+}
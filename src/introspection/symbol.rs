@@ -0,0 +1,732 @@
+//! This module contains the symbol tables of the modules mapped by a process, loaded from the
+//! dynamic symbols in memory, from the files on disk, or from their separate debug files.
+//! Based on https://www.man7.org/linux/man-pages/man5/elf.5.html and
+//! https://sourceware.org/gdb/current/onlinedocs/gdb.html/Separate-Debug-Files.html
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::introspection::{
+    access::MemoryReader,
+    arch::Bitness,
+    elf::{
+        Dynamic, Elf, Fields, Module, SectionHeader, DT_GNU_HASH, DT_HASH, DT_STRSZ, DT_STRTAB,
+        DT_SYMENT, DT_SYMTAB, PT_NOTE, SHT_DYNSYM, SHT_NOTE, SHT_SYMTAB,
+    },
+    process::Pid,
+};
+
+/// Index of undefined symbols, `SHN_UNDEF`.
+const SHN_UNDEF: u16 = 0;
+/// Index of symbols with an absolute value, `SHN_ABS`.
+const SHN_ABS: u16 = 0xfff1;
+/// Type of the GNU build ID note.
+const NT_GNU_BUILD_ID: u32 = 3;
+/// Directory of the separate debug files, looked up by build ID or by path.
+const DEBUG_DIRECTORY: &str = "usr/lib/debug";
+/// Largest table of the dynamic section read from memory, e.g. the dynamic string table.
+const MAX_DYNAMIC_TABLE: u64 = 1 << 28;
+
+/// Size of a symbol of a class.
+fn symbol_size(bitness: Bitness) -> usize {
+    match bitness {
+        Bitness::Bits32 => 16,
+        Bitness::Bits64 => 24,
+    }
+}
+
+/// Type of a symbol, from `st_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    NoType,
+    /// A variable
+    Object,
+    Function,
+    Section,
+    File,
+    Common,
+    /// A thread-local variable, whose value is an offset in the TLS block of the module
+    Tls,
+    /// A function returning the address of the implementation to use, e.g. `memcpy`
+    GnuIfunc,
+    Other(u8),
+}
+
+impl From<u8> for SymbolKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => SymbolKind::NoType,
+            1 => SymbolKind::Object,
+            2 => SymbolKind::Function,
+            3 => SymbolKind::Section,
+            4 => SymbolKind::File,
+            5 => SymbolKind::Common,
+            6 => SymbolKind::Tls,
+            10 => SymbolKind::GnuIfunc,
+            kind => SymbolKind::Other(kind),
+        }
+    }
+}
+
+/// Binding of a symbol, from `st_info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolBinding {
+    /// Only visible in its module, e.g. a `static` function
+    Local,
+    Global,
+    /// Global, but overridden by a global symbol of the same name
+    Weak,
+    /// Global, and unique in the whole process even when loaded in several namespaces
+    GnuUnique,
+    Other(u8),
+}
+
+impl From<u8> for SymbolBinding {
+    fn from(binding: u8) -> Self {
+        match binding {
+            0 => SymbolBinding::Local,
+            1 => SymbolBinding::Global,
+            2 => SymbolBinding::Weak,
+            10 => SymbolBinding::GnuUnique,
+            binding => SymbolBinding::Other(binding),
+        }
+    }
+}
+
+/// Demangles a Rust or C++ symbol name, e.g. `_ZN3std2io5stdio6_print17h0123456789abcdefE` into
+/// `std::io::stdio::_print`, without the hash of Rust names.
+pub fn demangle(name: &str) -> Option<String> {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{demangled:#}"));
+    }
+    if name.starts_with("_Z") {
+        return cpp_demangle::Symbol::new(name).ok()?.demangle().ok();
+    }
+
+    None
+}
+
+/// A symbol defined by a module, e.g. a function or a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// Name as found in the file, possibly mangled and versioned, e.g. `memcpy@@GLIBC_2.14`
+    name: String,
+    /// Demangled name, if the name is mangled
+    demangled: Option<String>,
+    /// Address in the process, or in the file if the table was not relocated
+    address: u64,
+    size: u64,
+    kind: SymbolKind,
+    binding: SymbolBinding,
+}
+
+impl Symbol {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name without the mangling of Rust or C++, the name itself if it is not mangled.
+    pub fn demangled(&self) -> &str {
+        self.demangled.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Size of the function or variable, in bytes, 0 if unknown.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+
+    pub fn binding(&self) -> SymbolBinding {
+        self.binding
+    }
+
+    /// Returns true if `address` is inside the symbol, or is the symbol itself if its size is
+    /// unknown.
+    pub fn contains(&self, address: u64) -> bool {
+        address == self.address || address.wrapping_sub(self.address) < self.size
+    }
+}
+
+/// An entry of a symbol table, defined or not, e.g. a function a module imports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SymbolEntry {
+    /// Name, empty if it is not in the string table
+    pub(crate) name: String,
+    pub(crate) value: u64,
+    pub(crate) size: u64,
+    pub(crate) info: u8,
+    /// Index of the section the symbol is defined in, [`SHN_UNDEF`] if undefined
+    pub(crate) section: u16,
+}
+
+impl SymbolEntry {
+    pub(crate) fn kind(&self) -> SymbolKind {
+        SymbolKind::from(self.info & 0xf)
+    }
+
+    pub(crate) fn binding(&self) -> SymbolBinding {
+        SymbolBinding::from(self.info >> 4)
+    }
+
+    pub(crate) fn is_defined(&self) -> bool {
+        self.section != SHN_UNDEF
+    }
+
+    /// The symbol the entry defines, unless it is undefined, unnamed, or names a section or a
+    /// file.
+    fn to_symbol(&self) -> Option<Symbol> {
+        let kind = self.kind();
+        if !self.is_defined()
+            || self.name.is_empty()
+            || matches!(kind, SymbolKind::Section | SymbolKind::File)
+            || (self.section == SHN_ABS && self.value == 0)
+        {
+            return None;
+        }
+
+        Some(Symbol {
+            name: self.name.clone(),
+            demangled: demangle(&self.name),
+            address: self.value,
+            size: self.size,
+            kind,
+            binding: self.binding(),
+        })
+    }
+}
+
+/// Parses every entry of the symbol table `symbols`, named in the string table `strings`, of a
+/// file of the given class, so that they keep their index.
+fn parse_entries(
+    symbols: &[u8],
+    strings: &[u8],
+    entry_size: usize,
+    bitness: Bitness,
+) -> Vec<SymbolEntry> {
+    if entry_size < symbol_size(bitness) {
+        return Vec::new();
+    }
+
+    symbols
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            let mut fields = Fields::new(entry, bitness);
+            let (name, value, size, info, section) = match bitness {
+                Bitness::Bits32 => {
+                    let name = fields.u32();
+                    let (value, size) = (fields.word(), fields.word());
+                    let (info, _other) = (fields.u8(), fields.u8());
+                    (name, value, size, info, fields.u16())
+                }
+                Bitness::Bits64 => {
+                    let name = fields.u32();
+                    let (info, _other) = (fields.u8(), fields.u8());
+                    let section = fields.u16();
+                    (name, fields.word(), fields.word(), info, section)
+                }
+            };
+            let name = strings.get(name as usize..).unwrap_or_default();
+            let len = name
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(name.len());
+
+            SymbolEntry {
+                name: String::from_utf8_lossy(&name[..len]).into_owned(),
+                value,
+                size,
+                info,
+                section,
+            }
+        })
+        .collect()
+}
+
+/// Parses the defined symbols of the symbol table `symbols`, named in the string table
+/// `strings`, of a file of the given class.
+fn parse_symbols(
+    symbols: &[u8],
+    strings: &[u8],
+    entry_size: usize,
+    bitness: Bitness,
+) -> Vec<Symbol> {
+    parse_entries(symbols, strings, entry_size, bitness)
+        .iter()
+        .filter_map(SymbolEntry::to_symbol)
+        .collect()
+}
+
+/// The dynamic symbol table of a module, as mapped in memory.
+#[derive(Debug, Clone)]
+pub(crate) struct DynamicSymbols {
+    pub(crate) elf: Elf,
+    pub(crate) dynamic: Dynamic,
+    pub(crate) bias: u64,
+    /// Every entry, in order, their values not relocated
+    pub(crate) entries: Vec<SymbolEntry>,
+}
+
+/// Reads the dynamic symbol table of the module mapped at `base` in `memory`, the start of the
+/// mapping of its start.
+pub(crate) fn read_dynamic_symbols<M>(memory: &M, base: u64) -> anyhow::Result<DynamicSymbols>
+where
+    M: MemoryReader + ?Sized,
+{
+    let elf = Elf::from_memory(memory, base)?;
+    let bias = elf.load_bias(base);
+    let Some(dynamic) = elf.read_dynamic(memory, bias)? else {
+        bail!("No dynamic section at {base:#x}");
+    };
+    let (Some(symbols), Some(strings), Some(strings_size)) = (
+        dynamic.address(DT_SYMTAB),
+        dynamic.address(DT_STRTAB),
+        dynamic.get(DT_STRSZ),
+    ) else {
+        bail!("No dynamic symbol table at {base:#x}");
+    };
+
+    let bitness = elf.header().bitness;
+    let entry_size = dynamic
+        .get(DT_SYMENT)
+        .unwrap_or(symbol_size(bitness) as u64);
+    let count = if let Some(hash) = dynamic.address(DT_HASH) {
+        // The number of chains of the hash table is the number of symbols
+        let mut words = [0; 8];
+        memory.read(hash, &mut words)?;
+        u32::from_le_bytes(words[4..].try_into().unwrap()) as u64
+    } else if let Some(hash) = dynamic.address(DT_GNU_HASH) {
+        gnu_hash_symbol_count(memory, &elf, bias, hash)?
+    } else {
+        bail!("No hash table at {base:#x} to count the dynamic symbols");
+    };
+
+    let strings_size = table_len(&elf, bias, strings, Some(strings_size))?;
+    let strings = memory.read_bytes(strings, strings_size)?;
+    let symbols_size = table_len(&elf, bias, symbols, count.checked_mul(entry_size))?;
+    let symbols = memory.read_bytes(symbols, symbols_size)?;
+    let entries = parse_entries(&symbols, &strings, entry_size as usize, bitness);
+
+    Ok(DynamicSymbols {
+        elf,
+        dynamic,
+        bias,
+        entries,
+    })
+}
+
+/// Size of the `len` bytes at `address` of a table of the dynamic section of `elf` mapped with
+/// the load bias `bias`, checked to be within one of its loaded segments before allocating them.
+/// `len` is `None` when its computation overflowed.
+fn table_len(elf: &Elf, bias: u64, address: u64, len: Option<u64>) -> anyhow::Result<usize> {
+    let Some(len) = len.filter(|&len| len <= MAX_DYNAMIC_TABLE) else {
+        bail!("Dynamic table at {address:#x} is too large");
+    };
+    let end = address.checked_add(len);
+    let loaded = end.is_some_and(|end| {
+        elf.loads().any(|load| {
+            let start = load.vaddr.wrapping_add(bias);
+            address >= start
+                && start
+                    .checked_add(load.memsz)
+                    .is_some_and(|load_end| end <= load_end)
+        })
+    });
+    if !loaded {
+        bail!("{len:#x} bytes of dynamic table at {address:#x} are not in a loaded segment");
+    }
+
+    Ok(len as usize)
+}
+
+/// Reads `len` bytes at `offset` of `file`.
+fn read_at(file: &fs::File, offset: u64, len: u64) -> anyhow::Result<Vec<u8>> {
+    // The length comes from the file, checked before allocating it
+    let size = file
+        .metadata()
+        .context("Failed to get the size of the file")?
+        .len();
+    if offset.checked_add(len).is_none_or(|end| end > size) {
+        bail!("{len:#x} bytes at {offset:#x} are past the end of the file of {size:#x} bytes");
+    }
+    let mut bytes = vec![0; len as usize];
+    file.read_exact_at(&mut bytes, offset)
+        .with_context(|| format!("Failed to read {len:#x} bytes at {offset:#x}"))?;
+
+    Ok(bytes)
+}
+
+/// Reads the symbols of the sections of type `kind` of `file`, e.g. [`SHT_SYMTAB`].
+fn read_section_symbols(file: &fs::File, elf: &Elf, kind: u32) -> anyhow::Result<Vec<Symbol>> {
+    let sections = elf.section_headers();
+    let mut symbols = Vec::new();
+    for section in sections.iter().filter(|section| section.kind == kind) {
+        let Some(strings) = sections.get(section.link as usize) else {
+            bail!("Invalid string table of {}", section.name);
+        };
+        let strings = read_at(file, strings.offset, strings.size)?;
+        let bytes = read_at(file, section.offset, section.size)?;
+        let bitness = elf.header().bitness;
+        let entry_size = match section.entsize {
+            0 => symbol_size(bitness),
+            entry_size => entry_size as usize,
+        };
+        symbols.extend(parse_symbols(&bytes, &strings, entry_size, bitness));
+    }
+
+    Ok(symbols)
+}
+
+/// Parses the GNU build ID in the notes `notes`, if any.
+fn parse_build_id(mut notes: &[u8]) -> Option<Vec<u8>> {
+    while notes.len() >= 12 {
+        let word = |i: usize| u32::from_le_bytes(notes[i..i + 4].try_into().unwrap()) as usize;
+        let (name_size, desc_size, kind) = (word(0), word(4), word(8) as u32);
+        let desc_start = 12 + name_size.next_multiple_of(4);
+        let desc_end = desc_start.checked_add(desc_size)?;
+        let desc = notes.get(desc_start..desc_end)?;
+        if kind == NT_GNU_BUILD_ID && notes.get(12..12 + name_size) == Some(b"GNU\0") {
+            return Some(desc.to_vec());
+        }
+        notes = notes.get(desc_start + desc_size.next_multiple_of(4)..)?;
+    }
+
+    None
+}
+
+/// Reads the GNU build ID of `file`, from its note sections or its note segments.
+fn read_build_id(file: &fs::File, elf: &Elf) -> Option<Vec<u8>> {
+    let sections = elf
+        .section_headers()
+        .iter()
+        .filter(|section| section.kind == SHT_NOTE)
+        .map(|section| (section.offset, section.size));
+    let segments = elf
+        .program_headers()
+        .iter()
+        .filter(|program_header| program_header.kind == PT_NOTE)
+        .map(|program_header| (program_header.offset, program_header.filesz));
+
+    sections
+        .chain(segments)
+        .filter_map(|(offset, size)| read_at(file, offset, size).ok())
+        .find_map(|notes| parse_build_id(&notes))
+}
+
+/// Reads the name of the separate debug file in the `.gnu_debuglink` section of `file`, if any.
+fn read_debuglink(file: &fs::File, section: &SectionHeader) -> Option<String> {
+    // The name is followed by padding and a CRC of the debug file
+    let bytes = read_at(file, section.offset, section.size).ok()?;
+    let len = bytes.iter().position(|&byte| byte == 0)?;
+    String::from_utf8(bytes[..len].to_vec()).ok()
+}
+
+/// Joins `path`, absolute in the root directory `root`, to `root`.
+fn in_root(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Candidate paths of the separate debug file of the file at `path`, by build ID first, then
+/// by the name of its `.gnu_debuglink` next to it or in the debug directory.
+fn debug_paths(
+    root: &Path,
+    path: &Path,
+    build_id: Option<&[u8]>,
+    debuglink: Option<&str>,
+) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some([first, rest @ ..]) = build_id {
+        let rest: String = rest.iter().map(|byte| format!("{byte:02x}")).collect();
+        paths.push(in_root(
+            root,
+            &Path::new("/")
+                .join(DEBUG_DIRECTORY)
+                .join(".build-id")
+                .join(format!("{first:02x}"))
+                .join(format!("{rest}.debug")),
+        ));
+    }
+    if let (Some(debuglink), Some(directory)) = (debuglink, path.parent()) {
+        for candidate in [
+            directory.join(debuglink),
+            directory.join(".debug").join(debuglink),
+            Path::new("/")
+                .join(DEBUG_DIRECTORY)
+                .join(directory.strip_prefix("/").unwrap_or(directory))
+                .join(debuglink),
+        ] {
+            if candidate != path {
+                paths.push(in_root(root, &candidate));
+            }
+        }
+    }
+
+    paths
+}
+
+/// Symbols defined by a module, sorted by address, e.g. to find the function an address is in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    /// Indices of the symbols by name, mangled and demangled
+    by_name: HashMap<String, usize>,
+    /// Size of the largest symbol, bounding the search for the symbol of an address
+    max_size: u64,
+    /// Path of the separate debug file the symbols were read from, if any
+    debug_file: Option<PathBuf>,
+}
+
+impl SymbolTable {
+    /// Builds a table from `symbols`, dropping the duplicates found in both the dynamic and
+    /// the full symbol table.
+    fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+        symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        // Global symbols win over local ones of the same name
+        for (i, symbol) in symbols.iter().enumerate() {
+            for name in [Some(&symbol.name), symbol.demangled.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                match by_name.get(name) {
+                    Some(&j) if symbols[j].binding != SymbolBinding::Local => {}
+                    _ => {
+                        by_name.insert(name.clone(), i);
+                    }
+                }
+            }
+        }
+
+        SymbolTable {
+            max_size: symbols.iter().map(|symbol| symbol.size).max().unwrap_or(0),
+            symbols,
+            by_name,
+            debug_file: None,
+        }
+    }
+
+    /// Reads the dynamic symbols of the module mapped at `base` in `memory`, the start of the
+    /// mapping of its start, at their addresses in the process.
+    ///
+    /// Only the symbols exported to other modules are found this way, see [`Self::load`].
+    pub fn from_memory<M>(memory: &M, base: u64) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let dynamic = read_dynamic_symbols(memory, base)?;
+        let mut symbols: Vec<_> = dynamic
+            .entries
+            .iter()
+            .filter_map(SymbolEntry::to_symbol)
+            .collect();
+        relocate(&mut symbols, dynamic.bias);
+
+        Ok(Self::new(symbols))
+    }
+
+    /// Reads the symbols of the ELF file at `path`, at their addresses in the file: its
+    /// dynamic symbols, and its full symbol table, from its separate debug file if it was
+    /// stripped.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Elf::from_file(&file)
+            .and_then(|elf| Self::from_file(&file, &elf, path, Path::new("/"), 0))
+            .with_context(|| format!("Failed to read the symbols of {}", path.display()))
+    }
+
+    /// Reads the symbols of `file` of headers `elf`, found at `path` in the root directory
+    /// `root` where its separate debug file is looked up, moved by the load bias `bias`.
+    fn from_file(
+        file: &fs::File,
+        elf: &Elf,
+        path: &Path,
+        root: &Path,
+        bias: u64,
+    ) -> anyhow::Result<Self> {
+        let mut symbols = read_section_symbols(file, elf, SHT_DYNSYM)?;
+        let full = read_section_symbols(file, elf, SHT_SYMTAB)?;
+        let mut debug_file = None;
+        if full.is_empty() {
+            debug_file = Self::read_debug_file(file, elf, path, root, &mut symbols);
+        }
+        symbols.extend(full);
+        relocate(&mut symbols, bias);
+
+        let mut table = Self::new(symbols);
+        table.debug_file = debug_file;
+        Ok(table)
+    }
+
+    /// Adds the full symbol table of the separate debug file of `file` to `symbols`, returning
+    /// its path if one was found.
+    fn read_debug_file(
+        file: &fs::File,
+        elf: &Elf,
+        path: &Path,
+        root: &Path,
+        symbols: &mut Vec<Symbol>,
+    ) -> Option<PathBuf> {
+        let build_id = read_build_id(file, elf);
+        let debuglink = elf
+            .section(".gnu_debuglink")
+            .and_then(|section| read_debuglink(file, section));
+
+        for candidate in debug_paths(root, path, build_id.as_deref(), debuglink.as_deref()) {
+            let Ok(debug) = fs::File::open(&candidate) else {
+                continue;
+            };
+            let Ok(debug_elf) = Elf::from_file(&debug) else {
+                continue;
+            };
+            // A debug file found by name must be the one of this build
+            if build_id.is_some() && read_build_id(&debug, &debug_elf) != build_id {
+                continue;
+            }
+            if let Ok(debug_symbols) = read_section_symbols(&debug, &debug_elf, SHT_SYMTAB) {
+                if !debug_symbols.is_empty() {
+                    symbols.extend(debug_symbols);
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Reads the symbols of `module`, mapped by the process `pid` whose memory is `memory`, at
+    /// their addresses in the process.
+    ///
+    /// The symbols are read from the file of the module, and from its separate debug file in
+    /// the root directory of the process if it was stripped. If the file cannot be read, only
+    /// the dynamic symbols are read from memory.
+    pub fn load<M>(module: &Module, pid: Pid, memory: &M) -> anyhow::Result<Self>
+    where
+        M: MemoryReader + ?Sized,
+    {
+        let root = PathBuf::from(format!("/proc/{pid}/root"));
+        let from_file = module.open(pid).and_then(|file| {
+            let elf = Elf::from_file(&file)?;
+            let bias = elf.load_bias(module.base());
+            Self::from_file(&file, &elf, module.path(), &root, bias)
+        });
+
+        match from_file {
+            Ok(table) => Ok(table),
+            Err(error) => Self::from_memory(memory, module.base()).with_context(|| {
+                format!(
+                    "Failed to read the symbols of {}: {error:#}",
+                    module.path().display()
+                )
+            }),
+        }
+    }
+
+    /// The symbols, sorted by address.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Path of the separate debug file the full symbol table was read from, if any.
+    pub fn debug_file(&self) -> Option<&Path> {
+        self.debug_file.as_deref()
+    }
+
+    /// The symbol named `name`, mangled or demangled, global symbols first.
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.by_name.get(name).map(|&i| &self.symbols[i])
+    }
+
+    /// The symbol `address` is in, with the offset of the address from its start, e.g. to
+    /// name the function of a return address.
+    pub fn symbol_at(&self, address: u64) -> Option<(&Symbol, u64)> {
+        let end = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        // The symbols starting before `address` and close enough to contain it, the innermost
+        // first
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .take_while(|symbol| address - symbol.address <= self.max_size)
+            .find(|symbol| symbol.contains(address))
+            .map(|symbol| (symbol, address - symbol.address))
+    }
+}
+
+/// Moves `symbols` by the load bias `bias` of their module, except the thread-local ones.
+fn relocate(symbols: &mut [Symbol], bias: u64) {
+    for symbol in symbols {
+        if symbol.kind != SymbolKind::Tls {
+            symbol.address = symbol.address.wrapping_add(bias);
+        }
+    }
+}
+
+/// Counts the dynamic symbols from the GNU hash table at `address` of `elf` mapped with the load
+/// bias `bias`: past the symbols that are not hashed, the last one is at the end of the chain of
+/// the last bucket.
+fn gnu_hash_symbol_count<M>(memory: &M, elf: &Elf, bias: u64, address: u64) -> anyhow::Result<u64>
+where
+    M: MemoryReader + ?Sized,
+{
+    let mut header = [0; 16];
+    memory.read(address, &mut header)?;
+    let word = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+    let (bucket_count, hashed_start, bloom_size) = (word(0), word(1), word(2));
+
+    let bloom_size = bloom_size as u64 * elf.header().bitness.pointer_size() as u64;
+    let buckets = address
+        .checked_add(16 + bloom_size)
+        .ok_or_else(|| anyhow!("Invalid GNU hash table at {address:#x}"))?;
+    let buckets_size = table_len(elf, bias, buckets, (bucket_count as u64).checked_mul(4))?;
+    let bytes = memory.read_bytes(buckets, buckets_size)?;
+    let last = bytes
+        .chunks_exact(4)
+        .map(|bucket| u32::from_le_bytes(bucket.try_into().unwrap()))
+        .max()
+        .unwrap_or(0);
+    if last < hashed_start {
+        return Ok(hashed_start as u64);
+    }
+
+    // The last entry of a chain has its lowest bit set
+    let chains = buckets + buckets_size as u64;
+    let mut index = last;
+    loop {
+        let mut entry = [0; 4];
+        let offset = (index - hashed_start) as u64 * 4;
+        let entry_address = chains
+            .checked_add(offset)
+            .ok_or_else(|| anyhow!("Unterminated GNU hash chain at {chains:#x}"))?;
+        memory.read(entry_address, &mut entry)?;
+        if u32::from_le_bytes(entry) & 1 != 0 {
+            return Ok(index as u64 + 1);
+        }
+        index = index
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Unterminated GNU hash chain at {chains:#x}"))?;
+    }
+}
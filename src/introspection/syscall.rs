@@ -0,0 +1,77 @@
+//! This module contains the structs and functions to introspect the system call a process is blocked in.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_syscall.5.html
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+/// A system call a task is currently blocked in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Syscall {
+    /// System call number, as defined for the architecture of the target
+    pub number: u64,
+    /// Arguments passed in registers, unused ones hold leftover values
+    pub args: [u64; 6],
+    /// Stack pointer
+    pub sp: u64,
+    /// Program counter
+    pub pc: u64,
+}
+
+/// Content of `/proc/<pid>/syscall`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallState {
+    /// The task is not blocked.
+    Running,
+    /// The task is blocked but not in a system call, e.g. stopped by a signal or handling a
+    /// page fault.
+    Blocked {
+        /// Stack pointer
+        sp: u64,
+        /// Program counter
+        pc: u64,
+    },
+    /// The task is blocked in a system call.
+    InSyscall(Syscall),
+}
+
+impl FromStr for SyscallState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "running" {
+            return Ok(SyscallState::Running);
+        }
+
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let hex = |i: usize| -> anyhow::Result<u64> {
+            let field = fields
+                .get(i)
+                .ok_or_else(|| anyhow!("Missing field {i} in syscall: {s}"))?;
+            u64::from_str_radix(field.trim_start_matches("0x"), 16)
+                .with_context(|| format!("Invalid syscall field: {field}"))
+        };
+
+        match fields.first() {
+            Some(&"-1") => Ok(SyscallState::Blocked {
+                sp: hex(1)?,
+                pc: hex(2)?,
+            }),
+            Some(number) => {
+                if fields.len() != 9 {
+                    bail!("Expected 9 fields in syscall, got {}: {s}", fields.len());
+                }
+
+                Ok(SyscallState::InSyscall(Syscall {
+                    number: number
+                        .parse()
+                        .with_context(|| format!("Invalid syscall number: {number}"))?,
+                    args: [hex(1)?, hex(2)?, hex(3)?, hex(4)?, hex(5)?, hex(6)?],
+                    sp: hex(7)?,
+                    pc: hex(8)?,
+                }))
+            }
+            None => bail!("Empty syscall file"),
+        }
+    }
+}
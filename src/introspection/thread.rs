@@ -0,0 +1,357 @@
+//! This module contains the threads of a process, as found in `/proc/<pid>/task/<tid>`.
+//! Based on https://www.man7.org/linux/man-pages/man5/proc_pid_task.5.html
+use std::{fs, io};
+
+use anyhow::{bail, Context};
+
+use crate::introspection::{
+    access::{MemoryReader, MemoryWriter},
+    arch::{Arch, Registers},
+    process::{Pid, Process, ProcessState},
+    ptrace::{PtraceSession, Stop},
+};
+
+/// Returns the IDs of the threads of the process `pid`, in increasing order.
+pub fn tids(pid: Pid) -> anyhow::Result<Vec<Pid>> {
+    let path = format!("/proc/{pid}/task");
+    let mut tids = Vec::new();
+    for entry in fs::read_dir(&path).with_context(|| format!("Failed to read {path}"))? {
+        if let Some(tid) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            tids.push(tid);
+        }
+    }
+    tids.sort_unstable();
+
+    Ok(tids)
+}
+
+/// Where a thread stepped with [`Thread::step`] or its variants stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepEvent {
+    /// The thread stopped before the instruction at `pc`, after `steps` instructions, a call
+    /// stepped over counting as one
+    Stepped { pc: u64, steps: u64 },
+    /// The thread received `signal` after `steps` instructions, which is delivered when it is
+    /// stepped again, see [`PtraceSession::pending_signal`]
+    Signal {
+        signal: libc::c_int,
+        pc: u64,
+        steps: u64,
+    },
+    /// The thread ended
+    Exited(Stop),
+}
+
+/// Executes a single instruction of the stopped thread of `session`, delivering its pending
+/// signal if any. Returns `None` once the instruction is executed, or the event interrupting it.
+fn single_step(session: &mut PtraceSession) -> anyhow::Result<Option<StepEvent>> {
+    loop {
+        session.step(session.pending_signal())?;
+        match session.wait()? {
+            Stop::Signal(libc::SIGTRAP) => {
+                session.suppress_signal();
+                return Ok(None);
+            }
+            stop if stop.is_exit() => return Ok(Some(StepEvent::Exited(stop))),
+            Stop::Signal(signal) => {
+                return Ok(Some(StepEvent::Signal {
+                    signal,
+                    pc: session.registers()?.pc(),
+                    steps: 0,
+                }))
+            }
+            // The instruction is still to be executed after an event stop
+            _ => {}
+        }
+    }
+}
+
+/// Returns where the call executed by the last single step of `session` returns to, given the
+/// registers and instruction before the step, or `None` if it was not a call.
+fn call_return_address(
+    session: &PtraceSession,
+    arch: Arch,
+    before: &Registers,
+    instruction: &[u8],
+) -> anyhow::Result<Option<u64>> {
+    let after = session.registers()?;
+    match arch {
+        Arch::Aarch64 => {
+            let opcode = u32::from_le_bytes(instruction[..4].try_into()?);
+            // BL or BLR, setting the link register to the next instruction
+            let is_call =
+                opcode & 0xfc00_0000 == 0x9400_0000 || opcode & 0xffff_fc1f == 0xd63f_0000;
+            Ok(is_call.then_some(before.pc() + 4))
+        }
+        Arch::X86 | Arch::X86_64 => {
+            // A call pushes the address following it, an instruction being at most 15 bytes,
+            // and jumps elsewhere
+            let size = arch.bitness().pointer_size();
+            if after.sp() != before.sp().wrapping_sub(size as u64) {
+                return Ok(None);
+            }
+            let pushed = arch
+                .bitness()
+                .pointer_from_bytes(&session.memory().read_bytes(after.sp(), size)?)?;
+            let follows = pushed > before.pc() && pushed <= before.pc() + 15;
+            Ok((follows && after.pc() != pushed).then_some(pushed))
+        }
+    }
+}
+
+/// Resumes the thread of `session` in a call until it returns to `address` with its stack
+/// pointer back to `sp`, with a temporary breakpoint at `address`.
+fn run_to_return(session: &mut PtraceSession, address: u64, sp: u64) -> anyhow::Result<StepEvent> {
+    let arch = session.arch()?;
+    let memory = session.memory();
+    let original = memory.read_bytes(address, arch.breakpoint().len())?;
+    memory.write(address, arch.breakpoint())?;
+
+    let mut run = || loop {
+        session.cont(session.pending_signal())?;
+        match session.wait()? {
+            Stop::Signal(libc::SIGTRAP) => {
+                let mut registers = session.registers()?;
+                if registers.pc().wrapping_sub(arch.breakpoint_pc_offset()) != address {
+                    // Not the breakpoint, the signal is delivered when resuming
+                    continue;
+                }
+                session.suppress_signal();
+                registers.set_pc(address);
+                session.set_registers(&registers)?;
+                if registers.sp() >= sp {
+                    return Ok(StepEvent::Stepped {
+                        pc: address,
+                        steps: 1,
+                    });
+                }
+                // A nested call of the same function returned, the breakpoint is stepped over
+                memory.write(address, &original)?;
+                if let Some(event) = single_step(session)? {
+                    return Ok(event);
+                }
+                memory.write(address, arch.breakpoint())?;
+            }
+            stop if stop.is_exit() => return Ok(StepEvent::Exited(stop)),
+            Stop::Signal(signal) => {
+                return Ok(StepEvent::Signal {
+                    signal,
+                    pc: session.registers()?.pc(),
+                    steps: 1,
+                })
+            }
+            _ => {}
+        }
+    };
+    let result = run();
+    if !matches!(result, Ok(StepEvent::Exited(_))) {
+        memory.write(address, &original)?;
+    }
+
+    result
+}
+
+/// A thread of a process, with the scheduling data of `/proc/<pid>/task/<tid>/stat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// ID of the process of the thread, which is also the ID of its main thread
+    pid: Pid,
+    tid: Pid,
+    /// Name of the thread, e.g. set with `pthread_setname_np`
+    name: String,
+    state: ProcessState,
+    /// CPU number last executed on
+    processor: i16,
+    /// Time spent in user mode, measured in clock ticks
+    utime: u64,
+    /// Time spent in kernel mode, measured in clock ticks
+    stime: u64,
+}
+
+impl Thread {
+    /// Reads the thread `tid` of the process `pid`.
+    pub fn from_tid(pid: Pid, tid: Pid) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/task/{tid}/stat");
+        let stat = fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+
+        Self::from_stat(pid, &stat)
+    }
+
+    /// Parses the `stat` file of a thread of the process `pid`, in the format of
+    /// `/proc/<pid>/stat`.
+    pub fn from_stat(pid: Pid, stat: &str) -> anyhow::Result<Self> {
+        let process: Process = stat.parse()?;
+        Ok(Thread {
+            pid,
+            tid: process.pid(),
+            name: process.name().to_string(),
+            state: process.state(),
+            processor: process.processor(),
+            utime: process.utime(),
+            stime: process.stime(),
+        })
+    }
+
+    /// Reads the threads of the process `pid`, in increasing ID order. Threads exiting while
+    /// they are listed are left out.
+    pub fn list(pid: Pid) -> anyhow::Result<Vec<Self>> {
+        let mut threads = Vec::new();
+        for tid in tids(pid)? {
+            match Self::from_tid(pid, tid) {
+                Ok(thread) => threads.push(thread),
+                Err(e)
+                    if e.downcast_ref::<io::Error>()
+                        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(threads)
+    }
+
+    /// Reads the scheduling data of the thread again.
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        *self = Self::from_tid(self.pid, self.tid)?;
+
+        Ok(())
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn tid(&self) -> Pid {
+        self.tid
+    }
+
+    /// Whether this is the main thread of its process.
+    pub fn is_main(&self) -> bool {
+        self.pid == self.tid
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> ProcessState {
+        self.state
+    }
+
+    pub fn processor(&self) -> i16 {
+        self.processor
+    }
+
+    pub fn utime(&self) -> u64 {
+        self.utime
+    }
+
+    pub fn stime(&self) -> u64 {
+        self.stime
+    }
+
+    /// Attaches to the thread, stopping it until the session is dropped.
+    pub fn attach(&self) -> anyhow::Result<PtraceSession> {
+        PtraceSession::attach(self.tid)
+    }
+
+    /// Fails unless `session` traces this thread.
+    fn check_session(&self, session: &PtraceSession) -> anyhow::Result<()> {
+        if session.tid() != self.tid {
+            bail!(
+                "The session traces thread {}, not {}",
+                session.tid(),
+                self.tid
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reads the general purpose registers of the thread, stopped by `session`.
+    pub fn registers(&self, session: &PtraceSession) -> anyhow::Result<Registers> {
+        self.check_session(session)?;
+        session.registers()
+    }
+
+    /// Writes the general purpose registers of the thread, stopped by `session`.
+    pub fn set_registers(
+        &self,
+        session: &PtraceSession,
+        registers: &Registers,
+    ) -> anyhow::Result<()> {
+        self.check_session(session)?;
+        session.set_registers(registers)
+    }
+
+    /// Executes a single instruction of the thread, stopped by `session`, with
+    /// `PTRACE_SINGLESTEP`.
+    pub fn step(&self, session: &mut PtraceSession) -> anyhow::Result<StepEvent> {
+        self.check_session(session)?;
+        if let Some(event) = single_step(session)? {
+            return Ok(event);
+        }
+
+        Ok(StepEvent::Stepped {
+            pc: session.registers()?.pc(),
+            steps: 1,
+        })
+    }
+
+    /// Executes a single instruction of the thread like [`Self::step`], running a call until it
+    /// returns instead of stepping into it.
+    ///
+    /// The call runs until a temporary breakpoint at its return address: other threads of the
+    /// process executing it meanwhile would be killed by the `SIGTRAP`, so they should be
+    /// stopped, e.g. with [`crate::introspection::process::Process::freeze`].
+    pub fn step_over_call(&self, session: &mut PtraceSession) -> anyhow::Result<StepEvent> {
+        self.check_session(session)?;
+        let arch = session.arch()?;
+        let before = session.registers()?;
+        let instruction = match arch {
+            Arch::Aarch64 => session.memory().read_bytes(before.pc(), 4)?,
+            Arch::X86 | Arch::X86_64 => Vec::new(),
+        };
+        if let Some(event) = single_step(session)? {
+            return Ok(event);
+        }
+
+        match call_return_address(session, arch, &before, &instruction)? {
+            Some(address) => run_to_return(session, address, before.sp()),
+            None => Ok(StepEvent::Stepped {
+                pc: session.registers()?.pc(),
+                steps: 1,
+            }),
+        }
+    }
+
+    /// Steps the thread, stopped by `session`, one instruction at a time until `until` returns
+    /// true for its registers, e.g. once its program counter leaves a range of code.
+    pub fn step_until(
+        &self,
+        session: &mut PtraceSession,
+        mut until: impl FnMut(&Registers) -> bool,
+    ) -> anyhow::Result<StepEvent> {
+        self.check_session(session)?;
+        let mut steps = 0;
+        loop {
+            match single_step(session)? {
+                None => steps += 1,
+                Some(StepEvent::Signal { signal, pc, .. }) => {
+                    return Ok(StepEvent::Signal { signal, pc, steps })
+                }
+                Some(event) => return Ok(event),
+            }
+            let registers = session.registers()?;
+            if until(&registers) {
+                return Ok(StepEvent::Stepped {
+                    pc: registers.pc(),
+                    steps,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! This module contains the io_uring backend to read the memory of a process in bulk.
+//! Based on https://www.man7.org/linux/man-pages/man7/io_uring.7.html
+use std::{fs, io, os::fd::AsRawFd, sync::Mutex};
+
+use anyhow::{anyhow, bail, Context};
+use io_uring::{opcode, types, IoUring};
+
+use crate::introspection::{access::MemoryReader, process::Pid};
+
+/// Default number of reads in flight.
+const DEFAULT_DEPTH: u32 = 64;
+
+/// Maximum size of a single queued read.
+const CHUNK_SIZE: usize = 256 << 10;
+
+/// Interval between the checks of the completion of the reads in flight, when they cannot be
+/// waited for.
+const DRAIN_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Memory reads of `/proc/<pid>/mem` queued concurrently on an io_uring, to dump large regions
+/// faster than one `pread` or `process_vm_readv` at a time.
+///
+/// Requires the same permissions as [`crate::introspection::mem::ProcMem`] and Linux 5.6.
+pub struct UringMem {
+    pid: Pid,
+    file: fs::File,
+    depth: u32,
+    /// Ring of the reads, dropped when reads could not be submitted, see [`Self::drain`]
+    ring: Mutex<Option<IoUring>>,
+}
+
+impl std::fmt::Debug for UringMem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UringMem")
+            .field("pid", &self.pid)
+            .field("depth", &self.depth)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UringMem {
+    /// Opens the memory of the process `pid` for reading.
+    pub fn open(pid: Pid) -> anyhow::Result<Self> {
+        Self::with_depth(pid, DEFAULT_DEPTH)
+    }
+
+    /// Opens the memory of the process `pid`, with at most `depth` reads in flight.
+    pub fn with_depth(pid: Pid, depth: u32) -> anyhow::Result<Self> {
+        let path = format!("/proc/{pid}/mem");
+        let file = fs::File::open(&path).with_context(|| format!("Failed to open {path}"))?;
+        let depth = depth.max(1);
+        let ring = IoUring::new(depth).context("Failed to set up io_uring")?;
+
+        Ok(UringMem {
+            pid,
+            file,
+            depth,
+            ring: Mutex::new(Some(ring)),
+        })
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Reads several ranges concurrently, failing if any byte is unreadable.
+    ///
+    /// On failure, no read is submitted anymore and the ones in flight are waited for before
+    /// returning, as the kernel writes to the buffers until they complete.
+    pub fn read_vectored(&self, requests: &mut [(u64, &mut [u8])]) -> anyhow::Result<()> {
+        // Remote address, local buffer and length of each queued read
+        let mut pieces: Vec<(u64, *mut u8, usize)> = Vec::new();
+        for (address, buffer) in requests.iter_mut() {
+            let mut offset = 0;
+            while offset < buffer.len() {
+                let len = CHUNK_SIZE.min(buffer.len() - offset);
+                pieces.push((*address + offset as u64, buffer[offset..].as_mut_ptr(), len));
+                offset += len;
+            }
+        }
+
+        let mut slot = self
+            .ring
+            .lock()
+            .map_err(|_| anyhow!("io_uring of {} is poisoned", self.pid))?;
+        let ring = match &mut *slot {
+            Some(ring) => ring,
+            ring => ring.insert(IoUring::new(self.depth).context("Failed to set up io_uring")?),
+        };
+
+        let mut in_flight = 0;
+        let result = self.submit_reads(ring, &mut pieces, &mut in_flight);
+        if result.is_err() && !Self::drain(ring, in_flight) {
+            // The reads left in the submission queue must never reach the kernel
+            *slot = None;
+        }
+
+        result
+    }
+
+    /// Queues the reads of `pieces` on `ring` until all of them completed, counting the ones
+    /// queued and not completed yet in `in_flight`.
+    fn submit_reads(
+        &self,
+        ring: &mut IoUring,
+        pieces: &mut [(u64, *mut u8, usize)],
+        in_flight: &mut usize,
+    ) -> anyhow::Result<()> {
+        let fd = types::Fd(self.file.as_raw_fd());
+        let mut pending: Vec<usize> = (0..pieces.len()).rev().collect();
+
+        while !pending.is_empty() || *in_flight > 0 {
+            while *in_flight < self.depth as usize {
+                let Some(piece) = pending.pop() else {
+                    break;
+                };
+                let (address, buffer, len) = pieces[piece];
+                let entry = opcode::Read::new(fd, buffer, len as u32)
+                    .offset(address)
+                    .build()
+                    .user_data(piece as u64);
+                // SAFETY: the buffer is borrowed until all reads completed, see `drain`
+                unsafe { ring.submission().push(&entry) }
+                    .map_err(|_| anyhow!("io_uring submission queue is full"))?;
+                *in_flight += 1;
+            }
+
+            ring.submit_and_wait(1)
+                .context("Failed to submit reads to io_uring")?;
+
+            let completions: Vec<(usize, i32)> = ring
+                .completion()
+                .map(|entry| (entry.user_data() as usize, entry.result()))
+                .collect();
+            *in_flight -= completions.len();
+            for (piece, result) in completions {
+                let (address, buffer, len) = pieces[piece];
+                if result < 0 {
+                    return Err(io::Error::from_raw_os_error(-result)).with_context(|| {
+                        format!("Failed to read {len} bytes at {address:#x} in {}", self.pid)
+                    });
+                }
+
+                let read = result as usize;
+                if read == 0 {
+                    bail!("Failed to read {len} bytes at {address:#x} in {}", self.pid);
+                }
+                if read < len {
+                    // SAFETY: `read` is less than the length of the buffer
+                    pieces[piece] = (
+                        address + read as u64,
+                        unsafe { buffer.add(read) },
+                        len - read,
+                    );
+                    pending.push(piece);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the `in_flight` reads queued on `ring` to complete, discarding their results,
+    /// so that their buffers can be released.
+    ///
+    /// If they cannot be submitted, only the ones the kernel took are waited for, and false is
+    /// returned: the others are still in the submission queue, and the ring must be dropped.
+    fn drain(ring: &mut IoUring, mut in_flight: usize) -> bool {
+        while in_flight > 0 {
+            let submitted = match ring.submit_and_wait(1) {
+                Ok(_) => true,
+                Err(error) => matches!(
+                    error.raw_os_error(),
+                    Some(libc::EINTR | libc::EAGAIN | libc::EBUSY)
+                ),
+            };
+            in_flight -= ring.completion().count();
+            if !submitted {
+                if in_flight == ring.submission().len() {
+                    return false;
+                }
+                // The completions are posted without entering the kernel
+                std::thread::sleep(DRAIN_INTERVAL);
+            }
+        }
+
+        true
+    }
+}
+
+impl MemoryReader for UringMem {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        self.read_vectored(&mut [(address, buffer)])
+    }
+
+    fn copy_to(&self, address: u64, len: u64, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        // Keeps the ring full: each window is read by `depth` concurrent chunks
+        let window = CHUNK_SIZE as u64 * self.depth as u64;
+        let mut buffer = vec![0; window.min(len) as usize];
+        let end = address + len;
+        let mut address = address;
+
+        while address < end {
+            let size = buffer.len().min((end - address) as usize);
+            self.read(address, &mut buffer[..size])?;
+            writer
+                .write_all(&buffer[..size])
+                .context("Failed to write memory copy")?;
+            address += size as u64;
+        }
+
+        Ok(())
+    }
+}
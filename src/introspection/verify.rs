@@ -0,0 +1,88 @@
+//! This module contains a layer over the memory backends checking that writes took effect.
+use std::fmt;
+
+use crate::introspection::access::{MemoryReader, MemoryWriter};
+
+/// Error of a [`VerifiedWriter`] write whose bytes read back differ from those written. It can be
+/// recovered from the returned error with [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteVerificationError {
+    pub address: u64,
+    pub expected: Vec<u8>,
+    pub observed: Vec<u8>,
+}
+
+impl WriteVerificationError {
+    /// Offset of the first byte that differs.
+    pub fn first_mismatch(&self) -> Option<usize> {
+        self.expected
+            .iter()
+            .zip(&self.observed)
+            .position(|(expected, observed)| expected != observed)
+    }
+}
+
+impl fmt::Display for WriteVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self.first_mismatch().unwrap_or(0);
+        write!(
+            f,
+            "Write of {} bytes at {:#x} did not take effect: byte at {:#x} is {:#04x}, expected \
+             {:#04x}",
+            self.expected.len(),
+            self.address,
+            self.address + offset as u64,
+            self.observed.get(offset).copied().unwrap_or_default(),
+            self.expected.get(offset).copied().unwrap_or_default(),
+        )
+    }
+}
+
+impl std::error::Error for WriteVerificationError {}
+
+/// Reads back every write and compares it with the bytes written, to catch writes that silently
+/// do not stick, e.g. to a copy-on-write page of another mapping or memory the target rewrites.
+///
+/// Reads are forwarded unchanged.
+#[derive(Debug)]
+pub struct VerifiedWriter<M> {
+    inner: M,
+}
+
+impl<M> VerifiedWriter<M> {
+    pub fn new(inner: M) -> Self {
+        VerifiedWriter { inner }
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> M {
+        self.inner
+    }
+}
+
+impl<M: MemoryReader> MemoryReader for VerifiedWriter<M> {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        self.inner.read(address, buffer)
+    }
+}
+
+impl<M: MemoryReader + MemoryWriter> MemoryWriter for VerifiedWriter<M> {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.inner.write(address, data)?;
+
+        let observed = self.inner.read_bytes(address, data.len())?;
+        if observed != data {
+            return Err(WriteVerificationError {
+                address,
+                expected: data.to_vec(),
+                observed,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! This module contains the default backend to access the memory of a process.
+//! Based on https://www.man7.org/linux/man-pages/man2/process_vm_readv.2.html
+use std::{io, mem::MaybeUninit};
+
+use anyhow::{anyhow, Context};
+
+use crate::introspection::{
+    access::{assume_init_mut, MemoryReader, MemoryWriter},
+    audit::audit_write,
+    process::Pid,
+};
+
+/// Maximum number of iovecs accepted by a single call, `UIO_MAXIOV`.
+const IOV_MAX: usize = libc::UIO_MAXIOV as usize;
+
+/// A contiguous piece of a transfer: remote address, local buffer and length.
+type Piece = (u64, *mut libc::c_void, usize);
+
+/// Merges the overlapping and adjacent `(address, len)` ranges of `requests`, returning the
+/// merged ranges sorted by address, as read by [`ProcessVm::read_many`].
+pub fn coalesce(requests: &[(u64, usize)]) -> Vec<(u64, usize)> {
+    merge(requests)
+        .into_iter()
+        .map(|(range, _)| range)
+        .collect()
+}
+
+/// Merged ranges of `requests`, along with the indices of the requests in each of them.
+fn merge(requests: &[(u64, usize)]) -> Vec<((u64, usize), Vec<usize>)> {
+    let mut order: Vec<usize> = (0..requests.len()).collect();
+    order.sort_by_key(|&index| requests[index].0);
+
+    let mut spans: Vec<(u64, u64, Vec<usize>)> = Vec::new();
+    for index in order {
+        let (address, len) = requests[index];
+        let end = address.saturating_add(len as u64);
+        match spans.last_mut() {
+            Some((_, span_end, members)) if address <= *span_end => {
+                *span_end = (*span_end).max(end);
+                members.push(index);
+            }
+            _ => spans.push((address, end, vec![index])),
+        }
+    }
+
+    spans
+        .into_iter()
+        .map(|(start, end, members)| ((start, (end - start) as usize), members))
+        .collect()
+}
+
+/// Memory access with `process_vm_readv` and `process_vm_writev`, the fastest and least
+/// intrusive backend: the target is not stopped and nothing is opened.
+///
+/// Writes honor the protection of the target pages, so read-only mappings (e.g. code) cannot be
+/// patched with this backend. Requires ptrace access mode `PTRACE_MODE_ATTACH_REALCREDS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessVm {
+    pid: Pid,
+}
+
+impl ProcessVm {
+    pub fn new(pid: Pid) -> Self {
+        ProcessVm { pid }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// Reads several ranges in as few calls as possible, failing if any byte is unreadable.
+    pub fn read_vectored(&self, requests: &mut [(u64, &mut [u8])]) -> anyhow::Result<()> {
+        let pieces = requests
+            .iter_mut()
+            .map(|(address, buffer)| (*address, buffer.as_mut_ptr().cast(), buffer.len()))
+            .collect();
+
+        self.transfer(pieces, false)
+    }
+
+    /// Writes several ranges in as few calls as possible, failing if any byte is not written.
+    pub fn write_vectored(&self, requests: &[(u64, &[u8])]) -> anyhow::Result<()> {
+        // The local buffers are only read by process_vm_writev
+        let pieces = requests
+            .iter()
+            .filter(|(address, data)| {
+                audit_write(self.pid, *address, data, "process_vm_writev", self)
+            })
+            .map(|(address, data)| (*address, data.as_ptr().cast_mut().cast(), data.len()))
+            .collect();
+
+        self.transfer(pieces, true)
+    }
+
+    /// Reads each `(address, len)` range independently, with as few calls as possible: the
+    /// overlapping and adjacent ranges are merged, see [`coalesce`], and up to `IOV_MAX` merged
+    /// ranges are read by each `process_vm_readv`.
+    ///
+    /// A range that is not entirely readable fails on its own, without affecting the others: the
+    /// ranges merged with it are read again separately.
+    pub fn read_many(&self, requests: &[(u64, usize)]) -> Vec<anyhow::Result<Vec<u8>>> {
+        let spans = merge(requests);
+        let ranges: Vec<(u64, usize)> = spans.iter().map(|(range, _)| *range).collect();
+        let mut results: Vec<Option<anyhow::Result<Vec<u8>>>> =
+            requests.iter().map(|_| None).collect();
+        let mut retried = Vec::new();
+
+        for ((range, members), result) in spans.iter().zip(self.read_ranges(&ranges)) {
+            match (result, &members[..]) {
+                (result, [index]) => results[*index] = Some(result),
+                (Ok(buffer), members) => {
+                    for &index in members {
+                        let (address, len) = requests[index];
+                        let offset = (address - range.0) as usize;
+                        results[index] = Some(Ok(buffer[offset..offset + len].to_vec()));
+                    }
+                }
+                (Err(_), members) => retried.extend_from_slice(members),
+            }
+        }
+
+        let ranges: Vec<(u64, usize)> = retried.iter().map(|&index| requests[index]).collect();
+        for (index, result) in retried.into_iter().zip(self.read_ranges(&ranges)) {
+            results[index] = Some(result);
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
+    /// Reads each range of `requests` independently, up to `IOV_MAX` of them by call.
+    fn read_ranges(&self, requests: &[(u64, usize)]) -> Vec<anyhow::Result<Vec<u8>>> {
+        let mut buffers: Vec<Vec<u8>> = requests.iter().map(|&(_, len)| vec![0; len]).collect();
+        let mut failures: Vec<Option<anyhow::Error>> = requests.iter().map(|_| None).collect();
+        let mut index = 0;
+
+        while index < requests.len() {
+            let end = (index + IOV_MAX).min(requests.len());
+            let (local, remote): (Vec<libc::iovec>, Vec<libc::iovec>) = buffers[index..end]
+                .iter_mut()
+                .zip(&requests[index..end])
+                .map(|(buffer, &(address, len))| {
+                    (
+                        libc::iovec {
+                            iov_base: buffer.as_mut_ptr().cast(),
+                            iov_len: len,
+                        },
+                        libc::iovec {
+                            iov_base: address as *mut libc::c_void,
+                            iov_len: len,
+                        },
+                    )
+                })
+                .unzip();
+
+            // SAFETY: the local iovecs point to the buffers, which outlive the call
+            let transferred = unsafe {
+                libc::process_vm_readv(
+                    self.pid as libc::pid_t,
+                    local.as_ptr(),
+                    local.len() as _,
+                    remote.as_ptr(),
+                    remote.len() as _,
+                    0,
+                )
+            };
+
+            if transferred < 0 {
+                let error = io::Error::last_os_error();
+                if error.raw_os_error() != Some(libc::EFAULT) {
+                    // The process itself cannot be read, every remaining range fails
+                    for (failure, (address, _)) in
+                        failures[index..].iter_mut().zip(&requests[index..])
+                    {
+                        *failure = Some(anyhow!(
+                            "Failed to read {address:#x} in {}: {error}",
+                            self.pid
+                        ));
+                    }
+                    break;
+                }
+            }
+
+            // The call stops at the first range it cannot read entirely
+            let mut transferred = transferred.max(0) as usize;
+            while index < end && transferred >= requests[index].1 {
+                transferred -= requests[index].1;
+                index += 1;
+            }
+            if index < end {
+                let address = requests[index].0;
+                failures[index] = Some(anyhow!("Failed to read {address:#x} in {}", self.pid));
+                index += 1;
+            }
+        }
+
+        buffers
+            .into_iter()
+            .zip(failures)
+            .map(|(buffer, failure)| match failure {
+                Some(error) => Err(error),
+                None => Ok(buffer),
+            })
+            .collect()
+    }
+
+    /// Transfers `pieces` by batches of at most `IOV_MAX` iovecs, resuming after partial
+    /// transfers. Those happen when a remote range crosses into an unmapped or protected page.
+    fn transfer(&self, pieces: Vec<Piece>, write: bool) -> anyhow::Result<()> {
+        let pieces: Vec<Piece> = pieces.into_iter().filter(|piece| piece.2 > 0).collect();
+        let (mut index, mut offset) = (0, 0);
+
+        while index < pieces.len() {
+            let (local, remote): (Vec<libc::iovec>, Vec<libc::iovec>) = pieces[index..]
+                .iter()
+                .take(IOV_MAX)
+                .enumerate()
+                .map(|(i, &(address, buffer, len))| {
+                    let skip = if i == 0 { offset } else { 0 };
+                    (
+                        libc::iovec {
+                            // SAFETY: `skip` is less than the length of the buffer
+                            iov_base: unsafe { buffer.byte_add(skip) },
+                            iov_len: len - skip,
+                        },
+                        libc::iovec {
+                            iov_base: (address + skip as u64) as *mut libc::c_void,
+                            iov_len: len - skip,
+                        },
+                    )
+                })
+                .unzip();
+
+            // SAFETY: the local iovecs point to buffers borrowed for the whole transfer
+            let transferred = unsafe {
+                if write {
+                    libc::process_vm_writev(
+                        self.pid as libc::pid_t,
+                        local.as_ptr(),
+                        local.len() as _,
+                        remote.as_ptr(),
+                        remote.len() as _,
+                        0,
+                    )
+                } else {
+                    libc::process_vm_readv(
+                        self.pid as libc::pid_t,
+                        local.as_ptr(),
+                        local.len() as _,
+                        remote.as_ptr(),
+                        remote.len() as _,
+                        0,
+                    )
+                }
+            };
+
+            let address = pieces[index].0 + offset as u64;
+            let action = if write { "write" } else { "read" };
+            if transferred < 0 {
+                return Err(io::Error::last_os_error())
+                    .with_context(|| format!("Failed to {action} {address:#x} in {}", self.pid));
+            }
+            if transferred == 0 {
+                // Nothing was transferred past a partial one: the next page faulted
+                return Err(io::Error::from_raw_os_error(libc::EFAULT))
+                    .with_context(|| format!("Failed to {action} {address:#x} in {}", self.pid));
+            }
+
+            let mut transferred = transferred as usize;
+            while transferred > 0 {
+                let remaining = pieces[index].2 - offset;
+                if transferred >= remaining {
+                    transferred -= remaining;
+                    index += 1;
+                    offset = 0;
+                } else {
+                    offset += transferred;
+                    transferred = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryReader for ProcessVm {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+        self.read_vectored(&mut [(address, buffer)])
+    }
+
+    fn read_uninit<'b>(
+        &self,
+        address: u64,
+        buffer: &'b mut [MaybeUninit<u8>],
+    ) -> anyhow::Result<&'b mut [u8]> {
+        // The kernel only writes to the buffer, it does not need to be initialized
+        self.transfer(
+            vec![(address, buffer.as_mut_ptr().cast(), buffer.len())],
+            false,
+        )?;
+        // SAFETY: every byte was written by the transfer
+        Ok(unsafe { assume_init_mut(buffer) })
+    }
+}
+
+impl MemoryWriter for ProcessVm {
+    fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.write_vectored(&[(address, data)])
+    }
+}
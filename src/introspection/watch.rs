@@ -0,0 +1,319 @@
+//! This module contains the structs and functions to watch the memory mappings of a process
+//! change over time.
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::ControlFlow,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::introspection::{
+    memory::MemorySummary,
+    process::Pid,
+    segment::{Segment, SegmentPermissions, Segments},
+};
+
+/// Default interval between two reads of `/proc/<pid>/maps`.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A change of the memory mappings of a process, see [`MapsWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapsEvent {
+    /// A mapping appeared, including the parts of a mapping split by `mprotect`.
+    SegmentAdded(Segment),
+    /// A mapping disappeared, or was split or merged.
+    SegmentRemoved(Segment),
+    /// A mapping kept its address range but changed permissions, from `old` to those of
+    /// `segment`.
+    SegmentPermissionsChanged {
+        old: SegmentPermissions,
+        segment: Segment,
+    },
+}
+
+/// Computes the events turning the mappings `old` into `new`.
+///
+/// Mappings are matched by address range, so a mapping that grows or is split shows up as
+/// removed and added again.
+pub fn diff(old: &[Segment], new: &[Segment]) -> Vec<MapsEvent> {
+    let old_ranges: HashMap<(u64, u64), &Segment> = old
+        .iter()
+        .map(|segment| ((segment.start(), segment.end()), segment))
+        .collect();
+    let new_ranges: HashMap<(u64, u64), &Segment> = new
+        .iter()
+        .map(|segment| ((segment.start(), segment.end()), segment))
+        .collect();
+
+    let removed = old
+        .iter()
+        .filter(|segment| !new_ranges.contains_key(&(segment.start(), segment.end())))
+        .map(|segment| MapsEvent::SegmentRemoved(segment.clone()));
+
+    let changed =
+        new.iter().filter_map(
+            |segment| match old_ranges.get(&(segment.start(), segment.end())) {
+                None => Some(MapsEvent::SegmentAdded(segment.clone())),
+                Some(old) if old.permissions() != segment.permissions() => {
+                    Some(MapsEvent::SegmentPermissionsChanged {
+                        old: old.permissions(),
+                        segment: segment.clone(),
+                    })
+                }
+                Some(_) => None,
+            },
+        );
+
+    removed.chain(changed).collect()
+}
+
+/// Polls the memory mappings of a process and reports their changes.
+#[derive(Debug)]
+pub struct MapsWatcher {
+    pid: Pid,
+    interval: Duration,
+    segments: Segments,
+}
+
+impl MapsWatcher {
+    /// Starts watching the process `pid`, from its current mappings.
+    pub fn new(pid: Pid) -> anyhow::Result<Self> {
+        Ok(MapsWatcher {
+            pid,
+            interval: DEFAULT_INTERVAL,
+            segments: Segments::from_pid(pid)?,
+        })
+    }
+
+    /// Sets the interval between two polls of [`Self::watch`] and [`Self::spawn`].
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Mappings as of the last poll.
+    pub fn segments(&self) -> &Segments {
+        &self.segments
+    }
+
+    /// Reads the mappings once and returns the changes since the last poll.
+    pub fn poll(&mut self) -> anyhow::Result<Vec<MapsEvent>> {
+        let segments = Segments::from_pid(self.pid)?;
+        let events = diff(self.segments.as_slice(), segments.as_slice());
+        self.segments = segments;
+
+        Ok(events)
+    }
+
+    /// Polls the mappings every interval and calls `callback` for each change, until it returns
+    /// [`ControlFlow::Break`] or the mappings can no longer be read (e.g. the process exited).
+    pub fn watch(
+        &mut self,
+        mut callback: impl FnMut(MapsEvent) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            for event in self.poll()? {
+                if callback(event).is_break() {
+                    return Ok(());
+                }
+            }
+            thread::sleep(self.interval);
+        }
+    }
+
+    /// Watches the mappings from a background thread, sending the changes through a channel.
+    ///
+    /// The thread stops at the first change after the receiver is dropped, or returns the error
+    /// that stopped it.
+    pub fn spawn(
+        mut self,
+    ) -> (
+        mpsc::Receiver<MapsEvent>,
+        thread::JoinHandle<anyhow::Result<()>>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            self.watch(|event| match sender.send(event) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            })
+        });
+
+        (receiver, handle)
+    }
+}
+
+/// Default number of samples kept by a [`GrowthTracker`].
+const DEFAULT_CAPACITY: usize = 64;
+
+/// A memory summary of a process taken by a [`GrowthTracker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrowthSample {
+    pub time: Instant,
+    pub summary: MemorySummary,
+}
+
+/// Growth of the memory footprint (resident and swapped) of the mappings sharing a name, between
+/// the oldest and newest samples of a [`GrowthTracker`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappingGrowth {
+    /// Backing file or pseudo-path, as in [`crate::introspection::memory::MappingUsage`]
+    pub name: String,
+    /// Footprint in the oldest sample, in bytes
+    pub first: u64,
+    /// Footprint in the newest sample, in bytes
+    pub last: u64,
+    /// Average growth, in bytes per second
+    pub rate: f64,
+    /// The footprint never decreased between two samples
+    pub monotonic: bool,
+}
+
+impl MappingGrowth {
+    /// Growth between the oldest and newest samples, in bytes.
+    pub fn growth(&self) -> u64 {
+        self.last - self.first
+    }
+}
+
+/// Samples the memory summary of a process to find the mappings growing over time, e.g. to
+/// hunt leaks. Only the most recent samples are kept.
+#[derive(Debug)]
+pub struct GrowthTracker {
+    pid: Pid,
+    interval: Duration,
+    capacity: usize,
+    samples: VecDeque<GrowthSample>,
+}
+
+impl GrowthTracker {
+    pub fn new(pid: Pid) -> Self {
+        GrowthTracker {
+            pid,
+            interval: DEFAULT_INTERVAL,
+            capacity: DEFAULT_CAPACITY,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Sets the interval between two samples of [`Self::run`].
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the number of samples kept, at least 2.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(2);
+        while self.samples.len() > self.capacity {
+            self.samples.pop_front();
+        }
+        self
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Samples kept, oldest first.
+    pub fn samples(&self) -> &VecDeque<GrowthSample> {
+        &self.samples
+    }
+
+    /// Records `summary` as taken now, dropping the oldest sample if full.
+    pub fn push(&mut self, summary: MemorySummary) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(GrowthSample {
+            time: Instant::now(),
+            summary,
+        });
+    }
+
+    /// Takes a sample of the memory summary of the process.
+    pub fn sample(&mut self) -> anyhow::Result<()> {
+        self.push(MemorySummary::from_pid(self.pid)?);
+        Ok(())
+    }
+
+    /// Takes `count` samples, waiting the interval between each.
+    pub fn run(&mut self, count: usize) -> anyhow::Result<()> {
+        for i in 0..count {
+            if i > 0 {
+                thread::sleep(self.interval);
+            }
+            self.sample()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the mappings whose footprint grew between the oldest and newest samples, fastest
+    /// growing first.
+    pub fn growing(&self) -> Vec<MappingGrowth> {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return Vec::new();
+        };
+        let seconds = last.time.duration_since(first.time).as_secs_f64();
+
+        let footprint = |sample: &GrowthSample, name: &str| {
+            sample
+                .summary
+                .mapping(name)
+                .map_or(0, |mapping| mapping.rss + mapping.swap)
+        };
+
+        let mut growing: Vec<MappingGrowth> = last
+            .summary
+            .mappings
+            .iter()
+            .filter_map(|mapping| {
+                let first_footprint = footprint(first, &mapping.name);
+                let last_footprint = mapping.rss + mapping.swap;
+                if last_footprint <= first_footprint {
+                    return None;
+                }
+
+                let footprints: Vec<u64> = self
+                    .samples
+                    .iter()
+                    .map(|sample| footprint(sample, &mapping.name))
+                    .collect();
+                let growth = (last_footprint - first_footprint) as f64;
+
+                Some(MappingGrowth {
+                    name: mapping.name.clone(),
+                    first: first_footprint,
+                    last: last_footprint,
+                    rate: if seconds > 0.0 { growth / seconds } else { 0.0 },
+                    monotonic: footprints.windows(2).all(|pair| pair[0] <= pair[1]),
+                })
+            })
+            .collect();
+
+        growing.sort_by(|a, b| {
+            b.growth()
+                .cmp(&a.growth())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        growing
+    }
+}
@@ -0,0 +1,463 @@
+//! This module contains the hardware breakpoints and watchpoints, trapping a traced thread when
+//! it executes or accesses an address, with the debug registers of the processor.
+use anyhow::{anyhow, bail};
+
+use crate::introspection::{
+    arch::Registers,
+    process::Pid,
+    ptrace::{PtraceSession, Stop},
+};
+
+/// Access trapped by a [`Watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    /// Execution of the instruction at the address, a hardware breakpoint
+    Execute,
+    Write,
+    /// Reads and writes, x86 cannot trap reads alone
+    ReadWrite,
+}
+
+/// A debug register of the thread of a [`WatchpointManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// Index of the watchpoint, reported when it fires
+    pub slot: usize,
+    pub address: u64,
+    pub len: usize,
+    pub kind: WatchKind,
+    /// Number of times the watchpoint fired
+    pub hits: u64,
+}
+
+/// A thread stopped by a watchpoint.
+///
+/// For [`WatchKind::Write`] and [`WatchKind::ReadWrite`], the thread stops after the accessing
+/// instruction on x86, and before it on aarch64: the instruction is executed when resuming.
+///
+/// For [`WatchKind::Execute`], the thread stops before the instruction on both, with the
+/// instruction pointer at the watched address. The instruction is executed when resuming, without
+/// firing again: x86 faults before running it and the kernel sets the resume flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub slot: usize,
+    /// Start of the watched range
+    pub address: u64,
+    pub tid: Pid,
+    /// Number of hits of the watchpoint, this one included
+    pub hits: u64,
+    pub registers: Registers,
+}
+
+/// What stopped the thread of a [`WatchpointManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchpointEvent {
+    Hit(Box<WatchpointHit>),
+    /// Any other stop, or the end of the thread
+    Stop(Stop),
+}
+
+/// The x86 debug registers, DR0 to DR3 holding the addresses, DR6 the status and DR7 the
+/// control bits, accessed in the `struct user` of the thread.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod debug_registers {
+    use anyhow::bail;
+
+    use super::{WatchKind, Watchpoint};
+    use crate::introspection::ptrace::PtraceSession;
+
+    /// Length of the execute breakpoints, whatever the instruction length.
+    pub const EXECUTE_LEN: usize = 1;
+
+    /// The processor reports accesses once they are done, so they are not trapped again.
+    pub const TRAPS_BEFORE_ACCESS: bool = false;
+
+    /// Offset of `u_debugreg` in the `struct user` of the inspector.
+    #[cfg(target_arch = "x86_64")]
+    const DEBUGREG_OFFSET: u64 = 848;
+    #[cfg(target_arch = "x86")]
+    const DEBUGREG_OFFSET: u64 = 252;
+
+    fn offset(register: u64) -> u64 {
+        DEBUGREG_OFFSET + register * std::mem::size_of::<libc::c_long>() as u64
+    }
+
+    pub fn slot_count(_session: &PtraceSession) -> anyhow::Result<usize> {
+        Ok(4)
+    }
+
+    /// Writes the addresses and enables the watchpoints of `slots`, disabling the others.
+    pub fn write(session: &PtraceSession, slots: &[Option<Watchpoint>]) -> anyhow::Result<()> {
+        let mut dr7 = 0;
+        for watchpoint in slots.iter().flatten() {
+            let rw = match watchpoint.kind {
+                WatchKind::Execute => 0b00,
+                WatchKind::Write => 0b01,
+                WatchKind::ReadWrite => 0b11,
+            };
+            let len = match watchpoint.len {
+                1 => 0b00,
+                2 => 0b01,
+                4 => 0b11,
+                8 if cfg!(target_arch = "x86_64") => 0b10,
+                len => bail!("Cannot watch {len} bytes"),
+            };
+            let slot = watchpoint.slot as u64;
+            // Local enable bit, then the access and length bits
+            dr7 |= 1 << (slot * 2);
+            dr7 |= (rw | len << 2) << (16 + slot * 4);
+        }
+
+        // The addresses are only checked once enabled, so they are written in between
+        session.poke_user(offset(7), 0)?;
+        for watchpoint in slots.iter().flatten() {
+            session.poke_user(offset(watchpoint.slot as u64), watchpoint.address)?;
+        }
+        session.poke_user(offset(7), dr7)
+    }
+
+    /// Slot of the watchpoint which fired according to DR6, which is cleared.
+    pub fn hit(
+        session: &PtraceSession,
+        slots: &[Option<Watchpoint>],
+    ) -> anyhow::Result<Option<usize>> {
+        let dr6 = session.peek_user(offset(6))?;
+        if dr6 & 0xf == 0 {
+            return Ok(None);
+        }
+        session.poke_user(offset(6), 0)?;
+
+        Ok(slots
+            .iter()
+            .flatten()
+            .find(|watchpoint| dr6 & (1 << watchpoint.slot) != 0)
+            .map(|watchpoint| watchpoint.slot))
+    }
+}
+
+/// The aarch64 debug registers, breakpoints and watchpoints being separate register sets.
+#[cfg(target_arch = "aarch64")]
+mod debug_registers {
+    use anyhow::{bail, Context};
+
+    use super::{WatchKind, Watchpoint};
+    use crate::introspection::{
+        fpu::{get_regset, set_regset},
+        ptrace::PtraceSession,
+    };
+
+    const NT_ARM_HW_BREAK: libc::c_int = 0x402;
+    const NT_ARM_HW_WATCH: libc::c_int = 0x403;
+
+    /// `si_code` of the `SIGTRAP` of a debug register.
+    const TRAP_HWBKPT: libc::c_int = 4;
+
+    /// Length of the execute breakpoints, an A64 instruction.
+    pub const EXECUTE_LEN: usize = 4;
+
+    /// The processor reports accesses before they are done, so the debugger must step over them.
+    pub const TRAPS_BEFORE_ACCESS: bool = true;
+
+    /// `struct user_hwdebug_state`: the number of registers in the low byte of the information
+    /// word, then the address and control of each register.
+    const HEADER_SIZE: usize = 8;
+    const REGISTER_SIZE: usize = 16;
+    const MAX_REGISTERS: usize = 16;
+
+    fn register_count(session: &PtraceSession, note: libc::c_int) -> anyhow::Result<usize> {
+        let mut state = [0; HEADER_SIZE + MAX_REGISTERS * REGISTER_SIZE];
+        get_regset(session.tid(), note, &mut state)
+            .with_context(|| format!("Failed to read the debug registers of {}", session.tid()))?;
+
+        Ok((state[0] as usize).min(MAX_REGISTERS))
+    }
+
+    pub fn slot_count(session: &PtraceSession) -> anyhow::Result<usize> {
+        Ok(register_count(session, NT_ARM_HW_BREAK)? + register_count(session, NT_ARM_HW_WATCH)?)
+    }
+
+    /// Writes the registers `note` with `watchpoints`, disabling the remaining ones.
+    fn write_set<'a>(
+        session: &PtraceSession,
+        note: libc::c_int,
+        watchpoints: impl Iterator<Item = &'a Watchpoint>,
+    ) -> anyhow::Result<()> {
+        let count = register_count(session, note)?;
+        let mut state = vec![0; HEADER_SIZE + count * REGISTER_SIZE];
+        for (index, watchpoint) in watchpoints.enumerate() {
+            if index == count {
+                bail!("The {count} debug registers of this kind are in use");
+            }
+            // Enabled for user space, with the watched bytes of the aligned doubleword
+            let offset = watchpoint.address % 8;
+            let mut control = 1 | 0b10 << 1;
+            control |= match watchpoint.kind {
+                WatchKind::Execute => 0xf << 5,
+                WatchKind::Write => (0b10 << 3) | (((1 << watchpoint.len) - 1) << (5 + offset)),
+                WatchKind::ReadWrite => (0b11 << 3) | (((1 << watchpoint.len) - 1) << (5 + offset)),
+            };
+            let register = &mut state[HEADER_SIZE + index * REGISTER_SIZE..];
+            register[..8].copy_from_slice(&(watchpoint.address - offset).to_ne_bytes());
+            register[8..12].copy_from_slice(&(control as u32).to_ne_bytes());
+        }
+
+        set_regset(session.tid(), note, &state)
+            .with_context(|| format!("Failed to write the debug registers of {}", session.tid()))
+    }
+
+    pub fn write(session: &PtraceSession, slots: &[Option<Watchpoint>]) -> anyhow::Result<()> {
+        let is_execute = |watchpoint: &&Watchpoint| watchpoint.kind == WatchKind::Execute;
+        write_set(
+            session,
+            NT_ARM_HW_BREAK,
+            slots.iter().flatten().filter(is_execute),
+        )?;
+        write_set(
+            session,
+            NT_ARM_HW_WATCH,
+            slots
+                .iter()
+                .flatten()
+                .filter(|watchpoint| !is_execute(watchpoint)),
+        )
+    }
+
+    /// Slot of the watchpoint containing the address reported by the `SIGTRAP`.
+    pub fn hit(
+        session: &PtraceSession,
+        slots: &[Option<Watchpoint>],
+    ) -> anyhow::Result<Option<usize>> {
+        let siginfo = session.siginfo()?;
+        if siginfo.si_code != TRAP_HWBKPT {
+            return Ok(None);
+        }
+        // SAFETY: the address is set for the SIGTRAP of a debug register
+        let address = unsafe { siginfo.si_addr() } as u64;
+
+        Ok(slots
+            .iter()
+            .flatten()
+            .find(|watchpoint| {
+                (watchpoint.address..watchpoint.address + watchpoint.len as u64).contains(&address)
+            })
+            .map(|watchpoint| watchpoint.slot))
+    }
+}
+
+/// Hardware breakpoints and watchpoints of a traced thread, set in the debug registers of the
+/// processor: DR0 to DR3 and DR7 on x86, or the breakpoint and watchpoint registers on aarch64.
+///
+/// Unlike software breakpoints the code is not modified, and memory accesses can be trapped,
+/// which tells what writes to an address. Debug registers are per thread: other threads of the
+/// process are not affected. Every watchpoint is removed when the manager is dropped.
+#[derive(Debug)]
+pub struct WatchpointManager {
+    session: PtraceSession,
+    /// Watchpoints by slot, as many as the thread has debug registers
+    slots: Vec<Option<Watchpoint>>,
+    /// Signal received while stepping over a watchpoint, delivered when resuming
+    pending_signal: libc::c_int,
+    /// Slot of the last hit, stepped over when resuming if the access is still to be done
+    last_hit: Option<usize>,
+}
+
+impl WatchpointManager {
+    /// Manages the debug registers of the stopped thread traced by `session`.
+    pub fn new(session: PtraceSession) -> anyhow::Result<Self> {
+        let slots = vec![None; debug_registers::slot_count(&session)?];
+        Ok(WatchpointManager {
+            session,
+            slots,
+            pending_signal: 0,
+            last_hit: None,
+        })
+    }
+
+    pub fn session(&self) -> &PtraceSession {
+        &self.session
+    }
+
+    /// The session of the thread, e.g. to read its memory while it is stopped.
+    pub fn session_mut(&mut self) -> &mut PtraceSession {
+        &mut self.session
+    }
+
+    /// Removes every watchpoint and returns the session.
+    pub fn into_session(mut self) -> anyhow::Result<PtraceSession> {
+        self.remove_all()?;
+        let mut manager = std::mem::ManuallyDrop::new(self);
+        // SAFETY: the fields are read or dropped once, and the manager is not dropped
+        unsafe {
+            std::ptr::drop_in_place(&mut manager.slots);
+            Ok(std::ptr::read(&manager.session))
+        }
+    }
+
+    /// Number of watchpoints the thread can have at once.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Watches `len` bytes at `address`, aligned to `len`, in a free debug register, and
+    /// returns its slot. The thread must be stopped.
+    pub fn insert(&mut self, address: u64, len: usize, kind: WatchKind) -> anyhow::Result<usize> {
+        if kind == WatchKind::Execute && len != debug_registers::EXECUTE_LEN {
+            bail!(
+                "Hardware breakpoints cover {} bytes",
+                debug_registers::EXECUTE_LEN
+            );
+        }
+        if ![1, 2, 4, 8].contains(&len) || !address.is_multiple_of(len as u64) {
+            bail!("Cannot watch {len} bytes at {address:#x}");
+        }
+        let Some(slot) = self.slots.iter().position(Option::is_none) else {
+            bail!("All the {} debug registers are in use", self.capacity());
+        };
+
+        self.slots[slot] = Some(Watchpoint {
+            slot,
+            address,
+            len,
+            kind,
+            hits: 0,
+        });
+        if let Err(error) = debug_registers::write(&self.session, &self.slots) {
+            self.slots[slot] = None;
+            return Err(error);
+        }
+
+        Ok(slot)
+    }
+
+    /// Sets a hardware breakpoint at `address`, which unlike a software breakpoint leaves the
+    /// code unchanged.
+    pub fn insert_breakpoint(&mut self, address: u64) -> anyhow::Result<usize> {
+        self.insert(address, debug_registers::EXECUTE_LEN, WatchKind::Execute)
+    }
+
+    /// Removes the watchpoint of `slot`.
+    pub fn remove(&mut self, slot: usize) -> anyhow::Result<Watchpoint> {
+        let watchpoint = self
+            .slots
+            .get_mut(slot)
+            .and_then(Option::take)
+            .ok_or_else(|| anyhow!("No watchpoint in slot {slot}"))?;
+        if self.last_hit == Some(slot) {
+            self.last_hit = None;
+        }
+        debug_registers::write(&self.session, &self.slots)?;
+
+        Ok(watchpoint)
+    }
+
+    /// Removes every watchpoint.
+    pub fn remove_all(&mut self) -> anyhow::Result<()> {
+        self.slots.fill(None);
+        self.last_hit = None;
+        debug_registers::write(&self.session, &self.slots)
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&Watchpoint> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Watchpoints in slot order.
+    pub fn watchpoints(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.slots.iter().flatten()
+    }
+
+    /// Resumes the thread, delivering its pending signal, first stepping over the access of the
+    /// last hit when the processor trapped before it.
+    pub fn cont(&mut self) -> anyhow::Result<()> {
+        // The signal of the current stop is delivered once the step is done
+        if self.session.pending_signal() != 0 {
+            self.pending_signal = self.session.pending_signal();
+        }
+        if let Some(stop) = self.step_over()? {
+            bail!(
+                "Thread {} stopped stepping over a watchpoint: {stop:?}",
+                self.session.tid()
+            );
+        }
+        let signal = std::mem::take(&mut self.pending_signal);
+        self.session.cont(signal)
+    }
+
+    /// Executes the instruction which fired the last hit with its watchpoint disabled. Returns
+    /// how the thread ended if it did.
+    fn step_over(&mut self) -> anyhow::Result<Option<Stop>> {
+        let Some(slot) = self.last_hit.take() else {
+            return Ok(None);
+        };
+        if !debug_registers::TRAPS_BEFORE_ACCESS {
+            return Ok(None);
+        }
+
+        let watchpoint = self.slots[slot].take();
+        debug_registers::write(&self.session, &self.slots)?;
+        self.slots[slot] = watchpoint;
+        loop {
+            self.session.step(0)?;
+            match self.session.wait()? {
+                Stop::Signal(libc::SIGTRAP) => {
+                    self.session.suppress_signal();
+                    break;
+                }
+                stop if stop.is_exit() => return Ok(Some(stop)),
+                // Delivered once the step is done
+                Stop::Signal(signal) => {
+                    self.pending_signal = signal;
+                    self.session.suppress_signal();
+                }
+                _ => {}
+            }
+        }
+        debug_registers::write(&self.session, &self.slots)?;
+
+        Ok(None)
+    }
+
+    /// Waits for the thread to stop, reporting the watchpoint which fired if any.
+    pub fn wait(&mut self) -> anyhow::Result<WatchpointEvent> {
+        let stop = self.session.wait()?;
+        if stop != Stop::Signal(libc::SIGTRAP) {
+            return Ok(WatchpointEvent::Stop(stop));
+        }
+        let Some(slot) = debug_registers::hit(&self.session, &self.slots)? else {
+            return Ok(WatchpointEvent::Stop(stop));
+        };
+
+        // The trap comes from the debug registers, not from a signal to deliver
+        self.session.suppress_signal();
+        self.last_hit = Some(slot);
+        let registers = self.session.registers()?;
+        let tid = self.session.tid();
+        let watchpoint = self.slots[slot]
+            .as_mut()
+            .ok_or_else(|| anyhow!("No watchpoint in slot {slot}"))?;
+        watchpoint.hits += 1;
+
+        Ok(WatchpointEvent::Hit(Box::new(WatchpointHit {
+            slot,
+            address: watchpoint.address,
+            tid,
+            hits: watchpoint.hits,
+            registers,
+        })))
+    }
+
+    /// Resumes the thread and waits for it to stop, see [`Self::cont`] and [`Self::wait`].
+    pub fn run(&mut self) -> anyhow::Result<WatchpointEvent> {
+        self.cont()?;
+        self.wait()
+    }
+}
+
+impl Drop for WatchpointManager {
+    fn drop(&mut self) {
+        // The debug registers can only be written while the thread is stopped
+        if self.session.stop().is_ok() {
+            let _ = self.remove_all();
+        }
+    }
+}
@@ -1,9 +1,5776 @@
 #[cfg(test)]
 mod tests {
+    use libinspector::introspection::{
+        access::{
+            AtomicWriter, MemoryReader, MemoryReaderExt, MemoryWriter, MemoryWriterExt,
+            PartialRead, RemoteString, UnreadableMemory,
+        },
+        arch::{Arch, Bitness, Registers, Regs32},
+        audit::{WritePolicy, WriteRecord},
+        auxv::{AuxVec, AT_ENTRY, AT_PAGESZ},
+        batch::{pids, BatchScan},
+        breakpoint::{BreakpointEvent, BreakpointManager},
+        cache::CachedReader,
+        capabilities::{Capabilities, Capability},
+        detour::{self, BranchKind, DetourBuilder},
+        elf::{self, Elf, Module},
+        fallback::{FallbackPolicy, FallbackWriter, WriteBackend},
+        fd::FdKind,
+        fpu::FpRegisters,
+        freeze::Freezer,
+        handle::{ProcessHandle, SegmentHandle},
+        idmap::IdMap,
+        kallsyms::Kallsyms,
+        layout::{FieldKind, StructAnalyzer},
+        limits::{LimitValue, ProcessLimits},
+        linkmap::{LoaderEvent, LoaderWatch},
+        location::{Location, LocationBase, Locator},
+        mem::ProcMem,
+        memory::{HugePageSummary, KsmStat, MappingUsage, MemoryRollup, MemorySummary},
+        mountinfo::{MountInfo, MountTable},
+        net::{InetProtocol, InetSocket, Socket, SocketState, UnixSocket},
+        ns::NsKind,
+        numa::{parse_numa_line, NumaPolicy},
+        pagemap::{page_size, Advice, ClearRefs, PagemapEntry, KPF_ANON},
+        patch::{Patch, PatchSet},
+        pattern::Pattern,
+        pod::Pod,
+        pointer::{
+            rank_paths, BreakReason, BrokenPointerPath, PathCheck, Pointer, PointerMap,
+            PointerPath, PointerSearch,
+        },
+        process::{LinkTarget, Process, ProcessState},
+        ptrace::{AttachMode, FrozenProcess, PtraceMem, PtraceSession, Stop},
+        remote::{
+            call_remote, find_instruction, inject_library, remote_madvise, remote_mlock,
+            remote_mmap, remote_mprotect, remote_munlock, remote_munmap, remote_symbol,
+            remote_syscall, Shellcode,
+        },
+        scan::{
+            CancellationToken, Predicate, ScanCancelled, ScanValue, Scanner, Snapshot, ValueType,
+        },
+        sched::SchedInfo,
+        seccomp::{seccomp_filters, BpfInstruction, SeccompMode},
+        segment::{
+            segments, DataSegment, Segment, SegmentPermission, SegmentPermissions, SegmentType,
+            Segments,
+        },
+        session::ScanSession,
+        spill::SpilledResults,
+        stack::parse_kernel_stack,
+        strace::{
+            syscall_info, syscall_number, SyscallArg, SyscallEntry, SyscallExit, SyscallTracer,
+            TraceEvent,
+        },
+        stream::{Chunk, HolePolicy},
+        strings::{StringEncoding, StringOptions},
+        symbol::{self, SymbolBinding, SymbolKind, SymbolTable},
+        syscall::SyscallState,
+        thread::{tids, StepEvent, Thread},
+        verify::{VerifiedWriter, WriteVerificationError},
+        vm::{self, ProcessVm},
+        watch::{GrowthTracker, MapsEvent, MapsWatcher},
+        watchpoint::{WatchKind, WatchpointEvent, WatchpointManager},
+    };
     use libinspector::*;
 
     #[test]
     fn test_test() {
         assert_eq!(test(), 1);
     }
+
+    #[test]
+    fn test_process_from_pid() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        assert_eq!(process.pid(), std::process::id());
+        assert_eq!(process.parent_id(), std::os::unix::process::parent_id());
+        assert!(process.num_threads().unwrap() >= 1);
+    }
+
+    #[test]
+    fn test_process_stat_name_with_parentheses() {
+        let stat = "42 (a) (b)) S 1 42 42 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+                    1000 4096 10 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 \
+                    0 0 0 0 0 0 0 0 0 0 0 0 0";
+        let process: Process = stat.parse().unwrap();
+
+        assert_eq!(process.pid(), 42);
+        assert_eq!(process.name(), "a) (b)");
+        assert_eq!(process.tpgid(), -1);
+        assert_eq!(process.processor(), 3);
+    }
+
+    #[test]
+    fn test_process_limits() {
+        let limits = Process::from_pid(std::process::id())
+            .unwrap()
+            .limits()
+            .unwrap();
+
+        assert!(limits.open_files.soft <= limits.open_files.hard);
+    }
+
+    #[test]
+    fn test_process_limits_parse() {
+        let limits: ProcessLimits = "\
+Limit                     Soft Limit           Hard Limit           Units     
+Max cpu time              unlimited            unlimited            seconds   
+Max file size             unlimited            unlimited            bytes     
+Max data size             unlimited            unlimited            bytes     
+Max stack size            8388608              unlimited            bytes     
+Max core file size        0                    unlimited            bytes     
+Max resident set          unlimited            unlimited            bytes     
+Max processes             63304                63304                processes 
+Max open files            1024                 524288               files     
+Max locked memory         8388608              8388608              bytes     
+Max address space         unlimited            unlimited            bytes     
+Max file locks            unlimited            unlimited            locks     
+Max pending signals       63304                63304                signals   
+Max msgqueue size         819200               819200               bytes     
+Max nice priority         0                    0                    
+Max realtime priority     0                    0                    
+Max realtime timeout      unlimited            unlimited            us        
+"
+        .parse()
+        .unwrap();
+
+        assert_eq!(limits.core_file_size.soft, LimitValue::Value(0));
+        assert_eq!(limits.core_file_size.hard, LimitValue::Unlimited);
+        assert_eq!(limits.locked_memory.soft, LimitValue::Value(8388608));
+        assert!(!limits.locked_memory.soft.allows(8388609));
+        assert_eq!(limits.realtime_priority.hard, LimitValue::Value(0));
+
+        // Malformed lines fail rather than panic, e.g. a character across the name column
+        let header = "Limit                     Soft Limit           Hard Limit           Units\n";
+        let misaligned = format!("{header}{}é 0 0 bytes\n", "x".repeat(25));
+        assert!(misaligned.parse::<ProcessLimits>().is_err());
+        assert!(format!("{header}Max cpu time\n")
+            .parse::<ProcessLimits>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_process_environ() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let environ = process.environ().unwrap();
+
+        for (key, value) in std::env::vars_os() {
+            if let Some(initial) = environ.get(&key) {
+                // The test harness does not modify the inherited environment
+                assert_eq!(initial, &value);
+            }
+        }
+        assert!(!environ.is_empty());
+    }
+
+    #[test]
+    fn test_process_cmdline() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let cmdline = process.cmdline().unwrap();
+
+        assert_eq!(cmdline, std::env::args_os().collect::<Vec<_>>());
+        assert!(process
+            .cmdline_display()
+            .unwrap()
+            .starts_with(&*cmdline[0].to_string_lossy()));
+    }
+
+    #[test]
+    fn test_process_cmdline_kernel_thread() {
+        // kthreadd is always PID 2, unless we are in a PID namespace
+        let Ok(process) = Process::from_pid(2) else {
+            return;
+        };
+        if process.name() != "kthreadd" {
+            return;
+        }
+
+        assert!(process.cmdline().unwrap().is_empty());
+        assert_eq!(process.cmdline_display().unwrap(), "[kthreadd]");
+    }
+
+    #[test]
+    fn test_process_fds() {
+        let (reader, _writer) = std::io::pipe().unwrap();
+        let file = std::fs::File::open("/proc/self/status").unwrap();
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let fds = process.fds().unwrap();
+
+        let pipe = fds
+            .iter()
+            .find(|fd| fd.fd() == std::os::fd::AsRawFd::as_raw_fd(&reader))
+            .unwrap();
+        assert!(matches!(pipe.kind(), FdKind::Pipe(_)));
+
+        let file = fds
+            .iter()
+            .find(|fd| fd.fd() == std::os::fd::AsRawFd::as_raw_fd(&file))
+            .unwrap();
+        assert_eq!(file.kind(), &FdKind::File);
+        assert_eq!(file.pos(), 0);
+        assert!(file.target().ends_with("status"));
+    }
+
+    #[test]
+    fn test_fd_kind_parse() {
+        assert_eq!(
+            "socket:[1234]".parse::<FdKind>().unwrap(),
+            FdKind::Socket(1234)
+        );
+        assert_eq!(
+            "anon_inode:[eventfd]".parse::<FdKind>().unwrap(),
+            FdKind::EventFd
+        );
+        assert_eq!(
+            "anon_inode:inotify".parse::<FdKind>().unwrap(),
+            FdKind::Inotify
+        );
+        assert_eq!(
+            "anon_inode:[io_uring]".parse::<FdKind>().unwrap(),
+            FdKind::AnonInode("io_uring".to_string())
+        );
+        assert_eq!("/dev/null".parse::<FdKind>().unwrap(), FdKind::File);
+    }
+
+    #[test]
+    fn test_process_sockets() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let sockets = process.sockets().unwrap();
+
+        let remote = sockets.iter().find_map(|(fd, socket)| match socket {
+            Socket::Inet(inet)
+                if fd.fd() == std::os::fd::AsRawFd::as_raw_fd(&client)
+                    && inet.state == SocketState::Established =>
+            {
+                Some(inet.remote)
+            }
+            _ => None,
+        });
+        assert_eq!(remote, Some(listener.local_addr().unwrap()));
+    }
+
+    #[test]
+    fn test_socket_parse() {
+        let tcp = InetSocket::parse(
+            "   0: 0100007F:0277 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 12345 1 0000000000000000 100 0 0 10 0",
+            InetProtocol::Tcp,
+        )
+        .unwrap();
+        assert_eq!(tcp.local, "127.0.0.1:631".parse().unwrap());
+        assert_eq!(tcp.state, SocketState::Listen);
+        assert_eq!(tcp.inode, 12345);
+
+        let unix: UnixSocket =
+            "0000000000000000: 00000002 00000000 00010000 0001 01 23456 /run/my socket"
+                .parse()
+                .unwrap();
+        assert!(unix.listening);
+        assert_eq!(unix.inode, 23456);
+        assert_eq!(unix.path.as_deref(), Some("/run/my socket"));
+    }
+
+    #[test]
+    fn test_process_links() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        let exe = process.exe_path().unwrap();
+        assert_eq!(exe.path, std::env::current_exe().unwrap());
+        assert!(!exe.deleted);
+        assert_eq!(
+            process.cwd().unwrap().path,
+            std::env::current_dir().unwrap()
+        );
+        assert_eq!(process.root().unwrap().path, std::path::Path::new("/"));
+    }
+
+    #[test]
+    fn test_link_target_deleted() {
+        let target = LinkTarget::from(std::path::PathBuf::from("/usr/bin/my app (deleted)"));
+
+        assert_eq!(target.path, std::path::Path::new("/usr/bin/my app"));
+        assert!(target.deleted);
+    }
+
+    #[test]
+    fn test_process_auxv() {
+        let auxv = Process::from_pid(std::process::id())
+            .unwrap()
+            .auxv()
+            .unwrap();
+
+        assert_eq!(auxv.page_size(), Some(4096));
+        assert!(auxv.entry().unwrap() != 0);
+        assert!(auxv.random().is_some());
+    }
+
+    #[test]
+    fn test_auxv_parse() {
+        let bytes: Vec<u8> = [AT_PAGESZ, 4096, AT_ENTRY, 0x401000, 0, 0]
+            .iter()
+            .flat_map(|word: &u64| word.to_ne_bytes())
+            .collect();
+        let auxv = AuxVec::parse(&bytes).unwrap();
+
+        assert_eq!(auxv.entries().len(), 2);
+        assert_eq!(auxv.entry(), Some(0x401000));
+        assert_eq!(auxv.base(), None);
+        assert!(AuxVec::parse(&bytes[..12]).is_err());
+    }
+
+    #[test]
+    fn test_process_kernel_stack() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        match process.kernel_stack() {
+            Ok(frames) => assert!(!frames.is_empty()),
+            Err(e) => assert_eq!(
+                e.downcast_ref::<std::io::Error>().unwrap().kind(),
+                std::io::ErrorKind::PermissionDenied
+            ),
+        }
+    }
+
+    #[test]
+    fn test_kernel_stack_parse() {
+        let frames = parse_kernel_stack(
+            "[<0>] do_wait+0x1b4/0x2a0\n[<ffffffffc0a01234>] nfs_wait [nfs]\n[<0>] entry_SYSCALL_64_after_hwframe+0x76/0x7e\n",
+        )
+        .unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].symbol, "do_wait");
+        assert_eq!(frames[0].offset, Some(0x1b4));
+        assert_eq!(frames[0].size, Some(0x2a0));
+        assert_eq!(frames[1].address, 0xffffffffc0a01234);
+        assert_eq!(frames[1].module.as_deref(), Some("nfs"));
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_process_syscall() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        // Let the child reach nanosleep
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let state = Process::from_pid(child.id()).unwrap().syscall().unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        match state {
+            // clock_nanosleep or nanosleep on x86_64
+            SyscallState::InSyscall(syscall) => assert!([35, 230].contains(&syscall.number)),
+            state => panic!("Unexpected syscall state: {state:?}"),
+        }
+    }
+
+    #[test]
+    fn test_syscall_parse() {
+        assert_eq!(
+            "running\n".parse::<SyscallState>().unwrap(),
+            SyscallState::Running
+        );
+        assert_eq!(
+            "-1 0x7ffd1234 0x401000\n".parse::<SyscallState>().unwrap(),
+            SyscallState::Blocked {
+                sp: 0x7ffd1234,
+                pc: 0x401000
+            }
+        );
+
+        let SyscallState::InSyscall(syscall) =
+            "7 0x7ffc 0x1 0xffffffff 0x0 0x0 0x0 0x7ffc0000 0x7f0000001234\n"
+                .parse::<SyscallState>()
+                .unwrap()
+        else {
+            panic!("Expected a syscall");
+        };
+        assert_eq!(syscall.number, 7);
+        assert_eq!(syscall.args[2], 0xffffffff);
+        assert_eq!(syscall.pc, 0x7f0000001234);
+    }
+
+    #[test]
+    fn test_process_wait_channel_name() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        let name = Process::from_pid(child.id())
+            .unwrap()
+            .wait_channel_name()
+            .unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        // Hidden unless we are allowed to see kernel pointers
+        if let Some(name) = name {
+            assert!(!name.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_kallsyms_resolve() {
+        let kallsyms: Kallsyms = "\
+ffffffff81000000 T _text
+ffffffff81001000 T do_wait
+ffffffff81002000 t helper
+ffffffffc0000000 t nfs_wait\t[nfs]
+"
+        .parse()
+        .unwrap();
+
+        let (symbol, offset) = kallsyms.resolve(0xffffffff810011b4).unwrap();
+        assert_eq!(symbol.name, "do_wait");
+        assert_eq!(offset, 0x1b4);
+        let (symbol, _) = kallsyms.resolve(0xffffffffc0000010).unwrap();
+        assert_eq!(symbol.module.as_deref(), Some("nfs"));
+        assert!(kallsyms.resolve(0x1000).is_none());
+        assert!(kallsyms.has_addresses());
+    }
+
+    #[test]
+    fn test_process_sched() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        let sched = process.sched().unwrap();
+        assert!(sched.nr_switches().is_some());
+        assert!(sched.sum_exec_runtime().unwrap() > std::time::Duration::ZERO);
+
+        let schedstat = process.schedstat().unwrap();
+        assert!(schedstat.run_time > std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_sched_parse() {
+        let sched: SchedInfo = "\
+cat (4553, #threads: 1)
+-------------------------------------------------------------------
+se.vruntime                                  :             7.023462
+se.sum_exec_runtime                          :             0.018757
+se.nr_migrations                             :                    2
+se.statistics.wait_sum                       :             1.500000
+nr_switches                                  :                    1
+current_node=0, numa_group_id=0
+"
+        .parse()
+        .unwrap();
+
+        assert_eq!(sched.nr_switches(), Some(1));
+        assert_eq!(sched.nr_migrations(), Some(2));
+        assert_eq!(
+            sched.wait_sum(),
+            Some(std::time::Duration::from_micros(1500))
+        );
+        assert_eq!(sched.get("se.vruntime"), Some(7.023462));
+    }
+
+    #[test]
+    fn test_process_mountinfo() {
+        let mounts = Process::from_pid(std::process::id())
+            .unwrap()
+            .mountinfo()
+            .unwrap();
+
+        let proc = mounts
+            .find(std::path::Path::new("/proc/self/maps"))
+            .unwrap();
+        assert_eq!(proc.fs_type, "proc");
+        assert!(mounts.find(std::path::Path::new("/")).is_some());
+    }
+
+    #[test]
+    fn test_mountinfo_parse() {
+        let mount: MountInfo =
+            "36 35 98:0 /mnt1 /mnt/my\\040disk rw,noatime master:1 shared:2 - ext3 /dev/root rw,errors=continue"
+                .parse()
+                .unwrap();
+
+        assert_eq!(mount.mount_id, 36);
+        assert_eq!(mount.device.major(), 98);
+        assert_eq!(mount.mount_point, std::path::Path::new("/mnt/my disk"));
+        assert_eq!(mount.optional_fields, ["master:1", "shared:2"]);
+        assert_eq!(mount.fs_type, "ext3");
+        assert_eq!(mount.source.as_deref(), Some("/dev/root"));
+        assert_eq!(
+            mount.to_fs_path(std::path::Path::new("/mnt/my disk/lib/libc.so")),
+            Some(std::path::PathBuf::from("/mnt1/lib/libc.so"))
+        );
+
+        let table: MountTable = "\
+1 0 8:1 / / rw - ext4 /dev/sda1 rw
+2 1 8:2 / /home rw - ext4 /dev/sda2 rw
+3 1 8:3 / /home rw - ext4 /dev/sda3 rw
+"
+        .parse()
+        .unwrap();
+        let home = table.find(std::path::Path::new("/home/user")).unwrap();
+        assert_eq!(home.mount_id, 3);
+    }
+
+    #[test]
+    fn test_process_namespaces() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let child_process = Process::from_pid(child.id()).unwrap();
+
+        let same_pid_ns = process.same_namespace(&child_process, NsKind::Pid);
+        let namespaces = process.namespaces().unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert!(same_pid_ns.unwrap());
+        let net = namespaces.get(NsKind::Net).unwrap();
+        let link = std::fs::read_link("/proc/self/ns/net").unwrap();
+        assert_eq!(link.to_str().unwrap(), format!("net:[{}]", net.inode));
+    }
+
+    #[test]
+    fn test_process_id_mapping() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        assert!(!process.uid_map().unwrap().entries().is_empty());
+        assert_eq!(process.uid_to_inspector(1000).unwrap(), Some(1000));
+        assert_eq!(process.gid_from_inspector(0).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_id_map_translate() {
+        let map: IdMap = "         0     100000      65536\n     65536       1000          1\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(map.to_outside(0), Some(100000));
+        assert_eq!(map.to_outside(65535), Some(165535));
+        assert_eq!(map.to_outside(65536), Some(1000));
+        assert_eq!(map.to_outside(65537), None);
+        assert_eq!(map.to_inside(1000), Some(65536));
+        assert_eq!(map.to_inside(99999), None);
+    }
+
+    #[test]
+    fn test_process_capabilities() {
+        let capabilities = Process::from_pid(std::process::id())
+            .unwrap()
+            .capabilities()
+            .unwrap();
+
+        assert_eq!(
+            capabilities.effective.bits() & !capabilities.permitted.bits(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_capabilities_parse() {
+        let capabilities: Capabilities = "0000000000080021".parse().unwrap();
+
+        assert!(capabilities.has(Capability::Chown));
+        assert!(capabilities.has(Capability::Kill));
+        assert!(capabilities.has(Capability::SysPtrace));
+        assert!(!capabilities.has(Capability::SysAdmin));
+        assert_eq!(
+            capabilities.to_string(),
+            "cap_chown,cap_kill,cap_sys_ptrace"
+        );
+    }
+
+    #[test]
+    fn test_seccomp_filters() {
+        // Allow everything but getpid(), which fails with EPERM
+        let mut filter = [
+            libc::sock_filter {
+                code: 0x20,
+                jt: 0,
+                jf: 0,
+                k: 0,
+            },
+            libc::sock_filter {
+                code: 0x15,
+                jt: 0,
+                jf: 1,
+                k: libc::SYS_getpid as u32,
+            },
+            libc::sock_filter {
+                code: 0x06,
+                jt: 0,
+                jf: 0,
+                k: 0x0005_0001,
+            },
+            libc::sock_filter {
+                code: 0x06,
+                jt: 0,
+                jf: 0,
+                k: 0x7fff_0000,
+            },
+        ];
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe {
+                libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0);
+                libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &program);
+                loop {
+                    libc::pause();
+                }
+            }
+        }
+
+        let process = Process::from_pid(pid as u32).unwrap();
+        while process.seccomp_mode().unwrap() != SeccompMode::Filter {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        unsafe {
+            libc::ptrace(libc::PTRACE_ATTACH, pid, 0, 0);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+        let filters = seccomp_filters(pid as u32);
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+
+        let filters = filters.unwrap();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(filters[0].instructions.len(), 4);
+        assert_eq!(filters[0].instructions[0].to_string(), "ld [nr]");
+        assert_eq!(
+            filters[0].instructions[2].to_string(),
+            "ret #0x50001 ; ERRNO(1)"
+        );
+    }
+
+    #[test]
+    fn test_bpf_disassemble() {
+        let jeq = BpfInstruction {
+            code: 0x15,
+            jt: 0,
+            jf: 3,
+            k: 0xc000003e,
+        };
+        let arg = BpfInstruction {
+            code: 0x20,
+            jt: 0,
+            jf: 0,
+            k: 24,
+        };
+
+        assert_eq!(jeq.to_string(), "jeq #0xc000003e, 0, 3");
+        assert_eq!(arg.to_string(), "ld [args[1]]");
+    }
+
+    #[test]
+    fn test_process_segments() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let segments = process.segments();
+
+        let function = test_process_segments as *const () as u64;
+        let code = segments.iter().find(|s| s.contains(function)).unwrap();
+        assert_eq!(code.segment_type(), Some(&SegmentType::Code));
+        assert_eq!(code.path().unwrap(), std::env::current_exe().unwrap());
+        assert!(segments
+            .iter()
+            .any(|s| s.segment_type() == Some(&SegmentType::Stack)));
+    }
+
+    #[test]
+    fn test_process_segments_with_details() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let segments = process.segments_with_details().unwrap();
+
+        assert_eq!(segments.len(), process.segments().len());
+        let stack = segments
+            .iter()
+            .find(|s| s.segment_type() == Some(&SegmentType::Stack))
+            .unwrap();
+        let details = stack.details().unwrap();
+        assert_eq!(details.size(), stack.size());
+        assert!(details.rss() > 0);
+        assert!(details.has_vm_flag("gd"));
+    }
+
+    #[test]
+    fn test_segment_parse() {
+        let segment: Segment =
+            "7f2c1e600000-7f2c1e628000 r-xp 00028000 08:01 1234                       /usr/lib/libc.so.6"
+                .parse()
+                .unwrap();
+
+        assert_eq!(segment.start(), 0x7f2c1e600000);
+        assert_eq!(segment.size(), 0x28000);
+        assert_eq!(segment.offset(), 0x28000);
+        assert_eq!(
+            segment.permissions().to_array(),
+            [
+                SegmentPermission::Read,
+                SegmentPermission::NoPermission,
+                SegmentPermission::Execute,
+                SegmentPermission::Private
+            ]
+        );
+        assert_eq!(segment.device().unwrap().minor(), 1);
+        assert_eq!(segment.inode(), Some(1234));
+        assert_eq!(
+            segment.path().unwrap(),
+            std::path::Path::new("/usr/lib/libc.so.6")
+        );
+
+        let heap: Segment = "55d0c8a4f000-55d0c8a70000 rw-p 00000000 00:00 0 [heap]"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            heap.segment_type(),
+            Some(&SegmentType::Data(DataSegment::Heap))
+        );
+        assert_eq!(heap.device(), None);
+        assert_eq!(heap.path(), None);
+    }
+
+    #[test]
+    fn test_smaps_parse() {
+        let segments = Segment::parse_smaps(
+            "\
+55ee24941000-55ee24943000 r--p 00000000 fe:00 317783                     /usr/bin/head
+Size:                  8 kB
+Rss:                   8 kB
+Pss:                   4 kB
+Swap:                  0 kB
+Locked:                0 kB
+THPeligible:           0
+VmFlags: rd mr mw me
+7ffd1c000000-7ffd1c021000 rw-p 00000000 00:00 0                          [stack]
+Size:                132 kB
+Rss:                  12 kB
+Private_Dirty:        12 kB
+THPeligible:           1
+VmFlags: rd wr mr mw me gd ac
+",
+        )
+        .unwrap();
+
+        assert_eq!(segments.len(), 2);
+        let details = segments[0].details().unwrap();
+        assert_eq!(details.pss(), 4096);
+        assert!(!details.thp_eligible());
+        let details = segments[1].details().unwrap();
+        assert_eq!(details.private_dirty(), 12 * 1024);
+        assert!(details.thp_eligible());
+        assert!(details.has_vm_flag("gd"));
+    }
+
+    #[test]
+    fn test_process_memory_rollup() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let rollup = process.memory_rollup().unwrap();
+
+        assert!(rollup.rss() > 0);
+        assert!(rollup.pss() <= rollup.rss());
+        assert!(rollup.uss() <= rollup.pss());
+
+        let summed = MemoryRollup::from_segments(&process.segments_with_details().unwrap());
+        // Both are snapshots of a running process, allow some drift
+        assert!(summed.rss().abs_diff(rollup.rss()) < rollup.rss() / 2);
+    }
+
+    #[test]
+    fn test_process_segments_with_numa() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let segments = process.segments_with_numa().unwrap();
+
+        let stack = segments
+            .iter()
+            .find(|segment| segment.segment_type() == Some(&SegmentType::Stack))
+            .unwrap();
+        let numa = stack.numa().unwrap();
+        assert!(numa.total_pages() > 0);
+        assert!(numa.kernel_page_size > 0);
+    }
+
+    #[test]
+    fn test_numa_maps_line() {
+        let (start, numa) = parse_numa_line(
+            "55fbed91e000 default file=/usr/bin/head anon=1 dirty=1 active=0 N0=1 kernelpagesize_kB=4",
+        )
+        .unwrap();
+
+        assert_eq!(start, 0x55fbed91e000);
+        assert_eq!(numa.policy, NumaPolicy::Default);
+        assert_eq!((numa.anon, numa.dirty, numa.active), (1, 1, Some(0)));
+        assert_eq!(numa.node_pages.get(&0), Some(&1));
+        assert_eq!(numa.kernel_page_size, 4096);
+
+        let (_, numa) =
+            parse_numa_line("7f0000000000 interleave:0-2,4 anon=8 mapmax=3 N0=4 N4=4 huge")
+                .unwrap();
+        assert_eq!(numa.policy, NumaPolicy::Interleave(vec![0, 1, 2, 4]));
+        assert_eq!(numa.mapmax, 3);
+        assert_eq!(numa.total_pages(), 8);
+        assert!(numa.huge);
+
+        let (_, numa) = parse_numa_line("7f0000000000 weighted interleave:0-1 N1=2").unwrap();
+        assert_eq!(numa.policy, NumaPolicy::WeightedInterleave(vec![0, 1]));
+        let (_, numa) = parse_numa_line("7f0000000000 prefer (many):0-1 anon=2 N0=1 N1=1").unwrap();
+        assert_eq!(numa.policy, NumaPolicy::Preferred(vec![0, 1]));
+        assert_eq!(numa.node_pages.len(), 2);
+        let (_, numa) = parse_numa_line("7f0000000000 prefer (many)=static:2 N2=1").unwrap();
+        assert_eq!(numa.policy, NumaPolicy::Preferred(vec![2]));
+        let (_, numa) = parse_numa_line("7f0000000000 prefer:1 anon=1 N1=1").unwrap();
+        assert_eq!(numa.policy, NumaPolicy::Preferred(vec![1]));
+        assert!(parse_numa_line("7f0000000000 bind:0 N0=x").is_err());
+    }
+
+    #[test]
+    fn test_process_pagemap() {
+        let page_size = page_size() as usize;
+        let buffer = vec![1u8; page_size * 4];
+        let start = buffer.as_ptr() as u64;
+
+        let pagemap = Process::from_pid(std::process::id())
+            .unwrap()
+            .pagemap()
+            .unwrap();
+        let pages = pagemap.pages(start..start + buffer.len() as u64).unwrap();
+
+        assert!(pages.len() >= 4);
+        assert_eq!(
+            pages[0].address,
+            start / page_size as u64 * page_size as u64
+        );
+        assert!(pages.iter().all(|page| page.entry.present()));
+        // Physical frames are hidden from unprivileged readers
+        if let Some(flags) = pages[0].flags {
+            assert!(flags.has(KPF_ANON));
+            assert!(pages[0].map_count.unwrap() >= 1);
+        }
+    }
+
+    #[test]
+    fn test_pagemap_entry_bits() {
+        let entry = PagemapEntry::from_bits(1 << 63 | 1 << 56 | 1 << 55 | 0x1234);
+        assert!(entry.present() && entry.exclusive() && entry.soft_dirty());
+        assert!(!entry.swapped());
+        assert_eq!(entry.pfn(), Some(0x1234));
+        assert_eq!(entry.swap_offset(), None);
+
+        let entry = PagemapEntry::from_bits(1 << 62 | 0x42 << 5 | 3);
+        assert!(!entry.present());
+        assert_eq!(entry.pfn(), None);
+        assert_eq!(
+            (entry.swap_type(), entry.swap_offset()),
+            (Some(3), Some(0x42))
+        );
+    }
+
+    #[test]
+    fn test_process_soft_dirty_pages() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let mut buffer = vec![0u8; page_size() as usize * 4];
+
+        process.clear_refs(ClearRefs::SoftDirty).unwrap();
+        buffer[0] = 1;
+        let dirty = process.soft_dirty_pages().unwrap();
+
+        // Soft-dirty bits are never set without CONFIG_MEM_SOFT_DIRTY
+        if !dirty.is_empty() {
+            let address = buffer.as_ptr() as u64 / page_size() * page_size();
+            assert!(dirty.iter().any(|segment| segment.pages.contains(&address)));
+        }
+        assert!(dirty.iter().all(|segment| !segment.pages.is_empty()));
+    }
+
+    #[test]
+    fn test_process_idle_page_tracker() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let tracker = match process.idle_page_tracker() {
+            Ok(tracker) => tracker,
+            // Kernel built without CONFIG_IDLE_PAGE_TRACKING
+            Err(e)
+                if e.downcast_ref::<std::io::Error>().map(|e| e.kind())
+                    == Some(std::io::ErrorKind::NotFound) =>
+            {
+                return
+            }
+            Err(e) => panic!("{e:?}"),
+        };
+
+        let mut buffer = vec![0u8; page_size() as usize * 4];
+        tracker.mark_idle().unwrap();
+        buffer.iter_mut().for_each(|byte| *byte = 1);
+        let working_set = tracker.working_set().unwrap();
+
+        assert!(working_set.accessed_pages() >= 4);
+        assert_eq!(
+            working_set.size(),
+            working_set.accessed_pages() * page_size()
+        );
+    }
+
+    #[test]
+    fn test_process_find_segment() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let local = 0u64;
+        let address = &local as *const u64 as u64;
+
+        let segment = process.find_segment(address).unwrap();
+        assert!(segment.contains(address));
+        assert_eq!(process.find_segment(0), None);
+    }
+
+    #[test]
+    fn test_segments_find_range() {
+        let segments: Segments = [
+            "7f0000003000-7f0000004000 rw-p 00000000 00:00 0",
+            "7f0000000000-7f0000001000 r--p 00000000 00:00 0",
+            "7f0000001000-7f0000002000 r-xp 00000000 00:00 0",
+        ]
+        .iter()
+        .map(|line| line.parse::<Segment>().unwrap())
+        .collect::<Vec<_>>()
+        .into();
+
+        assert_eq!(
+            segments.find(0x7f0000001fff).unwrap().start(),
+            0x7f0000001000
+        );
+        assert_eq!(segments.find(0x7f0000002000), None);
+        assert_eq!(segments.find(0x7f0000004000), None);
+
+        let range = segments.find_range(0x7f0000000800, 0x7f0000003001);
+        assert_eq!(range.len(), 3);
+        assert_eq!(range[0].start(), 0x7f0000000000);
+        assert!(segments
+            .find_range(0x7f0000002000, 0x7f0000003000)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_segments_iter() {
+        let pid = std::process::id();
+        let stack = segments(pid)
+            .unwrap()
+            .map(|segment| segment.unwrap())
+            .find(|segment| segment.segment_type() == Some(&SegmentType::Stack));
+        assert!(stack.is_some());
+
+        let streamed: Vec<Segment> = segments(pid).unwrap().map(|s| s.unwrap()).collect();
+        let collected = Segment::get_from_pid(pid).unwrap();
+        assert_eq!(streamed.len(), collected.len());
+        assert_eq!(streamed.first(), collected.first());
+    }
+
+    #[test]
+    fn test_segment_pseudo_paths() {
+        let parse = |path: &str| path.parse::<SegmentType>().unwrap();
+
+        assert_eq!(parse("[vvar]"), SegmentType::Vvar);
+        assert_eq!(parse("[vvar_vclock]"), SegmentType::VvarVclock);
+        assert_eq!(parse("[vsyscall]"), SegmentType::Vsyscall);
+        assert_eq!(parse("[uprobes]"), SegmentType::Uprobes);
+        assert_eq!(parse("[stack:1234]"), SegmentType::Stack);
+        assert_eq!(parse("[foo]"), SegmentType::Other("foo".to_string()));
+        assert!("vvar".parse::<SegmentType>().is_err());
+
+        let segment: Segment =
+            "ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0  [vsyscall]"
+                .parse()
+                .unwrap();
+        assert_eq!(segment.segment_type(), Some(&SegmentType::Vsyscall));
+        assert_eq!(segment.path(), None);
+    }
+
+    #[test]
+    fn test_segment_path_with_spaces_and_deleted() {
+        let segment: Segment =
+            "7f2c1e600000-7f2c1e628000 r-xp 00000000 08:01 1234       /tmp/my lib (1).so"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            segment.path().unwrap(),
+            std::path::Path::new("/tmp/my lib (1).so")
+        );
+        assert!(!segment.deleted());
+
+        let segment: Segment =
+            "7f2c1e600000-7f2c1e628000 rw-s 00000000 00:01 5678       /memfd:my buffer (deleted)"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            segment.path().unwrap(),
+            std::path::Path::new("/memfd:my buffer")
+        );
+        assert!(segment.deleted());
+    }
+
+    #[test]
+    fn test_segment_permissions() {
+        let permissions: SegmentPermissions = "rw-s".parse().unwrap();
+        assert!(permissions.is_readable() && permissions.is_writable());
+        assert!(!permissions.is_executable());
+        assert!(permissions.is_shared());
+        assert_eq!(permissions.to_string(), "rw-s");
+        assert_eq!(
+            permissions,
+            SegmentPermissions::READ | SegmentPermissions::WRITE | SegmentPermissions::SHARED
+        );
+
+        let old = [
+            SegmentPermission::Read,
+            SegmentPermission::NoPermission,
+            SegmentPermission::Execute,
+            SegmentPermission::Private,
+        ];
+        let permissions = SegmentPermissions::from(old);
+        assert_eq!(permissions.to_string(), "r-xp");
+        assert_eq!(<[SegmentPermission; 4]>::from(permissions), old);
+        assert!("r-x".parse::<SegmentPermissions>().is_err());
+        assert!("rwxq".parse::<SegmentPermissions>().is_err());
+    }
+
+    #[test]
+    fn test_process_map_files() {
+        use std::io::Read;
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let exe = std::env::current_exe().unwrap();
+        let map_files = process.map_files().unwrap();
+        let segments = Segments::from(process.segments().to_vec());
+
+        let map_file = map_files
+            .iter()
+            .find(|map_file| map_file.target().path == exe)
+            .unwrap();
+        let segment = map_file.segment(&segments).unwrap();
+        assert_eq!(segment.path().unwrap(), exe);
+
+        let mut magic = [0; 4];
+        map_file.open().unwrap().read_exact(&mut magic).unwrap();
+        assert_eq!(&magic, b"\x7fELF");
+        segment
+            .open_map_file(process.pid())
+            .unwrap()
+            .read_exact(&mut magic)
+            .unwrap();
+        assert_eq!(&magic, b"\x7fELF");
+
+        let anonymous = segments
+            .iter()
+            .find(|segment| segment.path().is_none())
+            .unwrap();
+        assert!(anonymous.open_map_file(process.pid()).is_err());
+    }
+
+    #[test]
+    fn test_process_handle_segment_access() {
+        let buffer: Vec<u8> = (0..=255).collect();
+        let address = buffer.as_ptr() as u64;
+
+        let handle = ProcessHandle::open(std::process::id()).unwrap();
+        let segments = handle.segments().unwrap();
+        let segment = segments
+            .iter()
+            .find(|segment| segment.segment().contains(address))
+            .unwrap();
+        let offset = address - segment.segment().start();
+
+        let mut read = [0; 4];
+        segment.read_at(offset + 10, &mut read).unwrap();
+        assert_eq!(read, [10, 11, 12, 13]);
+
+        segment.write(offset + 10, &[0xaa, 0xbb]).unwrap();
+        assert_eq!(std::hint::black_box(&buffer)[10..12], [0xaa, 0xbb]);
+
+        let contents = segment.read().unwrap();
+        let mut dump = Vec::new();
+        assert_eq!(
+            segment.dump_to(&mut dump).unwrap(),
+            segment.segment().size()
+        );
+        assert_eq!(dump.len(), contents.len());
+        assert_eq!(dump[offset as usize + 8..][..4], [8, 9, 0xaa, 0xbb]);
+        assert!(segment
+            .read_at(segment.segment().size(), &mut read)
+            .is_err());
+    }
+
+    #[test]
+    fn test_segment_swap_bytes() {
+        let segments = Segment::parse_smaps(
+            "\
+7f0000000000-7f0000010000 rw-p 00000000 00:00 0
+Size:                 64 kB
+Swap:                 16 kB
+7f0000010000-7f0000020000 rw-p 00000000 00:00 0
+Size:                 64 kB
+Swap:                  0 kB
+",
+        )
+        .unwrap();
+        assert_eq!(segments[0].swap_bytes(), Some(16 * 1024));
+        assert_eq!(segments[1].swap_bytes(), Some(0));
+
+        let segment: Segment = "7f0000000000-7f0000010000 rw-p 00000000 00:00 0"
+            .parse()
+            .unwrap();
+        assert_eq!(segment.swap_bytes(), None);
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let swapped = process.swapped_segments().unwrap();
+        assert!(swapped
+            .windows(2)
+            .all(|pair| pair[0].swap_bytes() >= pair[1].swap_bytes()));
+        assert!(swapped.iter().all(|segment| segment.swap_bytes() > Some(0)));
+    }
+
+    #[test]
+    fn test_huge_page_summary() {
+        let segments = Segment::parse_smaps(
+            "\
+7f0000000000-7f0000400000 rw-p 00000000 00:00 0
+Size:               4096 kB
+AnonHugePages:      2048 kB
+THPeligible:           1
+VmFlags: rd wr mr mw me ac hg
+7f0000400000-7f0000600000 rw-s 00000000 00:10 42                         /dev/hugepages/buffer
+Size:               2048 kB
+KernelPageSize:     2048 kB
+Shared_Hugetlb:     2048 kB
+THPeligible:           0
+VmFlags: rd wr sh mr mw me ms de ht
+",
+        )
+        .unwrap();
+
+        let summary = HugePageSummary::from_segments(&segments);
+        assert_eq!(summary.anon_huge_pages, 2 << 20);
+        assert_eq!(summary.thp(), 2 << 20);
+        assert_eq!(summary.hugetlb, 2 << 20);
+        assert_eq!(summary.thp_eligible_segments, 1);
+        assert_eq!(summary.thp_segments, 1);
+        assert_eq!(summary.hugetlb_segments, 1);
+        assert!(segments[1].details().unwrap().is_hugetlb());
+
+        let summary = Process::from_pid(std::process::id())
+            .unwrap()
+            .huge_pages()
+            .unwrap();
+        // The test process maps nothing from hugetlbfs
+        assert_eq!((summary.hugetlb, summary.hugetlb_segments), (0, 0));
+    }
+
+    #[test]
+    fn test_process_locked_segments() {
+        let page_size = page_size() as usize;
+        // A dedicated mapping, so the lock does not spread over a shared heap segment
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size * 2,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: the range was just mapped
+        assert_eq!(unsafe { libc::mlock(address, page_size * 2) }, 0);
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let locked = process.locked_segments().unwrap();
+        let segment = locked
+            .iter()
+            .find(|segment| segment.contains(address as u64))
+            .unwrap();
+        let details = segment.details().unwrap();
+        assert!(details.is_locked());
+        assert!(details.locked() >= 2 * page_size as u64);
+        assert!(process.locked_bytes().unwrap() >= 2 * page_size as u64);
+
+        // SAFETY: the range is no longer used
+        unsafe { libc::munmap(address, page_size * 2) };
+    }
+
+    #[test]
+    fn test_ksm_stat() {
+        let stat: KsmStat = "\
+ksm_rmap_items 12
+ksm_zero_pages 3
+ksm_merging_pages 8
+ksm_process_profit -4096
+ksm_merge_any: yes
+ksm_mergeable: yes
+"
+        .parse()
+        .unwrap();
+        assert_eq!(stat.rmap_items, 12);
+        assert_eq!(stat.zero_pages, Some(3));
+        assert_eq!(stat.merging_pages, Some(8));
+        assert_eq!(stat.process_profit, Some(-4096));
+        assert_eq!((stat.merge_any, stat.mergeable), (Some(true), Some(true)));
+
+        let stat: KsmStat = "ksm_rmap_items 0\n".parse().unwrap();
+        assert_eq!(stat.merging_pages, None);
+        assert!("ksm_merging_pages 1\n".parse::<KsmStat>().is_err());
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        if let Some(stat) = process.ksm_stat().unwrap() {
+            assert_ne!(stat.merge_any, Some(true));
+        }
+        // Nothing was made mergeable
+        assert_eq!(process.memory_rollup().unwrap().ksm().unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn test_memory_summary() {
+        let segments = Segment::parse_smaps(
+            "\
+7f0000000000-7f0000010000 r--p 00000000 08:01 1234                       /usr/lib/libc.so.6
+Rss:                  40 kB
+Pss:                  10 kB
+Shared_Clean:         40 kB
+7f0000010000-7f0000020000 rw-p 00010000 08:01 1234                       /usr/lib/libc.so.6
+Rss:                   8 kB
+Pss:                   8 kB
+Private_Dirty:         8 kB
+Swap:                  4 kB
+7f0000020000-7f0000030000 rw-p 00000000 00:00 0                          [heap]
+Rss:                  64 kB
+Pss:                  64 kB
+Private_Clean:         4 kB
+Private_Dirty:        60 kB
+",
+        )
+        .unwrap();
+
+        let summary = MemorySummary::from_segments(&segments);
+        assert_eq!(summary.rss, 112 * 1024);
+        assert_eq!(summary.pss, 82 * 1024);
+        assert_eq!(summary.uss, 72 * 1024);
+        assert_eq!(summary.shared, 40 * 1024);
+        assert_eq!(summary.swap, 4 * 1024);
+
+        assert_eq!(summary.mappings.len(), 2);
+        assert_eq!(summary.mappings[0].name, "[heap]");
+        let libc = summary.mapping("/usr/lib/libc.so.6").unwrap();
+        assert_eq!(libc.segments, 2);
+        assert_eq!(libc.pss, 18 * 1024);
+        assert_eq!(libc.uss, 8 * 1024);
+
+        let summary = Process::from_pid(std::process::id())
+            .unwrap()
+            .memory_summary()
+            .unwrap();
+        assert!(summary.uss <= summary.pss && summary.pss <= summary.rss);
+        assert!(summary.mapping("[stack]").is_some());
+    }
+
+    #[test]
+    fn test_maps_watcher() {
+        let page_size = page_size() as usize;
+        let mut watcher = MapsWatcher::new(std::process::id()).unwrap();
+        // Other tests map memory concurrently, only look at our mapping
+        let mut poll = |address: u64| -> Vec<MapsEvent> {
+            watcher
+                .poll()
+                .unwrap()
+                .into_iter()
+                .filter(|event| match event {
+                    MapsEvent::SegmentAdded(segment) | MapsEvent::SegmentRemoved(segment) => {
+                        segment.start() == address
+                    }
+                    MapsEvent::SegmentPermissionsChanged { segment, .. } => {
+                        segment.start() == address
+                    }
+                })
+                .collect()
+        };
+
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        let events = poll(address as u64);
+        assert!(
+            matches!(&events[..], [MapsEvent::SegmentAdded(segment)] if segment.size() == page_size as u64)
+        );
+
+        // SAFETY: the page was just mapped
+        assert_eq!(
+            unsafe { libc::mprotect(address, page_size, libc::PROT_READ | libc::PROT_EXEC) },
+            0
+        );
+        let events = poll(address as u64);
+        match &events[..] {
+            [MapsEvent::SegmentPermissionsChanged { old, segment }] => {
+                assert!(!old.is_executable());
+                assert!(segment.permissions().is_executable());
+            }
+            // The page may have been merged with a neighbour
+            _ => assert!(!events.is_empty()),
+        }
+
+        // SAFETY: the page is no longer used
+        unsafe { libc::munmap(address, page_size) };
+        let events = poll(address as u64);
+        assert!(matches!(&events[..], [MapsEvent::SegmentRemoved(_)]));
+    }
+
+    #[test]
+    fn test_growth_tracker() {
+        let summary = |heap: u64, libc: u64| MemorySummary {
+            mappings: vec![
+                MappingUsage {
+                    name: "[heap]".to_string(),
+                    rss: heap,
+                    ..Default::default()
+                },
+                MappingUsage {
+                    name: "/usr/lib/libc.so.6".to_string(),
+                    rss: libc,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut tracker = GrowthTracker::new(std::process::id()).with_capacity(3);
+        tracker.push(summary(100, 50));
+        tracker.push(summary(150, 60));
+        tracker.push(summary(200, 40));
+        tracker.push(summary(300, 40));
+        assert_eq!(tracker.samples().len(), 3);
+
+        let growing = tracker.growing();
+        assert_eq!(growing.len(), 1);
+        assert_eq!(growing[0].name, "[heap]");
+        assert_eq!((growing[0].first, growing[0].last), (150, 300));
+        assert_eq!(growing[0].growth(), 150);
+        assert!(growing[0].monotonic);
+
+        let mut tracker = GrowthTracker::new(std::process::id())
+            .with_interval(std::time::Duration::from_millis(10));
+        tracker.run(2).unwrap();
+        assert_eq!(tracker.samples().len(), 2);
+    }
+
+    #[test]
+    fn test_memory_traits_custom_backend() {
+        /// A fake address space starting at 0x1000
+        struct Buffer(std::cell::RefCell<Vec<u8>>);
+
+        impl MemoryReader for Buffer {
+            fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+                let start = (address - 0x1000) as usize;
+                buffer.copy_from_slice(&self.0.borrow()[start..start + buffer.len()]);
+                Ok(())
+            }
+        }
+
+        impl MemoryWriter for Buffer {
+            fn write(&self, address: u64, data: &[u8]) -> anyhow::Result<()> {
+                let start = (address - 0x1000) as usize;
+                self.0.borrow_mut()[start..start + data.len()].copy_from_slice(data);
+                Ok(())
+            }
+        }
+
+        let memory = Buffer(std::cell::RefCell::new((0..32).collect()));
+        let segment: Segment = "1000-1020 rw-p 00000000 00:00 0".parse().unwrap();
+        let segment = SegmentHandle::new(&memory, segment);
+
+        segment.write(4, &[0xff]).unwrap();
+        assert_eq!(segment.read().unwrap()[3..6], [3, 0xff, 5]);
+        let mut dump = Vec::new();
+        assert_eq!(segment.dump_to(&mut dump).unwrap(), 32);
+        assert_eq!(dump, memory.0.borrow().clone());
+
+        let reader: &dyn MemoryReader = &memory;
+        assert_eq!(reader.read_bytes(0x1004, 2).unwrap(), [0xff, 5]);
+        assert!(segment.write(31, &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_process_vm() {
+        let memory = Process::from_pid(std::process::id()).unwrap().memory();
+        let source: Vec<u8> = (0..=255).cycle().take(4096 * 3).collect();
+        let address = source.as_ptr() as u64;
+
+        let mut buffer = vec![0; source.len()];
+        memory.read(address, &mut buffer).unwrap();
+        assert_eq!(buffer, source);
+
+        // More pieces than a single call accepts
+        let mut bytes = vec![0u8; 2000];
+        let mut requests: Vec<(u64, &mut [u8])> = bytes
+            .chunks_mut(1)
+            .enumerate()
+            .map(|(i, byte)| (address + 2 * i as u64, byte))
+            .collect();
+        memory.read_vectored(&mut requests).unwrap();
+        assert!(bytes
+            .iter()
+            .enumerate()
+            .all(|(i, byte)| *byte == source[2 * i]));
+
+        let target = vec![0u8; 16];
+        let target_address = target.as_ptr() as u64;
+        memory
+            .write_vectored(&[(target_address, &[1, 2]), (target_address + 8, &[3])])
+            .unwrap();
+        assert_eq!(
+            std::hint::black_box(&target)[..9],
+            [1, 2, 0, 0, 0, 0, 0, 0, 3]
+        );
+
+        assert!(memory.read(0, &mut [0; 8]).is_err());
+        assert!(ProcessVm::new(std::process::id()).read_bytes(8, 1).is_err());
+    }
+
+    #[test]
+    fn test_proc_mem_writes_read_only_pages() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+
+        let mem = ProcMem::open(std::process::id()).unwrap();
+        assert!(mem.writable());
+        assert!(ProcessVm::new(std::process::id())
+            .write(address as u64, &[0x42])
+            .is_err());
+        mem.write(address as u64, &[0x42]).unwrap();
+        assert_eq!(mem.read_bytes(address as u64, 2).unwrap(), [0x42, 0]);
+        // SAFETY: the page is mapped readable
+        assert_eq!(unsafe { *(address as *const u8) }, 0x42);
+
+        // SAFETY: the page is no longer used
+        unsafe { libc::munmap(address, page_size) };
+    }
+
+    #[test]
+    fn test_ptrace_mem() {
+        let value: Vec<u8> = (0..32).collect();
+        let address = value.as_ptr() as u64;
+
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+
+        let mem = PtraceMem::new(pid as u32);
+        let read = mem.read_bytes(address + 3, 13);
+        let written = mem
+            .write(address + 5, &[0xaa; 10])
+            .and_then(|()| mem.read_bytes(address, 32));
+        let read_null = mem.read_bytes(0, 8);
+        let after = ProcessVm::new(pid as u32).read_bytes(address, 32);
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+
+        assert_eq!(read.unwrap(), value[3..16]);
+        let written = written.unwrap();
+        assert_eq!(written[..5], value[..5]);
+        assert_eq!(written[5..15], [0xaa; 10]);
+        assert_eq!(written[15..], value[15..]);
+        assert_eq!(after.unwrap(), written);
+        assert!(read_null.is_err());
+        // Only the child was modified
+        assert_eq!(value[5], 5);
+    }
+
+    #[test]
+    fn test_read_write_value() {
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Player {
+            health: u32,
+            score: u32,
+            position: [f32; 2],
+        }
+        // SAFETY: repr(C), no padding, any bit pattern is valid
+        unsafe impl Pod for Player {}
+
+        let player = Box::new(Player {
+            health: 100,
+            score: 42,
+            position: [1.5, -2.0],
+        });
+        let address = &*player as *const Player as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        assert_eq!(memory.read_value::<Player>(address).unwrap(), *player);
+        assert_eq!(memory.read_value::<u32>(address + 4).unwrap(), 42);
+        assert_eq!(memory.read_value::<f32>(address + 12).unwrap(), -2.0);
+        assert!(memory.read_value::<u32>(address + 1).is_err());
+        assert_eq!(memory.read_value_unaligned::<u16>(address + 1).unwrap(), 0);
+
+        memory.write_value(address, &7u32).unwrap();
+        memory.write_value(address + 8, &[3.0f32, 4.0f32]).unwrap();
+        let player = std::hint::black_box(player);
+        assert_eq!(player.health, 7);
+        assert_eq!(player.position, [3.0, 4.0]);
+        assert!(memory.write_value(address + 2, &1u64).is_err());
+    }
+
+    #[test]
+    fn test_read_strings() {
+        let memory = ProcessVm::new(std::process::id());
+
+        let bytes = b"hello\0world\0".to_vec();
+        let string = memory.read_cstring(bytes.as_ptr() as u64, 64).unwrap();
+        assert!(string.is_terminated());
+        assert_eq!(string.decode().unwrap(), "hello");
+
+        let string = memory.read_cstring(bytes.as_ptr() as u64, 3).unwrap();
+        assert!(!string.is_terminated());
+        assert_eq!(string.units(), b"hel");
+
+        let invalid = b"a\xffb\0".to_vec();
+        let string = memory.read_cstring(invalid.as_ptr() as u64, 64).unwrap();
+        assert!(string.decode().is_err());
+        assert_eq!(string.decode_lossy(), "a\u{fffd}b");
+
+        let utf16: Vec<u16> = "héllo wörld".encode_utf16().chain([0, 0x41]).collect();
+        let string = memory.read_utf16_string(utf16.as_ptr() as u64, 64).unwrap();
+        assert!(string.is_terminated());
+        assert_eq!(string.len(), 11);
+        assert_eq!(string.decode().unwrap(), "héllo wörld");
+
+        let surrogate = [0xd800u16, 0x41, 0];
+        let string: RemoteString<u16> = memory
+            .read_utf16_string(surrogate.as_ptr() as u64, 64)
+            .unwrap();
+        assert!(string.decode().is_err());
+        assert_eq!(string.decode_lossy(), "\u{fffd}A");
+
+        // A string running into an inaccessible page is returned unterminated
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: both pages are mapped writable
+        unsafe {
+            std::ptr::write_bytes(address.cast::<u8>(), b'x', 2 * page_size);
+            libc::mprotect(address.byte_add(page_size), page_size, libc::PROT_NONE);
+        }
+
+        let start = address as u64 + page_size as u64 - 4;
+        let string = memory.read_cstring(start, 4096).unwrap();
+        assert!(!string.is_terminated());
+        assert_eq!(string.decode().unwrap(), "xxxx");
+        assert!(memory
+            .read_cstring(address as u64 + page_size as u64, 16)
+            .is_err());
+
+        // SAFETY: the pages are no longer used
+        unsafe { libc::munmap(address, 2 * page_size) };
+    }
+
+    #[test]
+    fn test_read_write_vec() {
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Entity {
+            id: u32,
+            position: [f32; 3],
+        }
+        // SAFETY: repr(C), no padding, any bit pattern is valid
+        unsafe impl Pod for Entity {}
+
+        let entities: Vec<Entity> = (0..100)
+            .map(|id| Entity {
+                id,
+                position: [id as f32, 0.5, -1.0],
+            })
+            .collect();
+        let address = entities.as_ptr() as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        assert_eq!(memory.read_vec::<Entity>(address, 100).unwrap(), entities);
+        assert_eq!(
+            memory.read_vec::<u32>(address + 16, 2).unwrap(),
+            [1, 1.0f32.to_bits()]
+        );
+        assert!(memory.read_vec::<Entity>(address, 0).unwrap().is_empty());
+        assert!(memory.read_vec::<u32>(address + 2, 1).is_err());
+
+        let replacement = [Entity {
+            id: 1000,
+            position: [7.0; 3],
+        }; 2];
+        memory.write_slice(address + 16 * 10, &replacement).unwrap();
+        let entities = std::hint::black_box(entities);
+        assert_eq!(entities[10..12], replacement);
+        assert_eq!(entities[12].id, 12);
+        assert!(memory.write_slice(address + 1, &[1u16]).is_err());
+    }
+
+    #[test]
+    fn test_read_many() {
+        let values: Vec<u64> = (0..3000).collect();
+        let address = values.as_ptr() as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        // More ranges than a single call accepts, with unreadable ones in the middle
+        let mut requests: Vec<(u64, usize)> = (0..3000).map(|i| (address + i * 8, 8)).collect();
+        requests[5] = (8, 8);
+        requests[1500] = (0, 16);
+        requests[2000] = (address, 0);
+
+        let results = memory.read_many(&requests);
+        assert_eq!(results.len(), 3000);
+        for (i, result) in results.iter().enumerate() {
+            match i {
+                5 | 1500 => assert!(result.is_err()),
+                2000 => assert!(result.as_ref().unwrap().is_empty()),
+                _ => assert_eq!(result.as_ref().unwrap(), &(i as u64).to_ne_bytes()),
+            }
+        }
+
+        // A range running into an inaccessible page fails on its own
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let page = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(page, libc::MAP_FAILED);
+        // SAFETY: the second page is mapped
+        unsafe { libc::mprotect(page.byte_add(page_size), page_size, libc::PROT_NONE) };
+
+        let results = memory.read_many(&[
+            (address, 8),
+            (page as u64 + page_size as u64 - 4, 8),
+            (address + 8, 8),
+        ]);
+        assert_eq!(results[0].as_ref().unwrap(), &0u64.to_ne_bytes());
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &1u64.to_ne_bytes());
+
+        // Overlapping and adjacent ranges are read together, the others apart
+        let requests = [
+            (address + 16, 8),
+            (address, 12),
+            (address + 8, 8),
+            (address + 40, 4),
+            (address + 4, 4),
+            (address + 64, 0),
+        ];
+        assert_eq!(
+            vm::coalesce(&requests),
+            [(address, 24), (address + 40, 4), (address + 64, 0)]
+        );
+        let results = memory.read_many(&requests);
+        let bytes: Vec<u8> = values[..9]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        for ((address, len), result) in requests.iter().zip(&results) {
+            let offset = (address - values.as_ptr() as u64) as usize;
+            assert_eq!(result.as_ref().unwrap(), &bytes[offset..offset + len]);
+        }
+
+        // The ranges merged with one running into an inaccessible page are read apart
+        let end = page as u64 + page_size as u64;
+        assert_eq!(vm::coalesce(&[(end - 8, 8), (end - 4, 8)]), [(end - 8, 12)]);
+        let results = memory.read_many(&[(end - 8, 8), (end - 4, 8), (end - 16, 8)]);
+        assert_eq!(results[0].as_ref().unwrap(), &[0; 8]);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &[0; 8]);
+
+        // SAFETY: the pages are no longer used
+        unsafe { libc::munmap(page, 2 * page_size) };
+    }
+
+    #[cfg(feature = "io_uring")]
+    #[test]
+    fn test_uring_mem() {
+        use libinspector::introspection::uring::UringMem;
+
+        let value: Vec<u8> = (0..3 << 20).map(|i| (i % 251) as u8).collect();
+        let address = value.as_ptr() as u64;
+        let memory = UringMem::with_depth(std::process::id(), 4).unwrap();
+
+        assert_eq!(memory.read_bytes(address, value.len()).unwrap(), value);
+        assert_eq!(
+            memory.read_bytes(address + 1000, 10).unwrap(),
+            value[1000..1010]
+        );
+
+        let mut dump = Vec::new();
+        memory
+            .copy_to(address, value.len() as u64, &mut dump)
+            .unwrap();
+        assert_eq!(dump, value);
+
+        let (mut first, mut second) = ([0; 4], [0; 4]);
+        memory
+            .read_vectored(&mut [(address, &mut first), (address + 300, &mut second)])
+            .unwrap();
+        assert_eq!((first, second), ([0, 1, 2, 3], [49, 50, 51, 52]));
+        assert!(memory.read(8, &mut [0; 8]).is_err());
+        assert!(memory
+            .read_vectored(&mut [(address, &mut first), (0, &mut second)])
+            .is_err());
+
+        // A failure among reads in flight leaves none behind for the next calls
+        let mut large = vec![0; value.len()];
+        assert!(memory
+            .read_vectored(&mut [(0, &mut first), (address, &mut large)])
+            .is_err());
+        drop(large);
+        assert_eq!(memory.read_bytes(address, value.len()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_cached_reader() {
+        let page_size = page_size() as usize;
+        let mut values = vec![0u8; 4 * page_size];
+        let address = values.as_ptr() as u64;
+        let memory = CachedReader::new(ProcessVm::new(std::process::id()));
+
+        values[10] = 1;
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [1]);
+        let cached = memory.len();
+        assert!(cached >= 1);
+
+        // Changes are not seen until the page is invalidated
+        values[10] = 2;
+        std::hint::black_box(&mut values);
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [1]);
+        memory.invalidate(address + 10..address + 11);
+        assert_eq!(memory.len(), cached - 1);
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [2]);
+
+        // Reads spanning several pages, partially cached
+        values[2 * page_size] = 3;
+        std::hint::black_box(&mut values);
+        let read = memory.read_bytes(address, 3 * page_size).unwrap();
+        assert_eq!(read, values[..3 * page_size]);
+
+        // Writes go through and invalidate
+        memory.write(address + 10, &[4]).unwrap();
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [4]);
+        assert!(memory.read(8, &mut [0; 8]).is_err());
+
+        memory.clear();
+        assert!(memory.is_empty());
+
+        // Expired pages are read again
+        let memory = CachedReader::new(ProcessVm::new(std::process::id()))
+            .with_ttl(std::time::Duration::ZERO);
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [4]);
+        values[10] = 5;
+        std::hint::black_box(&mut values);
+        assert_eq!(memory.read_bytes(address + 10, 1).unwrap(), [5]);
+
+        // The capacity bounds the number of pages, even for larger reads
+        let memory = CachedReader::new(ProcessVm::new(std::process::id())).with_capacity(2);
+        let read = memory.read_bytes(address, 4 * page_size).unwrap();
+        assert_eq!(read, values);
+        assert_eq!(memory.len(), 2);
+    }
+
+    #[test]
+    fn test_read_stream() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: the pages are mapped writable, the second one is then made inaccessible
+        unsafe {
+            std::ptr::write_bytes(address.cast::<u8>(), 0xaa, 4 * page_size);
+            libc::mprotect(address.byte_add(page_size), page_size, libc::PROT_NONE);
+        }
+        let start = address as u64;
+        let range = start..start + 4 * page_size as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        let chunks: Vec<Chunk> = memory
+            .read_stream(range.clone(), HolePolicy::Skip)
+            .with_chunk_size(3 * page_size)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        let ranges: Vec<_> = chunks.iter().map(Chunk::range).collect();
+        let page = page_size as u64;
+        assert_eq!(
+            ranges,
+            [start..start + page, start + 2 * page..start + 4 * page]
+        );
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.data.iter().all(|&b| b == 0xaa)));
+
+        let mut dump = Vec::new();
+        std::io::copy(
+            &mut memory
+                .read_stream(range.clone(), HolePolicy::ZeroFill)
+                .with_chunk_size(page_size / 2 + 1),
+            &mut dump,
+        )
+        .unwrap();
+        assert_eq!(dump.len(), 4 * page_size);
+        assert!(dump[page_size..2 * page_size].iter().all(|&b| b == 0));
+        assert!(dump[2 * page_size..].iter().all(|&b| b == 0xaa));
+
+        let mut stream = memory.read_stream(range, HolePolicy::Fail);
+        assert_eq!(stream.policy(), HolePolicy::Fail);
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+
+        // SAFETY: the pages are no longer used
+        unsafe { libc::munmap(address, 4 * page_size) };
+    }
+
+    #[test]
+    fn test_read_uninit() {
+        let value: Vec<u8> = (0..64).collect();
+        let address = value.as_ptr() as u64;
+
+        let mut buffer = [std::mem::MaybeUninit::<u8>::uninit(); 16];
+        let memory = ProcessVm::new(std::process::id());
+        let read = memory.read_uninit(address + 8, &mut buffer).unwrap();
+        assert_eq!(read, &value[8..24]);
+
+        // Backends without a dedicated implementation, and through references
+        let mem = ProcMem::open(std::process::id()).unwrap();
+        let reader: &dyn MemoryReader = &mem;
+        let read = reader.read_uninit(address, &mut buffer[..4]).unwrap();
+        assert_eq!(read, [0, 1, 2, 3]);
+        assert!(memory.read_uninit(8, &mut buffer).is_err());
+
+        // Reused buffers do not allocate per read
+        let mut buffer = Vec::with_capacity(32);
+        for offset in [0, 16, 32] {
+            let read = memory
+                .read_uninit(address + offset, &mut buffer.spare_capacity_mut()[..32])
+                .unwrap();
+            assert_eq!(read, &value[offset as usize..offset as usize + 32]);
+        }
+
+        // Implementations returning less than the buffer are refused, not trusted
+        struct Short;
+        impl MemoryReader for Short {
+            fn read(&self, _: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+                buffer.fill(0);
+                Ok(())
+            }
+
+            fn read_uninit<'b>(
+                &self,
+                _: u64,
+                buffer: &'b mut [std::mem::MaybeUninit<u8>],
+            ) -> anyhow::Result<&'b mut [u8]> {
+                buffer[0].write(0);
+                // SAFETY: the first byte was just initialized
+                Ok(unsafe { &mut *(&mut buffer[..1] as *mut _ as *mut [u8]) })
+            }
+        }
+        assert!(Short.read_bytes(0, 8).is_err());
+        assert!(Short.read_vec::<u32>(0, 2).is_err());
+        assert_eq!(Short.read_bytes(0, 1).unwrap(), [0]);
+    }
+
+    #[test]
+    fn test_read_exact_partial() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                4 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: the pages are mapped writable, the middle ones are then made inaccessible
+        unsafe {
+            std::ptr::write_bytes(address.cast::<u8>(), 0xaa, 4 * page_size);
+            libc::mprotect(address.byte_add(page_size), 2 * page_size, libc::PROT_NONE);
+        }
+        let start = address as u64;
+        let page = page_size as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        let gap = start + page..start + 3 * page;
+        let mut buffer = vec![0xff; 3 * page_size];
+        let partial = memory.read_partial(start + page / 2, &mut buffer);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.read, page_size);
+        assert_eq!(partial.gaps.as_slice(), std::slice::from_ref(&gap));
+        assert!(buffer[..page_size / 2].iter().all(|&b| b == 0xaa));
+        assert!(buffer[page_size / 2..page_size * 5 / 2]
+            .iter()
+            .all(|&b| b == 0));
+        assert!(buffer[page_size * 5 / 2..].iter().all(|&b| b == 0xaa));
+
+        let error = memory
+            .read_exact(start, &mut vec![0; 4 * page_size])
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UnreadableMemory>().unwrap().gaps,
+            std::slice::from_ref(&gap)
+        );
+
+        let mut buffer = [0; 8];
+        memory.read_exact(start, &mut buffer).unwrap();
+        assert_eq!(buffer, [0xaa; 8]);
+        assert_eq!(
+            memory.read_partial(start, &mut buffer),
+            PartialRead {
+                read: 8,
+                gaps: Vec::new()
+            }
+        );
+
+        // SAFETY: the pages are no longer used
+        unsafe { libc::munmap(address, 4 * page_size) };
+    }
+
+    /// Builds a minimal static i386 executable calling `pause` in a loop.
+    fn elf32_pause() -> Vec<u8> {
+        const BASE: u32 = 0x0804_8000;
+        // mov eax, 29 (pause); int 0x80; jmp back to mov
+        let code = [0xb8, 0x1d, 0, 0, 0, 0xcd, 0x80, 0xeb, 0xf7];
+        let size = (52 + 32 + code.len()) as u32;
+
+        let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+        elf.resize(16, 0);
+        for half in [2u16, 3] {
+            elf.extend(half.to_le_bytes()); // e_type EXEC, e_machine EM_386
+        }
+        for word in [1u32, BASE + 84, 52, 0, 0] {
+            elf.extend(word.to_le_bytes()); // e_version, e_entry, e_phoff, e_shoff, e_flags
+        }
+        for half in [52u16, 32, 1, 0, 0, 0] {
+            elf.extend(half.to_le_bytes()); // e_ehsize, e_phentsize, e_phnum, e_sh*
+        }
+        for word in [1u32, 0, BASE, BASE, size, size, 5, 0x1000] {
+            elf.extend(word.to_le_bytes()); // PT_LOAD of the whole file, R+X
+        }
+        elf.extend(code);
+        elf
+    }
+
+    #[test]
+    fn test_bitness() {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(
+            Bitness::from_pid(std::process::id()).unwrap(),
+            Bitness::NATIVE
+        );
+        assert_eq!(
+            Bitness::from_elf_ident(b"\x7fELF\x01").unwrap(),
+            Bitness::Bits32
+        );
+        assert!(Bitness::from_elf_ident(b"\x7fELF\x03").is_err());
+        assert!(Bitness::from_elf_ident(b"MZ\x90\x00\x03").is_err());
+        assert_eq!(Bitness::Bits32.format_address(0x804a000), "0x0804a000");
+        assert_eq!(
+            Bitness::Bits64.format_address(0x7f3a1c000000),
+            "0x00007f3a1c000000"
+        );
+
+        let bytes: Vec<u8> = [AT_PAGESZ as u32, 4096, AT_ENTRY as u32, 0x8048054, 0, 0]
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect();
+        let auxv = AuxVec::parse_with(&bytes, Bitness::Bits32).unwrap();
+        assert_eq!(auxv.entry(), Some(0x8048054));
+        assert!(AuxVec::parse_with(&bytes[..20], Bitness::Bits32).is_err());
+
+        let pointers = [0x0804_8000u32, 0xffff_fff0, 0];
+        let memory = ProcessVm::new(std::process::id());
+        let address = pointers.as_ptr() as u64;
+        assert_eq!(
+            memory.read_pointer(address + 4, Bitness::Bits32).unwrap(),
+            0xffff_fff0
+        );
+        assert_eq!(
+            memory.read_pointers(address, 3, Bitness::Bits32).unwrap(),
+            [0x0804_8000, 0xffff_fff0, 0]
+        );
+        assert!(memory.read_pointer(address + 4, Bitness::Bits64).is_err());
+
+        // A real 32-bit process, where the kernel supports them
+        let path = std::env::temp_dir().join(format!("libinspector-elf32-{}", std::process::id()));
+        std::fs::write(&path, elf32_pause()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // Another test forking while the file was open for writing makes exec fail with ETXTBSY
+        let child = (0..50).find_map(|_| match std::process::Command::new(&path).spawn() {
+            Err(e) if e.raw_os_error() == Some(libc::ETXTBSY) => {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                None
+            }
+            result => Some(result),
+        });
+        std::fs::remove_file(&path).unwrap();
+        let Some(Ok(mut child)) = child else {
+            return;
+        };
+
+        let process = Process::from_pid(child.id()).unwrap();
+        assert_eq!(process.bitness().unwrap(), Bitness::Bits32);
+        let auxv = process.auxv().unwrap();
+        assert_eq!(auxv.entry(), Some(0x0804_8054));
+        assert_eq!(auxv.page_size(), Some(4096));
+        assert_eq!(process.arch().unwrap(), Arch::X86);
+
+        ptrace_attach(child.id() as libc::pid_t);
+        let registers = Registers::get(child.id(), Arch::X86);
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let registers = registers.unwrap();
+        assert!((0x0804_8054..0x0804_8054 + 9).contains(&registers.pc()));
+        assert!(registers.sp() <= u32::MAX as u64);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            // SAFETY: user_regs_struct is plain integers
+            let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+            regs.rip = 0x0804_8054;
+            regs.rax = 29;
+            let regs = Regs32::from(&regs);
+            assert_eq!((regs.eip, regs.eax, regs.esp), (0x0804_8054, 29, 0));
+        }
+    }
+
+    /// Attaches to `pid` and waits for it to stop.
+    fn ptrace_attach(pid: libc::pid_t) {
+        // SAFETY: PTRACE_ATTACH takes no pointer, `status` is a valid pointer
+        unsafe {
+            assert_eq!(libc::ptrace(libc::PTRACE_ATTACH, pid, 0, 0), 0);
+            let mut status = 0;
+            assert_eq!(libc::waitpid(pid, &mut status, libc::__WALL), pid);
+            assert!(libc::WIFSTOPPED(status));
+        }
+    }
+
+    #[test]
+    fn test_arch_registers() {
+        let mut header = b"\x7fELF\x02\x01\x01".to_vec();
+        header.resize(18, 0);
+        header.extend(183u16.to_le_bytes());
+        assert_eq!(Arch::from_elf_header(&header).unwrap(), Arch::Aarch64);
+        assert!(Arch::from_elf_header(&header[..19]).is_err());
+        header[18] = 40; // EM_ARM
+        assert!(Arch::from_elf_header(&header).is_err());
+        assert_eq!(Arch::from_elf_header(&elf32_pause()).unwrap(), Arch::X86);
+        assert_eq!(
+            Process::from_pid(std::process::id())
+                .unwrap()
+                .arch()
+                .unwrap(),
+            Arch::NATIVE
+        );
+        assert_eq!(Arch::X86.bitness(), Bitness::Bits32);
+        assert_eq!(Arch::Aarch64.breakpoint().len(), 4);
+
+        let mut registers = Registers::X86(Regs32::default());
+        registers.set_syscall(192, [0, 4096, 7, 0x22, u64::MAX, 0]);
+        assert_eq!(registers.syscall_args()[4], u32::MAX as u64);
+        registers.set_return_value(-12i64 as u64);
+        assert_eq!(registers.return_value() as i64, -12);
+
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        ptrace_attach(pid);
+
+        let process = Process::from_pid(pid as u32).unwrap();
+        let registers = Registers::get(pid as u32, Arch::NATIVE);
+        let wrong_arch = Registers::get(pid as u32, Arch::X86);
+        let mut moved = registers.as_ref().ok().copied();
+        if let Some(moved) = &mut moved {
+            moved.set_sp(moved.sp() - 64);
+            moved.set(pid as u32).unwrap();
+        }
+        let read_back = Registers::get(pid as u32, Arch::NATIVE);
+
+        // SAFETY: the child is not used after being killed
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+
+        let registers = registers.unwrap();
+        assert_eq!(registers.arch(), Arch::NATIVE);
+        // The forked thread runs on the stack of a test thread, an anonymous mapping
+        assert!(process
+            .find_segment(registers.sp())
+            .unwrap()
+            .permissions()
+            .is_writable());
+        assert!(process
+            .find_segment(registers.pc())
+            .unwrap()
+            .permissions()
+            .is_executable());
+        assert!(wrong_arch.is_err());
+        assert_eq!(read_back.unwrap().sp(), registers.sp() - 64);
+    }
+
+    #[test]
+    fn test_verified_writer() {
+        /// Backend whose writes are silently lost
+        struct LostWrites(ProcessVm);
+
+        impl MemoryReader for LostWrites {
+            fn read(&self, address: u64, buffer: &mut [u8]) -> anyhow::Result<()> {
+                self.0.read(address, buffer)
+            }
+        }
+
+        impl MemoryWriter for LostWrites {
+            fn write(&self, _address: u64, _data: &[u8]) -> anyhow::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value = vec![1u8, 2, 3, 4];
+        let address = value.as_ptr() as u64;
+        let memory = ProcessVm::new(std::process::id());
+
+        let verified = VerifiedWriter::new(memory);
+        verified.write(address + 1, &[7, 8]).unwrap();
+        assert_eq!(verified.read_bytes(address, 4).unwrap(), [1, 7, 8, 4]);
+        assert!(verified.write(8, &[0]).is_err());
+
+        let verified = VerifiedWriter::new(LostWrites(memory));
+        let error = verified.write(address, &[1, 9, 9]).unwrap_err();
+        let error = error.downcast_ref::<WriteVerificationError>().unwrap();
+        assert_eq!(error.address, address);
+        assert_eq!(error.observed, [1, 7, 8]);
+        assert_eq!(error.first_mismatch(), Some(1));
+        drop(value);
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let values = vec![0u64; 4];
+        let address = values.as_ptr() as u64;
+
+        let mem = ProcMem::open(std::process::id()).unwrap();
+        mem.write_atomic_u64(address, u64::MAX).unwrap();
+        mem.write_atomic_u32(address + 12, 0x1234_5678).unwrap();
+        assert!(mem.write_atomic_u64(address + 4, 1).is_err());
+        assert!(mem.write_atomic_u32(address + 2, 1).is_err());
+        let values = std::hint::black_box(values);
+        assert_eq!(values[0], u64::MAX);
+        assert_eq!(values[1], (0x1234_5678u64) << 32);
+
+        // ptrace stores words in a child, the parent cannot trace itself
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let memory = PtraceMem::new(pid as u32);
+        let u64_write = memory.write_atomic_u64(address + 16, 0xdead_beef_0000_0001);
+        let u32_write = memory.write_atomic_u32(address + 4, 7);
+        let words = memory.read_vec::<u64>(address, 3);
+        // SAFETY: the child is not used after being killed
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+
+        u64_write.unwrap();
+        // Half of a word cannot be stored alone
+        assert!(u32_write.is_err());
+        assert_eq!(
+            words.unwrap(),
+            [u64::MAX, 0x1234_5678 << 32, 0xdead_beef_0000_0001]
+        );
+        assert_eq!(values[2], 0);
+    }
+
+    #[test]
+    fn test_fallback_writer() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        let page = address as u64;
+        let value = vec![0u8; 4];
+        let process = Process::from_pid(std::process::id()).unwrap();
+
+        let writer = process.fallback_writer(FallbackPolicy::Never);
+        assert_eq!(
+            writer.write_with(value.as_ptr() as u64, &[1]).unwrap(),
+            WriteBackend::ProcessVm
+        );
+        assert!(writer.write(page, &[1]).is_err());
+
+        let writer = FallbackWriter::new(std::process::id(), FallbackPolicy::ProcMem);
+        assert_eq!(writer.policy(), FallbackPolicy::ProcMem);
+        assert_eq!(
+            writer.write_with(page, &[2]).unwrap(),
+            WriteBackend::ProcMem
+        );
+        assert_eq!(writer.read_bytes(page, 2).unwrap(), [2, 0]);
+        assert!(writer.write(0, &[1]).is_err());
+        // SAFETY: the page is mapped readable
+        assert_eq!(unsafe { *(address as *const u8) }, 2);
+        assert_eq!(std::hint::black_box(value)[0], 1);
+
+        // SAFETY: the page is no longer used
+        unsafe { libc::munmap(address, page_size) };
+    }
+
+    #[test]
+    fn test_fallback_writer_traced() {
+        let value: Vec<u8> = (0..16).collect();
+        let address = value.as_ptr() as u64;
+
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let pid = pid as u32;
+
+        let session = PtraceSession::attach(pid).unwrap();
+        let held = FallbackWriter::new(pid, FallbackPolicy::ProcMemThenPtrace)
+            .with_ptrace(session.memory())
+            .write(address, &[0xaa; 4]);
+        // A target traced by the calling thread is not attached to again
+        let detected = FallbackWriter::new(pid, FallbackPolicy::ProcMemThenPtrace)
+            .write(address + 4, &[0xbb; 4]);
+        let stopped = session.is_stopped();
+        let read = session.memory().read_bytes(address, 8);
+        drop(session);
+        unsafe {
+            libc::kill(pid as i32, libc::SIGKILL);
+            libc::waitpid(pid as i32, std::ptr::null_mut(), 0);
+        }
+
+        held.unwrap();
+        detected.unwrap();
+        assert!(stopped);
+        assert_eq!(
+            read.unwrap(),
+            [0xaa, 0xaa, 0xaa, 0xaa, 0xbb, 0xbb, 0xbb, 0xbb]
+        );
+        assert_eq!(value[0], 0);
+    }
+
+    #[test]
+    fn test_write_policy() {
+        let value = vec![1u8, 2, 3, 4];
+        let address = value.as_ptr() as u64;
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let page = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(page, libc::MAP_FAILED);
+
+        // SAFETY: the child only performs async-signal-safe system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let pid = pid as u32;
+        let memory = ProcessVm::new(pid);
+
+        let records: std::sync::Arc<std::sync::Mutex<Vec<WriteRecord>>> = Default::default();
+        let log = records.clone();
+        WritePolicy::new()
+            .dry_run(true)
+            .for_pid(pid)
+            .with_logger(move |write| {
+                log.lock().unwrap().push(WriteRecord {
+                    backtrace: None,
+                    old: write.old.clone(),
+                    new: write.new.clone(),
+                    ..*write
+                })
+            })
+            .install();
+        assert!(WritePolicy::installed().unwrap().is_dry_run());
+
+        let suppressed = memory.write(address + 1, &[9, 9]);
+        let after_dry_run = memory.read_bytes(address, 4);
+        let writer = FallbackWriter::new(pid, FallbackPolicy::ProcMem);
+        let suppressed_fallback = writer.write_with(page as u64, &[8]);
+        // Writes to other processes are not affected
+        ProcessVm::new(std::process::id())
+            .write(address, &[5])
+            .unwrap();
+
+        let log = records.clone();
+        WritePolicy::new()
+            .for_pid(pid)
+            .with_backtraces(true)
+            .with_logger(move |write| {
+                assert!(write.backtrace.is_some());
+                log.lock().unwrap().push(WriteRecord {
+                    backtrace: None,
+                    old: write.old.clone(),
+                    new: write.new.clone(),
+                    ..*write
+                })
+            })
+            .install();
+        let applied = memory.write(address + 1, &[7]);
+        let after_write = memory.read_bytes(address, 4);
+        // Logged once, with the backend that succeeded
+        let applied_fallback = writer.write_with(page as u64, &[6]);
+        let after_fallback = writer.read_bytes(page as u64, 1);
+        WritePolicy::uninstall();
+
+        // SAFETY: the child is not used after being killed
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            libc::waitpid(pid as libc::pid_t, std::ptr::null_mut(), 0);
+        }
+
+        // SAFETY: the page is no longer used
+        unsafe { libc::munmap(page, page_size) };
+
+        suppressed.unwrap();
+        applied.unwrap();
+        assert_eq!(suppressed_fallback.unwrap(), WriteBackend::ProcessVm);
+        assert_eq!(applied_fallback.unwrap(), WriteBackend::ProcMem);
+        assert_eq!(after_fallback.unwrap(), [6]);
+        assert_eq!(after_dry_run.unwrap(), [1, 2, 3, 4]);
+        assert_eq!(after_write.unwrap(), [1, 7, 3, 4]);
+        assert_eq!(std::hint::black_box(&value)[0], 5);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            (records[0].pid, records[0].address, records[0].suppressed),
+            (pid, address + 1, true)
+        );
+        assert_eq!(records[0].old.as_deref(), Some(&[2, 3][..]));
+        assert_eq!(records[0].new, [9, 9]);
+        assert_eq!(records[0].backend, "process_vm_writev");
+        assert_eq!(
+            (records[1].address, records[1].suppressed),
+            (page as u64, true)
+        );
+        assert_eq!(records[1].backend, "process_vm_writev");
+        assert!(!records[2].suppressed);
+        assert_eq!(records[2].new, [7]);
+        assert_eq!(
+            (records[3].address, records[3].suppressed),
+            (page as u64, false)
+        );
+        assert_eq!(records[3].old.as_deref(), Some(&[0][..]));
+        assert_eq!(records[3].backend, WriteBackend::ProcMem.name());
+    }
+
+    #[test]
+    fn test_scanner() {
+        let mut values: Box<[u64]> = vec![0; 16].into_boxed_slice();
+        values[3] = 0x5ca1_ab1e_0ddb_a110;
+        let address = values.as_ptr() as u64;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let in_buffer = |segment: &Segment| segment.contains(address);
+
+        let results = process
+            .scanner()
+            .filter_segments(in_buffer)
+            .scan(&ScanValue::U64(values[3]))
+            .unwrap();
+        assert_eq!(results.value_type(), ValueType::U64);
+        assert!(results.contains(address + 24));
+        let (index, _) = results
+            .iter()
+            .enumerate()
+            .find(|(_, (found, _))| *found == address + 24)
+            .unwrap();
+        assert_eq!(results.value(index), Some(ScanValue::U64(values[3])));
+
+        // Floats and byte strings, across chunk boundaries and at any alignment
+        values[5] = u64::from(1.5f32.to_bits()) << 32;
+        let bytes = [0xde, 0xad, 0xbe, 0xef, 0x42];
+        // SAFETY: the bytes are within the buffer
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), (address + 61) as *mut u8, bytes.len())
+        };
+        let scanner = process
+            .scanner()
+            .filter_segments(in_buffer)
+            .with_chunk_size(4099);
+        assert!(scanner
+            .scan(&ScanValue::from(1.5f32))
+            .unwrap()
+            .contains(address + 44));
+        let results = scanner.scan(&ScanValue::from(&bytes[..])).unwrap();
+        assert_eq!(results.value_type(), ValueType::Bytes(5));
+        assert!(results.contains(address + 61));
+        assert!(!scanner
+            .with_alignment(2)
+            .scan(&ScanValue::from(&bytes[..]))
+            .unwrap()
+            .contains(address + 61));
+
+        // Pages becoming unreadable after the segments were read are skipped
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                3 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED);
+        let start = pages as u64;
+        // SAFETY: the pages are mapped and writable
+        unsafe { *(pages.byte_add(2 * page_size + 8) as *mut i32) = -123_456_789 };
+        let process = Process::from_pid(std::process::id()).unwrap();
+        // SAFETY: the second page is mapped
+        unsafe { libc::mprotect(pages.byte_add(page_size), page_size, libc::PROT_NONE) };
+
+        let results = Scanner::new(ProcessVm::new(std::process::id()), process.segments())
+            .filter_segments(|segment| segment.contains(start))
+            .scan(&ScanValue::I32(-123_456_789))
+            .unwrap();
+        // SAFETY: mapped above
+        unsafe { libc::munmap(pages, 3 * page_size) };
+        assert!(results.contains(start + 2 * page_size as u64 + 8));
+        let hole = start + page_size as u64..start + 2 * page_size as u64;
+        assert!(!results.addresses().iter().any(|found| hole.contains(found)));
+    }
+
+    #[test]
+    fn test_scan_refine() {
+        let mut values: Box<[i32]> = vec![7_654_321; 4].into_boxed_slice();
+        let address = values.as_ptr() as u64;
+        let at = |index: u64| address + index * 4;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let memory = process.memory();
+        let mut results = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .scan(&ScanValue::I32(7_654_321))
+            .unwrap();
+        assert!((0..4).all(|index| results.contains(at(index))));
+
+        values[1] += 10;
+        values[2] -= 1;
+        values[3] += 3;
+        results.refine(&memory, &Predicate::Changed).unwrap();
+        assert!(!results.contains(at(0)));
+        assert!((1..4).all(|index| results.contains(at(index))));
+
+        // Values are compared to the ones read at the previous refinement
+        values[1] += 1;
+        values[2] -= 1;
+        values[3] += 1;
+        results.refine(&memory, &Predicate::Increased).unwrap();
+        assert!(!results.contains(at(2)));
+        assert!(results.contains(at(1)) && results.contains(at(3)));
+
+        values[1] += 5;
+        values[3] += 6;
+        results
+            .refine(&memory, &Predicate::IncreasedBy(ScanValue::I32(5)))
+            .unwrap();
+        assert!(results.contains(at(1)) && !results.contains(at(3)));
+        let index = results.addresses().binary_search(&at(1)).unwrap();
+        assert_eq!(results.value(index), Some(ScanValue::I32(7_654_337)));
+
+        results
+            .refine(&memory, &Predicate::Equal(ScanValue::I32(1)))
+            .unwrap();
+        assert!(!results.contains(at(1)));
+
+        // Undoing restores the candidates and their values before each refinement
+        assert_eq!(results.undo_len(), 4);
+        assert!(results.undo());
+        assert!(results.contains(at(1)));
+        assert!(results.undo());
+        assert!(results.contains(at(3)));
+        values[3] -= 10;
+        results.refine(&memory, &Predicate::Decreased).unwrap();
+        assert!(results.contains(at(3)) && !results.contains(at(1)));
+
+        let mut results = results.with_undo_depth(1);
+        assert_eq!(results.undo_len(), 1);
+        assert!(results.undo());
+        assert!(!results.undo());
+
+        // Predicates must match the type of the values
+        assert!(results
+            .refine(&memory, &Predicate::Equal(ScanValue::U64(1)))
+            .is_err());
+        let mut bytes = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .scan(&ScanValue::from(&[1u8, 2, 3][..]))
+            .unwrap();
+        assert!(bytes.refine(&memory, &Predicate::Increased).is_err());
+        assert!(bytes.refine(&memory, &Predicate::Unchanged).is_ok());
+    }
+
+    #[test]
+    fn test_scan_snapshot() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED);
+        // SAFETY: the pages are mapped and writable
+        let memory = unsafe { std::slice::from_raw_parts_mut(pages as *mut u8, 2 * page_size) };
+        let start = pages as u64;
+        memory[..8].copy_from_slice(&1000u32.to_ne_bytes().repeat(2));
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(start))
+            .with_chunk_size(page_size);
+        let snapshot: Snapshot = scanner.snapshot().unwrap();
+        assert!(snapshot
+            .ranges()
+            .iter()
+            .any(|range| range.contains(&start) && range.end >= start + 2 * page_size as u64));
+        assert!(snapshot.size() >= 2 * page_size as u64);
+
+        // The snapshot keeps reading the memory as it was
+        memory[..4].copy_from_slice(&1010u32.to_ne_bytes());
+        memory[page_size] = 0xff;
+        let mut value = [0; 4];
+        snapshot.read(start, &mut value).unwrap();
+        assert_eq!(u32::from_ne_bytes(value), 1000);
+        assert!(snapshot.read(8, &mut value).is_err());
+
+        let changed = scanner
+            .scan_snapshot(&snapshot, ValueType::U32, &Predicate::Changed)
+            .unwrap();
+        assert!(changed.contains(start) && !changed.contains(start + 4));
+        let index = changed.addresses().binary_search(&start).unwrap();
+        assert_eq!(changed.value(index), Some(ScanValue::U32(1010)));
+
+        let mut increased = scanner
+            .scan_snapshot(
+                &snapshot,
+                ValueType::U32,
+                &Predicate::IncreasedBy(ScanValue::U32(10)),
+            )
+            .unwrap();
+        assert!(increased.contains(start));
+        // The results are refined like the ones of a known value
+        memory[..4].copy_from_slice(&1005u32.to_ne_bytes());
+        increased
+            .refine(scanner.reader(), &Predicate::Decreased)
+            .unwrap();
+        assert!(increased.contains(start));
+
+        // Values across the boundary of two chunks are compared too
+        let bytes = scanner
+            .with_alignment(1)
+            .scan_snapshot(&snapshot, ValueType::Bytes(4), &Predicate::Changed)
+            .unwrap();
+        let boundary = start + page_size as u64;
+        assert!((boundary - 3..=boundary).all(|address| bytes.contains(address)));
+        assert!(!bytes.contains(boundary - 4) && !bytes.contains(boundary + 1));
+
+        // SAFETY: mapped above
+        unsafe { libc::munmap(pages, 2 * page_size) };
+    }
+
+    #[test]
+    fn test_scan_pattern() {
+        let pattern: Pattern = "48 8B ?? ? 89 0? **".parse().unwrap();
+        assert_eq!(pattern.len(), 7);
+        assert_eq!(pattern.to_string(), "48 8B ?? ?? 89 0? ??");
+        assert_eq!(pattern.masks(), &[0xff, 0xff, 0, 0, 0xff, 0xf0, 0]);
+        assert!(pattern.matches(&[0x48, 0x8b, 1, 2, 0x89, 0x05, 3, 4]));
+        assert!(!pattern.matches(&[0x48, 0x8b, 1, 2, 0x89, 0x15, 3]));
+        assert!(!pattern.matches(&[0x48, 0x8b]));
+        assert!("48 8G".parse::<Pattern>().is_err());
+        assert!("488B".parse::<Pattern>().is_err());
+        assert!("".parse::<Pattern>().is_err());
+
+        let code = Pattern::from_code(b"\xaa\x00\xaa", "x?x").unwrap();
+        assert_eq!(code.to_string(), "AA ?? AA");
+        assert_eq!(
+            code.find_iter(&[0xaa, 1, 0xaa, 2, 0xaa, 0xaa])
+                .collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert!(Pattern::from_code(b"\xaa", "xx").is_err());
+
+        // Finds the code of a function from a signature with wildcards
+        let function = test_scan_pattern as *const () as u64;
+        let memory = ProcessVm::new(std::process::id());
+        let mut bytes = [0; 16];
+        memory.read(function, &mut bytes).unwrap();
+        let mut masks = [0xff; 16];
+        masks[4..8].fill(0);
+        let signature = Pattern::new(&bytes, &masks).unwrap();
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let results = process
+            .scanner()
+            .filter_segments(|segment| segment.permissions().is_executable())
+            .scan_pattern(&signature)
+            .unwrap();
+        assert!(results.contains(function));
+        let index = results.addresses().binary_search(&function).unwrap();
+        assert_eq!(results.value_bytes(index), Some(&bytes[..]));
+    }
+
+    #[test]
+    fn test_scan_kernels() {
+        // Few distinct bytes, so values and patterns occur often and overlap
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let buffer: Vec<u8> = (0..1 << 16)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % 3) as u8
+            })
+            .collect();
+        let address = buffer.as_ptr() as u64;
+        let range = address..address + buffer.len() as u64;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = |alignment: usize| {
+            process
+                .scanner()
+                .filter_segments(|segment| segment.contains(address))
+                .with_chunk_size(4099)
+                .with_alignment(alignment)
+        };
+        let found = |results: &[u64], len: usize| -> Vec<u64> {
+            results
+                .iter()
+                .copied()
+                .filter(|found| range.contains(found) && found + len as u64 <= range.end)
+                .collect()
+        };
+        let expected = |alignment: usize, matches: &dyn Fn(&[u8]) -> bool, len: usize| {
+            (0..=buffer.len() - len)
+                .filter(|offset| (address + *offset as u64).is_multiple_of(alignment as u64))
+                .filter(|offset| matches(&buffer[*offset..*offset + len]))
+                .map(|offset| address + offset as u64)
+                .collect::<Vec<u64>>()
+        };
+
+        let values = [
+            ScanValue::U8(2),
+            ScanValue::U16(0x0101),
+            ScanValue::U32(0x0002_0100),
+            ScanValue::U64(0x0001_0200_0102_0001),
+            ScanValue::from(&[1u8, 1, 2][..]),
+        ];
+        for value in &values {
+            let bytes = value.to_bytes();
+            for alignment in [1, 2, 4, 8] {
+                let results = scanner(alignment).scan(value).unwrap();
+                assert_eq!(
+                    found(results.addresses(), bytes.len()),
+                    expected(alignment, &|data| data == bytes, bytes.len()),
+                    "{value:?} aligned on {alignment}"
+                );
+            }
+        }
+
+        let pattern: Pattern = "01 ?? 02 02 ?1".parse().unwrap();
+        for alignment in [1, 4] {
+            let results = scanner(alignment).scan_pattern(&pattern).unwrap();
+            assert_eq!(
+                found(results.addresses(), pattern.len()),
+                expected(alignment, &|data| pattern.matches(data), pattern.len()),
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_progress() {
+        let buffer = vec![7u8; 1 << 16];
+        let address = buffer.as_ptr() as u64;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .with_chunk_size(1 << 12)
+            .with_progress({
+                let reports = reports.clone();
+                move |progress| {
+                    assert!(progress.segment.unwrap().contains(address));
+                    reports.lock().unwrap().push((
+                        progress.scanned,
+                        progress.total,
+                        progress.fraction(),
+                    ))
+                }
+            });
+        let total: u64 = scanner
+            .segments()
+            .iter()
+            .map(|segment| segment.size())
+            .sum();
+
+        scanner.scan(&ScanValue::U32(0x0707_0707)).unwrap();
+        let reports = std::mem::take(&mut *reports.lock().unwrap());
+        assert!(reports.len() >= 16);
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(reports.iter().all(|report| report.1 == total));
+        assert_eq!(reports.last().unwrap().0, total);
+        assert_eq!(reports.last().unwrap().2, 1.0);
+
+        // Cancelling stops the scan at the next chunk
+        let token = CancellationToken::new();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .with_chunk_size(1 << 12)
+            .with_cancellation(token.clone())
+            .with_progress({
+                let token = token.clone();
+                move |_| token.cancel()
+            });
+        let error = scanner.scan(&ScanValue::U8(7)).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ScanCancelled>(),
+            Some(&ScanCancelled { scanned: 1 << 12 })
+        );
+        assert!(token.is_cancelled());
+        assert!(scanner.snapshot().is_err());
+    }
+
+    #[test]
+    fn test_scan_session() {
+        let values: Box<[u32]> = vec![0x1357_9bdf; 8].into_boxed_slice();
+        let address = values.as_ptr() as u64;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let memory = process.memory();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address));
+        let results = scanner.scan(&ScanValue::U32(0x1357_9bdf)).unwrap();
+        let segment = scanner.segments()[0].clone();
+
+        let mut session = ScanSession::new()
+            .with_results(results)
+            .with_snapshot(scanner.snapshot().unwrap());
+        session.refine(&memory, Predicate::Unchanged).unwrap();
+        session.push_predicate(Predicate::IncreasedBy(ScanValue::U32(2)));
+        session.push_predicate(Predicate::NearlyEqual {
+            value: ScanValue::F64(0.5),
+            epsilon: 1e-3,
+        });
+        session.push_predicate(Predicate::Between(ScanValue::U32(1), ScanValue::U32(9)));
+        session.push_predicate(Predicate::Masked {
+            value: ScanValue::U32(1),
+            mask: ScanValue::U32(3),
+        });
+        session.push_predicate(Predicate::PointsInto(vec![0x1000..0x3000, 0x8000..0x9000]));
+        assert!(ScanSession::new()
+            .refine(&memory, Predicate::Changed)
+            .is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("libinspector-session-{}", std::process::id()));
+        session.save(&path).unwrap();
+        let loaded = ScanSession::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.predicates(), session.predicates());
+        let (results, restored) = (session.results().unwrap(), loaded.results().unwrap());
+        assert_eq!(restored.value_type(), ValueType::U32);
+        assert_eq!(restored.addresses(), results.addresses());
+        assert!((0..8).all(|index| restored.contains(address + index * 4)));
+        assert!(restored.iter().eq(results.iter()));
+        assert_eq!(loaded.snapshot(), session.snapshot());
+        assert!(ScanSession::read_from(&mut &b"LISCAN\0\0\x07"[..]).is_err());
+        assert!(ScanSession::read_from(&mut &b"not a session"[..]).is_err());
+
+        // Addresses in file-backed mappings follow them when the target is mapped elsewhere
+        let line = |start: u64| {
+            format!(
+                "{start:x}-{:x} rw-p 00001000 08:01 1234 /opt/game/libgame.so",
+                start + segment.size()
+            )
+        };
+        let before: Segment = line(segment.start()).parse().unwrap();
+        let after: Segment = line(segment.start() + 0x1000_0000).parse().unwrap();
+        let mut moved = ScanSession::new()
+            .with_results(loaded.results().unwrap().clone())
+            .with_snapshot(loaded.snapshot().unwrap().clone())
+            .with_segments(std::slice::from_ref(&before));
+        assert_eq!(moved.relocate(&[]), 0);
+        assert_eq!(moved.results().unwrap().addresses(), results.addresses());
+        assert_eq!(moved.relocate(std::slice::from_ref(&after)), 1);
+        assert!(moved.results().unwrap().contains(address + 0x1000_0000));
+        let mut value = [0; 4];
+        moved
+            .snapshot()
+            .unwrap()
+            .read(address + 0x1000_0000, &mut value)
+            .unwrap();
+        assert_eq!(u32::from_ne_bytes(value), 0x1357_9bdf);
+    }
+
+    #[test]
+    fn test_freezer() {
+        let mut values: Box<[u32; 2]> = Box::new([10, 20]);
+        let address = values.as_mut_ptr();
+        let read = |index: usize| {
+            // SAFETY: the values live until the end of the test, written by the freezer thread
+            unsafe { address.add(index).read_volatile() }
+        };
+        let write = |index: usize, value: u32| {
+            // SAFETY: see above
+            unsafe { address.add(index).write_volatile(value) }
+        };
+        let wait_for = |condition: &dyn Fn() -> bool| {
+            let start = std::time::Instant::now();
+            while !condition() {
+                assert!(start.elapsed() < std::time::Duration::from_secs(5));
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        };
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let freezer = process.freezer();
+        freezer.set_interval(std::time::Duration::from_millis(5));
+        let health = freezer.freeze(address as u64, 100u32);
+        let mana = freezer.freeze(address as u64 + 4, 200u32);
+        wait_for(&|| read(0) == 100 && read(1) == 200);
+
+        // Values changed by the target are restored
+        write(0, 1);
+        wait_for(&|| read(0) == 100);
+
+        freezer.set_enabled(mana, false).unwrap();
+        freezer.set_value(health, 150u32).unwrap();
+        wait_for(&|| read(0) == 150);
+        write(1, 5);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(read(1), 5);
+        assert!(!freezer.get(mana).unwrap().enabled);
+
+        let entries = freezer.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].value, ScanValue::U32(150));
+        assert!(entries[0].error.is_none());
+        assert_eq!(freezer.unfreeze(health).unwrap().id, health);
+        assert!(freezer.unfreeze(health).is_none());
+        assert!(freezer.set_enabled(health, true).is_err());
+        drop(freezer);
+        write(0, 7);
+        assert_eq!(read(0), 7);
+
+        // Unwritable addresses report their error
+        let freezer = Freezer::new(std::process::id(), ProcessVm::new(std::process::id()));
+        let invalid = freezer.freeze(8, 1u8);
+        wait_for(&|| freezer.get(invalid).unwrap().error.is_some());
+
+        // The freezer pauses once the target exits
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let freezer = Freezer::new(child.id(), ProcessVm::new(child.id()));
+        freezer.set_interval(std::time::Duration::from_millis(5));
+        assert!(!freezer.is_paused());
+        child.kill().unwrap();
+        child.wait().unwrap();
+        wait_for(&|| freezer.is_paused());
+        drop(values);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_scan_regex() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED);
+        // SAFETY: the pages are mapped and writable
+        let memory = unsafe { std::slice::from_raw_parts_mut(pages as *mut u8, 2 * page_size) };
+        let start = pages as u64;
+        let first = b"url=https://first.example/index;";
+        memory[100..100 + first.len()].copy_from_slice(first);
+        // Across the boundary of the two chunks
+        let second = b"url=https://second.example/page_2;";
+        let offset = page_size - 10;
+        memory[offset..offset + second.len()].copy_from_slice(second);
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(start))
+            .with_chunk_size(page_size);
+        let regex = regex::bytes::Regex::new(r"https://([a-z.]+\.example)/(\w+)(\?\w+)?").unwrap();
+        let matches = scanner.scan_regex(&regex).unwrap();
+        let ours: Vec<_> = matches
+            .iter()
+            .filter(|found| (start..start + 2 * page_size as u64).contains(&found.range.start))
+            .collect();
+        assert_eq!(ours.len(), 2);
+
+        assert_eq!(ours[0].data, b"https://first.example/index");
+        assert_eq!(ours[0].range.start, start + 104);
+        assert_eq!(ours[0].groups.len(), 4);
+        assert_eq!(ours[0].groups[1], Some(start + 112..start + 125));
+        assert_eq!(ours[0].groups[3], None);
+
+        let base = start + offset as u64;
+        assert_eq!(ours[1].data, b"https://second.example/page_2");
+        assert_eq!(ours[1].range, base + 4..base + 33);
+        assert_eq!(ours[1].groups[2], Some(base + 27..base + 33));
+
+        // Longer matches are cut at the boundary of the chunks
+        let matches = scanner.scan_regex_with_max_len(&regex, 8).unwrap();
+        assert!(!matches.iter().any(|found| found.data == second[4..33]));
+
+        // SAFETY: mapped above
+        unsafe { libc::munmap(pages, 2 * page_size) };
+    }
+
+    #[test]
+    fn test_scan_strings() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size * 2,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: the range was just mapped and is zeroed
+        let memory = unsafe { std::slice::from_raw_parts_mut(address as *mut u8, page_size * 2) };
+        memory[16..29].copy_from_slice(b"hello strings");
+        memory[64..77].copy_from_slice("h\u{e9}llo w\u{f6}rld".as_bytes());
+        for (index, c) in "wide t\u{e9}xt".chars().enumerate() {
+            memory[128 + index * 2] = c as u8;
+        }
+        memory[256..259].copy_from_slice(b"abc");
+        memory[300..313].copy_from_slice(b"hello strings");
+        // Across the two chunks read
+        memory[page_size - 6..page_size + 6].copy_from_slice(b"page boundar");
+
+        let base = address as u64;
+        let range = base..base + page_size as u64 * 2;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(base))
+            .with_chunk_size(page_size);
+        let strings = |options: StringOptions| {
+            scanner
+                .strings(&options)
+                .unwrap()
+                .into_iter()
+                .filter(|found| range.contains(&found.address))
+                .map(|found| (found.address - base, found.encoding, found.text))
+                .collect::<Vec<_>>()
+        };
+
+        let offset = page_size as u64 - 6;
+        assert_eq!(
+            strings(StringOptions::new()),
+            vec![
+                (16, StringEncoding::Ascii, "hello strings".to_string()),
+                (
+                    64,
+                    StringEncoding::Utf8,
+                    "h\u{e9}llo w\u{f6}rld".to_string()
+                ),
+                (128, StringEncoding::Utf16Le, "wide t\u{e9}xt".to_string()),
+                (300, StringEncoding::Ascii, "hello strings".to_string()),
+                (offset, StringEncoding::Ascii, "page boundar".to_string()),
+            ]
+        );
+        assert_eq!(
+            strings(StringOptions::new().with_min_len(3).deduplicated(true)),
+            vec![
+                (16, StringEncoding::Ascii, "hello strings".to_string()),
+                (
+                    64,
+                    StringEncoding::Utf8,
+                    "h\u{e9}llo w\u{f6}rld".to_string()
+                ),
+                (128, StringEncoding::Utf16Le, "wide t\u{e9}xt".to_string()),
+                (256, StringEncoding::Ascii, "abc".to_string()),
+                (offset, StringEncoding::Ascii, "page boundar".to_string()),
+            ]
+        );
+        // Without ASCII, UTF-8 strings include pure ASCII ones
+        let utf8 = strings(StringOptions::new().with_encodings(&[StringEncoding::Utf8]));
+        assert_eq!(utf8.len(), 4);
+        assert!(utf8.iter().all(|found| found.1 == StringEncoding::Utf8));
+        assert_eq!(utf8[3].2, "page boundar");
+
+        // SAFETY: the range is no longer used
+        unsafe { libc::munmap(address, page_size * 2) };
+    }
+
+    #[test]
+    fn test_locator() {
+        let maps = "\
+55d0c0000000-55d0c0001000 r--p 00000000 fd:01 42 /usr/bin/game
+55d0c0001000-55d0c0003000 r-xp 00001000 fd:01 42 /usr/bin/game
+55d0c0003000-55d0c0004000 rw-p 00003000 fd:01 42 /usr/bin/game
+55d0c0004000-55d0c0006000 rw-p 00000000 00:00 0
+55d0c1000000-55d0c1021000 rw-p 00000000 00:00 0 [heap]
+7f0000000000-7f0000001000 rw-p 00000000 00:00 0
+7f0000001000-7f0000002000 r--p 00000000 fd:01 7 /usr/lib/libfoo.so
+7f0000002000-7f0000003000 r-xp 00001000 fd:01 7 /usr/lib/libfoo.so
+7ffc00000000-7ffc00021000 rw-p 00000000 00:00 0 [stack]";
+        let segments: Vec<Segment> = maps.lines().map(|line| line.parse().unwrap()).collect();
+        let locator = Locator::new(segments);
+
+        let locate = |address| locator.locate(address).unwrap().to_string();
+        assert_eq!(locate(0x55d0c0001a2b), "game+0x1a2b");
+        // The BSS belongs to the executable
+        assert_eq!(locate(0x55d0c0004010), "game+0x4010");
+        assert_eq!(locate(0x55d0c1000450), "[heap]+0x450");
+        assert_eq!(locate(0x7f0000002004), "libfoo.so+0x1004");
+        assert_eq!(locate(0x7f0000000010), "0x7f0000000000+0x10");
+        assert_eq!(locate(0x7ffc00000008), "[stack]+0x8");
+        assert!(locator.locate(0x1000).is_none());
+
+        let annotation = locator.annotate(0x7f0000002004).unwrap();
+        assert_eq!(annotation.segment_type, Some(SegmentType::Code));
+        assert!(annotation.permissions.is_executable());
+        assert_eq!(
+            annotation.to_string(),
+            "0x7f0000002004 libfoo.so+0x1004 (r-xp)"
+        );
+
+        // Locations survive a relocation of the mappings
+        let moved: Vec<Segment> = maps
+            .replace("55d0c", "5600a")
+            .replace("7f000", "7f123")
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        let moved = Locator::new(moved);
+        for (location, address) in [
+            ("game+0x1a2b", 0x5600a0001a2b),
+            ("/usr/bin/game+0x4010", 0x5600a0004010),
+            ("[heap]+0x450", 0x5600a1000450),
+            ("libfoo.so+0x1004", 0x7f1230002004),
+            ("0x7f1230000000+0x10", 0x7f1230000010),
+        ] {
+            let location: Location = location.parse().unwrap();
+            assert_eq!(moved.resolve(&location), Some(address));
+        }
+        assert_eq!(
+            "[anon:a+b]+0x10".parse::<Location>().unwrap().base,
+            LocationBase::Special(SegmentType::Anonymous("a+b".to_string()))
+        );
+        assert!(moved.resolve(&"libbar.so+0x10".parse().unwrap()).is_none());
+        assert!(moved
+            .resolve(&"/lib/libfoo.so+0x10".parse().unwrap())
+            .is_none());
+        assert!("game".parse::<Location>().is_err());
+        assert!("game+0xzz".parse::<Location>().is_err());
+        assert!("+0x10".parse::<Location>().is_err());
+
+        // Annotates the results of a scan of the current process
+        static MARKER: std::sync::atomic::AtomicU64 =
+            std::sync::atomic::AtomicU64::new(0x5eed_1e55_c0ff_ee42);
+        let address = MARKER.as_ptr() as u64;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let results = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .scan(&ScanValue::U64(
+                MARKER.load(std::sync::atomic::Ordering::Relaxed),
+            ))
+            .unwrap();
+        let locator = process.locator();
+        let index = results.addresses().binary_search(&address).unwrap();
+        let annotation = results.annotate(&locator).nth(index).unwrap().unwrap();
+        let executable = std::env::current_exe().unwrap();
+        assert_eq!(annotation.location.base, LocationBase::Module(executable));
+        assert_eq!(locator.resolve(&annotation.location), Some(address));
+    }
+
+    #[test]
+    fn test_scan_spilled() {
+        let mut buffer = vec![0u32; 4096];
+        for value in buffer.iter_mut().step_by(3) {
+            *value = 0x5b1d_f00d;
+        }
+        let address = buffer.as_ptr() as u64;
+        let range = address..address + buffer.len() as u64 * 4;
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(address))
+            .with_chunk_size(1000);
+        let value = ScanValue::U32(0x5b1d_f00d);
+        let expected: Vec<(u64, ScanValue)> = scanner
+            .scan(&value)
+            .unwrap()
+            .iter()
+            .filter(|(address, _)| range.contains(address))
+            .collect();
+        assert_eq!(expected.len(), 1366);
+
+        // Streams the results to a file with a few in memory
+        let mut spilled = SpilledResults::new(ValueType::U32).with_memory_limit(100);
+        scanner
+            .scan_each(&value, |address, value| match range.contains(&address) {
+                true => spilled.push(address, value),
+                false => Ok(()),
+            })
+            .unwrap();
+        assert_eq!(spilled.len(), 1366);
+        assert_eq!(spilled.spilled(), 1300);
+        let streamed = spilled
+            .iter()
+            .unwrap()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(streamed, expected);
+
+        // Pushes after reading back are appended
+        spilled.push(1, &7u32.to_ne_bytes()).unwrap();
+        assert_eq!(spilled.len(), 1367);
+        assert!(spilled.push(1, &[0; 8]).is_err());
+        let results = spilled.into_results().unwrap();
+        assert_eq!(results.addresses()[0], 1);
+        assert_eq!(results.value(0), Some(ScanValue::U32(7)));
+        assert_eq!(results.iter().skip(1).collect::<Vec<_>>(), expected);
+
+        // Errors of the callback stop the scan
+        let mut found = Vec::new();
+        let error = scanner
+            .scan_each(&value, |address, _| {
+                if range.contains(&address) {
+                    found.push(address);
+                }
+                if found.len() == 3 {
+                    anyhow::bail!("Enough");
+                }
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Enough");
+        assert_eq!(found, vec![address, address + 12, address + 24]);
+
+        // Through a bounded channel, as an iterator
+        let (sender, receiver) = std::sync::mpsc::sync_channel(16);
+        let streamed = std::thread::scope(|scope| {
+            scope.spawn(move || scanner.scan_each(&value, |address, _| Ok(sender.send(address)?)));
+            receiver
+                .iter()
+                .filter(|address| range.contains(address))
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(streamed.len(), 1366);
+        assert_eq!(streamed[1], address + 12);
+        drop(buffer);
+    }
+
+    #[test]
+    fn test_batch_scan() {
+        let marker = "30.0271828";
+        let mut children: Vec<_> = (0..2)
+            .map(|_| {
+                std::process::Command::new("sleep")
+                    .arg(marker)
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        let mut targets: Vec<u32> = children.iter().map(|child| child.id()).collect();
+        // Waits for the children to run `sleep`
+        for pid in &targets {
+            while Process::from_pid(*pid).unwrap().name() != "sleep" {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        assert!(pids().unwrap().contains(&targets[1]));
+
+        // The current process and the missing one are left out
+        targets.push(std::process::id());
+        targets.push(u32::MAX);
+        let scan = BatchScan::new(ScanValue::from(marker.as_bytes()))
+            .with_name("sleep")
+            .with_segment_filter(|segment| segment.segment_type() == Some(&SegmentType::Stack))
+            .with_threads(2);
+        let scanned = scan.run_pids(&targets);
+        assert_eq!(
+            scanned
+                .iter()
+                .map(|scanned| scanned.pid)
+                .collect::<Vec<_>>(),
+            targets[..2]
+        );
+        for scanned in &scanned {
+            assert_eq!(scanned.name, "sleep");
+            let results = scanned.results.as_ref().unwrap();
+            assert!(!results.is_empty());
+            let argv = Process::from_pid(scanned.pid).unwrap().arg_start();
+            assert!(results.addresses().iter().any(|address| *address > argv));
+        }
+
+        let pattern: Pattern = "73 6C 65 65 70 00".parse().unwrap();
+        let scanned = BatchScan::new(pattern)
+            .with_filter(move |process| process.parent_id() == std::process::id())
+            .run()
+            .unwrap();
+        for pid in &targets[..2] {
+            let scanned = scanned.iter().find(|scanned| scanned.pid == *pid).unwrap();
+            assert!(!scanned.results.as_ref().unwrap().is_empty());
+        }
+
+        for child in &mut children {
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pointer_map() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED);
+        let start = pages as u64;
+        let boxed = Box::new(0x1234u64);
+        let heap = &*boxed as *const u64 as u64;
+        // SAFETY: the pages are mapped and writable
+        let words = unsafe { std::slice::from_raw_parts_mut(pages as *mut u64, 2 * page_size / 8) };
+        words[0] = start + 0x100;
+        words[1] = heap;
+        words[2] = 0x10;
+        words[3] = start + 0x100;
+        // SAFETY: the bytes are in the first page
+        unsafe { ((pages as *mut u8).add(33) as *mut u64).write_unaligned(heap) };
+        // Pointers in read-only memory are left out
+        words[page_size / 8] = heap;
+        // SAFETY: the second page is mapped
+        let protected = unsafe {
+            libc::mprotect(
+                (pages as *mut u8).add(page_size) as *mut libc::c_void,
+                page_size,
+                libc::PROT_READ,
+            )
+        };
+        assert_eq!(protected, 0);
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(start) || segment.contains(heap))
+            .with_chunk_size(1000);
+        let map = scanner.pointer_map(Bitness::Bits64).unwrap();
+        assert_eq!(map.bitness(), Bitness::Bits64);
+        let range = start..start + 2 * page_size as u64;
+        let located = |target| {
+            map.pointers_to(target)
+                .filter(|location| range.contains(location))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(located(start + 0x100), vec![start, start + 24]);
+        assert_eq!(located(heap), vec![start + 8]);
+        assert!(map.iter().all(|pointer| pointer.target != 0x10));
+        assert!(map.pointers_into(start..start + 0x101).contains(&Pointer {
+            location: start + 24,
+            target: start + 0x100
+        }));
+        assert!(map.pointers_into(start + 0x101..start + 0x100).is_empty());
+        assert!(map.iter().is_sorted_by_key(|pointer| pointer.target));
+
+        // Unaligned pointers are found once asked for
+        let unaligned = scanner
+            .with_alignment(1)
+            .pointer_map(Bitness::Bits64)
+            .unwrap();
+        assert!(unaligned
+            .pointers_to(heap)
+            .any(|location| location == start + 33));
+        // Other threads may change the heap between the scans, so only our pages are compared
+        let count = |map: &PointerMap| {
+            map.iter()
+                .filter(|pointer| range.contains(&pointer.location))
+                .count()
+        };
+        assert!(count(&unaligned) > count(&map));
+
+        // SAFETY: the range is no longer used
+        unsafe { libc::munmap(pages, 2 * page_size) };
+        drop(boxed);
+    }
+
+    #[test]
+    fn test_pointer_search() {
+        // ROOT -> first + 0x18 -> second + 0x40
+        static ROOT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let mut first = Box::new([0u64; 8]);
+        let second = Box::new([0u64; 16]);
+        let target = &second[8] as *const u64 as u64;
+        first[3] = second.as_ptr() as u64;
+        ROOT.store(first.as_ptr() as u64, std::sync::atomic::Ordering::SeqCst);
+        let root = ROOT.as_ptr() as u64;
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let executable = std::env::current_exe().unwrap();
+        // The end of .bss may be in an anonymous mapping after the ones of the executable
+        let scanner = process.scanner().filter_segments(|segment| {
+            segment.path() == Some(executable.as_path())
+                || segment.contains(root)
+                || segment.contains(first.as_ptr() as u64)
+                || segment.contains(target)
+        });
+        let map = scanner.pointer_map(Bitness::NATIVE).unwrap();
+        let locator = process.locator();
+
+        let paths = PointerSearch::new(&map, &locator)
+            .with_max_depth(3)
+            .with_max_offset(0x100)
+            .find(target);
+        let path = paths
+            .iter()
+            .find(|path| locator.resolve(&path.base) == Some(root))
+            .unwrap();
+        assert_eq!(path.offsets, vec![0x18, 0x40]);
+        let memory = ProcessVm::new(std::process::id());
+        assert_eq!(
+            path.resolve(&memory, &locator, Bitness::NATIVE).unwrap(),
+            target
+        );
+
+        // Too short or too small offsets miss it
+        let short = PointerSearch::new(&map, &locator)
+            .with_max_depth(1)
+            .find(target);
+        assert!(!short.contains(path));
+        let small = PointerSearch::new(&map, &locator)
+            .with_max_offset(0x20)
+            .find(target);
+        assert!(!small.contains(path));
+        assert!(
+            PointerSearch::new(&map, &locator)
+                .with_max_results(1)
+                .find(target)
+                .len()
+                <= 1
+        );
+
+        // Paths still leading to the target rank first
+        let check = |target| PathCheck {
+            reader: &memory,
+            locator: &locator,
+            bitness: Bitness::NATIVE,
+            target,
+        };
+        let ranked = rank_paths(paths.clone(), &[check(target), check(target)]);
+        assert_eq!(ranked.len(), paths.len());
+        assert_eq!(ranked[0].matches, 2);
+        assert!(ranked
+            .iter()
+            .any(|ranked| ranked.path == *path && ranked.matches == 2));
+        first[3] = 0;
+        let ranked = rank_paths(vec![path.clone()], &[check(target)]);
+        assert_eq!(ranked[0].matches, 0);
+        let error = path
+            .resolve(&memory, &locator, Bitness::NATIVE)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BrokenPointerPath>(),
+            Some(&BrokenPointerPath {
+                hop: 1,
+                address: first.as_ptr() as u64 + 0x18,
+                reason: BreakReason::Null,
+            })
+        );
+
+        // Paths round-trip through their text form
+        let text = path.to_string();
+        assert!(text.ends_with(" -> 0x18 -> 0x40"));
+        let parsed: PointerPath = text.parse().unwrap();
+        assert_eq!(locator.resolve(&parsed.base), Some(root));
+        assert_eq!(parsed.offsets, path.offsets);
+        drop(second);
+    }
+
+    #[test]
+    fn test_pointer_path() {
+        let path: PointerPath = "libgame.so+0x12A0 -> 0x18 -> -0x8 -> 40".parse().unwrap();
+        assert_eq!(path.base.to_string(), "libgame.so+0x12a0");
+        assert_eq!(path.offsets, vec![0x18, -8, 0x40]);
+        assert_eq!(
+            path.to_string(),
+            "libgame.so+0x12a0 -> 0x18 -> -0x8 -> 0x40"
+        );
+        let path: PointerPath = "[heap]".parse().unwrap();
+        assert_eq!(path.base.offset, 0);
+        assert!(path.offsets.is_empty());
+        assert!("".parse::<PointerPath>().is_err());
+        assert!("game+0x10 -> 0xzz".parse::<PointerPath>().is_err());
+        assert!("game+0x10 -> ".parse::<PointerPath>().is_err());
+        assert!("game+0x10 -> 0x8000000000000000"
+            .parse::<PointerPath>()
+            .is_err());
+
+        // A chain in an anonymous mapping, then broken in each way
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let pages = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(pages, libc::MAP_FAILED);
+        let start = pages as u64;
+        // SAFETY: the pages are mapped and writable
+        let words = unsafe { std::slice::from_raw_parts_mut(pages as *mut u64, 2 * page_size / 8) };
+        words[0] = start + 0x100;
+        words[0x110 / 8] = start + 0x200;
+
+        let memory = ProcessVm::new(std::process::id());
+        let resolve = |text: &str| {
+            let locator = Locator::from_pid(std::process::id()).unwrap();
+            let path: PointerPath = text.parse().unwrap();
+            path.resolve(&memory, &locator, Bitness::NATIVE)
+        };
+        let broken = |text: &str| {
+            resolve(text)
+                .unwrap_err()
+                .downcast_ref::<BrokenPointerPath>()
+                .copied()
+                .unwrap()
+        };
+        // Mappings next to the pages may be merged with them
+        let base = Locator::from_pid(std::process::id())
+            .unwrap()
+            .locate(start)
+            .unwrap();
+        let path = |offsets: &str| format!("{base}{offsets}");
+        assert_eq!(resolve(&path(" -> 0x10 -> 0x8")).unwrap(), start + 0x208);
+        assert_eq!(resolve(&path(" -> -0x10")).unwrap(), start + 0xf0);
+
+        let null = broken(&path(" -> 0x18 -> 0x8"));
+        assert_eq!(null.hop, 1);
+        assert_eq!(null.address, start + 0x118);
+        assert_eq!(null.reason, BreakReason::Null);
+
+        words[0x110 / 8] = 0x10;
+        let unmapped = broken(&path(" -> 0x10 -> 0x8"));
+        assert_eq!((unmapped.hop, unmapped.address), (2, 0x18));
+        assert_eq!(unmapped.reason, BreakReason::Unmapped);
+        let unmapped = broken(&path(" -> 0x10 -> 0x8 -> 0x0"));
+        assert_eq!((unmapped.hop, unmapped.address), (2, 0x18));
+
+        words[0x110 / 8] = start + page_size as u64;
+        // SAFETY: the second page is mapped
+        let protected = unsafe {
+            libc::mprotect(
+                (pages as *mut u8).add(page_size) as *mut libc::c_void,
+                page_size,
+                libc::PROT_NONE,
+            )
+        };
+        assert_eq!(protected, 0);
+        let unreadable = broken(&path(" -> 0x10 -> 0x0 -> 0x0"));
+        assert_eq!(unreadable.hop, 2);
+        assert_eq!(unreadable.address, start + page_size as u64);
+        assert_eq!(unreadable.reason, BreakReason::Unreadable);
+        assert!(unreadable.to_string().contains("unreadable pointer"));
+
+        // SAFETY: the range is no longer used
+        unsafe { libc::munmap(pages, 2 * page_size) };
+    }
+
+    #[test]
+    fn test_struct_analyzer() {
+        #[repr(C)]
+        struct Sample {
+            vtable: usize,
+            name: *const u8,
+            health: f32,
+            level: i32,
+            speed: f64,
+            tag: [u8; 16],
+            next: *const u64,
+            callback: fn(),
+            global: *const u64,
+            count: u64,
+            junk: u64,
+            padding: u64,
+        }
+        static GLOBAL: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(7);
+        static NAME: &[u8] = b"player one\0";
+
+        // Trait objects are a pointer to the data then to the vtable
+        let object: &dyn std::fmt::Debug = &GLOBAL;
+        // SAFETY: a reference to a trait object is two pointers
+        let [_, vtable]: [usize; 2] = unsafe { std::mem::transmute(object) };
+        let next = Box::new(0u64);
+        let sample = Box::new(Sample {
+            vtable,
+            name: NAME.as_ptr(),
+            health: 100.0,
+            level: 3,
+            speed: 2.5,
+            tag: *b"KnightOfTheRound",
+            next: &*next,
+            callback: test_struct_analyzer,
+            global: GLOBAL.as_ptr(),
+            count: 42,
+            junk: 0x7f3a_91c4_5e2b_d6a7,
+            padding: 0,
+        });
+        let address = &*sample as *const Sample as u64;
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let memory = process.memory();
+        let locator = process.locator();
+        let analyzer = StructAnalyzer::new(&memory, &locator, Bitness::NATIVE);
+        let layout = analyzer
+            .analyze(address, std::mem::size_of::<Sample>() - 4)
+            .unwrap();
+        assert_eq!(layout.address, address);
+        let kinds: Vec<(usize, usize, &FieldKind)> = layout
+            .fields
+            .iter()
+            .map(|field| (field.offset, field.size, &field.kind))
+            .collect();
+        assert_eq!(kinds.len(), 12);
+        assert!(matches!(kinds[0], (0, 8, FieldKind::VtablePointer(_))));
+        assert!(
+            matches!(kinds[1], (8, 8, FieldKind::StringPointer(_, text)) if text == "player one")
+        );
+        assert_eq!(kinds[2], (16, 4, &FieldKind::Float(100.0)));
+        assert_eq!(kinds[3], (20, 4, &FieldKind::Int(3)));
+        assert_eq!(kinds[4], (24, 8, &FieldKind::Double(2.5)));
+        assert_eq!(
+            kinds[5],
+            (32, 16, &FieldKind::Text("KnightOfTheRound".to_string()))
+        );
+        assert!(matches!(kinds[6], (48, 8, FieldKind::HeapPointer(_))));
+        assert!(matches!(kinds[7], (56, 8, FieldKind::CodePointer(_))));
+        let executable = std::env::current_exe().unwrap();
+        assert!(
+            matches!(kinds[8], (64, 8, FieldKind::ModulePointer(location))
+            if location.base == LocationBase::Module(executable.clone()))
+        );
+        assert_eq!(kinds[9], (72, 8, &FieldKind::Int(42)));
+        assert_eq!(
+            kinds[10],
+            (80, 8, &FieldKind::Unknown(0x7f3a_91c4_5e2b_d6a7))
+        );
+        assert_eq!(kinds[11], (88, 8, &FieldKind::Zero));
+
+        let text = layout.to_string();
+        assert!(text.starts_with(&format!("struct struct_{address:x} {{\n")));
+        assert!(text.contains("    void *vtable_0; // +0x0 -> "));
+        assert!(text.contains("    float field_10; // +0x10 100\n"));
+        assert!(text.contains("    char field_20[16]; // +0x20 \"KnightOfTheRound\"\n"));
+        assert!(text.ends_with("};"));
+
+        let layout = process.analyze_struct(address, 8).unwrap();
+        assert!(matches!(layout.fields[..], [_]));
+        assert!(analyzer.analyze(8, 8).is_err());
+        drop(next);
+    }
+
+    #[test]
+    fn test_scan_rich_predicates() {
+        let page_size = page_size() as usize;
+        // SAFETY: anonymous private mapping, unmapped below
+        let page = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(page, libc::MAP_FAILED);
+        // SAFETY: the page is mapped and writable
+        let memory = unsafe { std::slice::from_raw_parts_mut(page as *mut u8, page_size) };
+        let start = page as u64;
+        let in_page = |address: &u64| (start..start + page_size as u64).contains(address);
+        let heap = Box::new(0u64);
+        for (index, health) in [99.9f32, 250.0, 100.2].iter().enumerate() {
+            memory[index * 4..index * 4 + 4].copy_from_slice(&health.to_ne_bytes());
+        }
+        memory[16..24].copy_from_slice(&[150u32, 201].map(u32::to_ne_bytes).concat());
+        memory[32..36].copy_from_slice(&0x8000_00a1u32.to_ne_bytes());
+        memory[48..56].copy_from_slice(&(&*heap as *const u64 as u64).to_ne_bytes());
+
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let scanner = process
+            .scanner()
+            .filter_segments(|segment| segment.contains(start));
+        let snapshot = scanner.snapshot().unwrap();
+        let found = |value_type, predicate: &Predicate| -> Vec<u64> {
+            let results = scanner
+                .scan_snapshot(&snapshot, value_type, predicate)
+                .unwrap();
+            results
+                .addresses()
+                .iter()
+                .copied()
+                .filter(in_page)
+                .collect()
+        };
+
+        let nearly = Predicate::NearlyEqual {
+            value: ScanValue::F32(100.0),
+            epsilon: 0.25,
+        };
+        assert_eq!(found(ValueType::F32, &nearly), [start, start + 8]);
+        let between = Predicate::Between(ScanValue::U32(100), ScanValue::U32(200));
+        assert_eq!(found(ValueType::U32, &between), [start + 16]);
+        let masked = Predicate::Masked {
+            value: ScanValue::U32(0x8000_0001),
+            mask: ScanValue::U32(0x8000_0009),
+        };
+        assert_eq!(found(ValueType::U32, &masked), [start + 32]);
+        let heap_address = &*heap as *const u64 as u64;
+        let points_into =
+            Predicate::points_into(process.segments(), |segment| segment.contains(heap_address));
+        assert!(found(ValueType::U64, &points_into).contains(&(start + 48)));
+
+        // The results are refined with the same predicates
+        let mut results = scanner.scan(&ScanValue::U32(150)).unwrap();
+        memory[16..20].copy_from_slice(&180u32.to_ne_bytes());
+        results.refine(scanner.reader(), &between).unwrap();
+        assert!(results.contains(start + 16));
+        memory[16..20].copy_from_slice(&250u32.to_ne_bytes());
+        results.refine(scanner.reader(), &between).unwrap();
+        assert!(!results.contains(start + 16));
+
+        // Predicates must fit the type of the values
+        assert!(results.refine(scanner.reader(), &nearly).is_err());
+        assert!(results.refine(scanner.reader(), &points_into).is_ok());
+        let empty = Predicate::Between(ScanValue::U32(2), ScanValue::U32(1));
+        assert!(results.refine(scanner.reader(), &empty).is_err());
+        assert!(scanner
+            .scan_snapshot(&snapshot, ValueType::U16, &points_into)
+            .is_err());
+
+        // SAFETY: mapped above
+        unsafe { libc::munmap(page, page_size) };
+    }
+
+    #[test]
+    fn test_ptrace_session() {
+        let tracer_pid = |pid: u32| {
+            let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap();
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("TracerPid:"))
+                .map(|value| value.trim().parse::<u32>().unwrap())
+                .unwrap()
+        };
+        let state = |pid: u32| Process::from_pid(pid).unwrap().state();
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        let mut session = Process::from_pid(pid).unwrap().attach().unwrap();
+        assert_eq!(session.tid(), pid);
+        assert!(session.is_stopped());
+        assert_eq!(state(pid), ProcessState::Tracing);
+        // The tracer is the thread which attached
+        assert_eq!(tracer_pid(pid), unsafe { libc::gettid() } as u32);
+        assert!(session.set_options(libc::PTRACE_O_TRACESYSGOOD).is_ok());
+        let code = Process::from_pid(pid).unwrap().startcode();
+        assert!(session.memory().read_bytes(code, 4).is_ok());
+
+        // Dropping a running session stops the thread again to detach
+        session.cont(0).unwrap();
+        assert!(!session.is_stopped());
+        assert!(session.set_options(0).is_err());
+        assert_eq!(session.try_wait().unwrap(), None);
+        drop(session);
+        assert_eq!(tracer_pid(pid), 0);
+        assert_ne!(state(pid), ProcessState::Tracing);
+
+        // The thread is resumed even when the tracer panics
+        let panicked = std::panic::catch_unwind(|| {
+            let _session = PtraceSession::attach(pid).unwrap();
+            panic!("while attached");
+        });
+        assert!(panicked.is_err());
+        assert_eq!(tracer_pid(pid), 0);
+        assert_ne!(state(pid), ProcessState::Stopped);
+
+        let mut session = PtraceSession::attach(pid).unwrap();
+        session.cont(0).unwrap();
+        child.kill().unwrap();
+        assert_eq!(session.wait().unwrap(), Stop::Killed(libc::SIGKILL));
+        assert!(session.cont(0).is_err());
+        session.detach().unwrap();
+        // The session reaped the child, being both its tracer and its parent
+        assert!(child.wait().is_err());
+        assert!(PtraceSession::attach(pid).is_err());
+    }
+
+    #[test]
+    fn test_ptrace_seize() {
+        let state = |pid: u32| Process::from_pid(pid).unwrap().state();
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        // The thread keeps running until interrupted
+        let mut session = Process::from_pid(pid)
+            .unwrap()
+            .seize(libc::PTRACE_O_TRACESYSGOOD)
+            .unwrap();
+        assert_eq!(session.mode(), AttachMode::Seize);
+        assert!(!session.is_stopped());
+        assert_ne!(state(pid), ProcessState::Tracing);
+        assert_eq!(session.interrupt().unwrap(), Stop::Interrupted);
+        assert!(session.is_stopped());
+        assert_eq!(state(pid), ProcessState::Tracing);
+        assert!(session.interrupt().is_err());
+        session.cont(0).unwrap();
+
+        // Group-stops are reported, and the thread stays stopped while listening
+        // SAFETY: kill takes no pointer
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGSTOP) };
+        assert_eq!(session.wait().unwrap(), Stop::Signal(libc::SIGSTOP));
+        session.cont(libc::SIGSTOP).unwrap();
+        assert_eq!(session.wait().unwrap(), Stop::GroupStop(libc::SIGSTOP));
+        session.listen().unwrap();
+        // SAFETY: kill takes no pointer
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGCONT) };
+        assert!(!session.wait().unwrap().is_exit());
+
+        // Dropping the session interrupts the thread to detach
+        session.cont(0).unwrap();
+        drop(session);
+        assert_ne!(state(pid), ProcessState::Tracing);
+        assert!(PtraceSession::attach(pid).unwrap().interrupt().is_err());
+        assert!(PtraceSession::attach(pid).unwrap().listen().is_err());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    /// Forks a child running `threads` threads which wait for signals, and waits for them to
+    /// start.
+    fn fork_threads(threads: usize) -> libc::pid_t {
+        // SAFETY: glibc keeps malloc usable in the child, which starts its threads right away
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            for _ in 1..threads {
+                std::thread::spawn(|| loop {
+                    unsafe { libc::pause() };
+                });
+            }
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let tasks = format!("/proc/{pid}/task");
+        while std::fs::read_dir(&tasks).unwrap().count() < threads {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        pid
+    }
+
+    #[test]
+    fn test_process_freeze() {
+        let value = 0x5eed_u64;
+        let address = &value as *const u64 as u64;
+        let pid = fork_threads(3);
+        let thread_state = |tid: u32| {
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/task/{tid}/stat")).unwrap();
+            stat.rsplit_once(") ").unwrap().1.chars().next().unwrap()
+        };
+
+        let process = Process::from_pid(pid as u32).unwrap();
+        let mut frozen = process.freeze().unwrap();
+        let tids = frozen.tids();
+        assert_eq!(frozen.pid(), pid as u32);
+        assert_eq!(tids.len(), 3);
+        assert_eq!(tids[0], pid as u32);
+        assert!(tids.iter().all(|tid| thread_state(*tid) == 't'));
+        assert!(frozen.sessions().iter().all(|session| session.is_stopped()));
+        assert_eq!(
+            frozen.memory().read_bytes(address, 8).unwrap(),
+            value.to_ne_bytes()
+        );
+        assert!(frozen.session_mut(tids[1]).unwrap().cont(0).is_ok());
+        assert!(frozen.session(1).is_none());
+        // Threads cannot be traced twice
+        assert!(FrozenProcess::freeze(pid as u32).is_err());
+
+        drop(frozen);
+        assert!(tids.iter().all(|tid| thread_state(*tid) != 't'));
+        process.freeze().unwrap().thaw().unwrap();
+        assert!(tids.iter().all(|tid| thread_state(*tid) != 't'));
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_process_threads() {
+        let pid = std::process::id();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let worker = std::thread::Builder::new()
+            .name("inspected".to_string())
+            .spawn(move || {
+                sender.send(unsafe { libc::gettid() } as u32).unwrap();
+                // Burn some CPU time before waiting
+                let start = std::time::Instant::now();
+                while start.elapsed() < std::time::Duration::from_millis(30) {}
+                stopped.recv().unwrap();
+            })
+            .unwrap();
+        let tid = receiver.recv().unwrap();
+
+        let mut process = Process::from_pid(pid).unwrap();
+        let threads = process.threads().unwrap();
+        assert!(threads.iter().is_sorted_by_key(|thread| thread.tid()));
+        assert!(threads[0].is_main() && threads[0].tid() == pid);
+        assert!(tids(pid).unwrap().contains(&tid));
+        let mut thread = threads
+            .iter()
+            .find(|thread| thread.tid() == tid)
+            .unwrap()
+            .clone();
+        assert_eq!((thread.pid(), thread.name()), (pid, "inspected"));
+        assert!(!thread.is_main());
+        assert!(thread.processor() >= 0);
+
+        // Waits until the thread blocks
+        while thread.state() != ProcessState::InterruptibleSleep {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            thread.refresh().unwrap();
+        }
+        assert!(thread.utime() + thread.stime() > 0);
+
+        stop.send(()).unwrap();
+        worker.join().unwrap();
+        assert!(thread.refresh().is_err());
+        process.refresh_threads().unwrap();
+        assert!(process
+            .threads()
+            .unwrap()
+            .iter()
+            .all(|thread| thread.tid() != tid));
+        assert!(Thread::list(u32::MAX).is_err());
+
+        // Fields 3 to 52 as numbered in proc(5)
+        let mut fields = vec!["0"; 50];
+        fields[0] = "R";
+        (fields[14 - 3], fields[15 - 3], fields[39 - 3]) = ("7", "3", "5");
+        let stat = format!("4553 (worker 1) {}", fields.join(" "));
+        let thread = Thread::from_stat(4500, &stat).unwrap();
+        assert_eq!((thread.tid(), thread.name()), (4553, "worker 1"));
+        assert_eq!(thread.state(), ProcessState::Running);
+        assert_eq!(
+            (thread.utime(), thread.stime(), thread.processor()),
+            (7, 3, 5)
+        );
+    }
+
+    #[test]
+    fn test_thread_registers() {
+        let pid = fork_threads(2);
+        let process = Process::from_pid(pid as u32).unwrap();
+        let threads = process.threads().unwrap();
+        let (main, worker) = (&threads[0], &threads[1]);
+
+        let session = worker.attach().unwrap();
+        assert_eq!(session.arch().unwrap(), Arch::NATIVE);
+        let registers = worker.registers(&session).unwrap();
+        assert_eq!(registers.arch(), Arch::NATIVE);
+        let code = process.find_segment(registers.pc()).unwrap();
+        assert!(code.permissions().is_executable());
+        // Each thread has its own stack, separate from the main one
+        let main_session = main.attach().unwrap();
+        let main_registers = main.registers(&main_session).unwrap();
+        assert_ne!(
+            process.find_segment(main_registers.sp()),
+            process.find_segment(registers.sp())
+        );
+        assert!(main.registers(&session).is_err());
+        drop(main_session);
+
+        let mut moved = registers;
+        moved.set_sp(registers.sp() - 64);
+        worker.set_registers(&session, &moved).unwrap();
+        assert_eq!(session.registers().unwrap().sp(), registers.sp() - 64);
+        session.set_registers(&registers).unwrap();
+        assert_eq!(session.registers().unwrap(), registers);
+        let other_arch = Registers::X86(Regs32::default());
+        assert!(session.set_registers(&other_arch).is_err());
+        drop(session);
+        // The thread runs again, so its registers are no longer accessible
+        assert!(Registers::get(worker.tid(), Arch::NATIVE).is_err());
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_fp_registers() {
+        const VALUE: u64 = 0x1234_5678_9abc_def0;
+        let avx = std::arch::is_x86_feature_detected!("avx");
+
+        // SAFETY: the child only sets vector registers and waits in pause(2)
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe {
+                if avx {
+                    // Copies xmm15 to the upper half of ymm14
+                    std::arch::asm!(
+                        "movq xmm15, {value}",
+                        "vinsertf128 ymm14, ymm14, xmm15, 1",
+                        "2:",
+                        "mov eax, 34",
+                        "syscall",
+                        "jmp 2b",
+                        value = in(reg) VALUE,
+                        options(noreturn)
+                    );
+                }
+                std::arch::asm!(
+                    "movq xmm15, {value}",
+                    "2:",
+                    "mov eax, 34",
+                    "syscall",
+                    "jmp 2b",
+                    value = in(reg) VALUE,
+                    options(noreturn)
+                );
+            }
+        }
+        // Waits for the child to reach pause(2)
+        while Process::from_pid(pid as u32).unwrap().state() != ProcessState::InterruptibleSleep {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let session = PtraceSession::attach(pid as u32).unwrap();
+        let mut registers = session.fp_registers().unwrap();
+        assert_eq!(registers.arch(), Arch::X86_64);
+        assert_eq!(registers.vector_count(), 16);
+        assert_eq!(registers.xmm(15), Some(VALUE as u128));
+        assert_eq!(registers.vector(15), registers.xmm(15));
+        assert!(registers.xmm(16).is_none() && registers.st(8).is_none());
+        assert!(registers.st(0).is_some());
+        // Default control and status: all exceptions masked
+        assert_eq!(registers.mxcsr().unwrap() & 0x1f80, 0x1f80);
+        assert!(registers.fpsr().is_none());
+        if avx {
+            assert!(registers.has_avx());
+            assert_eq!(registers.ymm(14).unwrap()[1], VALUE as u128);
+            assert_eq!(registers.ymm(15), Some([VALUE as u128, 0]));
+        }
+
+        registers.set_vector(13, u128::MAX - 1).unwrap();
+        assert!(registers.set_vector(16, 0).is_err());
+        if registers.has_avx() {
+            registers.set_ymm(12, [1, 2]).unwrap();
+        }
+        session.set_fp_registers(&registers).unwrap();
+        let read_back = FpRegisters::get(pid as u32, Arch::X86_64).unwrap();
+        assert_eq!(read_back.xmm(13), Some(u128::MAX - 1));
+        assert_eq!(read_back.xmm(15), Some(VALUE as u128));
+        if read_back.has_avx() {
+            assert_eq!(read_back.ymm(12), Some([1, 2]));
+        }
+        drop(session);
+        assert!(FpRegisters::get(pid as u32, Arch::X86_64).is_err());
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[inline(never)]
+    fn breakpoint_target(calls: &std::sync::atomic::AtomicU64) -> u64 {
+        calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        static CALLS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let address = breakpoint_target as *const () as u64;
+        // SAFETY: the child only calls the target and sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                std::hint::black_box(breakpoint_target(&CALLS));
+                unsafe { libc::usleep(1000) };
+            }
+        }
+        let calls = || {
+            let bytes = ProcMem::open(pid as u32)
+                .unwrap()
+                .read_bytes(&CALLS as *const _ as u64, 8)
+                .unwrap();
+            u64::from_ne_bytes(bytes.try_into().unwrap())
+        };
+
+        let code = unsafe { std::slice::from_raw_parts(address as *const u8, 8) }.to_vec();
+
+        let session = PtraceSession::attach(pid as u32).unwrap();
+        let mut manager = BreakpointManager::new(session).unwrap();
+        let callback_hits = std::rc::Rc::new(std::cell::Cell::new(0));
+        let id = {
+            let callback_hits = callback_hits.clone();
+            manager
+                .insert_with_callback(address, move |hit| callback_hits.set(hit.hits))
+                .unwrap()
+        };
+        assert!(manager.insert(address).is_err());
+        let breakpoint = manager.session().arch().unwrap().breakpoint().to_vec();
+        let memory = manager.session().memory();
+        assert_eq!(
+            memory.read_bytes(address, breakpoint.len()).unwrap(),
+            breakpoint
+        );
+
+        let mut calls_at_hit = Vec::new();
+        for hits in 1..=3 {
+            let BreakpointEvent::Hit(hit) = manager.run().unwrap() else {
+                panic!("The breakpoint was not hit");
+            };
+            assert_eq!((hit.id, hit.address, hit.tid), (id, address, pid as u32));
+            assert_eq!(hit.hits, hits);
+            assert_eq!(hit.registers.pc(), address);
+            assert_eq!(callback_hits.get(), hits);
+            calls_at_hit.push(calls());
+        }
+        // The original instruction runs between the hits
+        assert!(calls_at_hit.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert_eq!(manager.get(id).unwrap().hits, 3);
+
+        manager.set_enabled(id, false).unwrap();
+        assert_eq!(memory.read_bytes(address, 8).unwrap(), code);
+        manager.set_enabled(id, true).unwrap();
+        assert_eq!(manager.breakpoints().count(), 1);
+
+        // Dropping the manager while the thread runs removes the breakpoints before detaching
+        manager.cont().unwrap();
+        drop(manager);
+        assert_eq!(
+            ProcMem::open(pid as u32)
+                .unwrap()
+                .read_bytes(address, 8)
+                .unwrap(),
+            code
+        );
+        let before = calls();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(calls() > before);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_watchpoints() {
+        static WATCHED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let function = breakpoint_target as *const () as u64;
+        let watched = &WATCHED as *const _ as u64;
+
+        // SAFETY: the child only calls the target and sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                std::hint::black_box(breakpoint_target(&WATCHED));
+                unsafe { libc::usleep(1000) };
+            }
+        }
+        let value = || {
+            let bytes = ProcMem::open(pid as u32)
+                .unwrap()
+                .read_bytes(watched, 8)
+                .unwrap();
+            u64::from_ne_bytes(bytes.try_into().unwrap())
+        };
+
+        let session = PtraceSession::attach(pid as u32).unwrap();
+        let mut manager = WatchpointManager::new(session).unwrap();
+        assert_eq!(manager.capacity(), 4);
+        assert!(manager.insert(watched + 4, 8, WatchKind::Write).is_err());
+        assert!(manager.insert(watched, 3, WatchKind::Write).is_err());
+        assert!(manager.insert(function, 4, WatchKind::Execute).is_err());
+        assert_eq!(manager.insert_breakpoint(function).unwrap(), 0);
+        assert_eq!(manager.insert(watched, 8, WatchKind::Write).unwrap(), 1);
+
+        // The breakpoint fires before the call, the watchpoint after the write in it
+        let next_hit = |manager: &mut WatchpointManager| match manager.run().unwrap() {
+            WatchpointEvent::Hit(hit) => hit,
+            WatchpointEvent::Stop(stop) => panic!("Unexpected stop {stop:?}"),
+        };
+        let hit = next_hit(&mut manager);
+        assert_eq!((hit.slot, hit.address, hit.hits), (0, function, 1));
+        assert_eq!(hit.tid, pid as u32);
+        assert_eq!(hit.registers.pc(), function);
+        let before = value();
+        let hit = next_hit(&mut manager);
+        assert_eq!((hit.slot, hit.address, hit.hits), (1, watched, 1));
+        assert_ne!(hit.registers.pc(), function);
+        assert_eq!(value(), before + 1);
+        let hit = next_hit(&mut manager);
+        assert_eq!((hit.slot, hit.hits), (0, 2));
+
+        assert_eq!(manager.insert(watched, 4, WatchKind::ReadWrite).unwrap(), 2);
+        assert_eq!(manager.insert(watched + 4, 4, WatchKind::Write).unwrap(), 3);
+        assert!(manager.insert(watched, 1, WatchKind::Write).is_err());
+        assert_eq!(manager.watchpoints().count(), 4);
+        manager.remove(2).unwrap();
+        manager.remove(3).unwrap();
+        assert!(manager.remove(3).is_err());
+        assert_eq!(manager.remove(0).unwrap().hits, 2);
+
+        let hit = next_hit(&mut manager);
+        assert_eq!((hit.slot, hit.hits), (1, 2));
+        assert_eq!(manager.get(1).unwrap().hits, 2);
+
+        // Dropping the manager while the thread runs clears the debug registers
+        manager.cont().unwrap();
+        drop(manager);
+        let before = value();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(value() > before);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_thread_step() {
+        static STEPPED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let function = breakpoint_target as *const () as u64;
+
+        // SAFETY: the child only calls the target and sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                std::hint::black_box(breakpoint_target(&STEPPED));
+                unsafe { libc::usleep(1000) };
+            }
+        }
+        let calls = || {
+            let bytes = ProcMem::open(pid as u32)
+                .unwrap()
+                .read_bytes(&STEPPED as *const _ as u64, 8)
+                .unwrap();
+            u64::from_ne_bytes(bytes.try_into().unwrap())
+        };
+        let thread = Thread::from_tid(pid as u32, pid as u32).unwrap();
+        let mut session = thread.attach().unwrap();
+
+        let StepEvent::Stepped { pc, steps: 1 } = thread.step(&mut session).unwrap() else {
+            panic!("The thread did not step");
+        };
+        assert_eq!(session.registers().unwrap().pc(), pc);
+
+        // Steps into the target, remembering the call instruction
+        let mut previous = 0;
+        let mut call = 0;
+        let event = thread
+            .step_until(&mut session, |registers| {
+                call = std::mem::replace(&mut previous, registers.pc());
+                registers.pc() == function
+            })
+            .unwrap();
+        assert!(matches!(event, StepEvent::Stepped { pc, steps } if pc == function && steps > 1));
+        let sp = session.registers().unwrap().sp();
+        let bytes = session.memory().read_bytes(sp, 8).unwrap();
+        let return_address = u64::from_ne_bytes(bytes.try_into().unwrap());
+
+        // Goes around the loop back to the call, and runs the target at once
+        thread
+            .step_until(&mut session, |registers| registers.pc() == call)
+            .unwrap();
+        let before = calls();
+        assert_eq!(
+            thread.step_over_call(&mut session).unwrap(),
+            StepEvent::Stepped {
+                pc: return_address,
+                steps: 1
+            }
+        );
+        assert_eq!(calls(), before + 1);
+        let original = session.memory().read_bytes(return_address, 1).unwrap();
+        assert_ne!(original, [0xcc]);
+        // Other instructions are stepped normally
+        let StepEvent::Stepped { pc, steps: 1 } = thread.step_over_call(&mut session).unwrap()
+        else {
+            panic!("The thread did not step");
+        };
+        assert_ne!(pc, return_address);
+
+        let other = Thread::from_tid(std::process::id(), std::process::id()).unwrap();
+        assert!(other.step(&mut session).is_err());
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_syscall_tracer() {
+        assert_eq!(syscall_number(Arch::X86_64, "openat"), Some(257));
+        assert_eq!(syscall_number(Arch::X86, "openat"), Some(295));
+        assert_eq!(syscall_number(Arch::Aarch64, "open"), None);
+        assert_eq!(syscall_info(Arch::Aarch64, 56).unwrap().name, "openat");
+        assert!(syscall_info(Arch::X86_64, 100_000).is_none());
+
+        let null = std::fs::File::create("/dev/null").unwrap();
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&null);
+        let path = c"/nonexistent/libinspector";
+        // SAFETY: the child only makes system calls
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe {
+                    libc::write(fd, b"hello".as_ptr().cast(), 5);
+                    libc::open(path.as_ptr(), libc::O_RDONLY);
+                    libc::usleep(1000);
+                }
+            }
+        }
+
+        let new_tracer = || SyscallTracer::new(PtraceSession::attach(pid as u32).unwrap()).unwrap();
+        assert!(new_tracer().with_filter(&["write", "nope"]).is_err());
+        let mut tracer = new_tracer()
+            .with_filter(&["write", "openat"])
+            .unwrap()
+            .with_string_limit(4);
+
+        let mut events = Vec::new();
+        tracer
+            .trace(|event| {
+                events.push(event.clone());
+                match events.len() {
+                    12 => std::ops::ControlFlow::Break(()),
+                    _ => std::ops::ControlFlow::Continue(()),
+                }
+            })
+            .unwrap();
+        // The thread stays stopped at the last event
+        assert!(tracer.session().is_stopped());
+        let entries: Vec<&SyscallEntry> = events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Entry(entry) => Some(entry),
+                _ => None,
+            })
+            .collect();
+        let exits: Vec<&SyscallExit> = events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Exit(exit) => Some(exit),
+                _ => None,
+            })
+            .collect();
+        assert!(entries.len() >= 5 && exits.len() >= 5);
+        assert!(
+            entries
+                .iter()
+                .all(|entry| matches!(entry.name, Some("write" | "openat"))
+                    && entry.tid == pid as u32)
+        );
+
+        let write = entries
+            .iter()
+            .find(|entry| entry.name == Some("write"))
+            .unwrap();
+        assert_eq!(
+            write.args,
+            [
+                SyscallArg::Fd(fd),
+                SyscallArg::Bytes(b"hell".to_vec()),
+                SyscallArg::Int(5)
+            ]
+        );
+        assert_eq!(write.to_string(), format!("write({fd}, \"hell\", 5)"));
+        let write = exits
+            .iter()
+            .find(|exit| exit.name == Some("write"))
+            .unwrap();
+        assert_eq!((write.return_value, write.error()), (5, None));
+
+        let open = exits
+            .iter()
+            .find(|exit| exit.name == Some("openat"))
+            .unwrap();
+        assert_eq!(open.error(), Some(libc::ENOENT));
+        assert_eq!(
+            &open.args[..2],
+            [
+                SyscallArg::Fd(libc::AT_FDCWD),
+                SyscallArg::Str("/non".to_string())
+            ]
+        );
+        assert!(open
+            .to_string()
+            .starts_with("openat(AT_FDCWD, \"/non\", 0x0"));
+        assert!(open.to_string().ends_with(") = -2"));
+
+        // Every system call is reported without a filter
+        let mut tracer = SyscallTracer::new(tracer.into_session()).unwrap();
+        let mut names = std::collections::HashSet::new();
+        while names.len() < 3 {
+            if let TraceEvent::Entry(entry) = tracer.next_event().unwrap() {
+                names.insert(entry.name.unwrap_or("unknown"));
+            }
+        }
+        assert!(names.contains("clock_nanosleep") || names.contains("nanosleep"));
+        drop(tracer);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    static SIGUSR1_COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    static SIGUSR2_COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    extern "C" fn count_signal(signal: libc::c_int) {
+        let count = match signal {
+            libc::SIGUSR1 => &SIGUSR1_COUNT,
+            _ => &SIGUSR2_COUNT,
+        };
+        count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_ptrace_signals() {
+        // SAFETY: the child only counts the signals it receives
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            unsafe {
+                libc::signal(
+                    libc::SIGUSR1,
+                    count_signal as *const () as libc::sighandler_t,
+                );
+                libc::signal(
+                    libc::SIGUSR2,
+                    count_signal as *const () as libc::sighandler_t,
+                );
+                loop {
+                    libc::pause();
+                }
+            }
+        }
+        // Waits for the handlers to be installed
+        let handled = |pid: libc::pid_t| {
+            let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap();
+            let caught = status
+                .lines()
+                .find_map(|line| line.strip_prefix("SigCgt:"))
+                .unwrap();
+            u64::from_str_radix(caught.trim(), 16).unwrap() & 0xa00 == 0xa00
+        };
+        while !handled(pid) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let wait_for_counts = |usr1: u64, usr2: u64| {
+            let memory = ProcMem::open(pid as u32).unwrap();
+            let read = |count: &std::sync::atomic::AtomicU64| {
+                let bytes = memory.read_bytes(count as *const _ as u64, 8).unwrap();
+                u64::from_ne_bytes(bytes.try_into().unwrap())
+            };
+            for _ in 0..2000 {
+                if (read(&SIGUSR1_COUNT), read(&SIGUSR2_COUNT)) == (usr1, usr2) {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            panic!("The signal handlers did not run as expected");
+        };
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        assert_eq!(session.signal_info().unwrap().signal(), libc::SIGSTOP);
+        session.cont(0).unwrap();
+        assert!(session.set_pending_signal(0).is_err());
+
+        // Suppressed
+        unsafe { libc::kill(pid, libc::SIGUSR1) };
+        assert_eq!(session.wait().unwrap(), Stop::Signal(libc::SIGUSR1));
+        let info = session.signal_info().unwrap();
+        assert_eq!(info.signal(), libc::SIGUSR1);
+        assert!(info.is_user());
+        assert_eq!(
+            info.sender(),
+            Some((std::process::id(), unsafe { libc::getuid() }))
+        );
+        assert_eq!((info.fault_address(), info.value()), (None, None));
+        session.set_pending_signal(0).unwrap();
+        session.cont(session.pending_signal()).unwrap();
+
+        // Injected by the tracer
+        session.inject_signal(libc::SIGUSR2).unwrap();
+        assert_eq!(session.wait().unwrap(), Stop::Signal(libc::SIGUSR2));
+        session.cont(session.pending_signal()).unwrap();
+        wait_for_counts(0, 1);
+
+        // Changed
+        unsafe { libc::kill(pid, libc::SIGUSR1) };
+        assert_eq!(session.wait().unwrap(), Stop::Signal(libc::SIGUSR1));
+        let mut info = session.signal_info().unwrap();
+        info.set_signal(libc::SIGUSR2);
+        session.set_signal_info(&info).unwrap();
+        assert_eq!(session.pending_signal(), libc::SIGUSR2);
+        assert_eq!(session.signal_info().unwrap(), info);
+        session.cont(session.pending_signal()).unwrap();
+        wait_for_counts(0, 2);
+
+        // Queued with a value
+        session.queue_signal(libc::SIGUSR1, 42).unwrap();
+        assert_eq!(session.wait().unwrap(), Stop::Signal(libc::SIGUSR1));
+        let info = session.signal_info().unwrap();
+        assert_eq!((info.value(), info.code()), (Some(42), -1));
+        assert_eq!(info.sender().unwrap().0, std::process::id());
+        session.cont(session.pending_signal()).unwrap();
+        wait_for_counts(1, 2);
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    static REMOTE_STORED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    #[allow(clippy::too_many_arguments)]
+    extern "C" fn remote_sum(
+        a: u64,
+        b: u64,
+        c: u64,
+        d: u64,
+        e: u64,
+        f: u64,
+        g: u64,
+        h: u64,
+    ) -> u64 {
+        let sum = a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+        REMOTE_STORED.store(sum, std::sync::atomic::Ordering::SeqCst);
+        sum
+    }
+
+    #[test]
+    fn test_call_remote() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let registers = session.registers().unwrap();
+        let sum = remote_sum as *const () as u64;
+        assert_eq!(
+            session.call_remote(sum, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap(),
+            204
+        );
+        assert_eq!(session.registers().unwrap(), registers);
+        let memory = ProcMem::open(pid as u32).unwrap();
+        let stored = memory
+            .read_bytes(&REMOTE_STORED as *const _ as u64, 8)
+            .unwrap();
+        assert_eq!(u64::from_ne_bytes(stored.try_into().unwrap()), 204);
+
+        let getpid = libc::getpid as *const () as u64;
+        assert_eq!(call_remote(&mut session, getpid, &[]).unwrap(), pid as u64);
+
+        // Faults are reported, the thread restored
+        assert!(session.call_remote(8, &[]).is_err());
+        assert_eq!(session.registers().unwrap(), registers);
+        assert_eq!(
+            session.call_remote(sum, &[8, 7, 6, 5, 4, 3, 2, 1]).unwrap(),
+            120
+        );
+
+        // The thread goes on sleeping
+        session.cont(0).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status")).unwrap();
+        assert!(status.contains("State:\tS"));
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_inject_library() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        // dlopen takes locks the child may hold before reaching pause
+        while Process::from_pid(pid as u32).unwrap().state() != ProcessState::InterruptibleSleep {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let getpid = libc::getpid as *const () as u64;
+        assert_eq!(remote_symbol(pid as u32, "getpid").unwrap(), getpid);
+        assert!(remote_symbol(pid as u32, "no_such_symbol").is_err());
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let registers = session.registers().unwrap();
+        let loaded = |name: &str| {
+            Segment::get_from_pid(pid as u32)
+                .unwrap()
+                .iter()
+                .any(|segment| {
+                    segment
+                        .path()
+                        .is_some_and(|path| path.to_string_lossy().contains(name))
+                })
+        };
+        assert!(!loaded("libz.so"));
+        assert_ne!(session.inject_library("libz.so.1").unwrap(), 0);
+        assert!(loaded("libz.so"));
+        assert_eq!(session.registers().unwrap(), registers);
+
+        // The message of dlerror is reported
+        let error = inject_library(&mut session, "/nonexistent/libinspector.so").unwrap_err();
+        assert!(format!("{error:#}").contains("cannot open shared object file"));
+        assert_eq!(session.call_remote(getpid, &[]).unwrap(), pid as u64);
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_shellcode() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let executable = || {
+            Segment::get_from_pid(pid as u32)
+                .unwrap()
+                .iter()
+                .filter(|segment| segment.permissions().is_executable())
+                .count()
+        };
+        let mappings = executable();
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let registers = session.registers().unwrap();
+        // lea rax, [rdi + rsi]; ret
+        let add = Shellcode::new([0x48, 0x8d, 0x04, 0x37, 0xc3]).with_args(&[40, 2]);
+        assert_eq!(add.run(&mut session).unwrap(), 42);
+        assert_eq!(session.registers().unwrap(), registers);
+        assert_eq!(executable(), mappings);
+
+        // jmp $
+        let hang = Shellcode::new([0xeb, 0xfe]).with_timeout(std::time::Duration::from_millis(50));
+        assert!(hang.run(&mut session).is_err());
+        assert_eq!(session.registers().unwrap(), registers);
+        assert_eq!(executable(), mappings);
+
+        // ud2
+        assert!(Shellcode::new([0x0f, 0x0b]).run(&mut session).is_err());
+        assert_eq!(session.registers().unwrap(), registers);
+        assert_eq!(add.run(&mut session).unwrap(), 42);
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    static PATCHED_RESULT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    static PATCHED_STEP: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+    #[inline(never)]
+    extern "C" fn patch_target() -> u64 {
+        std::hint::black_box(1)
+    }
+
+    #[test]
+    fn test_patch_encoding() {
+        let jump = Patch::jump(Arch::X86_64, 0x1000, 0x2000).unwrap();
+        assert_eq!(jump.replacement(), [0xe9, 0xfb, 0x0f, 0x00, 0x00]);
+        assert!(Patch::jump(Arch::X86_64, 0x1000, 0x1_0000_0000).is_err());
+        // Wraps around the address space
+        let jump = Patch::jump(Arch::X86, 0xffff_f000, 0x10).unwrap();
+        assert_eq!(jump.replacement(), [0xe9, 0x0b, 0x10, 0x00, 0x00]);
+
+        let jump = Patch::jump(Arch::Aarch64, 0x1000, 0x800).unwrap();
+        assert_eq!(jump.replacement(), [0x00, 0xfe, 0xff, 0x17]);
+        assert!(Patch::jump(Arch::Aarch64, 0x1000, 0x1002).is_err());
+        assert!(Patch::jump(Arch::Aarch64, 0x1000, 0x1000_0000).is_err());
+
+        let nop = Patch::nop(Arch::Aarch64, 0x1000, 8).unwrap();
+        assert_eq!(nop.replacement(), [0x1f, 0x20, 0x03, 0xd5].repeat(2));
+        assert!(Patch::nop(Arch::Aarch64, 0x1000, 6).is_err());
+        assert_eq!(Patch::nop(Arch::X86_64, 0x1000, 3).unwrap().len(), 3);
+        assert_eq!(Patch::value(0x1000, 7u32).replacement(), [7, 0, 0, 0]);
+        assert!(!nop.is_applied());
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_patch_set() {
+        use std::sync::atomic::Ordering;
+
+        // SAFETY: the child only calls the patched function
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                let result = patch_target() + PATCHED_STEP.load(Ordering::SeqCst);
+                PATCHED_RESULT.store(result, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        let memory = ProcMem::open(pid as u32).unwrap();
+        let wait_for_result = |expected: u64| {
+            for _ in 0..2000 {
+                let bytes = memory
+                    .read_bytes(&PATCHED_RESULT as *const _ as u64, 8)
+                    .unwrap();
+                if u64::from_ne_bytes(bytes.try_into().unwrap()) == expected {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            panic!("The result of the child is not {expected}");
+        };
+        wait_for_result(2);
+
+        let function = patch_target as *const () as u64;
+        let code = memory.read_bytes(function, 6).unwrap();
+        let step = &PATCHED_STEP as *const _ as u64;
+        // mov eax, 40; ret
+        let mut patches = PatchSet::new()
+            .with(Patch::new(function, [0xb8, 0x28, 0x00, 0x00, 0x00, 0xc3]))
+            .with(Patch::value(step, 2u64));
+        let process = Process::from_pid(pid as u32).unwrap();
+        let frozen = process.freeze().unwrap();
+        patches.apply(&frozen).unwrap();
+        assert!(patches.apply(&frozen).is_err());
+        assert_eq!(patches.patches()[0].original(), Some(&code[..]));
+        frozen.thaw().unwrap();
+        wait_for_result(42);
+
+        let frozen = process.freeze().unwrap();
+        patches.revert(&frozen).unwrap();
+        assert!(!patches.is_applied());
+        assert_eq!(memory.read_bytes(function, 6).unwrap(), code);
+
+        // The patches applied are reverted when one fails
+        patches.push(Patch::value(8, 0u64));
+        assert!(patches.apply(&frozen).is_err());
+        assert!(!patches.is_applied());
+        assert_eq!(memory.read_bytes(function, 6).unwrap(), code);
+        frozen.thaw().unwrap();
+        wait_for_result(2);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_x86_decode() {
+        let decode = |code: &[u8]| detour::decode(code, 0x1000).unwrap();
+        let lengths: [&[u8]; 14] = [
+            &[0x55],
+            &[0x48, 0x89, 0xe5],
+            &[0xf3, 0x0f, 0x1e, 0xfa],
+            &[0x48, 0x83, 0xec, 0x20],
+            &[0x48, 0xb8, 1, 2, 3, 4, 5, 6, 7, 8],
+            &[0x66, 0x0f, 0x1f, 0x44, 0x00, 0x00],
+            &[0xc7, 0x44, 0x24, 0x08, 0x01, 0x00, 0x00, 0x00],
+            &[0xf6, 0xc1, 0x01],
+            &[0xf7, 0xd0],
+            &[0xc5, 0xf8, 0x77],
+            &[0xc4, 0xe3, 0x79, 0x16, 0xc0, 0x01],
+            &[0x66, 0x0f, 0x3a, 0x0f, 0xc1, 0x08],
+            &[0x41, 0x81, 0xfc, 0x00, 0x10, 0x00, 0x00],
+            &[0x0f, 0xb6, 0x04, 0x25, 0x00, 0x10, 0x00, 0x00],
+        ];
+        for code in lengths {
+            let instruction = decode(code);
+            assert_eq!(instruction.len, code.len(), "{code:02x?}");
+            assert_eq!(instruction.rip_displacement, None);
+            assert_eq!(instruction.branch, None);
+            assert!(instruction.falls_through);
+        }
+
+        // Relative operands
+        let load = decode(&[0x48, 0x8b, 0x05, 0x10, 0x00, 0x00, 0x00]);
+        assert_eq!((load.len, load.rip_displacement), (7, Some(3)));
+        let call = decode(&[0xe8, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(call.branch, Some((BranchKind::Call, 0x1105)));
+        assert!(call.falls_through);
+        let branch = decode(&[0x74, 0xfe]);
+        assert_eq!(branch.branch, Some((BranchKind::Conditional(4), 0x1000)));
+        let branch = decode(&[0x0f, 0x85, 0x00, 0xf0, 0xff, 0xff]);
+        assert_eq!(branch.branch, Some((BranchKind::Conditional(5), 0x6)));
+        let jump = decode(&[0xeb, 0x10]);
+        assert_eq!(jump.branch, Some((BranchKind::Jump, 0x1012)));
+        assert!(!jump.falls_through);
+        assert!(!decode(&[0xc3]).falls_through);
+        assert!(!decode(&[0xff, 0xe0]).falls_through);
+        assert!(decode(&[0xff, 0xd0]).falls_through);
+
+        assert!(detour::decode(&[0x48, 0x8b], 0).is_err());
+        assert!(detour::decode(&[0x06], 0).is_err());
+        assert!(detour::decode(&[0x66; 16], 0).is_err());
+    }
+
+    #[test]
+    fn test_x86_relocate() {
+        // mov rax, [rip + 0x10]; je +2
+        let code = [0x48, 0x8b, 0x05, 0x10, 0x00, 0x00, 0x00, 0x74, 0x02];
+        assert_eq!(
+            detour::relocate(&code, 0x1000, 0x5000).unwrap(),
+            [0x48, 0x8b, 0x05, 0x10, 0xc0, 0xff, 0xff, 0x0f, 0x84, 0xfe, 0xbf, 0xff, 0xff]
+        );
+        assert!(detour::relocate(&code, 0x1000, 0x7f00_0000_0000).is_err());
+
+        // Out of reach, the opposite condition jumps over an absolute jump
+        let mut expected = vec![0x75, 0x0e, 0xff, 0x25, 0x00, 0x00, 0x00, 0x00];
+        expected.extend_from_slice(&0x1004u64.to_le_bytes());
+        assert_eq!(
+            detour::relocate(&[0x74, 0x02], 0x1000, 0x7f00_0000_0000).unwrap(),
+            expected
+        );
+        assert_eq!(
+            detour::relocate(&[0xe8, 0x00, 0x01, 0x00, 0x00], 0x1000, 0x2000).unwrap(),
+            [0xe8, 0x00, 0xf1, 0xff, 0xff]
+        );
+        let mut expected = vec![0xff, 0x15, 0x02, 0x00, 0x00, 0x00, 0xeb, 0x08];
+        expected.extend_from_slice(&0x1105u64.to_le_bytes());
+        assert_eq!(
+            detour::relocate(&[0xe8, 0x00, 0x01, 0x00, 0x00], 0x1000, 0x7f00_0000_0000).unwrap(),
+            expected
+        );
+
+        assert!(detour::relocate(&[0xe2, 0xfe], 0x1000, 0x2000).is_err());
+        // Into the relocated instructions
+        assert!(detour::relocate(&[0xeb, 0x01, 0x90, 0x90], 0x1000, 0x2000).is_err());
+    }
+
+    static DETOUR_RESULT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    static DETOUR_TRAMPOLINE: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    #[inline(never)]
+    extern "C" fn detour_target(value: u64) -> u64 {
+        std::hint::black_box(value).wrapping_mul(2)
+    }
+
+    extern "C" fn detour_handler(value: u64) -> u64 {
+        let trampoline = DETOUR_TRAMPOLINE.load(std::sync::atomic::Ordering::SeqCst);
+        // SAFETY: the trampoline runs the original function
+        let original: extern "C" fn(u64) -> u64 =
+            unsafe { std::mem::transmute(trampoline as usize) };
+        original(value) + 1000
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_detour() {
+        use std::sync::atomic::Ordering;
+
+        // SAFETY: the child only calls the hooked function
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                let result = detour_target(std::hint::black_box(21));
+                DETOUR_RESULT.store(result, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        let memory = ProcMem::open(pid as u32).unwrap();
+        let wait_for_result = |expected: u64| {
+            for _ in 0..2000 {
+                let bytes = memory
+                    .read_bytes(&DETOUR_RESULT as *const _ as u64, 8)
+                    .unwrap();
+                if u64::from_ne_bytes(bytes.try_into().unwrap()) == expected {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            panic!("The result of the child is not {expected}");
+        };
+        wait_for_result(42);
+
+        let target = detour_target as *const () as u64;
+        let handler = detour_handler as *const () as u64;
+        let code = memory.read_bytes(target, 16).unwrap();
+        let process = Process::from_pid(pid as u32).unwrap();
+        for builder in [
+            DetourBuilder::new(target, handler),
+            DetourBuilder::new(target, handler).with_absolute_jump(),
+        ] {
+            let mut frozen = process.freeze().unwrap();
+            let detour = builder.install(&mut frozen).unwrap();
+            assert_eq!((detour.address(), detour.handler()), (target, handler));
+            assert!(detour.overwritten() >= 5);
+            if builder == DetourBuilder::new(target, handler) {
+                assert!(detour.trampoline().abs_diff(target) < 1 << 31);
+            } else {
+                assert!(detour.overwritten() >= 14);
+            }
+            memory
+                .write(
+                    &DETOUR_TRAMPOLINE as *const _ as u64,
+                    &detour.trampoline().to_ne_bytes(),
+                )
+                .unwrap();
+            frozen.thaw().unwrap();
+            wait_for_result(1042);
+
+            let mut frozen = process.freeze().unwrap();
+            detour.remove(&mut frozen).unwrap();
+            assert_eq!(memory.read_bytes(target, 16).unwrap(), code);
+            frozen.thaw().unwrap();
+            DETOUR_RESULT.store(0, Ordering::SeqCst);
+            wait_for_result(42);
+        }
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_remote_mmap() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let mapped = |address: u64| {
+            Segment::get_from_pid(pid as u32)
+                .unwrap()
+                .into_iter()
+                .find(|segment| segment.start() == address)
+        };
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let registers = session.registers().unwrap();
+        let getpid = libc::SYS_getpid as u64;
+        assert_eq!(
+            remote_syscall(&mut session, getpid, [0; 6]).unwrap(),
+            pid as u64
+        );
+        assert_eq!(session.registers().unwrap(), registers);
+
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let mut mapping = remote_mmap(&mut session, 8192, prot).unwrap();
+        let address = mapping.address();
+        let segment = mapped(address).unwrap();
+        assert_eq!(segment.end() - segment.start(), 8192);
+        assert_eq!(segment.permissions().to_string(), "rw-p");
+        let memory = mapping.session_mut().memory();
+        memory.write(address + 4096, b"mapped").unwrap();
+        assert_eq!(memory.read_bytes(address + 4096, 6).unwrap(), b"mapped");
+        drop(mapping);
+        assert!(mapped(address).is_none());
+
+        let address = remote_mmap(&mut session, 4096, libc::PROT_READ)
+            .unwrap()
+            .leak();
+        assert!(mapped(address).is_some());
+        remote_munmap(&mut session, address, 4096).unwrap();
+        assert!(mapped(address).is_none());
+        assert!(remote_munmap(&mut session, address + 1, 4096).is_err());
+        assert_eq!(session.registers().unwrap(), registers);
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_remote_mprotect() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let page = page_size();
+        let protections = |address: u64| {
+            Segment::get_from_pid(pid as u32)
+                .unwrap()
+                .into_iter()
+                .filter(|segment| segment.start() >= address && segment.end() <= address + 3 * page)
+                .map(|segment| {
+                    let pages = (segment.end() - segment.start()) / page;
+                    (pages, segment.permissions().to_string())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let address = remote_mmap(&mut session, 3 * page, libc::PROT_READ)
+            .unwrap()
+            .leak();
+        let rw = libc::PROT_READ | libc::PROT_WRITE;
+        remote_mprotect(&mut session, address + page..address + 2 * page, rw)
+            .unwrap()
+            .keep();
+        let original = vec![
+            (1, "r--p".to_string()),
+            (1, "rw-p".to_string()),
+            (1, "r--p".to_string()),
+        ];
+        assert_eq!(protections(address), original);
+
+        // Extended to whole pages
+        let rwx = rw | libc::PROT_EXEC;
+        let guard =
+            remote_mprotect(&mut session, address + 1..address + 3 * page - 1, rwx).unwrap();
+        assert_eq!(guard.original().len(), 3);
+        assert_eq!(
+            guard.original()[1],
+            (address + page..address + 2 * page, rw)
+        );
+        assert_eq!(protections(address), [(3, "rwxp".to_string())]);
+        drop(guard);
+        assert_eq!(protections(address), original);
+
+        let guard = remote_mprotect(&mut session, address..address + page, rw).unwrap();
+        guard.restore().unwrap();
+        assert_eq!(protections(address), original);
+        assert!(remote_mprotect(&mut session, 0x1000..0x2000, rw).is_err());
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_remote_madvise() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let page = page_size();
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        let address = remote_mmap(&mut session, 4 * page, libc::PROT_READ | libc::PROT_WRITE)
+            .unwrap()
+            .leak();
+        session.memory().write(address, &[0x42; 16]).unwrap();
+        session.memory().write(address + page, &[0x42; 16]).unwrap();
+
+        // Extended to whole pages
+        remote_madvise(&mut session, address + 1..address + 2, Advice::DontNeed).unwrap();
+        let memory = session.memory();
+        assert_eq!(memory.read_bytes(address, 16).unwrap(), [0; 16]);
+        assert_eq!(memory.read_bytes(address + page, 16).unwrap(), [0x42; 16]);
+        remote_madvise(&mut session, address..address + page, Advice::Cold).unwrap();
+        assert!(remote_madvise(&mut session, 0x1000..0x2000, Advice::WillNeed).is_err());
+
+        let process = Process::from_pid(pid as u32).unwrap();
+        let locked = |process: &Process| {
+            process
+                .locked_segments()
+                .unwrap()
+                .iter()
+                .any(|segment| segment.start() == address)
+        };
+        assert!(!locked(&process));
+        remote_mlock(&mut session, address..address + 4 * page).unwrap();
+        assert!(locked(&process));
+        remote_munlock(&mut session, address..address + 4 * page).unwrap();
+        assert!(!locked(&process));
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_process_advise() {
+        let page = page_size();
+        let len = 4 * page as usize;
+        // SAFETY: an anonymous mapping with no address hint
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(address, libc::MAP_FAILED);
+        // SAFETY: the mapping is writable and `len` bytes long
+        unsafe { std::ptr::write_bytes(address as *mut u8, 0x42, len) };
+        let address = address as u64;
+
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        let process = Process::from_pid(pid as u32).unwrap();
+        let end = address + len as u64;
+        // Extended to whole pages
+        assert_eq!(
+            process.advise(address + 1..end - 1, Advice::Cold).unwrap(),
+            len as u64
+        );
+        assert_eq!(
+            process.advise(address..end, Advice::PageOut).unwrap(),
+            len as u64
+        );
+        assert!(process.advise(address..end, Advice::DontNeed).is_err());
+        assert!(process.advise(0x1000..0x2000, Advice::Cold).is_err());
+
+        // SAFETY: kill and waitpid take no pointer, the mapping is not used anymore
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+            libc::munmap(address as *mut libc::c_void, len);
+        }
+    }
+
+    #[test]
+    fn test_elf_parse() {
+        let pid = std::process::id();
+        let segments = Segment::get_from_pid(pid).unwrap();
+        let modules = Module::group(&segments);
+        let exe = std::fs::canonicalize("/proc/self/exe").unwrap();
+        let module = modules.iter().find(|module| module.path() == exe).unwrap();
+        assert!(module.contains(test_elf_parse as *const () as u64));
+        assert!(modules
+            .iter()
+            .any(|module| module.name().starts_with("libc")));
+        assert!(modules
+            .windows(2)
+            .all(|pair| pair[0].base() < pair[1].base()));
+
+        let elf = module.read_elf(pid).unwrap();
+        let header = elf.header();
+        assert_eq!(header.bitness, Bitness::NATIVE);
+        assert_eq!(header.arch().unwrap(), Arch::NATIVE);
+        let text = elf.section(".text").unwrap();
+        assert!(text.is_alloc() && text.is_executable());
+        assert_eq!(elf.section_headers()[0].kind, elf::SHT_NULL);
+        assert!(elf.loads().any(|load| load.is_executable()));
+        let bias = elf.load_bias(module.base());
+        let entry = header.entry.wrapping_add(bias);
+        assert!(module.contains(entry));
+
+        let memory = Process::from_pid(pid).unwrap().memory();
+        let mapped = module.read_elf_from_memory(&memory).unwrap();
+        assert_eq!(mapped.header(), header);
+        assert_eq!(mapped.program_headers(), elf.program_headers());
+        assert_eq!(
+            elf.offset_to_vaddr(text.offset),
+            Some(text.addr),
+            "{text:?}"
+        );
+        assert!(Elf::parse(b"\x7fELF").is_err());
+        assert!(Elf::parse(&[0; 64]).is_err());
+
+        // A 32-bit file with one loaded segment and no sections
+        let mut bytes = vec![0x7f, b'E', b'L', b'F', 1, 1, 1];
+        bytes.resize(16, 0);
+        bytes.extend_from_slice(&elf::ET_EXEC.to_le_bytes());
+        bytes.extend_from_slice(&3u16.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for word in [0x0804_8054u32, 52, 0, 0] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for half in [52u16, 32, 1, 40, 0, 0] {
+            bytes.extend_from_slice(&half.to_le_bytes());
+        }
+        for word in [elf::PT_LOAD, 0, 0x0804_8000, 0x0804_8000, 84, 84] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(elf::PF_R | elf::PF_X).to_le_bytes());
+        bytes.extend_from_slice(&0x1000u32.to_le_bytes());
+        let elf = Elf::parse(&bytes).unwrap();
+        assert_eq!(elf.header().bitness, Bitness::Bits32);
+        assert_eq!(elf.header().arch().unwrap(), Arch::X86);
+        assert_eq!(elf.header().entry, 0x0804_8054);
+        assert!(elf.section_headers().is_empty());
+        let load = elf.program_headers()[0];
+        assert!(load.is_load() && load.is_executable());
+        assert_eq!(
+            (load.vaddr, load.memsz, load.align),
+            (0x0804_8000, 84, 0x1000)
+        );
+        assert_eq!(elf.load_bias(0x0804_8000), 0);
+        assert_eq!(elf.offset_to_vaddr(0x54), None);
+        assert_eq!(elf.offset_to_vaddr(0x40), Some(0x0804_8040));
+        assert!(Elf::parse(&bytes[..80]).is_err());
+
+        // Sizes past the end of the file fail before anything is allocated for them
+        let with_sections = |count: u16, first_size: u32, names_size: u32| {
+            let mut bytes = bytes.clone();
+            bytes[32..36].copy_from_slice(&84u32.to_le_bytes());
+            bytes[48..50].copy_from_slice(&count.to_le_bytes());
+            bytes[50..52].copy_from_slice(&1u16.to_le_bytes());
+            bytes.resize(164, 0);
+            bytes[104..108].copy_from_slice(&first_size.to_le_bytes());
+            bytes[128..132].copy_from_slice(&elf::SHT_STRTAB.to_le_bytes());
+            bytes[144..148].copy_from_slice(&names_size.to_le_bytes());
+            bytes
+        };
+        let elf = Elf::parse(&with_sections(2, 0, 16)).unwrap();
+        assert_eq!(elf.section_headers().len(), 2);
+        assert!(Elf::parse(&with_sections(0xffff, 0, 16)).is_err());
+        assert!(Elf::parse(&with_sections(0, u32::MAX, 16)).is_err());
+        assert!(Elf::parse(&with_sections(2, 0, u32::MAX)).is_err());
+
+        // The dynamic section read from memory must be within a loaded segment
+        let dynamic = [elf::DT_NEEDED as u32, 5, 0, 0];
+        let with_dynamic = |vaddr: u32, memsz: u32| {
+            let mut bytes = bytes.clone();
+            bytes[44..46].copy_from_slice(&2u16.to_le_bytes());
+            bytes[72..76].copy_from_slice(&0x2000u32.to_le_bytes());
+            for word in [elf::PT_DYNAMIC, 0, vaddr, vaddr, memsz, memsz, elf::PF_R, 4] {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            Elf::parse(&bytes).unwrap()
+        };
+        let memory = ProcessVm::new(pid);
+        let bias = (dynamic.as_ptr() as u64).wrapping_sub(0x0804_9000);
+        let read = with_dynamic(0x0804_9000, 16)
+            .read_dynamic(&memory, bias)
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.entries(), [(elf::DT_NEEDED, 5)]);
+        assert!(with_dynamic(0x0804_9000, u32::MAX)
+            .read_dynamic(&memory, bias)
+            .is_err());
+        assert!(with_dynamic(0x0804_a000, 16)
+            .read_dynamic(&memory, bias)
+            .is_err());
+        assert!(with_dynamic(0x0804_9000, 16)
+            .read_dynamic(&memory, u64::MAX << 62)
+            .is_err());
+    }
+
+    #[test]
+    fn test_dynamic_symbols_bounds() {
+        // A 64-bit file mapped in a buffer, its dynamic section pointing at a hash table, a symbol
+        // table and a string table
+        let image = |strings_size: u64, symbol_count: u32, entry_size: u64| {
+            let mut bytes = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+            bytes.resize(16, 0);
+            bytes.extend_from_slice(&elf::ET_DYN.to_le_bytes());
+            bytes.extend_from_slice(&62u16.to_le_bytes());
+            bytes.extend_from_slice(&1u32.to_le_bytes());
+            for word in [0u64, 64, 0] {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            bytes.extend_from_slice(&0u32.to_le_bytes());
+            for half in [64u16, 56, 2, 64, 0, 0] {
+                bytes.extend_from_slice(&half.to_le_bytes());
+            }
+            for (kind, offset, size) in [
+                (elf::PT_LOAD, 0u64, 0x1000u64),
+                (elf::PT_DYNAMIC, 0x200, 0x60),
+            ] {
+                bytes.extend_from_slice(&kind.to_le_bytes());
+                bytes.extend_from_slice(&elf::PF_R.to_le_bytes());
+                for word in [offset, offset, offset, size, size, 8] {
+                    bytes.extend_from_slice(&word.to_le_bytes());
+                }
+            }
+            bytes.resize(0x1000, 0);
+            let entries = [
+                (elf::DT_HASH, 0x280),
+                (elf::DT_SYMTAB, 0x300),
+                (elf::DT_STRTAB, 0x400),
+                (elf::DT_STRSZ, strings_size),
+                (elf::DT_SYMENT, entry_size),
+                (elf::DT_NULL, 0),
+            ];
+            for (i, (tag, value)) in entries.into_iter().enumerate() {
+                bytes[0x200 + i * 16..0x208 + i * 16].copy_from_slice(&tag.to_le_bytes());
+                bytes[0x208 + i * 16..0x210 + i * 16].copy_from_slice(&value.to_le_bytes());
+            }
+            bytes[0x284..0x288].copy_from_slice(&symbol_count.to_le_bytes());
+            bytes
+        };
+        let memory = ProcessVm::new(std::process::id());
+        let symbols = |bytes: &[u8]| SymbolTable::from_memory(&memory, bytes.as_ptr() as u64);
+
+        assert!(symbols(&image(16, 1, 24)).unwrap().is_empty());
+        // Sizes past the loaded segment, or overflowing, fail before anything is allocated
+        assert!(symbols(&image(u64::MAX, 1, 24)).is_err());
+        assert!(symbols(&image(0x1000, 1, 24)).is_err());
+        assert!(symbols(&image(16, u32::MAX, 24)).is_err());
+        assert!(symbols(&image(16, 2, u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        assert_eq!(
+            symbol::demangle("_ZN3std2io5stdio6_print17h0123456789abcdefE").as_deref(),
+            Some("std::io::stdio::_print")
+        );
+        assert_eq!(
+            symbol::demangle("_ZN9wikipedia7article6formatEv").as_deref(),
+            Some("wikipedia::article::format()")
+        );
+        assert_eq!(symbol::demangle("malloc"), None);
+
+        let pid = std::process::id();
+        let memory = Process::from_pid(pid).unwrap().memory();
+        let modules = Module::group(&Segment::get_from_pid(pid).unwrap());
+        let target = remote_sum as *const () as u64;
+        let exe = modules
+            .iter()
+            .find(|module| module.contains(target))
+            .unwrap();
+        let table = SymbolTable::load(exe, pid, &memory).unwrap();
+        let (symbol, offset) = table.symbol_at(target + 1).unwrap();
+        assert_eq!(symbol.address(), target);
+        assert_eq!(offset, 1);
+        assert_eq!(symbol.kind(), SymbolKind::Function);
+        assert!(symbol.demangled().ends_with("tests::remote_sum"));
+        assert_eq!(table.get(symbol.name()), Some(symbol));
+        assert_eq!(table.get(symbol.demangled()), Some(symbol));
+
+        // Only the dynamic symbols of the C library are in memory
+        let malloc = libc::malloc as *const () as u64;
+        let libc_module = modules
+            .iter()
+            .find(|module| module.contains(malloc))
+            .unwrap();
+        let dynamic = SymbolTable::from_memory(&memory, libc_module.base()).unwrap();
+        assert_eq!(dynamic.get("malloc").unwrap().address(), malloc);
+        assert_eq!(dynamic.symbol_at(malloc).unwrap().0.name(), "malloc");
+        let loaded = SymbolTable::load(libc_module, pid, &memory).unwrap();
+        assert_eq!(loaded.get("malloc").unwrap().address(), malloc);
+        assert!(loaded.len() >= dynamic.len());
+
+        // A stripped copy of the test binary, with its symbols in a separate debug file
+        let directory =
+            std::env::temp_dir().join(format!("libinspector-symbols-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (stripped, debug) = (directory.join("test"), directory.join("test.debug"));
+        let objcopy = |args: &[&std::ffi::OsStr]| {
+            let status = std::process::Command::new("objcopy")
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        let exe_path = std::fs::canonicalize("/proc/self/exe").unwrap();
+        objcopy(&[
+            "--only-keep-debug".as_ref(),
+            exe_path.as_ref(),
+            debug.as_ref(),
+        ]);
+        let debuglink = format!("--add-gnu-debuglink={}", debug.display());
+        objcopy(&[
+            "--strip-all".as_ref(),
+            debuglink.as_ref(),
+            exe_path.as_ref(),
+            stripped.as_ref(),
+        ]);
+        let table = SymbolTable::open(&stripped).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(table.debug_file(), Some(debug.as_path()));
+        let bias = exe.read_elf(pid).unwrap().load_bias(exe.base());
+        let symbol = table.get(symbol.name()).unwrap();
+        assert_eq!(symbol.address(), target - bias);
+    }
+
+    #[test]
+    fn test_symbol_resolver() {
+        let process = Process::from_pid(std::process::id()).unwrap();
+        let mut resolver = process.symbol_resolver();
+        let target = remote_sum as *const () as u64;
+        let (module, symbol, offset) = resolver.resolve_address(target + 2).unwrap();
+        assert_eq!(module.path(), std::env::current_exe().unwrap());
+        assert!(symbol.demangled().ends_with("tests::remote_sum"));
+        assert_eq!(offset, 2);
+        assert!(resolver.resolve_address(0x1000).is_none());
+
+        let malloc = libc::malloc as *const () as u64;
+        let libc_module = resolver.module_at(malloc).unwrap().clone();
+        let name = format!("{}!malloc", libc_module.name());
+        assert_eq!(resolver.resolve_symbol(&name), Some(malloc));
+        assert_eq!(resolver.resolve_symbol("malloc"), Some(malloc));
+        assert_eq!(resolver.format_address(malloc), Some(name));
+        assert_eq!(resolver.resolve_symbol("libnone.so!malloc"), None);
+        assert_eq!(resolver.resolve_symbol("libinspector_no_such_symbol"), None);
+        let format = format!("{}!{}+0x2", module.name(), symbol.demangled());
+        assert_eq!(resolver.format_address(target + 2), Some(format));
+
+        // Tables are loaded once, and kept while their module is mapped
+        let table = resolver.symbol_table(&libc_module).unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &table,
+            &resolver.symbol_table(&libc_module).unwrap()
+        ));
+        resolver.refresh().unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &table,
+            &resolver.symbol_table(&libc_module).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_process_modules() {
+        let pid = std::process::id();
+        let process = Process::from_pid(pid).unwrap();
+        let modules = process.modules();
+        assert!(modules.iter().all(|module| module.headers().is_some()));
+
+        let main = process.main_module().unwrap();
+        assert_eq!(main.path(), std::env::current_exe().unwrap());
+        let elf = main.read_elf(pid).unwrap();
+        assert_eq!(main.preferred_base(), Some(elf.preferred_base()));
+        assert_eq!(main.slide(), Some(elf.load_bias(main.base())));
+        let entry = process.entry_point().unwrap();
+        assert_eq!(
+            entry,
+            elf.header().entry.wrapping_add(main.slide().unwrap())
+        );
+
+        // Shared libraries prefer to be loaded at 0
+        let malloc = libc::malloc as *const () as u64;
+        let libc_module = modules
+            .iter()
+            .find(|module| module.contains(malloc))
+            .unwrap();
+        assert_eq!(libc_module.preferred_base(), Some(0));
+        assert_eq!(libc_module.slide(), Some(libc_module.base()));
+        assert!(libc_module.headers().unwrap().loads().count() > 1);
+    }
+
+    #[test]
+    fn test_imports_exports() {
+        let pid = std::process::id();
+        let process = Process::from_pid(pid).unwrap();
+        let memory = ProcessVm::new(pid);
+        let malloc = libc::malloc as *const () as u64;
+        unsafe { libc::free(libc::malloc(16)) };
+
+        let main = process.main_module().unwrap();
+        let imports = main.imports(&memory).unwrap();
+        let import = imports
+            .iter()
+            .find(|import| import.name() == "malloc")
+            .unwrap();
+        assert_eq!(import.kind(), SymbolKind::Function);
+        assert!(main.contains(import.slot()));
+        assert_eq!(import.address(), malloc);
+        assert!(import.is_bound(&main));
+        assert!(imports
+            .windows(2)
+            .all(|pair| pair[0].slot() < pair[1].slot()));
+
+        let resolver = process.symbol_resolver();
+        let provider = resolver.provider(import).unwrap();
+        assert!(provider.contains(malloc));
+        assert!(provider.name().starts_with("libc"));
+
+        let exports = provider.exports(&memory).unwrap();
+        let export = exports
+            .iter()
+            .find(|symbol| symbol.name() == "malloc")
+            .unwrap();
+        assert_eq!(export.address(), malloc);
+        assert!(exports
+            .iter()
+            .all(|symbol| symbol.binding() != SymbolBinding::Local));
+    }
+
+    #[test]
+    fn test_link_map() {
+        let pid = std::process::id();
+        let process = Process::from_pid(pid).unwrap();
+        let modules = process.modules();
+        let r_debug = process.r_debug().unwrap();
+        assert!(r_debug.version() >= 1);
+        assert!(r_debug.is_consistent());
+        assert!(modules.iter().any(|module| module.contains(r_debug.brk())));
+
+        // The executable comes first, without a name
+        let entries = process.link_map().unwrap();
+        let main = process.main_module().unwrap();
+        assert_eq!(entries[0].address(), r_debug.map());
+        assert_eq!(entries[0].path(), std::path::Path::new(""));
+        assert_eq!(entries[0].bias(), main.slide().unwrap());
+        assert_eq!(entries[0].module(&modules), Some(&main));
+        assert!(entries
+            .windows(2)
+            .all(|pair| pair[0].next() == pair[1].address()
+                && pair[1].previous() == pair[0].address()));
+
+        let malloc = libc::malloc as *const () as u64;
+        let libc_entry = entries
+            .iter()
+            .find(|entry| {
+                entry
+                    .module(&modules)
+                    .is_some_and(|module| module.contains(malloc))
+            })
+            .unwrap();
+        let libc_module = libc_entry.module(&modules).unwrap();
+        assert_eq!(libc_entry.bias(), libc_module.slide().unwrap());
+        assert_eq!(
+            std::fs::canonicalize(libc_entry.path()).unwrap(),
+            std::fs::canonicalize(libc_module.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_link_map_deleted() {
+        // SAFETY: the child only sleeps
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+        // dlopen takes locks the child may hold before reaching pause
+        while Process::from_pid(pid as u32).unwrap().state() != ProcessState::InterruptibleSleep {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        // A copy of a library, deleted once loaded
+        let mut session = PtraceSession::attach(pid as u32).unwrap();
+        session.inject_library("libz.so.1").unwrap();
+        let process = Process::from_pid(pid as u32).unwrap();
+        let original = process
+            .modules()
+            .into_iter()
+            .find(|module| module.name().starts_with("libz.so"))
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("libinspector-linkmap-{}.so", pid));
+        std::fs::copy(original.path(), &path).unwrap();
+        session.inject_library(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let process = Process::from_pid(pid as u32).unwrap();
+        let modules = process.modules();
+        let entries = process.link_map().unwrap();
+        let entry = entries.iter().find(|entry| entry.path() == path).unwrap();
+        let module = entry.module(&modules).unwrap();
+        assert!(module.deleted());
+        assert_eq!(module.path(), path);
+        assert_eq!(entry.bias(), module.slide().unwrap());
+        assert!(entries.iter().any(|entry| entry
+            .module(&modules)
+            .is_some_and(|module| module.inode() == original.inode())));
+        drop(session);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_loader_watch() {
+        static GO: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        // SAFETY: the child only loads and unloads a library once told to
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            while GO.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+                unsafe { libc::usleep(1000) };
+            }
+            unsafe {
+                let handle = libc::dlopen(c"libz.so.1".as_ptr(), libc::RTLD_NOW);
+                libc::dlclose(handle);
+            }
+            loop {
+                unsafe { libc::pause() };
+            }
+        }
+
+        let session = PtraceSession::attach(pid as u32).unwrap();
+        let mut manager = BreakpointManager::new(session).unwrap();
+        let mut watch = LoaderWatch::new(&mut manager).unwrap();
+        assert!(manager.get(watch.breakpoint()).is_some());
+        let count = watch.objects().len();
+        assert_eq!(
+            watch.objects(),
+            Process::from_pid(pid as u32).unwrap().link_map().unwrap()
+        );
+        manager
+            .session()
+            .memory()
+            .write(&GO as *const _ as u64, &1u64.to_ne_bytes())
+            .unwrap();
+
+        let mut events = Vec::new();
+        while events.len() < 2 {
+            let BreakpointEvent::Hit(hit) = manager.run().unwrap() else {
+                panic!("The rendezvous breakpoint was not hit");
+            };
+            events.extend(watch.handle(&manager, &hit).unwrap());
+        }
+        let [LoaderEvent::ModuleLoaded(loaded), LoaderEvent::ModuleUnloaded(unloaded)] =
+            &events[..]
+        else {
+            panic!("Unexpected events {events:?}");
+        };
+        assert_eq!(loaded, unloaded);
+        assert!(loaded.path().to_string_lossy().contains("libz.so"));
+        assert_eq!(watch.objects().len(), count);
+
+        watch.remove(&mut manager).unwrap();
+        assert_eq!(manager.breakpoints().count(), 0);
+        drop(manager);
+
+        // SAFETY: kill and waitpid take no pointer
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn test_find_instruction() {
+        let memory = ProcessVm::new(std::process::id());
+        let syscall = [0x0f, 0x05];
+        // Spans several chunks and ends in the middle of a possible instruction
+        let mut code = vec![0x90u8; 0x20001];
+        let range = |code: &[u8]| {
+            let start = code.as_ptr() as u64;
+            start..start + code.len() as u64
+        };
+        assert_eq!(
+            find_instruction(&memory, range(&code), &syscall, 1).unwrap(),
+            None
+        );
+        assert_eq!(
+            find_instruction(&memory, range(&code[..1]), &syscall, 1).unwrap(),
+            None
+        );
+
+        // Found across the boundary of two chunks, and at the end
+        let start = code.as_ptr() as u64;
+        code[0xffff..0x10001].copy_from_slice(&syscall);
+        assert_eq!(
+            find_instruction(&memory, range(&code), &syscall, 1).unwrap(),
+            Some(start + 0xffff)
+        );
+        code[0xffff..0x10001].copy_from_slice(&[0x90, 0x90]);
+        let end = code.len();
+        code[end - 2..].copy_from_slice(&syscall);
+        assert_eq!(
+            find_instruction(&memory, range(&code), &syscall, 1).unwrap(),
+            Some(start + end as u64 - 2)
+        );
+        assert_eq!(
+            find_instruction(&memory, range(&code[..end - 1]), &syscall, 1).unwrap(),
+            None
+        );
+    }
 }